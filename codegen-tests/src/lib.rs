@@ -116,6 +116,23 @@ fn test_large_serde_serialization() {
     assert_json_roundtrip(&make_large(&mut arena));
 }
 
+#[test]
+fn test_generated_serde_impls() {
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    let msg = make_medium(&mut arena);
+    let json = serde_json::to_string(&msg).expect("should serialize");
+    assert_eq!(json, serde_json::to_string(&msg.as_dyn()).unwrap());
+
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    // SAFETY: `arena` outlives `roundtrip`
+    let roundtrip: TestProto = unsafe { protocrap::serde::with_arena(&mut arena, || serde_json::from_str(&json)) }
+        .expect("should deserialize");
+    assert_eq!(
+        roundtrip.encode_vec::<100>().unwrap(),
+        msg.encode_vec::<100>().unwrap()
+    );
+}
+
 #[test]
 fn test_file_descriptor_serde_serialization() {
     assert_json_roundtrip(
@@ -349,10 +366,18 @@ fn test_defaults() {
     // Verify enum defaults
     assert_eq!(msg.status(), Some(Status::ACTIVE)); // Has explicit default
     assert_eq!(msg.status_no_default(), Some(Status::UNKNOWN)); // Falls back to first value (0)
+    assert_eq!(msg.raw_status(), Status::ACTIVE.to_i32());
+    assert_eq!(msg.raw_status_no_default(), 0);
 
     // Test set/clear behavior
     let mut msg2 = DefaultsTest::ProtoType::default();
 
+    // Numbers the enum doesn't know are kept raw
+    msg2.set_raw_status(77);
+    assert_eq!(msg2.status(), None);
+    assert_eq!(msg2.raw_status(), 77);
+    msg2.clear_status();
+
     // Set a field to a value different from default
     msg2.set_port(9000);
     assert_eq!(msg2.port(), 9000);
@@ -379,27 +404,224 @@ fn test_embedded_const() {
     assert_eq!(nested[1].x(), 200);
 }
 
+#[test]
+fn test_oneof_which() {
+    use test_protos::protobuf_test_messages::proto3::TestAllTypesProto3;
+    use TestAllTypesProto3::oneof_field::Which;
+
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    let mut msg = TestAllTypesProto3::ProtoType::default();
+    assert!(matches!(msg.which_oneof_field(), Which::None));
+
+    msg.set_oneof_uint32(7);
+    assert!(matches!(msg.which_oneof_field(), Which::OneofUint32(7)));
+
+    msg.set_oneof_string("hello", &mut arena).unwrap();
+    assert!(matches!(msg.which_oneof_field(), Which::OneofString("hello")));
+
+    msg.oneof_nested_message_mut(&mut arena).set_a(3);
+    match msg.which_oneof_field() {
+        Which::OneofNestedMessage(nested) => assert_eq!(nested.a(), 3),
+        other => panic!("unexpected oneof case {:?}", other),
+    }
+
+    msg.clear_oneof_nested_message();
+    assert!(matches!(msg.which_oneof_field(), Which::None));
+}
+
+#[test]
+fn test_oneof_set_from_which() {
+    use protocrap::ProtobufRef;
+    use test_protos::protobuf_test_messages::proto3::TestAllTypesProto3;
+    use TestAllTypesProto3::oneof_field::Which;
+
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    let mut source = TestAllTypesProto3::ProtoType::default();
+    source.oneof_nested_message_mut(&mut arena).set_a(3);
+
+    // Messages are copied, not shared
+    let mut msg = TestAllTypesProto3::ProtoType::default();
+    msg.set_oneof_field(source.which_oneof_field(), &mut arena).unwrap();
+    source.oneof_nested_message_mut(&mut arena).set_a(4);
+    match msg.which_oneof_field() {
+        Which::OneofNestedMessage(nested) => assert_eq!(nested.a(), 3),
+        other => panic!("unexpected oneof case {:?}", other),
+    }
+
+    msg.set_oneof_field(Which::OneofString("hello"), &mut arena).unwrap();
+    assert!(matches!(msg.which_oneof_field(), Which::OneofString("hello")));
+
+    // Switching cases starts from a clean slate
+    assert_eq!(msg.oneof_nested_message_mut(&mut arena).a(), 0);
+    msg.set_oneof_field(Which::OneofBytes(b"raw"), &mut arena).unwrap();
+    msg.set_oneof_uint32(7);
+    let mut expected = TestAllTypesProto3::ProtoType::default();
+    expected.set_oneof_uint32(7);
+    assert_eq!(msg.encode_vec::<32>().unwrap(), expected.encode_vec::<32>().unwrap());
+
+    msg.set_oneof_field(Which::None, &mut arena).unwrap();
+    assert!(matches!(msg.which_oneof_field(), Which::None));
+    assert!(msg.encode_vec::<32>().unwrap().is_empty());
+}
+
+#[test]
+fn test_clone_in() {
+    use protocrap::ProtobufMut;
+    use test_protos::protobuf_test_messages::proto3::TestAllTypesProto3;
+
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    let copy = {
+        let mut source_arena = protocrap::arena::Arena::new(&Global);
+        let msg = make_medium(&mut source_arena);
+        msg.clone_in(&mut arena).unwrap()
+    };
+    // The copy outlives the arena of the original
+    assert_eq!(copy.x(), 42);
+    assert_eq!(copy.z(), "Hello World! This is a test string with some content.");
+    assert_eq!(copy.child1().unwrap().y(), 456);
+
+    // Oneofs, maps and packed fields copy as they encode
+    let mut msg = TestAllTypesProto3::ProtoType::default();
+    // map_int32_int32 {1: 10}, repeated_int32 [1, 2] packed
+    let data: &[u8] = &[0xc2, 0x03, 0x04, 0x08, 0x01, 0x10, 0x0a, 0xfa, 0x01, 0x02, 0x01, 0x02];
+    assert!(msg.decode_flat::<32>(&mut arena, data));
+    msg.set_oneof_string("hello", &mut arena).unwrap();
+    let copy = msg.clone_in(&mut arena).unwrap();
+    assert_eq!(copy.encode_vec::<32>().unwrap(), msg.encode_vec::<32>().unwrap());
+}
+
+#[test]
+fn test_group_encoding() {
+    use protocrap::ProtobufMut;
+
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    let mut msg = TestProto::default();
+    msg.set_z("a", &mut arena).unwrap();
+    msg.child2_mut(&mut arena).set_x(1);
+    // z, then group Child2 between start (5, wire type 3) and end (wire type 4) tags
+    let expected: &[u8] = &[0x1a, 0x01, b'a', 0x2b, 0x08, 0x02, 0x2c];
+    assert_eq!(msg.encode_vec::<32>().unwrap(), expected);
+
+    let mut decoded = TestProto::default();
+    assert!(decoded.decode_flat::<32>(&mut arena, expected));
+    assert_eq!(decoded.z(), "a");
+    assert_eq!(decoded.child2().map(|g| g.x()), Some(1));
+}
+
+#[test]
+fn test_normalize_maps_last_wins() {
+    use protocrap::ProtobufMut;
+    use test_protos::protobuf_test_messages::proto3::TestAllTypesProto3;
+
+    // map_int32_int32 (field 56) with entries {1: 10}, {2: 20}, {1: 30}
+    let data: &[u8] = &[
+        0xc2, 0x03, 0x04, 0x08, 0x01, 0x10, 0x0a, //
+        0xc2, 0x03, 0x04, 0x08, 0x02, 0x10, 0x14, //
+        0xc2, 0x03, 0x04, 0x08, 0x01, 0x10, 0x1e,
+    ];
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    let mut msg = TestAllTypesProto3::ProtoType::default();
+    assert!(msg.decode_flat::<32>(&mut arena, data));
+    assert_eq!(msg.map_int32_int32().len(), 3);
+
+    msg.normalize_maps();
+    let entries: Vec<_> = msg
+        .map_int32_int32()
+        .iter()
+        .map(|e| (e.key(), e.value()))
+        .collect();
+    assert_eq!(entries, vec![(2, 20), (1, 30)]);
+}
+
+#[test]
+fn test_map_index() {
+    use protocrap::ProtobufMut;
+    use test_protos::protobuf_test_messages::proto3::TestAllTypesProto3;
+
+    // map_int32_int32 (field 56) with entries {1: 10}, {2: 20}, {1: 30}, then
+    // map_string_nested_message (field 71) with entry {"k": {a: 5}}
+    let data: &[u8] = &[
+        0xc2, 0x03, 0x04, 0x08, 0x01, 0x10, 0x0a, //
+        0xc2, 0x03, 0x04, 0x08, 0x02, 0x10, 0x14, //
+        0xc2, 0x03, 0x04, 0x08, 0x01, 0x10, 0x1e, //
+        0xba, 0x04, 0x07, 0x0a, 0x01, b'k', 0x12, 0x02, 0x08, 0x05,
+    ];
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    let mut msg = TestAllTypesProto3::ProtoType::default();
+    assert!(msg.decode_flat::<32>(&mut arena, data));
+
+    let map = msg.map_int32_int32_map(&mut arena).unwrap();
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.get(&1), Some(&30));
+    assert_eq!(map.get(&2), Some(&20));
+    assert_eq!(map.get(&3), None);
+
+    let nested = msg.map_string_nested_message_map(&mut arena).unwrap();
+    assert_eq!(nested.get(&"k").unwrap().unwrap().a(), 5);
+}
+
+#[test]
+fn test_map_iter_ordered() {
+    use protocrap::{DecodeOptions, ProtobufMut};
+    use test_protos::protobuf_test_messages::proto3::TestAllTypesProto3;
+
+    // map_int32_int32 (field 56) with entries {2: 20}, {1: 10}, {2: 30}
+    let data: &[u8] = &[
+        0xc2, 0x03, 0x04, 0x08, 0x02, 0x10, 0x14, //
+        0xc2, 0x03, 0x04, 0x08, 0x01, 0x10, 0x0a, //
+        0xc2, 0x03, 0x04, 0x08, 0x02, 0x10, 0x1e,
+    ];
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    let mut msg = TestAllTypesProto3::ProtoType::default();
+    assert!(msg.decode_flat::<32>(&mut arena, data));
+    let entries: Vec<_> = msg.map_int32_int32_iter_ordered().collect();
+    assert_eq!(entries, vec![(2, 20), (1, 10), (2, 30)]);
+
+    let options = DecodeOptions {
+        dedup_map_entries: true,
+        ..Default::default()
+    };
+    let mut msg = TestAllTypesProto3::ProtoType::default();
+    assert!(msg.decode_flat_with_options::<32>(&mut arena, data, options));
+    let entries: Vec<_> = msg.map_int32_int32_iter_ordered().collect();
+    assert_eq!(entries, vec![(1, 10), (2, 30)]);
+}
+
+#[test]
+fn test_canonicalize_sorts_maps() {
+    use protocrap::generated_code_only::Protobuf;
+    use test_protos::protobuf_test_messages::proto3::TestAllTypesProto3;
+
+    // map_int32_int32 (field 56) with entries {2: 20}, {1: 10}, {2: 30}
+    let data: &[u8] = &[
+        0xc2, 0x03, 0x04, 0x08, 0x02, 0x10, 0x14, //
+        0xc2, 0x03, 0x04, 0x08, 0x01, 0x10, 0x0a, //
+        0xc2, 0x03, 0x04, 0x08, 0x02, 0x10, 0x1e,
+    ];
+    let canonical =
+        protocrap::canonical::canonicalize(data, TestAllTypesProto3::ProtoType::table()).unwrap();
+    assert_eq!(
+        canonical,
+        [
+            0xc2, 0x03, 0x04, 0x08, 0x01, 0x10, 0x0a, //
+            0xc2, 0x03, 0x04, 0x08, 0x02, 0x10, 0x1e,
+        ]
+    );
+}
+
 #[cfg(test)]
 mod table_tests {
     use protocrap::test_utils::compare_tables_rec;
     use protocrap::descriptor_pool::DescriptorPool;
-    use protocrap::ProtobufMut;
     use std::collections::HashSet;
 
     use super::Global;
 
     #[test]
     fn test_static_vs_dynamic_tables() {
-        let mut pool = DescriptorPool::new(&Global);
-
-        // Load descriptor set
-        let mut fds = protocrap::google::protobuf::FileDescriptorSet::ProtoType::default();
-        assert!(fds.decode_flat::<32>(&mut pool.arena, test_protos::DESCRIPTOR_BYTES));
-        let fds = pool.arena.place(fds).unwrap();
-
-        for file in fds.file() {
-            pool.add_file(file.as_ref()).unwrap();
-        }
+        let pool =
+            DescriptorPool::from_file_descriptor_set_bytes_in(&Global, test_protos::DESCRIPTOR_BYTES)
+                .unwrap();
 
         // Test all message types
         let mut seen = HashSet::new();
@@ -417,3 +639,32 @@ mod table_tests {
         compare_tables_rec(static_table, dynamic_table, &mut seen);
     }
 }
+
+#[test]
+fn test_reserved_names() {
+    use test_protos::torture::r#type::{Self_, protocrap_::Option_};
+
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    let mut msg = Self_::ProtoType::default();
+    assert_eq!(msg.r#async(), Some(Option_::Self_));
+    msg.set_type(1);
+    // Accessors that would shadow generated methods get a `_` suffix
+    msg.set_set_type_(2);
+    msg.set_clear_(3);
+    msg.set_which_kind_(4);
+    msg.set_metadata(5);
+    msg.set_self_("s", &mut arena).unwrap();
+    msg.set_none(6);
+    assert!(matches!(msg.which_kind(), Self_::kind::Which::None_(6)));
+    msg.set_crate_("c", &mut arena).unwrap();
+    assert!(matches!(msg.which_kind(), Self_::kind::Which::Crate("c")));
+
+    assert_eq!(msg.r#type(), 1);
+    assert_eq!(msg.set_type_(), 2);
+    assert_eq!(msg.clear_(), 3);
+    assert_eq!(msg.which_kind_(), 4);
+    assert_eq!(msg.metadata(), 5);
+    assert_eq!(msg.self_(), "s");
+    msg.clear();
+    assert!(!msg.has_type());
+}
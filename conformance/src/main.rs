@@ -1,16 +1,22 @@
 use anyhow::{Context, Result, bail};
-use protocrap::proto_json::{ProtoJsonDeserializer, ProtoJsonSerializer};
+use protocrap::proto_json::{ProtoJsonDeserializer, ProtoJsonFormatter, ProtoJsonSerializer};
+use protocrap::serde::{JsonParseOptions, with_type_resolver};
 use protocrap::descriptor_pool::DescriptorPool;
 use protocrap::{ProtobufMut, ProtobufRef};
 use protocrap_conformance::{GLOBAL_ALLOC, load_descriptor_pool};
 use serde::Serialize;
-use test_protos::conformance::{ConformanceRequest, ConformanceResponse, WireFormat};
+use test_protos::conformance::{ConformanceRequest, ConformanceResponse, TestCategory, WireFormat};
 use test_protos::protobuf_test_messages::proto2::TestAllTypesProto2;
 use test_protos::protobuf_test_messages::proto3::TestAllTypesProto3;
-use std::io::{self, Read, Write};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
 
 const TEST_JSON: bool = true;
 
+/// File `--verbose` logs every test to, in the directory bazel keeps test
+/// outputs in if set.
+const LOG_FILE: &str = "conformance_tests.log";
+
 fn roundtrip_proto<'pool, T: protocrap::ProtobufMut<'pool>>(
     msg: &mut T,
     arena: &mut protocrap::arena::Arena,
@@ -35,8 +41,16 @@ fn roundtrip_proto<'pool, T: protocrap::ProtobufMut<'pool>>(
             response.set_skipped("Json format input not supported", arena).unwrap();
             return response;
         }
+        let ignore_unknown =
+            request.test_category() == Some(TestCategory::JSON_IGNORE_UNKNOWN_PARSING_TEST);
+        let options = JsonParseOptions {
+            ignore_unknown_fields: ignore_unknown,
+            ignore_unknown_enum_values: ignore_unknown,
+            ..Default::default()
+        };
         let mut inner = serde_json::Deserializer::from_str(data);
-        if let Err(e) = msg.serde_deserialize(arena, ProtoJsonDeserializer::new(&mut inner))
+        if let Err(e) =
+            msg.serde_deserialize_with_options(arena, ProtoJsonDeserializer::new(&mut inner), options)
         {
             response.set_parse_error(&format!("Failed to parse JSON message: {:?}", e), arena).unwrap();
             return response;
@@ -66,7 +80,7 @@ fn roundtrip_proto<'pool, T: protocrap::ProtobufMut<'pool>>(
                 response.set_skipped("Json format output not supported", arena).unwrap();
                 return response;
             }
-            let mut inner = serde_json::Serializer::new(Vec::new());
+            let mut inner = serde_json::Serializer::with_formatter(Vec::new(), ProtoJsonFormatter);
             let dynamic_msg = msg.as_dyn();
             match dynamic_msg.serialize(ProtoJsonSerializer::new(&mut inner)) {
                 Ok(()) => {
@@ -92,7 +106,7 @@ fn roundtrip_proto<'pool, T: protocrap::ProtobufMut<'pool>>(
     response
 }
 
-fn do_test_dynamic(
+fn do_test(
     pool: &DescriptorPool<'static>,
     request: &ConformanceRequest::ProtoType,
     arena: &mut protocrap::arena::Arena,
@@ -117,29 +131,89 @@ fn do_test_dynamic(
     roundtrip_proto(&mut msg, arena, request)
 }
 
-fn do_test(
-    request: &ConformanceRequest::ProtoType,
-    arena: &mut protocrap::arena::Arena,
-) -> ConformanceResponse::ProtoType {
-    let mut response = ConformanceResponse::ProtoType::default();
+/// Write `bytes` as lines of 16 hex bytes, prefixed by their offset.
+fn hexdump(out: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    for (i, line) in bytes.chunks(16).enumerate() {
+        write!(out, "  {:06x}:", i * 16)?;
+        for byte in line {
+            write!(out, " {:02x}", byte)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
 
-    let message_type = request.message_type();
-    let is_proto3 = message_type.contains("Proto3") || message_type.is_empty();
+/// Write the binary `payload` of a `message_type` message as a hexdump and
+/// in text format, unknown fields printed by number.
+fn dump_payload(
+    out: &mut impl Write,
+    pool: &DescriptorPool<'static>,
+    message_type: &str,
+    payload: &[u8],
+) -> io::Result<()> {
+    writeln!(out, "{} bytes:", payload.len())?;
+    hexdump(out, payload)?;
+    let Some(table) = pool.get_table(message_type) else {
+        return Ok(());
+    };
+    match protocrap::text_format::Printer::new().expand_any(pool).print(table, payload) {
+        Ok(text) => {
+            writeln!(out, "wire format:")?;
+            for line in text.lines() {
+                writeln!(out, "  {}", line)?;
+            }
+        }
+        Err(e) => writeln!(out, "wire format: {}", e)?,
+    }
+    Ok(())
+}
 
-    if is_proto3 {
-        let mut msg = TestAllTypesProto3::ProtoType::default();
-        roundtrip_proto::<TestAllTypesProto3::ProtoType>(&mut msg, arena, request)
+/// Log a test's request and our response, so failures the test runner
+/// reports can be triaged without running it again.
+///
+/// The runner alone knows the test names and the expected output; it
+/// reports failing tests with their request, which matches the input
+/// logged here.
+fn log_test(
+    out: &mut impl Write,
+    count: usize,
+    pool: &DescriptorPool<'static>,
+    request: &ConformanceRequest::ProtoType,
+    response: &ConformanceResponse::ProtoType,
+) -> io::Result<()> {
+    let message_type = request.message_type();
+    writeln!(
+        out,
+        "=== Test #{}: {}, {:?}, output {:?} ===",
+        count,
+        message_type,
+        request.test_category(),
+        request.requested_output_format()
+    )?;
+    write!(out, "input ")?;
+    if let Some(data) = request.get_protobuf_payload() {
+        dump_payload(out, pool, message_type, data)?;
+    } else if let Some(data) = request.get_json_payload() {
+        writeln!(out, "JSON: {}", data)?;
     } else {
-        if message_type != "protobuf_test_messages.proto2.TestAllTypesProto2" {
-            response.set_skipped(
-                &format!("Message type {} not supported", message_type),
-                arena,
-            ).unwrap();
-            return response;
-        }
-        let mut msg = TestAllTypesProto2::ProtoType::default();
-        roundtrip_proto::<TestAllTypesProto2::ProtoType>(&mut msg, arena, request)
+        writeln!(out, "in an unsupported format")?;
     }
+    let errors = [
+        ("parse error", response.get_parse_error()),
+        ("serialize error", response.get_serialize_error()),
+        ("runtime error", response.get_runtime_error()),
+    ];
+    if let Some((kind, Some(message))) = errors.into_iter().find(|(_, message)| message.is_some()) {
+        writeln!(out, "FAILED, {}: {}", kind, message)?;
+    } else if let Some(reason) = response.get_skipped() {
+        writeln!(out, "skipped: {}", reason)?;
+    } else if let Some(data) = response.get_protobuf_payload() {
+        write!(out, "output ")?;
+        dump_payload(out, pool, message_type, data)?;
+    } else if let Some(data) = response.get_json_payload() {
+        writeln!(out, "output JSON: {}", data)?;
+    }
+    writeln!(out)
 }
 
 fn main() -> Result<()> {
@@ -147,14 +221,25 @@ fn main() -> Result<()> {
 
     let args: Vec<String> = std::env::args().collect();
     let use_dynamic = args.contains(&"--dynamic".to_string());
+    let mut log = if args.contains(&"--verbose".to_string()) {
+        let dir = std::env::var("TEST_UNDECLARED_OUTPUTS_DIR").unwrap_or_else(|_| ".".into());
+        let path = std::path::Path::new(&dir).join(LOG_FILE);
+        eprintln!("Logging tests to {}", path.display());
+        Some(BufWriter::new(File::create(&path).context("Failed to create test log")?))
+    } else {
+        None
+    };
 
-    let pool = if use_dynamic {
+    // Both modes look message types up in the pool, static mode has it
+    // serve the generated tables of the test messages
+    let mut pool = load_descriptor_pool()?;
+    if use_dynamic {
         eprintln!("Protocrap conformance test runner starting (DYNAMIC MODE)...");
-        Some(load_descriptor_pool()?)
     } else {
         eprintln!("Protocrap conformance test runner starting...");
-        None
-    };
+        pool.register_static::<TestAllTypesProto2::ProtoType>()?;
+        pool.register_static::<TestAllTypesProto3::ProtoType>()?;
+    }
 
     let stdin = io::stdin();
     let stdout = io::stdout();
@@ -189,12 +274,13 @@ fn main() -> Result<()> {
         }
         eprintln!("Request is {:?}", &request);
 
-        // Process test
-        let response = if let Some(ref pool) = pool {
-            do_test_dynamic(pool, &request, &mut arena)
-        } else {
-            do_test(&request, &mut arena)
-        };
+        // Process test, resolving the types packed in Any from the test
+        // messages and their imports
+        let response = with_type_resolver(&pool, || do_test(&pool, &request, &mut arena));
+        if let Some(log) = &mut log {
+            log_test(log, count, &pool, &request, &response).context("Failed to write test log")?;
+            log.flush()?;
+        }
 
         // Serialize ConformanceResponse
         let response_bytes = response
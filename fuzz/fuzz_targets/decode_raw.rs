@@ -2,12 +2,17 @@
 
 use allocator_api2::alloc::Global;
 use libfuzzer_sys::fuzz_target;
-use protocrap::ProtobufMut;
+use protocrap::{ProtobufMut, ProtobufRef};
 
 fuzz_target!(|data: &[u8]| {
     // Try to decode arbitrary bytes as a FileDescriptorProto
     // This tests that malformed input doesn't cause crashes
     let mut arena = protocrap::arena::Arena::new(&Global);
     let mut msg = protocrap::google::protobuf::FileDescriptorProto::ProtoType::default();
-    let _ = msg.decode_flat::<32>(&mut arena, data);
+    if msg.decode_flat::<32>(&mut arena, data) {
+        // The size computed up front must match what the encoder writes
+        if let Ok(encoded) = msg.encode_vec::<32>() {
+            assert_eq!(msg.encoded_len::<32>().ok(), Some(encoded.len()));
+        }
+    }
 });
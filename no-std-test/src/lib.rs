@@ -4,6 +4,7 @@
 //! and works without the standard library, including encode/decode.
 //!
 //! Build with: cargo build -p no-std-test --target thumbv7m-none-eabi
+//! The `alloc` feature adds the conveniences that need a global allocator.
 
 #![no_std]
 
@@ -55,24 +56,55 @@ pub fn test_roundtrip(alloc: &dyn Allocator) -> bool {
     original.set_name("roundtrip.proto", &mut arena);
     original.set_package("my.package", &mut arena);
 
-    // Encode
+    // Encode to the start of the buffer
     let mut buffer = [0u8; 256];
-    let encoded = match original.encode_flat::<16>(&mut buffer) {
-        Ok(e) => e,
+    let len = match original.encode_flat_to_start::<16>(&mut buffer) {
+        Ok(e) => e.len(),
         Err(_) => return false,
     };
 
-    // Copy encoded data (encode_flat returns slice at end of buffer)
-    let mut encoded_copy = [0u8; 256];
-    let len = encoded.len();
-    encoded_copy[..len].copy_from_slice(encoded);
-
     // Decode into new message
     let mut decoded = FileDescriptorProto::ProtoType::default();
-    if !decoded.decode_flat::<16>(&mut arena, &encoded_copy[..len]) {
+    if !decoded.decode_flat::<16>(&mut arena, &buffer[..len]) {
         return false;
     }
 
     // Verify
     decoded.name() == "roundtrip.proto" && decoded.package() == "my.package"
 }
+
+/// Test `Vec`-based encoding works with `alloc` but without `std`
+#[cfg(feature = "alloc")]
+pub fn test_encode_vec(alloc: &dyn Allocator) -> bool {
+    let mut arena = Arena::new(alloc);
+
+    let mut msg = FileDescriptorProto::ProtoType::default();
+    if msg.set_name("vec.proto", &mut arena).is_err() {
+        return false;
+    }
+
+    match msg.encode_vec::<16>() {
+        Ok(encoded) => encoded[..] == *b"\x0a\x09vec.proto",
+        Err(_) => false,
+    }
+}
+
+/// Test dynamic decoding through a descriptor pool works with `alloc` but without `std`
+#[cfg(feature = "alloc")]
+pub fn test_descriptor_pool(alloc: &dyn Allocator) -> bool {
+    let mut pool = protocrap::descriptor_pool::DescriptorPool::new(alloc);
+    if pool
+        .add_file(FileDescriptorProto::ProtoType::file_descriptor())
+        .is_err()
+    {
+        return false;
+    }
+
+    let mut arena = Arena::new(alloc);
+    let Ok(mut msg) = pool.create_message("google.protobuf.FileDescriptorProto", &mut arena)
+    else {
+        return false;
+    };
+    let data: &[u8] = &[0x0a, 0x04, b'p', b'o', b'o', b'l'];
+    msg.decode_flat::<16>(&mut arena, data) && msg.as_ref().get_str("name") == Some("pool")
+}
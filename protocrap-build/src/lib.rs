@@ -0,0 +1,221 @@
+//! Compile `.proto` files from a Cargo build script.
+//!
+//! Runs `protoc` to produce a descriptor set, feeds it to `protocrap-codegen`
+//! and writes the generated Rust code to `OUT_DIR`.
+//!
+//! ```no_run
+//! // build.rs
+//! fn main() -> anyhow::Result<()> {
+//!     protocrap_build::Builder::new()
+//!         .include("protos")
+//!         .proto("protos/my_types.proto")
+//!         .compile()
+//! }
+//! ```
+//!
+//! ```ignore
+//! // src/lib.rs
+//! use protocrap;
+//! include!(concat!(env!("OUT_DIR"), "/protos.pc.rs"));
+//! ```
+//!
+//! `protoc` is taken from the `PROTOC` environment variable if set, otherwise
+//! from `PATH`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use protocrap_codegen::Options;
+
+/// Compile `protos` with the given include paths into `OUT_DIR/protos.pc.rs`.
+pub fn compile_protos(protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>]) -> Result<()> {
+    let mut builder = Builder::new();
+    for proto in protos {
+        builder = builder.proto(proto);
+    }
+    for include in includes {
+        builder = builder.include(include);
+    }
+    builder.compile()
+}
+
+/// Configures and runs proto compilation.
+#[derive(Default, Clone)]
+pub struct Builder {
+    protos: Vec<PathBuf>,
+    includes: Vec<PathBuf>,
+    protoc: Option<PathBuf>,
+    out_dir: Option<PathBuf>,
+    file_name: Option<String>,
+    ffi: bool,
+    options: Options,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a `.proto` file to compile.
+    pub fn proto(mut self, path: impl AsRef<Path>) -> Self {
+        self.protos.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Add an import search path (`protoc -I`).
+    pub fn include(mut self, path: impl AsRef<Path>) -> Self {
+        self.includes.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Use this `protoc` binary instead of `PROTOC` or the one on `PATH`.
+    pub fn protoc(mut self, path: impl AsRef<Path>) -> Self {
+        self.protoc = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Write output here instead of `OUT_DIR`.
+    pub fn out_dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.out_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Name of the generated file, `protos.pc.rs` by default.
+    pub fn file_name(mut self, name: &str) -> Self {
+        self.file_name = Some(name.to_string());
+        self
+    }
+
+    /// Map a proto package onto an existing Rust module instead of generating it.
+    ///
+    /// See [`Options::extern_path`].
+    pub fn extern_path(mut self, proto_package: &str, rust_path: &str) -> Result<Self> {
+        self.options = self.options.extern_path(proto_package, rust_path)?;
+        Ok(self)
+    }
+
+    /// Also generate RPC traits and client stubs for services.
+    ///
+    /// See [`Options::services`].
+    pub fn services(mut self, enabled: bool) -> Self {
+        self.options = self.options.services(enabled);
+        self
+    }
+
+    /// Export C table accessors for every message and write a matching C header
+    /// next to the generated file, e.g. `protos.h` for `protos.pc.rs`.
+    ///
+    /// See [`Options::ffi`].
+    pub fn ffi(mut self, enabled: bool) -> Self {
+        self.options = self.options.ffi(enabled);
+        self.ffi = enabled;
+        self
+    }
+
+    /// Implement `serde::Serialize` and `serde::Deserialize` on every message.
+    ///
+    /// See [`Options::serde`].
+    pub fn serde(mut self, enabled: bool) -> Self {
+        self.options = self.options.serde(enabled);
+        self
+    }
+
+    /// Generate a `<field>_map()` method building a hash map of every map field.
+    ///
+    /// See [`Options::maps`].
+    pub fn maps(mut self, enabled: bool) -> Self {
+        self.options = self.options.maps(enabled);
+        self
+    }
+
+    /// Leave descriptors out of the generated code, disabling reflection.
+    ///
+    /// See [`Options::strip_descriptors`].
+    pub fn strip_descriptors(mut self, enabled: bool) -> Self {
+        self.options = self.options.strip_descriptors(enabled);
+        self
+    }
+
+    /// Run `protoc` and the code generator, returning the path of the generated file.
+    pub fn compile_to_path(&self) -> Result<PathBuf> {
+        let out_dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => PathBuf::from(
+                std::env::var_os("OUT_DIR").context("OUT_DIR is not set; call from build.rs")?,
+            ),
+        };
+        let file_name = self.file_name.as_deref().unwrap_or("protos.pc.rs");
+        let descriptor_path = out_dir.join(format!("{file_name}.descriptor.bin"));
+
+        let protoc = self
+            .protoc
+            .clone()
+            .or_else(|| std::env::var_os("PROTOC").map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("protoc"));
+
+        let mut cmd = Command::new(&protoc);
+        cmd.arg("--include_imports")
+            .arg("--include_source_info")
+            .arg(format!(
+                "--descriptor_set_out={}",
+                descriptor_path.display()
+            ));
+        for include in &self.includes {
+            cmd.arg(format!("-I{}", include.display()));
+        }
+        cmd.args(&self.protos);
+
+        let output = cmd
+            .output()
+            .with_context(|| format!("failed to run protoc ({})", protoc.display()))?;
+        if !output.status.success() {
+            bail!(
+                "protoc failed with {}:\n{}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let descriptor = std::fs::read(&descriptor_path)
+            .with_context(|| format!("failed to read {}", descriptor_path.display()))?;
+        let code = protocrap_codegen::generate_with_options(&descriptor, &self.options)?;
+        let out_path = out_dir.join(file_name);
+        std::fs::write(&out_path, code)
+            .with_context(|| format!("failed to write {}", out_path.display()))?;
+        if self.ffi {
+            let header = protocrap_codegen::generate_c_header(&descriptor, &self.options)?;
+            let stem = file_name.split('.').next().unwrap_or(file_name);
+            let header_path = out_dir.join(format!("{stem}.h"));
+            std::fs::write(&header_path, header)
+                .with_context(|| format!("failed to write {}", header_path.display()))?;
+        }
+
+        println!("cargo:rerun-if-env-changed=PROTOC");
+        for proto in &self.protos {
+            println!("cargo:rerun-if-changed={}", proto.display());
+        }
+        Ok(out_path)
+    }
+
+    /// Run `protoc` and the code generator.
+    pub fn compile(&self) -> Result<()> {
+        self.compile_to_path().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_protoc_is_reported() {
+        let err = Builder::new()
+            .protoc("/nonexistent/protoc")
+            .out_dir(std::env::temp_dir())
+            .proto("a.proto")
+            .compile()
+            .unwrap_err();
+        assert!(err.to_string().contains("failed to run protoc"));
+    }
+}
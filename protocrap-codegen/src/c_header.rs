@@ -0,0 +1,102 @@
+// protocrap-codegen/src/c_header.rs
+
+use super::Options;
+use super::names::ffi_table_symbol;
+
+use protocrap::google::protobuf::DescriptorProto::ProtoType as DescriptorProto;
+use protocrap::google::protobuf::FileDescriptorProto::ProtoType as FileDescriptorProto;
+
+/// Declarations of the `protocrap::ffi` entry points. Repeated in every header;
+/// C and C++ accept identical redeclarations.
+const RUNTIME_DECLARATIONS: &str = "\
+/* Opaque runtime types */
+typedef struct pc_table pc_table;
+typedef struct pc_arena pc_arena;
+typedef struct pc_message pc_message;
+
+/* pc_encode status codes; on PC_BUFFER_TOO_SMALL *len is the size needed */
+#define PC_OK 0
+#define PC_BUFFER_TOO_SMALL 1
+#define PC_MESSAGE_TREE_TOO_DEEP 2
+
+pc_arena *pc_arena_new(void);
+void pc_arena_free(pc_arena *arena);
+pc_message *pc_decode(const pc_table *table, const uint8_t *buf, size_t len, pc_arena *arena);
+int32_t pc_encode(const pc_table *table, const pc_message *msg, uint8_t *buf, size_t *len);
+";
+
+pub(crate) fn generate_c_header<'a>(
+    files: impl IntoIterator<Item = &'a FileDescriptorProto>,
+    options: &Options,
+) -> String {
+    fn add_message(declarations: &mut String, scope: &str, message: &DescriptorProto, options: &Options) {
+        let full_name = if scope.is_empty() {
+            message.name().to_string()
+        } else {
+            format!("{}.{}", scope, message.name())
+        };
+        if options.custom.is_skipped(&full_name) {
+            return;
+        }
+        declarations.push_str(&format!(
+            "\n/* {} */\nconst pc_table *{}(void);\n",
+            full_name,
+            ffi_table_symbol(&full_name)
+        ));
+        for nested in message.nested_type() {
+            add_message(declarations, &full_name, nested, options);
+        }
+    }
+
+    // In file name order, like the generated Rust code
+    let mut files: Vec<_> = files.into_iter().collect();
+    files.sort_by_key(|file| file.name());
+
+    let mut declarations = String::new();
+    for file in files {
+        if options.is_extern_package(file.package()) {
+            continue;
+        }
+        for message in file.message_type() {
+            add_message(&mut declarations, file.package(), message, options);
+        }
+    }
+
+    format!(
+        "/* Auto-generated by protocrap-codegen\n * DO NOT EDIT */\n\n\
+         #pragma once\n\n\
+         #include <stddef.h>\n\
+         #include <stdint.h>\n\n\
+         #ifdef __cplusplus\n\
+         extern \"C\" {{\n\
+         #endif\n\n\
+         {RUNTIME_DECLARATIONS}{declarations}\n\
+         #ifdef __cplusplus\n\
+         }}\n\
+         #endif\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declares_nested_message_tables() {
+        let file_desc =
+            protocrap::google::protobuf::FileDescriptorProto::ProtoType::file_descriptor();
+        let header = generate_c_header([file_desc], &Options::default());
+
+        assert!(header.contains("pc_message *pc_decode("));
+        assert!(header.contains("const pc_table *pc_table_google_protobuf_FileDescriptorProto(void);"));
+        assert!(header.contains(
+            "const pc_table *pc_table_google_protobuf_DescriptorProto_ExtensionRange(void);"
+        ));
+
+        let options = Options::default()
+            .extern_path("google.protobuf", "protocrap::google::protobuf")
+            .unwrap();
+        let header = generate_c_header([file_desc], &options);
+        assert!(!header.contains("pc_table_google_protobuf"));
+    }
+}
@@ -5,7 +5,6 @@
 
 use std::collections::HashMap;
 
-use super::protocrap;
 use protocrap::ProtobufRef;
 use protocrap::google::protobuf::FieldDescriptorProto::Label;
 use protocrap::google::protobuf::FileDescriptorProto::ProtoType as FileDescriptorProto;
@@ -114,15 +113,37 @@ fn get_name_field(msg: &DynamicMessageRef) -> Option<String> {
 }
 
 /// Trim and clean up a comment string.
+///
+/// Lines continuing a list item are indented under its text, without that
+/// markdown reads them as a lazy continuation of the item.
 fn trim_comment(comment: &str) -> String {
     // Remove leading/trailing whitespace from each line and rejoin
-    comment
-        .lines()
-        .map(|line| line.trim())
-        .collect::<Vec<_>>()
-        .join("\n")
-        .trim()
-        .to_string()
+    let mut lines = Vec::new();
+    let mut indent = 0;
+    for line in comment.lines().map(str::trim) {
+        if line.is_empty() {
+            indent = 0;
+            lines.push(String::new());
+        } else if let Some(marker) = list_marker_len(line) {
+            indent = marker;
+            lines.push(line.to_string());
+        } else {
+            lines.push(format!("{:indent$}{}", "", line));
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// Length of the marker starting a markdown list item, `- `, `* `, `+ ` or
+/// `1. `, if `line` starts one.
+fn list_marker_len(line: &str) -> Option<usize> {
+    let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+    let marker = if digits == 0 {
+        line.get(..1).filter(|bullet| matches!(*bullet, "-" | "*" | "+"))?.len()
+    } else {
+        line[digits..].starts_with(['.', ')']).then_some(digits + 1)?
+    };
+    line[marker..].starts_with(' ').then_some(marker + 1)
 }
 
 #[cfg(test)]
@@ -183,6 +204,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn list_items_indent_continuation_lines() {
+        let comment = " Notes:\n - first item\n   continued\n 10. second\n continued\n\n after\n";
+        assert_eq!(
+            trim_comment(comment),
+            "Notes:\n- first item\n  continued\n10. second\n    continued\n\nafter"
+        );
+        assert_eq!(list_marker_len("-1 is negative"), None);
+        assert_eq!(list_marker_len("1.5 is a number"), None);
+        assert_eq!(list_marker_len("*"), None);
+    }
+
     #[test]
     fn dump_comments_to_file() {
         let file_desc =
@@ -0,0 +1,268 @@
+// protocrap-codegen/src/custom_options.rs
+
+//! The `protocrap.*` custom options of `proto/protocrap/options.proto`.
+//!
+//! The runtime drops unknown fields, so extensions of `MessageOptions` and
+//! `FieldOptions` don't survive decoding the descriptor set. They are read from
+//! the raw bytes instead, walking just the path from the set to the options.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Result, bail};
+use protocrap::wire::{Reader, WireType};
+
+/// `(protocrap.skip)`, a bool on `MessageOptions`.
+const SKIP: u32 = 50700;
+/// `(protocrap.rust_name)`, a string on `FieldOptions`.
+const RUST_NAME: u32 = 50700;
+
+/// Codegen hooks set by schema owners through custom options.
+#[derive(Default, Clone, Debug)]
+pub(crate) struct CustomOptions {
+    /// Fully qualified names, without leading dot, of messages not to generate.
+    skipped: HashSet<String>,
+    /// Accessor base names by message full name and field number.
+    rust_names: HashMap<(String, i32), String>,
+}
+
+impl CustomOptions {
+    /// Collect the custom options of every message and field in a serialized
+    /// `FileDescriptorSet`.
+    pub(crate) fn from_file_set_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut options = CustomOptions::default();
+        for (number, value) in fields(bytes)? {
+            if let (1, Value::Len(file)) = (number, value) {
+                options.add_file(file)?;
+            }
+        }
+        Ok(options)
+    }
+
+    /// Whether `full_name` (with or without leading dot) is a skipped message or
+    /// is declared inside one.
+    pub(crate) fn is_skipped(&self, full_name: &str) -> bool {
+        let mut name = full_name.trim_start_matches('.');
+        loop {
+            if self.skipped.contains(name) {
+                return true;
+            }
+            match name.rsplit_once('.') {
+                Some((scope, _)) => name = scope,
+                None => return false,
+            }
+        }
+    }
+
+    /// The `(protocrap.rust_name)` of field `number` of message `full_name`.
+    pub(crate) fn rust_name(&self, full_name: &str, number: i32) -> Option<&str> {
+        let key = (full_name.trim_start_matches('.').to_string(), number);
+        self.rust_names.get(&key).map(String::as_str)
+    }
+
+    fn add_file(&mut self, file: &[u8]) -> Result<()> {
+        let fields = fields(file)?;
+        let package = fields
+            .iter()
+            .filter_map(|(number, value)| match (number, value) {
+                (2, Value::Len(package)) => Some(std::str::from_utf8(package)),
+                _ => None,
+            })
+            .next_back()
+            .transpose()?
+            .unwrap_or("");
+        for (number, value) in fields {
+            if let (4, Value::Len(message)) = (number, value) {
+                self.add_message(package, message)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_message(&mut self, scope: &str, message: &[u8]) -> Result<()> {
+        let fields = fields(message)?;
+        let mut name = "";
+        for (number, value) in &fields {
+            if let (1, Value::Len(bytes)) = (number, value) {
+                name = std::str::from_utf8(bytes)?;
+            }
+        }
+        let full_name = if scope.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", scope, name)
+        };
+
+        for (number, value) in fields {
+            match (number, value) {
+                (2, Value::Len(field)) => self.add_field(&full_name, field)?,
+                (3, Value::Len(nested)) => self.add_message(&full_name, nested)?,
+                (7, Value::Len(options)) => {
+                    for (number, value) in self::fields(options)? {
+                        if let (SKIP, Value::Varint(skip)) = (number, value) {
+                            if skip != 0 {
+                                self.skipped.insert(full_name.clone());
+                            } else {
+                                self.skipped.remove(&full_name);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn add_field(&mut self, message: &str, field: &[u8]) -> Result<()> {
+        let mut number = None;
+        let mut rust_name = None;
+        for (tag, value) in fields(field)? {
+            match (tag, value) {
+                (3, Value::Varint(n)) => number = Some(n as i32),
+                (8, Value::Len(options)) => {
+                    for (tag, value) in fields(options)? {
+                        if let (RUST_NAME, Value::Len(name)) = (tag, value) {
+                            rust_name = Some(std::str::from_utf8(name)?.to_string());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let (Some(number), Some(rust_name)) = (number, rust_name) {
+            if rust_name.is_empty() {
+                bail!("{}: field {} has an empty (protocrap.rust_name)", message, number);
+            }
+            self.rust_names.insert((message.to_string(), number), rust_name);
+        }
+        Ok(())
+    }
+}
+
+/// A field value, with only the wire types the options are encoded with kept.
+enum Value<'a> {
+    Varint(u64),
+    Len(&'a [u8]),
+    Other,
+}
+
+/// The top level fields of the serialized message `bytes`.
+fn fields(bytes: &[u8]) -> Result<Vec<(u32, Value<'_>)>> {
+    let malformed = |_| anyhow::anyhow!("Malformed descriptor set");
+    let mut reader = Reader::new(bytes);
+    let mut fields = Vec::new();
+    while !reader.is_empty() {
+        let (number, wire_type) = reader.read_tag().map_err(malformed)?;
+        let value = match wire_type {
+            WireType::Varint => Value::Varint(reader.read_varint().map_err(malformed)?),
+            WireType::LengthDelimited => Value::Len(reader.read_bytes().map_err(malformed)?),
+            _ => {
+                reader.skip(number, wire_type).map_err(malformed)?;
+                Value::Other
+            }
+        };
+        fields.push((number, value));
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Options, generate_c_header, generate_with_options};
+    use allocator_api2::alloc::Global;
+    use protocrap::ProtobufRef;
+    use protocrap::google::protobuf::DescriptorProto::ProtoType as DescriptorProto;
+    use protocrap::google::protobuf::FieldDescriptorProto::{Label, ProtoType as FieldDescriptorProto, Type};
+    use protocrap::google::protobuf::FileDescriptorProto::ProtoType as FileDescriptorProto;
+
+    fn push_varint(out: &mut Vec<u8>, mut n: u64) {
+        while n >= 0x80 {
+            out.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        out.push(n as u8);
+    }
+
+    /// `bytes` as length delimited field `number`.
+    fn len_field(number: u32, bytes: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        push_varint(&mut out, u64::from(number) << 3 | 2);
+        push_varint(&mut out, bytes.len() as u64);
+        out.extend_from_slice(bytes);
+        out
+    }
+
+    /// Package `test` with a skipped `Internal { Inner }` and a `Kept` whose
+    /// field `id` is renamed to `key`, plus a field of `type_name` if given.
+    fn file_set(type_name: Option<&str>) -> Vec<u8> {
+        let mut arena = protocrap::arena::Arena::new(&Global);
+        let mut internal = DescriptorProto::default();
+        internal.set_name("Internal", &mut arena).unwrap();
+        internal.add_nested_type(&mut arena).unwrap().set_name("Inner", &mut arena).unwrap();
+        let mut internal = internal.encode_vec::<32>().unwrap();
+        let mut skip = Vec::new();
+        push_varint(&mut skip, u64::from(SKIP) << 3);
+        skip.push(1);
+        internal.extend(len_field(7, &skip));
+
+        let mut id = FieldDescriptorProto::default();
+        id.set_name("id", &mut arena).unwrap();
+        id.set_number(1);
+        id.set_label(Label::LABEL_OPTIONAL);
+        id.set_type(Type::TYPE_INT32);
+        let mut id = id.encode_vec::<32>().unwrap();
+        id.extend(len_field(8, &len_field(RUST_NAME, b"key")));
+
+        let mut kept = DescriptorProto::default();
+        kept.set_name("Kept", &mut arena).unwrap();
+        if let Some(type_name) = type_name {
+            let field = kept.add_field(&mut arena).unwrap();
+            field.set_name("internal", &mut arena).unwrap();
+            field.set_number(2);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(Type::TYPE_MESSAGE);
+            field.set_type_name(type_name, &mut arena).unwrap();
+        }
+        let mut kept = kept.encode_vec::<32>().unwrap();
+        kept.extend(len_field(2, &id));
+
+        let mut file = FileDescriptorProto::default();
+        file.set_name("test.proto", &mut arena).unwrap();
+        file.set_package("test", &mut arena).unwrap();
+        let mut file = file.encode_vec::<32>().unwrap();
+        file.extend(len_field(4, &internal));
+        file.extend(len_field(4, &kept));
+        len_field(1, &file)
+    }
+
+    #[test]
+    fn reads_options_from_raw_descriptors() {
+        let options = CustomOptions::from_file_set_bytes(&file_set(None)).unwrap();
+        assert!(options.is_skipped("test.Internal"));
+        assert!(options.is_skipped(".test.Internal.Inner"));
+        assert!(!options.is_skipped("test.Kept"));
+        assert_eq!(options.rust_name(".test.Kept", 1), Some("key"));
+        assert_eq!(options.rust_name("test.Kept", 2), None);
+        assert!(CustomOptions::from_file_set_bytes(&[0x0a, 0x05, 0x00]).is_err());
+    }
+
+    #[test]
+    fn skips_messages_and_renames_fields() {
+        let options = Options::default().ffi(true);
+        let code = generate_with_options(&file_set(None), &options).unwrap();
+        assert!(code.contains("pub mod Kept"));
+        assert!(!code.contains("pub mod Internal"));
+        assert!(!code.contains("pub mod Inner"));
+        assert!(code.contains("fn key(&self)"));
+        assert!(code.contains("pub fn set_key(&mut self"));
+        assert!(!code.contains("fn id(&self)"));
+
+        let header = generate_c_header(&file_set(None), &options).unwrap();
+        assert!(header.contains("pc_table_test_Kept"));
+        assert!(!header.contains("pc_table_test_Internal"));
+
+        let err = generate_with_options(&file_set(Some(".test.Internal.Inner")), &options).unwrap_err();
+        assert!(err.to_string().contains("field test.Kept.internal refers to test.Internal.Inner"));
+    }
+}
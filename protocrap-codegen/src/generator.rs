@@ -1,13 +1,12 @@
 // protocrap-codegen/src/generator.rs
 
-use super::protocrap;
-
 use std::collections::HashMap;
 use std::panic;
 
 use super::comments::extract_comments;
 use super::names::*;
 use super::tables;
+use super::Options;
 use allocator_api2::alloc::Global;
 use anyhow::Result;
 use proc_macro2::TokenStream;
@@ -16,14 +15,19 @@ use protocrap::google::protobuf::EnumDescriptorProto::ProtoType as EnumDescripto
 use protocrap::google::protobuf::FieldDescriptorProto::Type;
 use protocrap::google::protobuf::FileDescriptorProto::ProtoType as FileDescriptorProto;
 use protocrap::google::protobuf::FileDescriptorSet::ProtoType as FileDescriptorSet;
+use protocrap::google::protobuf::ServiceDescriptorProto::ProtoType as ServiceDescriptorProto;
 use protocrap::reflection::is_in_oneof;
 use protocrap::reflection::is_repeated;
+use protocrap::reflection::map_entry_error;
 use protocrap::reflection::needs_has_bit;
 use protocrap::{ProtobufMut, ProtobufRef};
 use quote::{format_ident, quote};
 
 #[allow(dead_code)]
-pub(crate) fn generate_file_set(file_set: &FileDescriptorSet) -> Result<TokenStream> {
+pub(crate) fn generate_file_set(
+    file_set: &FileDescriptorSet,
+    options: &Options,
+) -> Result<TokenStream> {
     // Build a tree of packages to handle hierarchical namespaces properly
     // This avoids duplicate module declarations for packages like:
     //   - protobuf_test_messages.proto2
@@ -36,12 +40,25 @@ pub(crate) fn generate_file_set(file_set: &FileDescriptorSet) -> Result<TokenStr
         children: std::collections::BTreeMap<String, PackageNode>,
     }
 
+    let options = &options
+        .with_closed_enums(file_set)
+        .with_max_encoded_sizes(file_set);
     let mut root = PackageNode::default();
 
+    // Files go in name order and packages in the tree are ordered too, so the
+    // output doesn't depend on the order the files were passed to protoc
+    let mut files: Vec<&FileDescriptorProto> = file_set.file().iter().map(|f| &**f).collect();
+    files.sort_by_key(|file| file.name());
+
     // Organize files into package tree
-    for file in file_set.file() {
-        let content = generate_file_content(file)?;
+    for file in files {
         let package = file.package();
+        if options.is_extern_package(package) {
+            // Types are provided by an existing module, don't generate them again
+            continue;
+        }
+        check_skipped_references(file, options)?;
+        let content = generate_file_content(file, options)?;
 
         if package.is_empty() {
             // Root package
@@ -63,7 +80,7 @@ pub(crate) fn generate_file_set(file_set: &FileDescriptorSet) -> Result<TokenStr
 
         for (name, child_node) in &node.children {
             let child_content = generate_node(child_node);
-            let ident = format_ident!("{}", name);
+            let ident = format_ident!("{}", sanitize_type_name(name));
             items.push(quote! {
                 pub mod #ident {
                     use super::protocrap;
@@ -86,7 +103,7 @@ pub(crate) fn generate_file_set(file_set: &FileDescriptorSet) -> Result<TokenStr
 }
 
 /// Generate the content of a single file (without package module wrapping)
-fn generate_file_content(file: &FileDescriptorProto) -> Result<TokenStream> {
+fn generate_file_content(file: &FileDescriptorProto, options: &Options) -> Result<TokenStream> {
     let mut items = Vec::new();
 
     // Extract comments from source_code_info
@@ -101,7 +118,18 @@ fn generate_file_content(file: &FileDescriptorProto) -> Result<TokenStream> {
     // Generate messages
     for (idx, message) in file.message_type().iter().enumerate() {
         let name = message.name();
-        items.push(generate_message(message, file, &comments, name, vec![idx])?);
+        items.push(generate_message(message, file, &comments, name, vec![idx], options)?);
+    }
+
+    // Generate services
+    if options.services {
+        for service in file.service() {
+            items.push(generate_service(service, file.package(), &comments, options));
+        }
+    }
+
+    if options.strip_descriptors {
+        return Ok(quote! { #(#items)* });
     }
 
     let file_descriptor = if file.name()
@@ -156,7 +184,7 @@ fn generate_enum(
     comments: &HashMap<String, String>,
     name_prefix: &str,
 ) -> Result<TokenStream> {
-    let name = format_ident!("{}", enum_desc.name());
+    let name = format_ident!("{}", sanitize_type_name(enum_desc.name()));
 
     // Get doc comment for the enum
     let enum_doc = make_doc_comment(comments.get(name_prefix));
@@ -174,7 +202,7 @@ fn generate_enum(
     let variants: Vec<_> = unique_values
         .iter()
         .map(|v| {
-            let variant_name = format_ident!("{}", v.name());
+            let variant_name = format_ident!("{}", sanitize_ident(v.name()));
             let number = v.number();
             let value_key = format!("{}.{}", name_prefix, v.name());
             let variant_doc = make_doc_comment(comments.get(&value_key));
@@ -189,7 +217,7 @@ fn generate_enum(
     let from_i32_arms: Vec<_> = unique_values
         .iter()
         .map(|v| {
-            let variant_name = format_ident!("{}", v.name());
+            let variant_name = format_ident!("{}", sanitize_ident(v.name()));
             let number = v.number();
             quote! { #number => Some(Self::#variant_name) }
         })
@@ -232,15 +260,238 @@ fn make_doc_comment(comment: Option<&String>) -> TokenStream {
     }
 }
 
+fn generate_service(
+    service: &ServiceDescriptorProto,
+    package: &str,
+    comments: &HashMap<String, String>,
+    options: &Options,
+) -> TokenStream {
+    let name = format_ident!("{}", sanitize_type_name(service.name()));
+    let full_name = if package.is_empty() {
+        service.name().to_string()
+    } else {
+        format!("{}.{}", package, service.name())
+    };
+    let service_doc = make_doc_comment(comments.get(service.name()));
+
+    let mut handler_methods = Vec::new();
+    let mut dispatch_arms = Vec::new();
+    let mut client_methods = Vec::new();
+    let mut streaming = Vec::new();
+
+    for method in service.method() {
+        if method.client_streaming() || method.server_streaming() {
+            streaming.push(format!("`{}`", method.name()));
+            continue;
+        }
+        let method_name = method.name();
+        let fn_name = format_ident!("{}", sanitize_ident(&to_snake_case(method_name)));
+        let input = rust_type_path(method.input_type(), options);
+        let output = rust_type_path(method.output_type(), options);
+        let path = format!("/{}/{}", full_name, method_name);
+        let method_doc =
+            make_doc_comment(comments.get(&format!("{}.{}", service.name(), method_name)));
+
+        handler_methods.push(quote! {
+            #method_doc
+            fn #fn_name(
+                &self,
+                request: protocrap::generated_code_only::rpc::Owned<#input::ProtoType>,
+            ) -> impl core::future::Future<
+                Output = Result<
+                    protocrap::generated_code_only::rpc::Owned<#output::ProtoType>,
+                    protocrap::generated_code_only::rpc::Status,
+                >,
+            > + Send;
+        });
+        dispatch_arms.push(quote! {
+            #method_name => {
+                let request = protocrap::generated_code_only::rpc::decode_request(request);
+                Box::pin(async move {
+                    let response = self.0.#fn_name(request?).await?;
+                    protocrap::generated_code_only::rpc::encode(&*response)
+                })
+            }
+        });
+        client_methods.push(quote! {
+            #method_doc
+            pub fn #fn_name(
+                &self,
+                request: &#input::ProtoType,
+            ) -> impl core::future::Future<
+                Output = Result<
+                    protocrap::generated_code_only::rpc::Owned<#output::ProtoType>,
+                    protocrap::generated_code_only::rpc::Status,
+                >,
+            > + Send + '_ {
+                let request = protocrap::generated_code_only::rpc::encode(request);
+                async move {
+                    let response = self.0.unary(#path, request?).await?;
+                    protocrap::generated_code_only::rpc::decode_response(&response)
+                }
+            }
+        });
+    }
+
+    let streaming_doc = if streaming.is_empty() {
+        quote! {}
+    } else {
+        let note = format!(
+            " Streaming methods are not generated: {}.",
+            streaming.join(", ")
+        );
+        quote! {
+            #[doc = ""]
+            #[doc = #note]
+        }
+    };
+
+    quote! {
+        #service_doc
+        #[allow(non_snake_case)]
+        pub mod #name {
+            use super::protocrap;
+            #[allow(unused_imports)]
+            use protocrap::generated_code_only::rpc::Channel;
+
+            /// Fully qualified service name.
+            pub const NAME: &str = #full_name;
+
+            /// Implement this to serve the service, then wrap it in a [`Server`].
+            #streaming_doc
+            pub trait Handler: Send + Sync + 'static {
+                #(#handler_methods)*
+            }
+
+            /// Dispatches encoded requests to a [`Handler`].
+            pub struct Server<T>(T);
+
+            impl<T: Handler> Server<T> {
+                pub fn new(handler: T) -> Self {
+                    Server(handler)
+                }
+
+                pub fn handler(&self) -> &T {
+                    &self.0
+                }
+            }
+
+            impl<T: Handler> protocrap::generated_code_only::rpc::Service for Server<T> {
+                const NAME: &'static str = NAME;
+
+                #[allow(unused_variables)]
+                fn call(
+                    &self,
+                    method: &str,
+                    request: &[u8],
+                ) -> protocrap::generated_code_only::rpc::BoxFuture<
+                    '_,
+                    Result<Vec<u8>, protocrap::generated_code_only::rpc::Status>,
+                > {
+                    match method {
+                        #(#dispatch_arms)*
+                        _ => {
+                            let status = protocrap::generated_code_only::rpc::Status::unimplemented(method);
+                            Box::pin(async move { Err(status) })
+                        }
+                    }
+                }
+            }
+
+            /// Calls the service over a [`Channel`].
+            pub struct Client<C>(C);
+
+            impl<C: protocrap::generated_code_only::rpc::Channel> Client<C> {
+                pub fn new(channel: C) -> Self {
+                    Client(channel)
+                }
+
+                #(#client_methods)*
+            }
+        }
+    }
+}
+
+/// Fully qualified name, without leading dot, of the message at `name_prefix`
+/// in `file`.
+fn message_full_name(file: &FileDescriptorProto, name_prefix: &str) -> String {
+    if file.package().is_empty() {
+        name_prefix.to_string()
+    } else {
+        format!("{}.{}", file.package(), name_prefix)
+    }
+}
+
+/// Fail if a generated field or RPC method of `file` refers to a type that
+/// isn't generated because of `(protocrap.skip)`.
+fn check_skipped_references(file: &FileDescriptorProto, options: &Options) -> Result<()> {
+    fn check_message(
+        file: &FileDescriptorProto,
+        name_prefix: &str,
+        message: &DescriptorProto,
+        options: &Options,
+    ) -> Result<()> {
+        let full_name = message_full_name(file, name_prefix);
+        if options.custom.is_skipped(&full_name) {
+            return Ok(());
+        }
+        for field in message.field() {
+            let is_type_reference = matches!(
+                field.r#type(),
+                Some(Type::TYPE_MESSAGE | Type::TYPE_GROUP | Type::TYPE_ENUM)
+            );
+            if is_type_reference && options.custom.is_skipped(field.type_name()) {
+                anyhow::bail!(
+                    "{}: field {}.{} refers to {}, which is skipped by (protocrap.skip)",
+                    file.name(),
+                    full_name,
+                    field.name(),
+                    field.type_name().trim_start_matches('.')
+                );
+            }
+        }
+        for nested in message.nested_type() {
+            check_message(file, &format!("{}.{}", name_prefix, nested.name()), nested, options)?;
+        }
+        Ok(())
+    }
+
+    for message in file.message_type() {
+        check_message(file, message.name(), message, options)?;
+    }
+    if options.services {
+        for service in file.service() {
+            for method in service.method() {
+                for type_name in [method.input_type(), method.output_type()] {
+                    if options.custom.is_skipped(type_name) {
+                        anyhow::bail!(
+                            "{}: method {}.{} refers to {}, which is skipped by (protocrap.skip)",
+                            file.name(),
+                            service.name(),
+                            method.name(),
+                            type_name.trim_start_matches('.')
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 fn generate_message(
     message: &DescriptorProto,
     file: &FileDescriptorProto,
     comments: &HashMap<String, String>,
     name_prefix: &str,
     path: Vec<usize>,
+    options: &Options,
 ) -> Result<TokenStream> {
-    let msg = generate_message_impl(message, file, comments, name_prefix, path)?;
-    let name = format_ident!("{}", sanitize_field_name(message.name()));
+    if options.custom.is_skipped(&message_full_name(file, name_prefix)) {
+        return Ok(quote! {});
+    }
+    let msg = generate_message_impl(message, file, comments, name_prefix, path, options)?;
+    let name = format_ident!("{}", sanitize_type_name(message.name()));
 
     Ok(quote! {
         #[allow(non_snake_case)]
@@ -259,7 +510,14 @@ fn generate_message_impl(
     comments: &HashMap<String, String>,
     name_prefix: &str,
     path: Vec<usize>,
+    options: &Options,
 ) -> Result<TokenStream> {
+    if let Some(error) = map_entry_error(message) {
+        anyhow::bail!("{}: {}: {}", file.name(), name_prefix, error);
+    }
+
+    let full_name = message_full_name(file, name_prefix);
+
     // Nested types first
 
     let mut nested_items = Vec::new();
@@ -273,6 +531,7 @@ fn generate_message_impl(
             comments,
             &nested_prefix,
             nested_path,
+            options,
         )?);
     }
 
@@ -310,25 +569,53 @@ fn generate_message_impl(
         }
     }
 
+    // Messages of only singular scalar fields own no arena data, so they can
+    // be copied like plain structs
+    let is_pod = message.field().iter().all(|f| {
+        !is_repeated(f)
+            && !matches!(
+                f.r#type(),
+                Some(Type::TYPE_MESSAGE | Type::TYPE_GROUP | Type::TYPE_STRING | Type::TYPE_BYTES)
+            )
+    });
+    let derives = if is_pod {
+        quote! { #[derive(Default, Clone, Copy)] }
+    } else {
+        quote! { #[derive(Default)] }
+    };
+    let union_derives = if is_pod {
+        quote! { #[derive(Clone, Copy)] }
+    } else {
+        quote! {}
+    };
+
     // Generate union types for each oneof
     let mut union_defs = Vec::new();
     let mut union_fields = Vec::new();
+    let mut which_methods = Vec::new();
+    let names = MessageNames::new(message, &full_name, options);
     for (idx, oneof) in message.oneof_decl().iter().enumerate() {
-        let union_name = format_ident!("{}Union", to_pascal_case(oneof.name()));
+        let union_name = names.union(idx);
         let oneof_field_name = format_ident!("{}", sanitize_field_name(oneof.name()));
 
         if let Some(fields) = oneof_fields.get(&(idx as i32)) {
+            let (which_def, which_method) =
+                generate_oneof_which(oneof.name(), has_bits_words + idx, fields, &names, options);
+            union_defs.push(which_def);
+            which_methods.push(which_method);
+
             let variants: Vec<_> = fields
                 .iter()
                 .map(|f| {
                     let variant_name = format_ident!("{}", sanitize_field_name(f.name()));
-                    let variant_type = rust_field_type_tokens(f);
+                    let variant_type = rust_field_type_tokens(f, options);
                     quote! { #variant_name: core::mem::ManuallyDrop<#variant_type> }
                 })
                 .collect();
 
             union_defs.push(quote! {
                 #[repr(C)]
+                #union_derives
                 pub union #union_name {
                     #(pub #variants,)*
                 }
@@ -351,7 +638,7 @@ fn generate_message_impl(
         .filter(|f| !is_in_oneof(f.as_ref()))
         .map(|field| {
             let field_name = format_ident!("{}", sanitize_field_name(field.name()));
-            let field_type = rust_field_type_tokens(field);
+            let field_type = rust_field_type_tokens(field, options);
             (field.number(), quote! { #field_name: #field_type })
         })
         .collect();
@@ -391,12 +678,72 @@ fn generate_message_impl(
         .collect();
 
     // Accessor methods
-    let accessors = generate_accessors(message, &has_bit_map, comments, name_prefix)?;
+    let accessors =
+        generate_accessors(message, &has_bit_map, &names, comments, name_prefix, options)?;
 
     // Protobuf trait impl
     let protobuf_impl = generate_protobuf_impl();
 
-    let table = tables::generate_table(message, &has_bit_map, &oneof_info, Some(file.syntax()))?;
+    let max_encoded_size = match options.max_encoded_size(&full_name) {
+        Some(size) => {
+            let doc = format!(
+                " Upper bound on the encoded size of any `{}`, for sizing `encode_flat` buffers at compile time.",
+                name_prefix.trim_start_matches('.')
+            );
+            let size = proc_macro2::Literal::usize_unsuffixed(size);
+            quote! {
+                #[doc = #doc]
+                pub const MAX_ENCODED_SIZE: usize = #size;
+            }
+        }
+        None => quote! {},
+    };
+
+    let ffi_table = if options.ffi {
+        let symbol = format_ident!("{}", ffi_table_symbol(&full_name));
+        quote! {
+            #[unsafe(no_mangle)]
+            pub extern "C" fn #symbol() -> *const protocrap::generated_code_only::Table {
+                &TABLE.table
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let serde_impls = if options.serde {
+        quote! {
+            impl protocrap::generated_code_only::serde::Serialize for ProtoType {
+                fn serialize<S: protocrap::generated_code_only::serde::Serializer>(
+                    &self,
+                    serializer: S,
+                ) -> Result<S::Ok, S::Error> {
+                    protocrap::generated_code_only::serialize_message(self, serializer)
+                }
+            }
+
+            impl<'de> protocrap::generated_code_only::serde::Deserialize<'de> for ProtoType {
+                fn deserialize<D: protocrap::generated_code_only::serde::Deserializer<'de>>(
+                    deserializer: D,
+                ) -> Result<Self, D::Error> {
+                    protocrap::generated_code_only::deserialize_message(deserializer)
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let file_features = file.options().and_then(|o| o.features());
+    let table = tables::generate_table(
+        message,
+        &full_name,
+        &has_bit_map,
+        &oneof_info,
+        Some(file.syntax()),
+        file_features,
+        options,
+    )?;
 
     // Build path to FILE_DESCRIPTOR_PROTO in the file-specific module
     let filename = std::path::Path::new(file.name())
@@ -410,7 +757,10 @@ fn generate_message_impl(
     let file_descriptor_path = if package.is_empty() {
         quote! { crate::#file_mod_name::#file_descriptor_ident }
     } else {
-        let mut parts: Vec<_> = package.split('.').map(|s| format_ident!("{}", s)).collect();
+        let mut parts: Vec<_> = package
+            .split('.')
+            .map(|s| format_ident!("{}", sanitize_type_name(s)))
+            .collect();
         parts.push(file_mod_name);
         parts.push(file_descriptor_ident);
 
@@ -438,6 +788,21 @@ fn generate_message_impl(
     );
     let file_descriptor_doc = format!(" Returns the file descriptor for `{}`.", proto_file);
     let descriptor_proto_doc = format!(" Returns the descriptor for `{}`.", message_name);
+    let descriptor_accessors = if options.strip_descriptors {
+        quote! {}
+    } else {
+        quote! {
+            #[doc = #file_descriptor_doc]
+            pub const fn file_descriptor() -> &'static protocrap::google::protobuf::FileDescriptorProto::ProtoType {
+                &#file_descriptor_path
+            }
+
+            #[doc = #descriptor_proto_doc]
+            pub const fn descriptor_proto() -> &'static protocrap::google::protobuf::DescriptorProto::ProtoType {
+                #message_descriptor_accessor
+            }
+        }
+    };
 
     Ok(quote! {
         #(#nested_items)*
@@ -447,7 +812,7 @@ fn generate_message_impl(
 
         #message_doc
         #[repr(C)]
-        #[derive(Default)]
+        #derives
         pub struct ProtoType {
             metadata: [u32; #metadata_words],
             #(#regular_fields,)*
@@ -475,26 +840,33 @@ fn generate_message_impl(
                 }
             }
 
+            #max_encoded_size
+
             #[doc = #clear_doc]
             pub fn clear(&mut self) {
                 *self = Self::default();
             }
 
-            #[doc = #file_descriptor_doc]
-            pub const fn file_descriptor() -> &'static protocrap::google::protobuf::FileDescriptorProto::ProtoType {
-                &#file_descriptor_path
+            /// A deep copy of this message, with its strings, bytes and
+            /// sub-messages allocated in `arena`.
+            pub fn clone_in(
+                &self,
+                arena: &mut protocrap::generated_code_only::Arena,
+            ) -> Result<Self, protocrap::generated_code_only::Error<core::alloc::LayoutError>> {
+                protocrap::generated_code_only::clone_message(self, arena)
             }
 
-            #[doc = #descriptor_proto_doc]
-            pub const fn descriptor_proto() -> &'static protocrap::google::protobuf::DescriptorProto::ProtoType {
-                #message_descriptor_accessor
-            }
+            #descriptor_accessors
 
             #accessors
+
+            #(#which_methods)*
         }
 
         #protobuf_impl
         #table
+        #ffi_table
+        #serde_impls
     })
 }
 
@@ -556,6 +928,7 @@ fn unescape_proto_string(s: &str) -> Result<Vec<u8>> {
 
 fn parse_primitive_default(
     field: &protocrap::google::protobuf::FieldDescriptorProto::ProtoType,
+    options: &Options,
 ) -> Option<TokenStream> {
     let Some(default_str) = field.get_default_value() else {
         return None;
@@ -651,8 +1024,8 @@ fn parse_primitive_default(
             if default_str.is_empty() {
                 return None;
             }
-            let enum_type = rust_type_tokens(field);
-            let variant = format_ident!("{}", default_str);
+            let enum_type = rust_type_tokens(field, options);
+            let variant = format_ident!("{}", sanitize_ident(default_str));
             Some(quote! { Some(#enum_type::#variant) })
         }
         Type::TYPE_MESSAGE | Type::TYPE_GROUP => {
@@ -661,11 +1034,256 @@ fn parse_primitive_default(
     }
 }
 
+/// Generate the `<oneof>::Which` enum, the `which_<oneof>()` accessor that
+/// returns the active case, so oneofs can be matched exhaustively, and the
+/// `set_<oneof>()` setter that takes a case.
+fn generate_oneof_which(
+    oneof_name: &str,
+    discriminant_word_idx: usize,
+    fields: &[&protocrap::google::protobuf::FieldDescriptorProto::ProtoType],
+    names: &MessageNames,
+    options: &Options,
+) -> (TokenStream, TokenStream) {
+    let mod_name = format_ident!("{}", sanitize_module_name(oneof_name));
+    let oneof_field_name = format_ident!("{}", sanitize_field_name(oneof_name));
+    let which_name = format_ident!("which_{}", oneof_name);
+    let set_name = format_ident!("set_{}", oneof_name);
+
+    let mut borrows = false;
+    let mut variants = Vec::new();
+    let mut arms = Vec::new();
+    let mut set_arms = Vec::new();
+    for field in fields {
+        let field_name = format_ident!("{}", sanitize_field_name(field.name()));
+        let variant_name = names.which_variant(field);
+        let field_number = field.number() as u32;
+        let (variant_type, value, stored) = match field.r#type().unwrap() {
+            Type::TYPE_STRING => {
+                borrows = true;
+                (
+                    quote! { &'a str },
+                    quote! { unsafe { (*self.#oneof_field_name.#field_name).as_str() } },
+                    quote! {{
+                        let mut string = protocrap::generated_code_only::String::new();
+                        string.assign(value, arena)?;
+                        string
+                    }},
+                )
+            }
+            Type::TYPE_BYTES => {
+                borrows = true;
+                (
+                    quote! { &'a [u8] },
+                    quote! { unsafe { (*self.#oneof_field_name.#field_name).slice() } },
+                    quote! {{
+                        let mut bytes = protocrap::generated_code_only::Bytes::new();
+                        bytes.assign(value, arena)?;
+                        bytes
+                    }},
+                )
+            }
+            Type::TYPE_MESSAGE | Type::TYPE_GROUP => {
+                borrows = true;
+                let msg_type = rust_type_tokens(field, options);
+                (
+                    quote! { &'a #msg_type::ProtoType },
+                    quote! { unsafe { (*self.#oneof_field_name.#field_name).as_ref() } },
+                    quote! { protocrap::generated_code_only::copy_message(value, arena)? },
+                )
+            }
+            Type::TYPE_ENUM => {
+                let enum_type = rust_type_tokens(field, options);
+                (
+                    quote! { Option<#enum_type> },
+                    quote! { #enum_type::from_i32(unsafe { *self.#oneof_field_name.#field_name }) },
+                    quote! { value.map_or(0, |value| value.to_i32()) },
+                )
+            }
+            _ => (
+                rust_element_type_tokens(field, options),
+                quote! { unsafe { *self.#oneof_field_name.#field_name } },
+                quote! { value },
+            ),
+        };
+        let variant_doc = format!(" The `{}` field is set.", field.name());
+        variants.push(quote! {
+            #[doc = #variant_doc]
+            #variant_name(#variant_type)
+        });
+        arms.push(quote! { #field_number => #mod_name::Which::#variant_name(#value) });
+        set_arms.push(quote! {
+            #mod_name::Which::#variant_name(value) => {
+                self.#oneof_field_name.#field_name = core::mem::ManuallyDrop::new(#stored);
+                #field_number
+            }
+        });
+    }
+
+    // Only carry a lifetime when some case actually borrows from the message
+    let lifetime = if borrows { quote! { <'a> } } else { quote! {} };
+    let return_lifetime = if borrows { quote! { <'_> } } else { quote! {} };
+
+    let enum_doc = format!(" The active case of the `{}` oneof.", oneof_name);
+    let which_doc = format!(" Returns which field of the `{}` oneof is set.", oneof_name);
+    let set_doc = format!(
+        " Sets the `{}` oneof to `value`, replacing whichever field was set.",
+        oneof_name
+    );
+
+    let which_def = quote! {
+        #[allow(non_snake_case)]
+        pub mod #mod_name {
+            #[allow(unused_imports)]
+            use super::protocrap;
+
+            #[doc = #enum_doc]
+            #[derive(Debug, Clone, Copy)]
+            pub enum Which #lifetime {
+                #(#variants,)*
+                /// No field of the oneof is set.
+                None,
+            }
+        }
+    };
+
+    // The previous case is wiped before storing the new one, so no bytes of
+    // it survive in the union. Cases that borrow are copied into the arena.
+    let set_body = quote! {
+        self.metadata[#discriminant_word_idx] = 0;
+        self.#oneof_field_name = Default::default();
+        self.metadata[#discriminant_word_idx] = match value {
+            #(#set_arms)*
+            #mod_name::Which::None => 0,
+        };
+    };
+    let set_method = if borrows {
+        let copy_doc = " Strings, bytes and messages are copied into `arena`. On error the oneof is left unset.";
+        quote! {
+            #[doc = #set_doc]
+            ///
+            #[doc = #copy_doc]
+            pub fn #set_name(
+                &mut self,
+                value: #mod_name::Which<'_>,
+                arena: &mut protocrap::generated_code_only::Arena,
+            ) -> Result<(), protocrap::generated_code_only::Error<core::alloc::LayoutError>> {
+                #set_body
+                Ok(())
+            }
+        }
+    } else {
+        quote! {
+            #[doc = #set_doc]
+            pub fn #set_name(&mut self, value: #mod_name::Which) {
+                #set_body
+            }
+        }
+    };
+
+    let which_method = quote! {
+        #[doc = #which_doc]
+        pub fn #which_name(&self) -> #mod_name::Which #return_lifetime {
+            match self.metadata[#discriminant_word_idx] {
+                #(#arms,)*
+                _ => #mod_name::Which::None,
+            }
+        }
+
+        #set_method
+    };
+
+    (which_def, which_method)
+}
+
+/// The getters of the key and value of map `entry`, with the types they return.
+fn map_entry_getters(
+    entry: &DescriptorProto,
+    entry_name: &str,
+    options: &Options,
+) -> [(proc_macro2::Ident, TokenStream); 2] {
+    // The types returned by the entry's getters
+    let getter_type = |field: &protocrap::google::protobuf::FieldDescriptorProto::ProtoType| {
+        match field.r#type().unwrap() {
+            Type::TYPE_STRING => quote! { &str },
+            Type::TYPE_BYTES => quote! { &[u8] },
+            Type::TYPE_ENUM => {
+                let enum_type = rust_type_tokens(field, options);
+                quote! { Option<#enum_type> }
+            }
+            Type::TYPE_MESSAGE | Type::TYPE_GROUP => {
+                let msg_type = rust_type_tokens(field, options);
+                quote! { Option<&#msg_type::ProtoType> }
+            }
+            _ => rust_element_type_tokens(field, options),
+        }
+    };
+    let entry_names = MessageNames::new(entry, entry_name, options);
+    [1, 2].map(|number| {
+        let field = entry.field().iter().find(|f| f.number() == number).unwrap();
+        (entry_names.accessor(field), getter_type(field))
+    })
+}
+
+/// Generate `<field>_map()`, indexing the entries of a map field by key.
+fn generate_map_method(
+    field: &protocrap::google::protobuf::FieldDescriptorProto::ProtoType,
+    entry: &DescriptorProto,
+    accessor_name: &proc_macro2::Ident,
+    options: &Options,
+) -> TokenStream {
+    let [(key_getter, key_type), (value_getter, value_type)] =
+        map_entry_getters(entry, field.type_name(), options);
+
+    let field_name = format_ident!("{}", sanitize_field_name(field.name()));
+    let map_name = format_ident!("{}_map", accessor_name);
+    let map_doc = format!(
+        " Returns the entries of `{}` indexed by key, the last entry winning for duplicate keys.",
+        field.name()
+    );
+    quote! {
+        #[doc = #map_doc]
+        pub fn #map_name(&self, arena: &mut protocrap::generated_code_only::Arena) -> Result<protocrap::generated_code_only::ProtoMap<#key_type, #value_type>, protocrap::generated_code_only::Error<core::alloc::LayoutError>> {
+            protocrap::generated_code_only::ProtoMap::from_entries(
+                self.#field_name.slice().iter().map(|entry| (entry.#key_getter(), entry.#value_getter())),
+                arena,
+            )
+        }
+    }
+}
+
+/// Generate `<field>_iter_ordered()`, the entries of a map field in the
+/// order they are stored.
+fn generate_map_iter_method(
+    field: &protocrap::google::protobuf::FieldDescriptorProto::ProtoType,
+    entry: &DescriptorProto,
+    accessor_name: &proc_macro2::Ident,
+    options: &Options,
+) -> TokenStream {
+    let [(key_getter, key_type), (value_getter, value_type)] =
+        map_entry_getters(entry, field.type_name(), options);
+
+    let field_name = format_ident!("{}", sanitize_field_name(field.name()));
+    let iter_name = format_ident!("{}_iter_ordered", accessor_name);
+    let iter_doc = format!(
+        " Returns the key and value of every entry of `{}`, in the order they were added or decoded. \
+         Repeated keys come once per entry, unless decoded with `dedup_map_entries` or after `normalize_maps()`.",
+        field.name()
+    );
+    quote! {
+        #[doc = #iter_doc]
+        pub fn #iter_name(&self) -> impl ExactSizeIterator<Item = (#key_type, #value_type)> + DoubleEndedIterator + '_ {
+            self.#field_name.slice().iter().map(|entry| (entry.#key_getter(), entry.#value_getter()))
+        }
+    }
+}
+
 fn generate_accessors(
     message: &DescriptorProto,
     has_bit_map: &std::collections::HashMap<i32, usize>,
+    names: &MessageNames,
     comments: &HashMap<String, String>,
     name_prefix: &str,
+    options: &Options,
 ) -> Result<TokenStream> {
     let mut methods = Vec::new();
 
@@ -679,6 +1297,7 @@ fn generate_accessors(
 
     for field in message.field() {
         let field_name = format_ident!("{}", sanitize_field_name(field.name()));
+        let accessor_name = names.accessor(field);
 
         // Get field doc comment
         let field_key = format!("{}.{}", name_prefix, field.name());
@@ -687,6 +1306,21 @@ fn generate_accessors(
         let has_doc = quote! { #[doc = #has_doc_str] };
         let clear_doc_str = format!(" Clears the `{}` field.", field.name());
         let clear_doc = quote! { #[doc = #clear_doc_str] };
+        let raw_doc_str = format!(
+            " The number of the `{}` value, including ones the enum doesn't know.",
+            field.name()
+        );
+        let raw_doc = quote! { #[doc = #raw_doc_str] };
+        let raw_setter_doc_str = format!(
+            " Sets the `{}` field by number, including ones the enum doesn't know.",
+            field.name()
+        );
+        let raw_setter_doc = quote! { #[doc = #raw_setter_doc_str] };
+        let static_setter_doc_str = format!(
+            " Sets the `{}` field to static data, borrowing it instead of copying it into the arena.",
+            field.name()
+        );
+        let static_setter_doc = quote! { #[doc = #static_setter_doc_str] };
 
         // Handle oneof fields specially
         if is_in_oneof(field.as_ref()) {
@@ -696,9 +1330,9 @@ fn generate_accessors(
             let discriminant_word_idx = has_bits_words + oneof_idx;
             let field_number = field.number() as u32;
 
-            let setter_name = format_ident!("set_{}", field_name);
-            let has_name = format_ident!("has_{}", field_name);
-            let clear_name = format_ident!("clear_{}", field_name);
+            let setter_name = format_ident!("set_{}", accessor_name);
+            let has_name = format_ident!("has_{}", accessor_name);
+            let clear_name = format_ident!("clear_{}", accessor_name);
 
             // Generate has_<field> - check if discriminant matches this field
             methods.push(quote! {
@@ -710,10 +1344,10 @@ fn generate_accessors(
 
             match field.r#type().unwrap() {
                 Type::TYPE_STRING => {
-                    let optional_name = format_ident!("get_{}", field_name);
+                    let optional_name = format_ident!("get_{}", accessor_name);
                     methods.push(quote! {
                         #field_doc
-                        pub fn #field_name(&self) -> &str {
+                        pub fn #accessor_name(&self) -> &str {
                             if self.#has_name() {
                                 unsafe { (*self.#oneof_field_name.#field_name).as_str() }
                             } else {
@@ -731,10 +1365,11 @@ fn generate_accessors(
                         }
 
                         #field_doc
-                        pub fn #setter_name(&mut self, value: &str, arena: &mut protocrap::arena::Arena) -> Result<(), protocrap::Error<core::alloc::LayoutError>> {
+                        pub fn #setter_name(&mut self, value: &str, arena: &mut protocrap::generated_code_only::Arena) -> Result<(), protocrap::generated_code_only::Error<core::alloc::LayoutError>> {
                             if !self.#has_name() {
+                                self.#oneof_field_name = Default::default();
                                 self.metadata[#discriminant_word_idx] = #field_number;
-                                self.#oneof_field_name.#field_name = core::mem::ManuallyDrop::new(protocrap::containers::String::new());
+                                self.#oneof_field_name.#field_name = core::mem::ManuallyDrop::new(protocrap::generated_code_only::String::new());
                             }
                             unsafe { (*self.#oneof_field_name.#field_name).assign(value, arena) }
                         }
@@ -742,14 +1377,15 @@ fn generate_accessors(
                         #clear_doc
                         pub fn #clear_name(&mut self) {
                             self.metadata[#discriminant_word_idx] = 0;
+                            self.#oneof_field_name = Default::default();
                         }
                     });
                 }
                 Type::TYPE_BYTES => {
-                    let optional_name = format_ident!("get_{}", field_name);
+                    let optional_name = format_ident!("get_{}", accessor_name);
                     methods.push(quote! {
                         #field_doc
-                        pub fn #field_name(&self) -> &[u8] {
+                        pub fn #accessor_name(&self) -> &[u8] {
                             if self.#has_name() {
                                 unsafe { (*self.#oneof_field_name.#field_name).slice() }
                             } else {
@@ -767,10 +1403,11 @@ fn generate_accessors(
                         }
 
                         #field_doc
-                        pub fn #setter_name(&mut self, value: &[u8], arena: &mut protocrap::arena::Arena) -> Result<(), protocrap::Error<core::alloc::LayoutError>> {
+                        pub fn #setter_name(&mut self, value: &[u8], arena: &mut protocrap::generated_code_only::Arena) -> Result<(), protocrap::generated_code_only::Error<core::alloc::LayoutError>> {
                             if !self.#has_name() {
+                                self.#oneof_field_name = Default::default();
                                 self.metadata[#discriminant_word_idx] = #field_number;
-                                self.#oneof_field_name.#field_name = core::mem::ManuallyDrop::new(protocrap::containers::Bytes::new());
+                                self.#oneof_field_name.#field_name = core::mem::ManuallyDrop::new(protocrap::generated_code_only::Bytes::new());
                             }
                             unsafe { (*self.#oneof_field_name.#field_name).assign(value, arena) }
                         }
@@ -778,15 +1415,16 @@ fn generate_accessors(
                         #clear_doc
                         pub fn #clear_name(&mut self) {
                             self.metadata[#discriminant_word_idx] = 0;
+                            self.#oneof_field_name = Default::default();
                         }
                     });
                 }
                 Type::TYPE_MESSAGE | Type::TYPE_GROUP => {
-                    let msg_type = rust_type_tokens(field);
-                    let field_name_mut = format_ident!("{}_mut", field_name);
+                    let msg_type = rust_type_tokens(field, options);
+                    let field_name_mut = format_ident!("{}_mut", accessor_name);
                     methods.push(quote! {
                         #field_doc
-                        pub fn #field_name(&self) -> Option<&#msg_type::ProtoType> {
+                        pub fn #accessor_name(&self) -> Option<&#msg_type::ProtoType> {
                             if self.#has_name() {
                                 use core::ops::Deref;
                                 Some(unsafe { self.#oneof_field_name.#field_name.deref() })
@@ -796,10 +1434,11 @@ fn generate_accessors(
                         }
 
                         #field_doc
-                        pub fn #field_name_mut(&mut self, arena: &mut protocrap::arena::Arena) -> &mut #msg_type::ProtoType {
+                        pub fn #field_name_mut(&mut self, arena: &mut protocrap::generated_code_only::Arena) -> &mut #msg_type::ProtoType {
                             if !self.#has_name() {
+                                self.#oneof_field_name = Default::default();
                                 self.metadata[#discriminant_word_idx] = #field_number;
-                                self.#oneof_field_name.#field_name = core::mem::ManuallyDrop::new(protocrap::TypedMessage::<#msg_type::ProtoType>::new_in(arena).unwrap());
+                                self.#oneof_field_name.#field_name = core::mem::ManuallyDrop::new(protocrap::generated_code_only::TypedMessage::<#msg_type::ProtoType>::new_in(arena).unwrap());
                             }
                             use core::ops::DerefMut;
                             unsafe { self.#oneof_field_name.#field_name.deref_mut() }
@@ -808,14 +1447,17 @@ fn generate_accessors(
                         #clear_doc
                         pub fn #clear_name(&mut self) {
                             self.metadata[#discriminant_word_idx] = 0;
+                            self.#oneof_field_name = Default::default();
                         }
                     });
                 }
                 Type::TYPE_ENUM => {
-                    let enum_type = rust_type_tokens(field);
+                    let enum_type = rust_type_tokens(field, options);
+                    let raw_name = format_ident!("raw_{}", accessor_name);
+                    let raw_setter_name = format_ident!("set_raw_{}", accessor_name);
                     methods.push(quote! {
                         #field_doc
-                        pub fn #field_name(&self) -> Option<#enum_type> {
+                        pub fn #accessor_name(&self) -> Option<#enum_type> {
                             if self.#has_name() {
                                 #enum_type::from_i32(unsafe { *self.#oneof_field_name.#field_name })
                             } else {
@@ -823,24 +1465,42 @@ fn generate_accessors(
                             }
                         }
 
+                        #raw_doc
+                        pub fn #raw_name(&self) -> i32 {
+                            if self.#has_name() {
+                                unsafe { *self.#oneof_field_name.#field_name }
+                            } else {
+                                0
+                            }
+                        }
+
                         #field_doc
                         pub fn #setter_name(&mut self, value: #enum_type) {
-                            self.metadata[#discriminant_word_idx] = #field_number;
-                            self.#oneof_field_name.#field_name = core::mem::ManuallyDrop::new(value.to_i32());
+                            self.#raw_setter_name(value.to_i32());
+                        }
+
+                        #raw_setter_doc
+                        pub fn #raw_setter_name(&mut self, value: i32) {
+                            if !self.#has_name() {
+                                self.#oneof_field_name = Default::default();
+                                self.metadata[#discriminant_word_idx] = #field_number;
+                            }
+                            self.#oneof_field_name.#field_name = core::mem::ManuallyDrop::new(value);
                         }
 
                         #clear_doc
                         pub fn #clear_name(&mut self) {
                             self.metadata[#discriminant_word_idx] = 0;
+                            self.#oneof_field_name = Default::default();
                         }
                     });
                 }
                 _ => {
                     // Scalar types
-                    let return_type = rust_element_type_tokens(field);
+                    let return_type = rust_element_type_tokens(field, options);
                     methods.push(quote! {
                         #field_doc
-                        pub fn #field_name(&self) -> #return_type {
+                        pub fn #accessor_name(&self) -> #return_type {
                             if self.#has_name() {
                                 unsafe { *self.#oneof_field_name.#field_name }
                             } else {
@@ -850,13 +1510,17 @@ fn generate_accessors(
 
                         #field_doc
                         pub fn #setter_name(&mut self, value: #return_type) {
-                            self.metadata[#discriminant_word_idx] = #field_number;
+                            if !self.#has_name() {
+                                self.#oneof_field_name = Default::default();
+                                self.metadata[#discriminant_word_idx] = #field_number;
+                            }
                             self.#oneof_field_name.#field_name = core::mem::ManuallyDrop::new(value);
                         }
 
                         #clear_doc
                         pub fn #clear_name(&mut self) {
                             self.metadata[#discriminant_word_idx] = 0;
+                            self.#oneof_field_name = Default::default();
                         }
                     });
                 }
@@ -872,48 +1536,84 @@ fn generate_accessors(
                 || field.r#type() == Some(Type::TYPE_GROUP)
             {
                 // Repeated message field
-                let msg_type = rust_type_tokens(field);
-                let field_name_mut = format_ident!("{}_mut", field_name);
-                let add_field_name = format_ident!("add_{}", field_name);
+                let msg_type = rust_type_tokens(field, options);
+                let field_name_mut = format_ident!("{}_mut", accessor_name);
+                let add_field_name = format_ident!("add_{}", accessor_name);
                 methods.push(quote! {
                     #field_doc
-                    pub const fn #field_name(&self) -> &[protocrap::TypedMessage<#msg_type::ProtoType>] {
+                    pub const fn #accessor_name(&self) -> &[protocrap::generated_code_only::TypedMessage<#msg_type::ProtoType>] {
                         self.#field_name.slice()
                     }
 
                     #field_doc
-                    pub fn #field_name_mut(&mut self) -> &mut protocrap::containers::RepeatedField<protocrap::TypedMessage<#msg_type::ProtoType>> {
+                    pub fn #field_name_mut(&mut self) -> &mut protocrap::generated_code_only::RepeatedField<protocrap::generated_code_only::TypedMessage<#msg_type::ProtoType>> {
                         &mut self.#field_name
                     }
 
                     #field_doc
-                    pub fn #add_field_name(&mut self, arena: &mut protocrap::arena::Arena) -> Result<&mut #msg_type::ProtoType, protocrap::Error<core::alloc::LayoutError>> {
-                        let msg = protocrap::TypedMessage::<#msg_type::ProtoType>::new_in(arena)?;
+                    pub fn #add_field_name(&mut self, arena: &mut protocrap::generated_code_only::Arena) -> Result<&mut #msg_type::ProtoType, protocrap::generated_code_only::Error<core::alloc::LayoutError>> {
+                        let msg = protocrap::generated_code_only::TypedMessage::<#msg_type::ProtoType>::new_in(arena)?;
                         let tp = self.#field_name.push(msg, arena)?;
                         Ok(tp.as_mut())
                     }
                 });
+                if let Some(entry) = map_entry(message, field) {
+                    methods.push(generate_map_iter_method(field, entry, &accessor_name, options));
+                    if options.maps {
+                        methods.push(generate_map_method(field, entry, &accessor_name, options));
+                    }
+                }
                 continue;
             }
-            let element_type = rust_element_type_tokens(field);
-            let field_name_mut = format_ident!("{}_mut", field_name);
+            let element_type = rust_element_type_tokens(field, options);
+            let field_name_mut = format_ident!("{}_mut", accessor_name);
+            // Enums get typed values, the raw numbers keeping the ones the
+            // enum doesn't know
+            let (slice_name, slice_doc) = if field.r#type() == Some(Type::TYPE_ENUM) {
+                let enum_type = rust_type_tokens(field, options);
+                let raw_name = format_ident!("raw_{}", accessor_name);
+                methods.push(quote! {
+                    #field_doc
+                    pub fn #accessor_name(&self) -> impl ExactSizeIterator<Item = Option<#enum_type>> + DoubleEndedIterator + Clone + '_ {
+                        self.#field_name.slice().iter().map(|&value| #enum_type::from_i32(value))
+                    }
+                });
+                let raw_doc_str = format!(
+                    " The numbers of the `{}` values, including ones the enum doesn't know.",
+                    field.name()
+                );
+                (raw_name, quote! { #[doc = #raw_doc_str] })
+            } else {
+                (accessor_name.clone(), field_doc.clone())
+            };
             methods.push(quote! {
-                #field_doc
-                pub const fn #field_name(&self) -> &[#element_type] {
+                #slice_doc
+                pub const fn #slice_name(&self) -> &[#element_type] {
                     self.#field_name.slice()
                 }
 
                 #field_doc
-                pub fn #field_name_mut(&mut self) -> &mut protocrap::containers::RepeatedField<#element_type> {
+                pub fn #field_name_mut(&mut self) -> &mut protocrap::generated_code_only::RepeatedField<#element_type> {
                     &mut self.#field_name
                 }
             });
+            if !matches!(field.r#type(), Some(Type::TYPE_STRING | Type::TYPE_BYTES)) {
+                // Scalars are Copy and can be replaced in bulk
+                let setter_name = format_ident!("set_{}", accessor_name);
+                methods.push(quote! {
+                    #field_doc
+                    pub fn #setter_name(&mut self, values: &[#element_type], arena: &mut protocrap::generated_code_only::Arena) -> Result<(), protocrap::generated_code_only::Error<core::alloc::LayoutError>> {
+                        self.#field_name.copy_from_slice(values, arena)
+                    }
+                });
+            }
         } else {
-            let setter_name = format_ident!("set_{}", field_name);
-            let optional_setter_name = format_ident!("set_optional_{}", field_name);
-            let optional_name = format_ident!("get_{}", field_name);
-            let clear_name = format_ident!("clear_{}", field_name);
-            let has_name = format_ident!("has_{}", field_name);
+            let setter_name = format_ident!("set_{}", accessor_name);
+            let optional_setter_name = format_ident!("set_optional_{}", accessor_name);
+            let static_setter_name = format_ident!("set_{}_static", accessor_name);
+            let optional_name = format_ident!("get_{}", accessor_name);
+            let clear_name = format_ident!("clear_{}", accessor_name);
+            let has_name = format_ident!("has_{}", accessor_name);
             let has_bit = if let Some(has_bit) = has_bit_map.get(&field.number()).cloned() {
                 methods.push(quote! {
                     #has_doc
@@ -928,7 +1628,7 @@ fn generate_accessors(
             match field.r#type().unwrap() {
                 Type::TYPE_STRING => {
                     // Parse default value if present
-                    let default_value = parse_primitive_default(field);
+                    let default_value = parse_primitive_default(field, options);
 
                     let getter_impl =
                         if has_bit_map.contains_key(&field.number()) && default_value.is_some() {
@@ -946,7 +1646,7 @@ fn generate_accessors(
 
                     methods.push(quote! {
                         #field_doc
-                        pub const fn #field_name(&self) -> &str {
+                        pub const fn #accessor_name(&self) -> &str {
                             #getter_impl
                         }
 
@@ -960,12 +1660,18 @@ fn generate_accessors(
                         }
 
                         #field_doc
-                        pub fn #setter_name(&mut self, value: &str, arena: &mut protocrap::arena::Arena) -> Result<(), protocrap::Error<core::alloc::LayoutError>> {
+                        pub fn #setter_name(&mut self, value: &str, arena: &mut protocrap::generated_code_only::Arena) -> Result<(), protocrap::generated_code_only::Error<core::alloc::LayoutError>> {
                             protocrap::generated_code_only::as_object_mut(self).set_has_bit(#has_bit);
                             self.#field_name.assign(value, arena)
                         }
 
-                        pub fn #optional_setter_name(&mut self, value: Option<&str>, arena: &mut protocrap::arena::Arena) -> Result<(), protocrap::Error<core::alloc::LayoutError>> {
+                        #static_setter_doc
+                        pub fn #static_setter_name(&mut self, value: &'static str) {
+                            protocrap::generated_code_only::as_object_mut(self).set_has_bit(#has_bit);
+                            self.#field_name = protocrap::generated_code_only::String::from_static(value);
+                        }
+
+                        pub fn #optional_setter_name(&mut self, value: Option<&str>, arena: &mut protocrap::generated_code_only::Arena) -> Result<(), protocrap::generated_code_only::Error<core::alloc::LayoutError>> {
                             match value {
                                 Some(v) => self.#setter_name(v, arena)?,
                                 None => self.#clear_name(),
@@ -981,7 +1687,7 @@ fn generate_accessors(
                     });
                 }
                 Type::TYPE_BYTES => {
-                    let default_value = parse_primitive_default(field);
+                    let default_value = parse_primitive_default(field, options);
                     let getter_impl = if let Some(default_tokens) = default_value {
                         quote! {
                             if self.#has_name() {
@@ -995,7 +1701,7 @@ fn generate_accessors(
                     };
                     methods.push(quote! {
                         #field_doc
-                        pub const fn #field_name(&self) -> &[u8] {
+                        pub const fn #accessor_name(&self) -> &[u8] {
                             #getter_impl
                         }
 
@@ -1009,12 +1715,18 @@ fn generate_accessors(
                         }
 
                         #field_doc
-                        pub fn #setter_name(&mut self, value: &[u8], arena: &mut protocrap::arena::Arena) -> Result<(), protocrap::Error<core::alloc::LayoutError>> {
+                        pub fn #setter_name(&mut self, value: &[u8], arena: &mut protocrap::generated_code_only::Arena) -> Result<(), protocrap::generated_code_only::Error<core::alloc::LayoutError>> {
                             protocrap::generated_code_only::as_object_mut(self).set_has_bit(#has_bit);
                             self.#field_name.assign(value, arena)
                         }
 
-                        pub fn #optional_setter_name(&mut self, value: Option<&[u8]>, arena: &mut protocrap::arena::Arena) -> Result<(), protocrap::Error<core::alloc::LayoutError>> {
+                        #static_setter_doc
+                        pub fn #static_setter_name(&mut self, value: &'static [u8]) {
+                            protocrap::generated_code_only::as_object_mut(self).set_has_bit(#has_bit);
+                            self.#field_name = protocrap::generated_code_only::Bytes::from_static(value);
+                        }
+
+                        pub fn #optional_setter_name(&mut self, value: Option<&[u8]>, arena: &mut protocrap::generated_code_only::Arena) -> Result<(), protocrap::generated_code_only::Error<core::alloc::LayoutError>> {
                             match value {
                                 Some(v) => self.#setter_name(v, arena)?,
                                 None => self.#clear_name(),
@@ -1030,8 +1742,8 @@ fn generate_accessors(
                     });
                 }
                 Type::TYPE_MESSAGE | Type::TYPE_GROUP => {
-                    let msg_type = rust_type_tokens(field);
-                    let field_name_mut = format_ident!("{}_mut", field_name);
+                    let msg_type = rust_type_tokens(field, options);
+                    let field_name_mut = format_ident!("{}_mut", accessor_name);
                     methods.push(quote! {
                         #has_doc
                         pub const fn #has_name(&self) -> bool {
@@ -1039,12 +1751,12 @@ fn generate_accessors(
                         }
 
                         #field_doc
-                        pub const fn #field_name(&self) -> Option<&#msg_type::ProtoType> {
+                        pub const fn #accessor_name(&self) -> Option<&#msg_type::ProtoType> {
                             self.#field_name.get()
                         }
 
                         #field_doc
-                        pub fn #field_name_mut(&mut self, arena: &mut protocrap::arena::Arena) -> &mut #msg_type::ProtoType {
+                        pub fn #field_name_mut(&mut self, arena: &mut protocrap::generated_code_only::Arena) -> &mut #msg_type::ProtoType {
                             self.#field_name.get_or_init(arena).unwrap()
                         }
 
@@ -1055,9 +1767,21 @@ fn generate_accessors(
                     });
                 }
                 Type::TYPE_ENUM => {
-                    let enum_type = rust_type_tokens(field);
-                    let default_value = parse_primitive_default(field);
+                    let enum_type = rust_type_tokens(field, options);
+                    let default_value = parse_primitive_default(field, options);
 
+                    let raw_getter_impl = if default_value.is_some() {
+                        let variant = format_ident!("{}", sanitize_ident(field.default_value()));
+                        quote! {
+                            if self.#has_name() {
+                                self.#field_name
+                            } else {
+                                #enum_type::#variant.to_i32()
+                            }
+                        }
+                    } else {
+                        quote! { self.#field_name }
+                    };
                     let getter_impl = if let Some(default_tokens) = default_value {
                         quote! {
                             if self.#has_name() {
@@ -1070,18 +1794,32 @@ fn generate_accessors(
                         quote! { #enum_type::from_i32(self.#field_name) }
                     };
 
+                    let raw_name = format_ident!("raw_{}", accessor_name);
+                    let raw_setter_name = format_ident!("set_raw_{}", accessor_name);
+
                     methods.push(quote! {
                         #field_doc
-                        pub const fn #field_name(&self) -> Option<#enum_type> {
+                        pub const fn #accessor_name(&self) -> Option<#enum_type> {
                             #getter_impl
                         }
 
+                        #raw_doc
+                        pub const fn #raw_name(&self) -> i32 {
+                            #raw_getter_impl
+                        }
+
                         #field_doc
                         pub fn #setter_name(&mut self, value: #enum_type) {
                             protocrap::generated_code_only::as_object_mut(self).set_has_bit(#has_bit);
                             self.#field_name = value.to_i32();
                         }
 
+                        #raw_setter_doc
+                        pub fn #raw_setter_name(&mut self, value: i32) {
+                            protocrap::generated_code_only::as_object_mut(self).set_has_bit(#has_bit);
+                            self.#field_name = value;
+                        }
+
                         pub fn #optional_setter_name(&mut self, value: Option<#enum_type>) {
                             match value {
                                 Some(v) => self.#setter_name(v),
@@ -1098,10 +1836,10 @@ fn generate_accessors(
                 }
                 _ => {
                     // Scalar types
-                    let return_type = rust_element_type_tokens(field);
+                    let return_type = rust_element_type_tokens(field, options);
 
                     // Parse default value if present
-                    let default_value = parse_primitive_default(field);
+                    let default_value = parse_primitive_default(field, options);
 
                     let getter_impl = if default_value.is_some() {
                         let default_tokens = default_value.unwrap();
@@ -1118,7 +1856,7 @@ fn generate_accessors(
 
                     methods.push(quote! {
                         #field_doc
-                        pub const fn #field_name(&self) -> #return_type {
+                        pub const fn #accessor_name(&self) -> #return_type {
                             #getter_impl
                         }
 
@@ -1191,5 +1929,16 @@ fn generate_protobuf_impl() -> TokenStream {
                 &TABLE.table
             }
         }
+
+        /// Decodes a message whose data is allocated in the arena.
+        impl core::convert::TryFrom<(&[u8], &mut protocrap::generated_code_only::Arena<'_>)> for ProtoType {
+            type Error = protocrap::generated_code_only::Error;
+
+            fn try_from(
+                (bytes, arena): (&[u8], &mut protocrap::generated_code_only::Arena<'_>),
+            ) -> Result<Self, Self::Error> {
+                protocrap::generated_code_only::decode_message(bytes, arena)
+            }
+        }
     }
 }
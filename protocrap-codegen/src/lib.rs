@@ -0,0 +1,346 @@
+//! Code generator for [protocrap](https://docs.rs/protocrap).
+//!
+//! Turns a `FileDescriptorSet` (as produced by `protoc --descriptor_set_out
+//! --include_imports`) into Rust source. The generated code only refers to the
+//! runtime through `protocrap::generated_code_only` and the public descriptor
+//! types. Use the same version of this crate as of `protocrap`; the output keeps
+//! compiling against later semver-compatible runtimes.
+//!
+//! Most users run the `protocrap-codegen` binary or call it from a build script
+//! through `protocrap-build`.
+
+use allocator_api2::alloc::Global;
+use anyhow::Result;
+use protocrap::ProtobufMut;
+use protocrap::google::protobuf::DescriptorProto::ProtoType as DescriptorProto;
+use protocrap::google::protobuf::EnumDescriptorProto::ProtoType as EnumDescriptorProto;
+use protocrap::google::protobuf::FieldDescriptorProto::ProtoType as FieldDescriptorProto;
+use protocrap::google::protobuf::FileDescriptorProto::ProtoType as FileDescriptorProto;
+use protocrap::google::protobuf::FileDescriptorSet::ProtoType as FileDescriptorSet;
+
+mod c_header;
+pub mod comments;
+mod custom_options;
+mod generator;
+mod max_size;
+mod names;
+mod static_gen;
+mod tables;
+
+/// Options controlling code generation.
+#[derive(Default, Clone)]
+pub struct Options {
+    /// Proto package -> Rust module path of packages that are not generated.
+    extern_paths: Vec<(String, syn::Path)>,
+    /// Generate RPC traits and stubs for services.
+    services: bool,
+    /// Export a C function returning the table of every message.
+    ffi: bool,
+    /// Implement `serde::Serialize` and `serde::Deserialize` on every message.
+    serde: bool,
+    /// Generate `<field>_map()` methods building a `ProtoMap` of map fields.
+    maps: bool,
+    /// Leave descriptors out of the generated code.
+    strip_descriptors: bool,
+    /// Values of the closed enums in the file set being generated, sorted and
+    /// keyed by fully qualified name with leading dot. Filled in by the generator.
+    closed_enums: std::collections::HashMap<String, Vec<i32>>,
+    /// Upper bounds on the encoded size of bounded messages, keyed by fully
+    /// qualified name without leading dot. Filled in by the generator.
+    max_encoded_sizes: std::collections::HashMap<String, usize>,
+    /// The `protocrap.*` options set in the descriptors. Filled in from the
+    /// descriptor bytes.
+    custom: custom_options::CustomOptions,
+}
+
+impl Options {
+    /// Map a proto package onto an existing Rust module instead of generating it.
+    ///
+    /// Files in `proto_package` (and its sub-packages) are skipped and references to
+    /// their types resolve against `rust_path`. For example, mapping `google.protobuf`
+    /// to `protocrap::google::protobuf` reuses the built-in descriptor types when
+    /// user protos import `descriptor.proto`.
+    pub fn extern_path(mut self, proto_package: &str, rust_path: &str) -> Result<Self> {
+        let path = syn::parse_str::<syn::Path>(rust_path)
+            .map_err(|e| anyhow::anyhow!("Invalid Rust path '{}': {}", rust_path, e))?;
+        let package = proto_package.trim_start_matches('.').to_string();
+        self.extern_paths.push((package, path));
+        Ok(self)
+    }
+
+    /// Generate a `Handler` trait, `Server` adapter and `Client` stub for every
+    /// proto `service`, built on `protocrap::rpc` (requires the `std` feature).
+    /// Only unary methods are generated; streaming methods are skipped.
+    pub fn services(mut self, enabled: bool) -> Self {
+        self.services = enabled;
+        self
+    }
+
+    /// Export an `extern "C" fn pc_table_<full name>()` returning the table of
+    /// every message, for use with `protocrap::ffi` (requires the `ffi` feature).
+    /// [`generate_c_header`] writes the matching declarations.
+    pub fn ffi(mut self, enabled: bool) -> Self {
+        self.ffi = enabled;
+        self
+    }
+
+    /// Implement `serde::Serialize` and `serde::Deserialize` directly on every
+    /// message (requires the `serde_support` feature), so messages work with
+    /// serde-based frameworks without wrappers. `Deserialize` allocates from
+    /// the arena installed with `protocrap::serde::with_arena`, and fails
+    /// outside of one.
+    pub fn serde(mut self, enabled: bool) -> Self {
+        self.serde = enabled;
+        self
+    }
+
+    /// Generate a `<field>_map(arena)` method for every map field, returning a
+    /// `protocrap::containers::ProtoMap` of the entries for constant time
+    /// lookups. Map fields are still stored as repeated entries; build the map
+    /// once after decoding when looking up many keys.
+    pub fn maps(mut self, enabled: bool) -> Self {
+        self.maps = enabled;
+        self
+    }
+
+    /// Leave the file and message descriptors out of the generated code, for
+    /// builds where flash is tight and only encoding and decoding are needed.
+    /// Tables then have no descriptor, and reflection, JSON, text format and
+    /// `Debug` field listings aren't available for the generated messages;
+    /// reflection panics on them. No `file_descriptor()` or
+    /// `descriptor_proto()` functions are generated.
+    pub fn strip_descriptors(mut self, enabled: bool) -> Self {
+        self.strip_descriptors = enabled;
+        self
+    }
+
+    /// Returns the mapped path for the longest extern package containing `name`
+    /// (a fully qualified proto name without leading dot), along with the rest of
+    /// the name relative to that package.
+    fn resolve_extern_path<'a>(&self, name: &'a str) -> Option<(&syn::Path, &'a str)> {
+        self.extern_paths
+            .iter()
+            .filter_map(|(package, path)| {
+                let rest = name.strip_prefix(package.as_str())?.strip_prefix('.')?;
+                Some((package.len(), path, rest))
+            })
+            .max_by_key(|(len, _, _)| *len)
+            .map(|(_, path, rest)| (path, rest))
+    }
+
+    /// Record the closed enums of `file_set`, so tables of fields using them
+    /// can carry their values.
+    fn with_closed_enums(&self, file_set: &FileDescriptorSet) -> Self {
+        fn add_enums<'a>(
+            closed_enums: &mut std::collections::HashMap<String, Vec<i32>>,
+            file: &FileDescriptorProto,
+            scope: &str,
+            enums: impl IntoIterator<Item = &'a EnumDescriptorProto>,
+        ) {
+            for enum_type in enums {
+                if !protocrap::reflection::is_closed_enum(enum_type, file) {
+                    continue;
+                }
+                let mut values: Vec<i32> = enum_type.value().iter().map(|v| v.number()).collect();
+                values.sort_unstable();
+                values.dedup();
+                closed_enums.insert(format!("{}.{}", scope, enum_type.name()), values);
+            }
+        }
+        fn add_nested(
+            closed_enums: &mut std::collections::HashMap<String, Vec<i32>>,
+            file: &FileDescriptorProto,
+            scope: &str,
+            message: &DescriptorProto,
+        ) {
+            let scope = format!("{}.{}", scope, message.name());
+            add_enums(closed_enums, file, &scope, message.enum_type().iter().map(|e| &**e));
+            for nested in message.nested_type() {
+                add_nested(closed_enums, file, &scope, nested);
+            }
+        }
+
+        let mut options = self.clone();
+        for file in file_set.file() {
+            let scope = if file.package().is_empty() {
+                String::new()
+            } else {
+                format!(".{}", file.package())
+            };
+            add_enums(&mut options.closed_enums, file, &scope, file.enum_type().iter().map(|e| &**e));
+            for message in file.message_type() {
+                add_nested(&mut options.closed_enums, file, &scope, message);
+            }
+        }
+        options
+    }
+
+    /// Record the custom options set in the serialized `FileDescriptorSet`.
+    fn with_custom_options(&self, descriptor_bytes: &[u8]) -> Result<Self> {
+        let mut options = self.clone();
+        options.custom = custom_options::CustomOptions::from_file_set_bytes(descriptor_bytes)?;
+        Ok(options)
+    }
+
+    /// Record the encoded size bounds of the messages in `file_set`.
+    fn with_max_encoded_sizes(mut self, file_set: &FileDescriptorSet) -> Self {
+        self.max_encoded_sizes = max_size::max_encoded_sizes(file_set);
+        self
+    }
+
+    /// Upper bound on the encoded size of `full_name`, if it is bounded.
+    fn max_encoded_size(&self, full_name: &str) -> Option<usize> {
+        self.max_encoded_sizes.get(full_name).copied()
+    }
+
+    /// Values of the enum used by `field`, if it is a closed enum.
+    fn closed_enum_values(&self, field: &FieldDescriptorProto) -> Option<&[i32]> {
+        if field.r#type() != Some(protocrap::google::protobuf::FieldDescriptorProto::Type::TYPE_ENUM) {
+            return None;
+        }
+        self.closed_enums.get(field.type_name()).map(|v| v.as_slice())
+    }
+
+    fn is_extern_package(&self, package: &str) -> bool {
+        self.extern_paths.iter().any(|(extern_package, _)| {
+            package == extern_package
+                || package
+                    .strip_prefix(extern_package.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    }
+}
+
+/// Generate Rust code from protobuf descriptor bytes (FileDescriptorSet binary format)
+///
+/// The output only depends on the descriptors, not on the order of the files
+/// in the set: packages and the files within them are emitted in name order,
+/// everything inside a file in declaration order.
+pub fn generate(descriptor_bytes: &[u8]) -> Result<String> {
+    generate_with_options(descriptor_bytes, &Options::default())
+}
+
+/// Like [`generate`], with explicit [`Options`].
+pub fn generate_with_options(descriptor_bytes: &[u8], options: &Options) -> Result<String> {
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    let mut file_set = FileDescriptorSet::default();
+    if !file_set.decode_flat::<100>(&mut arena, descriptor_bytes) {
+        return Err(anyhow::anyhow!("Failed to decode file descriptor set"));
+    }
+
+    let options = options.with_custom_options(descriptor_bytes)?;
+    let tokens = generator::generate_file_set(&file_set, &options)?;
+
+    let syntax_tree = syn::parse2(tokens)?;
+    Ok(prettyplease::unparse(&syntax_tree))
+}
+
+/// Generate a C header declaring the `protocrap::ffi` entry points and the table
+/// accessors exported by code generated with [`Options::ffi`].
+pub fn generate_c_header(descriptor_bytes: &[u8], options: &Options) -> Result<String> {
+    let mut arena = protocrap::arena::Arena::new(&Global);
+    let mut file_set = FileDescriptorSet::default();
+    if !file_set.decode_flat::<100>(&mut arena, descriptor_bytes) {
+        return Err(anyhow::anyhow!("Failed to decode file descriptor set"));
+    }
+    Ok(c_header::generate_c_header(
+        file_set.file().iter().map(|f| &**f),
+        &options.with_custom_options(descriptor_bytes)?,
+    ))
+}
+
+/// Generate a const initializer expression for embedding protobuf data.
+/// Supports both binary (.pb) and JSON (.json) input formats.
+/// Returns just the initializer expression suitable for `include!()`.
+/// `crate_path` specifies the module path prefix (e.g., "protocrap" or "" for local).
+pub fn generate_embed(
+    descriptor_bytes: &[u8],
+    data: &[u8],
+    type_name: &str,
+    is_json: bool,
+    crate_path: &str,
+) -> Result<String> {
+    let pool =
+        protocrap::descriptor_pool::DescriptorPool::from_file_descriptor_set_bytes(descriptor_bytes)?;
+    let mut arena = protocrap::arena::Arena::new(&Global);
+
+    // Decode the data using the pool
+    let mut msg = pool.create_message(type_name, &mut arena)?;
+    if is_json {
+        let json_str = std::str::from_utf8(data)?;
+        let mut deserializer = serde_json::Deserializer::from_str(json_str);
+        msg.serde_deserialize(&mut arena, &mut deserializer)
+            .map_err(|e| anyhow::anyhow!("JSON deserialization failed: {}", e))?;
+    } else if !msg.decode_flat::<100>(&mut arena, data) {
+        return Err(anyhow::anyhow!("Failed to decode protobuf data"));
+    }
+
+    // Generate the initializer expression
+    let tokens = static_gen::generate_static_dynamic(&msg.as_ref(), type_name, crate_path)?;
+
+    // Parse as expression (not file) and format
+    let expr: syn::Expr = syn::parse2(tokens)?;
+    let wrapped = syn::parse_quote! { fn __fmt() { #expr } };
+    let formatted = prettyplease::unparse(&wrapped);
+
+    // Extract just the expression (skip "fn __fmt() {" and trailing "}")
+    let start = formatted.find('{').unwrap() + 1;
+    let end = formatted.rfind('}').unwrap();
+    Ok(formatted[start..end].trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocrap::ProtobufRef;
+
+    #[test]
+    fn output_independent_of_file_order() {
+        let mut arena = protocrap::arena::Arena::new(&Global);
+        let mut extra = FileDescriptorProto::default();
+        extra.set_name("google/protobuf/aaa.proto", &mut arena).unwrap();
+        extra.set_package("google.protobuf", &mut arena).unwrap();
+        extra.add_message_type(&mut arena).unwrap().set_name("Extra", &mut arena).unwrap();
+        let extra = extra.encode_vec::<32>().unwrap();
+        let descriptor = FileDescriptorProto::file_descriptor().encode_vec::<32>().unwrap();
+
+        let file_set = |files: [&[u8]; 2]| {
+            let mut arena = protocrap::arena::Arena::new(&Global);
+            let mut file_set = FileDescriptorSet::default();
+            for file in files {
+                assert!(file_set.add_file(&mut arena).unwrap().decode_flat::<32>(&mut arena, file));
+            }
+            file_set.encode_vec::<32>().unwrap()
+        };
+        let forward = file_set([&descriptor, &extra]);
+        let backward = file_set([&extra, &descriptor]);
+
+        let code = generate(&forward).unwrap();
+        assert_eq!(code, generate(&forward).unwrap());
+        assert_eq!(code, generate(&backward).unwrap());
+        assert!(code.find("pub mod Extra").unwrap() < code.find("pub mod FileDescriptorProto").unwrap());
+        let options = Options::default().ffi(true);
+        assert_eq!(
+            generate_c_header(&forward, &options).unwrap(),
+            generate_c_header(&backward, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn strips_descriptors() {
+        let mut arena = protocrap::arena::Arena::new(&Global);
+        let mut file_set = FileDescriptorSet::default();
+        let descriptor = FileDescriptorProto::file_descriptor().encode_vec::<32>().unwrap();
+        assert!(file_set.add_file(&mut arena).unwrap().decode_flat::<32>(&mut arena, &descriptor));
+        let file_set = file_set.encode_vec::<32>().unwrap();
+
+        let full = generate(&file_set).unwrap();
+        let stripped = generate_with_options(&file_set, &Options::default().strip_descriptors(true)).unwrap();
+        assert!(full.contains("FILE_DESCRIPTOR_PROTO"));
+        assert!(!stripped.contains("FILE_DESCRIPTOR_PROTO"));
+        assert!(!stripped.contains("fn descriptor_proto"));
+        assert!(full.contains("descriptor: Some(ProtoType::descriptor_proto()),"));
+        assert!(stripped.contains("descriptor: None,"));
+        assert!(stripped.len() < full.len());
+    }
+}
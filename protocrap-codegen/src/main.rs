@@ -0,0 +1,253 @@
+use protocrap_codegen as codegen;
+
+use std::fs;
+use std::io::{self, Read, Write};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<_> = std::env::args().collect();
+
+    if args.len() < 2 {
+        print_usage(&args[0]);
+        return Ok(());
+    }
+
+    // Check for --embed mode
+    let embed_idx = args.iter().position(|a| a == "--embed");
+    if let Some(idx) = embed_idx {
+        return run_embed_mode(&args, idx);
+    }
+
+    // Check for --canonicalize mode
+    let canonicalize_idx = args.iter().position(|a| a == "--canonicalize");
+    if let Some(idx) = canonicalize_idx {
+        return run_canonicalize_mode(&args, idx);
+    }
+
+    // Normal codegen mode
+    run_codegen_mode(&args)
+}
+
+fn run_codegen_mode(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    // Parse: protocrap <descriptor.pb> [output.rs] [--services] [--ffi] [--serde] [--maps] [--strip-descriptors]
+    //        [--c-header <output.h>]
+    //        [--extern-path <package>=<rust path>]...
+    let mut options = codegen::Options::default();
+    let mut positional = Vec::new();
+    let mut c_header_path = None;
+    let mut iter = args[1..].iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--services" {
+            options = options.services(true);
+        } else if arg == "--ffi" {
+            options = options.ffi(true);
+        } else if arg == "--serde" {
+            options = options.serde(true);
+        } else if arg == "--maps" {
+            options = options.maps(true);
+        } else if arg == "--strip-descriptors" {
+            options = options.strip_descriptors(true);
+        } else if arg == "--c-header" {
+            c_header_path = Some(iter.next().ok_or("--c-header requires <output.h> argument")?);
+        } else if arg == "--extern-path" {
+            let mapping = iter
+                .next()
+                .ok_or("--extern-path requires <package>=<rust path> argument")?;
+            let (package, rust_path) = mapping.split_once('=').ok_or_else(|| {
+                format!(
+                    "Invalid --extern-path argument '{}': expected <package>=<rust path>",
+                    mapping
+                )
+            })?;
+            options = options.extern_path(package, rust_path)?;
+        } else {
+            positional.push(arg);
+        }
+    }
+    let Some(descriptor_path) = positional.first() else {
+        return Err("missing descriptor argument".into());
+    };
+
+    // Read descriptor bytes
+    let descriptor_bytes = if *descriptor_path == "-" {
+        let mut buf = Vec::new();
+        io::stdin().read_to_end(&mut buf)?;
+        buf
+    } else {
+        fs::read(descriptor_path)?
+    };
+
+    eprintln!("Read descriptor ({} bytes)", descriptor_bytes.len());
+
+    // Generate code
+    let code = codegen::generate_with_options(&descriptor_bytes, &options)?;
+
+    // Write output
+    if let Some(output_path) = positional.get(1) {
+        fs::write(output_path, &code)?;
+        eprintln!("Generated {}", output_path);
+    } else {
+        io::stdout().write_all(code.as_bytes())?;
+    }
+
+    if let Some(header_path) = c_header_path {
+        fs::write(header_path, codegen::generate_c_header(&descriptor_bytes, &options)?)?;
+        eprintln!("Generated {}", header_path);
+    }
+
+    Ok(())
+}
+
+fn run_embed_mode(args: &[String], embed_idx: usize) -> Result<(), Box<dyn std::error::Error>> {
+    // Parse: protocrap <descriptor.pb> --embed <data.pb>:<type_name> [-o <output.pc.rs>] [--crate-path <path>]
+    if args.len() < embed_idx + 2 {
+        eprintln!("Error: --embed requires <data.pb>:<type_name> argument");
+        return Err("missing embed argument".into());
+    }
+
+    let descriptor_path = &args[1];
+    let embed_arg = &args[embed_idx + 1];
+
+    // Parse data.pb:type_name
+    let (data_path, type_name) = embed_arg.split_once(':').ok_or_else(|| {
+        format!(
+            "Invalid --embed argument '{}': expected <data.pb>:<type_name>",
+            embed_arg
+        )
+    })?;
+
+    // Find output file (-o flag)
+    let output_path = args
+        .iter()
+        .position(|a| a == "-o")
+        .and_then(|i| args.get(i + 1));
+
+    // Find crate path (--crate-path flag, defaults to "protocrap")
+    let crate_path = args
+        .iter()
+        .position(|a| a == "--crate-path")
+        .and_then(|i| args.get(i + 1).map(|s| s.as_str()))
+        .unwrap_or("protocrap");
+
+    // Detect JSON by file extension
+    let is_json = data_path.ends_with(".json");
+
+    // Read files
+    let descriptor_bytes = fs::read(descriptor_path)?;
+    let data_bytes = fs::read(data_path)?;
+
+    eprintln!(
+        "Embedding {} ({} bytes) as {} ({})",
+        data_path,
+        data_bytes.len(),
+        type_name,
+        if is_json { "JSON" } else { "binary" }
+    );
+
+    // Generate initializer
+    let code = codegen::generate_embed(
+        &descriptor_bytes,
+        &data_bytes,
+        type_name,
+        is_json,
+        crate_path,
+    )?;
+
+    // Write output
+    if let Some(path) = output_path {
+        fs::write(path, &code)?;
+        eprintln!("Generated {}", path);
+    } else {
+        io::stdout().write_all(code.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn run_canonicalize_mode(
+    args: &[String],
+    canonicalize_idx: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Parse: protocrap <descriptor.pb> --canonicalize <type_name> [<data.pb>] [-o <output.pb>]
+    let type_name = args
+        .get(canonicalize_idx + 1)
+        .ok_or("--canonicalize requires <type_name> argument")?;
+    let descriptor_path = &args[1];
+    let data_path = args.get(canonicalize_idx + 2).filter(|a| *a != "-o");
+    let output_path = args
+        .iter()
+        .position(|a| a == "-o")
+        .and_then(|i| args.get(i + 1));
+
+    let descriptor_bytes = fs::read(descriptor_path)?;
+    let data_bytes = match data_path {
+        Some(path) if path != "-" => fs::read(path)?,
+        _ => {
+            let mut buf = Vec::new();
+            io::stdin().read_to_end(&mut buf)?;
+            buf
+        }
+    };
+
+    let pool = protocrap::descriptor_pool::DescriptorPool::from_file_descriptor_set_bytes(
+        &descriptor_bytes,
+    )?;
+    let table = pool
+        .get_table(type_name.trim_start_matches('.'))
+        .ok_or_else(|| format!("Unknown message type '{}'", type_name))?;
+    let canonical = protocrap::canonical::canonicalize(&data_bytes, table)?;
+
+    if let Some(path) = output_path {
+        fs::write(path, &canonical)?;
+        eprintln!("Generated {}", path);
+    } else {
+        io::stdout().write_all(&canonical)?;
+    }
+
+    Ok(())
+}
+
+fn print_usage(program: &str) {
+    eprintln!("Protocrap Code Generator");
+    eprintln!();
+    eprintln!("USAGE:");
+    eprintln!(
+        "  {program} <descriptor.pb> [output.rs] [--services] [--ffi] [--serde] [--maps] [--strip-descriptors]"
+    );
+    eprintln!("      [--c-header <output.h>]");
+    eprintln!("      [--extern-path <package>=<rust path>]...");
+    eprintln!("  {program} <descriptor.pb> --embed <data.pb>:<type> [-o output.pc.rs]");
+    eprintln!("  {program} <descriptor.pb> --canonicalize <type> [data.pb] [-o output.pb]");
+    eprintln!();
+    eprintln!("MODES:");
+    eprintln!("  codegen   Generate Rust structs from proto descriptors (default)");
+    eprintln!("  --embed   Generate const initializer from binary proto data");
+    eprintln!("  --canonicalize");
+    eprintln!("            Re-encode binary proto data in canonical form (sorted fields and");
+    eprintln!("            map keys), e.g. for hashing. Fails on fields unknown to the schema");
+    eprintln!();
+    eprintln!("ARGUMENTS:");
+    eprintln!("  descriptor.pb   FileDescriptorSet from protoc");
+    eprintln!("  output.rs       Output Rust file (default: stdout)");
+    eprintln!();
+    eprintln!("OPTIONS:");
+    eprintln!("  --services      Generate RPC handler traits and client stubs for services");
+    eprintln!("  --ffi           Export a C function returning the table of every message");
+    eprintln!("  --serde         Implement serde::Serialize and serde::Deserialize on every message");
+    eprintln!("  --maps          Generate <field>_map() methods indexing map fields by key");
+    eprintln!("  --strip-descriptors");
+    eprintln!("                  Leave descriptors out, disabling reflection, to shrink binaries");
+    eprintln!("  --c-header      Also write a C header declaring the FFI entry points");
+    eprintln!("  --extern-path   Use an existing Rust module for a proto package instead of");
+    eprintln!("                  generating it, e.g. google.protobuf=protocrap::google::protobuf");
+    eprintln!();
+    eprintln!("EXAMPLES:");
+    eprintln!("  # Generate Rust code from proto:");
+    eprintln!("  protoc --descriptor_set_out=desc.pb --include_imports my.proto");
+    eprintln!("  {program} desc.pb my.pc.rs");
+    eprintln!();
+    eprintln!("  # Embed binary proto as const:");
+    eprintln!("  {program} desc.pb --embed config.pb:my.package.Config -o config.pc.rs");
+    eprintln!(
+        "  # Then in Rust: const CONFIG: my::package::Config::ProtoType = include!(\"config.pc.rs\");"
+    );
+}
@@ -0,0 +1,141 @@
+// protocrap-codegen/src/max_size.rs
+
+use std::collections::{BTreeMap, HashMap};
+
+use protocrap::google::protobuf::DescriptorProto::ProtoType as DescriptorProto;
+use protocrap::google::protobuf::FeatureSet::ProtoType as FeatureSet;
+use protocrap::google::protobuf::FieldDescriptorProto::Type;
+use protocrap::google::protobuf::FileDescriptorSet::ProtoType as FileDescriptorSet;
+use protocrap::reflection::{is_delimited, is_in_oneof, is_repeated};
+
+fn varint_len(value: u64) -> usize {
+    (64 - (value | 1).leading_zeros() as usize).div_ceil(7)
+}
+
+/// Upper bounds on the encoded size of every message in `file_set` whose size
+/// is bounded, keyed by fully qualified name without leading dot.
+///
+/// Sizes are bounded for messages built from singular scalar fields and
+/// singular sub-messages that are bounded themselves. Repeated fields, strings,
+/// bytes and recursion make a message unbounded.
+pub(crate) fn max_encoded_sizes(file_set: &FileDescriptorSet) -> HashMap<String, usize> {
+    fn add_messages<'a>(
+        messages: &mut BTreeMap<String, (&'a DescriptorProto, Option<&'a FeatureSet>)>,
+        scope: &str,
+        message: &'a DescriptorProto,
+        file_features: Option<&'a FeatureSet>,
+    ) {
+        let full_name = if scope.is_empty() {
+            message.name().to_string()
+        } else {
+            format!("{}.{}", scope, message.name())
+        };
+        for nested in message.nested_type() {
+            add_messages(messages, &full_name, nested, file_features);
+        }
+        messages.insert(full_name, (message, file_features));
+    }
+
+    fn max_size(
+        name: &str,
+        messages: &BTreeMap<String, (&DescriptorProto, Option<&FeatureSet>)>,
+        sizes: &mut HashMap<String, Option<usize>>,
+    ) -> Option<usize> {
+        if let Some(&size) = sizes.get(name) {
+            return size;
+        }
+        // Recursive references see the message as unbounded
+        sizes.insert(name.to_string(), None);
+        let &(message, file_features) = messages.get(name)?;
+
+        let mut total = 0;
+        let mut oneofs = vec![0; message.oneof_decl().len()];
+        for field in message.field() {
+            if is_repeated(field) {
+                return None;
+            }
+            let tag = varint_len((field.number() as u64) << 3);
+            let size = match field.r#type()? {
+                Type::TYPE_BOOL => tag + 1,
+                Type::TYPE_FIXED32 | Type::TYPE_SFIXED32 | Type::TYPE_FLOAT => tag + 4,
+                Type::TYPE_FIXED64 | Type::TYPE_SFIXED64 | Type::TYPE_DOUBLE => tag + 8,
+                Type::TYPE_UINT32 | Type::TYPE_SINT32 => tag + 5,
+                // Negative int32 and enum values are sign extended to 10 bytes
+                Type::TYPE_INT32
+                | Type::TYPE_ENUM
+                | Type::TYPE_INT64
+                | Type::TYPE_UINT64
+                | Type::TYPE_SINT64 => tag + 10,
+                Type::TYPE_STRING | Type::TYPE_BYTES => return None,
+                Type::TYPE_MESSAGE if !is_delimited(message, field, file_features) => {
+                    let child = max_size(field.type_name().trim_start_matches('.'), messages, sizes)?;
+                    tag + varint_len(child as u64) + child
+                }
+                Type::TYPE_MESSAGE | Type::TYPE_GROUP => {
+                    let child = max_size(field.type_name().trim_start_matches('.'), messages, sizes)?;
+                    2 * tag + child
+                }
+            };
+            if is_in_oneof(field) {
+                let oneof = &mut oneofs[field.oneof_index() as usize];
+                *oneof = size.max(*oneof);
+            } else {
+                total += size;
+            }
+        }
+        let size = total + oneofs.iter().sum::<usize>();
+        sizes.insert(name.to_string(), Some(size));
+        Some(size)
+    }
+
+    // Walked in name order, so nothing depends on hash order
+    let mut messages = BTreeMap::new();
+    for file in file_set.file() {
+        let file_features = file.options().and_then(|o| o.features());
+        for message in file.message_type() {
+            add_messages(&mut messages, file.package(), message, file_features);
+        }
+    }
+    let mut sizes = HashMap::new();
+    for name in messages.keys() {
+        max_size(name, &messages, &mut sizes);
+    }
+    sizes
+        .into_iter()
+        .filter_map(|(name, size)| Some((name, size?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocrap::arena::Arena;
+    use protocrap::google::protobuf::FileDescriptorProto::ProtoType as FileDescriptorProto;
+
+    #[test]
+    fn bounded_messages() {
+        let mut arena = Arena::new(&allocator_api2::alloc::Global);
+        let mut file_set = FileDescriptorSet::default();
+        file_set
+            .file_mut()
+            .push(
+                protocrap::generated_code_only::TypedMessage::from_static(
+                    FileDescriptorProto::file_descriptor(),
+                ),
+                &mut arena,
+            )
+            .unwrap();
+        let sizes = max_encoded_sizes(&file_set);
+
+        // Two int32 fields
+        assert_eq!(sizes["google.protobuf.DescriptorProto.ReservedRange"], 22);
+        assert!(sizes.contains_key("google.protobuf.FeatureSet"));
+        // FeatureSet nested in a bounded message
+        let defaults = "google.protobuf.FeatureSetDefaults.FeatureSetEditionDefault";
+        assert!(sizes[defaults] > sizes["google.protobuf.FeatureSet"]);
+        // Strings, repeated fields and recursion are unbounded
+        assert!(!sizes.contains_key("google.protobuf.FileDescriptorProto"));
+        assert!(!sizes.contains_key("google.protobuf.DescriptorProto"));
+        assert!(!sizes.contains_key("google.protobuf.UninterpretedOption.NamePart"));
+    }
+}
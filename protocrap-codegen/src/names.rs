@@ -0,0 +1,413 @@
+// protocrap-codegen/src/names.rs
+
+use super::Options;
+
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::{Ident, TokenStream};
+use protocrap::google::protobuf::DescriptorProto::ProtoType as DescriptorProto;
+use protocrap::google::protobuf::FieldDescriptorProto::ProtoType as FieldDescriptorProto;
+use protocrap::google::protobuf::FieldDescriptorProto::Type;
+use protocrap::reflection::{is_in_oneof, is_repeated};
+use quote::{format_ident, quote};
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "async", "await", "dyn", "abstract", "become", "box", "do", "final", "gen", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// Keywords that can't be written as raw identifiers.
+const NON_RAW_KEYWORDS: &[&str] = &["crate", "self", "Self", "super", "_"];
+
+/// Names that generated modules define or import themselves, or that generated
+/// code uses unqualified, so a message, enum or package of that name would
+/// clash with or shadow them.
+const RESERVED_TYPE_NAMES: &[&str] = &[
+    "ProtoType", "Protobuf", "protocrap", "core", "Option", "Result", "Default", "Box", "bool",
+    "str", "u8", "u32", "u64", "i32", "i64", "f32", "f64", "usize",
+];
+
+/// Struct members of every message besides its fields.
+const RESERVED_FIELD_NAMES: &[&str] = &["metadata"];
+
+/// Associated items of every message besides its accessors.
+const RESERVED_METHOD_NAMES: &[&str] = &[
+    "clear", "clone_in", "file_descriptor", "descriptor_proto", "from_static", "MAX_ENCODED_SIZE",
+];
+
+/// Prefixes and suffixes the generator adds to a field's accessor name.
+const ACCESSOR_PREFIXES: &[&str] = &[
+    "set_", "set_optional_", "get_", "has_", "clear_", "add_", "raw_", "set_raw_",
+];
+const ACCESSOR_SUFFIXES: &[&str] = &["_mut"];
+
+/// Turn a proto name into a Rust identifier.
+///
+/// Characters Rust doesn't allow become `_` and a leading digit gets a `_`
+/// prefix. Keywords become raw identifiers if `raw` is set; otherwise, or if
+/// they can't be raw, they get a `_` suffix like the names in `reserved`.
+fn mangle(name: &str, raw: bool, reserved: &[&str]) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    if NON_RAW_KEYWORDS.contains(&ident.as_str())
+        || reserved.contains(&ident.as_str())
+        || (!raw && RUST_KEYWORDS.contains(&ident.as_str()))
+    {
+        ident.push('_');
+    } else if RUST_KEYWORDS.contains(&ident.as_str()) {
+        // Use rust r# syntax for keywords
+        ident.insert_str(0, "r#");
+    }
+    ident
+}
+
+/// Identifier for an enum value, RPC method or similar name that lives in a
+/// scope of its own.
+pub fn sanitize_ident(name: &str) -> String {
+    mangle(name, true, &[])
+}
+
+/// Identifier for the struct member holding a field or oneof.
+pub fn sanitize_field_name(name: &str) -> String {
+    mangle(name, true, RESERVED_FIELD_NAMES)
+}
+
+/// Identifier for the module of a message, the type of an enum or the module of
+/// a package or service. Every segment of a type path goes through here, so
+/// references agree with declarations.
+pub fn sanitize_type_name(name: &str) -> String {
+    mangle(name, true, RESERVED_TYPE_NAMES)
+}
+
+/// Sanitize a module name by appending underscore for keywords
+/// (can't use r# prefix for modules, especially with leading underscores)
+pub fn sanitize_module_name(name: &str) -> String {
+    mangle(name, false, RESERVED_TYPE_NAMES)
+}
+
+/// Generated identifiers of a message that are derived from several names
+/// and therefore can collide even when each name is valid on its own.
+///
+/// Collisions are resolved by appending `_` to the name that would otherwise
+/// shadow a generated item, keeping the plain accessors of the other fields.
+pub struct MessageNames {
+    /// Accessor base name by field number.
+    accessors: HashMap<i32, String>,
+    /// Union type name by oneof index.
+    unions: Vec<String>,
+    /// `Which` variant name by field number.
+    variants: HashMap<i32, String>,
+}
+
+impl MessageNames {
+    /// Names of `message`, whose fully qualified name is `full_name`.
+    pub fn new(message: &DescriptorProto, full_name: &str, options: &Options) -> Self {
+        let fields = message.field();
+
+        // A field's accessor collides with a fixed item, with another field's
+        // prefixed or suffixed accessors, or with an earlier accessor that
+        // already had to be renamed
+        let mut accessors: Vec<String> = fields
+            .iter()
+            .map(|f| sanitize_ident(options.custom.rust_name(full_name, f.number()).unwrap_or(f.name())))
+            .collect();
+        let map_fields: Vec<bool> = fields.iter().map(|f| map_entry(message, f).is_some()).collect();
+        loop {
+            let mut taken: HashSet<String> =
+                RESERVED_METHOD_NAMES.iter().map(|s| s.to_string()).collect();
+            for oneof in message.oneof_decl() {
+                taken.extend([format!("which_{}", oneof.name()), format!("set_{}", oneof.name())]);
+            }
+            let derived: Vec<Vec<String>> = accessors
+                .iter()
+                .zip(&map_fields)
+                .map(|(name, &is_map)| {
+                    let name = unraw(name);
+                    ACCESSOR_PREFIXES
+                        .iter()
+                        .map(|p| format!("{p}{name}"))
+                        .chain(ACCESSOR_SUFFIXES.iter().map(|s| format!("{name}{s}")))
+                        .chain([format!("set_{name}_static")])
+                        .chain(is_map.then(|| format!("{name}_iter_ordered")))
+                        .chain((is_map && options.maps).then(|| format!("{name}_map")))
+                        .collect()
+                })
+                .collect();
+            let mut renamed = false;
+            for (i, accessor) in accessors.iter_mut().enumerate() {
+                let name = unraw(accessor).to_string();
+                let shadows = taken.contains(&name)
+                    || derived
+                        .iter()
+                        .enumerate()
+                        .any(|(j, names)| j != i && names.contains(&name));
+                if shadows {
+                    *accessor = format!("{name}_");
+                    renamed = true;
+                }
+                taken.insert(unraw(accessor).to_string());
+            }
+            if !renamed {
+                break;
+            }
+        }
+
+        let mut types: HashSet<String> = message
+            .nested_type()
+            .iter()
+            .map(|m| sanitize_type_name(m.name()))
+            .chain(message.enum_type().iter().map(|e| sanitize_type_name(e.name())))
+            .chain(message.oneof_decl().iter().map(|o| sanitize_module_name(o.name())))
+            .collect();
+        let unions = message
+            .oneof_decl()
+            .iter()
+            .map(|oneof| claim(&mut types, format!("{}Union", to_pascal_case(oneof.name()))))
+            .collect();
+
+        let mut variants = HashMap::new();
+        for (idx, _) in message.oneof_decl().iter().enumerate() {
+            // `None` is the variant for an unset oneof
+            let mut taken = HashSet::from(["None".to_string()]);
+            for field in fields.iter().filter(|f| is_in_oneof(f) && f.oneof_index() as usize == idx) {
+                let name = mangle(&to_pascal_case(field.name()), true, &["None"]);
+                variants.insert(field.number(), claim(&mut taken, name));
+            }
+        }
+
+        MessageNames {
+            accessors: fields
+                .iter()
+                .map(|f| f.number())
+                .zip(accessors)
+                .collect(),
+            unions,
+            variants,
+        }
+    }
+
+    /// Base name of the accessors of `field`, the getter's name.
+    pub fn accessor(&self, field: &FieldDescriptorProto) -> Ident {
+        format_ident!("{}", self.accessors[&field.number()])
+    }
+
+    /// Name of the union type holding the oneof at `oneof_index`.
+    pub fn union(&self, oneof_index: usize) -> Ident {
+        format_ident!("{}", self.unions[oneof_index])
+    }
+
+    /// Name of the `Which` variant of the oneof member `field`.
+    pub fn which_variant(&self, field: &FieldDescriptorProto) -> Ident {
+        format_ident!("{}", self.variants[&field.number()])
+    }
+}
+
+/// The entry message of `field` if it is a map field of `message`.
+pub fn map_entry<'a>(
+    message: &'a DescriptorProto,
+    field: &FieldDescriptorProto,
+) -> Option<&'a DescriptorProto> {
+    if !is_repeated(field) || field.r#type() != Some(Type::TYPE_MESSAGE) {
+        return None;
+    }
+    let entry_name = field.type_name().rsplit('.').next()?;
+    message
+        .nested_type()
+        .iter()
+        .find(|nested| nested.name() == entry_name && nested.options().is_some_and(|o| o.map_entry()))
+        .map(|nested| &**nested)
+}
+
+/// `name` with `_` appended until it isn't in `taken`, then added to it.
+fn claim(taken: &mut HashSet<String>, mut name: String) -> String {
+    while taken.contains(&name) {
+        name.push('_');
+    }
+    taken.insert(name.clone());
+    name
+}
+
+fn unraw(name: &str) -> &str {
+    name.strip_prefix("r#").unwrap_or(name)
+}
+
+/// Convert snake_case to PascalCase (for union type names)
+pub fn to_pascal_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                None => String::new(),
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Convert PascalCase to snake_case (for RPC method names)
+pub fn to_snake_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_uppercase() {
+            if prev_lower {
+                result.push('_');
+            }
+            result.extend(c.to_lowercase());
+            prev_lower = false;
+        } else {
+            result.push(c);
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        }
+    }
+    result
+}
+
+/// Name of the exported C function returning the table of a message, given its
+/// fully qualified name, e.g. `pc_table_my_package_MyMessage`.
+pub fn ffi_table_symbol(full_name: &str) -> String {
+    format!("pc_table_{}", full_name.trim_start_matches('.').replace('.', "_"))
+}
+
+pub fn rust_field_type_tokens(field: &FieldDescriptorProto, options: &Options) -> TokenStream {
+    use protocrap::google::protobuf::FieldDescriptorProto::Label;
+
+    let is_repeated = field.label().unwrap() == Label::LABEL_REPEATED;
+    let is_message = matches!(
+        field.r#type(),
+        Some(Type::TYPE_MESSAGE) | Some(Type::TYPE_GROUP)
+    );
+
+    if is_repeated {
+        let element = rust_element_type_tokens(field, options);
+        quote! { protocrap::generated_code_only::RepeatedField<#element> }
+    } else if is_message {
+        // Singular message field uses typed OptionalMessage<T>
+        let msg_type = rust_type_tokens(field, options);
+        if is_in_oneof(field) {
+            // In oneof, use TypedMessage<T> because presence is tracked by the oneof discriminant
+            quote! { protocrap::generated_code_only::TypedMessage<#msg_type::ProtoType> }
+        } else {
+            quote! { protocrap::generated_code_only::OptionalMessage<#msg_type::ProtoType> }
+        }
+    } else {
+        rust_element_type_tokens(field, options)
+    }
+}
+
+pub fn rust_element_type_tokens(field: &FieldDescriptorProto, options: &Options) -> TokenStream {
+    match field.r#type().unwrap() {
+        Type::TYPE_MESSAGE | Type::TYPE_GROUP => {
+            let msg_type = rust_type_tokens(field, options);
+            quote! { protocrap::generated_code_only::TypedMessage<#msg_type::ProtoType> }
+        }
+        Type::TYPE_INT32 | Type::TYPE_SINT32 | Type::TYPE_SFIXED32 => quote! { i32 },
+        Type::TYPE_INT64 | Type::TYPE_SINT64 | Type::TYPE_SFIXED64 => quote! { i64 },
+        Type::TYPE_UINT32 | Type::TYPE_FIXED32 => quote! { u32 },
+        Type::TYPE_UINT64 | Type::TYPE_FIXED64 => quote! { u64 },
+        Type::TYPE_FLOAT => quote! { f32 },
+        Type::TYPE_DOUBLE => quote! { f64 },
+        Type::TYPE_BOOL => quote! { bool },
+        Type::TYPE_STRING => quote! { protocrap::generated_code_only::String },
+        Type::TYPE_BYTES => quote! { protocrap::generated_code_only::Bytes },
+        Type::TYPE_ENUM => quote! { i32 },
+    }
+}
+
+pub fn rust_type_tokens(field: &FieldDescriptorProto, options: &Options) -> TokenStream {
+    rust_type_path(field.type_name(), options)
+}
+
+/// Rust module path of a fully qualified proto type name.
+pub fn rust_type_path(type_name: &str, options: &Options) -> TokenStream {
+    // type_name is like ".google.protobuf.FileDescriptorProto"
+    let type_name = type_name.trim_start_matches('.');
+
+    // Types in an extern package resolve against the mapped Rust path
+    if let Some((rust_path, rest)) = options.resolve_extern_path(type_name) {
+        let parts: Vec<_> = rest
+            .split('.')
+            .map(|s| format_ident!("{}", sanitize_type_name(s)))
+            .collect();
+        return quote! { #rust_path::#(#parts)::* };
+    }
+
+    // Split into parts and convert to identifiers
+    let parts: Vec<_> = type_name
+        .split('.')
+        .map(|s| format_ident!("{}", sanitize_type_name(s)))
+        .collect();
+
+    // Build path: google::protobuf::FileDescriptorProto::ProtoType
+    quote! { crate::#(#parts)::* }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocrap::arena::Arena;
+    use protocrap::google::protobuf::FieldDescriptorProto::Label;
+
+    #[test]
+    fn sanitized_identifiers() {
+        assert_eq!(sanitize_ident("type"), "r#type");
+        assert_eq!(sanitize_ident("gen"), "r#gen");
+        assert_eq!(sanitize_ident("Self"), "Self_");
+        assert_eq!(sanitize_ident("_"), "__");
+        assert_eq!(sanitize_field_name("self"), "self_");
+        assert_eq!(sanitize_field_name("metadata"), "metadata_");
+        assert_eq!(sanitize_type_name("ProtoType"), "ProtoType_");
+        assert_eq!(sanitize_type_name("Option"), "Option_");
+        assert_eq!(sanitize_type_name("loop"), "r#loop");
+        assert_eq!(sanitize_module_name("loop"), "loop_");
+        assert_eq!(sanitize_module_name("3d-model"), "_3d_model");
+    }
+
+    #[test]
+    fn colliding_accessors() {
+        let mut arena = Arena::new(&allocator_api2::alloc::Global);
+        let mut message = DescriptorProto::default();
+        message.set_name("M", &mut arena).unwrap();
+        message.add_oneof_decl(&mut arena).unwrap().set_name("kind", &mut arena).unwrap();
+        for (number, name, oneof) in [
+            (1, "set_type", false),
+            (2, "type", false),
+            (3, "clear", false),
+            (4, "which_kind", false),
+            (5, "none", true),
+            (6, "a_b", true),
+            (7, "a__b", true),
+        ] {
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(Type::TYPE_INT32);
+            if oneof {
+                field.set_oneof_index(0);
+            }
+        }
+        message.add_nested_type(&mut arena).unwrap().set_name("KindUnion", &mut arena).unwrap();
+
+        let names = MessageNames::new(&message, "M", &Options::default());
+        let accessor = |i: usize| names.accessor(&message.field()[i]).to_string();
+        let variant = |i: usize| names.which_variant(&message.field()[i]).to_string();
+        assert_eq!(accessor(0), "set_type_");
+        assert_eq!(accessor(1), "r#type");
+        assert_eq!(accessor(2), "clear_");
+        assert_eq!(accessor(3), "which_kind_");
+        assert_eq!(accessor(4), "none");
+        assert_eq!(variant(4), "None_");
+        assert_eq!(variant(5), "AB");
+        assert_eq!(variant(6), "AB_");
+        assert_eq!(names.union(0).to_string(), "KindUnion_");
+    }
+}
@@ -1,7 +1,5 @@
 // protocrap-codegen/src/static_gen.rs
 
-use super::protocrap;
-
 use anyhow::Result;
 use proc_macro2::{Literal, TokenStream};
 use protocrap::{
@@ -137,10 +135,10 @@ fn generate_repeated_scalar<T: Copy + ToTokens>(
                 static ELEMENTS: [#type_name; #len] = [
                     #(#elements),*
                 ];
-                protocrap::containers::RepeatedField::from_static(&ELEMENTS)
+                protocrap::generated_code_only::RepeatedField::from_static(&ELEMENTS)
             }
         },
-        quote! { protocrap::containers::RepeatedField<#type_name> },
+        quote! { protocrap::generated_code_only::RepeatedField<#type_name> },
     ))
 }
 
@@ -178,17 +176,17 @@ fn generate_field_value(
         }
         Value::String(s) => Ok((
             quote! {
-                protocrap::containers::String::from_static(#s)
+                protocrap::generated_code_only::String::from_static(#s)
             },
-            quote! { protocrap::containers::String },
+            quote! { protocrap::generated_code_only::String },
         )),
         Value::Bytes(b) => {
             let bytes: Vec<_> = b.iter().map(|&byte| Literal::u8_unsuffixed(byte)).collect();
             Ok((
                 quote! {
-                    protocrap::containers::Bytes::from_static(&[#(#bytes),*])
+                    protocrap::generated_code_only::Bytes::from_static(&[#(#bytes),*])
                 },
-                quote! { protocrap::containers::Bytes },
+                quote! { protocrap::generated_code_only::Bytes },
             ))
         }
         Value::Message(msg) => {
@@ -201,6 +199,12 @@ fn generate_field_value(
         }
         Value::RepeatedBool(list) => generate_repeated_scalar(list),
         Value::RepeatedInt32(list) => generate_repeated_scalar(list),
+        // get_field doesn't produce typed enums, they are plain numbers here
+        Value::Enum { number, .. } => {
+            let lit = Literal::i32_unsuffixed(number);
+            Ok((quote! { #lit }, quote! { i32 }))
+        }
+        Value::RepeatedEnum { numbers, .. } => generate_repeated_scalar(numbers),
         Value::RepeatedInt64(list) => generate_repeated_scalar(list),
         Value::RepeatedUInt32(list) => generate_repeated_scalar(list),
         Value::RepeatedUInt64(list) => generate_repeated_scalar(list),
@@ -211,7 +215,7 @@ fn generate_field_value(
             for s in list {
                 let s_str = s.as_str();
                 let elem_init = quote! {
-                    protocrap::containers::String::from_static(#s_str)
+                    protocrap::generated_code_only::String::from_static(#s_str)
                 };
                 elements.push(elem_init);
             }
@@ -219,13 +223,13 @@ fn generate_field_value(
             Ok((
                 quote! {
                     {
-                        static ELEMENTS: [protocrap::containers::String; #len] = [
+                        static ELEMENTS: [protocrap::generated_code_only::String; #len] = [
                             #(#elements),*
                         ];
-                        protocrap::containers::RepeatedField::from_static(&ELEMENTS)
+                        protocrap::generated_code_only::RepeatedField::from_static(&ELEMENTS)
                     }
                 },
-                quote! { protocrap::containers::RepeatedField<protocrap::containers::String> },
+                quote! { protocrap::generated_code_only::RepeatedField<protocrap::generated_code_only::String> },
             ))
         }
         Value::RepeatedBytes(list) => {
@@ -233,7 +237,7 @@ fn generate_field_value(
             for b in list {
                 let bytes: Vec<_> = b.iter().map(|&byte| Literal::u8_unsuffixed(byte)).collect();
                 let elem_init = quote! {
-                    protocrap::containers::Bytes::from_static(&[#(#bytes),*])
+                    protocrap::generated_code_only::Bytes::from_static(&[#(#bytes),*])
                 };
                 elements.push(elem_init);
             }
@@ -241,13 +245,13 @@ fn generate_field_value(
             Ok((
                 quote! {
                     {
-                        static ELEMENTS: [protocrap::containers::Bytes; #len] = [
+                        static ELEMENTS: [protocrap::generated_code_only::Bytes; #len] = [
                             #(#elements),*
                         ];
-                        protocrap::containers::RepeatedField::from_static(&ELEMENTS)
+                        protocrap::generated_code_only::RepeatedField::from_static(&ELEMENTS)
                     }
                 },
-                quote! { protocrap::containers::RepeatedField<protocrap::containers::Bytes> },
+                quote! { protocrap::generated_code_only::RepeatedField<protocrap::generated_code_only::Bytes> },
             ))
         }
         Value::RepeatedMessage(list) => {
@@ -256,19 +260,19 @@ fn generate_field_value(
             let mut elements = Vec::new();
             for msg in list.iter() {
                 let static_ref = generate_nested_message(&msg, type_name, crate_path)?;
-                elements.push(quote! { protocrap::TypedMessage::from_static(#static_ref) });
+                elements.push(quote! { protocrap::generated_code_only::TypedMessage::from_static(#static_ref) });
             }
             let len = elements.len();
             Ok((
                 quote! {
                     {
-                        static ELEMENTS: [protocrap::TypedMessage<#prefix #(#path_parts)::* ::ProtoType>; #len] = [
+                        static ELEMENTS: [protocrap::generated_code_only::TypedMessage<#prefix #(#path_parts)::* ::ProtoType>; #len] = [
                             #(#elements),*
                         ];
-                        protocrap::containers::RepeatedField::from_static(&ELEMENTS)
+                        protocrap::generated_code_only::RepeatedField::from_static(&ELEMENTS)
                     }
                 },
-                quote! { protocrap::containers::RepeatedField<protocrap::TypedMessage<#prefix #(#path_parts)::* ::ProtoType>> },
+                quote! { protocrap::generated_code_only::RepeatedField<protocrap::generated_code_only::TypedMessage<#prefix #(#path_parts)::* ::ProtoType>> },
             ))
         }
     }
@@ -293,12 +297,12 @@ fn generate_nested_message(
 
 fn generate_default_value(field: &FieldDescriptorProto) -> TokenStream {
     if is_repeated(field) {
-        return quote! { protocrap::containers::RepeatedField::new() };
+        return quote! { protocrap::generated_code_only::RepeatedField::new() };
     }
 
     match field.r#type().unwrap() {
-        Type::TYPE_STRING => quote! { protocrap::containers::String::new() },
-        Type::TYPE_BYTES => quote! { protocrap::containers::Bytes::new() },
+        Type::TYPE_STRING => quote! { protocrap::generated_code_only::String::new() },
+        Type::TYPE_BYTES => quote! { protocrap::generated_code_only::Bytes::new() },
         Type::TYPE_MESSAGE | Type::TYPE_GROUP => quote! {
             protocrap::generated_code_only::OptionalMessage::none()
         },
@@ -1,11 +1,12 @@
 use super::names::{rust_type_tokens, sanitize_field_name};
-use super::protocrap;
+use super::Options;
 use anyhow::Result;
 use proc_macro2::TokenStream;
 use protocrap::google::protobuf::DescriptorProto::ProtoType as DescriptorProto;
+use protocrap::google::protobuf::FeatureSet::ProtoType as FeatureSet;
 use protocrap::google::protobuf::FieldDescriptorProto::ProtoType as FieldDescriptorProto;
 
-use protocrap::reflection::{calculate_tag_with_syntax, is_message};
+use protocrap::reflection::{calculate_tag_with_syntax, is_delimited, is_message, is_repeated};
 use quote::{format_ident, quote};
 
 /// Oneof info: field_number -> (discriminant_word_index, oneof_field_name)
@@ -16,6 +17,7 @@ fn generate_aux_entries(
     message: &DescriptorProto,
     oneof_info: &OneofInfo,
     aux_index_map: &mut std::collections::HashMap<i32, usize>,
+    options: &Options,
 ) -> Result<Vec<TokenStream>> {
     let aux_entries: Vec<_> = message
         .field()
@@ -28,7 +30,7 @@ fn generate_aux_entries(
             } else {
                 format_ident!("{}", sanitize_field_name(field.name()))
             };
-            let child_table = rust_type_tokens(field);
+            let child_table = rust_type_tokens(field, options);
             let num_aux = aux_index_map.len();
             aux_index_map.insert(field.number(), num_aux);
             quote! {
@@ -39,7 +41,25 @@ fn generate_aux_entries(
             }
         })
         .collect();
-    Ok(aux_entries)
+
+    // Closed enum fields follow, carrying the enum's values
+    let enum_entries = message.field().iter().filter_map(|field| {
+        let values = options.closed_enum_values(field)?;
+        let field_offset_name = if let Some((_, oneof_name)) = oneof_info.get(&field.number()) {
+            format_ident!("{}", sanitize_field_name(oneof_name))
+        } else {
+            format_ident!("{}", sanitize_field_name(field.name()))
+        };
+        let num_aux = aux_index_map.len();
+        aux_index_map.insert(field.number(), num_aux);
+        Some(quote! {
+            protocrap::generated_code_only::AuxTableEntry::closed_enum(
+                core::mem::offset_of!(ProtoType, #field_offset_name) as u32,
+                &protocrap::generated_code_only::EnumTable { values: &[#(#values),*] },
+            )
+        })
+    });
+    Ok(aux_entries.into_iter().chain(enum_entries).collect())
 }
 
 fn generate_encoding_entries(
@@ -48,6 +68,7 @@ fn generate_encoding_entries(
     oneof_info: &OneofInfo,
     aux_index_map: &std::collections::HashMap<i32, usize>,
     syntax: Option<&str>,
+    file_features: Option<&FeatureSet>,
 ) -> Result<Vec<TokenStream>> {
     let num_encode_entries = message.field().len();
     let num_aux_entries = aux_index_map.len();
@@ -66,8 +87,9 @@ fn generate_encoding_entries(
     let num_decode_entries = max_field_number as usize + 1;
 
     let entries: Vec<_> = message.field().iter().map(|field| {
-        let kind = field_kind_tokens(field);
-        let encoded_tag = calculate_tag_with_syntax(field, syntax);
+        let delimited = is_delimited(message, field, file_features);
+        let kind = field_kind_tokens(field, delimited);
+        let encoded_tag = calculate_tag_with_syntax(field, syntax, delimited);
 
         // Check oneof first (applies to all field types including message)
         if let Some((discriminant_word_idx, oneof_name)) = oneof_info.get(&field.number()) {
@@ -139,6 +161,8 @@ fn generate_decoding_table(
     has_bit_map: &std::collections::HashMap<i32, usize>,
     oneof_info: &OneofInfo,
     aux_index_map: &std::collections::HashMap<i32, usize>,
+    file_features: Option<&FeatureSet>,
+    options: &Options,
 ) -> Result<Vec<TokenStream>> {
     // Calculate masked table parameters
     let max_field_number = message
@@ -161,10 +185,30 @@ fn generate_decoding_table(
     // Generate entry table
     let entries: Vec<_> = (0..=max_field_number).map(|field_number| {
         if let Some(field) = message.field().iter().find(|f| f.number() == field_number as i32) {
-            let field_kind = field_kind_tokens(field);
+            let field_kind = field_kind_tokens(field, is_delimited(message, field, file_features));
 
-            // Check oneof first (applies to all field types including message)
-            if let Some((discriminant_word_idx, oneof_name)) = oneof_info.get(&field_number) {
+            if options.closed_enum_values(field).is_some() {
+                // Closed enum field - offset points to aux entry holding the enum values
+                let field_kind = if is_repeated(field) {
+                    quote! { protocrap::generated_code_only::FieldKind::RepeatedClosedEnum }
+                } else {
+                    quote! { protocrap::generated_code_only::FieldKind::ClosedEnum }
+                };
+                let has_bit = if let Some((discriminant_word_idx, _)) = oneof_info.get(&field_number) {
+                    (0x80 | *discriminant_word_idx) as u32
+                } else {
+                    has_bit_map.get(&field_number).copied().unwrap_or(0) as u32
+                };
+                let aux_index = *aux_index_map.get(&field_number).unwrap();
+                quote! { protocrap::generated_code_only::DecodeTableEntry::new(
+                    #field_kind,
+                    #has_bit,
+                    core::mem::offset_of!(protocrap::generated_code_only::TableWithEntries<#num_encode_entries, #num_decode_entries, #num_aux_entries>, aux_entries) +
+                    #aux_index * core::mem::size_of::<protocrap::generated_code_only::AuxTableEntry>() -
+                    core::mem::offset_of!(protocrap::generated_code_only::TableWithEntries<#num_encode_entries, #num_decode_entries, #num_aux_entries>, table)
+                ) }
+            } else if let Some((discriminant_word_idx, oneof_name)) = oneof_info.get(&field_number) {
+                // Check oneof first (applies to all field types including message)
                 // Oneof field: has_bit stores discriminant word index with 0x80 flag
                 let has_bit = (0x80 | *discriminant_word_idx) as u32;
                 let oneof_field_name = format_ident!("{}", sanitize_field_name(oneof_name));
@@ -219,23 +263,58 @@ fn generate_decoding_table(
     Ok(entries)
 }
 
+/// Indices of the fields of `message` by ascending field number, empty if
+/// they are declared in that order.
+fn field_number_order(message: &DescriptorProto) -> Vec<u16> {
+    let fields = message.field();
+    if fields.is_sorted_by_key(|field| field.number()) {
+        return Vec::new();
+    }
+    let mut order: Vec<u16> = (0..fields.len() as u16).collect();
+    order.sort_by_key(|&idx| fields[idx as usize].number());
+    order
+}
+
 pub(crate) fn generate_table(
     message: &DescriptorProto,
+    full_name: &str,
     has_bit_map: &std::collections::HashMap<i32, usize>,
     oneof_info: &OneofInfo,
     syntax: Option<&str>,
+    file_features: Option<&FeatureSet>,
+    options: &Options,
 ) -> Result<TokenStream> {
     let mut aux_index_map = std::collections::HashMap::<i32, usize>::new();
-    let aux_entries = generate_aux_entries(message, oneof_info, &mut aux_index_map)?;
+    let aux_entries = generate_aux_entries(message, oneof_info, &mut aux_index_map, options)?;
 
     let encoding_entries =
-        generate_encoding_entries(message, has_bit_map, oneof_info, &aux_index_map, syntax)?;
-    let decoding_entries =
-        generate_decoding_table(message, has_bit_map, oneof_info, &aux_index_map)?;
+        generate_encoding_entries(message, has_bit_map, oneof_info, &aux_index_map, syntax, file_features)?;
+    let decoding_entries = generate_decoding_table(
+        message,
+        has_bit_map,
+        oneof_info,
+        &aux_index_map,
+        file_features,
+        options,
+    )?;
 
     let num_encode_entries = encoding_entries.len();
     let num_decode_entries = decoding_entries.len();
+    let field_number_order = field_number_order(message);
     let num_aux_entries = aux_entries.len();
+    // The version of the runtime this generator is built with, not a path to
+    // the constant, so a different runtime can tell the table isn't its own
+    let version = protocrap::generated_code_only::TABLE_VERSION;
+    let syntax = protocrap::Syntax::from_name(syntax);
+    let syntax = format_ident!("{syntax:?}");
+    let (descriptor, file) = if options.strip_descriptors {
+        (quote! { None }, quote! { None })
+    } else {
+        (
+            quote! { Some(ProtoType::descriptor_proto()) },
+            quote! { Some(ProtoType::file_descriptor()) },
+        )
+    };
     Ok(quote! {
         #[allow(clippy::identity_op, clippy::erasing_op)]
         pub static TABLE: protocrap::generated_code_only::TableWithEntries<
@@ -247,10 +326,15 @@ pub(crate) fn generate_table(
                 #(#encoding_entries),*
             ],
             table: protocrap::generated_code_only::Table {
+                version: #version,
                 num_encode_entries: #num_encode_entries as u16,
                 num_decode_entries: #num_decode_entries as u16,
                 size: core::mem::size_of::<ProtoType>() as u16,
-                descriptor: ProtoType::descriptor_proto(),
+                syntax: protocrap::generated_code_only::Syntax::#syntax,
+                descriptor: #descriptor,
+                file: #file,
+                full_name: #full_name,
+                field_number_order: &[#(#field_number_order),*],
             },
             decode_entries: [
                 #(#decoding_entries),*
@@ -262,8 +346,8 @@ pub(crate) fn generate_table(
     })
 }
 
-fn field_kind_tokens(field: &FieldDescriptorProto) -> TokenStream {
-    let kind = protocrap::reflection::field_kind_tokens(field);
+fn field_kind_tokens(field: &FieldDescriptorProto, delimited: bool) -> TokenStream {
+    let kind = protocrap::reflection::field_kind_tokens(field, delimited);
     let ident = format_ident!("{kind:?}");
     quote! { protocrap::generated_code_only::FieldKind::#ident }
 }
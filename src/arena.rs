@@ -20,6 +20,10 @@
 //! let value = arena.place(String::from("hello")).unwrap(); // Returns Result<&mut String, Error<LayoutError>>
 //! assert_eq!(value, "hello");
 //!
+//! // Or copy slices and strings into the arena
+//! let name = arena.alloc_str("world").unwrap(); // Returns Result<&mut str, Error<LayoutError>>
+//! assert_eq!(name, "world");
+//!
 //! // All memory freed when arena drops
 //! ```
 //! # Custom Allocators
@@ -32,6 +36,14 @@
 //!
 //! Since the arena batches small allocations into large blocks, the overhead of
 //! dynamic dispatch on the allocator is negligible.
+//!
+//! # Mixing Arenas
+//!
+//! Everything stored in a message must come from the arena holding the
+//! message, or from an arena that outlives it. Setters take whichever arena
+//! they are given, so this is up to the caller; a resumed decode, which
+//! keeps allocating into the message across calls, refuses an arena other
+//! than the one it started with.
 
 use crate::Allocator;
 use core::alloc::Layout;
@@ -45,12 +57,54 @@ use core::ptr::NonNull;
 ///
 /// The arena grows automatically, starting with 8KB blocks and doubling up to
 /// 1MB. Large allocations (those that would waste significant space in the
-/// current block) get their own dedicated blocks.
+/// current block) get their own dedicated blocks. Decoding very large
+/// messages goes through fewer, larger allocations with a bigger first block
+/// from [`with_block_size`](Self::with_block_size), another
+/// [`GrowthPolicy`], or a [`reserve`](Self::reserve) sized to the input.
 pub struct Arena<'a> {
     current: *mut MemBlock,
     cursor: *mut u8,
     end: *mut u8,
     allocator: Option<&'a dyn Allocator>,
+    observer: Option<&'a (dyn Fn(Layout) + Sync)>,
+    stats: ArenaStats,
+    /// Bump space of the next block taken from the allocator.
+    next_block_size: usize,
+    growth: GrowthPolicy,
+    /// Unique per arena, lets a resumed decode tell it got another arena.
+    id: usize,
+}
+
+/// Allocation counters of an [`Arena`], see [`Arena::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// Number of allocation requests.
+    pub allocations: usize,
+    /// Total bytes requested, excluding alignment padding.
+    pub bytes_requested: usize,
+    /// Bytes currently held in blocks, including block headers and unused
+    /// space. The same as [`Arena::bytes_allocated`].
+    pub bytes_reserved: usize,
+    /// The largest `bytes_reserved` seen over the arena's lifetime.
+    pub high_water_mark: usize,
+}
+
+/// How an [`Arena`] sizes successive blocks, see
+/// [`Arena::set_growth_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrowthPolicy {
+    /// Every block is as large as the first.
+    Fixed,
+    /// Every block is twice as large as the one before.
+    Doubling,
+    /// Blocks double until they reach the given size, the default with 1MB.
+    Capped(usize),
+}
+
+impl Default for GrowthPolicy {
+    fn default() -> Self {
+        GrowthPolicy::Capped(MAX_BLOCK_SIZE)
+    }
 }
 
 // Mem block is a block of contiguous memory allocated from the allocator
@@ -65,30 +119,103 @@ const MAX_BLOCK_SIZE: usize = 1024 * 1024; // 1MB max block
 impl<'a> Arena<'a> {
     /// Create a new arena with the given allocator
     pub fn new(allocator: &'a dyn Allocator) -> Self {
+        Self::with_block_size(allocator, DEFAULT_BLOCK_SIZE)
+    }
+
+    /// Create an arena whose first block has room for `block_size` bytes.
+    ///
+    /// Later blocks grow from there according to the arena's
+    /// [`GrowthPolicy`], by default doubling up to 1MB or `block_size` if
+    /// that is larger. No memory is taken from `allocator` before the first
+    /// allocation.
+    pub fn with_block_size(allocator: &'a dyn Allocator, block_size: usize) -> Self {
+        // An empty but non-null bump region, so zero-sized requests never yield null
+        let empty = NonNull::<u8>::dangling().as_ptr();
         Self {
             current: ptr::null_mut(),
-            cursor: ptr::null_mut(),
-            end: ptr::null_mut(),
+            cursor: empty,
+            end: empty,
             allocator: Some(allocator),
+            observer: None,
+            stats: ArenaStats::default(),
+            next_block_size: block_size,
+            growth: GrowthPolicy::default(),
+            id: next_arena_id(),
         }
     }
 
     /// Create an arena from a pre-allocated memory slice
     pub fn from_slice(data: &'a mut [u8]) -> Self {
-        debug_assert!(data.len() >= core::mem::size_of::<MemBlock>());
-        let current = data.as_mut_ptr() as *mut MemBlock;
+        // The slice may start at any address, place the header at the first aligned one
+        let header_offset = data.as_mut_ptr().align_offset(core::mem::align_of::<MemBlock>());
+        debug_assert!(data.len() >= header_offset + core::mem::size_of::<MemBlock>());
         unsafe {
+            let current = data.as_mut_ptr().add(header_offset) as *mut MemBlock;
             (*current).prev = ptr::null_mut();
             (*current).layout = Layout::from_size_align_unchecked(data.len(), core::mem::align_of::<MemBlock>());
             Self {
                 current,
-                cursor: data.as_mut_ptr().add(core::mem::size_of::<MemBlock>()),
+                cursor: (current as *mut u8).add(core::mem::size_of::<MemBlock>()),
                 end: data.as_mut_ptr().add(data.len()),
                 allocator: None,
+                observer: None,
+                stats: ArenaStats {
+                    bytes_reserved: data.len(),
+                    high_water_mark: data.len(),
+                    ..ArenaStats::default()
+                },
+                next_block_size: DEFAULT_BLOCK_SIZE,
+                growth: GrowthPolicy::default(),
+                id: next_arena_id(),
             }
         }
     }
 
+    /// Call `observer` with the layout of every subsequent allocation request,
+    /// e.g. to build a histogram of allocation sizes or to spot decode paths
+    /// that allocate far more than expected.
+    pub fn set_observer(&mut self, observer: &'a (dyn Fn(Layout) + Sync)) {
+        self.observer = Some(observer);
+    }
+
+    /// Set how the blocks after the next one grow.
+    pub fn set_growth_policy(&mut self, policy: GrowthPolicy) {
+        self.growth = policy;
+    }
+
+    /// Make room for `bytes` more bytes of allocations in the current block.
+    ///
+    /// Takes a block of at least `bytes` from the allocator if the current
+    /// one has less space left. Reserving the size of the input before a
+    /// large decode serves it from a single block, instead of a chain of
+    /// growing ones. Padding for alignment counts against the reservation.
+    /// Fails with [`Error::ArenaAllocationFailed`](crate::Error::ArenaAllocationFailed)
+    /// if the allocator refuses the block or the arena has none, as with
+    /// [`from_slice`](Self::from_slice).
+    pub fn reserve(&mut self, bytes: usize) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+        if self.end as usize - self.cursor as usize >= bytes {
+            return Ok(());
+        }
+        // Smaller reservations take the block that was due anyway
+        let size = if bytes <= self.next_block_size {
+            self.grow()
+        } else {
+            bytes
+        };
+        let (layout, block_start) = Layout::new::<MemBlock>().extend(Layout::array::<u8>(size)?)?;
+        let block = self
+            .push_block(layout.pad_to_align())
+            .ok_or(crate::Error::ArenaAllocationFailed)?;
+        self.cursor = unsafe { block.add(block_start) };
+        Ok(())
+    }
+
+    /// Allocation counters, cheap enough to export as metrics after every
+    /// request.
+    pub fn stats(&self) -> ArenaStats {
+        self.stats
+    }
+
     /// Allocate uninitialized memory for type T, returning a raw pointer
     pub fn alloc<T>(&mut self) -> Result<*mut T, crate::Error<core::alloc::LayoutError>> {
         let layout = Layout::new::<T>();
@@ -112,42 +239,95 @@ impl<'a> Arena<'a> {
         Ok(ptr::slice_from_raw_parts_mut(ptr.as_ptr() as *mut T, len))
     }
 
+    /// Copy `values` into the arena, returning the arena-owned slice
+    ///
+    /// The slice borrows the arena, which frees it on drop.
+    pub fn alloc_slice_copy<T: Copy>(&mut self, values: &[T]) -> Result<&mut [T], crate::Error<core::alloc::LayoutError>> {
+        let p = self.alloc_slice::<T>(values.len())?;
+        unsafe {
+            (p as *mut T).copy_from_nonoverlapping(values.as_ptr(), values.len());
+            Ok(&mut *p)
+        }
+    }
+
+    /// Copy `s` into the arena, returning the arena-owned string
+    ///
+    /// The string borrows the arena, which frees it on drop:
+    ///
+    /// ```compile_fail
+    /// # use protocrap::arena::Arena;
+    /// # use allocator_api2::alloc::Global;
+    /// let mut arena = Arena::new(&Global);
+    /// let name = arena.alloc_str("world").unwrap();
+    /// drop(arena);
+    /// assert_eq!(name, "world");
+    /// ```
+    pub fn alloc_str(&mut self, s: &str) -> Result<&mut str, crate::Error<core::alloc::LayoutError>> {
+        let bytes = self.alloc_slice_copy(s.as_bytes())?;
+        // Safety: the bytes were copied from a valid str
+        Ok(unsafe { core::str::from_utf8_unchecked_mut(bytes) })
+    }
+
     /// Allocate raw memory with given size and alignment (uninitialized)
+    ///
+    /// Any power-of-two alignment is honored, including alignments larger than
+    /// the arena's block header, both in the current block and in fresh blocks.
     #[inline]
     pub fn alloc_raw(&mut self, layout: Layout) -> Result<NonNull<u8>, crate::Error<core::alloc::LayoutError>> {
         let size = layout.size();
         let align = layout.align();
+        self.stats.allocations += 1;
+        self.stats.bytes_requested += size;
+        if let Some(observer) = self.observer {
+            observer(layout);
+        }
 
-        // Align the cursor to the required alignment
-        let cursor_addr = self.cursor as usize;
-        let aligned_addr = (cursor_addr + align - 1) & !(align - 1);
-        let aligned_cursor = aligned_addr as *mut u8;
-
-        // Check if we have enough space: end - aligned_cursor >= size
-        let available = self.end as isize - aligned_cursor as isize;
-        if crate::utils::likely(available >= size as isize) {
+        // Align the cursor to the required alignment, keeping pointer provenance
+        let padding = (self.cursor as usize).wrapping_neg() & (align - 1);
+        let available = self.end as usize - self.cursor as usize;
+        if crate::utils::likely(available >= padding && available - padding >= size) {
             // Fits in current block - use it regardless of size
+            let aligned_cursor = unsafe { self.cursor.add(padding) };
             self.cursor = unsafe { aligned_cursor.add(size) };
             return unsafe { Ok(NonNull::new_unchecked(aligned_cursor)) };
         }
 
         // Doesn't fit - need new allocation strategy
-        self.alloc_outlined(layout, available as usize).ok_or(crate::Error::ArenaAllocationFailed)
+        self.alloc_outlined(layout, available.saturating_sub(padding)).ok_or(crate::Error::ArenaAllocationFailed)
     }
 
-    /// Get total bytes allocated by this arena
-    pub fn bytes_allocated(&self) -> usize {
-        let mut total = 0;
+    /// Whether `ptr` points into memory handed out by this arena.
+    ///
+    /// Walks the arena's blocks, so it takes time linear in their number.
+    pub fn contains<T>(&self, ptr: *const T) -> bool {
+        let addr = ptr as usize;
         let mut current = self.current;
-
-        unsafe {
-            while !current.is_null() {
-                total += (*current).layout.size();
+        while !current.is_null() {
+            unsafe {
+                let start = current as usize;
+                if addr >= start && addr < start + (*current).layout.size() {
+                    return true;
+                }
                 current = (*current).prev;
             }
         }
+        false
+    }
+
+    /// Identifies this arena among all arenas created by the process.
+    #[inline]
+    pub(crate) fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Get total bytes allocated by this arena
+    pub fn bytes_allocated(&self) -> usize {
+        self.stats.bytes_reserved
+    }
 
-        total
+    fn add_reserved(&mut self, bytes: usize) {
+        self.stats.bytes_reserved += bytes;
+        self.stats.high_water_mark = self.stats.high_water_mark.max(self.stats.bytes_reserved);
     }
 
     /// Allocate a new memory block - never inlined to keep fast path small
@@ -168,30 +348,42 @@ impl<'a> Arena<'a> {
 
     /// Allocate a new memory block
     fn allocate_new_block(&mut self, alloc_layout: Layout) -> Option<NonNull<u8>> {
-        let Some(allocator) = self.allocator else {
-            return None;
-        };
-
-        // Calculate block size - grow exponentially but respect min_size
+        self.allocator?;
 
         let (layout, offset) = Layout::new::<MemBlock>()
             .extend(alloc_layout)
             .expect("Layout overflow");
         let layout = layout.pad_to_align();
 
-        let new_block_size = if self.current.is_null() {
-            DEFAULT_BLOCK_SIZE
-        } else {
-            let current_block_size = unsafe { (*self.current).layout.size() };
-            (current_block_size * 2).min(MAX_BLOCK_SIZE)
-        };
-
+        let new_block_size = self.grow();
         let (layout, block_start) = layout
             .extend(Layout::array::<u8>(new_block_size).expect("Layout overflow"))
             .expect("Layout overflow");
-        let layout = layout.pad_to_align();
 
+        let block = self.push_block(layout.pad_to_align())?;
+        unsafe {
+            self.cursor = block.add(block_start);
+            Some(NonNull::new_unchecked(block.add(offset)))
+        }
+    }
+
+    /// Bump space for the next block, advancing the growth policy.
+    fn grow(&mut self) -> usize {
+        let size = self.next_block_size;
+        self.next_block_size = match self.growth {
+            GrowthPolicy::Fixed => size,
+            GrowthPolicy::Doubling => size.saturating_mul(2),
+            GrowthPolicy::Capped(max) => size.saturating_mul(2).min(max).max(size),
+        };
+        size
+    }
+
+    /// Take a block of `layout` from the allocator and make it the active
+    /// block, with the cursor right after the header.
+    fn push_block(&mut self, layout: Layout) -> Option<*mut u8> {
+        let allocator = self.allocator?;
         let ptr = allocator.allocate(layout).ok()?.as_ptr() as *mut MemBlock;
+        self.add_reserved(layout.size());
 
         unsafe {
             // Initialize the MemBlock header
@@ -200,9 +392,9 @@ impl<'a> Arena<'a> {
 
             // Update arena state - this becomes the new active block
             self.current = ptr;
-            self.cursor = (ptr as *mut u8).add(block_start);
+            self.cursor = (ptr as *mut u8).add(core::mem::size_of::<MemBlock>());
             self.end = (ptr as *mut u8).add(layout.size());
-            Some(NonNull::new_unchecked((ptr as *mut u8).add(offset)))
+            Some(ptr as *mut u8)
         }
     }
 
@@ -218,6 +410,7 @@ impl<'a> Arena<'a> {
         let final_layout = extended_layout.pad_to_align();
 
         let ptr = allocator.allocate(final_layout).ok()?.as_ptr() as *mut MemBlock;
+        self.add_reserved(final_layout.size());
 
         unsafe {
             (*ptr).layout = final_layout;
@@ -231,7 +424,7 @@ impl<'a> Arena<'a> {
                 // No blocks yet, this becomes the only block
                 (*ptr).prev = ptr::null_mut();
                 self.current = ptr;
-                // Still no active bump allocation (cursor/end remain null)
+                // Still no active bump allocation (cursor/end remain empty)
             }
 
             // Return aligned data pointer after header
@@ -265,6 +458,12 @@ impl<'a> Drop for Arena<'a> {
 // Safety: Arena can be sent between threads if the allocator supports it
 unsafe impl<'a> Send for Arena<'a> where &'a dyn Allocator: Send {}
 
+static NEXT_ARENA_ID: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(1);
+
+fn next_arena_id() -> usize {
+    NEXT_ARENA_ID.fetch_add(1, core::sync::atomic::Ordering::Relaxed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -335,4 +534,123 @@ mod tests {
             assert_eq!(large_slice[large_slice.len() - 1], 2);
         }
     }
+
+    #[test]
+    fn test_over_aligned_allocation() {
+        #[repr(align(32))]
+        struct Simd {
+            _lanes: [u8; 32],
+        }
+
+        let mut arena = Arena::new(&Global);
+        let _u8_ptr: *mut u8 = arena.alloc().unwrap();
+        let simd: *mut Simd = arena.alloc().unwrap();
+        assert_eq!(simd as usize % 32, 0);
+
+        for align in [16, 32, 64, 4096] {
+            // Small requests bump in the current block, large ones get a dedicated block
+            for size in [1, 3 * DEFAULT_BLOCK_SIZE] {
+                let _u8_ptr: *mut u8 = arena.alloc().unwrap();
+                let layout = Layout::from_size_align(size, align).unwrap();
+                let ptr = arena.alloc_raw(layout).unwrap();
+                assert_eq!(ptr.as_ptr() as usize % align, 0);
+            }
+        }
+
+        let zero_sized = arena.alloc_raw(Layout::from_size_align(0, 64).unwrap()).unwrap();
+        assert_eq!(zero_sized.as_ptr() as usize % 64, 0);
+    }
+
+    #[test]
+    fn test_from_slice_alignment() {
+        let mut buffer = [0u64; 128];
+        // Deliberately misalign the backing memory
+        let bytes = unsafe { core::slice::from_raw_parts_mut((buffer.as_mut_ptr() as *mut u8).add(1), 1000) };
+        let mut arena = Arena::from_slice(bytes);
+
+        let value = arena.place(7u64).unwrap();
+        assert_eq!(*value, 7);
+        assert_eq!(value as *mut u64 as usize % 8, 0);
+        let ptr = arena.alloc_raw(Layout::from_size_align(8, 16).unwrap()).unwrap();
+        assert_eq!(ptr.as_ptr() as usize % 16, 0);
+    }
+
+    #[test]
+    fn test_typed_slices() {
+        let mut arena = Arena::new(&Global);
+
+        let numbers = arena.alloc_slice_copy(&[1u32, 2, 3]).unwrap();
+        numbers[1] = 20;
+        assert_eq!(numbers, &[1, 20, 3]);
+
+        let s = arena.alloc_str("hello arena").unwrap();
+        s.make_ascii_uppercase();
+        assert_eq!(s, "HELLO ARENA");
+
+        assert!(arena.alloc_slice_copy::<u64>(&[]).unwrap().is_empty());
+        assert_eq!(arena.alloc_str("").unwrap(), "");
+    }
+
+    #[test]
+    fn test_stats_and_observer() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static OBSERVED: AtomicUsize = AtomicUsize::new(0);
+        let observer = |layout: Layout| {
+            OBSERVED.fetch_add(layout.size(), Ordering::Relaxed);
+        };
+        let mut arena = Arena::new(&Global);
+        assert_eq!(arena.stats(), ArenaStats::default());
+        arena.set_observer(&observer);
+
+        let _: *mut u64 = arena.alloc().unwrap();
+        arena.alloc_str("hello").unwrap();
+        let stats = arena.stats();
+        assert_eq!(stats.allocations, 2);
+        assert_eq!(stats.bytes_requested, 13);
+        assert_eq!(OBSERVED.load(Ordering::Relaxed), 13);
+        assert!(stats.bytes_reserved >= DEFAULT_BLOCK_SIZE);
+        assert_eq!(stats.bytes_reserved, arena.bytes_allocated());
+
+        let _: *mut [u8] = arena.alloc_slice(DEFAULT_BLOCK_SIZE * 2).unwrap();
+        let stats = arena.stats();
+        assert_eq!(stats.allocations, 3);
+        assert!(stats.bytes_reserved >= 3 * DEFAULT_BLOCK_SIZE);
+        assert_eq!(stats.high_water_mark, stats.bytes_reserved);
+    }
+
+    #[test]
+    fn test_block_sizing() {
+        let header = core::mem::size_of::<MemBlock>();
+        let block = |growth| {
+            let mut arena = Arena::with_block_size(&Global, 1024);
+            arena.set_growth_policy(growth);
+            // The third request no longer fits the first block
+            for _ in 0..3 {
+                let _: *mut [u8] = arena.alloc_slice(600).unwrap();
+            }
+            arena.bytes_allocated() - (header + 600 + 1024)
+        };
+        assert_eq!(block(GrowthPolicy::Fixed), header + 600 + 1024);
+        assert_eq!(block(GrowthPolicy::Doubling), header + 600 + 2048);
+        assert_eq!(block(GrowthPolicy::Capped(1536)), header + 600 + 1536);
+
+        // A reservation is served without going back to the allocator
+        let mut arena = Arena::new(&Global);
+        arena.reserve(1 << 20).unwrap();
+        let reserved = arena.bytes_allocated();
+        assert!(reserved >= 1 << 20);
+        for _ in 0..1000 {
+            let _: *mut [u64] = arena.alloc_slice(128).unwrap();
+        }
+        assert_eq!(arena.bytes_allocated(), reserved);
+        arena.reserve(16).unwrap();
+        assert_eq!(arena.bytes_allocated(), reserved);
+
+        let mut buffer = [0u64; 64];
+        let bytes = unsafe { core::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, 512) };
+        let mut arena = Arena::from_slice(bytes);
+        arena.reserve(256).unwrap();
+        assert!(matches!(arena.reserve(1024), Err(crate::Error::ArenaAllocationFailed)));
+    }
 }
@@ -0,0 +1,237 @@
+//! Export of repeated fields to Apache Arrow.
+//!
+//! [`repeated_array`] turns a repeated scalar field into an Arrow array and
+//! [`message_batch`] turns a repeated message field into a [`RecordBatch`]
+//! with a column per scalar field, so analytics pipelines can go from
+//! protobuf to columnar processing without looping over values themselves.
+//!
+//! Arrow arrays own their buffers while arena data lives only as long as the
+//! arena, so values are copied. Numeric and `bool` fields are copied in one
+//! go, strings and bytes into a single contiguous value buffer.
+//!
+//! | protobuf                                 | Arrow       |
+//! |------------------------------------------|-------------|
+//! | `int32`, `sint32`, `sfixed32`, enums     | `Int32`     |
+//! | `int64`, `sint64`, `sfixed64`            | `Int64`     |
+//! | `uint32`, `fixed32`                      | `UInt32`    |
+//! | `uint64`, `fixed64`                      | `UInt64`    |
+//! | `float`                                  | `Float32`   |
+//! | `double`                                 | `Float64`   |
+//! | `bool`                                   | `Boolean`   |
+//! | `string`                                 | `Utf8`      |
+//! | `bytes`                                  | `Binary`    |
+
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, BinaryArray, BooleanArray, Float32Array, Float64Array, Int32Array, Int64Array,
+    RecordBatch, StringArray, UInt32Array, UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+
+use crate::google::protobuf::FieldDescriptorProto::ProtoType as FieldDescriptorProto;
+use crate::google::protobuf::FieldDescriptorProto::Type;
+use crate::reflection::{DynamicMessageRef, Value, is_message, is_repeated};
+use crate::{Error, ProtobufRef};
+
+/// The repeated scalar field `field_name` of `msg` as an Arrow array, empty
+/// if the field is.
+///
+/// Fails with [`Error::MessageNotFound`] if `msg` has no repeated scalar
+/// field of that name.
+pub fn repeated_array<'pool>(
+    msg: &impl ProtobufRef<'pool>,
+    field_name: &str,
+) -> Result<ArrayRef, Error> {
+    let msg = msg.as_dyn();
+    let field = msg
+        .find_field_descriptor(field_name)
+        .filter(|field| is_repeated(field) && !is_message(field))
+        .ok_or(Error::MessageNotFound)?;
+    let array: ArrayRef = match msg.get_field(field) {
+        Some(Value::RepeatedInt32(values)) => Arc::new(Int32Array::from(values.to_vec())),
+        Some(Value::RepeatedInt64(values)) => Arc::new(Int64Array::from(values.to_vec())),
+        Some(Value::RepeatedUInt32(values)) => Arc::new(UInt32Array::from(values.to_vec())),
+        Some(Value::RepeatedUInt64(values)) => Arc::new(UInt64Array::from(values.to_vec())),
+        Some(Value::RepeatedFloat(values)) => Arc::new(Float32Array::from(values.to_vec())),
+        Some(Value::RepeatedDouble(values)) => Arc::new(Float64Array::from(values.to_vec())),
+        Some(Value::RepeatedBool(values)) => Arc::new(BooleanArray::from(values.to_vec())),
+        Some(Value::RepeatedString(values)) => {
+            Arc::new(StringArray::from_iter_values(values.iter().map(|s| s.as_str())))
+        }
+        Some(Value::RepeatedBytes(values)) => {
+            Arc::new(BinaryArray::from_iter_values(values.iter().map(|b| b.slice())))
+        }
+        _ => arrow_array::new_empty_array(&data_type(field).ok_or(Error::MessageNotFound)?),
+    };
+    Ok(array)
+}
+
+/// The repeated message field `field_name` of `msg` as a record batch, with a
+/// row per element and a column per singular scalar field of the element
+/// type, named after the field.
+///
+/// Unset fields are null, fields without presence never are. Repeated and
+/// message fields of the elements are left out. Map fields give a batch of
+/// `key` and `value` columns, provided the values are scalars.
+///
+/// Fails with [`Error::MessageNotFound`] if `msg` has no repeated message
+/// field of that name.
+pub fn message_batch<'pool>(
+    msg: &impl ProtobufRef<'pool>,
+    field_name: &str,
+) -> Result<RecordBatch, Error<ArrowError>> {
+    let msg = msg.as_dyn();
+    let field = msg
+        .find_field_descriptor(field_name)
+        .filter(|field| is_repeated(field) && is_message(field))
+        .ok_or(Error::MessageNotFound)?;
+    let rows: Vec<DynamicMessageRef> = match msg.get_field(field) {
+        Some(Value::RepeatedMessage(array)) => (0..array.len()).map(|i| array.get(i)).collect(),
+        _ => Vec::new(),
+    };
+    let entry = msg.table.field_entry(field.number() as u32).ok_or(Error::MessageNotFound)?;
+    let descriptor = msg.table.aux_entry_decode(entry).1.descriptor();
+
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for column in descriptor.field() {
+        if is_repeated(column) || is_message(column) {
+            continue;
+        }
+        let Some(data_type) = data_type(column) else {
+            continue;
+        };
+        let values = rows.iter().map(|row| row.get_field(column));
+        fields.push(Field::new(column.name(), data_type, true));
+        columns.push(column_array(column, values));
+    }
+    let schema = Arc::new(Schema::new(fields));
+    let options = arrow_array::RecordBatchOptions::new().with_row_count(Some(rows.len()));
+    Ok(RecordBatch::try_new_with_options(schema, columns, &options)?)
+}
+
+/// Arrow type of the values of `field`.
+fn data_type(field: &FieldDescriptorProto) -> Option<DataType> {
+    Some(match field.r#type()? {
+        Type::TYPE_INT32 | Type::TYPE_SINT32 | Type::TYPE_SFIXED32 | Type::TYPE_ENUM => {
+            DataType::Int32
+        }
+        Type::TYPE_INT64 | Type::TYPE_SINT64 | Type::TYPE_SFIXED64 => DataType::Int64,
+        Type::TYPE_UINT32 | Type::TYPE_FIXED32 => DataType::UInt32,
+        Type::TYPE_UINT64 | Type::TYPE_FIXED64 => DataType::UInt64,
+        Type::TYPE_FLOAT => DataType::Float32,
+        Type::TYPE_DOUBLE => DataType::Float64,
+        Type::TYPE_BOOL => DataType::Boolean,
+        Type::TYPE_STRING => DataType::Utf8,
+        Type::TYPE_BYTES => DataType::Binary,
+        Type::TYPE_MESSAGE | Type::TYPE_GROUP => return None,
+    })
+}
+
+/// A column of the singular scalar field `field`, one value per row.
+fn column_array<'pool, 'msg>(
+    field: &FieldDescriptorProto,
+    values: impl Iterator<Item = Option<Value<'pool, 'msg>>>,
+) -> ArrayRef {
+    macro_rules! column {
+        ($array:ty, $variant:ident) => {
+            Arc::new(
+                values
+                    .map(|value| match value {
+                        Some(Value::$variant(v)) => Some(v),
+                        _ => None,
+                    })
+                    .collect::<$array>(),
+            )
+        };
+    }
+    match field.r#type().unwrap() {
+        Type::TYPE_INT32 | Type::TYPE_SINT32 | Type::TYPE_SFIXED32 | Type::TYPE_ENUM => {
+            column!(Int32Array, Int32)
+        }
+        Type::TYPE_INT64 | Type::TYPE_SINT64 | Type::TYPE_SFIXED64 => column!(Int64Array, Int64),
+        Type::TYPE_UINT32 | Type::TYPE_FIXED32 => column!(UInt32Array, UInt32),
+        Type::TYPE_UINT64 | Type::TYPE_FIXED64 => column!(UInt64Array, UInt64),
+        Type::TYPE_FLOAT => column!(Float32Array, Float),
+        Type::TYPE_DOUBLE => column!(Float64Array, Double),
+        Type::TYPE_BOOL => column!(BooleanArray, Bool),
+        Type::TYPE_STRING => column!(StringArray, String),
+        Type::TYPE_BYTES => column!(BinaryArray, Bytes),
+        Type::TYPE_MESSAGE | Type::TYPE_GROUP => unreachable!("message fields have no column"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::Arena;
+    use crate::google::protobuf::{DescriptorProto, FileDescriptorProto};
+    use arrow_array::Array;
+
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    #[test]
+    fn repeated_scalars() {
+        let mut arena = Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        for dependency in [1, -2, 3] {
+            file.public_dependency_mut().push(dependency, &mut arena).unwrap();
+        }
+        for name in ["a.proto", "b.proto"] {
+            file.dependency_mut()
+                .push(crate::containers::String::from_str(name, &mut arena).unwrap(), &mut arena)
+                .unwrap();
+        }
+
+        let array = repeated_array(&file, "public_dependency").unwrap();
+        let ints = array.as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(ints.values(), &[1, -2, 3]);
+        let array = repeated_array(&file, "dependency").unwrap();
+        let strings = array.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(strings.iter().collect::<Vec<_>>(), [Some("a.proto"), Some("b.proto")]);
+
+        let empty = repeated_array(&file, "weak_dependency").unwrap();
+        assert_eq!((empty.len(), empty.data_type()), (0, &DataType::Int32));
+        for name in ["name", "message_type", "missing"] {
+            assert!(matches!(repeated_array(&file, name), Err(Error::MessageNotFound)));
+        }
+    }
+
+    #[test]
+    fn repeated_messages() {
+        let mut arena = Arena::new(&Global);
+        let mut message = DescriptorProto::ProtoType::default();
+        let range = message.add_reserved_range(&mut arena).unwrap();
+        range.set_start(1);
+        range.set_end(5);
+        let range = message.add_reserved_range(&mut arena).unwrap();
+        range.set_start(9);
+
+        let batch = message_batch(&message, "reserved_range").unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let column = |name| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(column("start"), [Some(1), Some(9)]);
+        assert_eq!(column("end"), [Some(5), None]);
+
+        // No rows, the columns still follow FieldDescriptorProto
+        let batch = message_batch(&message, "field").unwrap();
+        assert_eq!(batch.num_rows(), 0);
+        assert_eq!(batch.schema().field_with_name("name").unwrap().data_type(), &DataType::Utf8);
+        assert!(batch.schema().field_with_name("options").is_err());
+
+        assert!(matches!(message_batch(&message, "name"), Err(Error::MessageNotFound)));
+    }
+}
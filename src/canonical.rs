@@ -0,0 +1,97 @@
+//! Canonical re-encoding of binary protobuf payloads.
+//!
+//! Equal messages can be encoded in many ways: fields in any order, map
+//! entries in any order or repeated, non-minimal varints. [`canonicalize`]
+//! maps all of them onto a single encoding, so payloads can be hashed for
+//! deduplication or signing.
+//!
+//! The canonical form has fields in ascending field-number order, map entries
+//! deduplicated (last wins) and sorted by key, and minimal varints.
+//!
+//! protocrap doesn't keep unknown fields when decoding, so payloads with
+//! fields the schema doesn't declare, or closed enum values their enum
+//! doesn't declare, are refused rather than canonicalized without them:
+//! payloads that differ only there would otherwise get the same bytes, and so
+//! the same hash or signature.
+
+use crate::arena::Arena;
+use crate::base::Object;
+use crate::reflection::DynamicMessage;
+use crate::tables::Table;
+use crate::{DecodeOptions, EncodeOptions, Error, ProtobufMut, ProtobufRef};
+use alloc::vec::Vec;
+
+#[cfg(not(feature = "nightly"))]
+use allocator_api2::alloc::Global;
+#[cfg(feature = "nightly")]
+use alloc::alloc::Global;
+
+/// Stack depth used to decode and re-encode payloads.
+const STACK_DEPTH: usize = 64;
+
+/// Decode `buf` as a message of type `table` and re-encode it in canonical form.
+///
+/// Tables come from `Protobuf::table()` of generated types or from
+/// [`DescriptorPool::get_table`](crate::descriptor_pool::DescriptorPool::get_table).
+/// Fails with [`Error::UnknownFields`] if `buf`, or any message in it, has
+/// fields `table` doesn't declare, including fields of a known number sent
+/// with a wire type other than the schema's, or values of a closed enum
+/// field its enum doesn't declare.
+pub fn canonicalize(buf: &[u8], table: &Table) -> Result<Vec<u8>, Error> {
+    let mut arena = Arena::new(&Global);
+    let object =
+        Object::create(table.size as u32, &mut arena).map_err(|_| Error::ArenaAllocationFailed)?;
+    let mut msg = DynamicMessage { object, table };
+    let unknown = msg
+        .decode_flat_with_stats::<STACK_DEPTH>(&mut arena, buf, DecodeOptions::default())
+        .ok_or(Error::InvalidProtobufData)?;
+    if unknown.fields != 0 {
+        return Err(Error::UnknownFields);
+    }
+    msg.sort_maps();
+    msg.encode_vec_with_options::<STACK_DEPTH>(EncodeOptions {
+        canonical_field_order: true,
+    })
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::generated_code_only::Protobuf;
+    use crate::google::protobuf::{FieldDescriptorProto, FileDescriptorProto};
+
+    #[test]
+    fn canonical_form() {
+        let table = FileDescriptorProto::ProtoType::table();
+        // package: "p" (field 2) before name: "a" (field 1)
+        let scrambled = [
+            0x12, 0x01, b'p', //
+            0x0a, 0x01, b'a',
+        ];
+        let canonical = canonicalize(&scrambled, table).unwrap();
+        assert_eq!(canonical, [0x0a, 0x01, b'a', 0x12, 0x01, b'p']);
+        assert_eq!(canonicalize(&canonical, table).unwrap(), canonical);
+
+        // A length prefix with a redundant continuation byte
+        let non_minimal = [0x0a, 0x81, 0x00, b'a', 0x12, 0x01, b'p'];
+        assert_eq!(canonicalize(&non_minimal, table).unwrap(), canonical);
+
+        assert!(matches!(
+            canonicalize(&[0x0a, 0x05], table),
+            Err(Error::InvalidProtobufData)
+        ));
+
+        // Unknown field 1000 as a varint, and name (field 1) as a varint
+        for unknown in [&[0xc0, 0x3e, 0x01][..], &[0x08, 0x01]] {
+            let buf = [&canonical[..], unknown].concat();
+            assert!(matches!(canonicalize(&buf, table), Err(Error::UnknownFields)));
+        }
+
+        // descriptor.proto is proto2, so its enums are closed: type 99 would
+        // otherwise be dropped, leaving the bytes of a field without a type
+        let table = FieldDescriptorProto::ProtoType::table();
+        let typed = [0x0a, 0x01, b'a', 0x28, 0x63];
+        assert!(matches!(canonicalize(&typed, table), Err(Error::UnknownFields)));
+        assert_eq!(canonicalize(&typed[..3], table).unwrap(), typed[..3]);
+    }
+}
@@ -5,6 +5,7 @@
 //! - [`RepeatedField<T>`]: A growable array for repeated fields
 //! - [`String`]: UTF-8 string (equivalent to protobuf `string`)
 //! - [`Bytes`]: Byte array (equivalent to protobuf `bytes`)
+//! - [`ProtoMap<K, V>`]: Hash map for fast lookups in protobuf `map` fields
 //!
 //! These types are designed for arena allocation and do not implement `Drop`.
 //! Memory is freed when the arena is dropped.
@@ -62,55 +63,42 @@ impl RawVec {
     }
 
     #[inline(always)]
-    fn grow(&mut self, new_cap: usize, layout: Layout, arena: &mut crate::arena::Arena) -> Result<(), crate::Error<core::alloc::LayoutError>> {
-        let RawVecGrown { ptr, cap } = self.grow_outline(new_cap, layout, arena)?;
+    fn grow(&mut self, len: usize, new_cap: usize, layout: Layout, arena: &mut crate::arena::Arena) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+        let RawVecGrown { ptr, cap } = self.grow_outline(len, new_cap, layout, arena)?;
         self.ptr = ptr.as_ptr();
         self.cap = cap;
         Ok(())
     }
 
     #[inline(never)]
-    fn grow_outline(self, new_cap: usize, layout: Layout, arena: &mut crate::arena::Arena) -> Result<RawVecGrown, crate::Error<core::alloc::LayoutError>> {
+    fn grow_outline(self, len: usize, new_cap: usize, layout: Layout, arena: &mut crate::arena::Arena) -> Result<RawVecGrown, crate::Error<core::alloc::LayoutError>> {
         // since we set the capacity to usize::MAX when T has size 0,
         // getting to here necessarily means the Vec is overfull.
         assert!(layout.size() != 0, "capacity overflow");
 
-        let (new_cap, new_layout) = if self.cap == 0 {
-            if new_cap == 0 {
-                (1, layout)
-            } else {
-                let new_layout =
-                    Layout::from_size_align(layout.size() * new_cap, layout.align())?;
-                (new_cap, new_layout)
-            }
+        // Borrowed static data has no capacity but `len` elements, so the new
+        // buffer must hold at least those.
+        // This can't overflow because we ensure self.cap <= isize::MAX.
+        let new_cap = if new_cap == 0 {
+            (2 * self.cap).max(2 * len).max(1)
         } else {
-            // This can't overflow because we ensure self.cap <= isize::MAX.
-            let new_cap = if new_cap == 0 {
-                2 * self.cap
-            } else {
-                assert!(new_cap > self.cap);
-                new_cap
-            };
-
-            let new_layout =
-                Layout::from_size_align(layout.size() * new_cap, layout.align())?;
-
-            (new_cap, new_layout)
+            assert!(new_cap > self.cap);
+            new_cap.max(len)
         };
 
+        let new_layout =
+            Layout::from_size_align(layout.size() * new_cap, layout.align())?;
+
         // Ensure that the new allocation doesn't exceed `isize::MAX` bytes.
         assert!(
             new_layout.size() <= isize::MAX as usize,
             "Allocation too large"
         );
 
-        let new_ptr = if self.cap == 0 {
-            arena.alloc_raw(new_layout)?
-        } else {
-            let new_ptr = arena.alloc_raw(new_layout)?;
-            unsafe { core::ptr::copy_nonoverlapping(self.ptr, new_ptr.as_ptr(), layout.size() * self.cap) };
-            new_ptr
-        };
+        let new_ptr = arena.alloc_raw(new_layout)?;
+        if len != 0 {
+            unsafe { core::ptr::copy_nonoverlapping(self.ptr, new_ptr.as_ptr(), layout.size() * len) };
+        }
 
         Ok(RawVecGrown { ptr: new_ptr, cap: new_cap })
     }
@@ -129,9 +117,9 @@ impl RawVec {
     }
 
     #[inline(always)]
-    pub fn reserve(&mut self, new_cap: usize, layout: Layout, arena: &mut crate::arena::Arena) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+    pub fn reserve(&mut self, len: usize, new_cap: usize, layout: Layout, arena: &mut crate::arena::Arena) -> Result<(), crate::Error<core::alloc::LayoutError>> {
         if new_cap > self.cap {
-            self.grow(new_cap, layout, arena)?;
+            self.grow(len, new_cap, layout, arena)?;
         }
         Ok(())
     }
@@ -141,6 +129,10 @@ impl RawVec {
 
 /// Like `Vec<T>` but arena-allocated and never drops elements.
 /// Only suitable for trivial (Copy) types.
+///
+/// A field made with [`from_static`](Self::from_static) borrows its elements
+/// instead of copying them. They are copied into the arena on the first `push`,
+/// `insert` or `append`; changing the borrowed elements in place panics.
 #[repr(C)]
 pub struct RepeatedField<T> {
     buf: RawVec,
@@ -207,11 +199,13 @@ impl<T> RepeatedField<T> {
         Ok(rf)
     }
 
+    /// Borrow `slice` without copying it. The field has no capacity of its
+    /// own, so growing it copies the elements into the arena first.
     pub const fn from_static(slice: &'static [T]) -> Self {
         RepeatedField {
             buf: RawVec {
                 ptr: slice.as_ptr() as *mut u8,
-                cap: slice.len(),
+                cap: 0,
             },
             len: slice.len(),
             phantom: PhantomData,
@@ -220,7 +214,7 @@ impl<T> RepeatedField<T> {
 
     #[inline(always)]
     pub const fn slice(&self) -> &[T] {
-        if self.cap() == 0 {
+        if self.len == 0 {
             &[]
         } else {
             unsafe { core::slice::from_raw_parts(self.ptr(), self.len) }
@@ -229,18 +223,46 @@ impl<T> RepeatedField<T> {
 
     #[inline(always)]
     pub fn slice_mut(&mut self) -> &mut [T] {
-        if self.cap() == 0 {
+        if self.len == 0 {
             &mut []
+        } else if self.is_static() {
+            panic!("cannot modify borrowed static data in place")
         } else {
             unsafe { core::slice::from_raw_parts_mut(self.ptr(), self.len) }
         }
     }
 
+    /// The number of elements the field holds before it has to grow, like
+    /// [`Vec::capacity`].
+    #[inline(always)]
+    pub const fn capacity(&self) -> usize {
+        self.cap()
+    }
+
+    /// Whether the elements are borrowed static data, see
+    /// [`from_static`](Self::from_static).
+    #[inline(always)]
+    pub const fn is_static(&self) -> bool {
+        self.cap() == 0 && self.len != 0
+    }
+
+    /// The elements as a slice, like [`Vec::as_slice`].
+    #[inline(always)]
+    pub const fn as_slice(&self) -> &[T] {
+        self.slice()
+    }
+
+    /// The elements as a mutable slice, like [`Vec::as_mut_slice`].
+    #[inline(always)]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self.slice_mut()
+    }
+
     #[inline(always)]
     pub fn push(&mut self, elem: T, arena: &mut crate::arena::Arena) -> Result<&mut T, crate::Error<core::alloc::LayoutError>> {
         let l = self.len;
-        if l == self.cap() {
-            self.buf.grow(0, Layout::new::<T>(), arena)?;
+        if l >= self.cap() {
+            self.buf.grow(l, 0, Layout::new::<T>(), arena)?;
         }
         let res = unsafe {
             let p = self.ptr().add(l); 
@@ -266,8 +288,8 @@ impl<T> RepeatedField<T> {
     pub fn insert(&mut self, index: usize, elem: T, arena: &mut crate::arena::Arena) -> Result<(), crate::Error<core::alloc::LayoutError>> {
         assert!(index <= self.len, "index out of bounds");
         let len = self.len;
-        if len == self.cap() {
-            self.buf.grow(0, Layout::new::<T>(), arena)?;
+        if len >= self.cap() {
+            self.buf.grow(len, 0, Layout::new::<T>(), arena)?;
         }
 
         unsafe {
@@ -287,6 +309,7 @@ impl<T> RepeatedField<T> {
     pub fn remove(&mut self, index: usize) -> T {
         let len = self.len;
         assert!(index < len, "index out of bounds");
+        assert!(!self.is_static(), "cannot modify borrowed static data in place");
 
         let len = len - 1;
 
@@ -307,9 +330,21 @@ impl<T> RepeatedField<T> {
         self.len = 0
     }
 
+    /// Shortens the field to `len` elements. Has no effect if `len` is greater
+    /// than the current length.
+    #[inline(always)]
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.len = len;
+        }
+    }
+
+    /// Make room for `new_cap` elements in total, unlike [`Vec::reserve`]
+    /// which takes the number of additional elements. Reserving before a run
+    /// of `push`es avoids growing and copying the field in the arena.
     #[inline(always)]
     pub fn reserve(&mut self, new_cap: usize, arena: &mut crate::arena::Arena) -> Result<(), crate::Error<core::alloc::LayoutError>> {
-        self.buf.reserve(new_cap, Layout::new::<T>(), arena)
+        self.buf.reserve(self.len, new_cap, Layout::new::<T>(), arena)
     }
 
     #[inline(always)]
@@ -321,6 +356,17 @@ impl<T> RepeatedField<T> {
         self.append(slice, arena)
     }
 
+    /// Replace the contents with a copy of `slice`, growing the buffer at most
+    /// once. Same as [`assign`](Self::assign); unlike `<[T]>::copy_from_slice`
+    /// the lengths don't need to match.
+    #[inline(always)]
+    pub fn copy_from_slice(&mut self, slice: &[T], arena: &mut crate::arena::Arena) -> Result<(), crate::Error<core::alloc::LayoutError>>
+    where
+        T: Copy,
+    {
+        self.assign(slice, arena)
+    }
+
     #[inline(always)]
     pub fn append(&mut self, slice: &[T], arena: &mut crate::arena::Arena) -> Result<(), crate::Error<core::alloc::LayoutError>>
     where
@@ -336,6 +382,27 @@ impl<T> RepeatedField<T> {
         self.len = old_len + slice.len();
         Ok(())
     }
+
+    /// Append the elements whose in-memory representations are packed in
+    /// `bytes`, which need not be aligned. A trailing partial element is
+    /// ignored.
+    #[inline(always)]
+    pub(crate) fn append_bytes(&mut self, bytes: &[u8], arena: &mut crate::arena::Arena) -> Result<(), crate::Error<core::alloc::LayoutError>>
+    where
+        T: Copy,
+    {
+        let count = bytes.len() / core::mem::size_of::<T>();
+        let old_len = self.len;
+        self.reserve(old_len + count, arena)?;
+        unsafe {
+            self.ptr()
+                .add(old_len)
+                .cast::<u8>()
+                .copy_from_nonoverlapping(bytes.as_ptr(), count * core::mem::size_of::<T>());
+        }
+        self.len = old_len + count;
+        Ok(())
+    }
 }
 
 impl<T> Deref for RepeatedField<T> {
@@ -405,3 +472,399 @@ impl Deref for String {
         self.as_str()
     }
 }
+
+impl RepeatedField<String> {
+    /// Copy the strings of an iterator into a new field, like a repeated
+    /// `push` of each.
+    pub fn from_iter<I>(iter: I, arena: &mut crate::arena::Arena) -> Result<Self, crate::Error<core::alloc::LayoutError>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let iter = iter.into_iter();
+        let mut rf = Self::new();
+        rf.reserve(iter.size_hint().0, arena)?;
+        for s in iter {
+            rf.push(String::from_str(s.as_ref(), arena)?, arena)?;
+        }
+        Ok(rf)
+    }
+
+    /// Copy the strings out of the arena.
+    #[cfg(feature = "alloc")]
+    pub fn to_vec(&self) -> alloc::vec::Vec<alloc::string::String> {
+        self.iter().map(|s| s.as_str().into()).collect()
+    }
+}
+
+impl RepeatedField<Bytes> {
+    /// Copy the byte strings of an iterator into a new field, like a
+    /// repeated `push` of each.
+    pub fn from_iter<I>(iter: I, arena: &mut crate::arena::Arena) -> Result<Self, crate::Error<core::alloc::LayoutError>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let iter = iter.into_iter();
+        let mut rf = Self::new();
+        rf.reserve(iter.size_hint().0, arena)?;
+        for b in iter {
+            rf.push(Bytes::from_slice(b.as_ref(), arena)?, arena)?;
+        }
+        Ok(rf)
+    }
+
+    /// Copy the byte strings out of the arena.
+    #[cfg(feature = "alloc")]
+    pub fn to_vec(&self) -> alloc::vec::Vec<alloc::vec::Vec<u8>> {
+        self.iter().map(|b| b.slice().to_vec()).collect()
+    }
+}
+
+/// Key types of a [`ProtoMap`]: the integer and string types protobuf allows
+/// as map keys.
+pub trait MapKey: Copy + Eq {
+    /// Hash of the key, keyed by `seed`.
+    fn hash_key(&self, seed: u64) -> u64;
+}
+
+#[inline(always)]
+fn mix(value: u64) -> u64 {
+    let h = value.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    h ^ (h >> 32)
+}
+
+macro_rules! int_map_key {
+    ($($t:ty),*) => {$(
+        impl MapKey for $t {
+            #[inline(always)]
+            fn hash_key(&self, seed: u64) -> u64 {
+                mix(*self as u64 ^ seed)
+            }
+        }
+    )*};
+}
+
+int_map_key!(i32, i64, u32, u64, bool);
+
+impl MapKey for &str {
+    fn hash_key(&self, seed: u64) -> u64 {
+        let mut h = seed ^ self.len() as u64;
+        for chunk in self.as_bytes().chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            h = mix(h ^ u64::from_le_bytes(word));
+        }
+        h
+    }
+}
+
+/// Arena-allocated hash map for protobuf `map` fields.
+///
+/// Messages store map fields as repeated entries, which take a linear scan to
+/// search. A `ProtoMap` indexes them for constant time lookups; code generated
+/// with the `maps` option builds one from a map field with `<field>_map()`.
+/// Keys and values are `Copy`, so string keys and message values are borrowed
+/// from the message, e.g. `ProtoMap<&str, Option<&Value::ProtoType>>`.
+///
+/// Open addressing with linear probing in a power-of-two table. The hash is
+/// seeded with the table's address, so the layout differs between runs.
+/// Iteration order is unspecified.
+pub struct ProtoMap<K, V> {
+    slots: *mut Option<(K, V)>,
+    cap: usize,
+    len: usize,
+    seed: u64,
+}
+
+unsafe impl<K: Send, V: Send> Send for ProtoMap<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for ProtoMap<K, V> {}
+
+impl<K, V> Default for ProtoMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: MapKey + Debug, V: Copy + Debug> Debug for ProtoMap<K, V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V> ProtoMap<K, V> {
+    pub const fn new() -> Self {
+        ProtoMap {
+            slots: ptr::null_mut(),
+            cap: 0,
+            len: 0,
+            seed: 0,
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn slots(&self) -> &[Option<(K, V)>] {
+        if self.cap == 0 {
+            &[]
+        } else {
+            unsafe { core::slice::from_raw_parts(self.slots, self.cap) }
+        }
+    }
+
+    fn slots_mut(&mut self) -> &mut [Option<(K, V)>] {
+        if self.cap == 0 {
+            &mut []
+        } else {
+            unsafe { core::slice::from_raw_parts_mut(self.slots, self.cap) }
+        }
+    }
+}
+
+impl<K: MapKey, V: Copy> ProtoMap<K, V> {
+    /// An empty map with room for `capacity` entries before it reallocates.
+    pub fn with_capacity(capacity: usize, arena: &mut crate::arena::Arena) -> Result<Self, crate::Error<core::alloc::LayoutError>> {
+        let mut map = Self::new();
+        if capacity > 0 {
+            map.rehash(Self::table_size(capacity), arena)?;
+        }
+        Ok(map)
+    }
+
+    /// Build a map from `entries`, later entries replacing earlier ones with
+    /// the same key, as when decoding a map field.
+    pub fn from_entries(
+        entries: impl IntoIterator<Item = (K, V)>,
+        arena: &mut crate::arena::Arena,
+    ) -> Result<Self, crate::Error<core::alloc::LayoutError>> {
+        let entries = entries.into_iter();
+        let mut map = Self::with_capacity(entries.size_hint().0, arena)?;
+        for (key, value) in entries {
+            map.insert(key, value, arena)?;
+        }
+        Ok(map)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let index = self.find(key).ok()?;
+        self.slots()[index].as_ref().map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let index = self.find(key).ok()?;
+        self.slots_mut()[index].as_mut().map(|(_, value)| value)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find(key).is_ok()
+    }
+
+    /// Insert `value` under `key`, returning the value it replaces.
+    pub fn insert(&mut self, key: K, value: V, arena: &mut crate::arena::Arena) -> Result<Option<V>, crate::Error<core::alloc::LayoutError>> {
+        if let Ok(index) = self.find(&key) {
+            let (_, old) = self.slots_mut()[index].as_mut().unwrap();
+            return Ok(Some(core::mem::replace(old, value)));
+        }
+        if Self::table_size(self.len + 1) > self.cap {
+            self.rehash(Self::table_size(self.len + 1).max(2 * self.cap), arena)?;
+        }
+        let index = self.find(&key).unwrap_err();
+        self.slots_mut()[index] = Some((key, value));
+        self.len += 1;
+        Ok(None)
+    }
+
+    /// Remove `key`, returning its value.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let mut hole = self.find(key).ok()?;
+        let mask = self.cap - 1;
+        let seed = self.seed;
+        let slots = self.slots_mut();
+        let (_, value) = slots[hole].take().unwrap();
+        // Shift back the entries probing past the hole so lookups still find them
+        let mut index = hole;
+        loop {
+            index = (index + 1) & mask;
+            let Some((key, _)) = slots[index] else {
+                break;
+            };
+            let home = key.hash_key(seed) as usize & mask;
+            if (index.wrapping_sub(home) & mask) >= (index.wrapping_sub(hole) & mask) {
+                slots[hole] = slots[index].take();
+                hole = index;
+            }
+        }
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.slots_mut().fill(None);
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots()
+            .iter()
+            .filter_map(|slot| slot.as_ref().map(|(key, value)| (key, value)))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// Number of slots for `len` entries, keeping the load under 3/4.
+    fn table_size(len: usize) -> usize {
+        (len + len / 3 + 1).next_power_of_two().max(8)
+    }
+
+    /// The slot holding `key`, or the empty slot where it belongs.
+    fn find(&self, key: &K) -> Result<usize, usize> {
+        let slots = self.slots();
+        if slots.is_empty() {
+            return Err(0);
+        }
+        let mask = self.cap - 1;
+        let mut index = key.hash_key(self.seed) as usize & mask;
+        loop {
+            match &slots[index] {
+                None => return Err(index),
+                Some((k, _)) if k == key => return Ok(index),
+                Some(_) => index = (index + 1) & mask,
+            }
+        }
+    }
+
+    #[inline(never)]
+    fn rehash(&mut self, cap: usize, arena: &mut crate::arena::Arena) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+        let slots = arena.alloc_slice::<Option<(K, V)>>(cap)? as *mut Option<(K, V)>;
+        for i in 0..cap {
+            unsafe { slots.add(i).write(None) };
+        }
+        let old = core::mem::replace(
+            self,
+            ProtoMap {
+                slots,
+                cap,
+                len: 0,
+                seed: mix(slots as u64),
+            },
+        );
+        for &(key, value) in old.slots().iter().flatten() {
+            let index = self.find(&key).unwrap_err();
+            self.slots_mut()[index] = Some((key, value));
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::arena::Arena;
+
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    #[test]
+    fn string_and_bytes_fields_convert_to_vecs() {
+        let mut arena = Arena::new(&Global);
+        let strings = RepeatedField::<String>::from_iter(["a", "", "caf\u{e9}"], &mut arena).unwrap();
+        assert_eq!(strings.len(), 3);
+        assert_eq!(strings[2].as_str(), "caf\u{e9}");
+        assert_eq!(strings.to_vec(), ["a", "", "caf\u{e9}"]);
+
+        let owned = std::vec![std::vec![1u8, 2], std::vec![]];
+        let bytes = RepeatedField::<Bytes>::from_iter(&owned, &mut arena).unwrap();
+        assert_eq!(bytes.to_vec(), owned);
+        assert!(RepeatedField::<Bytes>::from_iter(std::vec::Vec::<&[u8]>::new(), &mut arena).unwrap().is_empty());
+    }
+
+    #[test]
+    fn static_data_is_copied_on_write() {
+        static DATA: [u32; 3] = [1, 2, 3];
+        let mut arena = Arena::new(&Global);
+        let mut field = RepeatedField::from_static(&DATA);
+        assert!(field.is_static());
+        assert_eq!(field.slice().as_ptr(), DATA.as_ptr());
+        assert_eq!(field.pop(), Some(3));
+        field.push(4, &mut arena).unwrap();
+        assert!(!field.is_static());
+        field.insert(0, 0, &mut arena).unwrap();
+        field[1] = 5;
+        assert_eq!(field.slice(), &[0, 5, 2, 4]);
+        assert_eq!(DATA, [1, 2, 3]);
+
+        let mut field = RepeatedField::from_static(&DATA);
+        field.reserve(1, &mut arena).unwrap();
+        assert_eq!(field.remove(0), 1);
+        assert_eq!(field.slice(), &[2, 3]);
+
+        let mut s = String::from_static("static");
+        s.assign("arena", &mut arena).unwrap();
+        assert_eq!(s.as_str(), "arena");
+        assert!(!RepeatedField::<u8>::from_static(&[]).is_static());
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot modify borrowed static data in place")]
+    fn static_data_is_not_modified_in_place() {
+        static DATA: [u32; 2] = [1, 2];
+        RepeatedField::from_static(&DATA)[0] = 3;
+    }
+
+    #[test]
+    fn proto_map_last_entry_wins() {
+        let mut arena = Arena::new(&Global);
+        let map = ProtoMap::from_entries([("a", 1), ("b", 2), ("a", 3)], &mut arena).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&"a"), Some(&3));
+        assert_eq!(map.get(&"b"), Some(&2));
+        assert_eq!(map.get(&"c"), None);
+        assert!(ProtoMap::<u32, u32>::new().get(&0).is_none());
+    }
+
+    #[test]
+    fn proto_map_matches_hash_map() {
+        let mut arena = Arena::new(&Global);
+        let mut map = ProtoMap::new();
+        let mut expected = std::collections::HashMap::new();
+        // A small key range so inserts, replacements and removals interleave
+        let mut state = 1u64;
+        for _ in 0..10_000 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let key = (state >> 33) as i64 % 300 - 150;
+            if state & 3 == 0 {
+                assert_eq!(map.remove(&key), expected.remove(&key));
+            } else {
+                let old = map.insert(key, state, &mut arena).unwrap();
+                assert_eq!(old, expected.insert(key, state));
+            }
+            assert_eq!(map.len(), expected.len());
+        }
+        for key in -150..150 {
+            assert_eq!(map.get(&key), expected.get(&key));
+        }
+        let mut entries: std::vec::Vec<_> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        entries.sort_unstable();
+        let mut expected: std::vec::Vec<_> = expected.into_iter().collect();
+        expected.sort_unstable();
+        assert_eq!(entries, expected);
+
+        map.clear();
+        assert!(map.is_empty() && map.get(&0).is_none());
+    }
+}
@@ -0,0 +1,239 @@
+//! Locating decode failures.
+//!
+//! The decoder notes where each field starts, and keeps the sub-messages it
+//! is in on its stack. When a decode fails, [`DecodeError`] is made from that
+//! state, see [`ResumeableDecode::error`](crate::decoding::ResumeableDecode::error).
+//!
+//! A walk over the fields of decoded input records [`FieldRange`]s for
+//! [`try_decode_flat_with_ranges`](crate::ProtobufMut::try_decode_flat_with_ranges).
+
+use core::ops::Range;
+
+#[cfg(feature = "alloc")]
+use crate::Error;
+#[cfg(feature = "alloc")]
+use crate::tables::Table;
+#[cfg(feature = "alloc")]
+use crate::wire::{Reader, WireType, make_tag, varint_len};
+
+/// Location of a decode failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    offset: usize,
+    #[cfg(feature = "alloc")]
+    path: alloc::string::String,
+}
+
+impl DecodeError {
+    pub(crate) fn at(offset: usize) -> Self {
+        Self {
+            offset,
+            #[cfg(feature = "alloc")]
+            path: alloc::string::String::new(),
+        }
+    }
+
+    /// Byte offset into the input of the field that failed to decode, at
+    /// its tag.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Best-effort path to the offending field, e.g. `child1.nested_message[3].z`.
+    ///
+    /// Empty when the failure is in an unknown field at the top level of the
+    /// message or the message was generated without descriptors, and always
+    /// empty without the `alloc` feature.
+    pub fn path(&self) -> &str {
+        #[cfg(feature = "alloc")]
+        {
+            &self.path
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            ""
+        }
+    }
+
+    /// Append field `field_number` of `table` to the path, with `index` for
+    /// an element of a repeated field. False if the field has no name to
+    /// append, being unknown or its descriptor stripped.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn push_field(&mut self, table: &Table, field_number: u32, index: Option<usize>) -> bool {
+        use core::fmt::Write;
+
+        let Some(field) = table
+            .descriptor
+            .and_then(|descriptor| descriptor.field().iter().find(|f| f.number() as u32 == field_number))
+        else {
+            return false;
+        };
+        if !self.path.is_empty() {
+            self.path.push('.');
+        }
+        self.path.push_str(field.name());
+        if let Some(index) = index {
+            let _ = write!(self.path, "[{index}]");
+        }
+        true
+    }
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid protobuf data at byte {}", self.offset)?;
+        if !self.path().is_empty() {
+            write!(f, " in field `{}`", self.path())?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+/// Where one occurrence of a field sits in the decoded buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldRange {
+    /// Number of the field.
+    pub field_number: u32,
+    /// The whole field, from its tag up to and including the END_GROUP tag
+    /// of a group. Copying these bytes re-emits the field unchanged.
+    pub field: Range<usize>,
+    /// The value alone: the payload of a length-delimited field (without
+    /// its length prefix), the contents of a group, or the varint or fixed
+    /// width bytes of a scalar.
+    pub value: Range<usize>,
+}
+
+/// Collect the ranges of the top-level fields of `buf` numbered in
+/// `field_numbers`, in input order.
+///
+/// `buf` must have decoded successfully, so the walk only needs to skip fields.
+#[cfg(feature = "alloc")]
+pub(crate) fn field_ranges(
+    buf: &[u8],
+    field_numbers: &[u32],
+    max_depth: usize,
+) -> Result<alloc::vec::Vec<FieldRange>, DecodeError> {
+    let mut reader = Reader::new(buf);
+    let offset = |reader: &Reader| buf.len() - reader.remaining().len();
+    let mut ranges = alloc::vec::Vec::new();
+    while !reader.is_empty() {
+        let field_start = offset(&reader);
+        let at_field = |_| DecodeError::at(field_start);
+        let (field_number, wire_type) = reader.read_tag().map_err(at_field)?;
+        let value = if wire_type == WireType::LengthDelimited {
+            let value = reader.read_bytes().map_err(at_field)?;
+            let value_end = offset(&reader);
+            value_end - value.len()..value_end
+        } else {
+            let value_start = offset(&reader);
+            skip(&mut reader, field_number, wire_type, max_depth).map_err(at_field)?;
+            let mut value_end = offset(&reader);
+            if wire_type == WireType::StartGroup {
+                // Exclude the END_GROUP tag
+                value_end -= varint_len(u64::from(make_tag(field_number, WireType::EndGroup)));
+            }
+            value_start..value_end
+        };
+        if field_numbers.contains(&field_number) {
+            ranges.push(FieldRange {
+                field_number,
+                field: field_start..offset(&reader),
+                value,
+            });
+        }
+    }
+    Ok(ranges)
+}
+
+/// Skip the value of field `field_number` encoded as `wire_type`, after its
+/// tag, descending into at most `depth` nested groups like the decoder.
+#[cfg(feature = "alloc")]
+fn skip(reader: &mut Reader, field_number: u32, wire_type: WireType, depth: usize) -> Result<(), Error> {
+    if wire_type != WireType::StartGroup {
+        return reader.skip(field_number, wire_type);
+    }
+    let depth = depth.checked_sub(1).ok_or(Error::InvalidProtobufData)?;
+    loop {
+        match reader.read_tag()? {
+            (number, WireType::EndGroup) if number == field_number => return Ok(()),
+            (number, wire_type) => skip(reader, number, wire_type, depth)?,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::ProtobufMut;
+    use crate::google::protobuf::FileDescriptorProto;
+
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    fn decode(bytes: &[u8]) -> Result<(), super::DecodeError> {
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut msg = FileDescriptorProto::ProtoType::default();
+        msg.try_decode_flat::<32>(&mut arena, bytes)
+    }
+
+    #[test]
+    fn nested_invalid_utf8() {
+        let bytes = [
+            // message_type[0] { name: "A" }
+            0x22, 0x03, 0x0a, 0x01, b'A',
+            // message_type[1] { name: "B" field[0] { name: <invalid utf-8> } }
+            0x22, 0x09, 0x0a, 0x01, b'B', 0x12, 0x04, 0x0a, 0x02, 0xff, 0xfe,
+        ];
+        let error = decode(&bytes).unwrap_err();
+        assert_eq!(error.offset(), 12);
+        assert_eq!(error.path(), "message_type[1].field[0].name");
+        assert_eq!(
+            error.to_string(),
+            "invalid protobuf data at byte 12 in field `message_type[1].field[0].name`"
+        );
+    }
+
+    #[test]
+    fn offset_past_first_buffer() {
+        // name: 40 bytes, message_type[0] { field[0] { name: <invalid utf-8> } },
+        // package: 20 bytes
+        let mut bytes = std::vec![0x0a, 40];
+        bytes.extend([b'a'; 40]);
+        bytes.extend([0x22, 0x06, 0x12, 0x04, 0x0a, 0x02, 0xff, 0xfe]);
+        bytes.extend([0x12, 20]);
+        bytes.extend([b'p'; 20]);
+        let error = decode(&bytes).unwrap_err();
+        assert_eq!((error.offset(), error.path()), (46, "message_type[0].field[0].name"));
+    }
+
+    #[test]
+    fn truncated_length() {
+        // name: length 5 but only one byte follows
+        let error = decode(&[0x0a, 0x05, b'a']).unwrap_err();
+        assert_eq!(error.offset(), 0);
+        assert_eq!(error.path(), "name");
+    }
+
+    #[test]
+    fn top_level_errors() {
+        // Wire type 7 does not exist, on unknown field 16
+        let error = decode(&[0x0a, 0x01, b'a', 0x87, 0x01]).unwrap_err();
+        assert_eq!((error.offset(), error.path()), (3, ""));
+
+        // END_GROUP outside of any group
+        let error = decode(&[0x0a, 0x01, b'a', 0x84, 0x01]).unwrap_err();
+        assert_eq!((error.offset(), error.path()), (3, ""));
+
+        // Known field 15 with wire type 7
+        let error = decode(&[0x0a, 0x01, b'a', 0x7f]).unwrap_err();
+        assert_eq!((error.offset(), error.path()), (3, "option_dependency"));
+    }
+
+    #[test]
+    fn valid_input() {
+        assert!(decode(&[0x0a, 0x01, b'a', 0x22, 0x00]).is_ok());
+    }
+}
@@ -3,13 +3,189 @@ use core::ptr::NonNull;
 
 use crate::base::{Message, Object};
 use crate::containers::{Bytes, RepeatedField};
+use crate::decode_error::DecodeError;
 use crate::reflection::DynamicMessage;
-use crate::tables::Table;
+use crate::tables::{EnumTable, Table};
+use crate::trace::{debug_event, trace_event};
 use crate::utils::{Ptr, PtrMut, Stack, StackWithStorage, UpdateByValue};
-use crate::wire::{FieldKind, ReadCursor, SLOP_SIZE, zigzag_decode};
+use crate::wire::{FieldKind, FixedWidth, ReadCursor, Reader, SLOP_SIZE, varint_size, zigzag_decode};
 
-#[cfg(feature = "std")]
-const TRACE_TAGS: bool = false;
+/// Limits guarding the decoder against hostile input.
+///
+/// Exceeding a limit fails the decode like malformed data does. The defaults are
+/// generous for real data while keeping a small payload (for example a
+/// decompressed gRPC message) from ballooning into gigabytes of arena memory.
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeOptions {
+    /// Maximum number of input bytes for one top-level message, including all
+    /// sub-messages and skipped unknown fields.
+    pub max_message_bytes: usize,
+    /// Maximum number of elements in a single repeated field (including map
+    /// entries and packed values).
+    pub max_repeated_elements: usize,
+    /// Maximum length of a single string or bytes value.
+    pub max_string_len: usize,
+    /// Maximum nesting depth of sub-messages and groups, up to which the
+    /// decoder's stack grows into the arena once its `STACK_DEPTH` entries
+    /// are used up. The default, 0, keeps `STACK_DEPTH` as the limit.
+    pub max_depth: usize,
+    /// Keep only the last entry of every key in map fields, as
+    /// [`normalize_maps`](crate::reflection::DynamicMessage::normalize_maps)
+    /// does once the message is complete. Off by default: map fields are
+    /// stored as repeated entries, kept in wire order with repeated keys, and
+    /// code relying on the order the sender added them in can count on it.
+    #[cfg(feature = "alloc")]
+    pub dedup_map_entries: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions {
+            max_message_bytes: 64 << 20,
+            max_repeated_elements: 16 << 20,
+            max_string_len: 64 << 20,
+            max_depth: 0,
+            #[cfg(feature = "alloc")]
+            dedup_map_entries: false,
+        }
+    }
+}
+
+/// Unknown fields a decode skipped, see
+/// [`ResumeableDecode::unknown_fields`] and
+/// [`decode_flat_with_stats`](crate::ProtobufMut::decode_flat_with_stats).
+///
+/// Operators can alert when producers start sending fields the consumer's
+/// schema doesn't have. Fields whose number is known but whose wire type
+/// doesn't match the schema are skipped and counted too, as are values a
+/// closed enum doesn't declare, each value of a packed field on its own.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownFieldStats {
+    /// Unknown fields skipped, in the message and its sub-messages. The
+    /// fields inside a skipped group are part of it, not counted apart.
+    pub fields: usize,
+    /// Encoded bytes of the skipped fields, tags included.
+    pub bytes: usize,
+    /// How many of the skipped fields are groups.
+    pub groups: usize,
+}
+
+impl UnknownFieldStats {
+    fn add_field(&mut self, bytes: isize) {
+        self.fields += 1;
+        self.add_bytes(bytes);
+    }
+
+    fn add_bytes(&mut self, bytes: isize) {
+        debug_assert!(bytes >= 0);
+        self.bytes += bytes as usize;
+    }
+}
+
+/// The limits of a decode and what it counted so far, passed along its
+/// decoding functions.
+struct DecodeSession {
+    options: DecodeOptions,
+    unknown: UnknownFieldStats,
+    child_slabs: ChildSlabs,
+    /// Tag of the field being decoded, in the buffer being parsed.
+    field_start: *const u8,
+    /// Position in the input and number of the last field started, noted
+    /// after each buffer to locate a failure.
+    last_field: (isize, u32),
+}
+
+impl DecodeSession {
+    /// Note where the field at `field_start` is, `buf` being the parsed
+    /// buffer at `offset` in the input.
+    #[cold]
+    fn note_field(&mut self, buf: &[u8], offset: isize) {
+        // The tag may run into the slop bytes past the end of `buf`
+        let tag = unsafe { core::slice::from_raw_parts(self.field_start, SLOP_SIZE) };
+        let field_number = read_varint(tag, &mut 0).map_or(0, |tag| (tag >> 3) as u32);
+        let pos = unsafe { self.field_start.offset_from(buf.as_ptr()) };
+        self.last_field = (offset + pos, field_number);
+        self.field_start = core::ptr::null();
+    }
+}
+
+/// Number of repeated message fields [`ChildSlabs`] keeps a slab for.
+const CHILD_SLABS: usize = 4;
+
+/// Zeroed objects for the elements of repeated message fields.
+///
+/// When a repeated message field grows, objects for all of its new capacity
+/// are allocated at once and handed out as elements are decoded, so fields
+/// with many elements take a handful of arena allocations instead of one per
+/// element. Slabs are kept for the few fields that are decoded interleaved,
+/// like a field of the elements of another; an evicted slab's objects are
+/// unused and its field allocates one object per element until it grows again.
+struct ChildSlabs {
+    slabs: [ChildSlab; CHILD_SLABS],
+    /// Slot the next slab replaces.
+    next: usize,
+}
+
+#[derive(Clone, Copy)]
+struct ChildSlab {
+    /// The field the objects are for, only compared.
+    field: *const RepeatedField<*mut Object>,
+    objects: *mut u8,
+    remaining: usize,
+}
+
+impl ChildSlabs {
+    const fn new() -> Self {
+        const EMPTY: ChildSlab = ChildSlab {
+            field: core::ptr::null(),
+            objects: core::ptr::null_mut(),
+            remaining: 0,
+        };
+        ChildSlabs {
+            slabs: [EMPTY; CHILD_SLABS],
+            next: 0,
+        }
+    }
+
+    /// A zeroed object of `size` bytes for the next element of `field`.
+    fn alloc(
+        &mut self,
+        field: &mut RepeatedField<*mut Object>,
+        size: u32,
+        arena: &mut crate::arena::Arena,
+    ) -> Result<&'static mut Object, crate::Error<core::alloc::LayoutError>> {
+        let stride = (size as usize).next_multiple_of(core::mem::align_of::<u64>());
+        let key = field as *const RepeatedField<*mut Object>;
+        if let Some(slab) = self
+            .slabs
+            .iter_mut()
+            .find(|slab| slab.field == key && slab.remaining > 0)
+        {
+            let object = slab.objects;
+            slab.objects = slab.objects.wrapping_add(stride);
+            slab.remaining -= 1;
+            return Ok(unsafe { &mut *(object as *mut Object) });
+        }
+        if field.len() < field.capacity() || size == 0 {
+            return Object::create(size, arena);
+        }
+
+        // Grow like `push` would and allocate the objects of the new capacity
+        let count = (2 * field.capacity()).max(2 * field.len()).max(1) - field.len();
+        field.reserve(field.len() + count, arena)?;
+        let bytes = stride.saturating_mul(count);
+        let layout = core::alloc::Layout::from_size_align(bytes, core::mem::align_of::<u64>())?;
+        let objects = arena.alloc_raw(layout)?.as_ptr();
+        unsafe { core::ptr::write_bytes(objects, 0, bytes) };
+        self.slabs[self.next] = ChildSlab {
+            field: key,
+            objects: objects.wrapping_add(stride),
+            remaining: count - 1,
+        };
+        self.next = (self.next + 1) % CHILD_SLABS;
+        Ok(unsafe { &mut *(objects as *mut Object) })
+    }
+}
 
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -21,7 +197,7 @@ impl TableEntry {
     }
 
     pub(crate) fn kind(&self) -> FieldKind {
-        debug_assert!((self.0 as u8) <= FieldKind::RepeatedGroup as u8);
+        debug_assert!((self.0 as u8) <= FieldKind::RepeatedClosedEnum as u8);
         unsafe { core::mem::transmute(self.0 as u8) }
     }
 
@@ -48,16 +224,55 @@ impl Table {
         Some(entries[field_number as usize])
     }
 
+    /// Like [`entry`](Self::entry), but closed enum entries are resolved to the
+    /// plain `Int32` / `RepeatedInt32` entry holding the field offset. Use this
+    /// when accessing the field rather than decoding it.
+    #[inline(always)]
+    pub(crate) fn field_entry(&self, field_number: u32) -> Option<TableEntry> {
+        let entry = self.entry(field_number)?;
+        debug_assert!(
+            self.descriptor
+                .and_then(|descriptor| descriptor.field().iter().find(|field| field.number() as u32 == field_number))
+                .is_none_or(|field| crate::reflection::kind_matches(field, entry.kind())),
+            "table entry of field {} of {} doesn't match the field type",
+            field_number,
+            self.full_name
+        );
+        match entry.kind() {
+            FieldKind::ClosedEnum | FieldKind::RepeatedClosedEnum => {
+                Some(self.closed_enum_entry(entry).0)
+            }
+            _ => Some(entry),
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn aux_entry_decode(&self, entry: TableEntry) -> (u32, &Table) {
         let offset = entry.aux_offset();
         self.aux_entry(offset as usize)
     }
+
+    #[inline(always)]
+    pub(crate) fn closed_enum_entry(&self, entry: TableEntry) -> (TableEntry, &EnumTable) {
+        let (offset, enum_table) = self.aux_enum_entry(entry.aux_offset() as usize);
+        let kind = if entry.kind() == FieldKind::RepeatedClosedEnum {
+            FieldKind::RepeatedInt32
+        } else {
+            FieldKind::Int32
+        };
+        (
+            TableEntry::new(kind, entry.has_bit_idx(), offset as usize),
+            enum_table,
+        )
+    }
 }
 
 struct StackEntry {
     obj_table: Option<(PtrMut<Object>, Ptr<Table>)>,
     delta_limit_or_group_tag: isize,
+    /// Field of `obj_table` being decoded, for locating failures.
+    #[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+    field_number: u32,
 }
 
 impl StackEntry {
@@ -103,6 +318,7 @@ enum DecodeObject<'a> {
     PackedBool(&'a mut RepeatedField<bool>),
     PackedFixed64(&'a mut RepeatedField<u64>),
     PackedFixed32(&'a mut RepeatedField<u32>),
+    PackedClosedEnum(&'a mut RepeatedField<u32>, &'a EnumTable),
 }
 
 #[repr(C)]
@@ -123,20 +339,26 @@ impl<'a> DecodeObjectState<'a> {
     #[inline(always)]
     fn push_limit(
         &mut self,
+        field_number: u32,
         len: isize,
         cursor: ReadCursor,
         end: NonNull<u8>,
         stack: &mut Stack<StackEntry>,
+        arena: &mut crate::arena::Arena,
     ) -> Option<NonNull<u8>> {
         let new_limit = cursor - end + len;
         let delta_limit = self.limit - new_limit;
         if delta_limit < 0 {
             return None;
         }
-        stack.push(StackEntry {
-            obj_table: Some((PtrMut::new(self.msg.object), Ptr::new(self.msg.table))),
-            delta_limit_or_group_tag: delta_limit,
-        })?;
+        stack.push_spilling(
+            StackEntry {
+                obj_table: Some((PtrMut::new(self.msg.object), Ptr::new(self.msg.table))),
+                delta_limit_or_group_tag: delta_limit,
+                field_number,
+            },
+            arena,
+        )?;
         self.limit = new_limit;
         Some(self.limited_end(end))
     }
@@ -152,11 +374,20 @@ impl<'a> DecodeObjectState<'a> {
     }
 
     #[inline(always)]
-    fn push_group(&mut self, field_number: u32, stack: &mut Stack<StackEntry>) -> Option<()> {
-        stack.push(StackEntry {
-            obj_table: Some((PtrMut::new(self.msg.object), Ptr::new(self.msg.table))),
-            delta_limit_or_group_tag: -(field_number as isize),
-        })?;
+    fn push_group(
+        &mut self,
+        field_number: u32,
+        stack: &mut Stack<StackEntry>,
+        arena: &mut crate::arena::Arena,
+    ) -> Option<()> {
+        stack.push_spilling(
+            StackEntry {
+                obj_table: Some((PtrMut::new(self.msg.object), Ptr::new(self.msg.table))),
+                delta_limit_or_group_tag: -(field_number as isize),
+                field_number,
+            },
+            arena,
+        )?;
         Some(())
     }
 
@@ -181,8 +412,37 @@ impl<'a> DecodeObjectState<'a> {
     }
 
     #[inline(always)]
-    fn add<T>(&mut self, entry: TableEntry, val: T, arena: &mut crate::arena::Arena) -> Result<&mut T, crate::Error<core::alloc::LayoutError>> {
-        self.msg.object.add(entry.aux_offset(), val, arena)
+    fn add<T>(
+        &mut self,
+        entry: TableEntry,
+        val: T,
+        arena: &mut crate::arena::Arena,
+        options: &DecodeOptions,
+    ) -> Option<()> {
+        self.check_repeated_len::<T>(entry, options)?;
+        self.msg.object.add(entry.aux_offset(), val, arena).ok()?;
+        Some(())
+    }
+
+    /// Fails if the repeated field of `entry` is already at the element limit.
+    #[inline(always)]
+    fn check_repeated_len<T>(&mut self, entry: TableEntry, options: &DecodeOptions) -> Option<()> {
+        let (offset, _) = match entry.kind() {
+            FieldKind::RepeatedMessage | FieldKind::RepeatedGroup => {
+                self.msg.table.aux_entry_decode(entry)
+            }
+            _ => (entry.offset(), self.msg.table),
+        };
+        let len = self.msg.object.ref_mut::<RepeatedField<T>>(offset).len();
+        if len >= options.max_repeated_elements {
+            debug_event!(
+                message_type = self.msg.table.full_name,
+                len,
+                "repeated field exceeds element limit"
+            );
+            return None;
+        }
+        Some(())
     }
 
     #[inline(always)]
@@ -237,13 +497,14 @@ impl<'a> DecodeObjectState<'a> {
         &mut self,
         entry: TableEntry,
         arena: &mut crate::arena::Arena,
+        slabs: &mut ChildSlabs,
     ) -> Result<DynamicMessage<'a, 'a>, crate::Error<core::alloc::LayoutError>> {
         let (offset, child_table) = self.msg.table.aux_entry_decode(entry);
         let field = self
             .msg
             .object
             .ref_mut::<RepeatedField<*mut Object>>(offset);
-        let child = Object::create(child_table.size as u32, arena)?;
+        let child = slabs.alloc(field, child_table.size as u32, arena)?;
         field.push(child, arena)?;
         Ok(DynamicMessage {
             object: child,
@@ -261,11 +522,14 @@ fn skip_length_delimited<'a>(
     end: NonNull<u8>,
     stack: &mut Stack<StackEntry>,
     arena: &mut crate::arena::Arena,
+    session: &mut DecodeSession,
 ) -> DecodeLoopResult<'a> {
     if limit > SLOP_SIZE as isize {
+        session.unknown.add_bytes(SLOP_SIZE as isize - (cursor - end));
         cursor.read_slice(SLOP_SIZE as isize - (cursor - end));
         return Some((cursor, limit, DecodeObject::SkipLengthDelimited));
     }
+    session.unknown.add_bytes(limit - (cursor - end));
     cursor.read_slice(limit - (cursor - end));
     let stack_entry = stack.pop()?;
     if stack_entry.obj_table.is_none() {
@@ -276,10 +540,11 @@ fn skip_length_delimited<'a>(
             end,
             stack,
             arena,
+            session,
         );
     }
     let ctx = stack_entry.into_context(limit, None)?;
-    decode_loop(ctx, cursor, end, stack, arena)
+    decode_loop(ctx, cursor, end, stack, arena, session)
 }
 
 #[inline(never)]
@@ -289,8 +554,11 @@ fn skip_group<'a>(
     end: NonNull<u8>,
     stack: &mut Stack<StackEntry>,
     arena: &mut crate::arena::Arena,
+    session: &mut DecodeSession,
 ) -> DecodeLoopResult<'a> {
     let limited_end = calc_limited_end(end, limit);
+    // The group's bytes are counted as they are skipped
+    let start = cursor - end;
     // loop popping the stack as needed
     loop {
         // inner parse loop
@@ -301,13 +569,7 @@ fn skip_group<'a>(
             if field_number == 0 {
                 return None;
             }
-            #[cfg(feature = "std")]
-            if TRACE_TAGS {
-                eprintln!(
-                    "Skipping unknown field with field number {} and wire type {}",
-                    field_number, wire_type
-                );
-            }
+            trace_event!(field_number, wire_type, "skipping field in unknown group");
             match wire_type {
                 0 => {
                     // varint
@@ -329,19 +591,28 @@ fn skip_group<'a>(
                         if delta_limit < 0 {
                             return None;
                         }
-                        stack.push(StackEntry {
-                            obj_table: None,
-                            delta_limit_or_group_tag: delta_limit,
-                        })?;
+                        stack.push_spilling(
+                            StackEntry {
+                                obj_table: None,
+                                delta_limit_or_group_tag: delta_limit,
+                                field_number,
+                            },
+                            arena,
+                        )?;
+                        session.unknown.add_bytes(cursor - end - start);
                         return Some((cursor, new_limit, DecodeObject::SkipLengthDelimited));
                     }
                 }
                 3 => {
                     // start group
-                    stack.push(StackEntry {
-                        obj_table: None,
-                        delta_limit_or_group_tag: -(field_number as isize),
-                    })?;
+                    stack.push_spilling(
+                        StackEntry {
+                            obj_table: None,
+                            delta_limit_or_group_tag: -(field_number as isize),
+                            field_number,
+                        },
+                        arena,
+                    )?;
                 }
                 4 => {
                     // end group
@@ -350,6 +621,7 @@ fn skip_group<'a>(
                         return None;
                     }
                     if let Some((mut obj, table)) = stack_entry.obj_table {
+                        session.unknown.add_bytes(cursor - end - start);
                         let ctx = DecodeObjectState {
                             limit,
                             msg: DynamicMessage {
@@ -357,7 +629,7 @@ fn skip_group<'a>(
                                 table: table.as_ref(),
                             },
                         };
-                        return decode_loop(ctx, cursor, end, stack, arena);
+                        return decode_loop(ctx, cursor, end, stack, arena, session);
                     }
                 }
                 5 => {
@@ -370,6 +642,7 @@ fn skip_group<'a>(
             }
         }
         if cursor - end == limit {
+            session.unknown.add_bytes(cursor - end - start);
             if stack.is_empty() {
                 return Some((cursor, limit, DecodeObject::None));
             }
@@ -380,7 +653,7 @@ fn skip_group<'a>(
             }
             let ctx = stack_entry.into_context(limit, None)?;
             // TODO: this relies on tail call optimization
-            return decode_loop(ctx, cursor, end, stack, arena);
+            return decode_loop(ctx, cursor, end, stack, arena, session);
         }
         if cursor >= end {
             break;
@@ -389,34 +662,126 @@ fn skip_group<'a>(
             return None;
         }
     }
+    session.unknown.add_bytes(cursor - end - start);
     Some((cursor, limit, DecodeObject::SkipGroup))
 }
 
+/// Make room for `count` more elements of a packed run. Growing at least
+/// doubles the capacity, so a run split over many buffers doesn't copy the
+/// field for each of them.
+///
+/// The unpack functions size the run from its bytes before `limited_end` and
+/// the first `SLOP_SIZE` of the `tail` bytes past it, which the slop region
+/// already holds. The rest of the tail isn't reserved up front, as a short
+/// input can claim a long run.
+#[inline(always)]
+fn reserve_packed<T>(
+    field: &mut RepeatedField<T>,
+    count: usize,
+    arena: &mut crate::arena::Arena,
+    options: &DecodeOptions,
+) -> Option<()> {
+    let needed = (field.len() + count).min(options.max_repeated_elements);
+    if needed > field.capacity() {
+        field.reserve(needed.max(2 * field.capacity()), arena).ok()?;
+    }
+    Some(())
+}
+
+/// The number of varints ending before `run_end`.
+#[inline(always)]
+fn count_varints(cursor: ReadCursor, run_end: NonNull<u8>) -> usize {
+    let available = -(cursor - run_end);
+    if available <= 0 {
+        return 0;
+    }
+    let bytes = unsafe { core::slice::from_raw_parts(cursor.0.as_ptr(), available as usize) };
+    bytes.iter().filter(|&&b| b < 0x80).count()
+}
+
 #[inline(always)]
 fn unpack_varint<T>(
     field: &mut RepeatedField<T>,
     mut cursor: ReadCursor,
     limited_end: NonNull<u8>,
+    tail: isize,
     arena: &mut crate::arena::Arena,
+    options: &DecodeOptions,
     decode_fn: impl Fn(u64) -> T,
 ) -> Option<ReadCursor> {
+    let count = count_varints(cursor, unsafe { limited_end.offset(tail.min(SLOP_SIZE as isize)) });
+    reserve_packed(field, count, arena, options)?;
     while cursor < limited_end {
+        if field.len() >= options.max_repeated_elements {
+            return None;
+        }
         let val = cursor.read_varint()?;
         field.push(decode_fn(val), arena).ok()?;
     }
     Some(cursor)
 }
 
+/// Like `unpack_varint`, dropping values that are not in the enum and
+/// counting them in `unknown`.
+#[allow(clippy::too_many_arguments)]
 #[inline(always)]
-fn unpack_fixed<T>(
-    field: &mut RepeatedField<T>,
+fn unpack_closed_enum(
+    field: &mut RepeatedField<u32>,
     mut cursor: ReadCursor,
     limited_end: NonNull<u8>,
+    tail: isize,
     arena: &mut crate::arena::Arena,
+    options: &DecodeOptions,
+    enum_table: &EnumTable,
+    unknown: &mut UnknownFieldStats,
 ) -> Option<ReadCursor> {
+    let count = count_varints(cursor, unsafe { limited_end.offset(tail.min(SLOP_SIZE as isize)) });
+    reserve_packed(field, count, arena, options)?;
     while cursor < limited_end {
-        let val = cursor.read_unaligned::<T>();
-        field.push(val, arena).ok()?;
+        if field.len() >= options.max_repeated_elements {
+            return None;
+        }
+        let start = cursor;
+        let val = cursor.read_varint()? as u32;
+        if enum_table.contains(val as i32) {
+            field.push(val, arena).ok()?;
+        } else {
+            unknown.add_field(cursor - start.0);
+        }
+    }
+    Some(cursor)
+}
+
+/// Append the packed elements up to `limited_end` with a single reserve and
+/// memcpy. Like the other unpack functions a trailing partial element is read
+/// past `limited_end` into the slop region, leaving the cursor past the limit
+/// for the caller to reject.
+#[inline(always)]
+fn unpack_fixed<T: FixedWidth>(
+    field: &mut RepeatedField<T>,
+    mut cursor: ReadCursor,
+    limited_end: NonNull<u8>,
+    tail: isize,
+    arena: &mut crate::arena::Arena,
+    options: &DecodeOptions,
+) -> Option<ReadCursor> {
+    let available = -(cursor - limited_end);
+    if available <= 0 {
+        return Some(cursor);
+    }
+    let size = core::mem::size_of::<T>() as isize;
+    let count = (available + size - 1) / size;
+    if field.len() + count as usize > options.max_repeated_elements {
+        return None;
+    }
+    let run = available + tail.min(SLOP_SIZE as isize);
+    reserve_packed(field, ((run + size - 1) / size) as usize, arena, options)?;
+    let old_len = field.len();
+    field.append_bytes(cursor.read_slice(count * size), arena).ok()?;
+    if cfg!(target_endian = "big") {
+        for val in &mut field[old_len..] {
+            *val = val.le_to_native();
+        }
     }
     Some(cursor)
 }
@@ -430,39 +795,83 @@ fn decode_packed<'a, T>(
     end: NonNull<u8>,
     stack: &mut Stack<StackEntry>,
     arena: &mut crate::arena::Arena,
+    session: &mut DecodeSession,
     decode_fn: impl Fn(u64) -> T,
     decode_obj: impl Fn(&'a mut RepeatedField<T>) -> DecodeObject<'a>,
 ) -> DecodeLoopResult<'a> {
     if limit > 0 {
-        let cursor = unpack_varint(field, cursor, end, arena, decode_fn)?;
+        let cursor = unpack_varint(field, cursor, end, limit, arena, &session.options, decode_fn)?;
         return Some((cursor, limit, decode_obj(field)));
     }
     let limited_end = calc_limited_end(end, limit);
-    let cursor = unpack_varint(field, cursor, limited_end, arena, decode_fn)?;
+    let cursor = unpack_varint(field, cursor, limited_end, 0, arena, &session.options, decode_fn)?;
     let ctx = stack.pop()?.into_context(limit, None)?;
-    decode_loop(ctx, cursor, end, stack, arena)
+    decode_loop(ctx, cursor, end, stack, arena, session)
 }
 
+#[allow(clippy::too_many_arguments)]
 #[inline(never)]
-fn decode_fixed<'a, T>(
+fn decode_packed_closed_enum<'a>(
+    limit: isize,
+    field: &'a mut RepeatedField<u32>,
+    enum_table: &'a EnumTable,
+    cursor: ReadCursor,
+    end: NonNull<u8>,
+    stack: &mut Stack<StackEntry>,
+    arena: &mut crate::arena::Arena,
+    session: &mut DecodeSession,
+) -> DecodeLoopResult<'a> {
+    if limit > 0 {
+        let cursor = unpack_closed_enum(
+            field,
+            cursor,
+            end,
+            limit,
+            arena,
+            &session.options,
+            enum_table,
+            &mut session.unknown,
+        )?;
+        return Some((cursor, limit, DecodeObject::PackedClosedEnum(field, enum_table)));
+    }
+    let limited_end = calc_limited_end(end, limit);
+    let cursor = unpack_closed_enum(
+        field,
+        cursor,
+        limited_end,
+        0,
+        arena,
+        &session.options,
+        enum_table,
+        &mut session.unknown,
+    )?;
+    let ctx = stack.pop()?.into_context(limit, None)?;
+    decode_loop(ctx, cursor, end, stack, arena, session)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[inline(never)]
+fn decode_fixed<'a, T: FixedWidth>(
     limit: isize,
     field: &'a mut RepeatedField<T>,
     cursor: ReadCursor,
     end: NonNull<u8>,
     stack: &mut Stack<StackEntry>,
     arena: &mut crate::arena::Arena,
+    session: &mut DecodeSession,
     decode_obj: impl Fn(&'a mut RepeatedField<T>) -> DecodeObject<'a>,
 ) -> DecodeLoopResult<'a> {
     if limit > 0 {
-        let cursor = unpack_fixed(field, cursor, end, arena)?;
+        let cursor = unpack_fixed(field, cursor, end, limit, arena, &session.options)?;
         return Some((cursor, limit, decode_obj(field)));
     }
     let limited_end = calc_limited_end(end, limit);
-    let cursor = unpack_fixed(field, cursor, limited_end, arena)?;
+    let cursor = unpack_fixed(field, cursor, limited_end, 0, arena, &session.options)?;
     let ctx = stack.pop()?.into_context(limit, None)?;
-    decode_loop(ctx, cursor, end, stack, arena)
+    decode_loop(ctx, cursor, end, stack, arena, session)
 }
 
+#[allow(clippy::too_many_arguments)]
 #[inline(never)]
 fn decode_string<'a>(
     limit: isize,
@@ -472,6 +881,7 @@ fn decode_string<'a>(
     end: NonNull<u8>,
     stack: &mut Stack<StackEntry>,
     arena: &mut crate::arena::Arena,
+    session: &mut DecodeSession,
 ) -> DecodeLoopResult<'a> {
     if limit > SLOP_SIZE as isize {
         bytes.append(
@@ -486,7 +896,7 @@ fn decode_string<'a>(
         return None;
     }
     let ctx = stack.pop()?.into_context(limit, None)?;
-    decode_loop(ctx, cursor, end, stack, arena)
+    decode_loop(ctx, cursor, end, stack, arena, session)
 }
 
 #[inline(never)]
@@ -496,39 +906,17 @@ fn decode_loop<'a>(
     end: NonNull<u8>,
     stack: &mut Stack<StackEntry>,
     arena: &mut crate::arena::Arena,
+    session: &mut DecodeSession,
 ) -> DecodeLoopResult<'a> {
+    let options = &session.options;
     let mut limited_end = ctx.limited_end(end);
     // loop popping the stack as needed
     loop {
         // inner parse loop
         'parse_loop: while cursor < limited_end {
+            session.field_start = cursor.0.as_ptr();
             let tag = cursor.read_tag()?;
             let field_number = tag >> 3;
-            #[cfg(feature = "std")]
-            if TRACE_TAGS {
-                let descriptor = ctx.msg.table.descriptor;
-                let field = descriptor
-                    .field()
-                    .iter()
-                    .find(|f| f.number() as u32 == field_number);
-                if let Some(field) = field {
-                    eprintln!(
-                        "Msg {} Field number: {}, Field name {}, wire type {}",
-                        descriptor.name(),
-                        field_number,
-                        field.name(),
-                        tag & 7
-                    );
-                } else {
-                    // field not found in descriptor, treat as unknown
-                    eprintln!(
-                        "Msg {} Unknown Field number: {}, wire type {}",
-                        descriptor.name(),
-                        field_number,
-                        tag & 7
-                    );
-                }
-            }
             if let Some(entry) = ctx.msg.table.entry(field_number) {
                 'unknown: {
                     match entry.kind() {
@@ -571,13 +959,13 @@ fn decode_loop<'a>(
                             if tag & 7 != 1 {
                                 break 'unknown;
                             };
-                            ctx.set(entry, field_number, cursor.read_unaligned::<u64>());
+                            ctx.set(entry, field_number, cursor.read_fixed::<u64>());
                         }
                         FieldKind::Fixed32 => {
                             if tag & 7 != 5 {
                                 break 'unknown;
                             };
-                            ctx.set(entry, field_number, cursor.read_unaligned::<u32>());
+                            ctx.set(entry, field_number, cursor.read_fixed::<u32>());
                         }
                         FieldKind::Bytes | FieldKind::String => {
                             if tag & 7 != 2 {
@@ -585,6 +973,9 @@ fn decode_loop<'a>(
                             };
                             let validate_utf8 = entry.kind() == FieldKind::String;
                             let len = cursor.read_size()?;
+                            if len as usize > options.max_string_len {
+                                return None;
+                            }
                             if cursor - limited_end + len <= SLOP_SIZE as isize {
                                 let slice = cursor.read_slice(len);
                                 if validate_utf8 && core::str::from_utf8(slice).is_err() {
@@ -592,7 +983,7 @@ fn decode_loop<'a>(
                                 }
                                 ctx.set_bytes(entry, field_number, slice, arena).ok()?;
                             } else {
-                                ctx.push_limit(len, cursor, end, stack)?;
+                                ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
 
                                 let DecodeObjectState { limit, msg } = ctx;
 
@@ -632,7 +1023,7 @@ fn decode_loop<'a>(
                                     field_number;
                             }
                             let len = cursor.read_size()?;
-                            limited_end = ctx.push_limit(len, cursor, end, stack)?;
+                            limited_end = ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
 
                             ctx.update(|ctx| {
                                 let limit = ctx.limit;
@@ -640,23 +1031,40 @@ fn decode_loop<'a>(
                                 let msg = ctx.get_or_create_child_object(entry, arena).unwrap();
                                 DecodeObjectState { limit, msg }
                             });
+                            trace_event!(
+                                message_type = ctx.msg.table.full_name,
+                                field_number,
+                                "decoding sub-message"
+                            );
                         }
                         FieldKind::Group => {
                             if tag & 7 != 3 {
                                 break 'unknown;
                             };
-                            ctx.push_group(field_number, stack)?;
+                            // For oneof group fields, set the discriminant
+                            let has_bit_idx = entry.has_bit_idx();
+                            if has_bit_idx & 0x80 != 0 {
+                                let discriminant_word_idx = has_bit_idx & 0x7F;
+                                *ctx.msg.object.ref_mut::<u32>(discriminant_word_idx * 4) =
+                                    field_number;
+                            }
+                            ctx.push_group(field_number, stack, arena)?;
                             ctx.update(|ctx| {
                                 let limit = ctx.limit;
                                 // TODO: remove unwrap
                                 let msg = ctx.get_or_create_child_object(entry, arena).unwrap();
                                 DecodeObjectState { limit, msg }
                             });
+                            trace_event!(
+                                message_type = ctx.msg.table.full_name,
+                                field_number,
+                                "decoding sub-message"
+                            );
                         }
                         FieldKind::RepeatedVarint64 => {
                             if tag & 7 == 0 {
                                 // Unpacked
-                                ctx.add(entry, cursor.read_varint()?, arena).ok()?;
+                                ctx.add(entry, cursor.read_varint()?, arena, options)?;
                             } else if tag & 7 == 2 {
                                 // Packed
                                 let len = cursor.read_size()?;
@@ -668,18 +1076,34 @@ fn decode_loop<'a>(
                                         .object
                                         .ref_mut::<RepeatedField<u64>>(entry.offset());
                                     let end = (cursor + len).0;
-                                    cursor = unpack_varint(field, cursor, end, arena, |v| v)?;
+                                    cursor = unpack_varint(
+                                        field,
+                                        cursor,
+                                        end,
+                                        0,
+                                        arena,
+                                        options,
+                                        |v| v,
+                                    )?;
                                     if cursor != end {
                                         return None;
                                     }
                                 } else {
                                     // Slow path: field spans buffers - transition to resumable parsing
-                                    ctx.push_limit(len, cursor, end, stack)?;
+                                    ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
                                     let field = ctx
                                         .msg
                                         .object
                                         .ref_mut::<RepeatedField<u64>>(entry.offset());
-                                    cursor = unpack_varint(field, cursor, end, arena, |v| v)?;
+                                    cursor = unpack_varint(
+                                        field,
+                                        cursor,
+                                        end,
+                                        ctx.limit,
+                                        arena,
+                                        options,
+                                        |v| v,
+                                    )?;
                                     return Some((
                                         cursor,
                                         ctx.limit,
@@ -693,7 +1117,7 @@ fn decode_loop<'a>(
                         FieldKind::RepeatedVarint32 | FieldKind::RepeatedInt32 => {
                             if tag & 7 == 0 {
                                 // Unpacked
-                                ctx.add(entry, cursor.read_varint()? as u32, arena).ok()?;
+                                ctx.add(entry, cursor.read_varint()? as u32, arena, options)?;
                             } else if tag & 7 == 2 {
                                 // Packed
                                 let len = cursor.read_size()?;
@@ -705,20 +1129,34 @@ fn decode_loop<'a>(
                                         .object
                                         .ref_mut::<RepeatedField<u32>>(entry.offset());
                                     let end = (cursor + len).0;
-                                    cursor =
-                                        unpack_varint(field, cursor, end, arena, |v| v as u32)?;
+                                    cursor = unpack_varint(
+                                        field,
+                                        cursor,
+                                        end,
+                                        0,
+                                        arena,
+                                        options,
+                                        |v| v as u32,
+                                    )?;
                                     if cursor != end {
                                         return None;
                                     }
                                 } else {
                                     // Slow path: field spans buffers - transition to resumable parsing
-                                    ctx.push_limit(len, cursor, end, stack)?;
+                                    ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
                                     let field = ctx
                                         .msg
                                         .object
                                         .ref_mut::<RepeatedField<u32>>(entry.offset());
-                                    cursor =
-                                        unpack_varint(field, cursor, end, arena, |v| v as u32)?;
+                                    cursor = unpack_varint(
+                                        field,
+                                        cursor,
+                                        end,
+                                        ctx.limit,
+                                        arena,
+                                        options,
+                                        |v| v as u32,
+                                    )?;
                                     return Some((
                                         cursor,
                                         ctx.limit,
@@ -732,7 +1170,12 @@ fn decode_loop<'a>(
                         FieldKind::RepeatedVarint64Zigzag => {
                             if tag & 7 == 0 {
                                 // Unpacked
-                                ctx.add(entry, zigzag_decode(cursor.read_varint()?), arena).ok()?;
+                                ctx.add(
+                                    entry,
+                                    zigzag_decode(cursor.read_varint()?),
+                                    arena,
+                                    options,
+                                )?;
                             } else if tag & 7 == 2 {
                                 // Packed
                                 let len = cursor.read_size()?;
@@ -744,7 +1187,7 @@ fn decode_loop<'a>(
                                         .object
                                         .ref_mut::<RepeatedField<i64>>(entry.offset());
                                     let end = (cursor + len).0;
-                                    cursor = unpack_varint(field, cursor, end, arena, |v| {
+                                    cursor = unpack_varint(field, cursor, end, 0, arena, options, |v| {
                                         zigzag_decode(v)
                                     })?;
                                     if cursor != end {
@@ -752,12 +1195,12 @@ fn decode_loop<'a>(
                                     }
                                 } else {
                                     // Slow path: field spans buffers - transition to resumable parsing
-                                    ctx.push_limit(len, cursor, end, stack)?;
+                                    ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
                                     let field = ctx
                                         .msg
                                         .object
                                         .ref_mut::<RepeatedField<i64>>(entry.offset());
-                                    cursor = unpack_varint(field, cursor, end, arena, |v| {
+                                    cursor = unpack_varint(field, cursor, end, ctx.limit, arena, options, |v| {
                                         zigzag_decode(v)
                                     })?;
                                     return Some((
@@ -777,7 +1220,8 @@ fn decode_loop<'a>(
                                     entry,
                                     zigzag_decode(cursor.read_varint()? as u32 as u64) as i32,
                                     arena,
-                                ).ok()?;
+                                    options,
+                                )?;
                             } else if tag & 7 == 2 {
                                 // Packed
                                 let len = cursor.read_size()?;
@@ -789,7 +1233,7 @@ fn decode_loop<'a>(
                                         .object
                                         .ref_mut::<RepeatedField<i32>>(entry.offset());
                                     let end = (cursor + len).0;
-                                    cursor = unpack_varint(field, cursor, end, arena, |v| {
+                                    cursor = unpack_varint(field, cursor, end, 0, arena, options, |v| {
                                         zigzag_decode(v as u32 as u64) as i32
                                     })?;
                                     if cursor != end {
@@ -797,12 +1241,12 @@ fn decode_loop<'a>(
                                     }
                                 } else {
                                     // Slow path: field spans buffers - transition to resumable parsing
-                                    ctx.push_limit(len, cursor, end, stack)?;
+                                    ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
                                     let field = ctx
                                         .msg
                                         .object
                                         .ref_mut::<RepeatedField<i32>>(entry.offset());
-                                    cursor = unpack_varint(field, cursor, end, arena, |v| {
+                                    cursor = unpack_varint(field, cursor, end, ctx.limit, arena, options, |v| {
                                         zigzag_decode(v as u32 as u64) as i32
                                     })?;
                                     return Some((
@@ -819,7 +1263,7 @@ fn decode_loop<'a>(
                             if tag & 7 == 0 {
                                 // Unpacked
                                 let val = cursor.read_varint()?;
-                                ctx.add(entry, val != 0, arena).ok()?;
+                                ctx.add(entry, val != 0, arena, options)?;
                             } else if tag & 7 == 2 {
                                 // Packed
                                 let len = cursor.read_size()?;
@@ -831,18 +1275,34 @@ fn decode_loop<'a>(
                                         .object
                                         .ref_mut::<RepeatedField<bool>>(entry.offset());
                                     let end = (cursor + len).0;
-                                    cursor = unpack_varint(field, cursor, end, arena, |v| v != 0)?;
+                                    cursor = unpack_varint(
+                                        field,
+                                        cursor,
+                                        end,
+                                        0,
+                                        arena,
+                                        options,
+                                        |v| v != 0,
+                                    )?;
                                     if cursor != end {
                                         return None;
                                     }
                                 } else {
                                     // Slow path: field spans buffers - transition to resumable parsing
-                                    ctx.push_limit(len, cursor, end, stack)?;
+                                    ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
                                     let field = ctx
                                         .msg
                                         .object
                                         .ref_mut::<RepeatedField<bool>>(entry.offset());
-                                    cursor = unpack_varint(field, cursor, end, arena, |v| v != 0)?;
+                                    cursor = unpack_varint(
+                                        field,
+                                        cursor,
+                                        end,
+                                        ctx.limit,
+                                        arena,
+                                        options,
+                                        |v| v != 0,
+                                    )?;
                                     return Some((
                                         cursor,
                                         ctx.limit,
@@ -856,7 +1316,7 @@ fn decode_loop<'a>(
                         FieldKind::RepeatedFixed64 => {
                             if tag & 7 == 1 {
                                 // Unpacked
-                                ctx.add(entry, cursor.read_unaligned::<u64>(), arena).ok()?;
+                                ctx.add(entry, cursor.read_fixed::<u64>(), arena, options)?;
                             } else if tag & 7 == 2 {
                                 // Packed
                                 let len = cursor.read_size()?;
@@ -868,18 +1328,18 @@ fn decode_loop<'a>(
                                         .object
                                         .ref_mut::<RepeatedField<u64>>(entry.offset());
                                     let end = (cursor + len).0;
-                                    cursor = unpack_fixed(field, cursor, end, arena)?;
+                                    cursor = unpack_fixed(field, cursor, end, 0, arena, options)?;
                                     if cursor != end {
                                         return None;
                                     }
                                 } else {
                                     // Slow path: field spans buffers - transition to resumable parsing
-                                    ctx.push_limit(len, cursor, end, stack)?;
+                                    ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
                                     let field = ctx
                                         .msg
                                         .object
                                         .ref_mut::<RepeatedField<u64>>(entry.offset());
-                                    cursor = unpack_fixed(field, cursor, end, arena)?;
+                                    cursor = unpack_fixed(field, cursor, end, ctx.limit, arena, options)?;
                                     return Some((
                                         cursor,
                                         ctx.limit,
@@ -893,7 +1353,7 @@ fn decode_loop<'a>(
                         FieldKind::RepeatedFixed32 => {
                             if tag & 7 == 5 {
                                 // Unpacked
-                                ctx.add(entry, cursor.read_unaligned::<u32>(), arena).ok()?;
+                                ctx.add(entry, cursor.read_fixed::<u32>(), arena, options)?;
                             } else if tag & 7 == 2 {
                                 // Packed
                                 let len = cursor.read_size()?;
@@ -905,18 +1365,18 @@ fn decode_loop<'a>(
                                         .object
                                         .ref_mut::<RepeatedField<u32>>(entry.offset());
                                     let end = (cursor + len).0;
-                                    cursor = unpack_fixed(field, cursor, end, arena)?;
+                                    cursor = unpack_fixed(field, cursor, end, 0, arena, options)?;
                                     if cursor != end {
                                         return None;
                                     }
                                 } else {
                                     // Slow path: field spans buffers - transition to resumable parsing
-                                    ctx.push_limit(len, cursor, end, stack)?;
+                                    ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
                                     let field = ctx
                                         .msg
                                         .object
                                         .ref_mut::<RepeatedField<u32>>(entry.offset());
-                                    cursor = unpack_fixed(field, cursor, end, arena)?;
+                                    cursor = unpack_fixed(field, cursor, end, ctx.limit, arena, options)?;
                                     return Some((
                                         cursor,
                                         ctx.limit,
@@ -933,6 +1393,10 @@ fn decode_loop<'a>(
                             };
                             let validate_utf8 = entry.kind() == FieldKind::RepeatedString;
                             let len = cursor.read_size()?;
+                            if len as usize > options.max_string_len {
+                                return None;
+                            }
+                            ctx.check_repeated_len::<Bytes>(entry, options)?;
                             if cursor - limited_end + len <= SLOP_SIZE as isize {
                                 let slice = cursor.read_slice(len);
                                 if validate_utf8 && core::str::from_utf8(slice).is_err() {
@@ -940,7 +1404,7 @@ fn decode_loop<'a>(
                                 }
                                 ctx.msg.object.add_bytes(entry.aux_offset(), slice, arena).ok()?;
                             } else {
-                                ctx.push_limit(len, cursor, end, stack)?;
+                                ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
                                 let DecodeObjectState { limit, msg } = ctx;
                                 let slice = cursor.read_slice(SLOP_SIZE as isize - (cursor - end));
                                 let bytes = msg.object.add_bytes(entry.aux_offset(), slice, arena).ok()?;
@@ -956,15 +1420,106 @@ fn decode_loop<'a>(
                                 break 'unknown;
                             };
                             let len = cursor.read_size()?;
-                            limited_end = ctx.push_limit(len, cursor, end, stack)?;
-                            ctx.msg = ctx.add_child_object(entry, arena).ok()?;
+                            limited_end = ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
+                            ctx.check_repeated_len::<*mut Object>(entry, options)?;
+                            ctx.msg = ctx.add_child_object(entry, arena, &mut session.child_slabs).ok()?;
+                            trace_event!(
+                                message_type = ctx.msg.table.full_name,
+                                field_number,
+                                "decoding sub-message"
+                            );
                         }
                         FieldKind::RepeatedGroup => {
                             if tag & 7 != 3 {
                                 break 'unknown;
                             };
-                            ctx.push_group(field_number, stack)?;
-                            ctx.msg = ctx.add_child_object(entry, arena).ok()?;
+                            ctx.push_group(field_number, stack, arena)?;
+                            ctx.check_repeated_len::<*mut Object>(entry, options)?;
+                            ctx.msg = ctx.add_child_object(entry, arena, &mut session.child_slabs).ok()?;
+                            trace_event!(
+                                message_type = ctx.msg.table.full_name,
+                                field_number,
+                                "decoding sub-message"
+                            );
+                        }
+                        FieldKind::ClosedEnum => {
+                            if tag & 7 != 0 {
+                                break 'unknown;
+                            };
+                            let (entry, enum_table) = ctx.msg.table.closed_enum_entry(entry);
+                            let val = cursor.read_varint()? as u32;
+                            if enum_table.contains(val as i32) {
+                                ctx.set(entry, field_number, val);
+                            } else {
+                                debug_event!(
+                                    message_type = ctx.msg.table.full_name,
+                                    field_number,
+                                    value = val as i32,
+                                    "dropping unknown enum value"
+                                );
+                                session.unknown.add_field(cursor.0.as_ptr() as isize - session.field_start as isize);
+                            }
+                        }
+                        FieldKind::RepeatedClosedEnum => {
+                            let (entry, enum_table) = ctx.msg.table.closed_enum_entry(entry);
+                            if tag & 7 == 0 {
+                                // Unpacked
+                                let val = cursor.read_varint()? as u32;
+                                if enum_table.contains(val as i32) {
+                                    ctx.add(entry, val, arena, options)?;
+                                } else {
+                                    session.unknown.add_field(cursor.0.as_ptr() as isize - session.field_start as isize);
+                                }
+                            } else if tag & 7 == 2 {
+                                // Packed
+                                let len = cursor.read_size()?;
+
+                                // Fast path: entire packed field fits in buffer
+                                if cursor - limited_end + len <= 0 {
+                                    let field = ctx
+                                        .msg
+                                        .object
+                                        .ref_mut::<RepeatedField<u32>>(entry.offset());
+                                    let end = (cursor + len).0;
+                                    cursor = unpack_closed_enum(
+                                        field,
+                                        cursor,
+                                        end,
+                                        0,
+                                        arena,
+                                        options,
+                                        enum_table,
+                                        &mut session.unknown,
+                                    )?;
+                                    if cursor != end {
+                                        return None;
+                                    }
+                                } else {
+                                    // Slow path: field spans buffers - transition to resumable parsing
+                                    ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
+                                    let field = ctx
+                                        .msg
+                                        .object
+                                        .ref_mut::<RepeatedField<u32>>(entry.offset());
+                                    cursor = unpack_closed_enum(
+                                        field,
+                                        cursor,
+                                        end,
+                                        ctx.limit,
+                                        arena,
+                                        options,
+                                        enum_table,
+                                        &mut session.unknown,
+                                    )?;
+                                    return Some((
+                                        cursor,
+                                        ctx.limit,
+                                        DecodeObject::PackedClosedEnum(field, enum_table),
+                                    ));
+                                }
+                            } else {
+                                break 'unknown;
+                            }
                         }
                         FieldKind::Unknown => {
                             break 'unknown;
@@ -985,6 +1540,13 @@ fn decode_loop<'a>(
                 // field number 0 is invalid
                 return None;
             }
+            debug_event!(
+                message_type = ctx.msg.table.full_name,
+                field_number,
+                wire_type = tag & 7,
+                "skipping unknown field"
+            );
+            let start = cursor - end - varint_size(tag as u64);
             match tag & 7 {
                 0 => {
                     // varint
@@ -1000,19 +1562,24 @@ fn decode_loop<'a>(
                     if cursor - limited_end + len <= SLOP_SIZE as isize {
                         cursor.read_slice(len);
                     } else {
-                        ctx.push_limit(len, cursor, end, stack)?;
+                        ctx.push_limit(field_number, len, cursor, end, stack, arena)?;
+                        // The value is counted as it is skipped
+                        session.unknown.add_field(cursor - end - start);
                         return Some((cursor, ctx.limit, DecodeObject::SkipLengthDelimited));
                     }
                 }
                 3 => {
                     // start group
                     // push to stack until end group
-                    ctx.push_group(field_number, stack)?;
-                    return skip_group(ctx.limit, cursor, end, stack, arena);
+                    ctx.push_group(field_number, stack, arena)?;
+                    session.unknown.groups += 1;
+                    session.unknown.add_field(cursor - end - start);
+                    return skip_group(ctx.limit, cursor, end, stack, arena, session);
                 }
                 4 => {
                     // end group
                     ctx.pop_group(field_number, stack)?;
+                    continue;
                 }
                 5 => {
                     // fixed32
@@ -1022,6 +1589,7 @@ fn decode_loop<'a>(
                     return None;
                 }
             }
+            session.unknown.add_field(cursor - end - start);
         }
         if cursor - end == ctx.limit {
             if stack.is_empty() {
@@ -1047,12 +1615,30 @@ struct ResumeableState<'a> {
 }
 
 impl<'a> ResumeableState<'a> {
+    /// Decode `buf`, the input from `offset` on.
     fn go_decode(
+        self,
+        buf: &[u8],
+        offset: isize,
+        stack: &mut Stack<StackEntry>,
+        arena: &mut crate::arena::Arena,
+        session: &mut DecodeSession,
+    ) -> Option<Self> {
+        let state = self.decode_buffer(buf, stack, arena, session);
+        if !session.field_start.is_null() {
+            session.note_field(buf, offset);
+        }
+        state
+    }
+
+    fn decode_buffer(
         mut self,
         buf: &[u8],
         stack: &mut Stack<StackEntry>,
         arena: &mut crate::arena::Arena,
+        session: &mut DecodeSession,
     ) -> Option<Self> {
+        stack.set_max_depth(session.options.max_depth);
         let len = buf.len() as isize;
         self.limit -= len;
         if self.overrun >= len {
@@ -1067,15 +1653,15 @@ impl<'a> ResumeableState<'a> {
                     limit: self.limit,
                     msg,
                 };
-                decode_loop(ctx, cursor, end, stack, arena)?
+                decode_loop(ctx, cursor, end, stack, arena, session)?
             }
             DecodeObject::Bytes(bytes, validate_utf8) => {
-                decode_string(self.limit, bytes, validate_utf8, cursor, end, stack, arena)?
+                decode_string(self.limit, bytes, validate_utf8, cursor, end, stack, arena, session)?
             }
             DecodeObject::SkipLengthDelimited => {
-                skip_length_delimited(self.limit, cursor, end, stack, arena)?
+                skip_length_delimited(self.limit, cursor, end, stack, arena, session)?
             }
-            DecodeObject::SkipGroup => skip_group(self.limit, cursor, end, stack, arena)?,
+            DecodeObject::SkipGroup => skip_group(self.limit, cursor, end, stack, arena, session)?,
             DecodeObject::PackedU64(field) => decode_packed(
                 self.limit,
                 field,
@@ -1083,6 +1669,7 @@ impl<'a> ResumeableState<'a> {
                 end,
                 stack,
                 arena,
+                session,
                 |v| v,
                 DecodeObject::PackedU64,
             )?,
@@ -1093,6 +1680,7 @@ impl<'a> ResumeableState<'a> {
                 end,
                 stack,
                 arena,
+                session,
                 |v| v as u32,
                 DecodeObject::PackedU32,
             )?,
@@ -1103,6 +1691,7 @@ impl<'a> ResumeableState<'a> {
                 end,
                 stack,
                 arena,
+                session,
                 zigzag_decode,
                 DecodeObject::PackedI64Zigzag,
             )?,
@@ -1113,6 +1702,7 @@ impl<'a> ResumeableState<'a> {
                 end,
                 stack,
                 arena,
+                session,
                 |v| zigzag_decode(v as u32 as u64) as i32,
                 DecodeObject::PackedI32Zigzag,
             )?,
@@ -1123,19 +1713,23 @@ impl<'a> ResumeableState<'a> {
                 end,
                 stack,
                 arena,
+                session,
                 |v| v != 0,
                 DecodeObject::PackedBool,
             )?,
             DecodeObject::PackedFixed64(field) => {
-                decode_fixed(self.limit, field, cursor, end, stack, arena, |f| {
+                decode_fixed(self.limit, field, cursor, end, stack, arena, session, |f| {
                     DecodeObject::PackedFixed64(f)
                 })?
             }
             DecodeObject::PackedFixed32(field) => {
-                decode_fixed(self.limit, field, cursor, end, stack, arena, |f| {
+                decode_fixed(self.limit, field, cursor, end, stack, arena, session, |f| {
                     DecodeObject::PackedFixed32(f)
                 })?
             }
+            DecodeObject::PackedClosedEnum(field, enum_table) => decode_packed_closed_enum(
+                self.limit, field, enum_table, cursor, end, stack, arena, session,
+            )?,
             DecodeObject::None => unreachable!(),
         };
         self.limit = new_limit;
@@ -1145,16 +1739,62 @@ impl<'a> ResumeableState<'a> {
     }
 }
 
+/// Varint at `*pos` in `buf`, None if it runs past the end.
+fn read_varint(buf: &[u8], pos: &mut isize) -> Option<u64> {
+    let mut reader = Reader::new(buf.get(*pos as usize..)?);
+    let value = reader.read_varint().ok()?;
+    *pos = (buf.len() - reader.remaining().len()) as isize;
+    Some(value)
+}
+
+/// Decoder fed the input of a message in chunks of any size, for protocol
+/// code receiving it piecemeal.
+///
+/// Pass each chunk to [`resume`](Self::resume) as it arrives and call
+/// [`finish`](Self::finish) once the input is complete.
+/// [`remaining_expected_bytes`](Self::remaining_expected_bytes) tells whether
+/// it already is. The last 16 bytes received are only parsed when more
+/// input arrives or by `finish`.
+///
+/// Every call must get the same arena. The part of the message decoded so
+/// far, the objects set aside for upcoming elements of repeated message
+/// fields, and the decoder's stack once it outgrows `STACK_DEPTH`, live in
+/// it, so an arena can't be swapped for a fresh one between chunks to bound
+/// memory: the earlier arena could be dropped while the message still points
+/// into it. A decoder given another arena fails instead.
 #[repr(C)]
 pub struct ResumeableDecode<'a, const STACK_DEPTH: usize> {
     state: MaybeUninit<ResumeableState<'a>>,
     patch_buffer: [u8; SLOP_SIZE * 2],
     stack: StackWithStorage<StackEntry, STACK_DEPTH>,
+    session: DecodeSession,
+    /// Table of the message decoded into.
+    table: &'a Table,
+    /// Input bytes received so far, checked against `max_message_bytes`.
+    total_bytes: usize,
+    /// Id of the arena of the first call, 0 before it.
+    arena_id: usize,
 }
 
 impl<'a, const STACK_DEPTH: usize> ResumeableDecode<'a, STACK_DEPTH> {
-    pub fn new<'pool: 'a>(msg: crate::reflection::DynamicMessage<'pool, 'a>, limit: isize) -> Self {
-        let object = DecodeObject::Message(msg);
+    /// Decode into `msg`, whose encoding is `limit` bytes long, `isize::MAX`
+    /// if it runs up to the end of the input.
+    ///
+    /// If the table of `msg` was generated for another runtime version, see
+    /// [`Table::check_version`](crate::generated_code_only::Table::check_version),
+    /// the decoder rejects all input.
+    pub fn new<'pool: 'a>(
+        msg: crate::reflection::DynamicMessage<'pool, 'a>,
+        limit: isize,
+        options: DecodeOptions,
+    ) -> Self {
+        let table = msg.table;
+        let object = if table.check_version().is_ok() {
+            DecodeObject::Message(msg)
+        } else {
+            debug_event!("table generated for another protocrap version");
+            DecodeObject::None
+        };
         Self {
             state: MaybeUninit::new(ResumeableState {
                 limit,
@@ -1163,52 +1803,302 @@ impl<'a, const STACK_DEPTH: usize> ResumeableDecode<'a, STACK_DEPTH> {
             }),
             patch_buffer: [0; SLOP_SIZE * 2],
             stack: Default::default(),
+            session: DecodeSession {
+                options,
+                unknown: UnknownFieldStats::default(),
+                child_slabs: ChildSlabs::new(),
+                field_start: core::ptr::null(),
+                last_field: (0, 0),
+            },
+            table,
+            total_bytes: 0,
+            arena_id: 0,
         }
     }
 
+    /// Decode the next chunk of input, allocating in `arena`. False if the
+    /// input is malformed, exceeds the [`DecodeOptions`] limits or `arena`
+    /// isn't the one of earlier calls, after which the decoder rejects
+    /// everything.
     #[must_use]
     pub fn resume(&mut self, buf: &[u8], arena: &mut crate::arena::Arena) -> bool {
-        self.resume_impl(buf, arena).is_some()
+        if self.use_arena(arena) && self.resume_impl(buf, arena).is_some() {
+            return true;
+        }
+        // The state was moved out or is stale, later calls must fail
+        self.state.write(ResumeableState {
+            limit: 0,
+            object: DecodeObject::None,
+            overrun: 0,
+        });
+        false
+    }
+
+    /// Number of bytes still needed before the input passed to
+    /// [`resume`](Self::resume) forms a complete message, 0 if it does and
+    /// [`finish`](Self::finish) can be called.
+    ///
+    /// Open sub-messages and length-delimited fields are waited for up to
+    /// their end, so the count is exact for them. A group, or a tag or
+    /// varint cut short, needs at least one more byte without its total
+    /// being known: the count is then a lower bound, to be asked again after
+    /// the next `resume`. Also 0 once decoding failed, `finish` then reports
+    /// the failure.
+    pub fn remaining_expected_bytes(&self) -> usize {
+        let state = unsafe { self.state.assume_init_ref() };
+        if matches!(state.object, DecodeObject::None) {
+            return 0;
+        }
+        // Positions are relative to the end of the parsed input. The last
+        // SLOP_SIZE bytes received are at the start of the patch buffer, not
+        // parsed from the cursor on.
+        let received = SLOP_SIZE as isize;
+        let mut pos = match state.object {
+            DecodeObject::Message(_) | DecodeObject::SkipGroup => state.overrun,
+            // Inside a length-delimited value, scan on from its end
+            _ => state.limit,
+        };
+        // End of the innermost open context and, from the stack, how it
+        // closes: at its end for a length, or by an END_GROUP tag
+        let mut end = state.limit;
+        let mut frames = self.stack.iter();
+        let mut frame = frames.next();
+        let mut missing = 0;
+        // Scan the fields not parsed yet
+        let tail = &self.patch_buffer[..SLOP_SIZE];
+        let mut groups = 0;
+        while pos < received {
+            if pos >= end {
+                match frame {
+                    Some(entry) if groups == 0 && pos == end && entry.delta_limit_or_group_tag >= 0 => {
+                        end += entry.delta_limit_or_group_tag;
+                        frame = frames.next();
+                        continue;
+                    }
+                    _ => return 0,
+                }
+            }
+            let Some(tag) = read_varint(tail, &mut pos) else {
+                missing = 1;
+                break;
+            };
+            match tag & 7 {
+                0 => {
+                    if read_varint(tail, &mut pos).is_none() {
+                        missing = 1;
+                        break;
+                    }
+                }
+                1 => pos += 8,
+                2 => match read_varint(tail, &mut pos) {
+                    Some(len) if len <= isize::MAX as u64 - pos as u64 => pos += len as isize,
+                    Some(_) => return 0,
+                    None => {
+                        missing = 1;
+                        break;
+                    }
+                },
+                3 => groups += 1,
+                4 if groups > 0 => groups -= 1,
+                4 => match frame {
+                    Some(entry) if -entry.delta_limit_or_group_tag == (tag >> 3) as isize => {
+                        frame = frames.next();
+                    }
+                    _ => return 0,
+                },
+                5 => pos += 4,
+                _ => return 0,
+            }
+        }
+        if groups > 0 {
+            missing = 1;
+        }
+        let mut needed = missing.max(pos - received);
+        // All open contexts but the top-level message need to close
+        while let Some(entry) = frame {
+            if entry.delta_limit_or_group_tag >= 0 {
+                needed = needed.max(end - received);
+                end += entry.delta_limit_or_group_tag;
+            } else {
+                needed = needed.max(1);
+            }
+            frame = frames.next();
+        }
+        needed as usize
     }
 
+    /// Unknown fields skipped in the input decoded so far. The last 16
+    /// bytes received are only counted once parsed.
+    pub fn unknown_fields(&self) -> UnknownFieldStats {
+        self.session.unknown
+    }
+
+    /// Decode the rest of the input, allocating in `arena`. False if it
+    /// doesn't end the message, if decoding failed before, or if `arena`
+    /// isn't the one of earlier calls.
     #[must_use]
     pub fn finish(self, arena: &mut crate::arena::Arena) -> bool {
-        let ResumeableDecode {
-            state,
-            patch_buffer,
-            mut stack,
-        } = self;
-        let state = unsafe { state.assume_init() };
-        if matches!(state.object, DecodeObject::None) {
-            return false;
+        self.finish_with_stats(arena).is_some()
+    }
+
+    /// Like [`finish`](Self::finish), returning the unknown fields skipped
+    /// in the whole input on success.
+    #[must_use]
+    pub fn finish_with_stats(mut self, arena: &mut crate::arena::Arena) -> Option<UnknownFieldStats> {
+        if !self.use_arena(arena) {
+            return None;
+        }
+        self.finish_impl(arena)
+    }
+
+    /// Like [`finish_with_stats`](Self::finish_with_stats), telling where
+    /// decoding failed if it did, see [`error`](Self::error).
+    pub(crate) fn try_finish(
+        mut self,
+        arena: &mut crate::arena::Arena,
+    ) -> Result<UnknownFieldStats, DecodeError> {
+        let finished = if self.use_arena(arena) { self.finish_impl(arena) } else { None };
+        finished.ok_or_else(|| self.error())
+    }
+
+    /// Where a failed decode went wrong: the start of the last field the
+    /// decoder began, and the path to it through the sub-messages still open
+    /// on the stack. The messages decoded into must still be alive.
+    pub(crate) fn error(&self) -> DecodeError {
+        let (offset, field_number) = self.session.last_field;
+        #[cfg_attr(not(feature = "alloc"), allow(unused_mut))]
+        let mut error = DecodeError::at(offset.max(0) as usize);
+        #[cfg(not(feature = "alloc"))]
+        let _ = field_number;
+        #[cfg(feature = "alloc")]
+        {
+            let open: alloc::vec::Vec<_> = self.stack.iter().collect();
+            let mut table = self.table;
+            // Outermost first, ending with the field being decoded
+            for entry in open.into_iter().rev() {
+                let Some((obj, obj_table)) = &entry.obj_table else {
+                    // Inside an unknown group
+                    return error;
+                };
+                table = obj_table.as_ref();
+                let Some(field) = table.entry(entry.field_number) else {
+                    return error;
+                };
+                let (offset, child_table) = match field.kind() {
+                    FieldKind::Message
+                    | FieldKind::Group
+                    | FieldKind::RepeatedMessage
+                    | FieldKind::RepeatedGroup => table.aux_entry_decode(field),
+                    // The failure is in the value of this field
+                    _ => {
+                        error.push_field(table, entry.field_number, None);
+                        return error;
+                    }
+                };
+                let index = match field.kind() {
+                    // The element being decoded is the last one
+                    FieldKind::RepeatedMessage | FieldKind::RepeatedGroup => {
+                        let elements = obj.as_ref().get_slice::<Message>(offset as usize);
+                        Some(elements.len().saturating_sub(1))
+                    }
+                    _ => None,
+                };
+                if !error.push_field(table, entry.field_number, index) {
+                    return error;
+                }
+                table = child_table;
+            }
+            error.push_field(table, field_number, None);
         }
-        let Some(state) = state.go_decode(&patch_buffer[..SLOP_SIZE], &mut stack, arena) else {
+        error
+    }
+
+    /// Note `arena` as the one of the decode, false if earlier calls got
+    /// another one.
+    fn use_arena(&mut self, arena: &crate::arena::Arena) -> bool {
+        if self.arena_id != 0 && self.arena_id != arena.id() {
+            debug_event!("resumed with another arena");
             return false;
-        };
+        }
+        self.arena_id = arena.id();
+        true
+    }
+
+    fn finish_impl(&mut self, arena: &mut crate::arena::Arena) -> Option<UnknownFieldStats> {
+        // Only read once, `finish` consumes the decoder
+        let state = unsafe { self.state.assume_init_read() };
+        if matches!(state.object, DecodeObject::None) {
+            return None;
+        }
+        let stack = &mut self.stack;
+        let session = &mut self.session;
+        let offset = self.total_bytes as isize - SLOP_SIZE as isize;
+        let state = state.go_decode(&self.patch_buffer[..SLOP_SIZE], offset, stack, arena, session)?;
 
-        state.overrun == 0 && matches!(state.object, DecodeObject::Message(_)) && stack.is_empty()
+        let complete =
+            state.overrun == 0 && matches!(state.object, DecodeObject::Message(_)) && stack.is_empty();
+        if !complete {
+            return None;
+        }
+        #[cfg(feature = "alloc")]
+        if session.options.dedup_map_entries
+            && let DecodeObject::Message(mut msg) = state.object
+        {
+            msg.normalize_maps();
+        }
+        Some(session.unknown)
     }
 
     fn resume_impl(&mut self, buf: &[u8], arena: &mut crate::arena::Arena) -> Option<()> {
         let size = buf.len();
+        self.total_bytes = self.total_bytes.saturating_add(size);
+        if self.total_bytes > self.session.options.max_message_bytes {
+            debug_event!(
+                total_bytes = self.total_bytes,
+                "message exceeds byte limit"
+            );
+            return None;
+        }
+        let session = &mut self.session;
         let mut state = unsafe { self.state.assume_init_read() };
         if matches!(state.object, DecodeObject::None) {
             // Already finished
             return None;
         }
+        // The patch buffer starts with the last SLOP_SIZE bytes received before
+        let received = self.total_bytes.saturating_sub(size) as isize;
+        let patch_offset = received - SLOP_SIZE as isize;
         if buf.len() > SLOP_SIZE {
             self.patch_buffer[SLOP_SIZE..].copy_from_slice(&buf[..SLOP_SIZE]);
-            state = state.go_decode(&self.patch_buffer[..SLOP_SIZE], &mut self.stack, arena)?;
+            state = state.go_decode(
+                &self.patch_buffer[..SLOP_SIZE],
+                patch_offset,
+                &mut self.stack,
+                arena,
+                session,
+            )?;
             if matches!(state.object, DecodeObject::None) {
                 // TODO: Alter the state to indicate that we've ended on a 0 tag
                 // Ended on 0 tag
                 return None;
             }
-            state = state.go_decode(&buf[..size - SLOP_SIZE], &mut self.stack, arena)?;
+            state = state.go_decode(
+                &buf[..size - SLOP_SIZE],
+                received,
+                &mut self.stack,
+                arena,
+                session,
+            )?;
             self.patch_buffer[..SLOP_SIZE].copy_from_slice(&buf[size - SLOP_SIZE..]);
         } else {
             self.patch_buffer[SLOP_SIZE..SLOP_SIZE + size].copy_from_slice(buf);
-            state = state.go_decode(&self.patch_buffer[..size], &mut self.stack, arena)?;
+            state = state.go_decode(
+                &self.patch_buffer[..size],
+                patch_offset,
+                &mut self.stack,
+                arena,
+                session,
+            )?;
             self.patch_buffer.copy_within(size..size + SLOP_SIZE, 0);
         }
         self.state.write(state);
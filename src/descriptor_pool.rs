@@ -9,6 +9,8 @@
 //! - Generic protobuf processors (proxies, loggers, transformers)
 //! - Schema-driven tools that load `.proto` definitions at runtime
 //! - Testing and debugging tools
+//! - Schemas defined at runtime, through [`TableBuilder`]
+//! - Messages passed between layers without borrows, through [`OwnedDynamicMessage`]
 //!
 //! # Example
 //!
@@ -40,14 +42,66 @@ use crate::{
     base::{Message, Object},
     google::protobuf::{
         DescriptorProto::ProtoType as DescriptorProto,
+        EnumDescriptorProto::ProtoType as EnumDescriptorProto,
+        FeatureSet::ProtoType as FeatureSet,
         FieldDescriptorProto::ProtoType as FieldDescriptorProto,
         FileDescriptorProto::ProtoType as FileDescriptorProto,
     },
     reflection::{
-        DynamicMessage, field_kind_tokens, is_in_oneof, is_message, is_repeated, needs_has_bit,
+        DynamicMessage, DynamicMessageRef, field_kind_tokens, is_closed_enum, is_delimited, is_in_oneof, is_message, is_repeated,
+        json_name_conflict, map_entry_error, needs_has_bit, table_error,
     },
-    tables::Table,
+    tables::{EnumTable, Table},
+    wire::FieldKind,
 };
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use hashbrown::HashMap;
+
+/// Stack depth used to decode and encode messages transcoded from and to JSON.
+#[cfg(feature = "json")]
+const JSON_STACK_DEPTH: usize = 64;
+
+/// What [`DescriptorPool::add_file`] does with a message or enum whose full
+/// name is already in the pool, as when merging the descriptor sets of
+/// several services.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Refuse the file.
+    Error,
+    /// Keep the type in the pool if the new definition is identical, down to
+    /// the syntax and features of its file, and refuse the file otherwise.
+    #[default]
+    SkipIfIdentical,
+}
+
+/// What [`DescriptorPool::add_file`] does with a message in which two fields
+/// share a JSON name, say `foo_bar` and `fooBar`, see
+/// [`json_name_conflict`](crate::reflection::json_name_conflict). JSON
+/// decoding of a kept message fills the field declared last.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonNameConflictPolicy {
+    /// Refuse the file.
+    Error,
+    /// Refuse the file as protoc does: always in proto3, in proto2 only if
+    /// both fields set `json_name`, and in editions unless the message or
+    /// file has the `LEGACY_BEST_EFFORT` JSON format feature, which works
+    /// like proto2.
+    #[default]
+    Protoc,
+    /// Keep the message.
+    Allow,
+}
+
+/// A message or enum declared in a file added to the pool, see
+/// [`DescriptorPool::iter_types`].
+#[derive(Debug, Clone, Copy)]
+pub enum Definition<'a> {
+    Message(&'a DescriptorProto),
+    Enum(&'a EnumDescriptorProto),
+}
 
 /// A registry of message types for dynamic protobuf operations.
 ///
@@ -56,7 +110,64 @@ use crate::{
 pub struct DescriptorPool<'alloc> {
     /// Arena used for allocating message data during decode operations.
     pub arena: Arena<'alloc>,
-    tables: std::collections::HashMap<std::string::String, &'alloc mut Table>,
+    tables: HashMap<String, &'alloc mut Table>,
+    /// Generated tables registered with [`register_static`](Self::register_static),
+    /// by full name. Lookups try these first.
+    static_tables: HashMap<String, &'static Table>,
+    /// Value tables of the closed enums added so far, by full name.
+    closed_enums: HashMap<String, &'alloc EnumTable>,
+    /// The files added so far, in the order they were added.
+    files: Vec<&'alloc FileDescriptorProto>,
+    /// Every message and enum added so far by full name, with its file.
+    types: HashMap<String, (&'alloc FileDescriptorProto, Definition<'alloc>)>,
+    duplicate_policy: DuplicatePolicy,
+    json_name_conflict_policy: JsonNameConflictPolicy,
+    /// Bytes taken by the tables built so far, and the most they may take.
+    table_memory: usize,
+    max_table_memory: usize,
+}
+
+impl DescriptorPool<'static> {
+    /// Build a pool from an encoded `FileDescriptorSet` using the global allocator.
+    ///
+    /// See [`from_file_descriptor_set_bytes_in`](Self::from_file_descriptor_set_bytes_in).
+    pub fn from_file_descriptor_set_bytes(
+        bytes: &[u8],
+    ) -> Result<Self, crate::Error<core::alloc::LayoutError>> {
+        #[cfg(not(feature = "nightly"))]
+        use allocator_api2::alloc::Global;
+        #[cfg(feature = "nightly")]
+        use alloc::alloc::Global;
+
+        Self::from_file_descriptor_set_bytes_in(&Global, bytes)
+    }
+
+    /// Create an empty `message_type` message in a new arena, sharing the
+    /// pool. See [`OwnedDynamicMessage::new`].
+    pub fn new_message(
+        self: &Rc<Self>,
+        message_type: &str,
+    ) -> Result<OwnedDynamicMessage, crate::Error<core::alloc::LayoutError>> {
+        OwnedDynamicMessage::new(self.clone(), message_type)
+    }
+
+    /// The prototype of `message_type` messages, creating them without
+    /// looking the type up by name again.
+    ///
+    /// Fails with [`Error::MessageNotFound`](crate::Error::MessageNotFound)
+    /// if the pool has no such message.
+    pub fn prototype(
+        self: &Rc<Self>,
+        message_type: &str,
+    ) -> Result<MessagePrototype, crate::Error<core::alloc::LayoutError>> {
+        let table = self
+            .get_table(message_type)
+            .ok_or(crate::Error::MessageNotFound)?;
+        Ok(MessagePrototype {
+            table: core::ptr::NonNull::from(table),
+            pool: self.clone(),
+        })
+    }
 }
 
 impl<'alloc> DescriptorPool<'alloc> {
@@ -64,8 +175,133 @@ impl<'alloc> DescriptorPool<'alloc> {
     pub fn new(alloc: &'alloc dyn crate::Allocator) -> Self {
         DescriptorPool {
             arena: Arena::new(alloc),
-            tables: std::collections::HashMap::new(),
+            tables: HashMap::new(),
+            static_tables: HashMap::new(),
+            closed_enums: HashMap::new(),
+            files: Vec::new(),
+            types: HashMap::new(),
+            duplicate_policy: DuplicatePolicy::default(),
+            json_name_conflict_policy: JsonNameConflictPolicy::default(),
+            table_memory: 0,
+            max_table_memory: usize::MAX,
+        }
+    }
+
+    /// Set what [`add_file`](Self::add_file) does with types already in the
+    /// pool, [`DuplicatePolicy::SkipIfIdentical`] by default.
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// Set what [`add_file`](Self::add_file) does with messages whose fields
+    /// share a JSON name, [`JsonNameConflictPolicy::Protoc`] by default.
+    pub fn set_json_name_conflict_policy(&mut self, policy: JsonNameConflictPolicy) {
+        self.json_name_conflict_policy = policy;
+    }
+
+    /// Cap the memory the pool's message and enum tables may take, in bytes.
+    ///
+    /// A message's table grows with its highest field number, so a hostile
+    /// descriptor set, say one fetched from a schema registry, can have a
+    /// few small descriptors take a lot of memory. Adding a file whose
+    /// tables would go over the cap fails with
+    /// [`MemoryLimitExceeded`](crate::Error::MemoryLimitExceeded). There is
+    /// no cap by default.
+    pub fn set_max_table_memory(&mut self, bytes: usize) {
+        self.max_table_memory = bytes;
+    }
+
+    /// The memory taken by the tables built so far, in bytes.
+    ///
+    /// Tables of generated types registered with
+    /// [`register_static`](Self::register_static) aren't counted, nor are the
+    /// descriptors themselves.
+    pub fn table_memory_bytes(&self) -> usize {
+        self.table_memory
+    }
+
+    /// Account for `bytes` more of table memory, unless that goes over the cap.
+    fn charge_table_memory(&mut self, bytes: usize) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+        let total = self.table_memory.saturating_add(bytes);
+        if total > self.max_table_memory {
+            return Err(crate::Error::MemoryLimitExceeded);
+        }
+        self.table_memory = total;
+        Ok(())
+    }
+
+    /// Build a pool from an encoded `FileDescriptorSet`, as written by
+    /// `protoc --include_imports --descriptor_set_out`.
+    ///
+    /// The descriptors are decoded into the pool's own arena, and files are
+    /// added in dependency order regardless of their order in the set.
+    pub fn from_file_descriptor_set_bytes_in(
+        alloc: &'alloc dyn crate::Allocator,
+        bytes: &[u8],
+    ) -> Result<Self, crate::Error<core::alloc::LayoutError>> {
+        let mut pool = DescriptorPool::new(alloc);
+        pool.add_file_descriptor_set_bytes(bytes)?;
+        Ok(pool)
+    }
+
+    /// Add the files of an encoded `FileDescriptorSet` to the pool, in
+    /// dependency order.
+    ///
+    /// Call it once per set to merge the sets of several services. Types
+    /// declared by more than one set are handled according to the pool's
+    /// [`DuplicatePolicy`]; files added before a refused one stay in the pool.
+    pub fn add_file_descriptor_set_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+        use crate::ProtobufMut;
+        use crate::google::protobuf::FileDescriptorSet::ProtoType as FileDescriptorSet;
+
+        let mut file_set = FileDescriptorSet::default();
+        if !file_set.decode_flat::<32>(&mut self.arena, bytes) {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        let file_set: &'alloc FileDescriptorSet = self.arena.place(file_set)?;
+        for file in Self::dependency_order(file_set.file()) {
+            self.add_file(file)?;
+        }
+        Ok(())
+    }
+
+    /// Order `files` so that every file comes after the files it imports.
+    /// Imports missing from `files` are ignored.
+    fn dependency_order(
+        files: &[crate::TypedMessage<FileDescriptorProto>],
+    ) -> Vec<&FileDescriptorProto> {
+        fn visit<'a>(
+            index: usize,
+            files: &'a [crate::TypedMessage<FileDescriptorProto>],
+            by_name: &HashMap<&str, usize>,
+            visited: &mut [bool],
+            order: &mut Vec<&'a FileDescriptorProto>,
+        ) {
+            if core::mem::replace(&mut visited[index], true) {
+                return;
+            }
+            for dependency in files[index].dependency() {
+                if let Some(&dep_index) = by_name.get(dependency.as_str()) {
+                    visit(dep_index, files, by_name, visited, order);
+                }
+            }
+            order.push(&files[index]);
+        }
+
+        let by_name: HashMap<&str, usize> = files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| (file.name(), index))
+            .collect();
+        let mut visited = vec![false; files.len()];
+        let mut order = Vec::with_capacity(files.len());
+        for index in 0..files.len() {
+            visit(index, files, &by_name, &mut visited, &mut order);
         }
+        order
     }
 
     /// Strip leading dot from type name (protobuf returns ".package.Type", we store "package.Type")
@@ -74,53 +310,256 @@ impl<'alloc> DescriptorPool<'alloc> {
     }
 
     /// Add a FileDescriptorProto to the pool
+    ///
+    /// Fails with [`InvalidProtobufData`](crate::Error::InvalidProtobufData)
+    /// if the file declares a malformed map entry, such as one with a `double`
+    /// key; [`map_entry_error`] tells what is wrong with it. The same goes for
+    /// messages whose table doesn't come out matching their fields, like ones
+    /// reusing a field number, see [`table_error`], and for messages and enums
+    /// already in the pool that the [`DuplicatePolicy`] doesn't let through,
+    /// or with fields sharing a JSON name that the [`JsonNameConflictPolicy`]
+    /// doesn't let through.
+    /// Files whose tables don't fit under the cap of
+    /// [`set_max_table_memory`](Self::set_max_table_memory) fail with
+    /// [`MemoryLimitExceeded`](crate::Error::MemoryLimitExceeded).
     pub fn add_file(&mut self, file: &'alloc FileDescriptorProto) -> Result<(), crate::Error<core::alloc::LayoutError>> {
         let package = if file.has_package() {
             file.package()
         } else {
             ""
         };
+        let full_name = |name: &str| {
+            if package.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}.{}", package, name)
+            }
+        };
 
-        // First pass: build all tables (child table pointers may be null)
+        // Types the pool already has are checked against the duplicate policy
+        // and, if it lets them through, left as they are
+        let mut declared = Vec::new();
+        for enum_type in file.enum_type() {
+            declared.push((full_name(enum_type.name()), Definition::Enum(enum_type)));
+        }
         for message in file.message_type() {
-            let full_name = if package.is_empty() {
-                message.name().to_string()
-            } else {
-                format!("{}.{}", package, message.name())
-            };
-            self.add_message(message, &full_name, file.get_syntax())?;
+            Self::declare_message(&mut declared, full_name(message.name()), message);
+        }
+        let mut names = hashbrown::HashSet::new();
+        for (name, definition) in &declared {
+            if !names.insert(name.clone()) {
+                return Err(crate::Error::InvalidProtobufData);
+            }
+            if let Some(&(other_file, other)) = self.types.get(name)
+                && (self.duplicate_policy == DuplicatePolicy::Error
+                    || !same_definition((file, *definition), (other_file, other)))
+            {
+                return Err(crate::Error::InvalidProtobufData);
+            }
+        }
+        let enums: Vec<_> = file
+            .enum_type()
+            .iter()
+            .map(|enum_type| (full_name(enum_type.name()), enum_type))
+            .filter(|(name, _)| !self.types.contains_key(name))
+            .collect();
+        let messages: Vec<_> = file
+            .message_type()
+            .iter()
+            .map(|message| (full_name(message.name()), message))
+            .filter(|(name, _)| !self.types.contains_key(name))
+            .collect();
+
+        // Enums first, so message tables can refer to them
+        for (name, enum_type) in &enums {
+            self.add_enum(enum_type, name, file)?;
+        }
+        for (name, message) in &messages {
+            self.add_nested_enums(message, name, file)?;
+        }
+
+        // First pass: build all tables (child table pointers may be null)
+        let file_features = file.options().and_then(|o| o.features());
+        for (name, message) in &messages {
+            self.add_message(message, name, file.get_syntax(), file_features, Some(file))?;
         }
 
         // Second pass: patch aux entries with correct child table pointers
-        for message in file.message_type() {
-            let full_name = if package.is_empty() {
-                message.name().to_string()
-            } else {
-                format!("{}.{}", package, message.name())
-            };
-            self.patch_message_aux_entries(&full_name)?;
+        for (name, _) in &messages {
+            self.patch_message_aux_entries(name)?;
+        }
+        for (name, definition) in declared {
+            self.types.entry(name).or_insert((file, definition));
+        }
+        if !self.files.iter().any(|added| added.name() == file.name()) {
+            self.files.push(file);
+        }
+        Ok(())
+    }
+
+    /// Append `message`, named `full_name`, and the types nested in it to
+    /// `declared`.
+    fn declare_message(
+        declared: &mut Vec<(String, Definition<'alloc>)>,
+        full_name: String,
+        message: &'alloc DescriptorProto,
+    ) {
+        for enum_type in message.enum_type() {
+            declared.push((format!("{}.{}", full_name, enum_type.name()), Definition::Enum(enum_type)));
+        }
+        for nested in message.nested_type() {
+            Self::declare_message(declared, format!("{}.{}", full_name, nested.name()), nested);
+        }
+        declared.push((full_name, Definition::Message(message)));
+    }
+
+    /// The files added to the pool, in the order they were added.
+    pub fn files(&self) -> &[&'alloc FileDescriptorProto] {
+        &self.files
+    }
+
+    /// Every message and enum in the pool, nested ones included, with its
+    /// package-qualified name and the file declaring it. The order is
+    /// unspecified.
+    pub fn iter_types(
+        &self,
+    ) -> impl Iterator<Item = (&str, &'alloc FileDescriptorProto, Definition<'alloc>)> + '_ {
+        self.types
+            .iter()
+            .map(|(name, &(file, definition))| (name.as_str(), file, definition))
+    }
+
+    /// The descriptor of the message type with package-qualified name
+    /// `full_name`, e.g. `google.protobuf.DescriptorProto.ExtensionRange`.
+    /// A leading dot, as in the type names of fields, is accepted.
+    pub fn find_message(&self, full_name: &str) -> Option<&'alloc DescriptorProto> {
+        match self.types.get(Self::normalize_type_name(full_name))? {
+            (_, Definition::Message(message)) => Some(message),
+            (_, Definition::Enum(_)) => None,
+        }
+    }
+
+    fn add_enum(
+        &mut self,
+        enum_type: &EnumDescriptorProto,
+        full_name: &str,
+        file: &FileDescriptorProto,
+    ) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+        if !is_closed_enum(enum_type, file) {
+            return Ok(());
+        }
+        let mut values: Vec<i32> =
+            enum_type.value().iter().map(|v| v.number()).collect();
+        values.sort_unstable();
+        values.dedup();
+        self.charge_table_memory(core::mem::size_of_val(values.as_slice()) + core::mem::size_of::<EnumTable>())?;
+        let values: *const [i32] = self.arena.alloc_slice_copy(&values)?;
+        let enum_table = self.arena.alloc::<EnumTable>()?;
+        unsafe {
+            // SAFETY: values live in the pool arena, which outlives the table usage
+            enum_table.write(EnumTable {
+                values: &*values,
+            });
+            self.closed_enums
+                .insert(full_name.to_string(), &*enum_table);
+        }
+        Ok(())
+    }
+
+    fn add_nested_enums(
+        &mut self,
+        message: &DescriptorProto,
+        full_name: &str,
+        file: &FileDescriptorProto,
+    ) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+        for enum_type in message.enum_type() {
+            self.add_enum(enum_type, &format!("{}.{}", full_name, enum_type.name()), file)?;
+        }
+        for nested in message.nested_type() {
+            self.add_nested_enums(nested, &format!("{}.{}", full_name, nested.name()), file)?;
         }
         Ok(())
     }
 
+    /// The value table of `field`'s enum type, if the field is a closed enum.
+    fn closed_enum_table(&self, field: &FieldDescriptorProto) -> Option<&'alloc EnumTable> {
+        use crate::google::protobuf::FieldDescriptorProto::Type;
+
+        if field.r#type() != Some(Type::TYPE_ENUM) {
+            return None;
+        }
+        self.closed_enums
+            .get(Self::normalize_type_name(field.type_name()))
+            .copied()
+    }
+
+    /// Build the tables of `message` and its nested types, declared in
+    /// `file` if they come from one.
     fn add_message(
         &mut self,
         message: &'alloc DescriptorProto,
         full_name: &str,
         syntax: Option<&str>,
+        file_features: Option<&FeatureSet>,
+        file: Option<&'alloc FileDescriptorProto>,
     ) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+        if map_entry_error(message).is_some() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        if let Some((first, second)) = json_name_conflict(message)
+            && self.refuses_json_name_conflict(message, first, second, syntax, file_features)
+        {
+            return Err(crate::Error::InvalidProtobufData);
+        }
         // Build table from descriptor
-        let table = self.build_table_from_descriptor(message, syntax)?;
+        let table = self.build_table_from_descriptor(message, full_name, syntax, file_features)?;
+        if table_error(table).is_some() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        // SAFETY: the file lives in the arena like the descriptor
+        table.file = file.map(|file| unsafe {
+            core::mem::transmute::<&'alloc FileDescriptorProto, &'static FileDescriptorProto>(file)
+        });
         self.tables.insert(full_name.to_string(), table);
 
         // Add nested types
         for nested in message.nested_type() {
             let nested_full_name = format!("{}.{}", full_name, nested.name());
-            self.add_message(nested, &nested_full_name, syntax)?;
+            self.add_message(nested, &nested_full_name, syntax, file_features, file)?;
         }
         Ok(())
     }
 
+    /// Whether the JSON name conflict policy refuses `message` for `first`
+    /// and `second` sharing a JSON name.
+    fn refuses_json_name_conflict(
+        &self,
+        message: &DescriptorProto,
+        first: &FieldDescriptorProto,
+        second: &FieldDescriptorProto,
+        syntax: Option<&str>,
+        file_features: Option<&FeatureSet>,
+    ) -> bool {
+        use crate::google::protobuf::FeatureSet::JsonFormat;
+
+        let legacy = match syntax {
+            Some("proto3") => false,
+            Some("editions") => {
+                let json_format = |features: Option<&FeatureSet>| {
+                    features.filter(|f| f.has_json_format()).and_then(|f| f.json_format())
+                };
+                json_format(message.options().and_then(|o| o.features())).or_else(|| json_format(file_features))
+                    == Some(JsonFormat::LEGACY_BEST_EFFORT)
+            }
+            _ => true,
+        };
+        match self.json_name_conflict_policy {
+            JsonNameConflictPolicy::Error => true,
+            JsonNameConflictPolicy::Protoc => !legacy || (first.has_json_name() && second.has_json_name()),
+            JsonNameConflictPolicy::Allow => false,
+        }
+    }
+
     fn patch_message_aux_entries(&mut self, full_name: &str) -> Result<(), crate::Error<core::alloc::LayoutError>> {
         use crate::tables::AuxTableEntry;
 
@@ -129,7 +568,7 @@ impl<'alloc> DescriptorPool<'alloc> {
             None => return Ok(()),
         };
 
-        let descriptor = table.descriptor;
+        let descriptor = table.descriptor();
 
         // Count aux entries (message fields)
         let num_aux_entries = descriptor.field().iter().filter(|f| is_message(f)).count();
@@ -181,32 +620,164 @@ impl<'alloc> DescriptorPool<'alloc> {
     }
 
     /// Get a table by message type name
+    ///
+    /// Generated tables registered with [`register_static`](Self::register_static)
+    /// take precedence over the ones built from descriptors.
     pub fn get_table(&self, message_type: &str) -> Option<&Table> {
+        if let Some(&table) = self.static_tables.get(message_type) {
+            return Some(table);
+        }
         self.tables.get(message_type).map(|t| &**t)
     }
 
+    /// Serve lookups of the generated message type `T` from its generated
+    /// table instead of the one built from its descriptor.
+    ///
+    /// Generated tables don't know the full name of their type, so `T` is
+    /// registered under every name whose descriptor in the pool is identical
+    /// to the one `T` was generated from. Messages created by name through
+    /// [`get_table`](Self::get_table), [`create_message`](Self::create_message)
+    /// or [`OwnedDynamicMessage`] then have the same layout as `T`, and a
+    /// pool can serve generated and runtime-only types alike. Only the lookups
+    /// change: `T` inside other dynamic messages keeps the table built for it.
+    ///
+    /// Fails with [`Error::MessageNotFound`](crate::Error::MessageNotFound) if
+    /// no message in the pool matches, and with
+    /// [`Error::VersionMismatch`](crate::Error::VersionMismatch) if `T` was
+    /// generated for another protocrap version.
+    ///
+    /// ```
+    /// use protocrap::descriptor_pool::DescriptorPool;
+    /// use protocrap::google::protobuf::FileDescriptorProto;
+    /// use protocrap::generated_code_only::Protobuf;
+    /// use allocator_api2::alloc::Global;
+    ///
+    /// let mut pool = DescriptorPool::new(&Global);
+    /// pool.add_file(FileDescriptorProto::ProtoType::file_descriptor()).unwrap();
+    /// pool.register_static::<FileDescriptorProto::ProtoType>().unwrap();
+    ///
+    /// let table = pool.get_table("google.protobuf.FileDescriptorProto").unwrap();
+    /// assert!(core::ptr::eq(table, FileDescriptorProto::ProtoType::table()));
+    /// ```
+    pub fn register_static<T: crate::generated_code_only::Protobuf>(
+        &mut self,
+    ) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+        use crate::ProtobufRef;
+
+        let table = T::table();
+        table.check_version().map_err(crate::Error::with_io)?;
+        let descriptor = table.descriptor();
+        let mut encoded = None;
+        let names: Vec<String> = self
+            .types
+            .iter()
+            .filter(|(_, (_, definition))| match *definition {
+                Definition::Message(message) => {
+                    core::ptr::eq(message, descriptor)
+                        || (message.name() == descriptor.name()
+                            && message.encode_vec::<32>().ok()
+                                == *encoded.get_or_insert_with(|| descriptor.encode_vec::<32>().ok()))
+                }
+                Definition::Enum(_) => false,
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        if names.is_empty() {
+            return Err(crate::Error::MessageNotFound);
+        }
+        for name in names {
+            self.static_tables.insert(name, table);
+        }
+        Ok(())
+    }
+
     /// Create an empty message of the given type, allocated in the arena.
     pub fn create_message<'pool, 'msg>(
         &'pool self,
         message_type: &str,
         arena: &mut Arena<'msg>,
     ) -> Result<DynamicMessage<'pool, 'msg>, crate::Error<core::alloc::LayoutError>> {
-        let table = &**self
-            .tables
-            .get(message_type)
+        let table = self
+            .get_table(message_type)
             .ok_or(crate::Error::MessageNotFound)?;
+        create_object(table, arena)
+    }
 
-        // Allocate object with proper alignment (8 bytes for all protobuf types)
-        let layout = core::alloc::Layout::from_size_align(table.size as usize, 8)?;
-        let ptr = arena.alloc_raw(layout)?.as_ptr() as *mut Object;
-        assert!((ptr as usize) & 7 == 0);
-        let object = unsafe {
-            // Zero-initialize the object
-            core::ptr::write_bytes(ptr as *mut u8, 0, table.size as usize);
-            &mut *ptr
-        };
+    /// Parse `json`, proto JSON of a `message_type` message, and encode it to
+    /// protobuf binary, transcoding from descriptors alone.
+    ///
+    /// `message_type` is a full name, with or without a leading dot, or a type
+    /// URL such as `type.googleapis.com/my.package.MyMessage`. The types packed
+    /// in `google.protobuf.Any` values are looked up in this pool, see
+    /// [`with_type_resolver`](crate::serde::with_type_resolver). JSON syntax
+    /// errors and values that don't fit the schema are returned as
+    /// [`Error::Io`](crate::Error::Io).
+    #[cfg(feature = "json")]
+    pub fn json_to_binary(
+        &self,
+        message_type: &str,
+        json: &str,
+    ) -> Result<Vec<u8>, crate::Error<serde_json::Error>> {
+        use crate::proto_json::ProtoJsonDeserializer;
+        use crate::{ProtobufMut, ProtobufRef};
+        #[cfg(not(feature = "nightly"))]
+        use allocator_api2::alloc::Global;
+        #[cfg(feature = "nightly")]
+        use alloc::alloc::Global;
+
+        let mut arena = Arena::new(&Global);
+        let mut msg = self.create_json_message(message_type, &mut arena)?;
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        crate::serde::with_type_resolver(self, || {
+            msg.serde_deserialize(&mut arena, ProtoJsonDeserializer::new(&mut deserializer))
+        })?;
+        deserializer.end()?;
+        msg.encode_vec::<JSON_STACK_DEPTH>()
+            .map_err(|_| crate::Error::MessageTreeTooDeep)
+    }
+
+    /// Decode `bytes` as a `message_type` message and write it as proto JSON,
+    /// the reverse of [`json_to_binary`](Self::json_to_binary).
+    #[cfg(feature = "json")]
+    pub fn binary_to_json(
+        &self,
+        message_type: &str,
+        bytes: &[u8],
+    ) -> Result<String, crate::Error<serde_json::Error>> {
+        use crate::proto_json::{ProtoJsonFormatter, ProtoJsonSerializer};
+        use crate::{ProtobufMut, ProtobufRef};
+        use serde::Serialize;
+        #[cfg(not(feature = "nightly"))]
+        use allocator_api2::alloc::Global;
+        #[cfg(feature = "nightly")]
+        use alloc::alloc::Global;
+
+        let mut arena = Arena::new(&Global);
+        let mut msg = self.create_json_message(message_type, &mut arena)?;
+        if !msg.decode_flat::<JSON_STACK_DEPTH>(&mut arena, bytes) {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        let mut serializer = serde_json::Serializer::with_formatter(Vec::new(), ProtoJsonFormatter);
+        crate::serde::with_type_resolver(self, || {
+            msg.as_dyn().serialize(ProtoJsonSerializer::new(&mut serializer))
+        })?;
+        // serde_json only writes valid UTF-8
+        Ok(String::from_utf8(serializer.into_inner()).unwrap_or_default())
+    }
 
-        Ok(DynamicMessage { object, table })
+    /// The empty message to transcode through, named by full name or type URL.
+    #[cfg(feature = "json")]
+    fn create_json_message<'pool, 'msg>(
+        &'pool self,
+        message_type: &str,
+        arena: &mut Arena<'msg>,
+    ) -> Result<DynamicMessage<'pool, 'msg>, crate::Error<serde_json::Error>> {
+        let name = message_type.rsplit('/').next().unwrap_or_default();
+        self.create_message(name.trim_start_matches('.'), arena)
+            .map_err(|e| match e {
+                crate::Error::MessageNotFound => crate::Error::MessageNotFound,
+                _ => crate::Error::ArenaAllocationFailed,
+            })
     }
 
     // TODO: improve lifetime annotations
@@ -214,11 +785,14 @@ impl<'alloc> DescriptorPool<'alloc> {
     fn build_table_from_descriptor(
         &mut self,
         descriptor: &'alloc DescriptorProto,
+        full_name: &str,
         syntax: Option<&str>,
+        file_features: Option<&FeatureSet>,
     ) -> Result<&'alloc mut Table, crate::Error<core::alloc::LayoutError>> {
         use crate::{
             decoding, encoding, reflection::calculate_tag_with_syntax, tables::AuxTableEntry,
         };
+        let delimited = |field: &FieldDescriptorProto| is_delimited(descriptor, field, file_features);
 
         // Calculate sizes
         let num_fields = descriptor.field().len();
@@ -247,7 +821,7 @@ impl<'alloc> DescriptorPool<'alloc> {
         let num_decode_entries = (max_field_number + 1) as usize;
 
         // Group fields by oneof_index and calculate union sizes
-        let mut oneof_sizes: std::vec::Vec<(usize, usize)> = vec![(0, 1); oneof_count]; // (size, align)
+        let mut oneof_sizes: Vec<(usize, usize)> = vec![(0, 1); oneof_count]; // (size, align)
         for field in descriptor.field() {
             if is_in_oneof(field) {
                 let oneof_idx = field.oneof_index() as usize;
@@ -268,7 +842,7 @@ impl<'alloc> DescriptorPool<'alloc> {
 
         // First pass: calculate offsets for regular fields (not in oneof)
         // Store in a map by field number
-        let mut regular_field_offsets = std::collections::HashMap::<i32, u32>::new();
+        let mut regular_field_offsets = HashMap::<i32, u32>::new();
         for field in descriptor.field() {
             if is_in_oneof(field) {
                 continue; // Skip oneof fields, handled separately
@@ -285,7 +859,7 @@ impl<'alloc> DescriptorPool<'alloc> {
         }
 
         // Then add unions for each oneof
-        let mut oneof_offsets = std::vec::Vec::new();
+        let mut oneof_offsets = Vec::new();
         for (oneof_idx, &(size, align)) in oneof_sizes.iter().enumerate() {
             if size > 0 {
                 let union_layout = core::alloc::Layout::from_size_align(size, align)?;
@@ -296,7 +870,7 @@ impl<'alloc> DescriptorPool<'alloc> {
         }
 
         // Build field_offsets in proto definition order (matching codegen)
-        let mut field_offsets = std::vec::Vec::new();
+        let mut field_offsets = Vec::new();
         for field in descriptor.field() {
             let offset = if is_in_oneof(field) {
                 let oneof_idx = field.oneof_index() as usize;
@@ -315,12 +889,17 @@ impl<'alloc> DescriptorPool<'alloc> {
         let layout = layout.pad_to_align();
         let total_size = layout.size() as u32;
 
-        // Count message fields for aux entries
-        let num_aux_entries = descriptor
+        // Aux entries: message fields, followed by closed enum fields
+        let num_message_aux_entries = descriptor
             .field()
             .iter()
             .filter(|f| is_message(&**f))
             .count();
+        let closed_enum_fields: Vec<_> = field_offsets
+            .iter()
+            .filter_map(|&(field, offset)| Some((field, offset, self.closed_enum_table(field)?)))
+            .collect();
+        let num_aux_entries = num_message_aux_entries + closed_enum_fields.len();
 
         // Allocate table with entries - use Layout::extend to handle padding correctly
         let encode_layout = core::alloc::Layout::array::<encoding::TableEntry>(num_fields)?;
@@ -330,7 +909,17 @@ impl<'alloc> DescriptorPool<'alloc> {
             .extend(core::alloc::Layout::array::<decoding::TableEntry>(num_decode_entries)?)?;
         let (layout, aux_offset) = layout
             .extend(core::alloc::Layout::array::<AuxTableEntry>(num_aux_entries)?)?;
+        // Indices of the fields by ascending field number, if not declared so
+        let mut field_number_order = Vec::new();
+        if !descriptor.field().is_sorted_by_key(|field| field.number()) {
+            field_number_order.extend(0..num_fields as u16);
+            field_number_order.sort_by_key(|&idx| descriptor.field()[idx as usize].number());
+        }
+        let (layout, order_offset) = layout
+            .extend(core::alloc::Layout::array::<u16>(field_number_order.len())?)?;
 
+        self.charge_table_memory(layout.size() + full_name.len())?;
+        let full_name: *const str = self.arena.alloc_str(full_name)?;
         let base_ptr = self.arena.alloc_raw(layout)?.as_ptr();
 
         unsafe {
@@ -338,20 +927,28 @@ impl<'alloc> DescriptorPool<'alloc> {
             let table_ptr = base_ptr.add(table_offset) as *mut Table;
             let decode_ptr = base_ptr.add(decode_offset) as *mut decoding::TableEntry;
             let aux_ptr = base_ptr.add(aux_offset) as *mut AuxTableEntry;
+            let order_ptr = base_ptr.add(order_offset) as *mut u16;
+            core::ptr::copy_nonoverlapping(field_number_order.as_ptr(), order_ptr, field_number_order.len());
 
             // Initialize Table header
+            (*table_ptr).version = crate::tables::TABLE_VERSION;
             (*table_ptr).num_encode_entries = num_fields as u16;
             (*table_ptr).num_decode_entries = num_decode_entries as u16;
             (*table_ptr).size = total_size as u16;
+            (*table_ptr).syntax = crate::Syntax::from_name(syntax);
             // SAFETY: descriptor lives in arena with 'alloc lifetime, which outlives the table usage
-            (*table_ptr).descriptor = core::mem::transmute::<
+            (*table_ptr).descriptor = Some(core::mem::transmute::<
                 &'alloc DescriptorProto,
                 &'static DescriptorProto,
-            >(descriptor);
+            >(descriptor));
+            (*table_ptr).file = None;
+            // SAFETY: the name lives in the pool arena too
+            (*table_ptr).full_name = &*full_name;
+            (*table_ptr).field_number_order = core::slice::from_raw_parts(order_ptr, field_number_order.len());
 
             // Build aux index map for message fields and has_bit index map
-            let mut aux_index_map = std::collections::HashMap::<i32, usize>::new();
-            let mut has_bit_index_map = std::collections::HashMap::<i32, u32>::new();
+            let mut aux_index_map = HashMap::<i32, usize>::new();
+            let mut has_bit_index_map = HashMap::<i32, u32>::new();
             let mut aux_idx = 0;
             let mut has_bit_idx = 0u32;
             for field in descriptor.field() {
@@ -364,6 +961,10 @@ impl<'alloc> DescriptorPool<'alloc> {
                     has_bit_idx += 1;
                 }
             }
+            for (field, _, _) in &closed_enum_fields {
+                aux_index_map.insert(field.number(), aux_idx);
+                aux_idx += 1;
+            }
 
             // Build encode entries
             let mut has_bit_idx = 0u8;
@@ -393,9 +994,9 @@ impl<'alloc> DescriptorPool<'alloc> {
 
                 encode_ptr.add(i).write(encoding::TableEntry {
                     has_bit,
-                    kind: field_kind_tokens(&field),
+                    kind: field_kind_tokens(field, delimited(field)),
                     offset: entry_offset,
-                    encoded_tag: calculate_tag_with_syntax(&field, syntax),
+                    encoded_tag: calculate_tag_with_syntax(field, syntax, delimited(field)),
                 });
             }
 
@@ -412,8 +1013,27 @@ impl<'alloc> DescriptorPool<'alloc> {
                         .map(|(_, o)| *o)
                         .unwrap_or(0);
 
-                    // Check oneof first (applies to all field types including message)
-                    let entry = if is_in_oneof(&**field) {
+                    // Closed enum fields point to an aux entry holding the enum values
+                    let entry = if self.closed_enum_table(field).is_some() {
+                        let has_bit = if is_in_oneof(field) {
+                            (0x80 | (has_bits_words + field.oneof_index() as usize)) as u32
+                        } else if needs_has_bit(field) {
+                            has_bit_index_map[&field_number]
+                        } else {
+                            0
+                        };
+                        let kind = if is_repeated(field) {
+                            FieldKind::RepeatedClosedEnum
+                        } else {
+                            FieldKind::ClosedEnum
+                        };
+                        let aux_index = aux_index_map[&field_number];
+                        let aux_offset =
+                            (aux_ptr as usize) + aux_index * core::mem::size_of::<AuxTableEntry>();
+                        let table_addr = table_ptr as usize;
+                        decoding::TableEntry::new(kind, has_bit, aux_offset - table_addr)
+                    } else if is_in_oneof(&**field) {
+                        // Check oneof first (applies to all field types including message)
                         // Oneof field: has_bit = 0x80 | discriminant_word_idx
                         let oneof_idx = field.oneof_index() as usize;
                         let has_bit = (0x80 | (has_bits_words + oneof_idx)) as u32;
@@ -425,13 +1045,13 @@ impl<'alloc> DescriptorPool<'alloc> {
                                 + aux_index * core::mem::size_of::<AuxTableEntry>();
                             let table_addr = table_ptr as usize;
                             decoding::TableEntry::new(
-                                field_kind_tokens(field),
+                                field_kind_tokens(field, delimited(field)),
                                 has_bit,
                                 aux_offset - table_addr,
                             )
                         } else {
                             decoding::TableEntry::new(
-                                field_kind_tokens(field),
+                                field_kind_tokens(field, delimited(field)),
                                 has_bit,
                                 offset as usize,
                             )
@@ -443,7 +1063,7 @@ impl<'alloc> DescriptorPool<'alloc> {
                             (aux_ptr as usize) + aux_index * core::mem::size_of::<AuxTableEntry>();
                         let table_addr = table_ptr as usize;
                         decoding::TableEntry::new(
-                            field_kind_tokens(field),
+                            field_kind_tokens(field, delimited(field)),
                             0, // has_bit not used for message fields
                             aux_offset - table_addr,
                         )
@@ -454,7 +1074,7 @@ impl<'alloc> DescriptorPool<'alloc> {
                             0
                         };
                         decoding::TableEntry::new(
-                            field_kind_tokens(field),
+                            field_kind_tokens(field, delimited(field)),
                             has_bit,
                             offset as usize,
                         )
@@ -487,6 +1107,14 @@ impl<'alloc> DescriptorPool<'alloc> {
                 });
             }
 
+            // Then for closed enum fields
+            for (i, &(_, offset, enum_table)) in closed_enum_fields.iter().enumerate() {
+                aux_ptr.add(num_message_aux_entries + i).write(AuxTableEntry::closed_enum(
+                    offset,
+                    &*(enum_table as *const EnumTable),
+                ));
+            }
+
             Ok(&mut *table_ptr)
         }
     }
@@ -530,7 +1158,324 @@ impl<'alloc> DescriptorPool<'alloc> {
     }
 }
 
-#[cfg(test)]
+/// Whether two definitions of a type, with the files declaring them, give
+/// the same tables.
+fn same_definition(
+    (file, definition): (&FileDescriptorProto, Definition),
+    (other_file, other): (&FileDescriptorProto, Definition),
+) -> bool {
+    use crate::ProtobufRef;
+
+    let features = |file: &FileDescriptorProto| {
+        file.options()
+            .and_then(|o| o.features())
+            .map(|features| features.encode_vec::<32>().ok())
+    };
+    let encoded = |definition| match definition {
+        Definition::Message(message) => message.encode_vec::<32>().ok(),
+        Definition::Enum(enum_type) => enum_type.encode_vec::<32>().ok(),
+    };
+    let encoding = encoded(definition);
+    file.get_syntax() == other_file.get_syntax()
+        && features(file) == features(other_file)
+        && encoding.is_some()
+        && encoding == encoded(other)
+}
+
+/// Create an empty message of type `table` in `arena`.
+fn create_object<'pool, 'msg>(
+    table: &'pool Table,
+    arena: &mut Arena<'msg>,
+) -> Result<DynamicMessage<'pool, 'msg>, crate::Error<core::alloc::LayoutError>> {
+    // Allocate object with proper alignment (8 bytes for all protobuf types)
+    let layout = core::alloc::Layout::from_size_align(table.size as usize, 8)?;
+    let ptr = arena.alloc_raw(layout)?.as_ptr() as *mut Object;
+    assert!((ptr as usize) & 7 == 0);
+    let object = unsafe {
+        // Zero-initialize the object
+        core::ptr::write_bytes(ptr as *mut u8, 0, table.size as usize);
+        &mut *ptr
+    };
+
+    Ok(DynamicMessage { object, table })
+}
+
+/// A message type resolved once in a shared pool, for servers creating many
+/// messages of the same types.
+///
+/// [`DescriptorPool::create_message`] and [`OwnedDynamicMessage::new`] look
+/// the type up by name on every call. A prototype holds the table found by
+/// [`DescriptorPool::prototype`], and creates messages from it directly. An
+/// empty message is all zero bytes in every table layout, so creating one is
+/// an allocation and a fill, with nothing to copy from.
+///
+/// ```
+/// use std::rc::Rc;
+/// use protocrap::descriptor_pool::DescriptorPool;
+/// use protocrap::google::protobuf::FileDescriptorProto;
+/// use allocator_api2::alloc::Global;
+///
+/// let mut pool = DescriptorPool::new(&Global);
+/// pool.add_file(FileDescriptorProto::ProtoType::file_descriptor()).unwrap();
+/// let file = Rc::new(pool).prototype("google.protobuf.FileDescriptorProto").unwrap();
+///
+/// for request in [&b"\x0a\x03a.p"[..], b"\x0a\x03b.p"] {
+///     let msg = file.decode(request).unwrap();
+///     assert!(msg.as_ref().get_str("name").unwrap().ends_with(".p"));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct MessagePrototype {
+    table: core::ptr::NonNull<Table>,
+    pool: Rc<DescriptorPool<'static>>,
+}
+
+impl MessagePrototype {
+    /// The table of the messages created.
+    pub fn table(&self) -> &Table {
+        // Lives in the arena of the shared pool.
+        unsafe { self.table.as_ref() }
+    }
+
+    /// The pool holding the table.
+    pub fn pool(&self) -> &Rc<DescriptorPool<'static>> {
+        &self.pool
+    }
+
+    /// Create an empty message in `arena`.
+    pub fn create_in<'msg>(
+        &self,
+        arena: &mut Arena<'msg>,
+    ) -> Result<DynamicMessage<'_, 'msg>, crate::Error<core::alloc::LayoutError>> {
+        create_object(self.table(), arena)
+    }
+
+    /// Create an empty message in a new arena.
+    pub fn new_message(&self) -> Result<OwnedDynamicMessage, crate::Error<core::alloc::LayoutError>> {
+        #[cfg(not(feature = "nightly"))]
+        use allocator_api2::alloc::Global;
+        #[cfg(feature = "nightly")]
+        use alloc::alloc::Global;
+
+        let mut arena = Arena::new(&Global);
+        let msg = self.create_in(&mut arena)?;
+        let object = core::ptr::NonNull::from(&*msg.object);
+        Ok(OwnedDynamicMessage {
+            object,
+            table: self.table,
+            arena,
+            pool: self.pool.clone(),
+        })
+    }
+
+    /// Decode `bytes` into a new message, see [`OwnedDynamicMessage::decode`].
+    pub fn decode(&self, bytes: &[u8]) -> Result<OwnedDynamicMessage, crate::Error> {
+        let msg = self
+            .new_message()
+            .map_err(|_| crate::Error::ArenaAllocationFailed)?;
+        msg.decode_into(bytes)
+    }
+}
+
+/// A dynamic message that owns its arena and shares its pool.
+///
+/// [`DynamicMessage`] borrows both the pool its table lives in and the arena
+/// its data lives in, which keeps it from being returned from the function
+/// that created them. This bundles all three, so a decoded message can be
+/// stored or handed to other layers like any owned value.
+///
+/// ```
+/// use std::rc::Rc;
+/// use protocrap::ProtobufRef;
+/// use protocrap::descriptor_pool::{DescriptorPool, OwnedDynamicMessage};
+/// use protocrap::google::protobuf::FileDescriptorProto;
+/// use allocator_api2::alloc::Global;
+///
+/// fn decode(pool: &Rc<DescriptorPool<'static>>, bytes: &[u8]) -> OwnedDynamicMessage {
+///     OwnedDynamicMessage::decode(pool.clone(), "google.protobuf.FileDescriptorProto", bytes)
+///         .unwrap()
+/// }
+///
+/// let mut pool = DescriptorPool::new(&Global);
+/// pool.add_file(FileDescriptorProto::ProtoType::file_descriptor()).unwrap();
+/// let msg = decode(&Rc::new(pool), &[0x0a, 0x03, b'a', b'.', b'p']);
+/// assert_eq!(msg.as_ref().get_str("name"), Some("a.p"));
+/// ```
+pub struct OwnedDynamicMessage {
+    object: core::ptr::NonNull<Object>,
+    table: core::ptr::NonNull<Table>,
+    arena: Arena<'static>,
+    pool: Rc<DescriptorPool<'static>>,
+}
+
+impl OwnedDynamicMessage {
+    /// Create an empty `message_type` message in a new arena.
+    pub fn new(
+        pool: Rc<DescriptorPool<'static>>,
+        message_type: &str,
+    ) -> Result<Self, crate::Error<core::alloc::LayoutError>> {
+        pool.prototype(message_type)?.new_message()
+    }
+
+    /// Decode `bytes` as a `message_type` message.
+    pub fn decode(
+        pool: Rc<DescriptorPool<'static>>,
+        message_type: &str,
+        bytes: &[u8],
+    ) -> Result<Self, crate::Error> {
+        let msg = Self::new(pool, message_type).map_err(|e| match e {
+            crate::Error::MessageNotFound => crate::Error::MessageNotFound,
+            _ => crate::Error::ArenaAllocationFailed,
+        })?;
+        msg.decode_into(bytes)
+    }
+
+    /// Decode `bytes` into this empty message.
+    fn decode_into(mut self, bytes: &[u8]) -> Result<Self, crate::Error> {
+        /// Stack depth used to decode messages.
+        const STACK_DEPTH: usize = 64;
+
+        let (mut dynamic, arena) = self.as_mut_with_arena();
+        if !crate::ProtobufMut::decode_flat::<STACK_DEPTH>(&mut dynamic, arena, bytes) {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        Ok(self)
+    }
+
+    /// The pool holding the message's table.
+    pub fn pool(&self) -> &Rc<DescriptorPool<'static>> {
+        &self.pool
+    }
+
+    /// Read-only view of the message.
+    pub fn as_ref(&self) -> DynamicMessageRef<'_, '_> {
+        // Both live as long as self: the object in the owned arena, the
+        // table in the arena of the shared pool.
+        unsafe {
+            DynamicMessageRef {
+                object: self.object.as_ref(),
+                table: self.table.as_ref(),
+            }
+        }
+    }
+
+    /// Mutable view of the message.
+    pub fn as_mut(&mut self) -> DynamicMessage<'_, '_> {
+        self.as_mut_with_arena().0
+    }
+
+    /// Mutable view of the message, along with the arena to allocate its
+    /// contents in, e.g. for decoding.
+    pub fn as_mut_with_arena(&mut self) -> (DynamicMessage<'_, '_>, &mut Arena<'static>) {
+        let msg = unsafe {
+            DynamicMessage {
+                object: self.object.as_mut(),
+                table: self.table.as_ref(),
+            }
+        };
+        (msg, &mut self.arena)
+    }
+}
+
+impl core::fmt::Debug for OwnedDynamicMessage {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(&self.as_ref(), f)
+    }
+}
+
+/// Builds tables for message descriptors assembled in memory rather than
+/// loaded from `.proto` files, such as user-defined event schemas.
+///
+/// Messages refer to each other through the fully qualified `type_name` of
+/// their fields, as in descriptors produced by protoc. Child tables are linked
+/// by [`build`](Self::build), so messages can be added in any order and may be
+/// recursive. Enum fields are treated as open enums.
+///
+/// ```
+/// use protocrap::{ProtobufMut, ProtobufRef, arena::Arena};
+/// use protocrap::descriptor_pool::TableBuilder;
+/// use protocrap::google::protobuf::DescriptorProto;
+/// use protocrap::google::protobuf::FieldDescriptorProto::{Label, Type};
+/// use allocator_api2::alloc::Global;
+///
+/// let mut arena = Arena::new(&Global);
+/// let mut event = DescriptorProto::ProtoType::default();
+/// event.set_name("Event", &mut arena).unwrap();
+/// let field = event.add_field(&mut arena).unwrap();
+/// field.set_name("id", &mut arena).unwrap();
+/// field.set_number(1);
+/// field.set_label(Label::LABEL_OPTIONAL);
+/// field.set_type(Type::TYPE_UINT64);
+///
+/// let mut builder = TableBuilder::new(&Global);
+/// builder.add_message("events.Event", &event, Some("proto3")).unwrap();
+/// let pool = builder.build().unwrap();
+///
+/// let mut msg = pool.create_message("events.Event", &mut arena).unwrap();
+/// assert!(msg.decode_flat::<32>(&mut arena, &[0x08, 0x2a]));
+/// assert_eq!(msg.as_ref().get_u64("id"), Some(42));
+/// ```
+pub struct TableBuilder<'alloc> {
+    pool: DescriptorPool<'alloc>,
+    /// Full names of the messages added, without their nested types.
+    messages: Vec<String>,
+}
+
+impl<'alloc> TableBuilder<'alloc> {
+    /// Create a builder allocating tables with `alloc`.
+    pub fn new(alloc: &'alloc dyn crate::Allocator) -> Self {
+        TableBuilder {
+            pool: DescriptorPool::new(alloc),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Add `message` and its nested types under the fully qualified name
+    /// `full_name`, without leading dot.
+    ///
+    /// `syntax` is the syntax of the file the message would be declared in.
+    /// With `"proto3"` repeated scalar fields are packed unless their options
+    /// say otherwise. Malformed map entries are refused like in
+    /// [`DescriptorPool::add_file`]. Without a file there are no file-wide
+    /// editions features, so message fields are only encoded delimited when
+    /// their own `message_encoding` feature says so.
+    pub fn add_message(
+        &mut self,
+        full_name: &str,
+        message: &'alloc DescriptorProto,
+        syntax: Option<&str>,
+    ) -> Result<&mut Self, crate::Error<core::alloc::LayoutError>> {
+        let full_name = DescriptorPool::normalize_type_name(full_name);
+        self.pool.add_message(message, full_name, syntax, None, None)?;
+        self.messages.push(full_name.to_string());
+        Ok(self)
+    }
+
+    /// Link the tables of all added messages into a pool.
+    ///
+    /// Fails with [`MessageNotFound`](crate::Error::MessageNotFound) if a
+    /// message field refers to a type that wasn't added.
+    pub fn build(mut self) -> Result<DescriptorPool<'alloc>, crate::Error<core::alloc::LayoutError>> {
+        let unresolved = self.pool.tables.values().any(|table| {
+            table.descriptor().field().iter().any(|field| {
+                is_message(field)
+                    && !self
+                        .pool
+                        .tables
+                        .contains_key(DescriptorPool::normalize_type_name(field.type_name()))
+            })
+        });
+        if unresolved {
+            return Err(crate::Error::MessageNotFound);
+        }
+        for full_name in &self.messages {
+            self.pool.patch_message_aux_entries(full_name)?;
+        }
+        Ok(self.pool)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
     use crate::generated_code_only::Protobuf;
@@ -554,4 +1499,595 @@ mod tests {
         let mut seen = HashSet::new();
         compare_tables_rec(static_table, dynamic_table, &mut seen);
     }
+    #[test]
+    fn file_descriptor_set_bytes_in_dependency_order() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::google::protobuf::FileDescriptorProto;
+        use crate::wire::{WireType, Writer, varint_len};
+        use crate::{ProtobufMut, ProtobufRef};
+
+        let mut arena = Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("user.proto", &mut arena).unwrap();
+        file.set_package("user", &mut arena).unwrap();
+        let dependency =
+            crate::containers::String::from_str("google/protobuf/descriptor.proto", &mut arena)
+                .unwrap();
+        file.dependency_mut().push(dependency, &mut arena).unwrap();
+        let message = file.add_message_type(&mut arena).unwrap();
+        message.set_name("Holder", &mut arena).unwrap();
+        let field = message.add_field(&mut arena).unwrap();
+        field.set_name("file", &mut arena).unwrap();
+        field.set_number(1);
+        field.set_label(Label::LABEL_OPTIONAL);
+        field.set_type(Type::TYPE_MESSAGE);
+        field
+            .set_type_name(".google.protobuf.FileDescriptorProto", &mut arena)
+            .unwrap();
+
+        // The importing file comes first in the set
+        let files = [&file, FileDescriptorProto::ProtoType::file_descriptor()]
+            .map(|file| file.encode_vec::<32>().unwrap());
+        let len = files
+            .iter()
+            .map(|encoded| 1 + varint_len(encoded.len() as u64) + encoded.len())
+            .sum();
+        let mut bytes = std::vec![0u8; len];
+        let mut writer = Writer::new(&mut bytes);
+        for encoded in &files {
+            writer.write_tag(1, WireType::LengthDelimited).unwrap();
+            writer.write_bytes(encoded).unwrap();
+        }
+
+        let pool = DescriptorPool::from_file_descriptor_set_bytes(&bytes).unwrap();
+        let mut holder = pool.create_message("user.Holder", &mut arena).unwrap();
+        // file { name: "a" }
+        let data = [0x0a, 0x03, 0x0a, 0x01, b'a'];
+        assert!(holder.decode_flat::<32>(&mut arena, &data));
+        assert_eq!(holder.encode_vec::<32>().unwrap(), data);
+
+        assert!(matches!(
+            DescriptorPool::from_file_descriptor_set_bytes(&[0x0a, 0x05]),
+            Err(crate::Error::InvalidProtobufData)
+        ));
+    }
+
+    #[test]
+    fn table_builder_links_in_any_order() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::{ProtobufMut, ProtobufRef};
+
+        let mut arena = Arena::new(&Global);
+        let mut message_field = |message: &mut DescriptorProto, name: &str, type_name: &str| {
+            let number = message.field().len() as i32 + 1;
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(Type::TYPE_MESSAGE);
+            field.set_type_name(type_name, &mut arena).unwrap();
+        };
+        // Node { Leaf leaf = 1; Node next = 2; }, Leaf { Node parent = 1; }
+        let mut node = DescriptorProto::default();
+        let mut leaf = DescriptorProto::default();
+        message_field(&mut node, "leaf", ".t.Leaf");
+        message_field(&mut node, "next", ".t.Node");
+        message_field(&mut leaf, "parent", "t.Node");
+        node.set_name("Node", &mut arena).unwrap();
+        leaf.set_name("Leaf", &mut arena).unwrap();
+
+        let mut builder = TableBuilder::new(&Global);
+        builder.add_message("t.Leaf", &leaf, None).unwrap();
+        builder.add_message(".t.Node", &node, None).unwrap();
+        let pool = builder.build().unwrap();
+
+        // next { leaf { parent {} } }
+        let data = [0x12, 0x04, 0x0a, 0x02, 0x0a, 0x00];
+        let mut msg = pool.create_message("t.Node", &mut arena).unwrap();
+        assert!(msg.decode_flat::<32>(&mut arena, &data));
+        assert_eq!(msg.encode_vec::<32>().unwrap(), data);
+
+        let mut builder = TableBuilder::new(&Global);
+        builder.add_message("t.Node", &node, None).unwrap();
+        assert!(matches!(builder.build(), Err(crate::Error::MessageNotFound)));
+    }
+
+    #[test]
+    fn rejects_malformed_map_entries() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::reflection::map_entry_error;
+
+        let mut arena = Arena::new(&Global);
+        let mut entry = DescriptorProto::default();
+        entry.set_name("CountsEntry", &mut arena).unwrap();
+        entry.options_mut(&mut arena).set_map_entry(true);
+        for (number, name) in [(1, "key"), (2, "value")] {
+            let field = entry.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(Type::TYPE_INT32);
+        }
+        assert_eq!(map_entry_error(&entry), None);
+        TableBuilder::new(&Global)
+            .add_message("t.CountsEntry", &entry, None)
+            .unwrap();
+
+        for (key_type, error) in [
+            (Type::TYPE_DOUBLE, "floating point map keys are not allowed"),
+            (Type::TYPE_FLOAT, "floating point map keys are not allowed"),
+            (Type::TYPE_BYTES, "bytes map keys are not allowed"),
+        ] {
+            entry.field_mut()[0].set_type(key_type);
+            assert_eq!(map_entry_error(&entry), Some(error));
+            assert!(matches!(
+                TableBuilder::new(&Global).add_message("t.CountsEntry", &entry, None),
+                Err(crate::Error::InvalidProtobufData)
+            ));
+        }
+
+        entry.field_mut()[0].set_type(Type::TYPE_STRING);
+        entry.field_mut()[1].set_number(3);
+        assert_eq!(
+            map_entry_error(&entry),
+            Some("map entry needs a key field 1 and a value field 2")
+        );
+        // Without the option it's an ordinary message
+        entry.options_mut(&mut arena).set_map_entry(false);
+        assert_eq!(map_entry_error(&entry), None);
+    }
+
+    #[test]
+    fn detects_json_name_conflicts() {
+        use crate::google::protobuf::FeatureSet::JsonFormat;
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::reflection::{effective_json_name, json_name_conflict};
+
+        let mut arena = Arena::new(&Global);
+        let mut message = DescriptorProto::default();
+        message.set_name("Clash", &mut arena).unwrap();
+        for (number, name) in [(1, "foo_bar"), (2, "fooBar")] {
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(Type::TYPE_INT32);
+        }
+        assert_eq!(effective_json_name(&message.field()[0]), "fooBar");
+        let (first, second) = json_name_conflict(&message).unwrap();
+        assert_eq!((first.name(), second.name()), ("foo_bar", "fooBar"));
+
+        let add = |policy, syntax, message: &DescriptorProto| {
+            let mut pool = DescriptorPool::new(&Global);
+            pool.set_json_name_conflict_policy(policy);
+            pool.add_message(message, "t.Clash", syntax, None, None).is_ok()
+        };
+        // Like protoc: refused in proto3 and editions, kept in proto2
+        assert!(!add(JsonNameConflictPolicy::Protoc, Some("proto3"), &message));
+        assert!(!add(JsonNameConflictPolicy::Protoc, Some("editions"), &message));
+        assert!(add(JsonNameConflictPolicy::Protoc, None, &message));
+        assert!(!add(JsonNameConflictPolicy::Error, None, &message));
+        assert!(add(JsonNameConflictPolicy::Allow, Some("proto3"), &message));
+
+        message
+            .options_mut(&mut arena)
+            .features_mut(&mut arena)
+            .set_json_format(JsonFormat::LEGACY_BEST_EFFORT);
+        assert!(add(JsonNameConflictPolicy::Protoc, Some("editions"), &message));
+
+        // Custom names clash in proto2 too
+        message.field_mut()[0].set_json_name("x", &mut arena).unwrap();
+        message.field_mut()[1].set_json_name("x", &mut arena).unwrap();
+        assert!(!add(JsonNameConflictPolicy::Protoc, Some("proto2"), &message));
+        message.field_mut()[1].set_json_name("y", &mut arena).unwrap();
+        assert!(json_name_conflict(&message).is_none());
+        assert!(add(JsonNameConflictPolicy::Error, Some("proto3"), &message));
+    }
+
+    #[test]
+    fn tables_without_descriptors_encode_and_decode() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::{ProtobufMut, ProtobufRef};
+
+        let mut arena = Arena::new(&Global);
+        let mut message = DescriptorProto::default();
+        message.set_name("Point", &mut arena).unwrap();
+        for (number, name) in [(1, "x"), (2, "y")] {
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(Type::TYPE_INT32);
+        }
+        let mut pool = DescriptorPool::new(&Global);
+        let table = pool
+            .build_table_from_descriptor(&message, "t.Point", None, None)
+            .unwrap();
+        // As generated with descriptors stripped
+        table.descriptor = None;
+        let table: &Table = table;
+
+        let mut msg = create_object(table, &mut arena).unwrap();
+        assert!(msg.decode_flat::<8>(&mut arena, &[0x08, 0x03, 0x10, 0x04]));
+        assert_eq!(msg.encode_vec::<8>().unwrap(), [0x08, 0x03, 0x10, 0x04]);
+        assert_eq!(format!("{:?}", msg), "t.Point { .. }");
+    }
+
+    #[test]
+    fn refuses_tables_of_other_versions() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::reflection::table_error;
+        use crate::{ProtobufMut, ProtobufRef};
+
+        let mut arena = Arena::new(&Global);
+        let mut message = DescriptorProto::default();
+        message.set_name("Point", &mut arena).unwrap();
+        let field = message.add_field(&mut arena).unwrap();
+        field.set_name("x", &mut arena).unwrap();
+        field.set_number(1);
+        field.set_label(Label::LABEL_OPTIONAL);
+        field.set_type(Type::TYPE_INT32);
+        let mut pool = DescriptorPool::new(&Global);
+        let table = pool
+            .build_table_from_descriptor(&message, "t.Point", None, None)
+            .unwrap();
+        assert!(table.check_version().is_ok());
+        // As generated by another release
+        table.version = crate::tables::TABLE_VERSION + 1;
+        let table: &Table = table;
+        assert!(matches!(table.check_version(), Err(crate::Error::VersionMismatch)));
+        assert_eq!(table_error(table), Some("table was generated for another protocrap version"));
+
+        let mut msg = create_object(table, &mut arena).unwrap();
+        assert!(!msg.decode_flat::<8>(&mut arena, &[0x08, 0x03]));
+        assert!(matches!(msg.encode_vec::<8>(), Err(crate::Error::VersionMismatch)));
+    }
+
+    #[test]
+    fn rejects_mismatched_tables() {
+        use crate::decoding::TableEntry;
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::reflection::table_error;
+
+        fn check_rec(table: &Table, seen: &mut HashSet<*const Table>) {
+            if !seen.insert(table) {
+                return;
+            }
+            assert_eq!(table_error(table), None, "{}", table.descriptor().name());
+            for field in table.descriptor().field() {
+                if is_message(field) {
+                    let entry = table.field_entry(field.number() as u32).unwrap();
+                    check_rec(table.aux_entry_decode(entry).1, seen);
+                }
+            }
+        }
+        check_rec(FileDescriptorProto::table(), &mut HashSet::new());
+
+        let mut arena = Arena::new(&Global);
+        let mut message = DescriptorProto::default();
+        message.set_name("Pair", &mut arena).unwrap();
+        for name in ["a", "b"] {
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(1);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(Type::TYPE_INT32);
+        }
+        assert!(matches!(
+            TableBuilder::new(&Global).add_message("t.Pair", &message, None),
+            Err(crate::Error::InvalidProtobufData)
+        ));
+
+        message.field_mut()[1].set_number(2);
+        let mut pool = DescriptorPool::new(&Global);
+        let table = pool
+            .build_table_from_descriptor(&message, "t.Pair", None, None)
+            .unwrap();
+        assert_eq!(table_error(table), None);
+        let decode_entries = unsafe { (table as *mut Table).add(1) as *mut TableEntry };
+        let entry = table.decode_entries()[1];
+        let corrupt = |kind, offset| TableEntry::new(kind, entry.has_bit_idx(), offset);
+        unsafe { *decode_entries.add(1) = corrupt(FieldKind::RepeatedMessage, entry.offset() as usize) };
+        assert_eq!(
+            table_error(table),
+            Some("table entry kind doesn't match the field type")
+        );
+        unsafe { *decode_entries.add(1) = corrupt(FieldKind::Int32, table.size as usize) };
+        assert_eq!(
+            table_error(table),
+            Some("encode and decode entries disagree on the field offset")
+        );
+        let encode_entries = unsafe { (table as *mut Table as *mut crate::encoding::TableEntry).sub(2) };
+        unsafe { (*encode_entries).offset = table.size };
+        assert_eq!(table_error(table), Some("field lies outside the object"));
+    }
+
+    #[test]
+    fn merges_descriptor_sets() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::google::protobuf::FileDescriptorSet;
+        use crate::{ProtobufMut, ProtobufRef};
+
+        let descriptor_proto = FileDescriptorProto::file_descriptor();
+        let mut arena = Arena::new(&Global);
+        let mut set = FileDescriptorSet::ProtoType::default();
+        let encoded = descriptor_proto.encode_vec::<32>().unwrap();
+        assert!(set.add_file(&mut arena).unwrap().decode_flat::<32>(&mut arena, &encoded));
+        let set = set.encode_vec::<32>().unwrap();
+
+        // Identical definitions are merged
+        let mut pool = DescriptorPool::from_file_descriptor_set_bytes(&set).unwrap();
+        pool.add_file_descriptor_set_bytes(&set).unwrap();
+        pool.add_file(descriptor_proto).unwrap();
+        assert_eq!(pool.files().len(), 1);
+
+        let mut strict = DescriptorPool::new(&Global);
+        strict.set_duplicate_policy(DuplicatePolicy::Error);
+        strict.add_file(descriptor_proto).unwrap();
+        assert!(matches!(
+            strict.add_file_descriptor_set_bytes(&set),
+            Err(crate::Error::InvalidProtobufData)
+        ));
+
+        // The same message name with another layout is refused
+        let mut file = |name: &str, ty: Type, syntax: &str| {
+            let mut file = FileDescriptorProto::default();
+            file.set_name(name, &mut arena).unwrap();
+            file.set_package("svc", &mut arena).unwrap();
+            file.set_syntax(syntax, &mut arena).unwrap();
+            let message = file.add_message_type(&mut arena).unwrap();
+            message.set_name("Event", &mut arena).unwrap();
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name("id", &mut arena).unwrap();
+            field.set_number(1);
+            field.set_label(Label::LABEL_REPEATED);
+            field.set_type(ty);
+            file
+        };
+        let a = file("a.proto", Type::TYPE_INT32, "proto2");
+        let b = file("b.proto", Type::TYPE_STRING, "proto2");
+        let packed = file("c.proto", Type::TYPE_INT32, "proto3");
+        let same = file("d.proto", Type::TYPE_INT32, "proto2");
+        let mut pool = DescriptorPool::new(&Global);
+        pool.add_file(&a).unwrap();
+        for conflicting in [&b, &packed] {
+            assert!(matches!(
+                pool.add_file(conflicting),
+                Err(crate::Error::InvalidProtobufData)
+            ));
+        }
+        pool.add_file(&same).unwrap();
+        assert_eq!(pool.files().len(), 2);
+        assert!(core::ptr::eq(
+            pool.get_table("svc.Event").unwrap().descriptor(),
+            &*a.message_type()[0]
+        ));
+    }
+
+    #[test]
+    fn tables_record_syntax() {
+        use crate::Syntax;
+
+        let mut arena = Arena::new(&Global);
+        let mut files = Vec::new();
+        for (package, syntax) in [("p2", None), ("p3", Some("proto3")), ("ed", Some("editions"))] {
+            let mut file = FileDescriptorProto::default();
+            file.set_name(&format!("{package}.proto"), &mut arena).unwrap();
+            file.set_package(package, &mut arena).unwrap();
+            if let Some(syntax) = syntax {
+                file.set_syntax(syntax, &mut arena).unwrap();
+            }
+            let message = file.add_message_type(&mut arena).unwrap();
+            message.set_name("M", &mut arena).unwrap();
+            message.add_nested_type(&mut arena).unwrap().set_name("N", &mut arena).unwrap();
+            files.push(file);
+        }
+        let mut pool = DescriptorPool::new(&Global);
+        for file in &files {
+            pool.add_file(file).unwrap();
+        }
+        let syntax = |name| pool.get_table(name).unwrap().syntax();
+        assert_eq!(syntax("p2.M"), Syntax::Proto2);
+        assert_eq!(syntax("p3.M"), Syntax::Proto3);
+        assert_eq!(syntax("p3.M.N"), Syntax::Proto3);
+        assert_eq!(syntax("ed.M"), Syntax::Editions);
+
+        // Generated tables record the syntax of their file too
+        assert_eq!(crate::google::protobuf::FileDescriptorProto::TABLE.table.syntax(), Syntax::Proto2);
+        assert_eq!(crate::google::protobuf::Timestamp::TABLE.table.syntax(), Syntax::Proto3);
+    }
+
+    #[test]
+    fn static_tables_serve_lookups() {
+        use crate::google::protobuf::{DescriptorProto, FileDescriptorSet};
+        use crate::{ProtobufMut, ProtobufRef};
+
+        // A pool decoded from bytes, so no descriptor is the generated one
+        let file_descriptor = FileDescriptorProto::file_descriptor();
+        let mut arena = Arena::new(&Global);
+        let mut set = FileDescriptorSet::ProtoType::default();
+        let encoded = file_descriptor.encode_vec::<32>().unwrap();
+        assert!(set.add_file(&mut arena).unwrap().decode_flat::<32>(&mut arena, &encoded));
+        let mut pool = DescriptorPool::from_file_descriptor_set_bytes(&set.encode_vec::<32>().unwrap()).unwrap();
+
+        pool.register_static::<FileDescriptorProto>().unwrap();
+        let name = "google.protobuf.FileDescriptorProto";
+        assert!(core::ptr::eq(pool.get_table(name).unwrap(), FileDescriptorProto::table()));
+        let other = pool.get_table("google.protobuf.DescriptorProto").unwrap();
+        assert!(!core::ptr::eq(other, DescriptorProto::ProtoType::table()));
+
+        let mut msg = pool.create_message(name, &mut arena).unwrap();
+        assert!(msg.decode_flat::<32>(&mut arena, &encoded));
+        assert_eq!(msg.encode_vec::<32>().unwrap(), encoded);
+
+        assert!(matches!(
+            pool.register_static::<crate::well_known::Duration>(),
+            Err(crate::Error::MessageNotFound)
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn transcodes_json_by_type_url() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+
+        let mut arena = Arena::new(&Global);
+        let mut file = FileDescriptorProto::default();
+        file.set_name("gateway.proto", &mut arena).unwrap();
+        file.set_package("gw", &mut arena).unwrap();
+        file.set_syntax("proto3", &mut arena).unwrap();
+        for (message_name, field_name, json_name, ty, type_name) in [
+            ("Envelope", "payload", "payload", Type::TYPE_MESSAGE, ".google.protobuf.Any"),
+            ("Inner", "item_count", "itemCount", Type::TYPE_INT64, ""),
+        ] {
+            let message = file.add_message_type(&mut arena).unwrap();
+            message.set_name(message_name, &mut arena).unwrap();
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name(field_name, &mut arena).unwrap();
+            field.set_json_name(json_name, &mut arena).unwrap();
+            field.set_number(1);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(ty);
+            if !type_name.is_empty() {
+                field.set_type_name(type_name, &mut arena).unwrap();
+            }
+        }
+        let mut pool = DescriptorPool::new(&Global);
+        pool.add_file(crate::well_known::Any::file_descriptor()).unwrap();
+        pool.add_file(&file).unwrap();
+
+        // The Any payload is a type only this pool knows
+        let json = r#"{"payload":{"@type":"type.googleapis.com/gw.Inner","itemCount":"7"}}"#;
+        let binary = pool.json_to_binary("type.googleapis.com/gw.Envelope", json).unwrap();
+        assert_eq!(binary, b"\x0a\x22\x0a\x1ctype.googleapis.com/gw.Inner\x12\x02\x08\x07"[..]);
+        for name in ["gw.Envelope", ".gw.Envelope", "type.googleapis.com/gw.Envelope"] {
+            assert_eq!(pool.binary_to_json(name, &binary).unwrap(), json);
+        }
+
+        assert!(matches!(pool.json_to_binary("gw.Missing", "{}"), Err(crate::Error::MessageNotFound)));
+        assert!(matches!(pool.json_to_binary("gw.Inner", r#"{"itemCount":true}"#), Err(crate::Error::Io(_))));
+        assert!(matches!(pool.binary_to_json("gw.Inner", b"\x08"), Err(crate::Error::InvalidProtobufData)));
+    }
+
+    #[test]
+    fn types_by_full_name() {
+        use crate::google::protobuf::DescriptorProto;
+        use crate::ProtobufRef;
+
+        let mut pool = DescriptorPool::new(&Global);
+        pool.add_file(FileDescriptorProto::file_descriptor()).unwrap();
+
+        let range = pool.find_message(".google.protobuf.DescriptorProto.ExtensionRange").unwrap();
+        assert_eq!(range.name(), "ExtensionRange");
+        assert!(pool.find_message("google.protobuf.FieldDescriptorProto.Type").is_none());
+        assert!(pool.find_message("ExtensionRange").is_none());
+
+        assert!(pool.iter_types().any(|(name, _, _)| name == "google.protobuf.FieldDescriptorProto.Type"));
+        for (name, file, definition) in pool.iter_types() {
+            assert_eq!(file.name(), "google/protobuf/descriptor.proto");
+            if let Definition::Message(message) = definition {
+                assert!(core::ptr::eq(pool.find_message(name).unwrap(), message));
+                assert_eq!(pool.get_table(name).unwrap().full_name, name);
+            }
+        }
+
+        let mut arena = Arena::new(&Global);
+        let msg = pool.create_message("google.protobuf.DescriptorProto.ExtensionRange", &mut arena).unwrap();
+        assert_eq!(msg.full_name(), "google.protobuf.DescriptorProto.ExtensionRange");
+        assert_eq!(DescriptorProto::ProtoType::default().full_name(), "google.protobuf.DescriptorProto");
+    }
+
+    #[test]
+    fn owned_message_outlives_its_scope() {
+        use crate::google::protobuf::FileDescriptorProto;
+        use crate::{ProtobufMut, ProtobufRef};
+
+        fn decode(bytes: &[u8]) -> OwnedDynamicMessage {
+            let mut pool = DescriptorPool::new(&Global);
+            pool.add_file(FileDescriptorProto::ProtoType::file_descriptor())
+                .unwrap();
+            OwnedDynamicMessage::decode(Rc::new(pool), "google.protobuf.FileDescriptorProto", bytes)
+                .unwrap()
+        }
+
+        // name: "a", package: "p"
+        let bytes = [0x0a, 0x01, b'a', 0x12, 0x01, b'p'];
+        let mut msg = decode(&bytes);
+        assert_eq!(msg.as_ref().get_str("name"), Some("a"));
+        assert_eq!(msg.as_ref().encode_vec::<32>().unwrap(), bytes);
+
+        // syntax: "proto3"
+        let (mut dynamic, arena) = msg.as_mut_with_arena();
+        assert!(dynamic.decode_flat::<32>(arena, b"\x62\x06proto3"));
+        let mut moved = std::vec![msg];
+        assert_eq!(moved[0].as_ref().get_str("syntax"), Some("proto3"));
+        assert!(std::format!("{:?}", moved[0]).contains("proto3"));
+
+        let pool = moved[0].pool().clone();
+        moved.clear();
+        assert!(pool.get_table("google.protobuf.FileDescriptorProto").is_some());
+        assert!(matches!(
+            OwnedDynamicMessage::decode(pool.clone(), "no.Such", &[]),
+            Err(crate::Error::MessageNotFound)
+        ));
+        assert!(matches!(
+            OwnedDynamicMessage::decode(pool, "google.protobuf.FileDescriptorProto", &[0x0a, 0x05]),
+            Err(crate::Error::InvalidProtobufData)
+        ));
+    }
+
+    #[test]
+    fn prototypes_create_messages_without_lookups() {
+        use crate::google::protobuf::FileDescriptorProto;
+        use crate::{ProtobufMut, ProtobufRef};
+
+        let mut pool = DescriptorPool::new(&Global);
+        pool.add_file(FileDescriptorProto::ProtoType::file_descriptor())
+            .unwrap();
+        let pool = Rc::new(pool);
+        let name = "google.protobuf.FileDescriptorProto";
+        let prototype = pool.prototype(name).unwrap();
+        assert!(core::ptr::eq(prototype.table(), pool.get_table(name).unwrap()));
+        assert!(matches!(pool.prototype("no.Such"), Err(crate::Error::MessageNotFound)));
+
+        // Messages start empty, whatever was set on earlier ones
+        for _ in 0..2 {
+            let mut msg = prototype.new_message().unwrap();
+            assert_eq!(msg.as_ref().encode_vec::<32>().unwrap().len(), 0);
+            let (mut dynamic, arena) = msg.as_mut_with_arena();
+            assert!(dynamic.decode_flat::<32>(arena, b"\x0a\x01a"));
+            assert_eq!(msg.as_ref().get_str("name"), Some("a"));
+        }
+        let msg = pool.new_message(name).unwrap();
+        assert_eq!(msg.as_ref().encode_vec::<32>().unwrap().len(), 0);
+        assert!(matches!(prototype.decode(&[0x0a, 0x05]), Err(crate::Error::InvalidProtobufData)));
+
+        let mut arena = Arena::new(&Global);
+        let msg = prototype.create_in(&mut arena).unwrap();
+        assert_eq!(msg.full_name(), name);
+    }
+
+    #[test]
+    fn caps_table_memory() {
+        let file_descriptor =
+            crate::google::protobuf::FileDescriptorProto::ProtoType::file_descriptor();
+        let mut pool = DescriptorPool::new(&Global);
+        assert_eq!(pool.table_memory_bytes(), 0);
+        pool.add_file(file_descriptor).unwrap();
+        let used = pool.table_memory_bytes();
+        assert!(used > 0);
+
+        let mut pool = DescriptorPool::new(&Global);
+        pool.set_max_table_memory(used);
+        pool.add_file(file_descriptor).unwrap();
+        assert_eq!(pool.table_memory_bytes(), used);
+
+        let mut pool = DescriptorPool::new(&Global);
+        pool.set_max_table_memory(used - 1);
+        assert!(matches!(
+            pool.add_file(file_descriptor),
+            Err(crate::Error::MemoryLimitExceeded)
+        ));
+        assert!(pool.table_memory_bytes() < used);
+    }
 }
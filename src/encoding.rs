@@ -2,10 +2,11 @@ use core::{mem::MaybeUninit, ptr::NonNull};
 
 use crate::{
     base::{Message, Object},
-    containers::Bytes,
+    containers::{Bytes, RepeatedField},
     tables::Table,
+    trace::trace_event,
     utils::{Ptr, Stack, StackWithStorage, as_bytes},
-    wire::{FieldKind, SLOP_SIZE, WriteCursor, zigzag_encode},
+    wire::{FieldKind, SLOP_SIZE, WriteCursor, varint_size, zigzag_encode},
 };
 
 #[repr(C)]
@@ -20,8 +21,12 @@ pub struct TableEntry {
 struct StackEntry {
     obj: Ptr<Object>,
     table: Ptr<[TableEntry]>,
+    order: Ptr<[u16]>,
     field_idx: usize,
+    entry_idx: usize,
     rep_field_idx: usize,
+    canonical: bool,
+    sort_maps: bool,
     byte_count: isize,
     tag: u32,
 }
@@ -32,8 +37,12 @@ impl StackEntry {
             ObjectEncodeState {
                 obj: self.obj.as_ref(),
                 table: self.table.as_ref(),
+                order: self.order.as_ref(),
                 field_idx: self.field_idx,
+                entry_idx: self.entry_idx,
                 rep_field_idx: self.rep_field_idx,
+                canonical: self.canonical,
+                sort_maps: self.sort_maps,
             },
             self.tag,
             self.byte_count,
@@ -45,6 +54,8 @@ enum EncodeObject<'a> {
     Done,
     Object(ObjectEncodeState<'a>),
     Bytes(&'a [u8]),
+    /// Packed fixed-width values of the given width, as native bytes.
+    Fixed(&'a [u8], usize),
     PackedVarint64(&'a [u64]),
     PackedVarint32(&'a [u32]),
     PackedInt32(&'a [i32]),
@@ -52,36 +63,154 @@ enum EncodeObject<'a> {
     PackedSint32(&'a [i32]),
 }
 
+/// Options controlling the binary encoding.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EncodeOptions {
+    /// Emit fields in ascending field-number order instead of table order.
+    ///
+    /// Tables list fields in declaration order, which is not guaranteed to be
+    /// sorted. Canonical order makes the output byte-stable regardless of how
+    /// the `.proto` file orders its fields.
+    pub canonical_field_order: bool,
+}
+
+/// Stack depth past which the encoder checks for cycles, see
+/// [`ObjectEncodeState::enter`]. Typical messages are shallower and skip the
+/// scan of the stack; a cycle is still caught within this many levels more
+/// than its length.
+const CYCLE_CHECK_DEPTH: usize = 8;
+
 struct ObjectEncodeState<'a> {
     obj: &'a Object,
     table: &'a [TableEntry],
+    /// Table indices of the fields by ascending field number, if canonical
+    /// order differs from table order. Empty otherwise.
+    order: &'a [u16],
+    /// Number of fields left to visit, counting down to zero.
     field_idx: usize,
+    /// Table index of the field being visited.
+    entry_idx: usize,
+    /// Repeated fields: entries left to visit. Maps with `sort_maps`: one
+    /// past the index of the next entry to visit.
     rep_field_idx: usize,
+    canonical: bool,
+    /// Emit map entries by descending key as they're written back to front,
+    /// so the output has them sorted, the last of a repeated key only.
+    sort_maps: bool,
 }
 
 impl<'a> ObjectEncodeState<'a> {
-    fn new(obj: &'a Object, table: &'a Table) -> Self {
+    fn new(obj: &'a Object, table: &'a Table, canonical: bool, sort_maps: bool) -> Self {
         let table_entries = table.encode_entries();
-        Self {
+        let order = if canonical { table.field_number_order } else { &[] };
+        let mut state = Self {
             obj,
             table: table_entries,
+            order,
             field_idx: table_entries.len(),
+            entry_idx: 0,
             rep_field_idx: 0,
+            canonical,
+            sort_maps,
+        };
+        state.entry_idx = state.entry_at(table_entries.len().wrapping_sub(1));
+        state
+    }
+
+    /// Table index of the field at `idx` in output order.
+    fn entry_at(&self, idx: usize) -> usize {
+        self.order.get(idx).map_or(idx, |&entry_idx| entry_idx as usize)
+    }
+
+    /// Move to the next field to encode. Encoding runs backwards, so this is
+    /// the field preceding the current one in the output.
+    fn advance(&mut self) {
+        self.field_idx -= 1;
+        if self.field_idx == 0 {
+            return;
         }
+        self.entry_idx = self.entry_at(self.field_idx - 1);
     }
 
     fn push(&self, tag: u32, byte_count: isize, stack: &mut Stack<StackEntry>) -> Option<()> {
         stack.push(StackEntry {
             obj: Ptr::new(self.obj),
             table: Ptr::new(self.table),
+            order: Ptr::new(self.order),
             field_idx: self.field_idx,
+            entry_idx: self.entry_idx,
             rep_field_idx: self.rep_field_idx,
+            canonical: self.canonical,
+            sort_maps: self.sort_maps,
             tag,
             byte_count,
         })?;
         Some(())
     }
 
+    /// Save the current state on the stack and continue with `child`.
+    ///
+    /// Past [`CYCLE_CHECK_DEPTH`], fails if `child` is already being encoded
+    /// further up the stack. Message trees built by hand from raw pointers can
+    /// contain cycles, which would otherwise be encoded over and over until
+    /// the stack runs out, taking exponential time when a cycle is reachable
+    /// through several fields.
+    fn enter(
+        &mut self,
+        child: &'a Object,
+        child_table: &'a Table,
+        tag: u32,
+        byte_count: isize,
+        stack: &mut Stack<StackEntry>,
+    ) -> Option<()> {
+        self.push(tag, byte_count, stack)?;
+        if stack.depth() > CYCLE_CHECK_DEPTH
+            && stack
+                .iter()
+                .any(|entry| core::ptr::eq(entry.obj.as_ref(), child))
+        {
+            return None;
+        }
+        *self = ObjectEncodeState::new(child, child_table, self.canonical, self.sort_maps);
+        Some(())
+    }
+
+    /// Index of the next element of a repeated message field of `len`
+    /// elements, moving on to the next field after the last one.
+    fn next_repeated(&mut self, len: usize) -> Option<usize> {
+        if self.rep_field_idx == 0 {
+            self.rep_field_idx = len;
+        }
+        if self.rep_field_idx == 0 {
+            return None;
+        }
+        self.rep_field_idx -= 1;
+        if self.rep_field_idx == 0 {
+            self.advance();
+        }
+        Some(self.rep_field_idx)
+    }
+
+    /// Like [`next_repeated`](Self::next_repeated) for the entries of a map
+    /// field when sorting maps, going by descending key instead of index.
+    #[cfg(feature = "alloc")]
+    fn next_map_entry(&mut self, slice: &[Message], table: &Table) -> Option<usize> {
+        use crate::reflection::prev_map_entry;
+
+        let current = match self.rep_field_idx {
+            0 => prev_map_entry(slice, table, None)?,
+            idx => idx - 1,
+        };
+        self.rep_field_idx = match prev_map_entry(slice, table, Some(current)) {
+            Some(prev) => prev + 1,
+            None => {
+                self.advance();
+                0
+            }
+        };
+        Some(current)
+    }
+
     fn pop(&mut self, stack: &mut Stack<StackEntry>) -> Option<(u32, isize)> {
         let (ctx, tag, byte_count) = stack.pop()?.into_context();
         *self = ctx;
@@ -119,8 +248,11 @@ impl<'a> ObjectEncodeState<'a> {
     }
 }
 
+/// Write the rest of a length-delimited field, `bytes` holding values of
+/// `width` bytes, 1 for strings and bytes.
 fn encode_bytes<'a>(
     bytes: &'a [u8],
+    width: usize,
     mut cursor: WriteCursor,
     begin: NonNull<u8>,
     byte_count: isize,
@@ -130,10 +262,12 @@ fn encode_bytes<'a>(
     assert!(cursor > begin);
     let buffer_size = (cursor - begin) as usize;
     if buffer_size < len {
-        cursor.write_slice(&bytes[len - buffer_size..]);
-        return Some((cursor, EncodeObject::Bytes(&bytes[..len - buffer_size])));
+        cursor.write_le_bytes(bytes, len - buffer_size, width);
+        let rest = &bytes[..len - buffer_size];
+        let object = if width == 1 { EncodeObject::Bytes(rest) } else { EncodeObject::Fixed(rest, width) };
+        return Some((cursor, object));
     }
-    cursor.write_slice(bytes);
+    cursor.write_le_bytes(bytes, 0, width);
     let (ctx, tag, old_byte_count) = stack.pop()?.into_context();
     let field_byte_count = count(cursor, begin, byte_count) - old_byte_count;
     cursor.write_varint(field_byte_count as u64);
@@ -243,40 +377,11 @@ fn encode_loop<'a>(
             kind,
             offset,
             encoded_tag: tag,
-        } = obj_state.table[obj_state.field_idx - 1];
-        #[cfg(feature = "std")]
-        if false {
-            let descriptor = unsafe {
-                *(obj_state.table.as_ptr()
-                    as *const &'static crate::google::protobuf::DescriptorProto::ProtoType)
-                    .sub(1)
-            };
-            let field_number = tag >> 3;
-            let field = descriptor
-                .field()
-                .iter()
-                .find(|f| f.number() as u32 == field_number);
-            if let Some(field) = field {
-                println!(
-                    "Msg {} Field number: {}, Field name {} kind: {:?}",
-                    descriptor.name(),
-                    field_number,
-                    field.name(),
-                    kind
-                );
-            } else {
-                // field not found in descriptor, treat as unknown
-                println!(
-                    "Msg {} Unknown Field number: {} kind: {:?}",
-                    descriptor.name(),
-                    field_number,
-                    kind
-                );
-            }
-        }
+        } = obj_state.table[obj_state.entry_idx];
         let offset = offset as usize;
         match kind {
-            FieldKind::Unknown => {
+            // Closed enums only appear in decode entries
+            FieldKind::Unknown | FieldKind::ClosedEnum | FieldKind::RepeatedClosedEnum => {
                 unreachable!()
             }
             FieldKind::Varint64 => {
@@ -342,7 +447,7 @@ fn encode_loop<'a>(
                     if cursor <= begin {
                         break;
                     }
-                    cursor.write_unaligned(obj_state.get::<u64>(offset));
+                    cursor.write_fixed(obj_state.get::<u64>(offset));
                     cursor.write_tag(tag);
                 }
             }
@@ -351,7 +456,7 @@ fn encode_loop<'a>(
                     if cursor <= begin {
                         break;
                     }
-                    cursor.write_unaligned(obj_state.get::<u32>(offset));
+                    cursor.write_fixed(obj_state.get::<u32>(offset));
                     cursor.write_tag(tag);
                 }
             }
@@ -365,7 +470,7 @@ fn encode_loop<'a>(
                     // We don't use slop as we need to write length prefix and tag too.
                     let buffer_size = (cursor - begin) as usize;
                     if buffer_size < len {
-                        obj_state.field_idx -= 1;
+                        obj_state.advance();
                         obj_state.push(tag, count(cursor, begin, byte_count), stack)?;
                         cursor.write_slice(&bytes[len - buffer_size..]);
                         return Some((cursor, EncodeObject::Bytes(&bytes[..len - buffer_size])));
@@ -383,28 +488,46 @@ fn encode_loop<'a>(
                     let (offset, child_table) = Table::table(obj_state.table).aux_entry(offset);
                     let child = obj_state.obj.ref_at::<Message>(offset as usize);
                     if !child.is_null() {
-                        obj_state.field_idx -= 1;
-                        obj_state.push(tag, count(cursor, begin, byte_count), stack)?;
-                        obj_state = ObjectEncodeState::new(child.as_ref(), child_table);
+                        obj_state.advance();
+                        obj_state.enter(
+                            child.as_ref(),
+                            child_table,
+                            tag,
+                            count(cursor, begin, byte_count),
+                            stack,
+                        )?;
+                        trace_event!(
+                            message_type = child_table.full_name,
+                            field_number = tag >> 3,
+                            "encoding sub-message"
+                        );
                         continue 'out; // Continue with child message
                     }
                 }
             }
             FieldKind::Group => {
-                let (offset, child_table) = Table::table(obj_state.table).aux_entry(offset);
-                let child = obj_state.obj.ref_at::<Message>(offset as usize);
-                if !child.is_null() {
-                    if cursor <= begin {
-                        break;
+                // For oneof fields, check if this is the active field
+                if has_bit & 0x80 != 0 && !obj_state.is_field_set(has_bit, tag) {
+                    // Skip - not the active oneof field
+                } else {
+                    let (offset, child_table) = Table::table(obj_state.table).aux_entry(offset);
+                    let child = obj_state.obj.ref_at::<Message>(offset as usize);
+                    if !child.is_null() {
+                        if cursor <= begin {
+                            break;
+                        }
+                        // Encoding runs backwards: the end tag goes out first,
+                        // the start tag when the group is popped.
+                        cursor.write_tag(tag + 1); // Set wire type to END_GROUP
+                        obj_state.advance();
+                        obj_state.enter(child.as_ref(), child_table, tag, -1, stack)?;
+                        trace_event!(
+                            message_type = child_table.full_name,
+                            field_number = tag >> 3,
+                            "encoding sub-message"
+                        );
+                        continue 'out; // Continue with child group
                     }
-                    obj_state.field_idx -= 1;
-                    let mut end_tag = tag;
-                    end_tag += 1; // Set wire type to END_GROUP
-                    cursor.write_tag(end_tag);
-                    obj_state.field_idx -= 1;
-                    obj_state.push(tag, -1, stack)?;
-                    obj_state = ObjectEncodeState::new(child.as_ref(), child_table);
-                    continue 'out; // Continue with child group
                 }
             }
             FieldKind::RepeatedVarint64 => {
@@ -415,7 +538,7 @@ fn encode_loop<'a>(
                         let remaining;
                         (cursor, remaining) = write_packed_varint(slice, cursor, begin, |v| v);
                         if !remaining.is_empty() || cursor <= begin {
-                            obj_state.field_idx -= 1;
+                            obj_state.advance();
                             obj_state.push(tag, start_count, stack)?;
                             return Some((cursor, EncodeObject::PackedVarint64(remaining)));
                         }
@@ -445,7 +568,7 @@ fn encode_loop<'a>(
                         (cursor, remaining) =
                             write_packed_varint(slice, cursor, begin, |v| v as u64);
                         if !remaining.is_empty() || cursor <= begin {
-                            obj_state.field_idx -= 1;
+                            obj_state.advance();
                             obj_state.push(tag, start_count, stack)?;
                             return Some((cursor, EncodeObject::PackedVarint32(remaining)));
                         }
@@ -475,7 +598,7 @@ fn encode_loop<'a>(
                         (cursor, remaining) =
                             write_packed_varint(slice, cursor, begin, |v| v as i64 as u64);
                         if !remaining.is_empty() || cursor <= begin {
-                            obj_state.field_idx -= 1;
+                            obj_state.advance();
                             obj_state.push(tag, start_count, stack)?;
                             return Some((cursor, EncodeObject::PackedInt32(remaining)));
                         }
@@ -505,7 +628,7 @@ fn encode_loop<'a>(
                         (cursor, remaining) =
                             write_packed_varint(slice, cursor, begin, |v| zigzag_encode(v));
                         if !remaining.is_empty() || cursor <= begin {
-                            obj_state.field_idx -= 1;
+                            obj_state.advance();
                             obj_state.push(tag, start_count, stack)?;
                             return Some((cursor, EncodeObject::PackedSint64(remaining)));
                         }
@@ -535,7 +658,7 @@ fn encode_loop<'a>(
                         (cursor, remaining) =
                             write_packed_varint(slice, cursor, begin, |v| zigzag_encode(v as i64));
                         if !remaining.is_empty() || cursor <= begin {
-                            obj_state.field_idx -= 1;
+                            obj_state.advance();
                             obj_state.push(tag, start_count, stack)?;
                             return Some((cursor, EncodeObject::PackedSint32(remaining)));
                         }
@@ -567,7 +690,7 @@ fn encode_loop<'a>(
                     let len = bytes.len();
                     let buffer_size = (cursor - begin) as usize;
                     if buffer_size < len {
-                        obj_state.field_idx -= 1;
+                        obj_state.advance();
                         obj_state.push(tag, count(cursor, begin, byte_count), stack)?;
                         cursor.write_slice(&bytes[len - buffer_size..]);
                         return Some((cursor, EncodeObject::Bytes(&bytes[..len - buffer_size])));
@@ -591,7 +714,7 @@ fn encode_loop<'a>(
             FieldKind::RepeatedFixed64 => {
                 let slice = obj_state.get_slice::<u64>(offset);
                 if tag & 7 == 2 && !slice.is_empty() {
-                    // Packed: treat as bytes
+                    // Packed: treat as bytes, little-endian on the wire
                     let bytes = as_bytes(slice);
                     if cursor <= begin {
                         break;
@@ -599,12 +722,12 @@ fn encode_loop<'a>(
                     let len = bytes.len();
                     let buffer_size = (cursor - begin) as usize;
                     if buffer_size < len {
-                        obj_state.field_idx -= 1;
+                        obj_state.advance();
                         obj_state.push(tag, count(cursor, begin, byte_count), stack)?;
-                        cursor.write_slice(&bytes[len - buffer_size..]);
-                        return Some((cursor, EncodeObject::Bytes(&bytes[..len - buffer_size])));
+                        cursor.write_le_bytes(bytes, len - buffer_size, 8);
+                        return Some((cursor, EncodeObject::Fixed(&bytes[..len - buffer_size], 8)));
                     }
-                    cursor.write_slice(bytes);
+                    cursor.write_le_bytes(bytes, 0, 8);
                     cursor.write_varint(len as u64);
                     cursor.write_tag(tag);
                 } else {
@@ -615,7 +738,7 @@ fn encode_loop<'a>(
                         tag,
                         slice,
                         |cursor, &val| {
-                            cursor.write_unaligned(val);
+                            cursor.write_fixed(val);
                         },
                     );
                 }
@@ -623,7 +746,7 @@ fn encode_loop<'a>(
             FieldKind::RepeatedFixed32 => {
                 let slice = obj_state.get_slice::<u32>(offset);
                 if tag & 7 == 2 && !slice.is_empty() {
-                    // Packed: treat as bytes
+                    // Packed: treat as bytes, little-endian on the wire
                     let bytes = as_bytes(slice);
                     if cursor <= begin {
                         break;
@@ -631,12 +754,12 @@ fn encode_loop<'a>(
                     let len = bytes.len();
                     let buffer_size = (cursor - begin) as usize;
                     if buffer_size < len {
-                        obj_state.field_idx -= 1;
+                        obj_state.advance();
                         obj_state.push(tag, count(cursor, begin, byte_count), stack)?;
-                        cursor.write_slice(&bytes[len - buffer_size..]);
-                        return Some((cursor, EncodeObject::Bytes(&bytes[..len - buffer_size])));
+                        cursor.write_le_bytes(bytes, len - buffer_size, 4);
+                        return Some((cursor, EncodeObject::Fixed(&bytes[..len - buffer_size], 4)));
                     }
-                    cursor.write_slice(bytes);
+                    cursor.write_le_bytes(bytes, 0, 4);
                     cursor.write_varint(len as u64);
                     cursor.write_tag(tag);
                 } else {
@@ -647,7 +770,7 @@ fn encode_loop<'a>(
                         tag,
                         slice,
                         |cursor, &val| {
-                            cursor.write_unaligned(val);
+                            cursor.write_fixed(val);
                         },
                     );
                 }
@@ -667,8 +790,11 @@ fn encode_loop<'a>(
                     // We don't use slop as we need to write length prefix and tag too.
                     let buffer_size = (cursor - begin) as usize;
                     if buffer_size < len {
-                        cursor.write_slice(&bytes[len - buffer_size..]);
+                        if obj_state.rep_field_idx == 0 {
+                            obj_state.advance();
+                        }
                         obj_state.push(tag, count(cursor, begin, byte_count), stack)?;
+                        cursor.write_slice(&bytes[len - buffer_size..]);
                         return Some((cursor, EncodeObject::Bytes(&bytes[..len - buffer_size])));
                     }
                     cursor.write_slice(bytes);
@@ -679,18 +805,28 @@ fn encode_loop<'a>(
             FieldKind::RepeatedMessage => {
                 let (offset, child_table) = Table::table(obj_state.table).aux_entry(offset);
                 let slice = obj_state.get_slice::<Message>(offset as usize);
-                if obj_state.rep_field_idx == 0 {
-                    obj_state.rep_field_idx = slice.len();
-                }
-                if obj_state.rep_field_idx > 0 {
-                    obj_state.rep_field_idx -= 1;
-                    if obj_state.rep_field_idx == 0 {
-                        obj_state.field_idx -= 1;
-                    }
-                    obj_state.push(tag, count(cursor, begin, byte_count), stack)?;
-                    obj_state = ObjectEncodeState::new(
-                        slice[obj_state.rep_field_idx].as_ref(),
+                #[cfg(feature = "alloc")]
+                let next = if obj_state.sort_maps
+                    && crate::reflection::is_map_entry(child_table.descriptor())
+                {
+                    obj_state.next_map_entry(slice, child_table)
+                } else {
+                    obj_state.next_repeated(slice.len())
+                };
+                #[cfg(not(feature = "alloc"))]
+                let next = obj_state.next_repeated(slice.len());
+                if let Some(idx) = next {
+                    obj_state.enter(
+                        slice[idx].as_ref(),
                         child_table,
+                        tag,
+                        count(cursor, begin, byte_count),
+                        stack,
+                    )?;
+                    trace_event!(
+                        message_type = child_table.full_name,
+                        field_number = tag >> 3,
+                        "encoding sub-message"
                     );
                     continue 'out; // Continue with child message
                 }
@@ -707,25 +843,296 @@ fn encode_loop<'a>(
                     }
                     obj_state.rep_field_idx -= 1;
                     if obj_state.rep_field_idx == 0 {
-                        obj_state.field_idx -= 1;
+                        obj_state.advance();
                     }
                     let mut end_tag = tag;
                     end_tag += 1; // Set wire type to END_GROUP
                     cursor.write_tag(end_tag);
-                    obj_state.push(tag, -1, stack)?;
-                    obj_state = ObjectEncodeState::new(
+                    obj_state.enter(
                         slice[obj_state.rep_field_idx].as_ref(),
                         child_table,
+                        tag,
+                        -1,
+                        stack,
+                    )?;
+                    trace_event!(
+                        message_type = child_table.full_name,
+                        field_number = tag >> 3,
+                        "encoding sub-message"
                     );
                     continue 'out; // Continue with child group
                 }
             }
         }
-        obj_state.field_idx -= 1;
+        if obj_state.rep_field_idx != 0 {
+            // Ran out of buffer in the middle of a repeated field
+            break;
+        }
+        obj_state.advance();
     }
     Some((cursor, EncodeObject::Object(obj_state)))
 }
 
+/// Number of bytes the encoder writes for `obj`, or `None` if the message
+/// tree nests more than `depth` levels deep.
+///
+/// Follows the same rules as `encode_loop`: presence from has-bits and oneof
+/// discriminants, non-null sub-messages, and packing decided by the tag.
+pub(crate) fn encoded_len(obj: &Object, table: &Table, depth: usize) -> Option<usize> {
+    fn len(n: u64) -> usize {
+        varint_size(n) as usize
+    }
+
+    fn repeated<T: Copy>(slice: &[T], tag: u32, value_len: impl Fn(T) -> usize) -> usize {
+        if slice.is_empty() {
+            return 0;
+        }
+        let values: usize = slice.iter().map(|&v| value_len(v)).sum();
+        if tag & 7 == 2 {
+            len(tag as u64) + len(values as u64) + values
+        } else {
+            slice.len() * len(tag as u64) + values
+        }
+    }
+
+    let child_len = |child: &Message, child_table: &Table| {
+        if depth == 0 {
+            return None;
+        }
+        encoded_len(child.as_ref(), child_table, depth - 1)
+    };
+
+    let state = ObjectEncodeState::new(obj, table, false, false);
+    let mut total = 0;
+    for &TableEntry {
+        has_bit,
+        kind,
+        offset,
+        encoded_tag: tag,
+    } in state.table
+    {
+        let offset = offset as usize;
+        let tag_len = len(tag as u64);
+        let set = state.is_field_set(has_bit, tag);
+        total += match kind {
+            // Closed enums only appear in decode entries
+            FieldKind::Unknown | FieldKind::ClosedEnum | FieldKind::RepeatedClosedEnum => {
+                unreachable!()
+            }
+            FieldKind::Varint64
+            | FieldKind::Varint32
+            | FieldKind::Int32
+            | FieldKind::Varint64Zigzag
+            | FieldKind::Varint32Zigzag
+            | FieldKind::Bool
+            | FieldKind::Fixed64
+            | FieldKind::Fixed32
+            | FieldKind::Bytes
+            | FieldKind::String
+                if !set =>
+            {
+                0
+            }
+            FieldKind::Varint64 => tag_len + len(state.get::<u64>(offset)),
+            FieldKind::Varint32 => tag_len + len(state.get::<u32>(offset) as u64),
+            FieldKind::Int32 => tag_len + len(state.get::<i32>(offset) as i64 as u64),
+            FieldKind::Varint64Zigzag => tag_len + len(zigzag_encode(state.get::<i64>(offset))),
+            FieldKind::Varint32Zigzag => {
+                tag_len + len(zigzag_encode(state.get::<i32>(offset) as i64) as u32 as u64)
+            }
+            FieldKind::Bool => tag_len + 1,
+            FieldKind::Fixed64 => tag_len + 8,
+            FieldKind::Fixed32 => tag_len + 4,
+            FieldKind::Bytes | FieldKind::String => {
+                let bytes = state.bytes(offset);
+                tag_len + len(bytes.len() as u64) + bytes.len()
+            }
+            FieldKind::Message | FieldKind::Group => {
+                let (offset, child_table) = table.aux_entry(offset);
+                let child = obj.ref_at::<Message>(offset as usize);
+                if (has_bit & 0x80 != 0 && !set) || child.is_null() {
+                    0
+                } else {
+                    let child_len = child_len(child, child_table)?;
+                    if kind == FieldKind::Group {
+                        2 * tag_len + child_len
+                    } else {
+                        tag_len + len(child_len as u64) + child_len
+                    }
+                }
+            }
+            FieldKind::RepeatedVarint64 => repeated(state.get_slice::<u64>(offset), tag, len),
+            FieldKind::RepeatedVarint32 => {
+                repeated(state.get_slice::<u32>(offset), tag, |v| len(v as u64))
+            }
+            FieldKind::RepeatedInt32 => {
+                repeated(state.get_slice::<i32>(offset), tag, |v| len(v as i64 as u64))
+            }
+            FieldKind::RepeatedVarint64Zigzag => {
+                repeated(state.get_slice::<i64>(offset), tag, |v| len(zigzag_encode(v)))
+            }
+            FieldKind::RepeatedVarint32Zigzag => repeated(state.get_slice::<i32>(offset), tag, |v| {
+                len(zigzag_encode(v as i64) as u32 as u64)
+            }),
+            FieldKind::RepeatedBool => repeated(state.get_slice::<bool>(offset), tag, |_| 1),
+            FieldKind::RepeatedFixed64 => repeated(state.get_slice::<u64>(offset), tag, |_| 8),
+            FieldKind::RepeatedFixed32 => repeated(state.get_slice::<u32>(offset), tag, |_| 4),
+            FieldKind::RepeatedBytes | FieldKind::RepeatedString => state
+                .get_slice::<Bytes>(offset)
+                .iter()
+                .map(|bytes| tag_len + len(bytes.len() as u64) + bytes.len())
+                .sum(),
+            FieldKind::RepeatedMessage | FieldKind::RepeatedGroup => {
+                let (offset, child_table) = table.aux_entry(offset);
+                let mut field_len = 0;
+                for child in state.get_slice::<Message>(offset as usize) {
+                    let child_len = child_len(child, child_table)?;
+                    field_len += if kind == FieldKind::RepeatedGroup {
+                        2 * tag_len + child_len
+                    } else {
+                        tag_len + len(child_len as u64) + child_len
+                    };
+                }
+                field_len
+            }
+        };
+    }
+    Some(total)
+}
+
+/// Copy the fields of `obj` into `copy`, a message of the same type with no
+/// fields set, allocating in `arena`. Fails with `MessageTreeTooDeep` if the
+/// message tree nests more than `depth` levels deep.
+///
+/// Copies what encoding `obj` and decoding the result into `copy` would,
+/// following the same rules as `encoded_len`, without the wire format.
+pub(crate) fn copy_object(
+    obj: &Object,
+    table: &Table,
+    copy: &mut Object,
+    arena: &mut crate::arena::Arena,
+    depth: usize,
+) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+    fn copy_child(
+        child: &Message,
+        child_table: &Table,
+        arena: &mut crate::arena::Arena,
+        depth: usize,
+    ) -> Result<Message, crate::Error<core::alloc::LayoutError>> {
+        if depth == 0 {
+            return Err(crate::Error::MessageTreeTooDeep);
+        }
+        let copy = Object::create(child_table.size as u32, arena)?;
+        copy_object(child.as_ref(), child_table, copy, arena, depth - 1)?;
+        Ok(Message::new(copy))
+    }
+
+    fn copy_value<T: Copy>(obj: &Object, copy: &mut Object, offset: usize) {
+        *copy.ref_mut::<T>(offset as u32) = obj.get::<T>(offset);
+    }
+
+    fn copy_slice<T: Copy>(
+        obj: &Object,
+        copy: &mut Object,
+        offset: usize,
+        arena: &mut crate::arena::Arena,
+    ) -> Result<(), crate::Error<core::alloc::LayoutError>> {
+        let slice = obj.get_slice::<T>(offset);
+        if !slice.is_empty() {
+            copy.ref_mut::<RepeatedField<T>>(offset as u32).assign(slice, arena)?;
+        }
+        Ok(())
+    }
+
+    let state = ObjectEncodeState::new(obj, table, false, false);
+    for &TableEntry {
+        has_bit,
+        kind,
+        offset,
+        encoded_tag: tag,
+    } in state.table
+    {
+        let offset = offset as usize;
+        let set = state.is_field_set(has_bit, tag);
+        let mark_set = |copy: &mut Object| {
+            if has_bit & 0x80 != 0 {
+                *copy.ref_mut::<u32>((has_bit & 0x7F) as u32 * 4) = tag >> 3;
+            } else {
+                copy.set_has_bit(has_bit as u32);
+            }
+        };
+        match kind {
+            // Closed enums only appear in decode entries
+            FieldKind::Unknown | FieldKind::ClosedEnum | FieldKind::RepeatedClosedEnum => {
+                unreachable!()
+            }
+            FieldKind::Varint64
+            | FieldKind::Varint32
+            | FieldKind::Int32
+            | FieldKind::Varint64Zigzag
+            | FieldKind::Varint32Zigzag
+            | FieldKind::Bool
+            | FieldKind::Fixed64
+            | FieldKind::Fixed32
+            | FieldKind::Bytes
+            | FieldKind::String
+                if !set => {}
+            FieldKind::Varint64 | FieldKind::Varint64Zigzag | FieldKind::Fixed64 => {
+                mark_set(copy);
+                copy_value::<u64>(obj, copy, offset);
+            }
+            FieldKind::Varint32 | FieldKind::Int32 | FieldKind::Varint32Zigzag | FieldKind::Fixed32 => {
+                mark_set(copy);
+                copy_value::<u32>(obj, copy, offset);
+            }
+            FieldKind::Bool => {
+                mark_set(copy);
+                copy_value::<bool>(obj, copy, offset);
+            }
+            FieldKind::Bytes | FieldKind::String => {
+                mark_set(copy);
+                copy.ref_mut::<Bytes>(offset as u32).assign(obj.bytes(offset), arena)?;
+            }
+            FieldKind::Message | FieldKind::Group => {
+                let (offset, child_table) = table.aux_entry(offset);
+                let child = obj.ref_at::<Message>(offset as usize);
+                if (has_bit & 0x80 == 0 || set) && !child.is_null() {
+                    if has_bit & 0x80 != 0 {
+                        mark_set(copy);
+                    }
+                    *copy.ref_mut::<Message>(offset) = copy_child(child, child_table, arena, depth)?;
+                }
+            }
+            FieldKind::RepeatedVarint64
+            | FieldKind::RepeatedVarint64Zigzag
+            | FieldKind::RepeatedFixed64 => copy_slice::<u64>(obj, copy, offset, arena)?,
+            FieldKind::RepeatedVarint32
+            | FieldKind::RepeatedInt32
+            | FieldKind::RepeatedVarint32Zigzag
+            | FieldKind::RepeatedFixed32 => copy_slice::<u32>(obj, copy, offset, arena)?,
+            FieldKind::RepeatedBool => copy_slice::<bool>(obj, copy, offset, arena)?,
+            FieldKind::RepeatedBytes | FieldKind::RepeatedString => {
+                let elements = obj.get_slice::<Bytes>(offset);
+                let field = copy.ref_mut::<RepeatedField<Bytes>>(offset as u32);
+                field.reserve(elements.len(), arena)?;
+                for bytes in elements {
+                    field.push(Bytes::from_slice(bytes, arena)?, arena)?;
+                }
+            }
+            FieldKind::RepeatedMessage | FieldKind::RepeatedGroup => {
+                let (offset, child_table) = table.aux_entry(offset);
+                let elements = obj.get_slice::<Message>(offset as usize);
+                let field = copy.ref_mut::<RepeatedField<Message>>(offset);
+                field.reserve(elements.len(), arena)?;
+                for child in elements {
+                    field.push(copy_child(child, child_table, arena, depth)?, arena)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 struct ResumableState<'a> {
     object: EncodeObject<'a>,
     overrun: isize,
@@ -749,7 +1156,10 @@ impl<'a> ResumableState<'a> {
                 EncodeObject::Done => (cursor, EncodeObject::Done),
                 EncodeObject::Object(ctx) => encode_loop(ctx, cursor, begin, byte_count, stack)?,
                 EncodeObject::Bytes(bytes) => {
-                    encode_bytes(bytes, cursor, begin, byte_count, stack)?
+                    encode_bytes(bytes, 1, cursor, begin, byte_count, stack)?
+                }
+                EncodeObject::Fixed(bytes, width) => {
+                    encode_bytes(bytes, width, cursor, begin, byte_count, stack)?
                 }
                 EncodeObject::PackedVarint64(slice) => encode_packed_varint(
                     slice,
@@ -824,9 +1234,16 @@ pub(crate) enum ResumeResult<'a> {
 }
 
 impl<'a, const STACK_DEPTH: usize> ResumeableEncode<'a, STACK_DEPTH> {
-    pub(crate) fn new<'pool: 'a>(msg: crate::reflection::DynamicMessageRef<'pool, 'a>) -> Self {
-        let encode_ctx = ObjectEncodeState::new(msg.object, msg.table);
-        Self {
+    /// Fails with [`VersionMismatch`](crate::Error::VersionMismatch) if the
+    /// message's table was generated for another runtime version.
+    pub(crate) fn new<'pool: 'a>(
+        msg: crate::reflection::DynamicMessageRef<'pool, 'a>,
+        options: EncodeOptions,
+    ) -> Result<Self, crate::Error> {
+        msg.table.check_version()?;
+        let encode_ctx =
+            ObjectEncodeState::new(msg.object, msg.table, options.canonical_field_order, false);
+        Ok(Self {
             state: MaybeUninit::new(ResumableState {
                 overrun: 0,
                 object: EncodeObject::Object(encode_ctx),
@@ -834,7 +1251,18 @@ impl<'a, const STACK_DEPTH: usize> ResumeableEncode<'a, STACK_DEPTH> {
             }),
             patch_buffer: [0; 2 * SLOP_SIZE],
             stack: Default::default(),
+        })
+    }
+
+    /// Emit the entries of every map sorted by key, keeping only the last
+    /// entry of a repeated key. Finding each entry scans the whole map.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn sort_maps(mut self) -> Self {
+        let state = unsafe { self.state.assume_init_mut() };
+        if let EncodeObject::Object(ctx) = &mut state.object {
+            ctx.sort_maps = true;
         }
+        self
     }
 
     pub(crate) fn resume_encode<'b>(&mut self, buffer: &'b mut [u8]) -> Option<ResumeResult<'b>> {
@@ -0,0 +1,292 @@
+//! Push-based decoding of wire data.
+//!
+//! [`decode_events`] walks an encoded message and reports each field it
+//! meets to a [`WireVisitor`], without building a message or allocating.
+//! Consumers that only look at a few values, like metrics extractors, skip
+//! the cost of decoding the rest:
+//!
+//! ```
+//! use protocrap::ProtobufRef;
+//! use protocrap::events::{WireVisitor, decode_events};
+//! use protocrap::generated_code_only::Protobuf;
+//! use protocrap::google::protobuf::FieldDescriptorProto::ProtoType as FieldDescriptorProto;
+//! use protocrap::google::protobuf::FileDescriptorProto;
+//!
+//! /// Counts the fields of all messages in a file.
+//! struct CountFields(usize);
+//!
+//! impl WireVisitor for CountFields {
+//!     fn on_message_start(&mut self, _number: u32, field: Option<&FieldDescriptorProto>) {
+//!         if field.is_some_and(|field| field.name() == "field") {
+//!             self.0 += 1;
+//!         }
+//!     }
+//! }
+//!
+//! let file = FileDescriptorProto::ProtoType::file_descriptor();
+//! let encoded = file.encode_vec::<32>().unwrap();
+//! let mut count = CountFields(0);
+//! decode_events(&encoded, FileDescriptorProto::ProtoType::table(), &mut count).unwrap();
+//! assert!(count.0 > 100);
+//! ```
+//!
+//! Fields of message type are reported as a start event, the events of
+//! their fields and an end event, map entries included. Packed repeated
+//! fields are unpacked into one event per element. Unknown fields are
+//! reported under their field number, with no descriptor; unknown groups
+//! are walked like messages.
+
+use crate::Error;
+use crate::google::protobuf::FieldDescriptorProto::{ProtoType as FieldDescriptorProto, Type};
+use crate::reflection::is_repeated;
+use crate::tables::Table;
+use crate::wire::{Reader, WireType};
+
+/// Deepest message nesting [`decode_events`] follows.
+const MAX_DEPTH: usize = 100;
+
+/// Receives the fields [`decode_events`] meets, in wire order.
+///
+/// Each event carries the field number and, for fields the table knows, the
+/// field's descriptor. Values are raw: varints aren't zigzag decoded or
+/// narrowed, fixed width values aren't reinterpreted as floats, bytes
+/// aren't checked to be UTF-8. All methods do nothing by default.
+pub trait WireVisitor {
+    /// A varint value, or one element of a packed varint field.
+    fn on_field_varint(&mut self, number: u32, field: Option<&FieldDescriptorProto>, value: u64) {
+        let _ = (number, field, value);
+    }
+
+    /// A 64-bit fixed width value, or one element of a packed field of
+    /// them.
+    fn on_field_fixed64(&mut self, number: u32, field: Option<&FieldDescriptorProto>, value: u64) {
+        let _ = (number, field, value);
+    }
+
+    /// A 32-bit fixed width value, or one element of a packed field of
+    /// them.
+    fn on_field_fixed32(&mut self, number: u32, field: Option<&FieldDescriptorProto>, value: u32) {
+        let _ = (number, field, value);
+    }
+
+    /// A length-delimited value other than a known message or packed field:
+    /// a string, bytes, or an unknown field.
+    fn on_field_bytes(&mut self, number: u32, field: Option<&FieldDescriptorProto>, value: &[u8]) {
+        let _ = (number, field, value);
+    }
+
+    /// The start of a message field or group, whose fields are reported
+    /// next.
+    fn on_message_start(&mut self, number: u32, field: Option<&FieldDescriptorProto>) {
+        let _ = (number, field);
+    }
+
+    /// The end of the message or group started last.
+    fn on_message_end(&mut self, number: u32, field: Option<&FieldDescriptorProto>) {
+        let _ = (number, field);
+    }
+}
+
+/// Walk `buf`, an encoded message of type `table`, reporting its fields to
+/// `visitor`.
+///
+/// Tables come from `Protobuf::table()` of generated types or from
+/// [`DescriptorPool::get_table`](crate::descriptor_pool::DescriptorPool::get_table).
+/// Fails with [`Error::InvalidProtobufData`] if `buf` isn't valid wire
+/// format, and with [`Error::MessageTreeTooDeep`] if it nests more than 100
+/// messages deep. Events up to the error have been reported by then.
+pub fn decode_events(buf: &[u8], table: &Table, visitor: &mut impl WireVisitor) -> Result<(), Error> {
+    fields(&mut Reader::new(buf), Some(table), None, visitor, 0)
+}
+
+/// Report the fields of a message of type `table`, if known, from `reader`,
+/// up to the end of the input or, for a group, its end tag.
+fn fields(
+    reader: &mut Reader,
+    table: Option<&Table>,
+    group: Option<u32>,
+    visitor: &mut impl WireVisitor,
+    depth: usize,
+) -> Result<(), Error> {
+    if depth > MAX_DEPTH {
+        return Err(Error::MessageTreeTooDeep);
+    }
+    while !reader.is_empty() {
+        let (number, wire_type) = reader.read_tag()?;
+        let field = table.and_then(|table| table.descriptor).and_then(|descriptor| {
+            descriptor
+                .field()
+                .iter()
+                .find(|field| field.number() == number as i32)
+        });
+        let field = field.map(|field| &**field);
+        match wire_type {
+            WireType::Varint => visitor.on_field_varint(number, field, reader.read_varint()?),
+            WireType::Fixed64 => visitor.on_field_fixed64(number, field, reader.read_fixed64()?),
+            WireType::LengthDelimited => {
+                let value = reader.read_bytes()?;
+                let child = table.zip(field).and_then(|(table, field)| child_table(table, field));
+                if let Some(child) = child {
+                    visitor.on_message_start(number, field);
+                    fields(&mut Reader::new(value), Some(child), None, visitor, depth + 1)?;
+                    visitor.on_message_end(number, field);
+                } else if let Some(wire_type) = field.and_then(packed_wire_type) {
+                    let mut elements = Reader::new(value);
+                    while !elements.is_empty() {
+                        match wire_type {
+                            WireType::Varint => visitor.on_field_varint(number, field, elements.read_varint()?),
+                            WireType::Fixed64 => visitor.on_field_fixed64(number, field, elements.read_fixed64()?),
+                            _ => visitor.on_field_fixed32(number, field, elements.read_fixed32()?),
+                        }
+                    }
+                } else {
+                    visitor.on_field_bytes(number, field, value);
+                }
+            }
+            WireType::StartGroup => {
+                let child = table.zip(field).and_then(|(table, field)| child_table(table, field));
+                visitor.on_message_start(number, field);
+                fields(reader, child, Some(number), visitor, depth + 1)?;
+                visitor.on_message_end(number, field);
+            }
+            WireType::EndGroup if group == Some(number) => return Ok(()),
+            WireType::EndGroup => return Err(Error::InvalidProtobufData),
+            WireType::Fixed32 => visitor.on_field_fixed32(number, field, reader.read_fixed32()?),
+        }
+    }
+    if group.is_some() {
+        return Err(Error::InvalidProtobufData);
+    }
+    Ok(())
+}
+
+/// The table of the messages in `field`, if it is a message field.
+fn child_table<'t>(table: &'t Table, field: &FieldDescriptorProto) -> Option<&'t Table> {
+    if !matches!(field.r#type(), Some(Type::TYPE_MESSAGE | Type::TYPE_GROUP)) {
+        return None;
+    }
+    let entry = table.field_entry(field.number() as u32)?;
+    Some(table.aux_entry_decode(entry).1)
+}
+
+/// The wire type of the elements of `field`, if it is a repeated field whose
+/// elements can be packed.
+fn packed_wire_type(field: &FieldDescriptorProto) -> Option<WireType> {
+    if !is_repeated(field) {
+        return None;
+    }
+    match field.r#type()? {
+        Type::TYPE_INT32
+        | Type::TYPE_INT64
+        | Type::TYPE_UINT32
+        | Type::TYPE_UINT64
+        | Type::TYPE_SINT32
+        | Type::TYPE_SINT64
+        | Type::TYPE_BOOL
+        | Type::TYPE_ENUM => Some(WireType::Varint),
+        Type::TYPE_FIXED64 | Type::TYPE_SFIXED64 | Type::TYPE_DOUBLE => Some(WireType::Fixed64),
+        Type::TYPE_FIXED32 | Type::TYPE_SFIXED32 | Type::TYPE_FLOAT => Some(WireType::Fixed32),
+        _ => None,
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::generated_code_only::Protobuf;
+    use crate::google::protobuf::FileDescriptorProto;
+    use std::format;
+    use std::string::String;
+    use std::vec::Vec;
+
+    /// Records events as lines of text.
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    fn name(number: u32, field: Option<&FieldDescriptorProto>) -> String {
+        field.map_or(format!("{number}"), |field| field.name().into())
+    }
+
+    impl WireVisitor for Log {
+        fn on_field_varint(&mut self, number: u32, field: Option<&FieldDescriptorProto>, value: u64) {
+            self.0.push(format!("{}: {value}", name(number, field)));
+        }
+
+        fn on_field_fixed64(&mut self, number: u32, field: Option<&FieldDescriptorProto>, value: u64) {
+            self.0.push(format!("{}: {value:#x}", name(number, field)));
+        }
+
+        fn on_field_fixed32(&mut self, number: u32, field: Option<&FieldDescriptorProto>, value: u32) {
+            self.0.push(format!("{}: {value:#x}", name(number, field)));
+        }
+
+        fn on_field_bytes(&mut self, number: u32, field: Option<&FieldDescriptorProto>, value: &[u8]) {
+            self.0.push(format!("{}: {:?}", name(number, field), value.escape_ascii().to_string()));
+        }
+
+        fn on_message_start(&mut self, number: u32, field: Option<&FieldDescriptorProto>) {
+            self.0.push(format!("{} {{", name(number, field)));
+        }
+
+        fn on_message_end(&mut self, number: u32, field: Option<&FieldDescriptorProto>) {
+            self.0.push(format!("}} {}", name(number, field)));
+        }
+    }
+
+    #[test]
+    fn reports_fields_in_wire_order() {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(b"\x0a\x07a.proto");
+        // public_dependency unpacked, then packed
+        buf.extend_from_slice(&[0x50, 0x03, 0x52, 0x02, 0x04, 0x05]);
+        // message_type { field { number: 1 } options { deprecated: true } }
+        buf.extend_from_slice(&[0x22, 0x08, 0x12, 0x02, 0x18, 0x01, 0x3a, 0x02, 0x18, 0x01]);
+        // 1000: 5, 1001 { 1: 1.0f32 }, group 1002 { 1: 2 }, 1003: 0x1 fixed64
+        buf.extend_from_slice(&[0xc0, 0x3e, 0x05]);
+        buf.extend_from_slice(&[0xca, 0x3e, 0x05, 0x0d, 0x00, 0x00, 0x80, 0x3f]);
+        buf.extend_from_slice(&[0xd3, 0x3e, 0x08, 0x02, 0xd4, 0x3e]);
+        buf.extend_from_slice(&[0xd9, 0x3e, 1, 0, 0, 0, 0, 0, 0, 0]);
+
+        let table = FileDescriptorProto::ProtoType::table();
+        let mut log = Log::default();
+        decode_events(&buf, table, &mut log).unwrap();
+        assert_eq!(
+            log.0,
+            [
+                "name: \"a.proto\"",
+                "public_dependency: 3",
+                "public_dependency: 4",
+                "public_dependency: 5",
+                "message_type {",
+                "field {",
+                "number: 1",
+                "} field",
+                "options {",
+                "deprecated: 1",
+                "} options",
+                "} message_type",
+                "1000: 5",
+                "1001: \"\\\\r\\\\x00\\\\x00\\\\x80?\"",
+                "1002 {",
+                "1: 2",
+                "} 1002",
+                "1003: 0x1",
+            ]
+        );
+
+        for bad in [&buf[..buf.len() - 1], &[0x22, 0x02, 0x12, 0x05][..], &[0x0b][..], &[0x0c][..]] {
+            assert!(matches!(
+                decode_events(bad, table, &mut Log::default()),
+                Err(Error::InvalidProtobufData)
+            ));
+        }
+        let mut deep = Vec::new();
+        for _ in 0..=MAX_DEPTH {
+            deep.extend_from_slice(&[0xd3, 0x3e]);
+        }
+        assert!(matches!(
+            decode_events(&deep, table, &mut Log::default()),
+            Err(Error::MessageTreeTooDeep)
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+//! C ABI entry points for embedding the table-driven codec in C and C++.
+//!
+//! Messages are handled as opaque objects described by their [`Table`]. Code
+//! generated with the `ffi` codegen option exports a `pc_table_<full_name>()`
+//! function per message returning its table, and `protocrap-codegen
+//! --c-header` writes a header declaring those together with the functions
+//! below.
+//!
+//! ```c
+//! pc_arena *arena = pc_arena_new();
+//! pc_message *msg = pc_decode(pc_table_my_package_MyMessage(), buf, len, arena);
+//! if (msg) {
+//!     size_t out_len = sizeof out;
+//!     int status = pc_encode(pc_table_my_package_MyMessage(), msg, out, &out_len);
+//! }
+//! pc_arena_free(arena);
+//! ```
+//!
+//! Decoded messages live in the arena they were decoded into and are freed
+//! with it.
+
+use crate::arena::Arena;
+use crate::base::Object;
+use crate::reflection::{DynamicMessage, DynamicMessageRef};
+use crate::tables::Table;
+use crate::{Error, ProtobufMut, ProtobufRef};
+
+#[cfg(not(feature = "nightly"))]
+use allocator_api2::alloc::Global;
+#[cfg(feature = "nightly")]
+use std::alloc::Global;
+
+/// Stack depth used by [`pc_decode`] and [`pc_encode`].
+const STACK_DEPTH: usize = 64;
+
+/// [`pc_encode`] succeeded.
+pub const PC_OK: i32 = 0;
+/// The output buffer of [`pc_encode`] is too small.
+pub const PC_BUFFER_TOO_SMALL: i32 = 1;
+/// The message nests deeper than the encoder's stack.
+pub const PC_MESSAGE_TREE_TOO_DEEP: i32 = 2;
+
+/// Create an arena allocating from the global allocator.
+#[unsafe(no_mangle)]
+pub extern "C" fn pc_arena_new() -> *mut Arena<'static> {
+    Box::into_raw(Box::new(Arena::new(&Global)))
+}
+
+/// Free an arena and every message allocated in it.
+///
+/// # Safety
+///
+/// `arena` must come from [`pc_arena_new`] and not be used afterwards. Null is
+/// ignored.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pc_arena_free(arena: *mut Arena<'static>) {
+    if !arena.is_null() {
+        drop(unsafe { Box::from_raw(arena) });
+    }
+}
+
+/// Decode `len` bytes at `buf` into a new message of type `table`, allocated in
+/// `arena`. Returns null if the input is invalid or the arena is exhausted.
+///
+/// # Safety
+///
+/// `table` must be a message table, `buf` must be valid for reads of `len`
+/// bytes and `arena` must come from [`pc_arena_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pc_decode(
+    table: *const Table,
+    buf: *const u8,
+    len: usize,
+    arena: *mut Arena<'static>,
+) -> *mut Object {
+    let (table, arena) = unsafe { (&*table, &mut *arena) };
+    let buf = if len == 0 {
+        &[]
+    } else {
+        unsafe { core::slice::from_raw_parts(buf, len) }
+    };
+    let Ok(object) = Object::create(table.size as u32, arena) else {
+        return core::ptr::null_mut();
+    };
+    let mut msg = DynamicMessage { object, table };
+    if !msg.decode_flat::<STACK_DEPTH>(arena, buf) {
+        return core::ptr::null_mut();
+    }
+    msg.object
+}
+
+/// Encode `msg` of type `table` into the `*len` bytes at `buf`.
+///
+/// On [`PC_OK`] the encoding starts at `buf` and `*len` is set to its length.
+/// On [`PC_BUFFER_TOO_SMALL`] `*len` is set to the length of the encoding,
+/// the size of the buffer to retry with.
+///
+/// # Safety
+///
+/// `msg` must be a message of type `table`, `buf` must be valid for writes of
+/// `*len` bytes and `len` must be valid for reads and writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pc_encode(
+    table: *const Table,
+    msg: *const Object,
+    buf: *mut u8,
+    len: *mut usize,
+) -> i32 {
+    let (table, object) = unsafe { (&*table, &*msg) };
+    let capacity = unsafe { *len };
+    let buffer = if capacity == 0 {
+        &mut []
+    } else {
+        unsafe { core::slice::from_raw_parts_mut(buf, capacity) }
+    };
+    let msg = DynamicMessageRef { object, table };
+    let encoded_len = match msg.encode_flat_to_start::<STACK_DEPTH>(buffer) {
+        Ok(encoded) => encoded.len(),
+        Err(Error::BufferTooSmall) => {
+            let Ok(needed) = msg.encoded_len::<STACK_DEPTH>() else {
+                return PC_MESSAGE_TREE_TOO_DEEP;
+            };
+            unsafe { *len = needed };
+            return PC_BUFFER_TOO_SMALL;
+        }
+        Err(_) => return PC_MESSAGE_TREE_TOO_DEEP,
+    };
+    unsafe { *len = encoded_len };
+    PC_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generated_code_only::Protobuf;
+    use crate::google::protobuf::FileDescriptorProto;
+
+    #[test]
+    fn decode_encode_roundtrip() {
+        let table = FileDescriptorProto::ProtoType::table();
+        let bytes = FileDescriptorProto::ProtoType::file_descriptor()
+            .encode_vec::<32>()
+            .unwrap();
+        unsafe {
+            let arena = pc_arena_new();
+            let msg = pc_decode(table, bytes.as_ptr(), bytes.len(), arena);
+            assert!(!msg.is_null());
+
+            let mut out = vec![0u8; bytes.len()];
+            let mut len = 10;
+            assert_eq!(
+                pc_encode(table, msg, out.as_mut_ptr(), &mut len),
+                PC_BUFFER_TOO_SMALL
+            );
+            assert_eq!(len, bytes.len());
+            assert_eq!(pc_encode(table, msg, out.as_mut_ptr(), &mut len), PC_OK);
+            assert_eq!(len, bytes.len());
+            assert_eq!(out, bytes);
+
+            assert!(pc_decode(table, [0xff].as_ptr(), 1, arena).is_null());
+            pc_arena_free(arena);
+        }
+    }
+}
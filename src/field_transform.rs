@@ -0,0 +1,283 @@
+//! Per-field byte transforms applied around decoding and encoding.
+//!
+//! Envelope-encrypted columns keep ciphertext on the wire and plaintext in
+//! memory. Register a [`FieldTransform`] for each such `bytes` field, keyed by
+//! message type and field number, then decode and encode through
+//! [`FieldTransforms`]: the fields are decrypted in place after decoding and
+//! encrypted on the way out. Only the transformed values are copied, the rest
+//! of the message is decoded and encoded as usual.
+//!
+//! Transforms run on `bytes` fields anywhere in the message tree, singular,
+//! repeated or in a oneof. Fields that are unset, or empty in proto3, are
+//! left alone in both directions.
+
+use crate::arena::Arena;
+use crate::base::{Message, Object};
+use crate::containers::{Bytes, RepeatedField};
+use crate::google::protobuf::DescriptorProto::ProtoType as DescriptorProto;
+use crate::google::protobuf::FieldDescriptorProto::Type;
+use crate::reflection::{DynamicMessage, is_message, is_repeated};
+use crate::tables::Table;
+use crate::{Error, ProtobufMut};
+use alloc::vec::Vec;
+
+#[cfg(not(feature = "nightly"))]
+use allocator_api2::alloc::Global;
+#[cfg(feature = "nightly")]
+use alloc::alloc::Global;
+
+/// A reversible transform of the value of a `bytes` field, such as
+/// decryption on decode and encryption on encode.
+pub trait FieldTransform {
+    /// Turn `value` as read from the wire into the value stored in the
+    /// message, appending it to the empty `out`.
+    fn decode(&self, value: &[u8], out: &mut Vec<u8>) -> Result<(), Error>;
+
+    /// Turn `value` as stored in the message into the bytes written to the
+    /// wire, appending them to the empty `out`.
+    fn encode(&self, value: &[u8], out: &mut Vec<u8>) -> Result<(), Error>;
+}
+
+struct Hook<'a> {
+    descriptor: &'a DescriptorProto,
+    field_number: i32,
+    transform: &'a dyn FieldTransform,
+}
+
+/// A set of [`FieldTransform`]s keyed by message type and field number.
+#[derive(Default)]
+pub struct FieldTransforms<'a> {
+    hooks: Vec<Hook<'a>>,
+}
+
+impl<'a> FieldTransforms<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply `transform` to the `bytes` field `field_number` of every message
+    /// of type `table`.
+    ///
+    /// Tables come from `Protobuf::table()` of generated types or, by full
+    /// name, from
+    /// [`DescriptorPool::get_table`](crate::descriptor_pool::DescriptorPool::get_table).
+    /// Fails with [`Error::MessageNotFound`] if the message has no `bytes`
+    /// field numbered `field_number`.
+    pub fn add(
+        &mut self,
+        table: &'a Table,
+        field_number: i32,
+        transform: &'a dyn FieldTransform,
+    ) -> Result<(), Error> {
+        let is_bytes = table
+            .descriptor()
+            .field()
+            .iter()
+            .any(|field| field.number() == field_number && field.r#type() == Some(Type::TYPE_BYTES));
+        if !is_bytes {
+            return Err(Error::MessageNotFound);
+        }
+        self.hooks.push(Hook {
+            descriptor: table.descriptor(),
+            field_number,
+            transform,
+        });
+        Ok(())
+    }
+
+    /// Decode `buf` into `msg`, then run the registered transforms' `decode`
+    /// on the fields they apply to, storing the results in `arena`.
+    ///
+    /// Fails with [`Error::InvalidProtobufData`] if `buf` doesn't decode, or
+    /// with the first error returned by a transform, in which case `msg` is
+    /// left partially transformed.
+    pub fn decode_flat<'pool, const STACK_DEPTH: usize>(
+        &self,
+        msg: &mut impl ProtobufMut<'pool>,
+        arena: &mut Arena,
+        buf: &[u8],
+    ) -> Result<(), Error> {
+        if !msg.decode_flat::<STACK_DEPTH>(arena, buf) {
+            return Err(Error::InvalidProtobufData);
+        }
+        let mut out = Vec::new();
+        self.visit(msg.as_dyn_mut(), &mut |value, transform| {
+            out.clear();
+            transform.decode(value.slice(), &mut out)?;
+            value
+                .assign(&out, arena)
+                .map_err(|_| Error::ArenaAllocationFailed)
+        })
+    }
+
+    /// Encode `msg` to a new Vec with the registered transforms' `encode` run
+    /// on the fields they apply to.
+    ///
+    /// The transformed values are swapped into `msg` for the duration of the
+    /// encoding and the originals put back before returning, also on error,
+    /// so `msg` needs to be mutable but reads the same afterwards.
+    pub fn encode_vec<'pool, const STACK_DEPTH: usize>(
+        &self,
+        msg: &mut impl ProtobufMut<'pool>,
+    ) -> Result<Vec<u8>, Error> {
+        let mut scratch = Arena::new(&Global);
+        let mut swapped = Restore(Vec::new());
+        let mut out = Vec::new();
+        self.visit(msg.as_dyn_mut(), &mut |value, transform| {
+            out.clear();
+            transform.encode(value.slice(), &mut out)?;
+            let encoded =
+                Bytes::from_slice(&out, &mut scratch).map_err(|_| Error::ArenaAllocationFailed)?;
+            swapped
+                .0
+                .push((value as *mut Bytes, core::mem::replace(value, encoded)));
+            Ok(())
+        })?;
+        msg.encode_vec::<STACK_DEPTH>()
+    }
+
+    /// Call `f` on every value of a field with a hook in `msg` and its
+    /// sub-messages.
+    fn visit(
+        &self,
+        msg: DynamicMessage<'_, '_>,
+        f: &mut impl FnMut(&mut Bytes, &dyn FieldTransform) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        if self.hooks.is_empty() {
+            return Ok(());
+        }
+        let table = msg.table;
+        for field in table.descriptor().field() {
+            let entry = table.field_entry(field.number() as u32).unwrap();
+            if is_message(field) {
+                let (offset, child_table) = table.aux_entry_decode(entry);
+                if is_repeated(field) {
+                    let children = msg.object.ref_mut::<RepeatedField<Message>>(offset);
+                    for child in children.iter_mut() {
+                        self.visit(child_message(child, child_table), f)?;
+                    }
+                } else if msg.get_field(field).is_some() {
+                    let child = msg.object.ref_mut::<Message>(offset);
+                    self.visit(child_message(child, child_table), f)?;
+                }
+                continue;
+            }
+            let Some(hook) = self.hooks.iter().find(|hook| {
+                core::ptr::eq(hook.descriptor, table.descriptor()) && hook.field_number == field.number()
+            }) else {
+                continue;
+            };
+            if is_repeated(field) {
+                let values = msg.object.ref_mut::<RepeatedField<Bytes>>(entry.offset());
+                for value in values.iter_mut() {
+                    f(value, hook.transform)?;
+                }
+            } else if msg.get_field(field).is_some() {
+                f(msg.object.ref_mut::<Bytes>(entry.offset()), hook.transform)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn child_message<'pool, 'msg>(
+    child: &'msg mut Message,
+    table: &'pool Table,
+) -> DynamicMessage<'pool, 'msg> {
+    let object: &'msg mut Object = child.as_mut();
+    DynamicMessage { object, table }
+}
+
+/// Field values swapped out by [`FieldTransforms::encode_vec`], put back on
+/// drop, before the scratch arena holding their replacements goes away.
+struct Restore(Vec<(*mut Bytes, Bytes)>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        for (slot, original) in self.0.drain(..).rev() {
+            unsafe { *slot = original };
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::ProtobufRef;
+    use crate::generated_code_only::Protobuf;
+    use crate::google::protobuf::{FileDescriptorProto, UninterpretedOption};
+
+    #[test]
+    fn field_transforms_roundtrip() {
+        struct Xor(u8);
+        impl FieldTransform for Xor {
+            fn decode(&self, value: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+                if value.first() != Some(&self.0) {
+                    return Err(Error::InvalidProtobufData);
+                }
+                out.extend(value[1..].iter().map(|b| b ^ self.0));
+                Ok(())
+            }
+            fn encode(&self, value: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+                out.push(self.0);
+                out.extend(value.iter().map(|b| b ^ self.0));
+                Ok(())
+            }
+        }
+
+        let mut arena = Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("a.proto", &mut arena).unwrap();
+        let options = file.options_mut(&mut arena);
+        for value in [&b"secret"[..], b"other"] {
+            let option = options.add_uninterpreted_option(&mut arena).unwrap();
+            option.set_string_value(value, &mut arena).unwrap();
+        }
+        let plain = file.encode_vec::<32>().unwrap();
+
+        let xor = Xor(0x5a);
+        let mut transforms = FieldTransforms::new();
+        // Field 1 is a string, 2 doesn't exist
+        for number in [1, 2] {
+            assert!(matches!(
+                transforms.add(UninterpretedOption::ProtoType::table(), number, &xor),
+                Err(Error::MessageNotFound)
+            ));
+        }
+        transforms
+            .add(UninterpretedOption::ProtoType::table(), 7, &xor)
+            .unwrap();
+
+        let encrypted = transforms.encode_vec::<32>(&mut file).unwrap();
+        assert_ne!(encrypted, plain);
+        assert_eq!(encrypted.len(), plain.len() + 2);
+        // The message itself is untouched
+        assert_eq!(file.encode_vec::<32>().unwrap(), plain);
+
+        let mut raw = FileDescriptorProto::ProtoType::default();
+        assert!(raw.decode_flat::<32>(&mut arena, &encrypted));
+        let stored = &raw.options().unwrap().uninterpreted_option()[0];
+        assert_eq!(stored.string_value()[0], 0x5a);
+
+        let mut decrypted = FileDescriptorProto::ProtoType::default();
+        transforms
+            .decode_flat::<32>(&mut decrypted, &mut arena, &encrypted)
+            .unwrap();
+        assert_eq!(decrypted.name(), "a.proto");
+        let values: Vec<_> = decrypted
+            .options()
+            .unwrap()
+            .uninterpreted_option()
+            .iter()
+            .map(|option| option.string_value())
+            .collect();
+        assert_eq!(values, [&b"secret"[..], b"other"]);
+
+        // A failing transform surfaces its error
+        let mut failed = FileDescriptorProto::ProtoType::default();
+        assert!(matches!(
+            transforms.decode_flat::<32>(&mut failed, &mut arena, &plain),
+            Err(Error::InvalidProtobufData)
+        ));
+    }
+}
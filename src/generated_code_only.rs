@@ -1,11 +1,22 @@
 //! Internal types for generated code. **Do not use directly.**
 //!
-//! This module re-exports implementation details that generated code needs access to.
-//! These are not part of the stable public API and may change without notice.
+//! This module re-exports everything generated code needs from the runtime, and
+//! `protocrap-codegen` emits no other runtime paths (apart from the public
+//! `google::protobuf` descriptor types). Names are only added here in
+//! semver-compatible releases, so generated code keeps compiling against later
+//! runtimes when internals move. The items themselves are implementation
+//! details and not meant for hand-written code.
 
 use crate::ProtobufRef;
+
+// Re-export the public types that appear in generated signatures
+pub use crate::arena::Arena;
+pub use crate::containers::{Bytes, ProtoMap, RepeatedField, String};
+pub use crate::{Error, TypedMessage};
+#[cfg(feature = "std")]
+pub use crate::rpc;
 // Re-export table types
-pub use crate::tables::{AuxTableEntry, Table, TableWithEntries};
+pub use crate::tables::{AuxTableEntry, EnumTable, Syntax, TABLE_VERSION, Table, TableWithEntries};
 
 // Re-export codec table entries
 pub use crate::decoding::TableEntry as DecodeTableEntry;
@@ -33,3 +44,71 @@ pub const fn as_object_mut<T: Protobuf>(msg: &mut T) -> &mut crate::base::Object
 pub fn debug_message<T: Protobuf>(msg: &T, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
     core::fmt::Debug::fmt(&msg.as_dyn(), f)
 }
+
+/// A deep copy of `msg` in `arena`, for setters that take borrowed messages.
+pub fn copy_message<T: Protobuf>(
+    msg: &T,
+    arena: &mut Arena,
+) -> Result<TypedMessage<T>, Error<core::alloc::LayoutError>> {
+    let mut copy = TypedMessage::<T>::new_in(arena)?;
+    copy_into(msg, &mut copy, arena)?;
+    Ok(copy)
+}
+
+/// A deep copy of `msg` whose data is in `arena`, behind generated `clone_in`.
+pub fn clone_message<T: Protobuf>(
+    msg: &T,
+    arena: &mut Arena,
+) -> Result<T, Error<core::alloc::LayoutError>> {
+    let mut copy = T::default();
+    copy_into(msg, &mut copy, arena)?;
+    Ok(copy)
+}
+
+/// Decode `bytes` into a new message, behind generated `TryFrom` impls.
+pub fn decode_message<T: Protobuf>(bytes: &[u8], arena: &mut Arena) -> Result<T, Error> {
+    use crate::ProtobufMut;
+
+    const STACK_DEPTH: usize = 64;
+    let mut msg = T::default();
+    if !msg.decode_flat::<STACK_DEPTH>(arena, bytes) {
+        return Err(Error::InvalidProtobufData);
+    }
+    Ok(msg)
+}
+
+/// Copy the fields of `msg` into the empty message `copy`, allocating in
+/// `arena`.
+fn copy_into<T: Protobuf>(
+    msg: &T,
+    copy: &mut T,
+    arena: &mut Arena,
+) -> Result<(), Error<core::alloc::LayoutError>> {
+    const STACK_DEPTH: usize = 64;
+    let table = T::table();
+    table.check_version().map_err(|_| Error::VersionMismatch)?;
+    crate::encoding::copy_object(as_object(msg), table, as_object_mut(copy), arena, STACK_DEPTH)
+}
+
+#[cfg(feature = "serde_support")]
+pub use ::serde;
+
+#[cfg(feature = "serde_support")]
+pub fn serialize_message<T: Protobuf, S: ::serde::Serializer>(
+    msg: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    ::serde::Serialize::serialize(&msg.as_dyn(), serializer)
+}
+
+#[cfg(feature = "serde_support")]
+pub fn deserialize_message<'de, T: Protobuf + 'static, D: ::serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    use ::serde::de::{DeserializeSeed, Error};
+
+    crate::serde::with_scoped_arena(|arena| {
+        crate::serde::SerdeDeserialize::<T>::new(arena).deserialize(deserializer)
+    })
+    .unwrap_or_else(|| Err(D::Error::custom("no arena to deserialize into, see protocrap::serde::with_arena")))
+}
@@ -0,0 +1,533 @@
+//! Streaming conversion of binary payloads to proto JSON.
+//!
+//! [`write_json`] walks the wire bytes with the message's [`Table`] and writes
+//! JSON to an [`io::Write`] as it goes, without decoding into an arena. Memory
+//! use is bounded by the nesting depth rather than the payload, which suits
+//! gateways that log messages they otherwise only pass through.
+//!
+//! Output follows the proto3 JSON mapping as the serde support writes it:
+//! fields under their `json_name` in declaration order, 64-bit integers as
+//! strings, bytes as base64, enums by name, floats as protobuf prints them
+//! (`1`, `0.1`, `1e+21`) and non-finite ones as `"NaN"`, `"Infinity"` and
+//! `"-Infinity"`. What would need the decoded message is
+//! traded away:
+//!
+//! - Map entries are written in wire order, duplicate keys included.
+//! - A singular field written more than once takes its last value; message
+//!   values aren't merged.
+//! - Well-known types such as `Timestamp` and `Any` print as plain messages.
+//! - Unknown fields are dropped.
+
+use std::io::{self, Write};
+
+use crate::Error;
+use crate::google::protobuf::FieldDescriptorProto::{ProtoType as FieldDescriptorProto, Type};
+use crate::proto_json::{ProtoDouble, ProtoFloat};
+use crate::reflection::{is_in_oneof, is_map_entry, is_repeated, lookup_enum_name};
+use crate::tables::Table;
+use crate::wire::{Reader, WireType};
+
+/// Nesting depth beyond which conversion fails, as for text format.
+const MAX_DEPTH: usize = 100;
+
+/// Write `buf`, an encoded message of type `table`, to `out` as JSON.
+///
+/// Output is written while `buf` is read, so on [`Error::InvalidProtobufData`]
+/// or [`Error::MessageTreeTooDeep`] (more than 100 nested messages) `out` may
+/// hold a partial document. Write failures are returned as [`Error::Io`].
+/// Wrap unbuffered writers in an [`io::BufWriter`], as output comes in many
+/// small writes.
+pub fn write_json(table: &Table, buf: &[u8], out: &mut impl Write) -> Result {
+    message(out, table, buf, 0)
+}
+
+type Result<T = ()> = core::result::Result<T, Error<io::Error>>;
+
+/// A field value as read off the wire. Groups hold their contents, without
+/// the END_GROUP tag.
+#[derive(Clone, Copy)]
+enum Wire<'b> {
+    Varint(u64),
+    Fixed64(u64),
+    Fixed32(u32),
+    Bytes(&'b [u8]),
+    Group(&'b [u8]),
+}
+
+impl Wire<'_> {
+    fn wire_type(&self) -> WireType {
+        match self {
+            Wire::Varint(_) => WireType::Varint,
+            Wire::Fixed64(_) => WireType::Fixed64,
+            Wire::Bytes(_) => WireType::LengthDelimited,
+            Wire::Group(_) => WireType::StartGroup,
+            Wire::Fixed32(_) => WireType::Fixed32,
+        }
+    }
+}
+
+fn message(out: &mut impl Write, table: &Table, buf: &[u8], depth: usize) -> Result {
+    if depth > MAX_DEPTH {
+        return Err(Error::MessageTreeTooDeep);
+    }
+    raw(out, "{")?;
+    let mut first = true;
+    for field in table.descriptor().field() {
+        let field = &**field;
+        if is_repeated(field) {
+            if last(table, buf, depth, |f| f.number() == field.number())?.is_none() {
+                continue;
+            }
+            key(out, &mut first, field)?;
+            repeated(out, table, field, buf, depth)?;
+            continue;
+        }
+        // Of the members of a oneof only the one set last counts
+        let found = if is_in_oneof(field) {
+            last(table, buf, depth, |f| {
+                is_in_oneof(f) && f.oneof_index() == field.oneof_index()
+            })?
+            .filter(|&(f, _)| f.number() == field.number())
+        } else {
+            last(table, buf, depth, |f| f.number() == field.number())?
+        };
+        let Some((_, value)) = found else {
+            continue;
+        };
+        key(out, &mut first, field)?;
+        self::value(out, table, table, field, value, depth)?;
+    }
+    raw(out, "}")
+}
+
+/// The last value in `buf` of a field `pred` selects, skipping values whose
+/// wire type the field can't have, as decoding does.
+fn last<'t, 'b>(
+    table: &'t Table,
+    buf: &'b [u8],
+    depth: usize,
+    pred: impl Fn(&FieldDescriptorProto) -> bool,
+) -> Result<Option<(&'t FieldDescriptorProto, Wire<'b>)>> {
+    let mut reader = Reader::new(buf);
+    let mut found = None;
+    while !reader.is_empty() {
+        let (number, value) = read_field(&mut reader, depth)?;
+        let field = table
+            .descriptor()
+            .field()
+            .iter()
+            .find(|f| f.number() == number as i32);
+        if let Some(field) = field
+            && pred(field)
+            && accepts(field, value.wire_type())
+        {
+            found = Some((&**field, value));
+        }
+    }
+    Ok(found)
+}
+
+/// Write the values of repeated `field` as an array, or as an object for
+/// maps.
+fn repeated(
+    out: &mut impl Write,
+    table: &Table,
+    field: &FieldDescriptorProto,
+    buf: &[u8],
+    depth: usize,
+) -> Result {
+    let entry = match field.r#type() {
+        Some(Type::TYPE_MESSAGE) => child_table(table, field).filter(|child| is_map_entry(child.descriptor())),
+        _ => None,
+    };
+    raw(out, if entry.is_some() { "{" } else { "[" })?;
+    let mut first = true;
+    let mut reader = Reader::new(buf);
+    while !reader.is_empty() {
+        let (number, value) = read_field(&mut reader, depth)?;
+        if number != field.number() as u32 || !accepts(field, value.wire_type()) {
+            continue;
+        }
+        if let Some(entry) = entry {
+            let Wire::Bytes(bytes) = value else {
+                return Err(Error::InvalidProtobufData);
+            };
+            comma(out, &mut first)?;
+            map_entry(out, table, entry, bytes, depth + 1)?;
+        } else if let Wire::Bytes(bytes) = value
+            && wire_type_of(field) != WireType::LengthDelimited
+        {
+            let mut packed = Reader::new(bytes);
+            while !packed.is_empty() {
+                let value = read_value(&mut packed, wire_type_of(field))?;
+                comma(out, &mut first)?;
+                self::value(out, table, table, field, value, depth)?;
+            }
+        } else {
+            comma(out, &mut first)?;
+            self::value(out, table, table, field, value, depth)?;
+        }
+    }
+    raw(out, if entry.is_some() { "}" } else { "]" })
+}
+
+/// Write one map entry as `"key": value`. Missing keys and values take
+/// their defaults.
+fn map_entry(out: &mut impl Write, table: &Table, entry: &Table, buf: &[u8], depth: usize) -> Result {
+    if depth > MAX_DEPTH {
+        return Err(Error::MessageTreeTooDeep);
+    }
+    let fields = entry.descriptor().field();
+    let (Some(key_field), Some(value_field)) = (
+        fields.iter().find(|f| f.number() == 1),
+        fields.iter().find(|f| f.number() == 2),
+    ) else {
+        return Err(Error::InvalidProtobufData);
+    };
+    let key = last(entry, buf, depth, |f| f.number() == 1)?.map(|(_, v)| v);
+    let value = last(entry, buf, depth, |f| f.number() == 2)?.map(|(_, v)| v);
+    // JSON object keys are strings, so quote keys not written as one
+    let quote = !matches!(
+        key_field.r#type(),
+        Some(
+            Type::TYPE_STRING
+                | Type::TYPE_INT64
+                | Type::TYPE_UINT64
+                | Type::TYPE_SINT64
+                | Type::TYPE_FIXED64
+                | Type::TYPE_SFIXED64
+        )
+    );
+    if quote {
+        raw(out, "\"")?;
+    }
+    value_or_default(out, entry, entry, key_field, key, depth)?;
+    if quote {
+        raw(out, "\"")?;
+    }
+    raw(out, ":")?;
+    // Enum values are looked up from the map field's message, the entry
+    // having no nested enums
+    value_or_default(out, table, entry, value_field, value, depth)
+}
+
+fn value_or_default(
+    out: &mut impl Write,
+    scope: &Table,
+    table: &Table,
+    field: &FieldDescriptorProto,
+    value: Option<Wire>,
+    depth: usize,
+) -> Result {
+    let value = value.unwrap_or(match wire_type_of(field) {
+        WireType::Fixed64 => Wire::Fixed64(0),
+        WireType::LengthDelimited => Wire::Bytes(&[]),
+        WireType::Fixed32 => Wire::Fixed32(0),
+        _ => Wire::Varint(0),
+    });
+    self::value(out, scope, table, field, value, depth)
+}
+
+/// Write a single value of `field`, a field of `table`'s message. Enum names
+/// are looked up from `scope`'s message.
+fn value(
+    out: &mut impl Write,
+    scope: &Table,
+    table: &Table,
+    field: &FieldDescriptorProto,
+    value: Wire,
+    depth: usize,
+) -> Result {
+    let ty = field.r#type().ok_or(Error::InvalidProtobufData)?;
+    match (ty, value) {
+        (Type::TYPE_MESSAGE | Type::TYPE_GROUP, Wire::Bytes(buf) | Wire::Group(buf)) => {
+            match child_table(table, field) {
+                Some(child) => message(out, child, buf, depth + 1),
+                None => raw(out, "{}"),
+            }
+        }
+        (Type::TYPE_INT32, Wire::Varint(v)) => number(out, v as i32),
+        (Type::TYPE_INT64, Wire::Varint(v)) => quoted(out, v as i64),
+        (Type::TYPE_UINT32, Wire::Varint(v)) => number(out, v as u32),
+        (Type::TYPE_UINT64, Wire::Varint(v)) => quoted(out, v),
+        (Type::TYPE_SINT32, Wire::Varint(v)) => {
+            number(out, crate::wire::zigzag_decode(v as u32 as u64) as i32)
+        }
+        (Type::TYPE_SINT64, Wire::Varint(v)) => quoted(out, crate::wire::zigzag_decode(v)),
+        (Type::TYPE_BOOL, Wire::Varint(v)) => raw(out, if v != 0 { "true" } else { "false" }),
+        (Type::TYPE_ENUM, Wire::Varint(v)) => {
+            let v = v as i32;
+            match lookup_enum_name(scope, field.type_name(), v) {
+                Some(name) => string(out, name),
+                None => number(out, v),
+            }
+        }
+        (Type::TYPE_FIXED32, Wire::Fixed32(v)) => number(out, v),
+        (Type::TYPE_SFIXED32, Wire::Fixed32(v)) => number(out, v as i32),
+        (Type::TYPE_FLOAT, Wire::Fixed32(v)) => {
+            let v = f32::from_bits(v);
+            if v.is_finite() {
+                number(out, ProtoFloat(v))
+            } else {
+                non_finite(out, v as f64)
+            }
+        }
+        (Type::TYPE_FIXED64, Wire::Fixed64(v)) => quoted(out, v),
+        (Type::TYPE_SFIXED64, Wire::Fixed64(v)) => quoted(out, v as i64),
+        (Type::TYPE_DOUBLE, Wire::Fixed64(v)) => {
+            let v = f64::from_bits(v);
+            if v.is_finite() {
+                number(out, ProtoDouble(v))
+            } else {
+                non_finite(out, v)
+            }
+        }
+        (Type::TYPE_STRING, Wire::Bytes(v)) => {
+            string(out, core::str::from_utf8(v).map_err(|_| Error::InvalidProtobufData)?)
+        }
+        (Type::TYPE_BYTES, Wire::Bytes(v)) => {
+            raw(out, "\"")?;
+            {
+                let engine = &base64::engine::general_purpose::STANDARD;
+                let mut encoder = base64::write::EncoderWriter::new(&mut *out, engine);
+                encoder.write_all(v).map_err(Error::Io)?;
+                encoder.finish().map_err(Error::Io)?;
+            }
+            raw(out, "\"")
+        }
+        _ => Err(Error::InvalidProtobufData),
+    }
+}
+
+/// Whether decoding takes a value of wire type `wire_type` for `field`.
+fn accepts(field: &FieldDescriptorProto, wire_type: WireType) -> bool {
+    let expected = wire_type_of(field);
+    wire_type == expected
+        // Message fields with delimited encoding
+        || (wire_type == WireType::StartGroup && field.r#type() == Some(Type::TYPE_MESSAGE))
+        || (wire_type == WireType::LengthDelimited
+            && is_repeated(field)
+            && expected != WireType::LengthDelimited
+            && expected != WireType::StartGroup)
+}
+
+/// The wire type values of `field` are encoded with, unpacked.
+fn wire_type_of(field: &FieldDescriptorProto) -> WireType {
+    match field.r#type() {
+        Some(Type::TYPE_FIXED64 | Type::TYPE_SFIXED64 | Type::TYPE_DOUBLE) => WireType::Fixed64,
+        Some(Type::TYPE_STRING | Type::TYPE_BYTES | Type::TYPE_MESSAGE) => WireType::LengthDelimited,
+        Some(Type::TYPE_GROUP) => WireType::StartGroup,
+        Some(Type::TYPE_FIXED32 | Type::TYPE_SFIXED32 | Type::TYPE_FLOAT) => WireType::Fixed32,
+        _ => WireType::Varint,
+    }
+}
+
+fn child_table<'t>(table: &'t Table, field: &FieldDescriptorProto) -> Option<&'t Table> {
+    let entry = table.field_entry(field.number() as u32)?;
+    Some(table.aux_entry_decode(entry).1)
+}
+
+fn raw(out: &mut impl Write, s: &str) -> Result {
+    out.write_all(s.as_bytes()).map_err(Error::Io)
+}
+
+fn number(out: &mut impl Write, v: impl core::fmt::Display) -> Result {
+    write!(out, "{v}").map_err(Error::Io)
+}
+
+fn quoted(out: &mut impl Write, v: impl core::fmt::Display) -> Result {
+    write!(out, "\"{v}\"").map_err(Error::Io)
+}
+
+fn non_finite(out: &mut impl Write, v: f64) -> Result {
+    raw(
+        out,
+        match v {
+            v if v.is_nan() => "\"NaN\"",
+            v if v > 0.0 => "\"Infinity\"",
+            _ => "\"-Infinity\"",
+        },
+    )
+}
+
+fn comma(out: &mut impl Write, first: &mut bool) -> Result {
+    if !core::mem::take(first) {
+        raw(out, ",")?;
+    }
+    Ok(())
+}
+
+fn key(out: &mut impl Write, first: &mut bool, field: &FieldDescriptorProto) -> Result {
+    comma(out, first)?;
+    string(out, field.json_name())?;
+    raw(out, ":")
+}
+
+/// Write `s` as a JSON string.
+fn string(out: &mut impl Write, s: &str) -> Result {
+    raw(out, "\"")?;
+    let mut rest = s;
+    while let Some(at) = rest.find(|c: char| c == '"' || c == '\\' || c < ' ') {
+        raw(out, &rest[..at])?;
+        let c = rest.as_bytes()[at];
+        match c {
+            b'"' => raw(out, "\\\"")?,
+            b'\\' => raw(out, "\\\\")?,
+            b'\n' => raw(out, "\\n")?,
+            b'\r' => raw(out, "\\r")?,
+            b'\t' => raw(out, "\\t")?,
+            _ => write!(out, "\\u{c:04x}").map_err(Error::Io)?,
+        }
+        rest = &rest[at + 1..];
+    }
+    raw(out, rest)?;
+    raw(out, "\"")
+}
+
+/// Read one field, failing on END_GROUP tags.
+fn read_field<'b>(reader: &mut Reader<'b>, depth: usize) -> Result<(u32, Wire<'b>)> {
+    let (number, wire_type) = reader.read_tag().map_err(Error::with_io)?;
+    let value = match wire_type {
+        WireType::StartGroup => Wire::Group(read_group(reader, number, depth)?),
+        wire_type => read_value(reader, wire_type)?,
+    };
+    Ok((number, value))
+}
+
+/// Read a value of non-group `wire_type`.
+fn read_value<'b>(reader: &mut Reader<'b>, wire_type: WireType) -> Result<Wire<'b>> {
+    let value = match wire_type {
+        WireType::Varint => reader.read_varint().map(Wire::Varint),
+        WireType::Fixed64 => reader.read_fixed64().map(Wire::Fixed64),
+        WireType::LengthDelimited => reader.read_bytes().map(Wire::Bytes),
+        WireType::Fixed32 => reader.read_fixed32().map(Wire::Fixed32),
+        WireType::StartGroup | WireType::EndGroup => Err(Error::InvalidProtobufData),
+    };
+    value.map_err(Error::with_io)
+}
+
+/// The contents of group `number`, consuming its END_GROUP tag.
+fn read_group<'b>(reader: &mut Reader<'b>, number: u32, depth: usize) -> Result<&'b [u8]> {
+    if depth >= MAX_DEPTH {
+        return Err(Error::MessageTreeTooDeep);
+    }
+    let start = reader.remaining();
+    loop {
+        let mut next = reader.clone();
+        if let (end, WireType::EndGroup) = next.read_tag().map_err(Error::with_io)? {
+            if end != number {
+                return Err(Error::InvalidProtobufData);
+            }
+            let contents = &start[..start.len() - reader.remaining().len()];
+            *reader = next;
+            return Ok(contents);
+        }
+        read_field(reader, depth + 1)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProtobufRef;
+    use crate::generated_code_only::Protobuf;
+    use crate::google::protobuf::{FileDescriptorProto, UninterpretedOption};
+    use crate::proto_json::ProtoJsonSerializer;
+    use ::serde::Serialize;
+
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    fn to_json(table: &Table, buf: &[u8]) -> Result<std::string::String> {
+        let mut out = std::vec::Vec::new();
+        write_json(table, buf, &mut out)?;
+        Ok(std::string::String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn matches_serde_output() {
+        let file = FileDescriptorProto::ProtoType::file_descriptor();
+        let buf = file.encode_vec::<32>().unwrap();
+        let mut serializer = serde_json::Serializer::new(std::vec::Vec::new());
+        file.as_dyn()
+            .serialize(ProtoJsonSerializer::new(&mut serializer))
+            .unwrap();
+        let expected = std::string::String::from_utf8(serializer.into_inner()).unwrap();
+        assert_eq!(to_json(FileDescriptorProto::ProtoType::table(), &buf).unwrap(), expected);
+    }
+
+    #[test]
+    fn scalars_and_wire_quirks() {
+        let table = UninterpretedOption::ProtoType::table();
+        let mut buf = std::vec::Vec::new();
+        // identifier_value twice, the last one wins
+        buf.extend_from_slice(b"\x1a\x01x\x1a\x04\"a\n\x01");
+        // negative_int_value -1, double_value NaN, string_value [0xfb, 0xff]
+        buf.extend_from_slice(&[0x28, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        buf.push(0x31);
+        buf.extend_from_slice(&f64::NAN.to_le_bytes());
+        buf.extend_from_slice(&[0x3a, 0x02, 0xfb, 0xff]);
+        // Unknown field and a string_value with the wrong wire type, dropped
+        buf.extend_from_slice(&[0xc0, 0x3e, 0x05, 0x38, 0x01]);
+        assert_eq!(
+            to_json(table, &buf).unwrap(),
+            r#"{"identifierValue":"\"a\n\u0001","negativeIntValue":"-1","doubleValue":"NaN","stringValue":"+/8="}"#
+        );
+
+        // public_dependency unpacked and packed, one array
+        let table = FileDescriptorProto::ProtoType::table();
+        assert_eq!(
+            to_json(table, &[0x50, 0x03, 0x52, 0x02, 0x04, 0x05]).unwrap(),
+            r#"{"publicDependency":[3,4,5]}"#
+        );
+
+        // Truncated, a nested message overrunning its length, unterminated
+        // and unmatched groups
+        for bad in [&buf[..buf.len() - 1], &[0x42, 0x02, 0x0a, 0x05][..], &[0x0b][..], &[0x0c][..]] {
+            assert!(matches!(to_json(table, bad), Err(Error::InvalidProtobufData)));
+        }
+    }
+    #[test]
+    fn maps_keep_wire_order_and_duplicates() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("counts.proto", &mut arena).unwrap();
+        file.set_syntax("proto3", &mut arena).unwrap();
+        let message = file.add_message_type(&mut arena).unwrap();
+        message.set_name("Counts", &mut arena).unwrap();
+        let field = message.add_field(&mut arena).unwrap();
+        field.set_name("counts", &mut arena).unwrap();
+        field.set_json_name("counts", &mut arena).unwrap();
+        field.set_number(1);
+        field.set_label(Label::LABEL_REPEATED);
+        field.set_type(Type::TYPE_MESSAGE);
+        field.set_type_name(".Counts.CountsEntry", &mut arena).unwrap();
+        let entry = message.add_nested_type(&mut arena).unwrap();
+        entry.set_name("CountsEntry", &mut arena).unwrap();
+        entry.options_mut(&mut arena).set_map_entry(true);
+        for (number, name, ty) in [(1, "key", Type::TYPE_INT32), (2, "value", Type::TYPE_INT64)] {
+            let field = entry.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(ty);
+        }
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(&file).unwrap();
+        let table = pool.get_table("Counts").unwrap();
+
+        // counts: {2: 20, 1: 10, 2: 5, 3: <value missing>}
+        let buf = [
+            0x0a, 4, 0x08, 2, 0x10, 20, 0x0a, 4, 0x08, 1, 0x10, 10, 0x0a, 4, 0x08, 2, 0x10, 5, 0x0a,
+            2, 0x08, 3,
+        ];
+        assert_eq!(
+            to_json(table, &buf).unwrap(),
+            r#"{"counts":{"2":"20","1":"10","2":"5","3":"0"}}"#
+        );
+    }
+}
@@ -36,6 +36,13 @@
 //! include!("types.pc.rs");
 //! ```
 //!
+//! Or compile protos from `build.rs` with `protocrap-build` and include
+//! `concat!(env!("OUT_DIR"), "/protos.pc.rs")`.
+//!
+//! Generated code only names runtime items through [`generated_code_only`]
+//! and the public descriptor types, so it keeps compiling across releases of
+//! the same major version.
+//!
 //! ### Encoding Messages
 //!
 //! ```
@@ -76,6 +83,10 @@
 //! let mut decoded = FileDescriptorProto::ProtoType::default();
 //! decoded.decode_flat::<32>(&mut arena, &bytes);
 //! assert_eq!(decoded.name(), "example.proto");
+//!
+//! // `try_decode_flat` reports where malformed input went wrong
+//! let err = decoded.try_decode_flat::<32>(&mut arena, &bytes[..bytes.len() - 1]);
+//! assert_eq!(err.unwrap_err().path(), "name");
 //! ```
 //!
 //! ### Runtime Reflection
@@ -150,6 +161,9 @@
 //!
 //! - `ProtoType`: The message struct with `#[repr(C)]` layout
 //! - Accessor methods following protobuf conventions
+//! - `clone_in(&mut Arena)`: a deep copy with its data in another arena.
+//!   Messages of only singular scalar fields own no arena data and derive
+//!   `Clone` and `Copy` as well
 //!
 //! Field accessors follow this pattern:
 //!
@@ -159,6 +173,7 @@
 //! | String/Bytes | `field() -> &str`/`&[u8]` | `set_field(&str, &mut Arena) -> Result` | `has_field()`, `clear_field()` |
 //! | Message | `field() -> Option<&M>` | `field_mut(&mut Arena) -> Result<&mut M>` | `has_field()`, `clear_field()` |
 //! | Repeated | `field() -> &[T]` | `field_mut() -> &mut RepeatedField<T>` | `add_field(...) -> Result` |
+//! | Oneof | `which_oneof() -> oneof::Which` | `set_oneof(oneof::Which, ...)`, per-field setters | `oneof::Which::None` when unset |
 //!
 //! **Note**: Operations that allocate from the arena return `Result` to handle allocation failures.
 //!
@@ -167,19 +182,33 @@
 //! - [`arena`]: Arena allocator for message data
 //! - [`containers`]: Collection types ([`containers::RepeatedField`], [`containers::String`], [`containers::Bytes`])
 //! - [`reflection`]: Runtime message inspection and dynamic decoding
+//! - [`well_known`]: Well-known types (`Timestamp`, `Duration`, `Any`, `Struct`, ...)
 //! - [`TypedMessage`]: Wrapper for repeated message elements
 //!
 //! ## Feature Flags
 //!
-//! - `std` (default): Enables `std::io` integration, `Vec`-based encoding
+//! - `std` (default): Enables `std::io` integration and the modules built on it
+//!   ([`record_stream`], [`rpc`], [`snapshot`]); implies `alloc`
+//! - `alloc`: Heap conveniences that need no operating system: `Vec`-based
+//!   encoding, [`descriptor_pool`], [`canonical`], map normalization and
+//!   map-aware equality, and field paths in [`DecodeError`]
 //! - `serde_support` (default): Enables serde serialization via reflection
+//! - `json`: `DescriptorPool::json_to_binary` and `binary_to_json` for transcoding
+//!   between proto JSON and binary by type name, through `serde_json`, and
+//!   `serde_json::Value` conversions and JSON path queries of dynamic messages
+//! - `chrono`: Conversions between well-known `Timestamp`/`Duration` and `chrono` types
+//! - `tracing`: Emit `tracing` spans per encode/decode call and events per sub-message and unknown field
+//! - `bytes`: `encode_into_bytes_mut` for encoding into a `bytes::BytesMut`
+//! - `tonic`: [`rpc`] glue for serving and calling generated services through `tonic`
+//! - `mmap`: `decode_from_mmap` for decoding memory-mapped files
 //! - `nightly`: Use nightly Rust features for slightly better codegen (branch hints)
 //!
-//! For `no_std` environments, disable default features:
+//! For `no_std` environments, disable default features, adding `alloc` back
+//! when a global allocator is available:
 //!
 //! ```toml
 //! [dependencies]
-//! protocrap = { version = "0.1", default-features = false }
+//! protocrap = { version = "0.1", default-features = false, features = ["alloc"] }
 //! ```
 //!
 //! ## Restrictions
@@ -201,15 +230,43 @@
 #![cfg_attr(feature = "nightly", feature(likely_unlikely, allocator_api))]
 #![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 pub mod arena;
 pub(crate) mod base;
 pub mod containers;
 pub mod reflection;
+pub mod well_known;
+pub mod wire;
 
 // Re-export user-facing types at crate root
 pub use base::TypedMessage;
-#[cfg(feature = "std")]
+pub use decode_error::{DecodeError, FieldRange};
+pub use decoding::{DecodeOptions, ResumeableDecode, UnknownFieldStats};
+pub use encoding::EncodeOptions;
+pub use tables::{FieldLayout, OneofLayout, Syntax, TableInspector};
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "alloc")]
+pub mod canonical;
+#[cfg(feature = "alloc")]
 pub mod descriptor_pool;
+pub mod events;
+#[cfg(feature = "alloc")]
+pub mod field_transform;
+#[cfg(feature = "std")]
+pub mod record_stream;
+#[cfg(feature = "std")]
+pub mod rpc;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "alloc")]
+pub mod text_format;
+#[cfg(feature = "alloc")]
+pub mod transform;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 #[doc(hidden)]
 #[cfg(feature = "std")]
 pub mod test_utils;
@@ -222,11 +279,12 @@ pub use core::alloc::Allocator;
 
 // Internal modules - only accessible within the crate
 // Types needed by generated code are re-exported via generated_code_only
+pub(crate) mod decode_error;
 pub(crate) mod decoding;
 pub(crate) mod encoding;
 pub(crate) mod tables;
+pub(crate) mod trace;
 pub(crate) mod utils;
-pub(crate) mod wire;
 
 /// Internal types for generated code. **Do not use directly.**
 #[doc(hidden)]
@@ -241,8 +299,11 @@ pub mod serde;
 #[cfg(feature = "serde_support")]
 pub mod proto_json;
 
-#[cfg(feature = "codegen")]
-pub mod codegen;
+#[cfg(feature = "serde_support")]
+pub mod json_stream;
+
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
 
 /// Errors that can occur during protobuf encoding/decoding operations.
 #[derive(Debug)]
@@ -252,13 +313,68 @@ pub enum Error<E = ()> {
     InvalidProtobufData,
     MessageNotFound,
     ArenaAllocationFailed,
+    /// A configured memory limit, like the one of
+    /// [`DescriptorPool::set_max_table_memory`](descriptor_pool::DescriptorPool::set_max_table_memory),
+    /// would be exceeded.
+    MemoryLimitExceeded,
+    /// A generated table was built for another protocrap version than the
+    /// runtime's, see
+    /// [`Table::check_version`](generated_code_only::Table::check_version).
+    VersionMismatch,
+    /// The input has fields its schema doesn't declare, where dropping them
+    /// would lose data, see [`canonical::canonicalize`].
+    UnknownFields,
     UnknownError,
     Io(E),
 }
 
+impl<E> Error<E> {
+    /// Convert the I/O error with `f`, keeping every other variant. Lets
+    /// helpers generic over their source share one error type with callers
+    /// that read from elsewhere.
+    pub fn map_io<F>(self, f: impl FnOnce(E) -> F) -> Error<F> {
+        match self {
+            Error::MessageTreeTooDeep => Error::MessageTreeTooDeep,
+            Error::BufferTooSmall => Error::BufferTooSmall,
+            Error::InvalidProtobufData => Error::InvalidProtobufData,
+            Error::MessageNotFound => Error::MessageNotFound,
+            Error::ArenaAllocationFailed => Error::ArenaAllocationFailed,
+            Error::MemoryLimitExceeded => Error::MemoryLimitExceeded,
+            Error::VersionMismatch => Error::VersionMismatch,
+            Error::UnknownFields => Error::UnknownFields,
+            Error::UnknownError => Error::UnknownError,
+            Error::Io(e) => Error::Io(f(e)),
+        }
+    }
+}
+
+impl Error {
+    /// Widen an error without I/O into one for a source with error type
+    /// `E`, for use with `?` in functions returning `Error<E>`.
+    pub fn with_io<E>(self) -> Error<E> {
+        match self {
+            Error::Io(()) => Error::UnknownError,
+            e => e.map_io(|()| unreachable!()),
+        }
+    }
+}
+
 impl<E: core::fmt::Debug> core::fmt::Display for Error<E> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        core::fmt::Debug::fmt(self, f)
+        match self {
+            Error::MessageTreeTooDeep => f.write_str("message tree too deep"),
+            Error::BufferTooSmall => f.write_str("buffer too small"),
+            Error::InvalidProtobufData => f.write_str("invalid protobuf data"),
+            Error::MessageNotFound => f.write_str("message type not found"),
+            Error::ArenaAllocationFailed => f.write_str("arena allocation failed"),
+            Error::MemoryLimitExceeded => f.write_str("memory limit exceeded"),
+            Error::VersionMismatch => {
+                f.write_str("generated code doesn't match the protocrap runtime version, regenerate it")
+            }
+            Error::UnknownFields => f.write_str("input has fields unknown to the schema"),
+            Error::UnknownError => f.write_str("unknown error"),
+            Error::Io(e) => write!(f, "I/O error: {e:?}"),
+        }
     }
 }
 
@@ -280,12 +396,65 @@ pub trait ProtobufRef<'pool> {
         self.as_dyn().descriptor()
     }
 
+    /// Package-qualified name of the message type.
+    /// See [`reflection::DynamicMessageRef::full_name`].
+    fn full_name(&self) -> &'pool str {
+        self.as_dyn().full_name()
+    }
+
+    /// Hash the canonical encoding without serializing to a buffer.
+    /// See [`reflection::DynamicMessageRef::stable_hash`].
+    fn stable_hash(&self, hasher: &mut impl core::hash::Hasher) -> Result<(), Error> {
+        self.as_dyn().stable_hash(hasher)
+    }
+
+    /// Compare field values with another message of the same type, ignoring
+    /// the order of map entries.
+    /// See [`reflection::DynamicMessageRef::message_eq`].
+    #[cfg(feature = "alloc")]
+    fn message_eq<'other>(&self, other: &impl ProtobufRef<'other>) -> bool {
+        self.as_dyn().message_eq(&other.as_dyn())
+    }
+
     /// Encode to a fixed buffer. Returns the encoded slice or an error.
+    ///
+    /// The encoder works backwards, which lets it write each length prefix
+    /// after the bytes it counts, so the returned slice is the *tail* of
+    /// `buffer`. Use [`encode_flat_to_start`](Self::encode_flat_to_start) to
+    /// get the encoding at the start instead.
     fn encode_flat<'a, const STACK_DEPTH: usize>(
         &self,
         buffer: &'a mut [u8],
     ) -> Result<&'a [u8], Error> {
-        let mut resumeable_encode = encoding::ResumeableEncode::<STACK_DEPTH>::new(self.as_dyn());
+        self.encode_flat_with_options::<STACK_DEPTH>(buffer, EncodeOptions::default())
+    }
+
+    /// Encode to the start of a fixed buffer. Returns the encoded prefix of
+    /// `buffer` or an error.
+    ///
+    /// Computes the size with [`encoded_len`](Self::encoded_len) first and
+    /// encodes into exactly that many leading bytes, so unlike
+    /// [`encode_flat`](Self::encode_flat) no copy is needed to move the result
+    /// into place. Costs one extra walk over the message.
+    fn encode_flat_to_start<'a, const STACK_DEPTH: usize>(
+        &self,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a [u8], Error> {
+        let len = self.encoded_len::<STACK_DEPTH>()?;
+        let buffer = buffer.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+        encode_exact::<STACK_DEPTH>(self, buffer)?;
+        Ok(buffer)
+    }
+
+    /// Like [`encode_flat`](Self::encode_flat), with explicit [`EncodeOptions`].
+    fn encode_flat_with_options<'a, const STACK_DEPTH: usize>(
+        &self,
+        buffer: &'a mut [u8],
+        options: EncodeOptions,
+    ) -> Result<&'a [u8], Error> {
+        trace::enter_span!("encode", message_type = self.full_name());
+        let mut resumeable_encode =
+            encoding::ResumeableEncode::<STACK_DEPTH>::new(self.as_dyn(), options)?;
         let encoding::ResumeResult::Done(buf) = resumeable_encode
             .resume_encode(buffer)
             .ok_or(Error::MessageTreeTooDeep)?
@@ -296,11 +465,29 @@ pub trait ProtobufRef<'pool> {
     }
 
     /// Encode to a new Vec, growing as needed.
-    #[cfg(feature = "std")]
-    fn encode_vec<const STACK_DEPTH: usize>(&self) -> Result<Vec<u8>, Error> {
-        let mut buffer = vec![0u8; 1024];
-        let mut stack = Vec::new();
-        let mut resumeable_encode = encoding::ResumeableEncode::<STACK_DEPTH>::new(self.as_dyn());
+    #[cfg(feature = "alloc")]
+    fn encode_vec<const STACK_DEPTH: usize>(&self) -> Result<alloc::vec::Vec<u8>, Error> {
+        self.encode_vec_with_options::<STACK_DEPTH>(EncodeOptions::default())
+    }
+
+    /// Encode to a new Vec with a stack depth of 64, the counterpart of the
+    /// generated `TryFrom<(&[u8], &mut Arena)>` impls.
+    #[cfg(feature = "alloc")]
+    fn try_into_vec(&self) -> Result<alloc::vec::Vec<u8>, Error> {
+        self.encode_vec::<64>()
+    }
+
+    /// Like [`encode_vec`](Self::encode_vec), with explicit [`EncodeOptions`].
+    #[cfg(feature = "alloc")]
+    fn encode_vec_with_options<const STACK_DEPTH: usize>(
+        &self,
+        options: EncodeOptions,
+    ) -> Result<alloc::vec::Vec<u8>, Error> {
+        trace::enter_span!("encode", message_type = self.full_name());
+        let mut buffer = alloc::vec![0u8; 1024];
+        let mut stack = alloc::vec::Vec::new();
+        let mut resumeable_encode =
+            encoding::ResumeableEncode::<STACK_DEPTH>::new(self.as_dyn(), options)?;
         loop {
             match resumeable_encode
                 .resume_encode(&mut buffer)
@@ -317,7 +504,7 @@ pub trait ProtobufRef<'pool> {
                 encoding::ResumeResult::NeedsMoreBuffer => {
                     let len = buffer.len().min(1024 * 1024);
                     stack.push(core::mem::take(&mut buffer));
-                    buffer = vec![0u8; len * 2];
+                    buffer = alloc::vec![0u8; len * 2];
                 }
             };
         }
@@ -326,6 +513,73 @@ pub trait ProtobufRef<'pool> {
         }
         Ok(buffer)
     }
+
+    /// Exact number of bytes [`encode_flat`](Self::encode_flat) writes for
+    /// this message.
+    ///
+    /// Walks the message tree without encoding it. Fails with
+    /// [`Error::MessageTreeTooDeep`] if the tree nests deeper than
+    /// `STACK_DEPTH`.
+    fn encoded_len<const STACK_DEPTH: usize>(&self) -> Result<usize, Error> {
+        let msg = self.as_dyn();
+        encoding::encoded_len(msg.object, msg.table, STACK_DEPTH).ok_or(Error::MessageTreeTooDeep)
+    }
+
+    /// Append the encoding to `buf`, growing it exactly once.
+    ///
+    /// Unlike [`encode_vec`](Self::encode_vec), the size is computed up front
+    /// with [`encoded_len`](Self::encoded_len), so `buf` never over-allocates
+    /// and whatever it already holds, such as a frame header, stays in front.
+    #[cfg(feature = "alloc")]
+    fn encode_into_vec<const STACK_DEPTH: usize>(
+        &self,
+        buf: &mut alloc::vec::Vec<u8>,
+    ) -> Result<(), Error> {
+        let len = self.encoded_len::<STACK_DEPTH>()?;
+        let start = buf.len();
+        buf.reserve_exact(len);
+        buf.resize(start + len, 0);
+        let result = encode_exact::<STACK_DEPTH>(self, &mut buf[start..]);
+        if result.is_err() {
+            buf.truncate(start);
+        }
+        result
+    }
+
+    /// Like [`encode_into_vec`](Self::encode_into_vec), appending to a
+    /// [`bytes::BytesMut`].
+    #[cfg(feature = "bytes")]
+    fn encode_into_bytes_mut<const STACK_DEPTH: usize>(
+        &self,
+        buf: &mut bytes::BytesMut,
+    ) -> Result<(), Error> {
+        let len = self.encoded_len::<STACK_DEPTH>()?;
+        let start = buf.len();
+        buf.reserve(len);
+        buf.resize(start + len, 0);
+        let result = encode_exact::<STACK_DEPTH>(self, &mut buf[start..]);
+        if result.is_err() {
+            buf.truncate(start);
+        }
+        result
+    }
+}
+
+/// Encode `msg` into `buffer`, sized by [`ProtobufRef::encoded_len`].
+fn encode_exact<'pool, const STACK_DEPTH: usize>(
+    msg: &(impl ProtobufRef<'pool> + ?Sized),
+    buffer: &mut [u8],
+) -> Result<(), Error> {
+    if buffer.is_empty() {
+        return Ok(());
+    }
+    // Encoding fills the buffer from its end, so an exact fit leaves nothing to move
+    let encoded = msg.encode_flat::<STACK_DEPTH>(buffer)?.len();
+    debug_assert_eq!(encoded, buffer.len(), "encoded_len disagrees with the encoder");
+    if encoded != buffer.len() {
+        return Err(Error::UnknownError);
+    }
+    Ok(())
 }
 
 /// Mutable protobuf operations (decode, deserialize).
@@ -340,17 +594,88 @@ pub trait ProtobufMut<'pool>: ProtobufRef<'pool> {
         arena: &mut crate::arena::Arena,
         buf: &[u8],
     ) -> bool {
+        self.decode_flat_with_options::<STACK_DEPTH>(arena, buf, DecodeOptions::default())
+    }
+
+    /// Like [`decode_flat`](Self::decode_flat), with explicit [`DecodeOptions`].
+    #[must_use]
+    fn decode_flat_with_options<const STACK_DEPTH: usize>(
+        &mut self,
+        arena: &mut crate::arena::Arena,
+        buf: &[u8],
+        options: DecodeOptions,
+    ) -> bool {
+        self.decode_flat_with_stats::<STACK_DEPTH>(arena, buf, options).is_some()
+    }
+
+    /// Like [`decode_flat_with_options`](Self::decode_flat_with_options),
+    /// returning on success the unknown fields the input had, see
+    /// [`UnknownFieldStats`].
+    #[must_use]
+    fn decode_flat_with_stats<const STACK_DEPTH: usize>(
+        &mut self,
+        arena: &mut crate::arena::Arena,
+        buf: &[u8],
+        options: DecodeOptions,
+    ) -> Option<UnknownFieldStats> {
+        trace::enter_span!("decode", message_type = self.full_name());
         let mut decoder =
-            decoding::ResumeableDecode::<STACK_DEPTH>::new(self.as_dyn_mut(), isize::MAX);
-        if !decoder.resume(buf, arena) {
+            decoding::ResumeableDecode::<STACK_DEPTH>::new(self.as_dyn_mut(), isize::MAX, options);
+        let stats = if decoder.resume(buf, arena) {
+            decoder.finish_with_stats(arena)
+        } else {
+            None
+        };
+        if stats.is_none() {
             self.as_dyn_mut().clear();
-            return false;
         }
-        if !decoder.finish(arena) {
+        stats
+    }
+
+    /// Decode from a byte slice, reporting where malformed input failed.
+    ///
+    /// Equivalent to [`decode_flat`](Self::decode_flat), but on failure the
+    /// decoder's state tells the offending field's byte offset and path.
+    fn try_decode_flat<const STACK_DEPTH: usize>(
+        &mut self,
+        arena: &mut crate::arena::Arena,
+        buf: &[u8],
+    ) -> Result<(), DecodeError> {
+        trace::enter_span!("decode", message_type = self.full_name());
+        let result = {
+            let mut decoder = decoding::ResumeableDecode::<STACK_DEPTH>::new(
+                self.as_dyn_mut(),
+                isize::MAX,
+                DecodeOptions::default(),
+            );
+            if decoder.resume(buf, arena) {
+                decoder.try_finish(arena).map(|_| ())
+            } else {
+                Err(decoder.error())
+            }
+        };
+        if result.is_err() {
             self.as_dyn_mut().clear();
-            return false;
         }
-        true
+        result
+    }
+
+    /// Like [`try_decode_flat`](Self::try_decode_flat), also returning where
+    /// the top-level fields numbered in `field_numbers` sit in `buf`.
+    ///
+    /// The ranges let callers verify signatures over the original bytes of a
+    /// sub-message, or copy untouched fields into a new encoding without
+    /// re-encoding them. Finding them takes a second pass over `buf` after
+    /// decoding, so plain decodes pay nothing for this.
+    #[cfg(feature = "alloc")]
+    fn try_decode_flat_with_ranges<const STACK_DEPTH: usize>(
+        &mut self,
+        arena: &mut crate::arena::Arena,
+        buf: &[u8],
+        field_numbers: &[u32],
+    ) -> Result<alloc::vec::Vec<FieldRange>, DecodeError> {
+        self.try_decode_flat::<STACK_DEPTH>(arena, buf)?;
+        decode_error::field_ranges(buf, field_numbers, STACK_DEPTH)
     }
 
     fn decode<'a, E>(
@@ -358,7 +683,19 @@ pub trait ProtobufMut<'pool>: ProtobufRef<'pool> {
         arena: &mut crate::arena::Arena,
         provider: &'a mut impl FnMut() -> Result<Option<&'a [u8]>, E>,
     ) -> Result<(), Error<E>> {
-        let mut decoder = decoding::ResumeableDecode::<32>::new(self.as_dyn_mut(), isize::MAX);
+        self.decode_with_options(arena, provider, DecodeOptions::default())
+    }
+
+    /// Like [`decode`](Self::decode), with explicit [`DecodeOptions`].
+    fn decode_with_options<'a, E>(
+        &mut self,
+        arena: &mut crate::arena::Arena,
+        provider: &'a mut impl FnMut() -> Result<Option<&'a [u8]>, E>,
+        options: DecodeOptions,
+    ) -> Result<(), Error<E>> {
+        trace::enter_span!("decode", message_type = self.full_name());
+        let mut decoder =
+            decoding::ResumeableDecode::<32>::new(self.as_dyn_mut(), isize::MAX, options);
         loop {
             let Some(buffer) = provider().map_err(Error::Io)? else {
                 break;
@@ -375,31 +712,42 @@ pub trait ProtobufMut<'pool>: ProtobufRef<'pool> {
         Ok(())
     }
 
-    fn async_decode<'a, E, F>(
+    /// Decode from an async `provider` of buffers. The returned future
+    /// borrows `arena` only until it completes, so one arena can serve any
+    /// number of sequential decodes.
+    fn async_decode<'a, 'arena, 'alloc, E, F>(
         &'a mut self,
-        arena: &mut crate::arena::Arena,
+        arena: &'arena mut crate::arena::Arena<'alloc>,
         provider: &'a mut impl FnMut() -> F,
     ) -> impl core::future::Future<Output = Result<(), Error<E>>>
     where
         F: core::future::Future<Output = Result<Option<&'a [u8]>, E>> + 'a,
     {
-        async move {
-            let mut decoder = decoding::ResumeableDecode::<32>::new(self.as_dyn_mut(), isize::MAX);
-            loop {
-                let Some(buffer) = provider().await.map_err(Error::Io)? else {
-                    break;
-                };
-                if !decoder.resume(buffer, arena) {
+        trace::instrument!(
+            async move {
+                let mut decoder = decoding::ResumeableDecode::<32>::new(
+                    self.as_dyn_mut(),
+                    isize::MAX,
+                    DecodeOptions::default(),
+                );
+                loop {
+                    let Some(buffer) = provider().await.map_err(Error::Io)? else {
+                        break;
+                    };
+                    if !decoder.resume(buffer, arena) {
+                        self.as_dyn_mut().clear();
+                        return Err(Error::InvalidProtobufData);
+                    }
+                }
+                if !decoder.finish(arena) {
                     self.as_dyn_mut().clear();
                     return Err(Error::InvalidProtobufData);
                 }
-            }
-            if !decoder.finish(arena) {
-                self.as_dyn_mut().clear();
-                return Err(Error::InvalidProtobufData);
-            }
-            Ok(())
-        }
+                Ok(())
+            },
+            "decode",
+            message_type = self.full_name()
+        )
     }
 
     #[cfg(feature = "std")]
@@ -408,8 +756,12 @@ pub trait ProtobufMut<'pool>: ProtobufRef<'pool> {
         arena: &mut crate::arena::Arena,
         reader: &mut impl std::io::BufRead,
     ) -> Result<(), Error<std::io::Error>> {
-        let mut decoder =
-            decoding::ResumeableDecode::<STACK_DEPTH>::new(self.as_dyn_mut(), isize::MAX);
+        trace::enter_span!("decode", message_type = self.full_name());
+        let mut decoder = decoding::ResumeableDecode::<STACK_DEPTH>::new(
+            self.as_dyn_mut(),
+            isize::MAX,
+            DecodeOptions::default(),
+        );
         loop {
             let buffer = reader.fill_buf().map_err(Error::Io)?;
             let len = buffer.len();
@@ -429,6 +781,37 @@ pub trait ProtobufMut<'pool>: ProtobufRef<'pool> {
         Ok(())
     }
 
+    /// Decode all remaining bytes of `buf`, chunk by chunk, so chained
+    /// buffers (e.g. `BytesMut` segments from hyper or tonic) aren't copied
+    /// into a contiguous buffer first. `buf` is advanced past the input.
+    #[cfg(feature = "bytes")]
+    fn decode_from_buf<const STACK_DEPTH: usize>(
+        &mut self,
+        arena: &mut crate::arena::Arena,
+        buf: &mut impl bytes::Buf,
+    ) -> Result<(), Error> {
+        trace::enter_span!("decode", message_type = self.full_name());
+        let mut decoder = decoding::ResumeableDecode::<STACK_DEPTH>::new(
+            self.as_dyn_mut(),
+            isize::MAX,
+            DecodeOptions::default(),
+        );
+        while buf.has_remaining() {
+            let chunk = buf.chunk();
+            let len = chunk.len();
+            if !decoder.resume(chunk, arena) {
+                self.as_dyn_mut().clear();
+                return Err(Error::InvalidProtobufData);
+            }
+            buf.advance(len);
+        }
+        if !decoder.finish(arena) {
+            self.as_dyn_mut().clear();
+            return Err(Error::InvalidProtobufData);
+        }
+        Ok(())
+    }
+
     #[cfg(feature = "std")]
     fn decode_from_read<const STACK_DEPTH: usize>(
         &mut self,
@@ -439,41 +822,72 @@ pub trait ProtobufMut<'pool>: ProtobufRef<'pool> {
         self.decode_from_bufread::<STACK_DEPTH>(arena, &mut buf_reader)
     }
 
+    /// Decode the file at `path` by mapping it into memory instead of
+    /// reading it, so a multi-GB file isn't held in RAM twice: the input is
+    /// paged in from the file as the decoder walks it, and only the decoded
+    /// message takes arena memory.
+    ///
+    /// The file must not be modified or truncated while it is decoded, which
+    /// can fault the process on some platforms.
+    #[cfg(feature = "mmap")]
+    fn decode_from_mmap<const STACK_DEPTH: usize>(
+        &mut self,
+        arena: &mut crate::arena::Arena,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), Error<std::io::Error>> {
+        let file = std::fs::File::open(path).map_err(Error::Io)?;
+        // SAFETY: the mapping is only read during this call, decoded strings
+        // and bytes are copied into the arena. Concurrent writes to the file
+        // are excluded by the caller.
+        let map = unsafe { memmap2::Mmap::map(&file) }.map_err(Error::Io)?;
+        if !self.decode_flat::<STACK_DEPTH>(arena, &map) {
+            return Err(Error::InvalidProtobufData);
+        }
+        Ok(())
+    }
+
     #[cfg(feature = "std")]
-    fn decode_from_async_bufread<'a, const STACK_DEPTH: usize>(
-        &'a mut self,
-        arena: &'a mut crate::arena::Arena<'a>,
+    fn decode_from_async_bufread<'msg, 'arena, 'alloc, const STACK_DEPTH: usize>(
+        &'msg mut self,
+        arena: &'arena mut crate::arena::Arena<'alloc>,
         reader: &mut (impl futures::io::AsyncBufRead + Unpin),
     ) -> impl core::future::Future<Output = Result<(), Error<futures::io::Error>>> {
         use futures::io::AsyncBufReadExt;
 
-        async move {
-            let mut decoder =
-                decoding::ResumeableDecode::<STACK_DEPTH>::new(self.as_dyn_mut(), isize::MAX);
-            loop {
-                let buffer = reader.fill_buf().await.map_err(Error::Io)?;
-                let len = buffer.len();
-                if len == 0 {
-                    break;
+        trace::instrument!(
+            async move {
+                let mut decoder = decoding::ResumeableDecode::<STACK_DEPTH>::new(
+                    self.as_dyn_mut(),
+                    isize::MAX,
+                    DecodeOptions::default(),
+                );
+                loop {
+                    let buffer = reader.fill_buf().await.map_err(Error::Io)?;
+                    let len = buffer.len();
+                    if len == 0 {
+                        break;
+                    }
+                    if !decoder.resume(buffer, arena) {
+                        self.as_dyn_mut().clear();
+                        return Err(Error::InvalidProtobufData);
+                    }
+                    reader.consume_unpin(len);
                 }
-                if !decoder.resume(buffer, arena) {
+                if !decoder.finish(arena) {
                     self.as_dyn_mut().clear();
                     return Err(Error::InvalidProtobufData);
                 }
-                reader.consume_unpin(len);
-            }
-            if !decoder.finish(arena) {
-                self.as_dyn_mut().clear();
-                return Err(Error::InvalidProtobufData);
-            }
-            Ok(())
-        }
+                Ok(())
+            },
+            "decode",
+            message_type = self.full_name()
+        )
     }
 
     #[cfg(feature = "std")]
-    fn decode_from_async_read<'a, const STACK_DEPTH: usize>(
-        &'a mut self,
-        arena: &'a mut crate::arena::Arena<'a>,
+    fn decode_from_async_read<'msg, 'arena, 'alloc, const STACK_DEPTH: usize>(
+        &'msg mut self,
+        arena: &'arena mut crate::arena::Arena<'alloc>,
         reader: &mut (impl futures::io::AsyncRead + Unpin),
     ) -> impl core::future::Future<Output = Result<(), Error<futures::io::Error>>> {
         async move {
@@ -483,6 +897,13 @@ pub trait ProtobufMut<'pool>: ProtobufRef<'pool> {
         }
     }
 
+    /// Deduplicate map fields, keeping the last entry for each key.
+    /// See [`reflection::DynamicMessage::normalize_maps`].
+    #[cfg(feature = "alloc")]
+    fn normalize_maps(&mut self) {
+        self.as_dyn_mut().normalize_maps()
+    }
+
     #[cfg(feature = "serde_support")]
     fn serde_deserialize<'arena, 'alloc, 'de, D>(
         &'de mut self,
@@ -494,6 +915,21 @@ pub trait ProtobufMut<'pool>: ProtobufRef<'pool> {
     {
         serde::serde_deserialize_struct(self.as_dyn_mut(), arena, deserializer)
     }
+
+    /// Like [`serde_deserialize`](Self::serde_deserialize), with explicit
+    /// [`JsonParseOptions`](serde::JsonParseOptions).
+    #[cfg(feature = "serde_support")]
+    fn serde_deserialize_with_options<'arena, 'alloc, 'de, D>(
+        &'de mut self,
+        arena: &'arena mut crate::arena::Arena<'alloc>,
+        deserializer: D,
+        options: serde::JsonParseOptions,
+    ) -> Result<(), D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        serde::serde_deserialize_struct_with_options(self.as_dyn_mut(), arena, deserializer, options)
+    }
 }
 
 // Blanket impl for static protobuf types
@@ -517,6 +953,7 @@ impl<T: generated_code_only::Protobuf> ProtobufMut<'static> for T {
 
 #[cfg(all(test, feature = "std"))]
 mod tests {
+    use crate::EncodeOptions;
     use crate::ProtobufMut;
     use crate::ProtobufRef;
 
@@ -540,6 +977,24 @@ mod tests {
         assert_eq!(nested_descriptor.name(), "ExtensionRange");
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn generated_tables_are_read_only() {
+        let address = &crate::google::protobuf::FileDescriptorProto::TABLE as *const _ as usize;
+        let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+        let permissions = maps
+            .lines()
+            .find_map(|line| {
+                let (range, rest) = line.split_once(' ')?;
+                let (start, end) = range.split_once('-')?;
+                let start = usize::from_str_radix(start, 16).ok()?;
+                let end = usize::from_str_radix(end, 16).ok()?;
+                (start..end).contains(&address).then(|| &rest[..4])
+            })
+            .unwrap();
+        assert!(!permissions.contains('w'), "table mapped {permissions}");
+    }
+
     #[test]
     fn file_descriptor_roundtrip() {
         crate::test_utils::assert_roundtrip(
@@ -600,6 +1055,70 @@ mod tests {
         );
     }
 
+    #[test]
+    fn canonical_field_order() {
+        /// Top-level fields as (field number, length-delimited payload) pairs.
+        fn fields(bytes: &[u8]) -> Vec<(u64, &[u8])> {
+            use crate::wire::{Reader, WireType};
+
+            let mut reader = Reader::new(bytes);
+            let mut fields = Vec::new();
+            while !reader.is_empty() {
+                let (number, wire_type) = reader.read_tag().unwrap();
+                let payload = match wire_type {
+                    WireType::LengthDelimited => reader.read_bytes().unwrap(),
+                    WireType::StartGroup | WireType::EndGroup => panic!("unexpected wire type {wire_type:?}"),
+                    _ => {
+                        reader.skip(number, wire_type).unwrap();
+                        &[][..]
+                    }
+                };
+                fields.push((u64::from(number), payload));
+            }
+            fields
+        }
+        fn is_sorted(fields: &[(u64, &[u8])]) -> bool {
+            fields.is_sorted_by_key(|&(number, _)| number)
+        }
+
+        // DescriptorProto declares `extension` (6) before `nested_type` (3), and
+        // FieldDescriptorProto declares `extendee` (2) after `type_name` (6)
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut descriptor = crate::google::protobuf::DescriptorProto::ProtoType::default();
+        descriptor.set_name("Outer", &mut arena).unwrap();
+        descriptor
+            .add_nested_type(&mut arena)
+            .unwrap()
+            .set_name("Inner", &mut arena)
+            .unwrap();
+        let extension = descriptor.add_extension(&mut arena).unwrap();
+        extension.set_number(100);
+        extension.set_type_name(".Inner", &mut arena).unwrap();
+        extension.set_extendee(".Outer", &mut arena).unwrap();
+        let options = EncodeOptions {
+            canonical_field_order: true,
+        };
+        let declared = descriptor.encode_vec::<32>().unwrap();
+        assert!(!is_sorted(&fields(&declared)));
+
+        let canonical = descriptor.encode_vec_with_options::<32>(options).unwrap();
+        let top_level = fields(&canonical);
+        assert!(is_sorted(&top_level));
+        let (_, extension) = top_level.iter().find(|&&(number, _)| number == 6).unwrap();
+        assert!(is_sorted(&fields(extension)));
+
+        let mut buffer = vec![0u8; canonical.len()];
+        let flat = descriptor
+            .encode_flat_with_options::<32>(&mut buffer, options)
+            .unwrap();
+        assert_eq!(flat, canonical.as_slice());
+
+        // Only the order changes, not the content
+        let mut decoded = crate::google::protobuf::DescriptorProto::ProtoType::default();
+        assert!(decoded.decode_flat::<32>(&mut arena, &canonical));
+        assert_eq!(decoded.encode_vec::<32>().unwrap(), declared);
+    }
+
     #[test]
     fn dynamic_file_descriptor_roundtrip() {
         let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
@@ -638,4 +1157,1820 @@ mod tests {
             "decoding invalid UTF-8 in string field should fail"
         );
     }
+    #[test]
+    fn decode_limits() {
+        use crate::DecodeOptions;
+        use crate::google::protobuf::FileDescriptorProto;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let decode = |arena: &mut crate::arena::Arena, bytes: &[u8], options| {
+            let mut msg = FileDescriptorProto::ProtoType::default();
+            msg.decode_flat_with_options::<32>(arena, bytes, options)
+        };
+
+        // name: "abcdef"
+        let name = [0x0a, 0x06, b'a', b'b', b'c', b'd', b'e', b'f'];
+        let options = DecodeOptions {
+            max_string_len: 5,
+            ..Default::default()
+        };
+        assert!(!decode(&mut arena, &name, options));
+        let options = DecodeOptions {
+            max_message_bytes: 7,
+            ..Default::default()
+        };
+        assert!(!decode(&mut arena, &name, options));
+        assert!(decode(&mut arena, &name, DecodeOptions::default()));
+
+        // dependency (3): "a" three times, and public_dependency (10): packed [1, 2, 3]
+        let dependency = [0x1a, 0x01, b'a', 0x1a, 0x01, b'a', 0x1a, 0x01, b'a'];
+        let public_dependency = [0x52, 0x03, 0x01, 0x02, 0x03];
+        let options = DecodeOptions {
+            max_repeated_elements: 2,
+            ..Default::default()
+        };
+        assert!(!decode(&mut arena, &dependency, options));
+        assert!(!decode(&mut arena, &public_dependency, options));
+        let options = DecodeOptions {
+            max_repeated_elements: 3,
+            ..Default::default()
+        };
+        assert!(decode(&mut arena, &dependency, options));
+        assert!(decode(&mut arena, &public_dependency, options));
+    }
+
+    #[test]
+    fn decode_with_field_ranges() {
+        use crate::google::protobuf::DescriptorProto;
+
+        // name: "M", field { name: "f" }, unknown group 100 { 1: 1 }, field {}
+        let bytes = [
+            0x0a, 0x01, b'M', //
+            0x12, 0x03, 0x0a, 0x01, b'f', //
+            0xa3, 0x06, 0x08, 0x01, 0xa4, 0x06, //
+            0x12, 0x00,
+        ];
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut msg = DescriptorProto::ProtoType::default();
+        let ranges = msg
+            .try_decode_flat_with_ranges::<32>(&mut arena, &bytes, &[2, 100])
+            .unwrap();
+        assert_eq!(msg.field().len(), 2);
+        let spans: std::vec::Vec<_> = ranges
+            .iter()
+            .map(|r| (r.field_number, r.field.clone(), r.value.clone()))
+            .collect();
+        assert_eq!(
+            spans,
+            [(2, 3..8, 5..8), (100, 8..14, 10..12), (2, 14..16, 16..16)]
+        );
+        // The value of a sub-message field is its encoding
+        let field = &msg.field()[0];
+        assert_eq!(field.encode_vec::<32>().unwrap(), &bytes[ranges[0].value.clone()]);
+
+        assert!(
+            msg.try_decode_flat_with_ranges::<32>(&mut arena, &bytes[..4], &[2])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn take_and_set_child() {
+        use crate::google::protobuf::FileDescriptorProto;
+
+        // name: "a", options { java_package: "p" }
+        let bytes = [0x0a, 0x01, b'a', 0x42, 0x03, 0x0a, 0x01, b'p'];
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut other_arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        let mut foreign = [(); 2].map(|_| FileDescriptorProto::ProtoType::default());
+        assert!(file.decode_flat::<32>(&mut arena, &bytes));
+        for msg in &mut foreign {
+            assert!(msg.decode_flat::<32>(&mut other_arena, &bytes));
+        }
+        let mut other = FileDescriptorProto::ProtoType::default();
+
+        let mut file_dyn = file.as_dyn_mut();
+        let options = file_dyn.take_child("options").unwrap();
+        assert_eq!(options.as_ref().get_str("java_package"), Some("p"));
+        assert!(file_dyn.take_child("options").is_none());
+        assert!(file_dyn.take_child("name").is_none());
+
+        let mut other_dyn = other.as_dyn_mut();
+        let [first, second] = &mut foreign;
+        let wrong_type = first.as_dyn_mut().take_child("options").unwrap();
+        assert!(matches!(
+            other_dyn.set_child("source_code_info", wrong_type, &arena),
+            Err(crate::Error::MessageNotFound)
+        ));
+        let wrong_arena = second.as_dyn_mut().take_child("options").unwrap();
+        assert!(matches!(
+            other_dyn.set_child("options", wrong_arena, &arena),
+            Err(crate::Error::InvalidProtobufData)
+        ));
+        other_dyn.set_child("options", options, &arena).unwrap();
+
+        assert_eq!(file.encode_vec::<32>().unwrap(), [0x0a, 0x01, b'a']);
+        assert_eq!(other.options().unwrap().java_package(), "p");
+        assert!(foreign[0].options().is_none());
+    }
+
+    #[test]
+    fn has_bits_bulk_operations() {
+        use crate::google::protobuf::FileDescriptorProto;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        assert!(!file.as_dyn().has_bits().any());
+
+        file.set_syntax("proto3", &mut arena).unwrap();
+        file.set_name("a.proto", &mut arena).unwrap();
+        file.public_dependency_mut().push(0, &mut arena).unwrap();
+        let options = file.options_mut(&mut arena);
+        options.set_java_package("p", &mut arena).unwrap();
+        let has_bits = file.as_dyn().has_bits();
+        assert!(has_bits.any());
+        // Repeated and message fields carry no has-bit
+        assert_eq!(has_bits.count_set(), 2);
+        let set: std::vec::Vec<_> = has_bits.iter().map(|field| field.name()).collect();
+        assert_eq!(set, ["name", "syntax"]);
+
+        file.as_dyn_mut().clear_has_bits();
+        assert_eq!(file.as_dyn().has_bits().count_set(), 0);
+        assert!(!file.has_name());
+        assert_eq!(file.public_dependency(), [0]);
+        assert_eq!(file.options().unwrap().java_package(), "p");
+    }
+
+    #[test]
+    fn encode_into_vec_reserves_exactly() {
+        use crate::google::protobuf::{FileDescriptorProto, SourceCodeInfo};
+
+        let file = FileDescriptorProto::ProtoType::file_descriptor();
+        let mut arena = crate::arena::Arena::new(&Global);
+        // Packed path with a negative element, sign extended to 10 bytes
+        let mut info = SourceCodeInfo::ProtoType::default();
+        let location = info.add_location(&mut arena).unwrap();
+        for v in [1, -1, 300] {
+            location.path_mut().push(v, &mut arena).unwrap();
+        }
+        location.set_leading_comments("comment", &mut arena).unwrap();
+
+        type Encode<'a> = &'a dyn Fn(&mut std::vec::Vec<u8>) -> Result<(), crate::Error>;
+        let messages: [Encode; 3] = [
+            &|buf| file.encode_into_vec::<64>(buf),
+            &|buf| info.encode_into_vec::<64>(buf),
+            &|buf| SourceCodeInfo::ProtoType::default().encode_into_vec::<64>(buf),
+        ];
+        let expected = [
+            file.encode_vec::<64>().unwrap(),
+            info.encode_vec::<64>().unwrap(),
+            std::vec::Vec::new(),
+        ];
+        for (encode, expected) in messages.iter().zip(&expected) {
+            let mut buf = std::vec![0xff, 0xfe];
+            encode(&mut buf).unwrap();
+            assert_eq!(buf.capacity(), 2 + expected.len());
+            assert_eq!(&buf[..2], [0xff, 0xfe]);
+            assert_eq!(&buf[2..], &expected[..]);
+        }
+        assert_eq!(info.encoded_len::<64>().unwrap(), expected[1].len());
+        assert!(matches!(
+            file.encoded_len::<1>(),
+            Err(crate::Error::MessageTreeTooDeep)
+        ));
+        let mut buf = std::vec![1];
+        assert!(file.encode_into_vec::<1>(&mut buf).is_err());
+        assert_eq!(buf, [1]);
+
+        #[cfg(feature = "bytes")]
+        {
+            let mut buf = bytes::BytesMut::from(&b"hdr"[..]);
+            info.encode_into_bytes_mut::<64>(&mut buf).unwrap();
+            assert_eq!(&buf[..3], b"hdr");
+            assert_eq!(&buf[3..], &expected[1][..]);
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn decode_from_chained_buf() {
+        use crate::google::protobuf::FileDescriptorProto;
+        use bytes::Buf;
+
+        let file = FileDescriptorProto::ProtoType::file_descriptor();
+        let expected = file.encode_vec::<32>().unwrap();
+        let mut arena = crate::arena::Arena::new(&Global);
+        for split in [1, 15, 16, 17, 1000, expected.len() - 1] {
+            let (first, second) = expected.split_at(split);
+            let mut chain = first.chain(bytes::Bytes::copy_from_slice(second));
+            let mut msg = FileDescriptorProto::ProtoType::default();
+            msg.decode_from_buf::<32>(&mut arena, &mut chain).unwrap();
+            assert!(!chain.has_remaining());
+            assert_eq!(msg.encode_vec::<32>().unwrap(), expected);
+        }
+
+        let mut msg = FileDescriptorProto::ProtoType::default();
+        let mut truncated = &expected[..expected.len() - 1];
+        assert!(matches!(
+            msg.decode_from_buf::<32>(&mut arena, &mut truncated),
+            Err(crate::Error::InvalidProtobufData)
+        ));
+    }
+
+    #[test]
+    fn encode_flat_to_start() {
+        use crate::google::protobuf::FileDescriptorProto;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("a.proto", &mut arena).unwrap();
+        let expected = file.encode_vec::<32>().unwrap();
+
+        let mut buffer = [0xaa; 64];
+        assert_eq!(file.encode_flat_to_start::<32>(&mut buffer).unwrap(), &expected[..]);
+        assert_eq!(&buffer[..expected.len()], &expected[..]);
+        assert!(buffer[expected.len()..].iter().all(|&b| b == 0xaa));
+        assert!(matches!(
+            file.encode_flat_to_start::<32>(&mut buffer[..expected.len() - 1]),
+            Err(crate::Error::BufferTooSmall)
+        ));
+        let empty = FileDescriptorProto::ProtoType::default();
+        assert_eq!(empty.encode_flat_to_start::<32>(&mut []).unwrap(), b"");
+    }
+
+    #[test]
+    fn group_encoding() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::google::protobuf::FileDescriptorProto;
+
+        // message Outer {
+        //   optional int32 a = 1;
+        //   optional group G = 2 { optional int32 b = 3; }
+        //   repeated G r = 4;
+        //   oneof kind { int32 n = 5; G o = 6; }
+        // }
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("group.proto", &mut arena).unwrap();
+        file.set_syntax("proto2", &mut arena).unwrap();
+        let outer = file.add_message_type(&mut arena).unwrap();
+        outer.set_name("Outer", &mut arena).unwrap();
+        outer
+            .add_oneof_decl(&mut arena)
+            .unwrap()
+            .set_name("kind", &mut arena)
+            .unwrap();
+        for (number, name, ty, label) in [
+            (1, "a", Type::TYPE_INT32, Label::LABEL_OPTIONAL),
+            (2, "g", Type::TYPE_GROUP, Label::LABEL_OPTIONAL),
+            (4, "r", Type::TYPE_GROUP, Label::LABEL_REPEATED),
+            (5, "n", Type::TYPE_INT32, Label::LABEL_OPTIONAL),
+            (6, "o", Type::TYPE_GROUP, Label::LABEL_OPTIONAL),
+        ] {
+            let field = outer.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_type(ty);
+            field.set_label(label);
+            if ty == Type::TYPE_GROUP {
+                field.set_type_name(".Outer.G", &mut arena).unwrap();
+            }
+            if number >= 5 {
+                field.set_oneof_index(0);
+            }
+        }
+        let group = outer.add_nested_type(&mut arena).unwrap();
+        group.set_name("G", &mut arena).unwrap();
+        let field = group.add_field(&mut arena).unwrap();
+        field.set_name("b", &mut arena).unwrap();
+        field.set_number(3);
+        field.set_type(Type::TYPE_INT32);
+        field.set_label(Label::LABEL_OPTIONAL);
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(&file).unwrap();
+
+        let roundtrip = |arena: &mut crate::arena::Arena, bytes: &[u8]| {
+            let mut msg = pool.create_message("Outer", arena).unwrap();
+            assert!(msg.decode_flat::<32>(arena, bytes));
+            msg.encode_vec::<32>().unwrap()
+        };
+        // a: 5, g { b: 7 }, r { b: 1 }, r {}, n: 9
+        let bytes = [
+            0x08, 0x05, //
+            0x13, 0x18, 0x07, 0x14, //
+            0x23, 0x18, 0x01, 0x24, 0x23, 0x24, //
+            0x28, 0x09,
+        ];
+        assert_eq!(roundtrip(&mut arena, &bytes), bytes);
+        // A group selected in a oneof, and replaced by a later member
+        let bytes = [0x08, 0x05, 0x33, 0x18, 0x02, 0x34];
+        assert_eq!(roundtrip(&mut arena, &bytes), bytes);
+        assert_eq!(roundtrip(&mut arena, &[0x33, 0x34, 0x28, 0x01]), [0x28, 0x01]);
+    }
+
+    #[test]
+    fn delimited_message_encoding() {
+        use crate::google::protobuf::FeatureSet::MessageEncoding;
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::google::protobuf::FileDescriptorProto;
+
+        // edition = "2023";
+        // option features.message_encoding = DELIMITED;
+        // message Outer {
+        //   Inner d = 1;
+        //   Inner l = 2 [features.message_encoding = LENGTH_PREFIXED];
+        // }
+        // message Inner { int32 b = 3; }
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("delimited.proto", &mut arena).unwrap();
+        file.set_syntax("editions", &mut arena).unwrap();
+        file.options_mut(&mut arena)
+            .features_mut(&mut arena)
+            .set_message_encoding(MessageEncoding::DELIMITED);
+        let outer = file.add_message_type(&mut arena).unwrap();
+        outer.set_name("Outer", &mut arena).unwrap();
+        for (number, name) in [(1, "d"), (2, "l")] {
+            let field = outer.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_type(Type::TYPE_MESSAGE);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type_name(".Inner", &mut arena).unwrap();
+            if number == 2 {
+                field
+                    .options_mut(&mut arena)
+                    .features_mut(&mut arena)
+                    .set_message_encoding(MessageEncoding::LENGTH_PREFIXED);
+            }
+        }
+        let inner = file.add_message_type(&mut arena).unwrap();
+        inner.set_name("Inner", &mut arena).unwrap();
+        let field = inner.add_field(&mut arena).unwrap();
+        field.set_name("b", &mut arena).unwrap();
+        field.set_number(3);
+        field.set_type(Type::TYPE_INT32);
+        field.set_label(Label::LABEL_OPTIONAL);
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(&file).unwrap();
+
+        // d { b: 7 }, l { b: 1 }
+        let bytes = [0x0b, 0x18, 0x07, 0x0c, 0x12, 0x02, 0x18, 0x01];
+        let mut msg = pool.create_message("Outer", &mut arena).unwrap();
+        assert!(msg.decode_flat::<32>(&mut arena, &bytes));
+        assert_eq!(msg.encode_vec::<32>().unwrap(), bytes);
+        let text = crate::text_format::Printer::new().print_message(&msg).unwrap();
+        assert_eq!(text, "d {\n  b: 7\n}\nl {\n  b: 1\n}\n");
+    }
+
+    #[test]
+    fn typed_enum_values() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::google::protobuf::FileDescriptorProto;
+        use crate::reflection::Value;
+
+        // package pkg;
+        // enum Status { UNKNOWN = 0; ACTIVE = 1; }
+        // message Outer {
+        //   message Inner { enum Color { RED = 0; BLUE = 1; } }
+        //   optional Inner.Color color = 1;
+        //   repeated Inner.Color colors = 2;
+        //   optional Status status = 3;
+        // }
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("enums.proto", &mut arena).unwrap();
+        file.set_package("pkg", &mut arena).unwrap();
+        file.set_syntax("proto3", &mut arena).unwrap();
+        let add_enum = |enum_type: &mut crate::google::protobuf::EnumDescriptorProto::ProtoType,
+                        name: &str,
+                        values: [&str; 2],
+                        arena: &mut crate::arena::Arena| {
+            enum_type.set_name(name, arena).unwrap();
+            for (number, value_name) in values.into_iter().enumerate() {
+                let value = enum_type.add_value(arena).unwrap();
+                value.set_name(value_name, arena).unwrap();
+                value.set_number(number as i32);
+            }
+        };
+        let status = file.add_enum_type(&mut arena).unwrap();
+        add_enum(status, "Status", ["UNKNOWN", "ACTIVE"], &mut arena);
+        let outer = file.add_message_type(&mut arena).unwrap();
+        outer.set_name("Outer", &mut arena).unwrap();
+        let inner = outer.add_nested_type(&mut arena).unwrap();
+        inner.set_name("Inner", &mut arena).unwrap();
+        let color = inner.add_enum_type(&mut arena).unwrap();
+        add_enum(color, "Color", ["RED", "BLUE"], &mut arena);
+        for (number, name, label, type_name) in [
+            (1, "color", Label::LABEL_OPTIONAL, ".pkg.Outer.Inner.Color"),
+            (2, "colors", Label::LABEL_REPEATED, ".pkg.Outer.Inner.Color"),
+            (3, "status", Label::LABEL_OPTIONAL, ".pkg.Status"),
+        ] {
+            let field = outer.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(label);
+            field.set_type(Type::TYPE_ENUM);
+            field.set_type_name(type_name, &mut arena).unwrap();
+        }
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(&file).unwrap();
+
+        let mut msg = pool.create_message("pkg.Outer", &mut arena).unwrap();
+        // color: BLUE, colors: [BLUE, RED, 7], status: ACTIVE
+        assert!(msg.decode_flat::<32>(&mut arena, &[0x08, 0x01, 0x12, 0x03, 0x01, 0x00, 0x07, 0x18, 0x01]));
+        let msg = msg.as_ref();
+        let field = |name| msg.find_field_descriptor(name).unwrap();
+
+        assert!(matches!(msg.get_field(field("color")), Some(Value::Int32(1))));
+        let color = msg.get_field_typed(field("color")).unwrap();
+        let Value::Enum { number: 1, descriptor: Some(descriptor) } = color else {
+            panic!("{color:?}");
+        };
+        assert_eq!(descriptor.name(), "Color");
+        assert_eq!(color.enum_name(), Some("BLUE"));
+        assert_eq!(std::format!("{color:?}"), "BLUE");
+
+        let colors = msg.get_field_typed(field("colors")).unwrap();
+        let Value::RepeatedEnum { numbers, descriptor: Some(descriptor) } = colors else {
+            panic!("{colors:?}");
+        };
+        assert_eq!((numbers, descriptor.name()), (&[1, 0, 7][..], "Color"));
+        let names: std::vec::Vec<_> = numbers
+            .iter()
+            .map(|&number| Value::Enum { number, descriptor: Some(descriptor) }.enum_name())
+            .collect();
+        assert_eq!(names, [Some("BLUE"), Some("RED"), None]);
+
+        // Declared at file scope, found through the file of the table
+        let status = msg.get_field_typed(field("status")).unwrap();
+        assert!(matches!(status, Value::Enum { number: 1, descriptor: Some(_) }));
+        assert_eq!(status.enum_name(), Some("ACTIVE"));
+    }
+
+    #[test]
+    fn decode_stack_spills_into_arena() {
+        use crate::DecodeOptions;
+        use crate::google::protobuf::DescriptorProto;
+
+        // DescriptorProto nested 100 deep through nested_type (3)
+        let mut bytes = std::vec::Vec::new();
+        for _ in 0..100 {
+            let len = bytes.len();
+            let mut outer = match len {
+                0..0x80 => std::vec![0x1a, len as u8],
+                _ => std::vec![0x1a, len as u8 | 0x80, (len >> 7) as u8],
+            };
+            outer.extend_from_slice(&bytes);
+            bytes = outer;
+        }
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let decode = |arena: &mut crate::arena::Arena, max_depth| {
+            let mut msg = DescriptorProto::ProtoType::default();
+            let options = DecodeOptions {
+                max_depth,
+                ..Default::default()
+            };
+            msg.decode_flat_with_options::<8>(arena, &bytes, options)
+                .then(|| msg.encode_vec::<128>().unwrap())
+        };
+        assert_eq!(decode(&mut arena, 0), None);
+        assert_eq!(decode(&mut arena, 64), None);
+        assert_eq!(decode(&mut arena, 128).as_ref(), Some(&bytes));
+
+        // Spilled entries survive across chunks
+        let mut msg = DescriptorProto::ProtoType::default();
+        let options = DecodeOptions {
+            max_depth: 128,
+            ..Default::default()
+        };
+        let mut decoder =
+            crate::decoding::ResumeableDecode::<4>::new(msg.as_dyn_mut(), isize::MAX, options);
+        for chunk in bytes.chunks(7) {
+            assert!(decoder.resume(chunk, &mut arena));
+        }
+        assert!(decoder.finish(&mut arena));
+        assert_eq!(msg.encode_vec::<128>().unwrap(), bytes);
+    }
+
+    #[test]
+    fn resumed_decode_at_every_boundary() {
+        use crate::google::protobuf::FileDescriptorProto;
+        use crate::{DecodeOptions, ResumeableDecode};
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("a/path/longer/than/the/slop.proto", &mut arena).unwrap();
+        let message = file.add_message_type(&mut arena).unwrap();
+        message.set_name("M", &mut arena).unwrap();
+        let field = message.add_field(&mut arena).unwrap();
+        field.set_name("f", &mut arena).unwrap();
+        field.set_number(1);
+        field.set_json_name("aJsonNameLongerThanTheSlop", &mut arena).unwrap();
+        let mut bytes = file.encode_vec::<32>().unwrap();
+        // public_dependency: [1, 300] packed, unknown group 99 { 1: 5 }, 2: 7
+        bytes.extend([0x52, 0x03, 0x01, 0xac, 0x02]);
+        bytes.extend([0x9b, 0x06, 0x08, 0x05, 0x9c, 0x06]);
+        bytes.extend([0x10, 0x07]);
+
+        // Start, payload start if length-delimited, and end of the top-level fields
+        let mut fields = Vec::new();
+        let mut reader = crate::wire::Reader::new(&bytes);
+        let pos = |reader: &crate::wire::Reader| bytes.len() - reader.remaining().len();
+        while !reader.is_empty() {
+            let start = pos(&reader);
+            let (number, wire_type) = reader.read_tag().unwrap();
+            let payload = match wire_type {
+                crate::wire::WireType::LengthDelimited => {
+                    let payload = reader.read_bytes().unwrap();
+                    Some(pos(&reader) - payload.len())
+                }
+                _ => {
+                    reader.skip(number, wire_type).unwrap();
+                    None
+                }
+            };
+            fields.push((start, payload, pos(&reader)));
+        }
+
+        for n in 0..=bytes.len() {
+            let prefix = &bytes[..n];
+            for chunk_size in [1, 3, 16, 17, n.max(1)] {
+                let mut msg = FileDescriptorProto::ProtoType::default();
+                let mut decoder = ResumeableDecode::<32>::new(
+                    msg.as_dyn_mut(),
+                    isize::MAX,
+                    DecodeOptions::default(),
+                );
+                for chunk in prefix.chunks(chunk_size) {
+                    assert!(decoder.resume(chunk, &mut arena));
+                }
+                let remaining = decoder.remaining_expected_bytes();
+                match fields.iter().find(|&&(start, _, end)| start < n && n < end) {
+                    None => {
+                        assert_eq!(remaining, 0, "prefix {n}, chunks of {chunk_size}");
+                        assert!(decoder.finish(&mut arena));
+                        let mut expected = FileDescriptorProto::ProtoType::default();
+                        assert!(expected.decode_flat::<32>(&mut arena, prefix));
+                        assert_eq!(msg.encode_vec::<32>().unwrap(), expected.encode_vec::<32>().unwrap());
+                    }
+                    Some(&(_, payload, end)) => {
+                        assert!(remaining > 0, "prefix {n}, chunks of {chunk_size}");
+                        assert!(n + remaining <= end, "prefix {n}, chunks of {chunk_size}");
+                        if payload.is_some_and(|payload| n >= payload) {
+                            assert_eq!(n + remaining, end, "prefix {n}, chunks of {chunk_size}");
+                        }
+                        assert!(!decoder.finish(&mut arena));
+                    }
+                }
+            }
+        }
+
+        // Nothing is expected once the input failed to decode
+        let mut msg = FileDescriptorProto::ProtoType::default();
+        let mut decoder =
+            ResumeableDecode::<32>::new(msg.as_dyn_mut(), isize::MAX, DecodeOptions::default());
+        assert!(!decoder.resume(&[0xff; 40], &mut arena));
+        assert_eq!(decoder.remaining_expected_bytes(), 0);
+        assert!(!decoder.resume(&bytes, &mut arena));
+        assert!(!decoder.finish(&mut arena));
+
+        // Every chunk must be decoded into the same arena
+        let mut other = crate::arena::Arena::new(&Global);
+        let mut msg = FileDescriptorProto::ProtoType::default();
+        let mut decoder =
+            ResumeableDecode::<32>::new(msg.as_dyn_mut(), isize::MAX, DecodeOptions::default());
+        assert!(decoder.resume(&bytes[..20], &mut arena));
+        assert!(!decoder.resume(&bytes[20..], &mut other));
+        assert!(!decoder.finish(&mut arena));
+        let mut msg = FileDescriptorProto::ProtoType::default();
+        let mut decoder =
+            ResumeableDecode::<32>::new(msg.as_dyn_mut(), isize::MAX, DecodeOptions::default());
+        assert!(decoder.resume(&bytes, &mut arena));
+        assert!(!decoder.finish(&mut other));
+    }
+
+    #[test]
+    fn counts_skipped_unknown_fields() {
+        use crate::google::protobuf::FileDescriptorProto;
+        use crate::{DecodeOptions, ResumeableDecode, UnknownFieldStats};
+
+        let mut bytes = std::vec::Vec::new();
+        bytes.extend_from_slice(b"\x0a\x07a.proto");
+        // 1000: 5, 1001: fixed32, 1002: fixed64
+        bytes.extend_from_slice(&[0xc0, 0x3e, 0x05]);
+        bytes.extend_from_slice(&[0xcd, 0x3e, 1, 2, 3, 4]);
+        bytes.extend_from_slice(&[0xd1, 0x3e, 1, 2, 3, 4, 5, 6, 7, 8]);
+        // 1003: 40 bytes, longer than the slop
+        bytes.extend_from_slice(&[0xda, 0x3e, 40]);
+        bytes.extend_from_slice(&[b'x'; 40]);
+        // group 1004 { 1: 5, 2: 20 bytes, group 3 {} }
+        bytes.extend_from_slice(&[0xe3, 0x3e, 0x08, 0x05, 0x12, 20]);
+        bytes.extend_from_slice(&[b'y'; 20]);
+        bytes.extend_from_slice(&[0x1b, 0x1c, 0xe4, 0x3e]);
+        // message_type { name: "M", 1000: 7 }
+        bytes.extend_from_slice(&[0x22, 0x06, 0x0a, 0x01, b'M', 0xc0, 0x3e, 0x07]);
+        // name with the wrong wire type
+        bytes.extend_from_slice(&[0x08, 0x01]);
+        let expected = UnknownFieldStats {
+            fields: 7,
+            bytes: 3 + 6 + 10 + 43 + 30 + 3 + 2,
+            groups: 1,
+        };
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut msg = FileDescriptorProto::ProtoType::default();
+        let stats = msg.decode_flat_with_stats::<32>(&mut arena, &bytes, DecodeOptions::default());
+        assert_eq!(stats, Some(expected));
+        assert_eq!(msg.name(), "a.proto");
+        assert_eq!(msg.message_type()[0].name(), "M");
+
+        for chunk_size in 1..=bytes.len() {
+            let mut msg = FileDescriptorProto::ProtoType::default();
+            let mut decoder =
+                ResumeableDecode::<32>::new(msg.as_dyn_mut(), isize::MAX, DecodeOptions::default());
+            for chunk in bytes.chunks(chunk_size) {
+                assert!(decoder.resume(chunk, &mut arena));
+                assert!(decoder.unknown_fields().bytes <= expected.bytes);
+            }
+            assert_eq!(decoder.finish_with_stats(&mut arena), Some(expected), "chunks of {chunk_size}");
+        }
+
+        let known = msg.encode_vec::<32>().unwrap();
+        let stats = msg.decode_flat_with_stats::<32>(&mut arena, &known, DecodeOptions::default());
+        assert_eq!(stats, Some(UnknownFieldStats::default()));
+        let stats = msg.decode_flat_with_stats::<32>(&mut arena, &bytes[..10], DecodeOptions::default());
+        assert_eq!(stats, None);
+    }
+
+    #[test]
+    fn repeated_scalar_bulk_copy() {
+        use crate::google::protobuf::FileDescriptorProto;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_public_dependency(&[1, 2, 3], &mut arena).unwrap();
+        assert_eq!(file.public_dependency(), &[1, 2, 3]);
+
+        file.public_dependency_mut().as_mut_slice()[0] = 7;
+        assert_eq!(file.public_dependency_mut().as_slice().to_vec(), vec![7, 2, 3]);
+
+        let column: Vec<i32> = (0..100).collect();
+        file.public_dependency_mut()
+            .copy_from_slice(&column, &mut arena)
+            .unwrap();
+        assert_eq!(file.public_dependency(), column.as_slice());
+    }
+
+    #[test]
+    fn resumed_encode_in_small_chunks() {
+        use crate::encoding::{ResumeResult, ResumeableEncode};
+        use crate::google::protobuf::FileDescriptorProto;
+
+        // Chunk boundaries fall inside repeated strings and repeated scalars
+        let file = FileDescriptorProto::ProtoType::file_descriptor();
+        let expected = file.encode_vec::<32>().unwrap();
+        for chunk_size in [1, 7, 16, 17, 33, 64, 100] {
+            let mut encoder = ResumeableEncode::<32>::new(file.as_dyn(), EncodeOptions::default()).unwrap();
+            let mut chunks = Vec::new();
+            let mut buffer = vec![0; chunk_size];
+            loop {
+                match encoder.resume_encode(&mut buffer).unwrap() {
+                    ResumeResult::Done(tail) => {
+                        chunks.push(tail.to_vec());
+                        break;
+                    }
+                    ResumeResult::NeedsMoreBuffer => chunks.push(buffer.clone()),
+                }
+            }
+            let encoded: Vec<u8> = chunks.into_iter().rev().flatten().collect();
+            assert!(encoded == expected, "chunk size {chunk_size}");
+        }
+    }
+
+    #[test]
+    fn stable_hash_ignores_field_order() {
+        use crate::google::protobuf::FileDescriptorProto;
+
+        /// Records everything written to it.
+        #[derive(Default)]
+        struct Recorder(Vec<u8>);
+
+        impl core::hash::Hasher for Recorder {
+            fn finish(&self) -> u64 {
+                0
+            }
+
+            fn write(&mut self, bytes: &[u8]) {
+                self.0.extend_from_slice(bytes);
+            }
+        }
+
+        let hash = |bytes: &[u8]| {
+            let mut arena = crate::arena::Arena::new(&Global);
+            let mut msg = FileDescriptorProto::ProtoType::default();
+            assert!(msg.decode_flat::<32>(&mut arena, bytes));
+            let mut recorder = Recorder::default();
+            msg.stable_hash(&mut recorder).unwrap();
+            recorder.0
+        };
+
+        // name: "a", package: "p" in both orders
+        let hashed = hash(&[0x0a, 0x01, b'a', 0x12, 0x01, b'p']);
+        assert_eq!(hashed, hash(&[0x12, 0x01, b'p', 0x0a, 0x01, b'a']));
+        assert_eq!(hashed, [0x0a, 0x01, b'a', 0x12, 0x01, b'p']);
+        assert_ne!(hashed, hash(&[0x0a, 0x01, b'b']));
+
+        // Larger than a chunk: every encoded byte is hashed exactly once
+        let bytes = FileDescriptorProto::ProtoType::file_descriptor()
+            .encode_vec::<32>()
+            .unwrap();
+        let mut hashed = hash(&bytes);
+        let mut encoded = bytes.clone();
+        assert_eq!(hashed.len(), encoded.len());
+        hashed.sort_unstable();
+        encoded.sort_unstable();
+        assert_eq!(hashed, encoded);
+    }
+
+    #[test]
+    fn map_order_independent_equality() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::google::protobuf::FileDescriptorProto;
+        use core::hash::Hasher;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("counts.proto", &mut arena).unwrap();
+        file.set_syntax("proto3", &mut arena).unwrap();
+        let message = file.add_message_type(&mut arena).unwrap();
+        message.set_name("Counts", &mut arena).unwrap();
+        let field = message.add_field(&mut arena).unwrap();
+        field.set_name("counts", &mut arena).unwrap();
+        field.set_number(1);
+        field.set_label(Label::LABEL_REPEATED);
+        field.set_type(Type::TYPE_MESSAGE);
+        field.set_type_name(".Counts.CountsEntry", &mut arena).unwrap();
+        let entry = message.add_nested_type(&mut arena).unwrap();
+        entry.set_name("CountsEntry", &mut arena).unwrap();
+        entry.options_mut(&mut arena).set_map_entry(true);
+        for (number, name) in [(1, "key"), (2, "value")] {
+            let field = entry.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(Type::TYPE_INT32);
+        }
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(&file).unwrap();
+
+        let mut decode = |bytes: &[u8]| {
+            let mut msg = pool.create_message("Counts", &mut arena).unwrap();
+            assert!(msg.decode_flat::<32>(&mut arena, bytes));
+            msg
+        };
+        let hash = |msg: &crate::reflection::DynamicMessage| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            msg.stable_hash(&mut hasher).unwrap();
+            hasher.finish()
+        };
+
+        // counts: {1: 10, 2: 20}
+        let sorted = decode(&[0x0a, 4, 0x08, 1, 0x10, 10, 0x0a, 4, 0x08, 2, 0x10, 20]);
+        let reversed = decode(&[0x0a, 4, 0x08, 2, 0x10, 20, 0x0a, 4, 0x08, 1, 0x10, 10]);
+        // The last entry of a repeated key wins
+        let repeated = decode(
+            &[0x0a, 4, 0x08, 2, 0x10, 5, 0x0a, 4, 0x08, 1, 0x10, 10, 0x0a, 4, 0x08, 2, 0x10, 20],
+        );
+        let different = decode(&[0x0a, 4, 0x08, 2, 0x10, 21, 0x0a, 4, 0x08, 1, 0x10, 10]);
+        let missing = decode(&[0x0a, 4, 0x08, 1, 0x10, 10]);
+
+        for msg in [&reversed, &repeated] {
+            assert!(sorted.message_eq(msg));
+            assert!(msg.message_eq(&sorted));
+            assert_eq!(hash(&sorted), hash(msg));
+        }
+        for msg in [&different, &missing] {
+            assert!(!sorted.message_eq(msg));
+            assert!(!msg.message_eq(&sorted));
+            assert_ne!(hash(&sorted), hash(msg));
+        }
+        // Hashing leaves the stored order alone
+        assert_eq!(
+            reversed.encode_vec::<32>().unwrap(),
+            [0x0a, 4, 0x08, 2, 0x10, 20, 0x0a, 4, 0x08, 1, 0x10, 10]
+        );
+        // Entries are kept in wire order, repeated keys included, unless
+        // decoding is asked to drop the earlier entries of a key
+        let repeated_bytes =
+            [0x0a, 4, 0x08, 2, 0x10, 5, 0x0a, 4, 0x08, 1, 0x10, 10, 0x0a, 4, 0x08, 2, 0x10, 20];
+        assert_eq!(repeated.encode_vec::<32>().unwrap(), repeated_bytes);
+        let options = crate::DecodeOptions {
+            dedup_map_entries: true,
+            ..Default::default()
+        };
+        let mut deduped = pool.create_message("Counts", &mut arena).unwrap();
+        assert!(deduped.decode_flat_with_options::<32>(&mut arena, &repeated_bytes, options));
+        assert_eq!(
+            deduped.encode_vec::<32>().unwrap(),
+            [0x0a, 4, 0x08, 1, 0x10, 10, 0x0a, 4, 0x08, 2, 0x10, 20]
+        );
+
+        let file = FileDescriptorProto::ProtoType::file_descriptor();
+        assert!(file.message_eq(file));
+        assert!(!sorted.message_eq(file));
+    }
+
+    #[test]
+    fn closed_enum_drops_unknown_values() {
+        use crate::google::protobuf::{FieldDescriptorProto, FieldOptions};
+        use crate::{DecodeOptions, ResumeableDecode, UnknownFieldStats};
+
+        let mut arena = crate::arena::Arena::new(&Global);
+
+        // descriptor.proto is proto2, so its enums are closed.
+        // label = 7 (unknown), then label = 2 (LABEL_REQUIRED), then label = 9 (unknown)
+        let mut field = FieldDescriptorProto::ProtoType::default();
+        assert!(field.decode_flat::<32>(&mut arena, &[0x20, 0x07]));
+        assert!(!field.has_label());
+        assert!(field.decode_flat::<32>(&mut arena, &[0x20, 0x02, 0x20, 0x09]));
+        assert_eq!(field.label(), Some(FieldDescriptorProto::Label::LABEL_REQUIRED));
+        assert_eq!(field.raw_label(), 2);
+        field.set_raw_label(9);
+        assert_eq!(field.label(), None);
+        assert_eq!(field.raw_label(), 9);
+
+        // targets (19): unpacked 1, 42, 2, then packed [3, 50, 4]
+        let bytes = [
+            0x98, 0x01, 0x01, 0x98, 0x01, 0x2a, 0x98, 0x01, 0x02, 0x9a, 0x01, 0x03, 0x03, 0x32,
+            0x04,
+        ];
+        let mut options = FieldOptions::ProtoType::default();
+        assert!(options.decode_flat::<32>(&mut arena, &bytes));
+        assert_eq!(options.raw_targets(), &[1, 2, 3, 4]);
+        assert!(options.targets().eq([1, 2, 3, 4].map(FieldOptions::OptionTargetType::from_i32)));
+
+        // Dropped values count as unknown fields, in packed fields one by one
+        let mut field = FieldDescriptorProto::ProtoType::default();
+        let label = [0x20, 0x02, 0x20, 0x09];
+        let stats = field.decode_flat_with_stats::<32>(&mut arena, &label, DecodeOptions::default());
+        assert_eq!(stats, Some(UnknownFieldStats { fields: 1, bytes: 2, groups: 0 }));
+        let expected = UnknownFieldStats { fields: 2, bytes: 3 + 1, groups: 0 };
+        let mut options = FieldOptions::ProtoType::default();
+        let stats = options.decode_flat_with_stats::<32>(&mut arena, &bytes, DecodeOptions::default());
+        assert_eq!(stats, Some(expected));
+        for chunk_size in 1..=bytes.len() {
+            let mut options = FieldOptions::ProtoType::default();
+            let mut decoder =
+                ResumeableDecode::<32>::new(options.as_dyn_mut(), isize::MAX, DecodeOptions::default());
+            for chunk in bytes.chunks(chunk_size) {
+                assert!(decoder.resume(chunk, &mut arena));
+            }
+            assert_eq!(decoder.finish_with_stats(&mut arena), Some(expected), "chunks of {chunk_size}");
+            assert_eq!(options.raw_targets(), &[1, 2, 3, 4]);
+        }
+
+        // Tables built at runtime behave the same
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(FieldOptions::ProtoType::file_descriptor()).unwrap();
+        let mut dynamic = pool
+            .create_message("google.protobuf.FieldOptions", &mut arena)
+            .unwrap();
+        assert!(dynamic.decode_flat::<32>(&mut arena, &bytes));
+        assert_eq!(
+            dynamic.encode_vec::<32>().unwrap(),
+            options.encode_vec::<32>().unwrap()
+        );
+    }
+
+    #[test]
+    fn ten_byte_negative_varints() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::google::protobuf::FileDescriptorProto;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("signs.proto", &mut arena).unwrap();
+        let sign = file.add_enum_type(&mut arena).unwrap();
+        sign.set_name("Sign", &mut arena).unwrap();
+        for (number, name) in [(-1, "NEG"), (0, "ZERO"), (1, "POS")] {
+            let value = sign.add_value(&mut arena).unwrap();
+            value.set_name(name, &mut arena).unwrap();
+            value.set_number(number);
+        }
+        let message = file.add_message_type(&mut arena).unwrap();
+        message.set_name("M", &mut arena).unwrap();
+        for (number, name, label, packed, ty) in [
+            (1, "int", Label::LABEL_OPTIONAL, false, Type::TYPE_INT32),
+            (2, "sign", Label::LABEL_OPTIONAL, false, Type::TYPE_ENUM),
+            (3, "ints", Label::LABEL_REPEATED, false, Type::TYPE_INT32),
+            (4, "packed_ints", Label::LABEL_REPEATED, true, Type::TYPE_INT32),
+            (5, "signs", Label::LABEL_REPEATED, false, Type::TYPE_ENUM),
+            (6, "packed_signs", Label::LABEL_REPEATED, true, Type::TYPE_ENUM),
+        ] {
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(label);
+            field.set_type(ty);
+            if ty == Type::TYPE_ENUM {
+                field.set_type_name(".Sign", &mut arena).unwrap();
+            }
+            if packed {
+                field.options_mut(&mut arena).set_packed(true);
+            }
+        }
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(&file).unwrap();
+
+        // Negative int32 and enum values are sign-extended to 10 bytes on the
+        // wire. Writers of uint32 truncate them to 5 bytes, and bits past the
+        // 64th, here in a 10th byte of 0x7f, are discarded as by protoc.
+        let neg1 = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        let neg2 = [0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        let long_zero = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00];
+        let wide_neg1 = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f];
+        let short_neg1 = [0xff, 0xff, 0xff, 0xff, 0x0f];
+        let mut input = std::vec![0x08];
+        input.extend(neg1);
+        input.push(0x10);
+        input.extend(wide_neg1);
+        for value in [&neg2[..], &long_zero, &short_neg1] {
+            input.push(0x18);
+            input.extend(value);
+        }
+        input.extend([0x22, 11]);
+        input.extend(neg2);
+        input.push(0x01);
+        // -2 isn't a Sign, closed enums drop it
+        for value in [neg1, neg2] {
+            input.push(0x28);
+            input.extend(value);
+        }
+        input.extend([0x32, 21]);
+        input.extend(neg2);
+        input.extend(long_zero);
+        input.push(0x01);
+
+        let mut expected = std::vec![0x08];
+        expected.extend(neg1);
+        expected.push(0x10);
+        expected.extend(neg1);
+        for value in [&neg2[..], &[0x00], &neg1] {
+            expected.push(0x18);
+            expected.extend(value);
+        }
+        expected.extend([0x22, 11]);
+        expected.extend(neg2);
+        expected.push(0x01);
+        expected.push(0x28);
+        expected.extend(neg1);
+        expected.extend([0x32, 2, 0x00, 0x01]);
+
+        for chunk_size in 1..=input.len() {
+            let mut msg = pool.create_message("M", &mut arena).unwrap();
+            let mut chunks = input.chunks(chunk_size);
+            let mut provider = || Ok::<_, ()>(chunks.next());
+            msg.decode(&mut arena, &mut provider).unwrap();
+            assert_eq!(msg.encode_vec::<32>().unwrap(), expected, "chunk size {chunk_size}");
+        }
+        let mut msg = pool.create_message("M", &mut arena).unwrap();
+        assert!(msg.decode_flat::<32>(&mut arena, &input));
+        assert_eq!(msg.get_i32("int"), Some(-1));
+        assert_eq!(msg.get_i32("sign"), Some(-1));
+
+        // An 11th byte is still malformed
+        let mut overlong = std::vec![0x08];
+        overlong.extend([0x80; 10]);
+        overlong.push(0x00);
+        assert_eq!(msg.try_decode_flat::<32>(&mut arena, &overlong).unwrap_err().offset(), 0);
+        assert!(msg.try_decode_flat::<32>(&mut arena, &input).is_ok());
+    }
+
+    #[test]
+    fn packed_fixed_bulk_decode() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::google::protobuf::FileDescriptorProto;
+        use crate::reflection::Value;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("samples.proto", &mut arena).unwrap();
+        file.set_syntax("proto3", &mut arena).unwrap();
+        let message = file.add_message_type(&mut arena).unwrap();
+        message.set_name("Samples", &mut arena).unwrap();
+        for (number, name, ty) in [(1, "values", Type::TYPE_DOUBLE), (2, "ids", Type::TYPE_FIXED32)] {
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(Label::LABEL_REPEATED);
+            field.set_type(ty);
+        }
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(&file).unwrap();
+
+        let values: std::vec::Vec<f64> = (0..1000).map(|i| i as f64 * 0.5).collect();
+        let ids = [7u32, 0xdeadbeef, 42];
+        let mut bytes = std::vec![0x0a, 0xc0, 0x3e];
+        for v in &values {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        bytes.extend_from_slice(&[0x12, 12]);
+        for v in &ids {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+
+        // In one buffer and split at odd offsets inside elements
+        for chunk_size in [bytes.len(), 7] {
+            let mut msg = pool.create_message("Samples", &mut arena).unwrap();
+            let mut chunks = bytes.chunks(chunk_size);
+            let mut provider = || Ok::<_, ()>(chunks.next());
+            msg.decode(&mut arena, &mut provider).unwrap();
+            let descriptor = msg.descriptor();
+            let Some(Value::RepeatedDouble(decoded)) =
+                msg.as_ref().get_field(&descriptor.field()[0])
+            else {
+                panic!("values not decoded");
+            };
+            assert_eq!(decoded, values.as_slice());
+            let Some(Value::RepeatedUInt32(decoded)) =
+                msg.as_ref().get_field(&descriptor.field()[1])
+            else {
+                panic!("ids not decoded");
+            };
+            assert_eq!(decoded, ids.as_slice());
+            assert_eq!(msg.encode_vec::<32>().unwrap(), bytes);
+        }
+
+        // A length that is not a multiple of the element size
+        let mut msg = pool.create_message("Samples", &mut arena).unwrap();
+        assert!(!msg.decode_flat::<32>(&mut arena, &[0x12, 0x06, 1, 0, 0, 0, 2, 0]));
+
+        // The element limit counts the whole packed run
+        let options = crate::DecodeOptions {
+            max_repeated_elements: 999,
+            ..Default::default()
+        };
+        let mut msg = pool.create_message("Samples", &mut arena).unwrap();
+        assert!(!msg.decode_flat_with_options::<32>(&mut arena, &bytes, options));
+    }
+
+    #[test]
+    fn packed_varints_reserve_once() {
+        use crate::google::protobuf::SourceCodeInfo::Location;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let path: std::vec::Vec<i32> = (0..300).chain([-1]).collect();
+        let mut location = Location::ProtoType::default();
+        location.set_path(&path, &mut arena).unwrap();
+        let bytes = location.encode_vec::<32>().unwrap();
+
+        // Whole run in one buffer: exactly one element per varint
+        let mut decoded = Location::ProtoType::default();
+        assert!(decoded.decode_flat::<32>(&mut arena, &bytes));
+        assert_eq!(decoded.path(), path.as_slice());
+        assert_eq!(decoded.path_mut().capacity(), path.len());
+
+        // Split over many buffers the field grows geometrically
+        let mut decoded = Location::ProtoType::default();
+        let mut chunks = bytes.chunks(5);
+        let mut provider = || Ok::<_, ()>(chunks.next());
+        decoded.decode(&mut arena, &mut provider).unwrap();
+        assert_eq!(decoded.path(), path.as_slice());
+        assert!(decoded.path_mut().capacity() < 2 * path.len());
+    }
+
+    #[test]
+    fn repeated_messages_allocate_in_batches() {
+        use crate::google::protobuf::{DescriptorProto, FileDescriptorProto};
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut message = DescriptorProto::ProtoType::default();
+        for i in 0..1000 {
+            let range = message.add_reserved_range(&mut arena).unwrap();
+            range.set_start(i);
+            range.set_end(i + 1);
+        }
+        let bytes = message.encode_vec::<32>().unwrap();
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut decoded = DescriptorProto::ProtoType::default();
+        assert!(decoded.decode_flat::<32>(&mut arena, &bytes));
+        assert_eq!(decoded.encode_vec::<32>().unwrap(), bytes);
+        assert!(arena.stats().allocations < 50);
+
+        // Elements of interleaved repeated fields, decoded in small chunks
+        let mut file = FileDescriptorProto::ProtoType::default();
+        for i in 0..100 {
+            let message = file.add_message_type(&mut arena).unwrap();
+            for j in 0..i % 7 {
+                message.add_reserved_range(&mut arena).unwrap().set_start(i * 10 + j);
+            }
+        }
+        let bytes = file.encode_vec::<32>().unwrap();
+        let mut decoded = FileDescriptorProto::ProtoType::default();
+        let mut chunks = bytes.chunks(3);
+        let mut provider = || Ok::<_, ()>(chunks.next());
+        decoded.decode(&mut arena, &mut provider).unwrap();
+        assert_eq!(decoded.encode_vec::<32>().unwrap(), bytes);
+    }
+
+    #[test]
+    fn cyclic_message_graph_rejected() {
+        use crate::base::TypedMessage;
+        use crate::google::protobuf::DescriptorProto;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut root = TypedMessage::<DescriptorProto::ProtoType>::new_in(&mut arena).unwrap();
+        root.set_name("Node", &mut arena).unwrap();
+        let shared = root.add_nested_type(&mut arena).unwrap();
+        shared.set_name("Leaf", &mut arena).unwrap();
+        let shared = TypedMessage::from_static(unsafe { &*(shared as *const _) });
+
+        // The same sub-message reachable twice is fine
+        root.nested_type_mut().push(shared, &mut arena).unwrap();
+        let encoded = root.encode_vec::<32>().unwrap();
+        let mut decoded = DescriptorProto::ProtoType::default();
+        assert!(decoded.decode_flat::<32>(&mut arena, &encoded));
+        assert_eq!(decoded.nested_type().len(), 2);
+
+        // A message containing itself, twice so that without cycle detection
+        // the work would grow exponentially with the stack depth
+        for _ in 0..2 {
+            let this = TypedMessage::from_static(unsafe { &*(root.as_ref() as *const _) });
+            root.nested_type_mut().push(this, &mut arena).unwrap();
+        }
+        assert!(matches!(
+            root.encode_vec::<1024>(),
+            Err(crate::Error::MessageTreeTooDeep)
+        ));
+        let mut buffer = [0u8; 4096];
+        assert!(matches!(
+            root.encode_flat::<1024>(&mut buffer),
+            Err(crate::Error::MessageTreeTooDeep)
+        ));
+    }
+
+    #[test]
+    fn downcast_dynamic_messages() {
+        use crate::google::protobuf::{DescriptorProto, FileDescriptorProto};
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        let mut dynamic = file.as_dyn_mut();
+        assert!(dynamic.downcast::<DescriptorProto::ProtoType>().is_none());
+        dynamic
+            .downcast::<FileDescriptorProto::ProtoType>()
+            .unwrap()
+            .set_name("a.proto", &mut arena)
+            .unwrap();
+        assert_eq!(file.name(), "a.proto");
+
+        // Tables a pool builds from the compiled-in descriptor are compatible
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(FileDescriptorProto::ProtoType::file_descriptor())
+            .unwrap();
+        let mut msg = pool
+            .create_message("google.protobuf.FileDescriptorProto", &mut arena)
+            .unwrap();
+        assert!(msg.decode_flat::<32>(&mut arena, &[0x12, 0x01, b'p']));
+        let typed = msg.downcast::<FileDescriptorProto::ProtoType>().unwrap();
+        assert_eq!(typed.package(), "p");
+        typed.add_message_type(&mut arena).unwrap();
+        let encoded = msg.encode_vec::<32>().unwrap();
+        assert_eq!(encoded, [0x12, 0x01, b'p', 0x22, 0x00]);
+        assert!(msg.as_ref().downcast::<DescriptorProto::ProtoType>().is_none());
+    }
+
+    #[test]
+    fn max_encoded_size_fits_stack_buffer() {
+        use crate::google::protobuf::{Duration, Edition, FeatureSetDefaults};
+
+        // Both fields take the largest varints
+        let mut duration = Duration::ProtoType::default();
+        duration.set_seconds(i64::MIN);
+        duration.set_nanos(-1);
+        let mut buffer = [0u8; Duration::ProtoType::MAX_ENCODED_SIZE];
+        let encoded = duration.encode_flat::<32>(&mut buffer).unwrap();
+        assert_eq!(encoded.len(), Duration::ProtoType::MAX_ENCODED_SIZE);
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut defaults = FeatureSetDefaults::FeatureSetEditionDefault::ProtoType::default();
+        defaults.set_edition(Edition::EDITION_MAX);
+        defaults.fixed_features_mut(&mut arena);
+        defaults.overridable_features_mut(&mut arena);
+        let mut buffer = [0u8; FeatureSetDefaults::FeatureSetEditionDefault::ProtoType::MAX_ENCODED_SIZE];
+        assert!(defaults.encode_flat::<32>(&mut buffer).is_ok());
+    }
+
+    #[cfg(feature = "serde_support")]
+    #[test]
+    fn json_null_clears_fields() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::google::protobuf::FileDescriptorProto;
+
+        fn parse<T: crate::generated_code_only::Protobuf>(
+            msg: &mut T,
+            arena: &mut crate::arena::Arena,
+            json: &str,
+        ) -> Result<(), serde_json::Error> {
+            let mut deserializer = serde_json::Deserializer::from_str(json);
+            msg.serde_deserialize(arena, &mut deserializer)
+        }
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        parse(
+            &mut file,
+            &mut arena,
+            r#"{"name":"a.proto","dependency":["b.proto"],"options":{"javaPackage":"p"}}"#,
+        )
+        .unwrap();
+        assert!(file.has_name() && file.options().is_some());
+
+        // Null resets scalar, repeated and message fields to their defaults
+        parse(
+            &mut file,
+            &mut arena,
+            r#"{"name":null,"dependency":null,"options":null,"package":null}"#,
+        )
+        .unwrap();
+        assert!(!file.has_name());
+        assert!(file.dependency().is_empty());
+        assert!(file.options().is_none());
+        assert_eq!(file.encode_vec::<32>().unwrap(), b"");
+
+        // Null isn't a valid element of a repeated field
+        assert!(parse(&mut file, &mut arena, r#"{"dependency":["a",null]}"#).is_err());
+
+        // A null oneof member doesn't select the oneof, two set members are an error
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("choice.proto", &mut arena).unwrap();
+        file.set_syntax("proto3", &mut arena).unwrap();
+        let message = file.add_message_type(&mut arena).unwrap();
+        message.set_name("Choice", &mut arena).unwrap();
+        message
+            .add_oneof_decl(&mut arena)
+            .unwrap()
+            .set_name("kind", &mut arena)
+            .unwrap();
+        for (number, name) in [(1, "a"), (2, "b")] {
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(Type::TYPE_INT32);
+            field.set_oneof_index(0);
+        }
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(&file).unwrap();
+        let parse_choice = |arena: &mut crate::arena::Arena, json: &str| {
+            let mut msg = pool.create_message("Choice", arena).unwrap();
+            let mut deserializer = serde_json::Deserializer::from_str(json);
+            msg.serde_deserialize(arena, &mut deserializer)
+                .map(|()| msg.encode_vec::<32>().unwrap())
+        };
+        assert_eq!(parse_choice(&mut arena, r#"{"a":null,"b":5}"#).unwrap(), [0x10, 5]);
+        assert_eq!(parse_choice(&mut arena, r#"{"b":5,"a":null}"#).unwrap(), [0x10, 5]);
+        assert!(parse_choice(&mut arena, r#"{"a":1,"b":5}"#).is_err());
+    }
+
+    #[cfg(feature = "serde_support")]
+    #[test]
+    fn deserialize_from_scoped_arena() {
+        use crate::generated_code_only::deserialize_message;
+        use crate::google::protobuf::FileDescriptorProto;
+
+        fn parse(json: &str) -> Result<FileDescriptorProto::ProtoType, serde_json::Error> {
+            deserialize_message(&mut serde_json::Deserializer::from_str(json))
+        }
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut inner = crate::arena::Arena::new(&Global);
+        // Both arenas outlive every message parsed into them
+        let file = unsafe {
+            crate::serde::with_arena(&mut arena, || {
+                let file = crate::serde::with_arena(&mut inner, || parse(r#"{"name":"inner.proto"}"#));
+                assert_eq!(file.unwrap().name(), "inner.proto");
+                // The outer arena is back in place
+                parse(r#"{"name":"outer.proto"}"#)
+            })
+        };
+        assert_eq!(file.unwrap().name(), "outer.proto");
+        assert!(arena.bytes_allocated() > 0);
+        assert!(inner.bytes_allocated() > 0);
+
+        // Outside of with_arena there is nothing to allocate from
+        let err = parse(r#"{"name":"nowhere.proto"}"#).unwrap_err();
+        assert!(err.to_string().contains("no arena"));
+    }
+
+    #[cfg(feature = "serde_support")]
+    #[test]
+    fn json_parse_options() {
+        use crate::google::protobuf::FileDescriptorProto;
+        use crate::serde::JsonParseOptions;
+
+        fn parse(json: &str, options: JsonParseOptions) -> Result<std::vec::Vec<u8>, serde_json::Error> {
+            let mut arena = crate::arena::Arena::new(&Global);
+            let mut file = FileDescriptorProto::ProtoType::default();
+            let mut deserializer = serde_json::Deserializer::from_str(json);
+            file.serde_deserialize_with_options(&mut arena, &mut deserializer, options)?;
+            Ok(file.encode_vec::<32>().unwrap())
+        }
+
+        let strict = JsonParseOptions::default();
+        let lenient = JsonParseOptions {
+            ignore_unknown_fields: true,
+            reject_duplicate_fields: false,
+            ignore_unknown_enum_values: true,
+        };
+
+        let unknown = r#"{"name":"a","bogus":{"x":[1]}}"#;
+        let err = parse(unknown, strict).unwrap_err();
+        assert!(err.to_string().contains("bogus"));
+        assert_eq!(parse(unknown, lenient).unwrap(), b"\x0a\x01a");
+
+        // The JSON name and the proto name address the same field
+        let duplicate = r#"{"publicDependency":[1],"public_dependency":[2]}"#;
+        assert!(parse(duplicate, strict).is_err());
+        assert!(parse(duplicate, lenient).is_ok());
+
+        // Unknown enum names leave singular fields unset and drop elements
+        let unknown_enum = r#"{"options":{"optimizeFor":"FASTEST","ccGenericServices":true},
+            "messageType":[{"field":[{"options":{"targets":["TARGET_TYPE_FILE","BOGUS","TARGET_TYPE_FIELD"]}}]}]}"#;
+        let known = r#"{"options":{"ccGenericServices":true},
+            "messageType":[{"field":[{"options":{"targets":["TARGET_TYPE_FILE","TARGET_TYPE_FIELD"]}}]}]}"#;
+        let err = parse(unknown_enum, strict).unwrap_err();
+        assert!(err.to_string().contains("FASTEST"));
+        assert_eq!(parse(unknown_enum, lenient).unwrap(), parse(known, strict).unwrap());
+    }
+
+    #[cfg(feature = "serde_support")]
+    #[test]
+    fn json_bytes_base64() {
+        use crate::google::protobuf::UninterpretedOption;
+        use crate::proto_json::{Base64Encoding, ProtoJsonSerializer};
+        use ::serde::Serialize;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut option = UninterpretedOption::ProtoType::default();
+        option.set_string_value(&[0xfb, 0xff], &mut arena).unwrap();
+
+        // Plain serde_json and the proto JSON wrapper agree on the default
+        let plain = serde_json::to_string(&option.as_dyn()).unwrap();
+        assert_eq!(plain, r#"{"stringValue":"+/8="}"#);
+        let mut serializer = serde_json::Serializer::new(std::vec::Vec::new());
+        option
+            .as_dyn()
+            .serialize(ProtoJsonSerializer::new(&mut serializer))
+            .unwrap();
+        assert_eq!(serializer.into_inner(), plain.as_bytes());
+
+        let url_safe = Base64Encoding {
+            url_safe: true,
+            padding: false,
+        };
+        let mut serializer = serde_json::Serializer::new(std::vec::Vec::new());
+        option
+            .as_dyn()
+            .serialize(ProtoJsonSerializer::new(&mut serializer).with_base64(url_safe))
+            .unwrap();
+        assert_eq!(serializer.into_inner(), br#"{"stringValue":"-_8"}"#);
+
+        // Either flavour parses back
+        for json in [plain.as_str(), r#"{"stringValue":"-_8"}"#] {
+            let mut parsed = UninterpretedOption::ProtoType::default();
+            let mut deserializer = serde_json::Deserializer::from_str(json);
+            parsed.serde_deserialize(&mut arena, &mut deserializer).unwrap();
+            assert_eq!(parsed.string_value(), [0xfb, 0xff]);
+        }
+    }
+
+    #[cfg(feature = "serde_support")]
+    #[test]
+    fn json_any_with_well_known_types() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+        use crate::google::protobuf::FileDescriptorProto;
+        use crate::proto_json::ProtoJsonSerializer;
+        use crate::serde::with_type_resolver;
+        use crate::well_known::Any;
+        use ::serde::Serialize;
+
+        fn parse(arena: &mut crate::arena::Arena, json: &str) -> Result<Any, serde_json::Error> {
+            let mut any = Any::default();
+            let mut deserializer = serde_json::Deserializer::from_str(json);
+            any.serde_deserialize(arena, &mut deserializer)?;
+            Ok(any)
+        }
+
+        fn print(any: &Any) -> Result<std::string::String, serde_json::Error> {
+            let mut serializer = serde_json::Serializer::new(std::vec::Vec::new());
+            any.as_dyn().serialize(ProtoJsonSerializer::new(&mut serializer))?;
+            Ok(std::string::String::from_utf8(serializer.into_inner()).unwrap())
+        }
+
+        // google.protobuf.Int64Value isn't built in, declare it in a pool
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut wrappers = FileDescriptorProto::ProtoType::default();
+        wrappers.set_name("google/protobuf/wrappers.proto", &mut arena).unwrap();
+        wrappers.set_package("google.protobuf", &mut arena).unwrap();
+        wrappers.set_syntax("proto3", &mut arena).unwrap();
+        let message = wrappers.add_message_type(&mut arena).unwrap();
+        message.set_name("Int64Value", &mut arena).unwrap();
+        let field = message.add_field(&mut arena).unwrap();
+        field.set_name("value", &mut arena).unwrap();
+        field.set_number(1);
+        field.set_label(Label::LABEL_OPTIONAL);
+        field.set_type(Type::TYPE_INT64);
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(&wrappers).unwrap();
+        pool.add_file(FileDescriptorProto::ProtoType::file_descriptor())
+            .unwrap();
+
+        with_type_resolver(&pool, || {
+            for json in [
+                r#"{"@type":"type.googleapis.com/google.protobuf.Timestamp","value":"1970-01-01T00:00:01Z"}"#,
+                r#"{"@type":"type.googleapis.com/google.protobuf.Duration","value":"-2s"}"#,
+                r#"{"@type":"type.googleapis.com/google.protobuf.Struct","value":{"a":[true,null,"x"]}}"#,
+                r#"{"@type":"type.googleapis.com/google.protobuf.Int64Value","value":"5"}"#,
+                r#"{"@type":"type.googleapis.com/google.protobuf.FileDescriptorProto","name":"x","publicDependency":[1]}"#,
+                r#"{"@type":"type.googleapis.com/google.protobuf.Any","value":{"@type":"type.googleapis.com/google.protobuf.Duration","value":"1s"}}"#,
+                "{}",
+            ] {
+                let any = parse(&mut arena, json).unwrap();
+                assert_eq!(print(&any).unwrap(), json);
+            }
+
+            let any = parse(
+                &mut arena,
+                r#"{"value":"1970-01-01T00:00:03Z","@type":"type.googleapis.com/google.protobuf.Timestamp"}"#,
+            )
+            .unwrap();
+            let mut timestamp = crate::well_known::Timestamp::default();
+            assert!(timestamp.decode_flat::<32>(&mut arena, any.value()));
+            assert_eq!((timestamp.seconds(), timestamp.nanos()), (3, 0));
+
+            // A well-known type goes under "value" only, a regular message never does
+            for json in [
+                r#"{"@type":"type.googleapis.com/google.protobuf.Duration","seconds":1}"#,
+                r#"{"@type":"type.googleapis.com/google.protobuf.FileDescriptorProto","value":"x"}"#,
+                r#"{"value":"1s"}"#,
+                r#"{"@type":"type.googleapis.com/unknown.Type"}"#,
+            ] {
+                assert!(parse(&mut arena, json).is_err(), "{json}");
+            }
+        });
+
+        // Without the pool only the built-in well-known types resolve
+        let any = parse(&mut arena, r#"{"@type":"x/google.protobuf.Duration","value":"1s"}"#).unwrap();
+        assert!(print(&any).is_ok());
+        let mut any = Any::default();
+        any.set_type_url("x/google.protobuf.FileDescriptorProto", &mut arena)
+            .unwrap();
+        assert!(print(&any).is_err());
+    }
+
+    #[test]
+    fn typed_field_getters() {
+        use crate::google::protobuf::FileDescriptorProto;
+
+        let file_desc = FileDescriptorProto::ProtoType::file_descriptor();
+        let file = file_desc.as_dyn();
+        assert_eq!(file.get_str("name"), Some("google/protobuf/descriptor.proto"));
+        assert_eq!(file.get_i32("name"), None);
+        assert_eq!(file.get_str("no_such_field"), None);
+        // Repeated fields aren't singular
+        assert_eq!(file.get_str("dependency"), None);
+
+        let names: std::vec::Vec<_> = file
+            .repeated_messages("message_type")
+            .map(|m| m.get_str("name").unwrap())
+            .collect();
+        assert_eq!(names.len(), file_desc.message_type().len());
+        assert_eq!(names[0], file_desc.message_type()[0].name());
+        assert_eq!(file.repeated_messages("name").count(), 0);
+
+        let options = file.get_message("options").unwrap();
+        assert_eq!(options.get_str("java_package"), Some("com.google.protobuf"));
+        // Unset fields read as defaults
+        assert_eq!(options.get_bool("deprecated"), Some(false));
+        assert!(file.get_message("name").is_none());
+
+        let field = file
+            .repeated_messages("message_type")
+            .flat_map(|m| m.repeated_messages("field"))
+            .find(|f| f.get_str("name") == Some("number"))
+            .unwrap();
+        assert_eq!(field.get_i32("type"), Some(5)); // TYPE_INT32
+    }
+
+    #[test]
+    fn sequential_async_decodes_share_arena() {
+        use crate::google::protobuf::FileDescriptorProto;
+
+        let encoded = FileDescriptorProto::ProtoType::file_descriptor()
+            .encode_vec::<32>()
+            .unwrap();
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut first = FileDescriptorProto::ProtoType::default();
+        let mut second = FileDescriptorProto::ProtoType::default();
+        futures::executor::block_on(async {
+            let mut reader = futures::io::BufReader::with_capacity(64, &encoded[..]);
+            first
+                .decode_from_async_bufread::<32>(&mut arena, &mut reader)
+                .await
+                .unwrap();
+            second
+                .decode_from_async_read::<32>(&mut arena, &mut &encoded[..])
+                .await
+                .unwrap();
+        });
+        // The arena is free again once the futures are done
+        let mut third = FileDescriptorProto::ProtoType::default();
+        assert!(third.decode_flat::<32>(&mut arena, &encoded));
+        assert_eq!(first.encode_vec::<32>().unwrap(), encoded);
+        assert_eq!(second.encode_vec::<32>().unwrap(), encoded);
+        assert_eq!(third.name(), first.name());
+    }
+
+    #[test]
+    fn error_conversions() {
+        use crate::Error;
+
+        let error: Error<std::io::Error> = Error::InvalidProtobufData.with_io();
+        assert!(matches!(error, Error::InvalidProtobufData));
+        assert_eq!(error.to_string(), "invalid protobuf data");
+
+        let io = Error::Io(std::io::Error::other("eof")).map_io(|e| e.kind());
+        assert!(matches!(io, Error::Io(std::io::ErrorKind::Other)));
+        assert_eq!(io.to_string(), "I/O error: Other");
+        assert!(matches!(Error::<()>::Io(()).with_io::<u8>(), Error::UnknownError));
+
+        // Usable with `?` wherever a boxed error is expected
+        fn decode(bytes: &[u8]) -> Result<(), std::boxed::Box<dyn core::error::Error>> {
+            let mut arena = crate::arena::Arena::new(&Global);
+            let mut msg = crate::google::protobuf::FileDescriptorProto::ProtoType::default();
+            if !msg.decode_flat::<32>(&mut arena, bytes) {
+                return Err(Error::<()>::InvalidProtobufData.into());
+            }
+            Ok(())
+        }
+        assert!(decode(&[]).is_ok());
+        assert_eq!(decode(&[0xff]).unwrap_err().to_string(), "invalid protobuf data");
+    }
+
+    #[test]
+    fn clone_in_and_plain_copies() {
+        use crate::google::protobuf::{DescriptorProto, FileDescriptorProto};
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let copy = {
+            let mut source_arena = crate::arena::Arena::new(&Global);
+            let file = FileDescriptorProto::ProtoType::file_descriptor();
+            let mut source = FileDescriptorProto::ProtoType::default();
+            assert!(source.decode_flat::<32>(&mut source_arena, &file.encode_vec::<32>().unwrap()));
+            source.clone_in(&mut arena).unwrap()
+        };
+        assert_eq!(copy.name(), "google/protobuf/descriptor.proto");
+        assert_eq!(
+            copy.encode_vec::<32>().unwrap(),
+            FileDescriptorProto::ProtoType::file_descriptor().encode_vec::<32>().unwrap()
+        );
+
+        // Messages of only scalars are Copy
+        let mut range = DescriptorProto::ReservedRange::ProtoType::default();
+        range.set_start(1);
+        let mut other = range;
+        other.set_end(2);
+        assert!(!range.has_end());
+        assert_eq!((other.start(), other.end()), (1, 2));
+    }
+
+    #[test]
+    fn static_setters_borrow_data() {
+        use crate::google::protobuf::FileDescriptorProto;
+
+        const NAME: &str = "static.proto";
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name_static(NAME);
+        assert!(file.has_name());
+        assert_eq!(file.name().as_ptr(), NAME.as_ptr());
+        let encoded = file.encode_vec::<32>().unwrap();
+
+        file.set_name("arena.proto", &mut arena).unwrap();
+        assert_eq!(file.name(), "arena.proto");
+        assert_eq!(NAME, "static.proto");
+
+        let mut decoded = FileDescriptorProto::ProtoType::default();
+        assert!(decoded.decode_flat::<32>(&mut arena, &encoded));
+        assert_eq!(decoded.name(), NAME);
+    }
+
+    #[test]
+    fn try_from_bytes_and_into_vec() {
+        use crate::google::protobuf::FileDescriptorProto;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("convert.proto", &mut arena).unwrap();
+        let bytes = file.try_into_vec().unwrap();
+
+        let decoded = FileDescriptorProto::ProtoType::try_from((&bytes[..], &mut arena)).unwrap();
+        assert_eq!(decoded.name(), "convert.proto");
+        let err = FileDescriptorProto::ProtoType::try_from((&[0xff][..], &mut arena)).unwrap_err();
+        assert!(matches!(err, crate::Error::InvalidProtobufData));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn decode_memory_mapped_file() {
+        use crate::google::protobuf::FileDescriptorProto;
+
+        let encoded = FileDescriptorProto::ProtoType::file_descriptor()
+            .encode_vec::<32>()
+            .unwrap();
+        let dir = std::env::temp_dir().join(std::format!("protocrap-mmap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("descriptor.pb");
+        std::fs::write(&path, &encoded).unwrap();
+        std::fs::write(dir.join("empty.pb"), b"").unwrap();
+        std::fs::write(dir.join("bad.pb"), [0x0a]).unwrap();
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.decode_from_mmap::<32>(&mut arena, &path).unwrap();
+        assert_eq!(file.encode_vec::<32>().unwrap(), encoded);
+
+        let mut empty = FileDescriptorProto::ProtoType::default();
+        empty.decode_from_mmap::<32>(&mut arena, dir.join("empty.pb")).unwrap();
+        assert!(matches!(
+            empty.decode_from_mmap::<32>(&mut arena, dir.join("bad.pb")),
+            Err(crate::Error::InvalidProtobufData)
+        ));
+        assert!(matches!(
+            empty.decode_from_mmap::<32>(&mut arena, dir.join("missing.pb")),
+            Err(crate::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "serde_support")]
+    #[test]
+    fn json_int64_strings_and_numbers() {
+        use crate::google::protobuf::UninterpretedOption;
+        use crate::proto_json::ProtoJsonSerializer;
+        use ::serde::Serialize;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut option = UninterpretedOption::ProtoType::default();
+        option.set_positive_int_value(u64::MAX);
+        option.set_negative_int_value(i64::MIN);
+
+        // Every JSON path writes strings by default
+        let strings = r#"{"positiveIntValue":"18446744073709551615","negativeIntValue":"-9223372036854775808"}"#;
+        assert_eq!(serde_json::to_string(&option.as_dyn()).unwrap(), strings);
+        let mut serializer = serde_json::Serializer::new(std::vec::Vec::new());
+        option
+            .as_dyn()
+            .serialize(ProtoJsonSerializer::new(&mut serializer))
+            .unwrap();
+        assert_eq!(serializer.into_inner(), strings.as_bytes());
+
+        let numbers = r#"{"positiveIntValue":18446744073709551615,"negativeIntValue":-9223372036854775808}"#;
+        let mut serializer = serde_json::Serializer::new(std::vec::Vec::new());
+        option
+            .as_dyn()
+            .serialize(ProtoJsonSerializer::new(&mut serializer).with_int64_as_number(true))
+            .unwrap();
+        assert_eq!(serializer.into_inner(), numbers.as_bytes());
+
+        // Plain serde_json parses both
+        for json in [strings, numbers] {
+            let mut parsed = UninterpretedOption::ProtoType::default();
+            parsed
+                .serde_deserialize(&mut arena, &mut serde_json::Deserializer::from_str(json))
+                .unwrap();
+            assert_eq!(parsed.positive_int_value(), u64::MAX);
+            assert_eq!(parsed.negative_int_value(), i64::MIN);
+        }
+        let mut parsed = UninterpretedOption::ProtoType::default();
+        let negative = r#"{"positiveIntValue":"-1"}"#;
+        assert!(parsed
+            .serde_deserialize(&mut arena, &mut serde_json::Deserializer::from_str(negative))
+            .is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_value_round_trip() {
+        use crate::google::protobuf::FieldDescriptorProto;
+        use crate::google::protobuf::FieldDescriptorProto::Type;
+        use crate::generated_code_only::Protobuf;
+        use crate::reflection::DynamicMessage;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut field = FieldDescriptorProto::ProtoType::default();
+        field.set_name("id", &mut arena).unwrap();
+        field.set_number(1);
+        field.set_type(Type::TYPE_INT64);
+
+        let mut value = field.as_dyn().to_json_value().unwrap();
+        assert_eq!(value, serde_json::json!({"name": "id", "number": 1, "type": "TYPE_INT64"}));
+
+        value["number"] = serde_json::json!(2);
+        value["jsonName"] = serde_json::json!("ident");
+        let table = FieldDescriptorProto::ProtoType::table();
+        let mut msg = DynamicMessage::from_json_value(table, &value, &mut arena).unwrap();
+        let edited = msg.downcast::<FieldDescriptorProto::ProtoType>().unwrap();
+        assert_eq!(edited.number(), 2);
+        assert_eq!(edited.json_name(), "ident");
+        assert_eq!(edited.r#type(), Some(Type::TYPE_INT64));
+
+        assert!(DynamicMessage::from_json_value(table, &serde_json::json!({"number": "x"}), &mut arena).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn query_json_paths() {
+        use crate::google::protobuf::FieldDescriptorProto::Type;
+        use crate::google::protobuf::FileDescriptorProto;
+        use serde_json::json;
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("query.proto", &mut arena).unwrap();
+        let message = file.add_message_type(&mut arena).unwrap();
+        message.set_name("Foo", &mut arena).unwrap();
+        for (name, number) in [("bar", 1), ("baz_qux", 2)] {
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_type(Type::TYPE_INT64);
+        }
+        let msg = file.as_dyn();
+
+        assert_eq!(msg.query_json("$.name").unwrap(), Some(json!("query.proto")));
+        assert_eq!(msg.query_json("$.messageType[0].field[1].name").unwrap(), Some(json!("baz_qux")));
+        assert_eq!(msg.query_json("$.message_type[0][\"field\"][0].type").unwrap(), Some(json!("TYPE_INT64")));
+        assert_eq!(msg.query_json("$.messageType[0].field[0]").unwrap(), Some(json!({"name": "bar", "number": 1, "type": "TYPE_INT64"})));
+        assert_eq!(msg.query_json("$.messageType[0].field[1].number").unwrap(), Some(json!(2)));
+        assert_eq!(msg.query_json("$").unwrap(), Some(file.as_dyn().to_json_value().unwrap()));
+        assert_eq!(msg.query_json("$.messageType[1]").unwrap(), None);
+        assert_eq!(msg.query_json("$.package").unwrap(), None);
+        assert_eq!(msg.query_json("$.name[0]").unwrap(), None);
+        assert!(msg.query_json("$.nope").is_err());
+        assert!(msg.query_json("messageType").is_err());
+        assert!(msg.query_json("$.messageType[x]").is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_floats_match_protobuf() {
+        use crate::google::protobuf::UninterpretedOption;
+        use crate::generated_code_only::Protobuf;
+        use crate::proto_json::{ProtoDouble, ProtoFloat, ProtoJsonFormatter, ProtoJsonSerializer};
+        use serde::Serialize;
+
+        for (v, text) in [
+            (1.0, "1"),
+            (-0.0, "-0"),
+            (0.1, "0.1"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (123456789012345680.0, "1.2345678901234568e+17"),
+            (1e21, "1e+21"),
+            (1e-5, "1e-05"),
+            (0.0001, "0.0001"),
+            (-2.22507e-308, "-2.22507e-308"),
+            (f64::MAX, "1.7976931348623157e+308"),
+            (5e-324, "4.94065645841247e-324"),
+        ] {
+            assert_eq!(ProtoDouble(v).to_string(), text);
+        }
+        for (v, text) in [(1.0, "1"), (0.1, "0.1"), (3.4028235e38, "3.40282347e+38"), (1e-7, "1e-07")] {
+            assert_eq!(ProtoFloat(v).to_string(), text);
+        }
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut msg = UninterpretedOption::ProtoType::default();
+        msg.set_double_value(2.22507e-308);
+        let mut serializer = serde_json::Serializer::with_formatter(Vec::new(), ProtoJsonFormatter);
+        msg.as_dyn().serialize(ProtoJsonSerializer::new(&mut serializer)).unwrap();
+        let json = String::from_utf8(serializer.into_inner()).unwrap();
+        assert_eq!(json, r#"{"doubleValue":2.22507e-308}"#);
+
+        let mut parsed = UninterpretedOption::ProtoType::default();
+        parsed
+            .serde_deserialize(&mut arena, &mut serde_json::Deserializer::from_str(&json))
+            .unwrap();
+        assert_eq!(parsed.double_value(), 2.22507e-308);
+
+        let bytes = msg.encode_vec::<32>().unwrap();
+        let mut streamed = Vec::new();
+        crate::json_stream::write_json(UninterpretedOption::ProtoType::table(), &bytes, &mut streamed).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), json);
+    }
 }
@@ -4,7 +4,12 @@
 //! standard serde serializers/deserializers to apply proto JSON spec transformations:
 //!
 //! - Float NaN/Infinity as strings: `"NaN"`, `"Infinity"`, `"-Infinity"`
-//! - Bytes as base64-encoded strings
+//! - Bytes as base64-encoded strings, in a configurable [`Base64Encoding`]
+//! - 64-bit integers as strings, or as numbers with
+//!   [`ProtoJsonSerializer::with_int64_as_number`]
+//!
+//! With the `json` feature, [`ProtoJsonFormatter`] makes `serde_json` write
+//! floats in protobuf's own formatting.
 //!
 //! Well-known type handling (Timestamp, Duration, wrappers) remains in the base
 //! `Serialize` impl using `is_human_readable()`.
@@ -15,20 +20,277 @@ use serde::ser::{
     SerializeTupleStruct, SerializeTupleVariant,
 };
 
+/// Base64 flavour written for bytes values.
+///
+/// Parsing accepts either alphabet with or without padding, so this only
+/// affects output. The default is the standard alphabet with padding, as the
+/// proto3 JSON spec prescribes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Base64Encoding {
+    /// Use the URL-safe alphabet (`-` and `_` instead of `+` and `/`).
+    pub url_safe: bool,
+    /// Pad the output with `=` to a multiple of 4 characters.
+    pub padding: bool,
+}
+
+impl Default for Base64Encoding {
+    fn default() -> Self {
+        Base64Encoding {
+            url_safe: false,
+            padding: true,
+        }
+    }
+}
+
+impl Base64Encoding {
+    pub fn encode(&self, bytes: &[u8]) -> String {
+        use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+        match (self.url_safe, self.padding) {
+            (false, true) => STANDARD.encode(bytes),
+            (false, false) => STANDARD_NO_PAD.encode(bytes),
+            (true, true) => URL_SAFE.encode(bytes),
+            (true, false) => URL_SAFE_NO_PAD.encode(bytes),
+        }
+    }
+}
+
+/// Decode base64 in either alphabet, with or without padding.
+pub(crate) fn decode_base64(v: &str) -> Option<Vec<u8>> {
+    use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+    let config = GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+        .with_decode_allow_trailing_bits(true);
+    // Try standard alphabet first, then URL-safe
+    let standard = GeneralPurpose::new(&base64::alphabet::STANDARD, config);
+    let url_safe = GeneralPurpose::new(&base64::alphabet::URL_SAFE, config);
+    standard.decode(v).or_else(|_| url_safe.decode(v)).ok()
+}
+
+/// Newtype struct name marking bytes values, so `ProtoJsonSerializer` can
+/// encode them with its own [`Base64Encoding`].
+const BYTES_TOKEN: &str = "$protocrap::private::Bytes";
+
+/// Serialize a bytes field value.
+///
+/// Human-readable serializers get base64 text: in the encoding of the
+/// enclosing `ProtoJsonSerializer`, or the default one for any other
+/// serializer. Binary formats get the raw bytes.
+pub(crate) fn serialize_bytes<S: serde::Serializer>(
+    serializer: S,
+    bytes: &[u8],
+) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.serialize_newtype_struct(BYTES_TOKEN, &Base64Bytes(bytes))
+    } else {
+        serializer.serialize_bytes(bytes)
+    }
+}
+
+struct Base64Bytes<'a>(&'a [u8]);
+
+impl serde::Serialize for Base64Bytes<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&Base64Encoding::default().encode(self.0))
+        } else {
+            // ProtoJsonSerializer asks for the raw bytes to encode them itself
+            serializer.serialize_bytes(self.0)
+        }
+    }
+}
+
+/// Newtype struct name marking 64-bit integer values, so `ProtoJsonSerializer`
+/// can write them as numbers when asked to.
+const INT64_TOKEN: &str = "$protocrap::private::Int64";
+
+/// A 64-bit integer field value.
+///
+/// Human-readable serializers get a decimal string, as proto JSON prescribes
+/// to keep JavaScript's doubles from rounding it, unless an enclosing
+/// `ProtoJsonSerializer` writes numbers, see
+/// [`with_int64_as_number`](ProtoJsonSerializer::with_int64_as_number).
+/// Binary formats get the number.
+pub(crate) struct Int64<T>(pub T);
+
+impl<T: serde::Serialize + core::fmt::Display> serde::Serialize for Int64<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_newtype_struct(INT64_TOKEN, &Int64Text(&self.0))
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+struct Int64Text<'a, T>(&'a T);
+
+impl<T: serde::Serialize + core::fmt::Display> serde::Serialize for Int64Text<'_, T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self.0)
+        } else {
+            // ProtoJsonSerializer asks for the number to write it as configured
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+/// Accepts a 64-bit integer as a JSON number or string from human-readable
+/// deserializers, as proto JSON parsers must.
+impl<'de, T> serde::Deserialize<'de> for Int64<T>
+where
+    T: serde::Deserialize<'de> + TryFrom<i64> + TryFrom<u64>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return T::deserialize(deserializer).map(Int64);
+        }
+        deserializer
+            .deserialize_any(FlexibleIntVisitor(Int64Visitor(core::marker::PhantomData)))
+            .map(Int64)
+    }
+}
+
+struct Int64Visitor<T>(core::marker::PhantomData<T>);
+
+impl<'de, T: TryFrom<i64> + TryFrom<u64>> Visitor<'de> for Int64Visitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter.write_str("a 64-bit integer as a number or string")
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<T, E> {
+        T::try_from(v).map_err(|_| E::invalid_value(serde::de::Unexpected::Signed(v), &self))
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<T, E> {
+        T::try_from(v).map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+    }
+}
+
+/// A finite `double` as protobuf's JSON writer prints it: `%.15g` if that reads
+/// back as the same value, `%.17g` otherwise. Integral values have no `.0`
+/// and exponents are signed with at least two digits, as in `1e+21`.
+pub(crate) struct ProtoDouble(pub f64);
+
+impl core::fmt::Display for ProtoDouble {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let short = format_g(self.0, 15);
+        if short.parse::<f64>() == Ok(self.0) {
+            f.write_str(&short)
+        } else {
+            f.write_str(&format_g(self.0, 17))
+        }
+    }
+}
+
+/// A finite `float` as protobuf's JSON writer prints it: `%.6g` if that reads
+/// back as the same `float`, `%.9g` otherwise.
+pub(crate) struct ProtoFloat(pub f32);
+
+impl core::fmt::Display for ProtoFloat {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let short = format_g(self.0 as f64, 6);
+        if short.parse::<f32>() == Ok(self.0) {
+            f.write_str(&short)
+        } else {
+            f.write_str(&format_g(self.0 as f64, 9))
+        }
+    }
+}
+
+/// `v` as C's `printf("%.{precision}g")` writes it.
+fn format_g(v: f64, precision: usize) -> String {
+    // %g picks the style from the exponent of the rounded scientific form
+    let scientific = format!("{:.*e}", precision - 1, v);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    if exponent < -4 || exponent >= precision as i32 {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim_fraction(mantissa), sign, exponent.unsigned_abs())
+    } else {
+        let fixed = format!("{:.*}", (precision as i32 - 1 - exponent) as usize, v);
+        trim_fraction(&fixed).to_string()
+    }
+}
+
+/// `s` without trailing zeros after the decimal point, nor a bare point.
+fn trim_fraction(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
+
+/// A [`serde_json`] formatter writing floats as protobuf does, so JSON output
+/// matches other implementations byte for byte.
+///
+/// serde_json's own formatting writes the shortest digits that read back, but
+/// keeps a `.0` on integral values and spells exponents differently
+/// (`1e21` rather than `1e+21`). Pass it to `serde_json::Serializer::with_formatter`
+/// and wrap that serializer in a [`ProtoJsonSerializer`].
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtoJsonFormatter;
+
+#[cfg(feature = "json")]
+impl serde_json::ser::Formatter for ProtoJsonFormatter {
+    fn write_f32<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, value: f32) -> std::io::Result<()> {
+        write!(writer, "{}", ProtoFloat(value))
+    }
+
+    fn write_f64<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, value: f64) -> std::io::Result<()> {
+        write!(writer, "{}", ProtoDouble(value))
+    }
+}
+
+/// What a `ProtoJsonSerializer` passes on to the serializers of nested values.
+#[derive(Clone, Copy, Default)]
+struct Options {
+    base64: Base64Encoding,
+    int64_as_number: bool,
+}
+
 /// A serde Serializer wrapper that applies proto JSON transformations.
 ///
 /// Wraps any serde Serializer and transforms:
 /// - `f32::NAN` / `f64::NAN` → `"NaN"`
 /// - `f32::INFINITY` / `f64::INFINITY` → `"Infinity"`
 /// - `f32::NEG_INFINITY` / `f64::NEG_INFINITY` → `"-Infinity"`
-/// - bytes → base64-encoded string
+/// - bytes → base64-encoded string, see [`with_base64`](Self::with_base64)
+/// - 64-bit integers → decimal string, see
+///   [`with_int64_as_number`](Self::with_int64_as_number)
 pub struct ProtoJsonSerializer<S> {
     inner: S,
+    options: Options,
+    /// Set while serializing a `Base64Bytes` or `Int64Text`, to receive its
+    /// raw value.
+    raw: bool,
 }
 
 impl<S> ProtoJsonSerializer<S> {
     pub fn new(inner: S) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            options: Options::default(),
+            raw: false,
+        }
+    }
+
+    /// Write bytes values in `base64` instead of the default encoding.
+    pub fn with_base64(mut self, base64: Base64Encoding) -> Self {
+        self.options.base64 = base64;
+        self
+    }
+
+    /// Write 64-bit integers as JSON numbers instead of the strings proto
+    /// JSON prescribes, for consumers that parse them without losing
+    /// precision. Parsing accepts either form.
+    pub fn with_int64_as_number(mut self, int64_as_number: bool) -> Self {
+        self.options.int64_as_number = int64_as_number;
+        self
     }
 
     pub fn into_inner(self) -> S {
@@ -65,7 +327,11 @@ impl<S: serde::Serializer> serde::Serializer for ProtoJsonSerializer<S> {
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
         // Proto JSON spec: int64 as string for JavaScript precision
-        self.inner.serialize_str(&v.to_string())
+        if self.options.int64_as_number {
+            self.inner.serialize_i64(v)
+        } else {
+            self.inner.serialize_str(&v.to_string())
+        }
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
@@ -82,7 +348,11 @@ impl<S: serde::Serializer> serde::Serializer for ProtoJsonSerializer<S> {
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
         // Proto JSON spec: uint64 as string for JavaScript precision
-        self.inner.serialize_str(&v.to_string())
+        if self.options.int64_as_number {
+            self.inner.serialize_u64(v)
+        } else {
+            self.inner.serialize_str(&v.to_string())
+        }
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
@@ -118,8 +388,7 @@ impl<S: serde::Serializer> serde::Serializer for ProtoJsonSerializer<S> {
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        let encoded = base64::engine::general_purpose::STANDARD.encode(v);
-        self.inner.serialize_str(&encoded)
+        self.inner.serialize_str(&self.options.base64.encode(v))
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
@@ -156,9 +425,12 @@ impl<S: serde::Serializer> serde::Serializer for ProtoJsonSerializer<S> {
         name: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error> {
+        if name == BYTES_TOKEN || name == INT64_TOKEN {
+            return value.serialize(ProtoJsonSerializer { raw: true, ..self });
+        }
         // Serialize through our wrapper to apply transformations
         self.inner
-            .serialize_newtype_struct(name, &ProtoJsonValue(value))
+            .serialize_newtype_struct(name, &ProtoJsonValue(value, self.options))
     }
 
     fn serialize_newtype_variant<T: ?Sized + serde::Serialize>(
@@ -169,18 +441,20 @@ impl<S: serde::Serializer> serde::Serializer for ProtoJsonSerializer<S> {
         value: &T,
     ) -> Result<Self::Ok, Self::Error> {
         self.inner
-            .serialize_newtype_variant(name, variant_index, variant, &ProtoJsonValue(value))
+            .serialize_newtype_variant(name, variant_index, variant, &ProtoJsonValue(value, self.options))
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
         Ok(ProtoJsonSerializeSeq {
             inner: self.inner.serialize_seq(len)?,
+            options: self.options,
         })
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
         Ok(ProtoJsonSerializeTuple {
             inner: self.inner.serialize_tuple(len)?,
+            options: self.options,
         })
     }
 
@@ -191,6 +465,7 @@ impl<S: serde::Serializer> serde::Serializer for ProtoJsonSerializer<S> {
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
         Ok(ProtoJsonSerializeTupleStruct {
             inner: self.inner.serialize_tuple_struct(name, len)?,
+            options: self.options,
         })
     }
 
@@ -205,12 +480,14 @@ impl<S: serde::Serializer> serde::Serializer for ProtoJsonSerializer<S> {
             inner: self
                 .inner
                 .serialize_tuple_variant(name, variant_index, variant, len)?,
+            options: self.options,
         })
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
         Ok(ProtoJsonSerializeMap {
             inner: self.inner.serialize_map(len)?,
+            options: self.options,
         })
     }
 
@@ -221,6 +498,7 @@ impl<S: serde::Serializer> serde::Serializer for ProtoJsonSerializer<S> {
     ) -> Result<Self::SerializeStruct, Self::Error> {
         Ok(ProtoJsonSerializeStruct {
             inner: self.inner.serialize_struct(name, len)?,
+            options: self.options,
         })
     }
 
@@ -235,20 +513,26 @@ impl<S: serde::Serializer> serde::Serializer for ProtoJsonSerializer<S> {
             inner: self
                 .inner
                 .serialize_struct_variant(name, variant_index, variant, len)?,
+            options: self.options,
         })
     }
 
     fn is_human_readable(&self) -> bool {
-        true // Proto JSON is always human-readable
+        // Proto JSON is always human-readable
+        !self.raw
     }
 }
 
 /// Wrapper to serialize a value through ProtoJsonSerializer
-struct ProtoJsonValue<'a, T: ?Sized>(&'a T);
+struct ProtoJsonValue<'a, T: ?Sized>(&'a T, Options);
 
 impl<T: serde::Serialize + ?Sized> serde::Serialize for ProtoJsonValue<'_, T> {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        self.0.serialize(ProtoJsonSerializer::new(serializer))
+        self.0.serialize(ProtoJsonSerializer {
+            inner: serializer,
+            options: self.1,
+            raw: false,
+        })
     }
 }
 
@@ -257,6 +541,7 @@ impl<T: serde::Serialize + ?Sized> serde::Serialize for ProtoJsonValue<'_, T> {
 #[doc(hidden)]
 pub struct ProtoJsonSerializeSeq<S> {
     inner: S,
+    options: Options,
 }
 
 impl<S: SerializeSeq> SerializeSeq for ProtoJsonSerializeSeq<S> {
@@ -267,7 +552,7 @@ impl<S: SerializeSeq> SerializeSeq for ProtoJsonSerializeSeq<S> {
         &mut self,
         value: &T,
     ) -> Result<(), Self::Error> {
-        self.inner.serialize_element(&ProtoJsonValue(value))
+        self.inner.serialize_element(&ProtoJsonValue(value, self.options))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -278,6 +563,7 @@ impl<S: SerializeSeq> SerializeSeq for ProtoJsonSerializeSeq<S> {
 #[doc(hidden)]
 pub struct ProtoJsonSerializeTuple<S> {
     inner: S,
+    options: Options,
 }
 
 impl<S: SerializeTuple> SerializeTuple for ProtoJsonSerializeTuple<S> {
@@ -288,7 +574,7 @@ impl<S: SerializeTuple> SerializeTuple for ProtoJsonSerializeTuple<S> {
         &mut self,
         value: &T,
     ) -> Result<(), Self::Error> {
-        self.inner.serialize_element(&ProtoJsonValue(value))
+        self.inner.serialize_element(&ProtoJsonValue(value, self.options))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -299,6 +585,7 @@ impl<S: SerializeTuple> SerializeTuple for ProtoJsonSerializeTuple<S> {
 #[doc(hidden)]
 pub struct ProtoJsonSerializeTupleStruct<S> {
     inner: S,
+    options: Options,
 }
 
 impl<S: SerializeTupleStruct> SerializeTupleStruct for ProtoJsonSerializeTupleStruct<S> {
@@ -309,7 +596,7 @@ impl<S: SerializeTupleStruct> SerializeTupleStruct for ProtoJsonSerializeTupleSt
         &mut self,
         value: &T,
     ) -> Result<(), Self::Error> {
-        self.inner.serialize_field(&ProtoJsonValue(value))
+        self.inner.serialize_field(&ProtoJsonValue(value, self.options))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -320,6 +607,7 @@ impl<S: SerializeTupleStruct> SerializeTupleStruct for ProtoJsonSerializeTupleSt
 #[doc(hidden)]
 pub struct ProtoJsonSerializeTupleVariant<S> {
     inner: S,
+    options: Options,
 }
 
 impl<S: SerializeTupleVariant> SerializeTupleVariant for ProtoJsonSerializeTupleVariant<S> {
@@ -330,7 +618,7 @@ impl<S: SerializeTupleVariant> SerializeTupleVariant for ProtoJsonSerializeTuple
         &mut self,
         value: &T,
     ) -> Result<(), Self::Error> {
-        self.inner.serialize_field(&ProtoJsonValue(value))
+        self.inner.serialize_field(&ProtoJsonValue(value, self.options))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -341,6 +629,7 @@ impl<S: SerializeTupleVariant> SerializeTupleVariant for ProtoJsonSerializeTuple
 #[doc(hidden)]
 pub struct ProtoJsonSerializeMap<S> {
     inner: S,
+    options: Options,
 }
 
 impl<S: SerializeMap> SerializeMap for ProtoJsonSerializeMap<S> {
@@ -348,14 +637,14 @@ impl<S: SerializeMap> SerializeMap for ProtoJsonSerializeMap<S> {
     type Error = S::Error;
 
     fn serialize_key<T: ?Sized + serde::Serialize>(&mut self, key: &T) -> Result<(), Self::Error> {
-        self.inner.serialize_key(&ProtoJsonValue(key))
+        self.inner.serialize_key(&ProtoJsonValue(key, self.options))
     }
 
     fn serialize_value<T: ?Sized + serde::Serialize>(
         &mut self,
         value: &T,
     ) -> Result<(), Self::Error> {
-        self.inner.serialize_value(&ProtoJsonValue(value))
+        self.inner.serialize_value(&ProtoJsonValue(value, self.options))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -366,6 +655,7 @@ impl<S: SerializeMap> SerializeMap for ProtoJsonSerializeMap<S> {
 #[doc(hidden)]
 pub struct ProtoJsonSerializeStruct<S> {
     inner: S,
+    options: Options,
 }
 
 impl<S: SerializeStruct> SerializeStruct for ProtoJsonSerializeStruct<S> {
@@ -377,7 +667,7 @@ impl<S: SerializeStruct> SerializeStruct for ProtoJsonSerializeStruct<S> {
         key: &'static str,
         value: &T,
     ) -> Result<(), Self::Error> {
-        self.inner.serialize_field(key, &ProtoJsonValue(value))
+        self.inner.serialize_field(key, &ProtoJsonValue(value, self.options))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -388,6 +678,7 @@ impl<S: SerializeStruct> SerializeStruct for ProtoJsonSerializeStruct<S> {
 #[doc(hidden)]
 pub struct ProtoJsonSerializeStructVariant<S> {
     inner: S,
+    options: Options,
 }
 
 impl<S: SerializeStructVariant> SerializeStructVariant for ProtoJsonSerializeStructVariant<S> {
@@ -399,7 +690,7 @@ impl<S: SerializeStructVariant> SerializeStructVariant for ProtoJsonSerializeStr
         key: &'static str,
         value: &T,
     ) -> Result<(), Self::Error> {
-        self.inner.serialize_field(key, &ProtoJsonValue(value))
+        self.inner.serialize_field(key, &ProtoJsonValue(value, self.options))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -804,22 +1095,10 @@ impl<'de, V: Visitor<'de>> Visitor<'de> for FlexibleBytesVisitor<V> {
     }
 
     fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
-        use base64::Engine;
-        use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
-        // Lenient base64 decoder: accepts standard or URL-safe, with or without padding
-        let config = GeneralPurposeConfig::new()
-            .with_decode_padding_mode(DecodePaddingMode::Indifferent)
-            .with_decode_allow_trailing_bits(true);
-        // Try standard alphabet first, then URL-safe
-        let standard = GeneralPurpose::new(&base64::alphabet::STANDARD, config);
-        if let Ok(bytes) = standard.decode(v) {
-            return self.0.visit_byte_buf(bytes);
-        }
-        let url_safe = GeneralPurpose::new(&base64::alphabet::URL_SAFE, config);
-        if let Ok(bytes) = url_safe.decode(v) {
-            return self.0.visit_byte_buf(bytes);
+        match decode_base64(v) {
+            Some(bytes) => self.0.visit_byte_buf(bytes),
+            None => Err(E::custom("invalid base64")),
         }
-        Err(E::custom("invalid base64"))
     }
 
     fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
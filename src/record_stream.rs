@@ -0,0 +1,370 @@
+//! Decoding streams of length-delimited messages.
+//!
+//! Logs and dumps are often a plain concatenation of same-type messages, each
+//! preceded by its length as a varint (the format of Java's
+//! `writeDelimitedTo` and C++'s `SerializeDelimitedToOstream`).
+//! [`RecordStreamDecoder`] reads such a stream from a [`BufRead`] one record
+//! at a time, and [`AsyncRecordStreamDecoder`] from an [`AsyncBufRead`].
+//!
+//! Records are decoded straight from the reader's buffer into a single message
+//! and arena owned by the decoder, so a record is only borrowed until the next
+//! one is read. The arena is replaced once it holds more than
+//! [`ARENA_RESET_BYTES`], which keeps memory bounded on long streams without
+//! paying for an allocation per record.
+//!
+//! ```
+//! use protocrap::record_stream::RecordStreamDecoder;
+//! use protocrap::google::protobuf::FileDescriptorProto;
+//! use protocrap::wire::{Writer, varint_len};
+//! use protocrap::ProtobufRef;
+//!
+//! let file = FileDescriptorProto::ProtoType::file_descriptor();
+//! let encoded = file.encode_vec::<32>().unwrap();
+//! let mut stream = vec![0u8; 2 * (varint_len(encoded.len() as u64) + encoded.len())];
+//! let mut writer = Writer::new(&mut stream);
+//! for _ in 0..2 {
+//!     writer.write_bytes(&encoded).unwrap();
+//! }
+//!
+//! let mut records = RecordStreamDecoder::<FileDescriptorProto::ProtoType, _>::new(&stream[..]);
+//! while let Some(record) = records.next_record() {
+//!     assert_eq!(record.unwrap().name(), "google/protobuf/descriptor.proto");
+//! }
+//! ```
+
+use std::io::BufRead;
+
+use futures::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::arena::Arena;
+use crate::decoding::ResumeableDecode;
+use crate::generated_code_only::Protobuf;
+use crate::wire::Reader;
+use crate::{DecodeOptions, Error, ProtobufMut};
+
+#[cfg(not(feature = "nightly"))]
+use allocator_api2::alloc::Global;
+#[cfg(feature = "nightly")]
+use std::alloc::Global;
+
+/// Stack depth used to decode records.
+const STACK_DEPTH: usize = 64;
+
+/// Once the arena holds more than this many bytes it is replaced by a fresh
+/// one before the next record is decoded.
+pub const ARENA_RESET_BYTES: usize = 1024 * 1024;
+
+/// Collects the varint length prefix of a record one byte at a time.
+#[derive(Default)]
+struct LengthPrefix {
+    bytes: [u8; 10],
+    len: usize,
+}
+
+impl LengthPrefix {
+    /// Feed the next byte. Returns the length once the varint is complete.
+    fn push(&mut self, byte: u8) -> Result<Option<usize>, Error<std::io::Error>> {
+        let slot = self.bytes.get_mut(self.len).ok_or(Error::InvalidProtobufData)?;
+        *slot = byte;
+        self.len += 1;
+        if byte & 0x80 != 0 {
+            return Ok(None);
+        }
+        let len = Reader::new(&self.bytes[..self.len])
+            .read_varint()
+            .map_err(Error::with_io)?;
+        usize::try_from(len)
+            .map(Some)
+            .map_err(|_| Error::InvalidProtobufData)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+fn unexpected_eof() -> Error<std::io::Error> {
+    Error::Io(std::io::ErrorKind::UnexpectedEof.into())
+}
+
+/// The message and arena records are decoded into.
+struct Record<T: Protobuf> {
+    // Declared before the arena so it is dropped first.
+    msg: T,
+    arena: Arena<'static>,
+    options: DecodeOptions,
+    failed: bool,
+}
+
+impl<T: Protobuf> Record<T> {
+    fn new(options: DecodeOptions) -> Self {
+        Record {
+            msg: T::default(),
+            arena: Arena::new(&Global),
+            options,
+            failed: false,
+        }
+    }
+
+    /// Clear the previous record, recycling the arena if it grew too large,
+    /// and check the length of the next one.
+    fn start(&mut self, len: usize) -> Result<(), Error<std::io::Error>> {
+        self.msg = T::default();
+        if self.arena.bytes_allocated() > ARENA_RESET_BYTES {
+            self.arena = Arena::new(&Global);
+        }
+        if len > self.options.max_message_bytes {
+            return Err(Error::InvalidProtobufData);
+        }
+        Ok(())
+    }
+
+    /// Record the outcome of reading a record, ending the stream on errors
+    /// since the reader is no longer at a record boundary.
+    fn finish(&mut self, result: Result<(), Error<std::io::Error>>) -> Option<Result<&T, Error<std::io::Error>>> {
+        match result {
+            Ok(()) => Some(Ok(&self.msg)),
+            Err(e) => {
+                self.failed = true;
+                self.msg = T::default();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Reads length-delimited `T` records from a [`BufRead`].
+pub struct RecordStreamDecoder<T: Protobuf, R> {
+    reader: R,
+    record: Record<T>,
+}
+
+impl<T: Protobuf, R: BufRead> RecordStreamDecoder<T, R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, DecodeOptions::default())
+    }
+
+    /// Like [`new`](Self::new), applying `options` to every record.
+    /// `max_message_bytes` also bounds the length prefixes.
+    pub fn with_options(reader: R, options: DecodeOptions) -> Self {
+        RecordStreamDecoder {
+            reader,
+            record: Record::new(options),
+        }
+    }
+
+    /// Decode the next record. Returns `None` at the end of the stream and
+    /// after an error.
+    pub fn next_record(&mut self) -> Option<Result<&T, Error<std::io::Error>>> {
+        if self.record.failed {
+            return None;
+        }
+        let len = match self.read_length() {
+            Ok(Some(len)) => len,
+            Ok(None) => return None,
+            Err(e) => return self.record.finish(Err(e)),
+        };
+        let result = self.read_record(len);
+        self.record.finish(result)
+    }
+
+    /// Return the underlying reader, positioned after the last record read.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read a length prefix, or `None` on a clean end of stream.
+    fn read_length(&mut self) -> Result<Option<usize>, Error<std::io::Error>> {
+        let mut prefix = LengthPrefix::default();
+        loop {
+            let Some(&byte) = self.reader.fill_buf()?.first() else {
+                return if prefix.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(unexpected_eof())
+                };
+            };
+            self.reader.consume(1);
+            if let Some(len) = prefix.push(byte)? {
+                return Ok(Some(len));
+            }
+        }
+    }
+
+    fn read_record(&mut self, len: usize) -> Result<(), Error<std::io::Error>> {
+        self.record.start(len)?;
+        let Record { msg, arena, options, .. } = &mut self.record;
+        let mut decoder = ResumeableDecode::<STACK_DEPTH>::new(msg.as_dyn_mut(), isize::MAX, *options);
+        let mut remaining = len;
+        while remaining > 0 {
+            let buffer = self.reader.fill_buf()?;
+            if buffer.is_empty() {
+                return Err(unexpected_eof());
+            }
+            let n = buffer.len().min(remaining);
+            if !decoder.resume(&buffer[..n], arena) {
+                return Err(Error::InvalidProtobufData);
+            }
+            self.reader.consume(n);
+            remaining -= n;
+        }
+        if !decoder.finish(arena) {
+            return Err(Error::InvalidProtobufData);
+        }
+        Ok(())
+    }
+}
+
+/// Reads length-delimited `T` records from an [`AsyncBufRead`].
+pub struct AsyncRecordStreamDecoder<T: Protobuf, R> {
+    reader: R,
+    record: Record<T>,
+}
+
+impl<T: Protobuf, R: AsyncBufRead + Unpin> AsyncRecordStreamDecoder<T, R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, DecodeOptions::default())
+    }
+
+    /// Like [`new`](Self::new), applying `options` to every record.
+    /// `max_message_bytes` also bounds the length prefixes.
+    pub fn with_options(reader: R, options: DecodeOptions) -> Self {
+        AsyncRecordStreamDecoder {
+            reader,
+            record: Record::new(options),
+        }
+    }
+
+    /// Decode the next record. Resolves to `None` at the end of the stream and
+    /// after an error.
+    pub async fn next_record(&mut self) -> Option<Result<&T, Error<std::io::Error>>> {
+        if self.record.failed {
+            return None;
+        }
+        let len = match self.read_length().await {
+            Ok(Some(len)) => len,
+            Ok(None) => return None,
+            Err(e) => return self.record.finish(Err(e)),
+        };
+        let result = self.read_record(len).await;
+        self.record.finish(result)
+    }
+
+    /// Return the underlying reader, positioned after the last record read.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    async fn read_length(&mut self) -> Result<Option<usize>, Error<std::io::Error>> {
+        let mut prefix = LengthPrefix::default();
+        loop {
+            let Some(&byte) = self.reader.fill_buf().await?.first() else {
+                return if prefix.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(unexpected_eof())
+                };
+            };
+            self.reader.consume_unpin(1);
+            if let Some(len) = prefix.push(byte)? {
+                return Ok(Some(len));
+            }
+        }
+    }
+
+    async fn read_record(&mut self, len: usize) -> Result<(), Error<std::io::Error>> {
+        self.record.start(len)?;
+        let Record { msg, arena, options, .. } = &mut self.record;
+        let mut decoder = ResumeableDecode::<STACK_DEPTH>::new(msg.as_dyn_mut(), isize::MAX, *options);
+        let mut remaining = len;
+        while remaining > 0 {
+            let buffer = self.reader.fill_buf().await?;
+            if buffer.is_empty() {
+                return Err(unexpected_eof());
+            }
+            let n = buffer.len().min(remaining);
+            if !decoder.resume(&buffer[..n], arena) {
+                return Err(Error::InvalidProtobufData);
+            }
+            self.reader.consume_unpin(n);
+            remaining -= n;
+        }
+        if !decoder.finish(arena) {
+            return Err(Error::InvalidProtobufData);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::google::protobuf::FileDescriptorProto;
+    use crate::wire::{Writer, varint_len};
+    use crate::ProtobufRef;
+
+    fn delimited(records: &[&[u8]]) -> Vec<u8> {
+        let len = records
+            .iter()
+            .map(|record| varint_len(record.len() as u64) + record.len())
+            .sum();
+        let mut stream = vec![0u8; len];
+        let mut writer = Writer::new(&mut stream);
+        for record in records {
+            writer.write_bytes(record).unwrap();
+        }
+        stream
+    }
+
+    #[test]
+    fn sync_and_async_records() {
+        let large = FileDescriptorProto::ProtoType::file_descriptor()
+            .encode_vec::<32>()
+            .unwrap();
+        let stream = delimited(&[b"\x0a\x01a", b"", &large, b"\x12\x01p"]);
+        let expected = ["a", "", "google/protobuf/descriptor.proto", ""];
+
+        // A tiny buffer splits records and length prefixes across reads
+        let reader = std::io::BufReader::with_capacity(3, &stream[..]);
+        let mut records = RecordStreamDecoder::<FileDescriptorProto::ProtoType, _>::new(reader);
+        for name in expected {
+            assert_eq!(records.next_record().unwrap().unwrap().name(), name);
+        }
+        assert!(records.next_record().is_none());
+
+        let reader = futures::io::BufReader::with_capacity(3, &stream[..]);
+        let mut records = AsyncRecordStreamDecoder::<FileDescriptorProto::ProtoType, _>::new(reader);
+        futures::executor::block_on(async {
+            for name in expected {
+                assert_eq!(records.next_record().await.unwrap().unwrap().name(), name);
+            }
+            assert!(records.next_record().await.is_none());
+        });
+    }
+
+    #[test]
+    fn malformed_streams() {
+        type Decoder<'a> = RecordStreamDecoder<FileDescriptorProto::ProtoType, &'a [u8]>;
+
+        // Truncated record, then the stream ends
+        let mut records = Decoder::new(&[0x03, 0x0a, 0x01][..]);
+        assert!(matches!(records.next_record(), Some(Err(Error::Io(_)))));
+        assert!(records.next_record().is_none());
+
+        // Truncated length prefix
+        let mut records = Decoder::new(&[0x80][..]);
+        assert!(matches!(records.next_record(), Some(Err(Error::Io(_)))));
+
+        // Invalid record contents
+        let mut records = Decoder::new(&[0x02, 0x0a, 0x05, 0x00][..]);
+        assert!(matches!(records.next_record(), Some(Err(Error::InvalidProtobufData))));
+
+        // Length above the limit
+        let options = DecodeOptions {
+            max_message_bytes: 2,
+            ..Default::default()
+        };
+        let mut records = Decoder::with_options(&[0x03, 0x0a, 0x01, b'a'][..], options);
+        assert!(matches!(records.next_record(), Some(Err(Error::InvalidProtobufData))));
+    }
+}
@@ -34,16 +34,29 @@ use crate::{
     ProtobufMut, ProtobufRef,
     base::{Message, Object},
     containers::{Bytes, String},
+    generated_code_only::Protobuf,
     google::protobuf::{
         DescriptorProto::ProtoType as DescriptorProto,
+        EnumDescriptorProto::ProtoType as EnumDescriptorProto,
+        FeatureSet::{EnumType, MessageEncoding, ProtoType as FeatureSet},
         FieldDescriptorProto::{Label, ProtoType as FieldDescriptorProto, Type},
+        FileDescriptorProto::ProtoType as FileDescriptorProto,
     },
     tables::Table,
     wire,
 };
 
+/// The kind of table entry for `field`. `delimited` is [`is_delimited`] for
+/// the field, making message fields groups.
 #[doc(hidden)]
-pub fn field_kind_tokens(field: &FieldDescriptorProto) -> wire::FieldKind {
+pub fn field_kind_tokens(field: &FieldDescriptorProto, delimited: bool) -> wire::FieldKind {
+    if delimited {
+        return if is_repeated(field) {
+            wire::FieldKind::RepeatedGroup
+        } else {
+            wire::FieldKind::Group
+        };
+    }
     if field.label().unwrap() == Label::LABEL_REPEATED {
         match field.r#type().unwrap() {
             Type::TYPE_INT32 => wire::FieldKind::RepeatedInt32,
@@ -85,8 +98,319 @@ pub fn field_kind_tokens(field: &FieldDescriptorProto) -> wire::FieldKind {
     }
 }
 
+/// Whether `enum_type`, declared in `file`, is closed: decoding drops values
+/// outside the enum instead of storing them. Proto2 enums are closed, proto3
+/// enums open; editions files use the `enum_type` feature of the enum or file.
+#[doc(hidden)]
+pub fn is_closed_enum(enum_type: &EnumDescriptorProto, file: &FileDescriptorProto) -> bool {
+    match file.get_syntax() {
+        Some("proto3") => false,
+        Some("editions") => {
+            let feature = |features: Option<&FeatureSet>| features.and_then(|f| f.enum_type());
+            feature(enum_type.options().and_then(|o| o.features()))
+                .or_else(|| feature(file.options().and_then(|o| o.features())))
+                == Some(EnumType::CLOSED)
+        }
+        _ => true,
+    }
+}
+
+/// Why `message` is not a valid map entry, or `None` if it is one or isn't
+/// marked with the `map_entry` option.
+///
+/// Entries hold exactly a singular `key` field 1 and a singular `value` field
+/// 2, and keys are integers, `bool` or `string`. Floating point keys in
+/// particular are rejected, as NaN keys would never compare equal.
+/// [`DescriptorPool`](crate::descriptor_pool::DescriptorPool) refuses
+/// messages for which this returns `Some`, use it to find out why.
+pub fn map_entry_error(message: &DescriptorProto) -> Option<&'static str> {
+    if !message.options().is_some_and(|o| o.map_entry()) {
+        return None;
+    }
+    let field = |number| message.field().iter().find(|f| f.number() == number);
+    let (Some(key), Some(value)) = (field(1), field(2)) else {
+        return Some("map entry needs a key field 1 and a value field 2");
+    };
+    if message.field().len() != 2 {
+        return Some("map entry has fields besides key and value");
+    }
+    if is_repeated(key) || is_repeated(value) {
+        return Some("map entry key and value can't be repeated");
+    }
+    match key.r#type() {
+        Some(Type::TYPE_FLOAT | Type::TYPE_DOUBLE) => Some("floating point map keys are not allowed"),
+        Some(Type::TYPE_BYTES) => Some("bytes map keys are not allowed"),
+        Some(Type::TYPE_ENUM) => Some("enum map keys are not allowed"),
+        Some(Type::TYPE_MESSAGE | Type::TYPE_GROUP) => Some("message map keys are not allowed"),
+        None => Some("map key has no type"),
+        _ => None,
+    }
+}
+
+/// The JSON name of `field`: its `json_name` if set, else its name in
+/// lowerCamelCase, the way protoc derives it.
+#[cfg(feature = "alloc")]
+pub fn effective_json_name(field: &FieldDescriptorProto) -> alloc::string::String {
+    if field.has_json_name() {
+        return field.json_name().into();
+    }
+    let mut json_name = alloc::string::String::with_capacity(field.name().len());
+    let mut capitalize = false;
+    for c in field.name().chars() {
+        if c == '_' {
+            capitalize = true;
+        } else if core::mem::take(&mut capitalize) {
+            json_name.push(c.to_ascii_uppercase());
+        } else {
+            json_name.push(c);
+        }
+    }
+    json_name
+}
+
+/// The first two fields of `message`, in declaration order, that share a
+/// [JSON name](effective_json_name), or `None` if all names are distinct.
+///
+/// JSON decoding can't tell such fields apart.
+/// [`DescriptorPool`](crate::descriptor_pool::DescriptorPool) refuses them
+/// according to its
+/// [`JsonNameConflictPolicy`](crate::descriptor_pool::JsonNameConflictPolicy).
+#[cfg(feature = "alloc")]
+pub fn json_name_conflict(message: &DescriptorProto) -> Option<(&FieldDescriptorProto, &FieldDescriptorProto)> {
+    let mut seen = hashbrown::HashMap::new();
+    for field in message.field() {
+        match seen.entry(effective_json_name(field)) {
+            hashbrown::hash_map::Entry::Occupied(first) => return Some((*first.get(), &**field)),
+            hashbrown::hash_map::Entry::Vacant(slot) => {
+                slot.insert(&**field);
+            }
+        }
+    }
+    None
+}
+
+/// Why `table` doesn't fit its message descriptor, or `None` if it does.
+///
+/// Decoding and reflection trust the table: an entry whose kind disagrees
+/// with the type of its field, or a slot reaching past the end of the object,
+/// makes them write over memory the message doesn't own.
+/// [`DescriptorPool`](crate::descriptor_pool::DescriptorPool) checks the
+/// tables it builds and refuses messages for which this returns `Some`, so a
+/// layout gone wrong fails there instead of corrupting memory later.
+pub fn table_error(table: &Table) -> Option<&'static str> {
+    if table.check_version().is_err() {
+        return Some("table was generated for another protocrap version");
+    }
+    let descriptor = table.descriptor();
+    let size = table.size as usize;
+    if table.encode_entries().len() != descriptor.field().len() {
+        return Some("table doesn't have an encode entry per field");
+    }
+    for field in descriptor.field() {
+        if field.r#type().is_none() || field.label().is_none() {
+            return Some("field has no type or label");
+        }
+        let number = field.number();
+        if number <= 0 {
+            return Some("field numbers must be positive");
+        }
+        let mut encode_entries = table
+            .encode_entries()
+            .iter()
+            .filter(|entry| entry.encoded_tag >> 3 == number as u32);
+        let (Some(encode_entry), None) = (encode_entries.next(), encode_entries.next()) else {
+            return Some("field numbers must be unique");
+        };
+        let Some(decode_entry) = table.entry(number as u32) else {
+            return Some("field has no decode entry");
+        };
+        if !kind_matches(field, decode_entry.kind()) || !kind_matches(field, encode_entry.kind) {
+            return Some("table entry kind doesn't match the field type");
+        }
+        let entry = table.field_entry(number as u32)?;
+        if entry.offset() != encode_entry.offset as u32 {
+            return Some("encode and decode entries disagree on the field offset");
+        }
+        let offset = if is_message(field) {
+            table.aux_field_offset(entry.aux_offset() as usize)
+        } else {
+            entry.offset()
+        } as usize;
+        let slot = slot_layout(field);
+        if !offset.is_multiple_of(slot.align()) || offset + slot.size() > size {
+            return Some("field lies outside the object");
+        }
+        // Oneofs keep the number of the set field in a word of their own
+        let has_bit = entry.has_bit_idx() as usize;
+        let metadata_word = if is_in_oneof(field) {
+            Some(has_bit & 0x7f)
+        } else {
+            needs_has_bit(field).then_some(has_bit / 32)
+        };
+        if metadata_word.is_some_and(|word| (word + 1) * 4 > size) {
+            return Some("has bit lies outside the object");
+        }
+    }
+    None
+}
+
+/// Whether a table entry of `kind` can hold `field`: the kind
+/// [`field_kind_tokens`] gives it, delimited or not for message fields, or a
+/// closed enum kind for enum fields.
+pub(crate) fn kind_matches(field: &FieldDescriptorProto, kind: wire::FieldKind) -> bool {
+    let (Some(ty), Some(_)) = (field.r#type(), field.label()) else {
+        return false;
+    };
+    let closed_enum = if is_repeated(field) {
+        wire::FieldKind::RepeatedClosedEnum
+    } else {
+        wire::FieldKind::ClosedEnum
+    };
+    kind == field_kind_tokens(field, false)
+        || (is_message(field) && kind == field_kind_tokens(field, true))
+        || (ty == Type::TYPE_ENUM && kind == closed_enum)
+}
+
+/// Layout of the slot holding `field` in a message object.
+fn slot_layout(field: &FieldDescriptorProto) -> core::alloc::Layout {
+    use core::alloc::Layout;
+
+    if is_repeated(field) {
+        return Layout::new::<crate::containers::RepeatedField<u8>>();
+    }
+    match field.r#type().unwrap() {
+        Type::TYPE_BOOL => Layout::new::<bool>(),
+        Type::TYPE_INT32
+        | Type::TYPE_UINT32
+        | Type::TYPE_SINT32
+        | Type::TYPE_FIXED32
+        | Type::TYPE_SFIXED32
+        | Type::TYPE_FLOAT
+        | Type::TYPE_ENUM => Layout::new::<u32>(),
+        Type::TYPE_INT64
+        | Type::TYPE_UINT64
+        | Type::TYPE_SINT64
+        | Type::TYPE_FIXED64
+        | Type::TYPE_SFIXED64
+        | Type::TYPE_DOUBLE => Layout::new::<u64>(),
+        Type::TYPE_STRING | Type::TYPE_BYTES => Layout::new::<Bytes>(),
+        Type::TYPE_MESSAGE | Type::TYPE_GROUP => Layout::new::<Message>(),
+    }
+}
+
+/// The enum `type_name`, a fully qualified name, if the message of `table`
+/// declares it, itself or in a nested message, or else the message's file
+/// does, at file scope or in any of its messages.
+///
+/// Enums of other files, and of the file of a table that doesn't record it,
+/// give None.
+pub(crate) fn find_enum<'a>(table: &'a Table, type_name: &str) -> Option<&'a EnumDescriptorProto> {
+    find_nested_enum(table.descriptor(), type_name)
+        .or_else(|| find_file_enum(table.file()?, type_name))
+}
+
+/// The enum `type_name` if `descriptor` declares it itself or in a nested
+/// message.
+fn find_nested_enum<'a>(
+    descriptor: &'a DescriptorProto,
+    type_name: &str,
+) -> Option<&'a EnumDescriptorProto> {
+    let (scope, enum_name) = type_name.rsplit_once('.')?;
+    // `descriptor` is the scope up to some dot, nested messages the rest
+    let mut outer = scope;
+    loop {
+        let is_descriptor = outer
+            .strip_suffix(descriptor.name())
+            .is_some_and(|parent| parent.ends_with('.'));
+        if is_descriptor {
+            let mut message = descriptor;
+            let nested = scope[outer.len()..].split('.').skip(1).all(|name| {
+                let next = message.nested_type().iter().find(|m| m.name() == name);
+                next.map(|next| message = next).is_some()
+            });
+            let found = message.enum_type().iter().find(|e| e.name() == enum_name);
+            if let (true, Some(found)) = (nested, found) {
+                return Some(found);
+            }
+        }
+        outer = outer.rsplit_once('.')?.0;
+    }
+}
+
+/// The enum `type_name` if `file` declares it, at file scope or in one of
+/// its messages.
+fn find_file_enum<'a>(file: &'a FileDescriptorProto, type_name: &str) -> Option<&'a EnumDescriptorProto> {
+    let name = type_name.strip_prefix('.')?;
+    let name = match file.package() {
+        "" => name,
+        package => name.strip_prefix(package)?.strip_prefix('.')?,
+    };
+    let (enums, enum_name) = match name.rsplit_once('.') {
+        None => (file.enum_type(), name),
+        Some((scope, enum_name)) => {
+            let mut names = scope.split('.');
+            let first = names.next()?;
+            let mut message: &DescriptorProto = file.message_type().iter().find(|m| m.name() == first)?;
+            for name in names {
+                message = message.nested_type().iter().find(|m| m.name() == name)?;
+            }
+            (message.enum_type(), enum_name)
+        }
+    };
+    enums.iter().find(|e| e.name() == enum_name).map(|e| &**e)
+}
+
+/// Name of `value` of the enum `type_name`, looked up as by [`find_enum`]
+#[cfg(feature = "alloc")]
+pub(crate) fn lookup_enum_name<'a>(table: &'a Table, type_name: &str, value: i32) -> Option<&'a str> {
+    let enum_type = find_enum(table, type_name)?;
+    enum_type
+        .value()
+        .iter()
+        .find(|v| v.number() == value)
+        .map(|v| v.name())
+}
+
+/// Whether the message field `field` of `message` is encoded like a group,
+/// delimited by start and end tags instead of a length prefix.
+///
+/// That is the case for groups and, in editions, for message fields with the
+/// `message_encoding = DELIMITED` feature, set on the field or `message` or
+/// defaulted by `file_features`, the features of the file declaring
+/// `message`. Map fields
+/// are always length-prefixed.
+#[doc(hidden)]
+pub fn is_delimited(
+    message: &DescriptorProto,
+    field: &FieldDescriptorProto,
+    file_features: Option<&FeatureSet>,
+) -> bool {
+    match field.r#type() {
+        Some(Type::TYPE_GROUP) => return true,
+        Some(Type::TYPE_MESSAGE) => {}
+        _ => return false,
+    }
+    let is_map = is_repeated(field)
+        && message.nested_type().iter().any(|nested| {
+            nested.options().is_some_and(|o| o.map_entry())
+                && field.type_name().rsplit('.').next() == Some(nested.name())
+        });
+    let encoding = |features: Option<&FeatureSet>| features.and_then(|f| f.message_encoding());
+    !is_map
+        && encoding(field.options().and_then(|o| o.features()))
+            .or_else(|| encoding(message.options().and_then(|o| o.features())))
+            .or_else(|| encoding(file_features))
+            == Some(MessageEncoding::DELIMITED)
+}
+
+/// The tag `field` is encoded with. `delimited` is [`is_delimited`] for the
+/// field.
 #[doc(hidden)]
-pub fn calculate_tag_with_syntax(field: &FieldDescriptorProto, syntax: Option<&str>) -> u32 {
+pub fn calculate_tag_with_syntax(
+    field: &FieldDescriptorProto,
+    syntax: Option<&str>,
+    delimited: bool,
+) -> u32 {
     let is_repeated = field.label().unwrap() == Label::LABEL_REPEATED;
 
     // Determine if packed encoding should be used (only for repeated primitive fields)
@@ -120,6 +444,7 @@ pub fn calculate_tag_with_syntax(field: &FieldDescriptorProto, syntax: Option<&s
         Type::TYPE_FIXED64 | Type::TYPE_SFIXED64 | Type::TYPE_DOUBLE => {
             if is_packed { 2 } else { 1 } // Packed encoding for repeated fixed64
         }
+        Type::TYPE_MESSAGE if delimited => 3,
         Type::TYPE_STRING | Type::TYPE_BYTES | Type::TYPE_MESSAGE => 2,
         Type::TYPE_GROUP => 3,
         Type::TYPE_FIXED32 | Type::TYPE_SFIXED32 | Type::TYPE_FLOAT => {
@@ -130,6 +455,22 @@ pub fn calculate_tag_with_syntax(field: &FieldDescriptorProto, syntax: Option<&s
     (field.number() as u32) << 3 | wire_type
 }
 
+/// Whether `descriptor` is `google.protobuf.Any`, judged by its shape since
+/// tables don't record the package.
+#[cfg(feature = "alloc")]
+pub(crate) fn is_any(descriptor: &DescriptorProto) -> bool {
+    let field = |number: i32, name: &str, ty: Type| {
+        descriptor
+            .field()
+            .iter()
+            .any(|f| f.number() == number && f.name() == name && f.r#type() == Some(ty))
+    };
+    descriptor.name() == "Any"
+        && descriptor.field().len() == 2
+        && field(1, "type_url", Type::TYPE_STRING)
+        && field(2, "value", Type::TYPE_BYTES)
+}
+
 #[doc(hidden)]
 pub fn is_repeated(field: &FieldDescriptorProto) -> bool {
     field.label().unwrap() == Label::LABEL_REPEATED
@@ -210,8 +551,12 @@ impl<'pool, 'msg> core::ops::Deref for DynamicMessage<'pool, 'msg> {
 
 impl<'pool, 'msg> core::fmt::Debug for DynamicMessageRef<'pool, 'msg> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let mut debug_struct = f.debug_struct(self.table.descriptor.name());
-        for field in self.table.descriptor.field() {
+        let Some(descriptor) = self.table.descriptor else {
+            // Without a descriptor there are no field names to show
+            return f.debug_struct(self.table.full_name).finish_non_exhaustive();
+        };
+        let mut debug_struct = f.debug_struct(descriptor.name());
+        for field in descriptor.field() {
             if let Some(value) = self.get_field(field) {
                 debug_struct.field(field.name(), &value);
             }
@@ -227,14 +572,62 @@ impl<'pool, 'msg> core::fmt::Debug for DynamicMessage<'pool, 'msg> {
     }
 }
 
+/// Defines a typed getter for a singular scalar field looked up by name.
+macro_rules! scalar_getter {
+    ($(#[$doc:meta])* $fn_name:ident -> $ty:ty, $variant:ident, $default:expr, $($t:ident)|+) => {
+        $(#[$doc])*
+        ///
+        /// Unset fields read as the default value. `None` if the message has
+        /// no singular field of that name and type.
+        pub fn $fn_name(&self, field_name: &str) -> Option<$ty> {
+            let field = self.find_field_descriptor(field_name)?;
+            if is_repeated(field) || !matches!(field.r#type()?, $(Type::$t)|+) {
+                return None;
+            }
+            match self.get_field(field) {
+                Some(Value::$variant(v)) => Some(v),
+                _ => Some($default),
+            }
+        }
+    };
+}
+
 impl<'pool, 'msg> DynamicMessageRef<'pool, 'msg> {
     pub fn descriptor(&self) -> &'pool DescriptorProto {
-        self.table.descriptor
+        self.table.descriptor()
+    }
+
+    /// Package-qualified name of the message type, e.g.
+    /// `google.protobuf.FieldDescriptorProto` where
+    /// [`descriptor`](Self::descriptor) only names `FieldDescriptorProto`.
+    /// Appending it to `type.googleapis.com/` gives the type URL of an `Any`.
+    pub fn full_name(&self) -> &'pool str {
+        self.table.full_name
+    }
+
+    /// Whether this message has the layout of the generated type `T`: it uses
+    /// `T`'s table, or a table a
+    /// [`DescriptorPool`](crate::descriptor_pool::DescriptorPool) built from
+    /// `T`'s compiled-in descriptor.
+    pub fn is<T: Protobuf>(&self) -> bool {
+        let table = T::table();
+        core::ptr::eq(self.table, table)
+            || (core::ptr::eq(self.table.descriptor(), table.descriptor())
+                && self.table.size == table.size)
+    }
+
+    /// View this message as the generated type `T`, without copying. Returns
+    /// `None` unless [`is::<T>()`](Self::is).
+    pub fn downcast<T: Protobuf>(&self) -> Option<&'msg T> {
+        if !self.is::<T>() {
+            return None;
+        }
+        Some(unsafe { &*(self.object as *const Object as *const T) })
     }
 
     pub fn find_field_descriptor(&self, field_name: &str) -> Option<&'pool FieldDescriptorProto> {
         self.table
-            .descriptor
+            .descriptor()
             .field()
             .iter()
             .find(|f| f.name() == field_name)
@@ -246,15 +639,38 @@ impl<'pool, 'msg> DynamicMessageRef<'pool, 'msg> {
         field_number: i32,
     ) -> Option<&'pool FieldDescriptorProto> {
         self.table
-            .descriptor
+            .descriptor()
             .field()
             .iter()
             .find(|f| f.number() == field_number)
             .map(|f| &**f)
     }
 
+    /// Like [`get_field`](Self::get_field), with enum fields returned as
+    /// [`Value::Enum`] and [`Value::RepeatedEnum`] so their names can be
+    /// shown.
+    ///
+    /// The enum descriptor is found when the message or its file declares
+    /// the enum. Enums of other files, or any enum of a message whose table
+    /// doesn't record its file, come with None.
+    pub fn get_field_typed(
+        &self,
+        field: &'pool FieldDescriptorProto,
+    ) -> Option<Value<'pool, 'msg>> {
+        let value = self.get_field(field)?;
+        if field.r#type() != Some(Type::TYPE_ENUM) {
+            return Some(value);
+        }
+        let descriptor = find_enum(self.table, field.type_name());
+        Some(match value {
+            Value::Int32(number) => Value::Enum { number, descriptor },
+            Value::RepeatedInt32(numbers) => Value::RepeatedEnum { numbers, descriptor },
+            value => value,
+        })
+    }
+
     pub fn get_field(&self, field: &'pool FieldDescriptorProto) -> Option<Value<'pool, 'msg>> {
-        let entry = self.table.entry(field.number() as u32).unwrap();
+        let entry = self.table.field_entry(field.number() as u32).unwrap();
         if field.label().unwrap() == Label::LABEL_REPEATED {
             // Repeated field
             match field.r#type().unwrap() {
@@ -402,6 +818,127 @@ impl<'pool, 'msg> DynamicMessageRef<'pool, 'msg> {
             Some(value)
         }
     }
+
+    scalar_getter!(
+        /// The `int32`, `sint32`, `sfixed32` or enum field `field_name`.
+        get_i32 -> i32, Int32, 0, TYPE_INT32 | TYPE_SINT32 | TYPE_SFIXED32 | TYPE_ENUM
+    );
+    scalar_getter!(
+        /// The `int64`, `sint64` or `sfixed64` field `field_name`.
+        get_i64 -> i64, Int64, 0, TYPE_INT64 | TYPE_SINT64 | TYPE_SFIXED64
+    );
+    scalar_getter!(
+        /// The `uint32` or `fixed32` field `field_name`.
+        get_u32 -> u32, UInt32, 0, TYPE_UINT32 | TYPE_FIXED32
+    );
+    scalar_getter!(
+        /// The `uint64` or `fixed64` field `field_name`.
+        get_u64 -> u64, UInt64, 0, TYPE_UINT64 | TYPE_FIXED64
+    );
+    scalar_getter!(
+        /// The `float` field `field_name`.
+        get_f32 -> f32, Float, 0.0, TYPE_FLOAT
+    );
+    scalar_getter!(
+        /// The `double` field `field_name`.
+        get_f64 -> f64, Double, 0.0, TYPE_DOUBLE
+    );
+    scalar_getter!(
+        /// The `bool` field `field_name`.
+        get_bool -> bool, Bool, false, TYPE_BOOL
+    );
+    scalar_getter!(
+        /// The `string` field `field_name`.
+        get_str -> &'msg str, String, "", TYPE_STRING
+    );
+    scalar_getter!(
+        /// The `bytes` field `field_name`.
+        get_bytes -> &'msg [u8], Bytes, &[], TYPE_BYTES
+    );
+
+    /// The singular message field `field_name`, or `None` if it is unset or the
+    /// message has no such field.
+    pub fn get_message(&self, field_name: &str) -> Option<DynamicMessageRef<'pool, 'msg>> {
+        let field = self.find_field_descriptor(field_name)?;
+        match self.get_field(field) {
+            Some(Value::Message(msg)) => Some(msg),
+            _ => None,
+        }
+    }
+
+    /// The elements of the repeated message field `field_name`. Empty if the
+    /// message has no repeated message field of that name.
+    pub fn repeated_messages(&self, field_name: &str) -> DynamicMessageArrayIter<'pool, 'msg> {
+        match self
+            .find_field_descriptor(field_name)
+            .and_then(|field| self.get_field(field))
+        {
+            Some(Value::RepeatedMessage(array)) => DynamicMessageArrayIter {
+                object: array.object,
+                table: array.table,
+                index: 0,
+            },
+            _ => DynamicMessageArrayIter {
+                object: &[],
+                table: self.table,
+                index: 0,
+            },
+        }
+    }
+
+    /// The presence bits of this message's fields, for checking or listing
+    /// set fields a word at a time instead of field by field.
+    pub fn has_bits(&self) -> HasBits<'pool, 'msg> {
+        let len = has_bit_words(self.table.descriptor());
+        let words =
+            unsafe { core::slice::from_raw_parts(self.object as *const Object as *const u32, len) };
+        HasBits {
+            words,
+            table: self.table,
+        }
+    }
+
+    /// Feed the encoding of this message, with fields in canonical order, into
+    /// `hasher` without materializing it.
+    ///
+    /// The encoder produces the output back to front in fixed-size chunks,
+    /// which are hashed in that order. The result is therefore not the hash of
+    /// the encoded bytes, but it is a stable function of them, suitable for
+    /// cache keys.
+    ///
+    /// Maps are hashed as if [`DynamicMessage::sort_maps`] had been called, so
+    /// the hash doesn't depend on the order in which an implementation wrote
+    /// the entries, and a repeated key counts only with its last entry. When a
+    /// map isn't sorted already, the encoder picks its entries in key order,
+    /// which takes time quadratic in the size of the map but doesn't allocate.
+    /// Without the `alloc` feature map entries are hashed in stored order.
+    pub fn stable_hash(&self, hasher: &mut impl core::hash::Hasher) -> Result<(), crate::Error> {
+        const CHUNK_SIZE: usize = 1024;
+        const STACK_DEPTH: usize = 64;
+
+        let options = crate::EncodeOptions {
+            canonical_field_order: true,
+        };
+        let mut encoder =
+            crate::encoding::ResumeableEncode::<STACK_DEPTH>::new(self.as_dyn(), options)?;
+        #[cfg(feature = "alloc")]
+        if !self.maps_sorted() {
+            encoder = encoder.sort_maps();
+        }
+        let mut buffer = [0u8; CHUNK_SIZE];
+        loop {
+            match encoder
+                .resume_encode(&mut buffer)
+                .ok_or(crate::Error::MessageTreeTooDeep)?
+            {
+                crate::encoding::ResumeResult::Done(tail) => {
+                    hasher.write(tail);
+                    return Ok(());
+                }
+                crate::encoding::ResumeResult::NeedsMoreBuffer => hasher.write(&buffer),
+            }
+        }
+    }
 }
 
 impl<'pool, 'msg> DynamicMessage<'pool, 'msg> {
@@ -412,6 +949,16 @@ impl<'pool, 'msg> DynamicMessage<'pool, 'msg> {
         }
     }
 
+    /// Mutable view of this message as the generated type `T`, without
+    /// copying. Returns `None` unless [`is::<T>()`](DynamicMessageRef::is).
+    /// The reverse conversion is [`ProtobufMut::as_dyn_mut`].
+    pub fn downcast<T: Protobuf>(&mut self) -> Option<&mut T> {
+        if !self.is::<T>() {
+            return None;
+        }
+        Some(unsafe { &mut *(self.object as *mut Object as *mut T) })
+    }
+
     /// Zeroes all fields of this message.
     pub fn clear(&mut self) {
         unsafe {
@@ -422,6 +969,386 @@ impl<'pool, 'msg> DynamicMessage<'pool, 'msg> {
             );
         }
     }
+
+    /// Unset every field tracked by a has-bit, see [`HasBits`].
+    ///
+    /// Cheaper than clearing the fields one by one. Their values stay in
+    /// memory but read as unset and aren't encoded. Sub-messages, repeated
+    /// fields and oneofs are left alone.
+    pub fn clear_has_bits(&mut self) {
+        for word in 0..has_bit_words(self.table.descriptor()) as u32 {
+            *self.object.ref_mut::<u32>(word * 4) = 0;
+        }
+    }
+
+    /// Detach the singular message field `field_name` and return it as a
+    /// message of its own, leaving the field unset.
+    ///
+    /// Nothing is copied: the sub-message stays where it was decoded, in the
+    /// arena holding this message. Useful to lift the payload out of a batch
+    /// envelope. `None` if the field is unset or the message has no singular
+    /// message field of that name.
+    pub fn take_child(&mut self, field_name: &str) -> Option<DynamicMessage<'pool, 'msg>> {
+        let field = self.find_field_descriptor(field_name)?;
+        if is_repeated(field) || !is_message(field) {
+            return None;
+        }
+        let entry = self.table.field_entry(field.number() as u32)?;
+        let has_bit_idx = entry.has_bit_idx();
+        if has_bit_idx & 0x80 != 0 {
+            let discriminant = self.object.ref_mut::<u32>((has_bit_idx & 0x7F) * 4);
+            if *discriminant != field.number() as u32 {
+                return None;
+            }
+            *discriminant = 0;
+        }
+        let (offset, child_table) = self.table.aux_entry_decode(entry);
+        let child = core::mem::take(self.object.ref_mut::<Message>(offset));
+        if child.is_null() {
+            return None;
+        }
+        Some(DynamicMessage {
+            // The field no longer references the child, so this is its only reference
+            object: unsafe { &mut *child.0 },
+            table: child_table,
+        })
+    }
+
+    /// Make `child` the singular message field `field_name`, without copying.
+    ///
+    /// `child` must be of the field's type and allocated in `arena`, the arena
+    /// holding this message's sub-messages, so that dropping another arena
+    /// can't leave the field dangling. A message taken with
+    /// [`take_child`](Self::take_child) can be put back or moved to another
+    /// message sharing the arena. Fails with
+    /// [`Error::MessageNotFound`](crate::Error::MessageNotFound) if the message
+    /// has no such field of `child`'s type, and with
+    /// [`Error::InvalidProtobufData`](crate::Error::InvalidProtobufData) if
+    /// `child` lives outside `arena`.
+    pub fn set_child(
+        &mut self,
+        field_name: &str,
+        child: DynamicMessage<'pool, 'msg>,
+        arena: &crate::arena::Arena,
+    ) -> Result<(), crate::Error> {
+        let field = self
+            .find_field_descriptor(field_name)
+            .filter(|field| !is_repeated(field) && is_message(field))
+            .ok_or(crate::Error::MessageNotFound)?;
+        let entry = self
+            .table
+            .field_entry(field.number() as u32)
+            .ok_or(crate::Error::MessageNotFound)?;
+        let (offset, child_table) = self.table.aux_entry_decode(entry);
+        let same_type = core::ptr::eq(child.table, child_table)
+            || (core::ptr::eq(child.table.descriptor(), child_table.descriptor())
+                && child.table.size == child_table.size);
+        if !same_type {
+            return Err(crate::Error::MessageNotFound);
+        }
+        if !arena.contains(child.object) {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        let has_bit_idx = entry.has_bit_idx();
+        if has_bit_idx & 0x80 != 0 {
+            *self.object.ref_mut::<u32>((has_bit_idx & 0x7F) * 4) = field.number() as u32;
+        }
+        *self.object.ref_mut::<Message>(offset) = Message::new(child.object);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'pool, 'msg> DynamicMessage<'pool, 'msg> {
+    /// Deduplicates all map fields in this message and its sub-messages.
+    ///
+    /// The wire decoder appends map entries as they arrive, so a payload that
+    /// repeats a key yields several entries for it. This keeps only the last
+    /// entry per key, matching the last-wins semantics of other implementations,
+    /// so that re-encoding and reflective comparisons agree with them.
+    pub fn normalize_maps(&mut self) {
+        self.normalize_maps_impl(false)
+    }
+
+    /// Like [`normalize_maps`](Self::normalize_maps), and also sorts the entries
+    /// of every map by key, so equal maps encode to the same bytes.
+    pub fn sort_maps(&mut self) {
+        self.normalize_maps_impl(true)
+    }
+
+    fn normalize_maps_impl(&mut self, sort: bool) {
+        let table = self.table;
+        for field in table.descriptor().field() {
+            if !is_message(field) {
+                continue;
+            }
+            let entry = table.field_entry(field.number() as u32).unwrap();
+            let (offset, child_table) = table.aux_entry_decode(entry);
+            if is_repeated(field) {
+                let messages = self
+                    .object
+                    .ref_mut::<crate::containers::RepeatedField<Message>>(offset);
+                if is_map_entry(child_table.descriptor()) {
+                    dedup_map_entries(messages, child_table);
+                    if sort {
+                        sort_map_entries(messages, child_table);
+                    }
+                }
+                for msg in messages.iter_mut() {
+                    DynamicMessage {
+                        object: msg.as_mut(),
+                        table: child_table,
+                    }
+                    .normalize_maps_impl(sort);
+                }
+            } else if self.get_field(field).is_some() {
+                let msg = self.object.ref_mut::<Message>(offset);
+                DynamicMessage {
+                    object: msg.as_mut(),
+                    table: child_table,
+                }
+                .normalize_maps_impl(sort);
+            }
+        }
+    }
+}
+
+/// Field value equality for [`DynamicMessageRef::message_eq`].
+#[cfg(feature = "alloc")]
+fn value_eq(a: Option<Value<'_, '_>>, b: Option<Value<'_, '_>>) -> bool {
+    fn floats_eq<T: Copy, B: PartialEq>(a: &[T], b: &[T], bits: impl Fn(T) -> B) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(&a, &b)| bits(a) == bits(b))
+    }
+
+    let (a, b) = match (a, b) {
+        (None, None) => return true,
+        (Some(a), Some(b)) => (a, b),
+        _ => return false,
+    };
+    match (a, b) {
+        (Value::Int32(a), Value::Int32(b)) => a == b,
+        (Value::Int64(a), Value::Int64(b)) => a == b,
+        (Value::UInt32(a), Value::UInt32(b)) => a == b,
+        (Value::UInt64(a), Value::UInt64(b)) => a == b,
+        (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+        (Value::Double(a), Value::Double(b)) => a.to_bits() == b.to_bits(),
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Bytes(a), Value::Bytes(b)) => a == b,
+        (Value::Message(a), Value::Message(b)) => a.message_eq(&b),
+        (Value::RepeatedInt32(a), Value::RepeatedInt32(b)) => a == b,
+        (Value::RepeatedInt64(a), Value::RepeatedInt64(b)) => a == b,
+        (Value::RepeatedUInt32(a), Value::RepeatedUInt32(b)) => a == b,
+        (Value::RepeatedUInt64(a), Value::RepeatedUInt64(b)) => a == b,
+        (Value::RepeatedFloat(a), Value::RepeatedFloat(b)) => floats_eq(a, b, f32::to_bits),
+        (Value::RepeatedDouble(a), Value::RepeatedDouble(b)) => floats_eq(a, b, f64::to_bits),
+        (Value::RepeatedBool(a), Value::RepeatedBool(b)) => a == b,
+        (Value::RepeatedString(a), Value::RepeatedString(b)) => a == b,
+        (Value::RepeatedBytes(a), Value::RepeatedBytes(b)) => a == b,
+        (Value::Enum { number: a, .. }, Value::Enum { number: b, .. }) => a == b,
+        (Value::RepeatedEnum { numbers: a, .. }, Value::RepeatedEnum { numbers: b, .. }) => a == b,
+        (Value::RepeatedMessage(a), Value::RepeatedMessage(b)) => {
+            if is_map_entry(a.table.descriptor())
+                && let (Some(a), Some(b)) = (map_entries(&a), map_entries(&b))
+            {
+                return a.len() == b.len()
+                    && a.iter().all(|(key, entry)| {
+                        b.get(key).is_some_and(|other| entry.message_eq(other))
+                    });
+            }
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.message_eq(&b))
+        }
+        _ => false,
+    }
+}
+
+/// The entries of a map field by key, the last entry winning for a repeated
+/// key. `None` if an entry has no usable key.
+#[cfg(feature = "alloc")]
+fn map_entries<'pool, 'msg>(
+    array: &DynamicMessageArray<'pool, 'msg>,
+) -> Option<alloc::collections::BTreeMap<MapKey<'msg>, DynamicMessageRef<'pool, 'msg>>> {
+    (0..array.len())
+        .map(|index| {
+            let entry = array.get(index);
+            Some((entry.map_key()?, entry))
+        })
+        .collect()
+}
+
+/// Removes all but the last entry for each key, preserving the relative order
+/// of the surviving entries.
+#[cfg(feature = "alloc")]
+fn dedup_map_entries(messages: &mut crate::containers::RepeatedField<Message>, table: &Table) {
+    let mut keep = alloc::vec::Vec::with_capacity(messages.len());
+    let mut seen = hashbrown::HashSet::new();
+    for msg in messages.iter().rev() {
+        let entry = DynamicMessageRef {
+            object: msg.as_ref(),
+            table,
+        };
+        // Entries without a usable key are left alone
+        keep.push(entry.map_key().is_none_or(|key| seen.insert(key)));
+    }
+    // The keys borrow from `messages`
+    drop(seen);
+    keep.reverse();
+    if keep.iter().all(|&k| k) {
+        return;
+    }
+
+    let mut len = 0;
+    for (idx, keep) in keep.into_iter().enumerate() {
+        if keep {
+            messages.swap(len, idx);
+            len += 1;
+        }
+    }
+    messages.truncate(len);
+}
+
+/// Sorts map entries by key. Entries without a usable key sort first.
+#[cfg(feature = "alloc")]
+fn sort_map_entries(messages: &mut crate::containers::RepeatedField<Message>, table: &Table) {
+    fn key<'msg>(msg: &'msg Message, table: &Table) -> Option<MapKey<'msg>> {
+        DynamicMessageRef {
+            object: msg.as_ref(),
+            table,
+        }
+        .map_key()
+    }
+    messages.sort_by(|a, b| key(a, table).cmp(&key(b, table)));
+}
+
+/// Index of the map entry preceding entry `before` in key order, or of the
+/// last entry if `before` is `None`. Only the last entry of a repeated key
+/// counts, and entries without a usable key come first in stored order, as
+/// after [`dedup_map_entries`] and [`sort_map_entries`].
+#[cfg(feature = "alloc")]
+pub(crate) fn prev_map_entry(
+    messages: &[Message],
+    table: &Table,
+    before: Option<usize>,
+) -> Option<usize> {
+    // Entries of a repeated key share a position, the last one wins ties
+    let position = |idx: usize| {
+        let key = DynamicMessageRef {
+            object: messages[idx].as_ref(),
+            table,
+        }
+        .map_key();
+        let tiebreak = if key.is_none() { idx } else { 0 };
+        (key, tiebreak)
+    };
+    let bound = before.map(position);
+    let mut best = None;
+    for idx in 0..messages.len() {
+        let pos = position(idx);
+        if bound.is_some_and(|bound| pos >= bound) {
+            continue;
+        }
+        if best.as_ref().is_none_or(|(_, best_pos)| pos >= *best_pos) {
+            best = Some((idx, pos));
+        }
+    }
+    best.map(|(idx, _)| idx)
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) fn is_map_entry(descriptor: &DescriptorProto) -> bool {
+    descriptor
+        .options()
+        .map(|o| o.map_entry())
+        .unwrap_or(false)
+}
+
+/// Key of a map entry, as used for deduplication.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum MapKey<'msg> {
+    Bool(bool),
+    Int32(i32),
+    Int64(i64),
+    UInt32(u32),
+    UInt64(u64),
+    String(&'msg str),
+}
+
+#[cfg(feature = "alloc")]
+impl<'pool, 'msg> DynamicMessageRef<'pool, 'msg> {
+    /// Whether `other` is a message of the same type with equal field values.
+    ///
+    /// Map fields compare as maps: entry order doesn't matter, and a repeated
+    /// key counts only with its last entry, as when decoding. Floating-point
+    /// values compare bitwise, so NaNs with equal bits are equal and `0.0`
+    /// differs from `-0.0`, matching [`stable_hash`](Self::stable_hash).
+    pub fn message_eq(&self, other: &DynamicMessageRef<'_, '_>) -> bool {
+        let (fields, other_fields) = (self.descriptor().field(), other.descriptor().field());
+        if self.descriptor().name() != other.descriptor().name()
+            || fields.len() != other_fields.len()
+        {
+            return false;
+        }
+        fields.iter().zip(other_fields).all(|(field, other_field)| {
+            field.number() == other_field.number()
+                && field.r#type() == other_field.r#type()
+                && field.label() == other_field.label()
+                && value_eq(self.get_field(field), other.get_field(other_field))
+        })
+    }
+
+    /// Whether every map in this message and its sub-messages has its
+    /// entries sorted by key, without repeated keys.
+    fn maps_sorted(&self) -> bool {
+        self.descriptor().field().iter().all(|field| {
+            match self.get_field(field) {
+                Some(Value::Message(msg)) => msg.maps_sorted(),
+                Some(Value::RepeatedMessage(array)) => {
+                    if is_map_entry(array.table.descriptor()) {
+                        let mut keys = array.iter().map(|entry| entry.map_key());
+                        let mut previous = match keys.next() {
+                            Some(Some(key)) => key,
+                            Some(None) => return false,
+                            None => return true,
+                        };
+                        for key in keys {
+                            match key {
+                                Some(key) if key > previous => previous = key,
+                                _ => return false,
+                            }
+                        }
+                    }
+                    array.iter().all(|msg| msg.maps_sorted())
+                }
+                _ => true,
+            }
+        })
+    }
+
+    /// Returns the key of this map entry, or `None` if the key field is missing
+    /// or not of a valid map key type. An unset key counts as the default value.
+    pub(crate) fn map_key(&self) -> Option<MapKey<'msg>> {
+        let key_field = self.find_field_descriptor_by_number(1)?;
+        let key = match self.get_field(key_field) {
+            Some(Value::Bool(v)) => MapKey::Bool(v),
+            Some(Value::Int32(v)) => MapKey::Int32(v),
+            Some(Value::Int64(v)) => MapKey::Int64(v),
+            Some(Value::UInt32(v)) => MapKey::UInt32(v),
+            Some(Value::UInt64(v)) => MapKey::UInt64(v),
+            Some(Value::String(v)) => MapKey::String(v),
+            Some(_) => return None,
+            None => match key_field.r#type()? {
+                Type::TYPE_BOOL => MapKey::Bool(false),
+                Type::TYPE_INT32 | Type::TYPE_SINT32 | Type::TYPE_SFIXED32 => MapKey::Int32(0),
+                Type::TYPE_INT64 | Type::TYPE_SINT64 | Type::TYPE_SFIXED64 => MapKey::Int64(0),
+                Type::TYPE_UINT32 | Type::TYPE_FIXED32 => MapKey::UInt32(0),
+                Type::TYPE_UINT64 | Type::TYPE_FIXED64 => MapKey::UInt64(0),
+                Type::TYPE_STRING => MapKey::String(""),
+                _ => return None,
+            },
+        };
+        Some(key)
+    }
 }
 
 impl<'pool, 'msg> From<DynamicMessage<'pool, 'msg>> for DynamicMessageRef<'pool, 'msg> {
@@ -542,6 +1469,63 @@ impl<'pool, 'a> ExactSizeIterator for DynamicMessageArrayIter<'pool, 'a> {
     }
 }
 
+/// Number of `u32` words holding the has-bits of messages of `descriptor`.
+fn has_bit_words(descriptor: &DescriptorProto) -> usize {
+    let fields = descriptor.field().iter().filter(|f| needs_has_bit(f)).count();
+    fields.div_ceil(32)
+}
+
+/// Presence bits of a message, see [`DynamicMessageRef::has_bits`].
+///
+/// Covers the fields that track presence with a has-bit: singular scalar,
+/// string, bytes and enum fields outside of oneofs. Sub-messages are present
+/// when set, repeated fields when non-empty and oneof members when selected;
+/// none of them has a bit here.
+#[derive(Clone, Copy)]
+pub struct HasBits<'pool, 'msg> {
+    words: &'msg [u32],
+    table: &'pool Table,
+}
+
+impl<'pool, 'msg> HasBits<'pool, 'msg> {
+    /// The raw bit words. Bit `i % 32` of word `i / 32` belongs to the `i`-th
+    /// field with a has-bit in declaration order.
+    pub fn words(&self) -> &'msg [u32] {
+        self.words
+    }
+
+    /// Whether any field with a has-bit is set.
+    pub fn any(&self) -> bool {
+        self.words.iter().any(|&word| word != 0)
+    }
+
+    /// Number of fields with a has-bit that are set.
+    pub fn count_set(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// The set fields, in declaration order.
+    pub fn iter(&self) -> impl Iterator<Item = &'pool FieldDescriptorProto> + use<'pool, 'msg> {
+        let words = self.words;
+        let table = self.table;
+        table.descriptor().field().iter().filter_map(move |field| {
+            if !needs_has_bit(field) {
+                return None;
+            }
+            let bit = table.field_entry(field.number() as u32)?.has_bit_idx() as usize;
+            (words[bit / 32] & (1 << (bit % 32)) != 0).then_some(&**field)
+        })
+    }
+}
+
+impl core::fmt::Debug for HasBits<'_, '_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list()
+            .entries(self.iter().map(|field| field.name()))
+            .finish()
+    }
+}
+
 // IntoIterator for easy use with for loops
 impl<'pool, 'msg> IntoIterator for &DynamicMessageArray<'pool, 'msg> {
     type Item = DynamicMessageRef<'pool, 'msg>;
@@ -601,6 +1585,33 @@ pub enum Value<'pool, 'msg> {
     RepeatedString(&'msg [String]),
     RepeatedBytes(&'msg [Bytes]),
     RepeatedMessage(DynamicMessageArray<'pool, 'msg>),
+    /// An enum value with its enum type, None if the message can't lead to
+    /// it (see [`DynamicMessageRef::get_field_typed`]).
+    Enum {
+        number: i32,
+        descriptor: Option<&'pool EnumDescriptorProto>,
+    },
+    /// Repeated counterpart of [`Value::Enum`].
+    RepeatedEnum {
+        numbers: &'msg [i32],
+        descriptor: Option<&'pool EnumDescriptorProto>,
+    },
+}
+
+impl<'pool> Value<'pool, '_> {
+    /// Name of an [`Enum`](Value::Enum) value, None for other values and for
+    /// numbers the enum doesn't declare.
+    pub fn enum_name(&self) -> Option<&'pool str> {
+        let Value::Enum {
+            number,
+            descriptor: Some(descriptor),
+        } = *self
+        else {
+            return None;
+        };
+        let value = descriptor.value().iter().find(|v| v.number() == number)?;
+        Some(value.name())
+    }
 }
 
 impl core::fmt::Debug for Value<'_, '_> {
@@ -626,6 +1637,11 @@ impl core::fmt::Debug for Value<'_, '_> {
             Value::RepeatedString(v) => v.fmt(f),
             Value::RepeatedBytes(v) => v.fmt(f),
             Value::RepeatedMessage(ref v) => v.fmt(f),
+            Value::Enum { number, .. } => match self.enum_name() {
+                Some(name) => f.write_str(name),
+                None => number.fmt(f),
+            },
+            Value::RepeatedEnum { numbers, .. } => numbers.fmt(f),
         }
     }
 }
@@ -0,0 +1,586 @@
+//! Transport-agnostic RPC support for generated services.
+//!
+//! With the `services` codegen option every proto `service` becomes a module
+//! holding a `Handler` trait to implement, a `Server` adapter that dispatches
+//! encoded requests to it, and a `Client` that sends requests over any
+//! [`Channel`]. The transport only ever sees encoded messages, so plugging in a
+//! new one means implementing [`Channel`] on the client side and routing
+//! request bodies to [`Service::call`] on the server side.
+//!
+//! For HTTP/2 stacks such as `hyper`, [`encode_frame`] and [`decode_frame`]
+//! handle the gRPC length-prefixed framing, and [`FrameDecoder`] decodes a
+//! frame as its data arrives. With the `tonic` feature,
+//! [`tonic`](self::tonic) provides a ready-made channel and server adapter.
+//!
+//! ```
+//! use protocrap::rpc::{Channel, Loopback, Service, Status};
+//! use protocrap::rpc::BoxFuture;
+//!
+//! struct Echo;
+//!
+//! impl Service for Echo {
+//!     const NAME: &'static str = "example.Echo";
+//!
+//!     fn call(&self, method: &str, request: &[u8]) -> BoxFuture<'_, Result<Vec<u8>, Status>> {
+//!         let result = match method {
+//!             "Echo" => Ok(request.to_vec()),
+//!             _ => Err(Status::unimplemented(method)),
+//!         };
+//!         Box::pin(async move { result })
+//!     }
+//! }
+//!
+//! let channel = Loopback(Echo);
+//! let response = futures::executor::block_on(channel.unary("/example.Echo/Echo", vec![1, 2]));
+//! assert_eq!(response.unwrap(), [1, 2]);
+//! ```
+
+use core::future::Future;
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+
+use crate::arena::Arena;
+use crate::decoding::{DecodeOptions, ResumeableDecode};
+use crate::generated_code_only::Protobuf;
+use crate::{ProtobufMut, ProtobufRef};
+
+#[cfg(not(feature = "nightly"))]
+use allocator_api2::alloc::Global;
+#[cfg(feature = "nightly")]
+use std::alloc::Global;
+
+#[cfg(feature = "tonic")]
+pub mod tonic;
+
+/// Stack depth used to encode and decode RPC messages.
+const STACK_DEPTH: usize = 64;
+
+/// A boxed, `Send` future as returned by [`Service::call`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// gRPC status codes.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Code {
+    Ok = 0,
+    Cancelled = 1,
+    Unknown = 2,
+    InvalidArgument = 3,
+    DeadlineExceeded = 4,
+    NotFound = 5,
+    AlreadyExists = 6,
+    PermissionDenied = 7,
+    ResourceExhausted = 8,
+    FailedPrecondition = 9,
+    Aborted = 10,
+    OutOfRange = 11,
+    Unimplemented = 12,
+    Internal = 13,
+    Unavailable = 14,
+    DataLoss = 15,
+    Unauthenticated = 16,
+}
+
+impl Code {
+    /// Maps a wire value to a code; values outside the gRPC range become `Unknown`.
+    pub const fn from_i32(value: i32) -> Self {
+        match value {
+            0 => Code::Ok,
+            1 => Code::Cancelled,
+            3 => Code::InvalidArgument,
+            4 => Code::DeadlineExceeded,
+            5 => Code::NotFound,
+            6 => Code::AlreadyExists,
+            7 => Code::PermissionDenied,
+            8 => Code::ResourceExhausted,
+            9 => Code::FailedPrecondition,
+            10 => Code::Aborted,
+            11 => Code::OutOfRange,
+            12 => Code::Unimplemented,
+            13 => Code::Internal,
+            14 => Code::Unavailable,
+            15 => Code::DataLoss,
+            16 => Code::Unauthenticated,
+            _ => Code::Unknown,
+        }
+    }
+}
+
+/// The outcome of a failed RPC: a [`Code`] plus a human readable message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    code: Code,
+    message: String,
+}
+
+impl Status {
+    pub fn new(code: Code, message: impl Into<String>) -> Self {
+        Status {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(Code::InvalidArgument, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(Code::NotFound, message)
+    }
+
+    pub fn unimplemented(message: impl Into<String>) -> Self {
+        Self::new(Code::Unimplemented, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(Code::Internal, message)
+    }
+
+    pub fn resource_exhausted(message: impl Into<String>) -> Self {
+        Self::new(Code::ResourceExhausted, message)
+    }
+
+    pub fn code(&self) -> Code {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl core::fmt::Display for Status {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl core::error::Error for Status {}
+
+/// A message together with the arena holding its contents.
+///
+/// A bare [`Arena`] borrows its allocator and therefore can't cross threads.
+/// `Owned` always allocates from the global allocator, so it is `Send` and can
+/// be held across `.await` in the futures RPC stacks expect.
+pub struct Owned<T: Protobuf> {
+    // Declared before the arena so it is dropped first.
+    msg: T,
+    arena: Arena<'static>,
+}
+
+// Safety: the arena only ever uses the global allocator, which is thread safe.
+unsafe impl<T: Protobuf + Send> Send for Owned<T> {}
+unsafe impl<T: Protobuf + Sync> Sync for Owned<T> {}
+
+impl<T: Protobuf> Owned<T> {
+    /// An empty message with a fresh arena.
+    pub fn new() -> Self {
+        Owned {
+            msg: T::default(),
+            arena: Arena::new(&Global),
+        }
+    }
+
+    /// Decode `bytes` into a fresh message, returning `None` if they are invalid.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut owned = Self::new();
+        let (msg, arena) = owned.parts_mut();
+        msg.decode_flat::<STACK_DEPTH>(arena, bytes)
+            .then_some(owned)
+    }
+
+    /// Encode the message.
+    pub fn encode(&self) -> Result<Vec<u8>, crate::Error> {
+        self.msg.encode_vec::<STACK_DEPTH>()
+    }
+
+    /// The message and the arena to allocate its contents from, e.g. for setters.
+    pub fn parts_mut(&mut self) -> (&mut T, &mut Arena<'static>) {
+        (&mut self.msg, &mut self.arena)
+    }
+}
+
+impl<T: Protobuf> Default for Owned<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Protobuf> Deref for Owned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.msg
+    }
+}
+
+impl<T: Protobuf> DerefMut for Owned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.msg
+    }
+}
+
+impl<T: Protobuf> TryFrom<&[u8]> for Owned<T> {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(bytes).ok_or(crate::Error::InvalidProtobufData)
+    }
+}
+
+impl<T: Protobuf> TryFrom<&Owned<T>> for Vec<u8> {
+    type Error = crate::Error;
+
+    fn try_from(owned: &Owned<T>) -> Result<Self, Self::Error> {
+        owned.encode()
+    }
+}
+
+impl<T: Protobuf> core::fmt::Debug for Owned<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(&self.msg, f)
+    }
+}
+
+/// Client side transport for unary calls.
+pub trait Channel: Send + Sync {
+    /// Send an encoded request to `path` (`/package.Service/Method`) and
+    /// resolve to the encoded response.
+    fn unary(
+        &self,
+        path: &'static str,
+        request: Vec<u8>,
+    ) -> impl Future<Output = Result<Vec<u8>, Status>> + Send;
+}
+
+/// Server side dispatch of encoded requests, implemented by generated `Server`s.
+pub trait Service: Send + Sync + 'static {
+    /// Fully qualified service name, e.g. `helloworld.Greeter`.
+    const NAME: &'static str;
+
+    /// Handle one call to `method` (the bare method name, e.g. `SayHello`).
+    fn call(&self, method: &str, request: &[u8]) -> BoxFuture<'_, Result<Vec<u8>, Status>>;
+}
+
+/// A [`Channel`] that calls a [`Service`] in-process, for tests and for
+/// services living in the same binary as their clients.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Loopback<S>(pub S);
+
+impl<S: Service> Channel for Loopback<S> {
+    async fn unary(&self, path: &'static str, request: Vec<u8>) -> Result<Vec<u8>, Status> {
+        let method = split_path(path)
+            .filter(|(service, _)| *service == S::NAME)
+            .map(|(_, method)| method)
+            .ok_or_else(|| Status::unimplemented(path))?;
+        self.0.call(method, &request).await
+    }
+}
+
+/// Split `/package.Service/Method` into service and method name.
+pub fn split_path(path: &str) -> Option<(&str, &str)> {
+    path.strip_prefix('/')?.split_once('/')
+}
+
+/// Prefix `message` with the 5 byte gRPC frame header (uncompressed).
+pub fn encode_frame(message: &[u8]) -> Vec<u8> {
+    let len = u32::try_from(message.len()).expect("gRPC messages are limited to 4GB");
+    let mut frame = Vec::with_capacity(5 + message.len());
+    frame.push(0);
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(message);
+    frame
+}
+
+/// A gRPC frame split off the front of a buffer by [`decode_frame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub message: &'a [u8],
+    /// The bytes following the frame.
+    pub rest: &'a [u8],
+}
+
+/// Split the first gRPC frame off `buf`.
+///
+/// Returns `Ok(None)` if `buf` doesn't hold a complete frame yet.
+pub fn decode_frame(buf: &[u8]) -> Result<Option<Frame<'_>>, Status> {
+    let Some((header, rest)) = buf.split_first_chunk::<5>() else {
+        return Ok(None);
+    };
+    if header[0] != 0 {
+        return Err(Status::unimplemented("compressed gRPC messages"));
+    }
+    let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    Ok(rest
+        .split_at_checked(len)
+        .map(|(message, rest)| Frame { message, rest }))
+}
+
+/// Decodes one gRPC frame into a message as its data arrives, so large
+/// messages are never buffered whole.
+///
+/// [`push`](Self::push) takes the bytes of the stream as they come in and
+/// consumes those of the frame. The 5 byte header is checked first: a
+/// compressed message fails with `Unimplemented` and one longer than
+/// `max_message_bytes` of the [`DecodeOptions`] with `ResourceExhausted`,
+/// before any of its body is read. The body goes straight to a
+/// [`ResumeableDecode`]; [`finish`](Self::finish) completes the message once
+/// [`is_complete`](Self::is_complete). After an error the decoder rejects all
+/// further input.
+///
+/// ```
+/// use protocrap::google::protobuf::FileDescriptorProto;
+/// use protocrap::rpc::{FrameDecoder, encode_frame};
+/// use protocrap::{ProtobufMut, ProtobufRef, arena::Arena};
+///
+/// let mut file = FileDescriptorProto::ProtoType::default();
+/// let mut arena = Arena::new(&allocator_api2::alloc::Global);
+/// file.set_name("a.proto", &mut arena).unwrap();
+/// let stream = encode_frame(&file.encode_vec::<32>().unwrap());
+///
+/// let mut decoded = FileDescriptorProto::ProtoType::default();
+/// let mut decoder = FrameDecoder::<32>::new(decoded.as_dyn_mut(), Default::default());
+/// for chunk in stream.chunks(4) {
+///     assert_eq!(decoder.push(chunk, &mut arena).unwrap(), chunk.len());
+/// }
+/// decoder.finish(&mut arena).unwrap();
+/// assert_eq!(decoded.name(), "a.proto");
+/// ```
+pub struct FrameDecoder<'a, const STACK_DEPTH: usize> {
+    header: [u8; 5],
+    header_len: usize,
+    /// Body bytes not received yet, once the header is complete.
+    remaining: usize,
+    max_message_bytes: usize,
+    failed: bool,
+    decoder: ResumeableDecode<'a, STACK_DEPTH>,
+}
+
+impl<'a, const STACK_DEPTH: usize> FrameDecoder<'a, STACK_DEPTH> {
+    /// Decode the next frame into `msg`.
+    pub fn new<'pool: 'a>(
+        msg: crate::reflection::DynamicMessage<'pool, 'a>,
+        options: DecodeOptions,
+    ) -> Self {
+        FrameDecoder {
+            header: [0; 5],
+            header_len: 0,
+            remaining: 0,
+            max_message_bytes: options.max_message_bytes,
+            failed: false,
+            decoder: ResumeableDecode::new(msg, isize::MAX, options),
+        }
+    }
+
+    /// Decode the bytes of the frame at the start of `buf`, returning how many
+    /// that are. Bytes past the end of the frame are left for the next one.
+    pub fn push(&mut self, buf: &[u8], arena: &mut Arena) -> Result<usize, Status> {
+        if self.failed {
+            return Err(Status::invalid_argument("gRPC frame already failed to decode"));
+        }
+        let result = self.push_impl(buf, arena);
+        self.failed = result.is_err();
+        result
+    }
+
+    fn push_impl(&mut self, mut buf: &[u8], arena: &mut Arena) -> Result<usize, Status> {
+        let len = buf.len();
+        if self.header_len < 5 {
+            let n = buf.len().min(5 - self.header_len);
+            self.header[self.header_len..self.header_len + n].copy_from_slice(&buf[..n]);
+            self.header_len += n;
+            buf = &buf[n..];
+            if self.header_len < 5 {
+                return Ok(len);
+            }
+            if self.header[0] != 0 {
+                return Err(Status::unimplemented("compressed gRPC messages"));
+            }
+            let [_, length @ ..] = self.header;
+            self.remaining = u32::from_be_bytes(length) as usize;
+            if self.remaining > self.max_message_bytes {
+                return Err(Status::resource_exhausted(format!(
+                    "gRPC message of {} bytes exceeds the limit of {}",
+                    self.remaining, self.max_message_bytes
+                )));
+            }
+        }
+        let body = &buf[..buf.len().min(self.remaining)];
+        if !body.is_empty() && !self.decoder.resume(body, arena) {
+            return Err(Status::invalid_argument("failed to decode gRPC message"));
+        }
+        self.remaining -= body.len();
+        Ok(len - buf.len() + body.len())
+    }
+
+    /// Consume the bytes of the frame at the front of `buf`, chunk by chunk.
+    #[cfg(feature = "bytes")]
+    pub fn push_buf(&mut self, buf: &mut impl bytes::Buf, arena: &mut Arena) -> Result<(), Status> {
+        while buf.has_remaining() && !self.is_complete() {
+            let n = self.push(buf.chunk(), arena)?;
+            buf.advance(n);
+        }
+        Ok(())
+    }
+
+    /// Whether the whole frame was pushed.
+    pub fn is_complete(&self) -> bool {
+        !self.failed && self.header_len == 5 && self.remaining == 0
+    }
+
+    /// Complete the message. Fails if the frame was cut short or its message
+    /// is invalid.
+    pub fn finish(self, arena: &mut Arena) -> Result<(), Status> {
+        if !self.is_complete() {
+            return Err(Status::invalid_argument("truncated gRPC frame"));
+        }
+        if !self.decoder.finish(arena) {
+            return Err(Status::invalid_argument("failed to decode gRPC message"));
+        }
+        Ok(())
+    }
+}
+
+/// Decode a request for a generated server.
+#[doc(hidden)]
+pub fn decode_request<T: Protobuf>(bytes: &[u8]) -> Result<Owned<T>, Status> {
+    Owned::decode(bytes).ok_or_else(|| Status::invalid_argument("failed to decode request"))
+}
+
+/// Decode a response for a generated client.
+#[doc(hidden)]
+pub fn decode_response<T: Protobuf>(bytes: &[u8]) -> Result<Owned<T>, Status> {
+    Owned::decode(bytes).ok_or_else(|| Status::internal("failed to decode response"))
+}
+
+/// Encode a request or response for generated code.
+#[doc(hidden)]
+pub fn encode<T: Protobuf>(msg: &T) -> Result<Vec<u8>, Status> {
+    msg.encode_vec::<STACK_DEPTH>()
+        .map_err(|_| Status::internal("failed to encode message"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::google::protobuf::FileDescriptorProto;
+
+    #[test]
+    fn frame_roundtrip() {
+        let mut buf = encode_frame(b"abc");
+        buf.extend_from_slice(&encode_frame(b""));
+        buf.push(0);
+
+        let first = decode_frame(&buf).unwrap().unwrap();
+        assert_eq!(first.message, b"abc");
+        let second = decode_frame(first.rest).unwrap().unwrap();
+        assert_eq!(second.message, b"");
+        assert_eq!(decode_frame(second.rest).unwrap(), None);
+        assert_eq!(decode_frame(&buf[..6]).unwrap(), None);
+
+        let compressed = [1, 0, 0, 0, 0];
+        assert_eq!(
+            decode_frame(&compressed).unwrap_err().code(),
+            Code::Unimplemented
+        );
+    }
+
+    #[test]
+    fn frame_decoder_streams_body() {
+        let mut msg = Owned::<FileDescriptorProto::ProtoType>::new();
+        let (file, arena) = msg.parts_mut();
+        file.set_name("a.proto", arena).unwrap();
+        file.set_package("pkg", arena).unwrap();
+        let frame = encode_frame(&msg.encode().unwrap());
+        let mut stream = frame.clone();
+        stream.extend_from_slice(&encode_frame(b"next"));
+
+        // Split inside the header and the body, the next frame is left over
+        let mut arena = Arena::new(&Global);
+        let mut decoded = FileDescriptorProto::ProtoType::default();
+        let mut decoder = FrameDecoder::<32>::new(decoded.as_dyn_mut(), DecodeOptions::default());
+        let mut consumed = 0;
+        for chunk in stream.chunks(3) {
+            consumed += decoder.push(chunk, &mut arena).unwrap();
+        }
+        assert_eq!(consumed, frame.len());
+        assert!(decoder.is_complete());
+        decoder.finish(&mut arena).unwrap();
+        assert_eq!((decoded.name(), decoded.package()), ("a.proto", "pkg"));
+
+        // The length is checked before the body arrives
+        let options = DecodeOptions {
+            max_message_bytes: 4,
+            ..Default::default()
+        };
+        let mut decoded = FileDescriptorProto::ProtoType::default();
+        let mut decoder = FrameDecoder::<32>::new(decoded.as_dyn_mut(), options);
+        let err = decoder.push(&frame[..5], &mut arena).unwrap_err();
+        assert_eq!(err.code(), Code::ResourceExhausted);
+        assert!(decoder.push(&frame[5..], &mut arena).is_err());
+        assert!(decoder.finish(&mut arena).is_err());
+
+        let mut decoded = FileDescriptorProto::ProtoType::default();
+        let mut decoder = FrameDecoder::<32>::new(decoded.as_dyn_mut(), DecodeOptions::default());
+        assert_eq!(decoder.push(&[1, 0, 0, 0, 0], &mut arena).unwrap_err().code(), Code::Unimplemented);
+
+        let mut decoded = FileDescriptorProto::ProtoType::default();
+        let mut decoder = FrameDecoder::<32>::new(decoded.as_dyn_mut(), DecodeOptions::default());
+        decoder.push(&frame[..frame.len() - 1], &mut arena).unwrap();
+        assert!(!decoder.is_complete());
+        assert_eq!(decoder.finish(&mut arena).unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn frame_decoder_reads_buf() {
+        use bytes::Buf;
+
+        let mut msg = Owned::<FileDescriptorProto::ProtoType>::new();
+        let (file, arena) = msg.parts_mut();
+        file.set_name("a.proto", arena).unwrap();
+        let frame = encode_frame(&msg.encode().unwrap());
+        let mut buf = (&frame[..4]).chain(&frame[4..]).chain(&b"rest"[..]);
+
+        let mut arena = Arena::new(&Global);
+        let mut decoded = FileDescriptorProto::ProtoType::default();
+        let mut decoder = FrameDecoder::<32>::new(decoded.as_dyn_mut(), DecodeOptions::default());
+        decoder.push_buf(&mut buf, &mut arena).unwrap();
+        decoder.finish(&mut arena).unwrap();
+        assert_eq!(decoded.name(), "a.proto");
+        assert_eq!(buf.chunk(), b"rest");
+    }
+
+    #[test]
+    fn owned_roundtrip() {
+        let mut msg = Owned::<FileDescriptorProto::ProtoType>::new();
+        let (file, arena) = msg.parts_mut();
+        file.set_name("a.proto", arena).unwrap();
+        let bytes = msg.encode().unwrap();
+
+        let decoded = decode_request::<FileDescriptorProto::ProtoType>(&bytes).unwrap();
+        assert_eq!(decoded.name(), "a.proto");
+
+        let err = decode_response::<FileDescriptorProto::ProtoType>(&[0xff]).unwrap_err();
+        assert_eq!(err.code(), Code::Internal);
+    }
+
+    #[test]
+    fn owned_try_from_bytes() {
+        let mut msg = Owned::<FileDescriptorProto::ProtoType>::new();
+        let (file, arena) = msg.parts_mut();
+        file.set_name("a.proto", arena).unwrap();
+        let bytes = Vec::<u8>::try_from(&msg).unwrap();
+
+        let decoded = Owned::<FileDescriptorProto::ProtoType>::try_from(&bytes[..]).unwrap();
+        assert_eq!(decoded.name(), "a.proto");
+        assert!(Owned::<FileDescriptorProto::ProtoType>::try_from(&[0xff][..]).is_err());
+    }
+
+    #[test]
+    fn owned_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Owned<FileDescriptorProto::ProtoType>>();
+    }
+}
@@ -0,0 +1,185 @@
+//! Glue between generated services and [`tonic`](::tonic).
+//!
+//! Clients use a [`tonic::client::Grpc`](::tonic::client::Grpc) as their
+//! [`Channel`]; servers wrap a generated `Server` in [`TonicServer`] and add it
+//! to a tonic router like any other service.
+//!
+//! ```ignore
+//! let channel = tonic::transport::Channel::from_static("http://[::1]:50051").connect().await?;
+//! let client = Greeter::Client::new(tonic::client::Grpc::new(channel));
+//!
+//! tonic::transport::Server::builder()
+//!     .add_service(TonicServer::new(Greeter::Server::new(MyGreeter)))
+//!     .serve(addr)
+//!     .await?;
+//! ```
+
+use core::future::Future;
+use core::task::{Context, Poll};
+use std::sync::Arc;
+
+use ::tonic::body::Body;
+use ::tonic::client::{Grpc, GrpcService};
+use ::tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use ::tonic::codegen::http::uri::PathAndQuery;
+use ::tonic::codegen::{Body as HttpBody, BoxFuture, Bytes, StdError, http};
+use ::tonic::server::NamedService;
+use bytes::{Buf, BufMut};
+
+use super::{Channel, Code, Service, Status};
+
+impl From<Status> for ::tonic::Status {
+    fn from(status: Status) -> Self {
+        ::tonic::Status::new((status.code as i32).into(), status.message)
+    }
+}
+
+impl From<::tonic::Status> for Status {
+    fn from(status: ::tonic::Status) -> Self {
+        Status::new(Code::from_i32(status.code() as i32), status.message())
+    }
+}
+
+/// A tonic [`Codec`] passing encoded messages through unchanged.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BytesCodec;
+
+impl Codec for BytesCodec {
+    type Encode = Vec<u8>;
+    type Decode = Vec<u8>;
+    type Encoder = BytesCodec;
+    type Decoder = BytesCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        BytesCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        BytesCodec
+    }
+}
+
+impl Encoder for BytesCodec {
+    type Item = Vec<u8>;
+    type Error = ::tonic::Status;
+
+    fn encode(&mut self, item: Vec<u8>, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        dst.put_slice(&item);
+        Ok(())
+    }
+}
+
+impl Decoder for BytesCodec {
+    type Item = Vec<u8>;
+    type Error = ::tonic::Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Vec<u8>>, Self::Error> {
+        let mut item = vec![0; src.remaining()];
+        src.copy_to_slice(&mut item);
+        Ok(Some(item))
+    }
+}
+
+impl<T> Channel for Grpc<T>
+where
+    T: GrpcService<Body> + Clone + Send + Sync,
+    T::Error: Into<StdError>,
+    T::Future: Send,
+    T::ResponseBody: HttpBody<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+{
+    fn unary(
+        &self,
+        path: &'static str,
+        request: Vec<u8>,
+    ) -> impl Future<Output = Result<Vec<u8>, Status>> + Send {
+        let mut grpc = self.clone();
+        async move {
+            grpc.ready()
+                .await
+                .map_err(|e| Status::new(Code::Unavailable, e.into().to_string()))?;
+            // Spelled out: the `Channel` method of the same name would shadow it.
+            let response = Grpc::unary(
+                &mut grpc,
+                ::tonic::Request::new(request),
+                PathAndQuery::from_static(path),
+                BytesCodec,
+            )
+            .await?;
+            Ok(response.into_inner())
+        }
+    }
+}
+
+/// Serves a [`Service`] through tonic.
+pub struct TonicServer<S>(Arc<S>);
+
+impl<S> TonicServer<S> {
+    pub fn new(service: S) -> Self {
+        TonicServer(Arc::new(service))
+    }
+}
+
+impl<S> Clone for TonicServer<S> {
+    fn clone(&self) -> Self {
+        TonicServer(self.0.clone())
+    }
+}
+
+impl<S: Service> NamedService for TonicServer<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S, B> ::tonic::codegen::Service<http::Request<B>> for TonicServer<S>
+where
+    S: Service,
+    B: HttpBody + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = core::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let method = super::split_path(request.uri().path())
+            .filter(|(service, _)| *service == S::NAME)
+            .map(|(_, method)| method.to_string());
+        let service = self.0.clone();
+        Box::pin(async move {
+            let Some(method) = method else {
+                return Ok(::tonic::Status::unimplemented("").into_http());
+            };
+            let mut grpc = ::tonic::server::Grpc::new(BytesCodec);
+            Ok(grpc.unary(MethodCall { service, method }, request).await)
+        })
+    }
+}
+
+/// One method of a [`Service`] as a tonic unary service.
+struct MethodCall<S> {
+    service: Arc<S>,
+    method: String,
+}
+
+impl<S: Service> ::tonic::codegen::Service<::tonic::Request<Vec<u8>>> for MethodCall<S> {
+    type Response = ::tonic::Response<Vec<u8>>;
+    type Error = ::tonic::Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: ::tonic::Request<Vec<u8>>) -> Self::Future {
+        let service = self.service.clone();
+        let method = core::mem::take(&mut self.method);
+        Box::pin(async move {
+            let response = service.call(&method, request.get_ref()).await?;
+            Ok(::tonic::Response::new(response))
+        })
+    }
+}
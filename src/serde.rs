@@ -1,12 +1,14 @@
 use serde::ser::{SerializeSeq, SerializeStruct};
 use serde::de::Error;
 
-use crate::ProtobufMut;
+use crate::{ProtobufMut, ProtobufRef};
 use crate::base::Object;
 use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
 use crate::reflection::{
-    DynamicMessage, DynamicMessageArray, DynamicMessageRef, Value, default_value,
+    DynamicMessage, DynamicMessageArray, DynamicMessageRef, Value, default_value, is_any,
+    is_map_entry, lookup_enum_name,
 };
+use crate::proto_json::Int64;
 use crate::tables::Table;
 
 fn unbound_lifetime<'a, T: ?Sized>(t: &T) -> &'a T {
@@ -31,6 +33,56 @@ fn set_field<T>(obj: &mut Object, entry: crate::decoding::TableEntry, field_numb
     }
 }
 
+/// Reset a field to its default value, as JSON `null` does.
+fn clear_field(
+    obj: &mut Object,
+    table: &Table,
+    entry: crate::decoding::TableEntry,
+    field: &crate::google::protobuf::FieldDescriptorProto::ProtoType,
+) {
+    let ty = field.r#type().unwrap();
+    let offset = match ty {
+        Type::TYPE_MESSAGE | Type::TYPE_GROUP => table.aux_entry_decode(entry).0,
+        _ => entry.offset(),
+    };
+    if field.label() == Some(Label::LABEL_REPEATED) {
+        // The layout of a repeated field doesn't depend on the element type
+        obj.ref_mut::<crate::containers::RepeatedField<u8>>(offset).clear();
+        return;
+    }
+    let has_bit_idx = entry.has_bit_idx();
+    if has_bit_idx & 0x80 != 0 {
+        let discriminant = obj.ref_mut::<u32>((has_bit_idx & 0x7F) * 4);
+        if *discriminant == field.number() as u32 {
+            *discriminant = 0;
+        }
+        return;
+    }
+    obj.clear_has_bit(has_bit_idx);
+    match ty {
+        Type::TYPE_BOOL => *obj.ref_mut::<bool>(offset) = false,
+        Type::TYPE_INT64
+        | Type::TYPE_UINT64
+        | Type::TYPE_SINT64
+        | Type::TYPE_FIXED64
+        | Type::TYPE_SFIXED64
+        | Type::TYPE_DOUBLE => *obj.ref_mut::<u64>(offset) = 0,
+        Type::TYPE_STRING | Type::TYPE_BYTES => {
+            obj.ref_mut::<crate::containers::Bytes>(offset).clear()
+        }
+        Type::TYPE_MESSAGE | Type::TYPE_GROUP => {
+            *obj.ref_mut::<crate::base::Message>(offset) =
+                crate::base::Message(core::ptr::null_mut())
+        }
+        _ => *obj.ref_mut::<u32>(offset) = 0,
+    }
+}
+
+/// `google.protobuf.NullValue`, whose only value is spelled `null` in JSON.
+fn is_null_value_enum(type_name: &str) -> bool {
+    type_name == ".google.protobuf.NullValue"
+}
+
 // Well-known type detection and handling
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum WellKnownType {
@@ -48,6 +100,7 @@ enum WellKnownType {
     Value,
     Struct,
     ListValue,
+    Any,
     None,
 }
 
@@ -72,71 +125,24 @@ fn detect_well_known_type(
         "Value" => WellKnownType::Value,
         "Struct" => WellKnownType::Struct,
         "ListValue" => WellKnownType::ListValue,
+        "Any" if is_any(descriptor) => WellKnownType::Any,
         _ => WellKnownType::None,
     }
 }
 
-/// Look up enum value by name from the message descriptor
-/// Returns the integer value if found, None otherwise
-fn lookup_enum_value(
-    descriptor: &crate::google::protobuf::DescriptorProto::ProtoType,
-    type_name: &str,
-    value_name: &str,
-) -> Option<i32> {
-    // Extract enum name from type_name (e.g., ".package.Message.EnumName" -> "EnumName")
-    let enum_name = type_name.rsplit('.').next()?;
-
-    // Search nested enums in this message
-    for enum_type in descriptor.enum_type() {
-        if enum_type.name() == enum_name {
-            for value in enum_type.value() {
-                if value.name() == value_name {
-                    return Some(value.number());
-                }
-            }
-        }
-    }
-
-    // Search in nested messages
-    for nested in descriptor.nested_type() {
-        if let Some(v) = lookup_enum_value(nested.as_ref(), type_name, value_name) {
-            return Some(v);
-        }
-    }
-
-    None
-}
-
-/// Look up enum name by value from the message descriptor
-fn lookup_enum_name<'a>(
-    descriptor: &'a crate::google::protobuf::DescriptorProto::ProtoType,
-    type_name: &str,
-    value: i32,
-) -> Option<&'a str> {
-    let enum_name = type_name.rsplit('.').next()?;
-
-    for enum_type in descriptor.enum_type() {
-        if enum_type.name() == enum_name {
-            for enum_value in enum_type.value() {
-                if enum_value.number() == value {
-                    return Some(enum_value.name());
-                }
-            }
-        }
-    }
-
-    for nested in descriptor.nested_type() {
-        if let Some(name) = lookup_enum_name(nested.as_ref(), type_name, value) {
-            return Some(name);
-        }
-    }
-
-    None
+/// Look up enum value by name, finding the enum as
+/// [`lookup_enum_name`] does.
+fn lookup_enum_value(table: &Table, type_name: &str, value_name: &str) -> Option<i32> {
+    crate::reflection::find_enum(table, type_name)?
+        .value()
+        .iter()
+        .find(|value| value.name() == value_name)
+        .map(|value| value.number())
 }
 
 /// Wrapper for serializing a single enum value as its string name.
 struct EnumValue<'a> {
-    descriptor: &'a crate::google::protobuf::DescriptorProto::ProtoType,
+    table: &'a Table,
     type_name: &'a str,
     value: i32,
 }
@@ -146,8 +152,15 @@ impl serde::Serialize for EnumValue<'_> {
     where
         S: serde::Serializer,
     {
+        if TEXT_FORMAT_NAMES.get() {
+            // Unit variants, so the text format can write names unquoted
+            return match lookup_enum_name(self.table, self.type_name, self.value) {
+                Some(name) => serializer.serialize_unit_variant("", self.value as u32, unbound_lifetime(name)),
+                None => serializer.serialize_i32(self.value),
+            };
+        }
         if serializer.is_human_readable() {
-            match lookup_enum_name(self.descriptor, self.type_name, self.value) {
+            match lookup_enum_name(self.table, self.type_name, self.value) {
                 Some(name) => serializer.serialize_str(name),
                 None => serializer.serialize_i32(self.value),
             }
@@ -159,7 +172,7 @@ impl serde::Serialize for EnumValue<'_> {
 
 /// Wrapper for serializing repeated enum values as string names.
 struct RepeatedEnumValue<'a> {
-    descriptor: &'a crate::google::protobuf::DescriptorProto::ProtoType,
+    table: &'a Table,
     type_name: &'a str,
     values: &'a [i32],
 }
@@ -170,17 +183,13 @@ impl serde::Serialize for RepeatedEnumValue<'_> {
         S: serde::Serializer,
     {
         use serde::ser::SerializeSeq;
-        let human_readable = serializer.is_human_readable();
         let mut seq = serializer.serialize_seq(Some(self.values.len()))?;
-        for &v in self.values {
-            if human_readable {
-                match lookup_enum_name(self.descriptor, self.type_name, v) {
-                    Some(name) => seq.serialize_element(name)?,
-                    None => seq.serialize_element(&v)?,
-                }
-            } else {
-                seq.serialize_element(&v)?;
-            }
+        for &value in self.values {
+            seq.serialize_element(&EnumValue {
+                table: self.table,
+                type_name: self.type_name,
+                value,
+            })?;
         }
         seq.end()
     }
@@ -385,6 +394,67 @@ where
     serializer.serialize_str(&duration_str)
 }
 
+/// Stack depth used to decode and encode the message packed in an `Any`.
+const ANY_STACK_DEPTH: usize = 64;
+
+/// Serialize an `Any` as the JSON of the message it packs plus an `"@type"`
+/// member, or as `{"@type": ..., "value": ...}` if that message is a
+/// well-known type with its own JSON form. The packed type is resolved as
+/// described in [`with_type_resolver`].
+fn serialize_any<S>(msg: &DynamicMessageRef, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    use serde::ser::{Error, SerializeMap};
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    let type_url = match msg.find_field_descriptor_by_number(1).and_then(|f| msg.get_field(f)) {
+        Some(Value::String(s)) => s,
+        _ => "",
+    };
+    let value = match msg.find_field_descriptor_by_number(2).and_then(|f| msg.get_field(f)) {
+        Some(Value::Bytes(b)) => b,
+        _ => &[],
+    };
+    if type_url.is_empty() {
+        if !value.is_empty() {
+            return Err(S::Error::custom("Any has a value but no type URL"));
+        }
+        return serializer.serialize_map(Some(0))?.end();
+    }
+    let table = resolve_any_type(type_url)
+        .ok_or_else(|| S::Error::custom(format!("can't resolve Any type '{}'", type_url)))?;
+
+    let mut arena = crate::arena::Arena::new(&Global);
+    let object = Object::create(table.size as u32, &mut arena).map_err(S::Error::custom)?;
+    let mut packed = DynamicMessage { object, table };
+    if !packed.decode_flat::<ANY_STACK_DEPTH>(&mut arena, value) {
+        return Err(S::Error::custom(format!("invalid value of Any type '{}'", type_url)));
+    }
+    let packed = packed.as_ref();
+
+    let mut map = serializer.serialize_map(None)?;
+    map.serialize_entry("@type", type_url)?;
+    if detect_well_known_type(table.descriptor()) == WellKnownType::None {
+        for field in table.descriptor().field() {
+            if let Some(value) = packed.get_field(field) {
+                let value = FieldJson {
+                    table,
+                    field,
+                    value,
+                };
+                map.serialize_entry(field.json_name(), &value)?;
+            }
+        }
+    } else {
+        map.serialize_entry("value", &packed)?;
+    }
+    map.end()
+}
+
 impl<'pool, 'msg> serde::Serialize for DynamicMessageRef<'pool, 'msg> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -403,7 +473,7 @@ impl<'pool, 'msg> serde::Serialize for DynamicMessageRef<'pool, 'msg> {
                 _ => None,
             }),
             WellKnownType::Int64Value => serialize_wrapper(self, serializer, |v| match v {
-                Value::Int64(i) => Some(*i),
+                Value::Int64(i) => Some(Int64(*i)),
                 _ => None,
             }),
             WellKnownType::UInt32Value => serialize_wrapper(self, serializer, |v| match v {
@@ -411,7 +481,7 @@ impl<'pool, 'msg> serde::Serialize for DynamicMessageRef<'pool, 'msg> {
                 _ => None,
             }),
             WellKnownType::UInt64Value => serialize_wrapper(self, serializer, |v| match v {
-                Value::UInt64(u) => Some(*u),
+                Value::UInt64(u) => Some(Int64(*u)),
                 _ => None,
             }),
             WellKnownType::FloatValue => serialize_wrapper(self, serializer, |v| match v {
@@ -437,7 +507,7 @@ impl<'pool, 'msg> serde::Serialize for DynamicMessageRef<'pool, 'msg> {
                     .find_field_descriptor_by_number(1)
                     .ok_or_else(|| serde::ser::Error::custom("BytesValue missing 'value' field"))?;
                 if let Some(Value::Bytes(b)) = self.get_field(field) {
-                    serializer.serialize_bytes(b)
+                    crate::proto_json::serialize_bytes(serializer, b)
                 } else {
                     serializer.serialize_none()
                 }
@@ -518,7 +588,9 @@ impl<'pool, 'msg> serde::Serialize for DynamicMessageRef<'pool, 'msg> {
                 }
                 seq.end()
             }
-            WellKnownType::None => {
+            // Binary formats keep the plain message, see serialize_any
+            WellKnownType::Any if serializer.is_human_readable() => serialize_any(self, serializer),
+            WellKnownType::Any | WellKnownType::None => {
                 // Regular message serialization
                 // Count fields first
                 let field_count = descriptor
@@ -533,51 +605,210 @@ impl<'pool, 'msg> serde::Serialize for DynamicMessageRef<'pool, 'msg> {
                         continue;
                     };
                     // Transmute needed due to serialize_field requiring 'static
-                    let json_name: &'static str = unbound_lifetime(field.json_name());
-
-                    // Check if this is an enum field - use wrapper that respects is_human_readable
-                    if field.r#type() == Some(Type::TYPE_ENUM) {
-                        let type_name = field.type_name();
-                        match value {
-                            Value::Int32(int_val) => {
-                                let enum_val = EnumValue {
-                                    descriptor,
-                                    type_name,
-                                    value: int_val,
-                                };
-                                struct_serializer.serialize_field(json_name, &enum_val)?;
-                            }
-                            Value::RepeatedInt32(list) => {
-                                let enum_vals = RepeatedEnumValue {
-                                    descriptor,
-                                    type_name,
-                                    values: list,
-                                };
-                                struct_serializer.serialize_field(json_name, &enum_vals)?;
-                            }
-                            _ => {
-                                // Can't happen
-                                unreachable!("Enum field with non-int32 value");
+                    let name = if TEXT_FORMAT_NAMES.get() { field.name() } else { field.json_name() };
+                    let name: &'static str = unbound_lifetime(name);
+
+                    let value = FieldJson {
+                        table: self.table,
+                        field,
+                        value,
+                    };
+                    struct_serializer.serialize_field(name, &value)?;
+                }
+                struct_serializer.end()
+            }
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl DynamicMessageRef<'_, '_> {
+    /// This message as a proto JSON tree, for code that edits JSON in memory
+    /// before writing it out.
+    ///
+    /// `google.protobuf.Any` fields need a pool to resolve their type, see
+    /// [`with_type_resolver`].
+    pub fn to_json_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+        use serde::Serialize;
+
+        self.serialize(crate::proto_json::ProtoJsonSerializer::new(serde_json::value::Serializer))
+    }
+
+    /// The value at `path` in this message's proto JSON, without converting
+    /// the whole message.
+    ///
+    /// `path` is a small JSONPath subset: `$` followed by `.name` and
+    /// `["name"]` steps for fields (JSON or proto name) and map keys, and
+    /// `[index]` steps for repeated fields, e.g. `$.child1.nested[0].x`.
+    /// Messages are walked through reflection and only the value reached is
+    /// converted. Returns `None` for unset fields, missing map keys and
+    /// indices out of range; naming a field the message doesn't have is an
+    /// error.
+    pub fn query_json(&self, path: &str) -> Result<Option<serde_json::Value>, serde_json::Error> {
+        use serde::Serialize;
+
+        let steps = parse_json_path(path)?;
+        let mut steps = steps.iter().peekable();
+        let mut msg = DynamicMessageRef {
+            object: self.object,
+            table: self.table,
+        };
+        let json = loop {
+            let descriptor = msg.table.descriptor();
+            if steps.peek().is_none() || detect_well_known_type(descriptor) != WellKnownType::None {
+                // Well-known types have a JSON form of their own
+                break msg.to_json_value()?;
+            }
+            let JsonPathStep::Name(name) = steps.next().unwrap() else {
+                return Ok(None);
+            };
+            let field = descriptor
+                .field()
+                .iter()
+                .find(|f| f.json_name() == name || f.name() == name)
+                .ok_or_else(|| {
+                    serde_json::Error::custom(format!("no field {} in {}", name, msg.full_name()))
+                })?;
+            let Some(value) = msg.get_field(field) else {
+                return Ok(None);
+            };
+            match value {
+                Value::Message(child) => msg = child,
+                Value::RepeatedMessage(array) if !is_map_entry(array.table.descriptor()) => {
+                    match steps.peek() {
+                        Some(JsonPathStep::Index(index)) => {
+                            steps.next();
+                            if *index >= array.len() {
+                                return Ok(None);
                             }
+                            msg = array.get(*index);
                         }
-                    } else {
-                        struct_serializer.serialize_field(json_name, &value)?;
+                        Some(JsonPathStep::Name(_)) => return Ok(None),
+                        None => break array.serialize(serde_json::value::Serializer)?,
                     }
                 }
-                struct_serializer.end()
+                value => {
+                    let value = FieldJson {
+                        table: msg.table,
+                        field,
+                        value,
+                    };
+                    break value.serialize(crate::proto_json::ProtoJsonSerializer::new(
+                        serde_json::value::Serializer,
+                    ))?;
+                }
+            }
+        };
+
+        // The rest of the path is inside a scalar, map or well-known type
+        let mut json = &json;
+        for step in steps {
+            let next = match step {
+                JsonPathStep::Name(name) => json.get(name.as_str()),
+                JsonPathStep::Index(index) => json.get(index),
+            };
+            match next {
+                Some(next) => json = next,
+                None => return Ok(None),
             }
         }
+        Ok(Some(json.clone()))
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-enum MapKey {
-    Bool(bool),
-    Int32(i32),
-    Int64(i64),
-    UInt32(u32),
-    UInt64(u64),
-    String(std::string::String),
+/// A step of a [`query_json`](DynamicMessageRef::query_json) path.
+#[cfg(feature = "json")]
+enum JsonPathStep {
+    Name(std::string::String),
+    Index(usize),
+}
+
+#[cfg(feature = "json")]
+fn parse_json_path(path: &str) -> Result<std::vec::Vec<JsonPathStep>, serde_json::Error> {
+    let invalid = || serde_json::Error::custom(format!("invalid JSON path {:?}", path));
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut steps = std::vec::Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            steps.push(JsonPathStep::Name(after[..end].into()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let (inner, after) = after.split_once(']').ok_or_else(invalid)?;
+            let quoted = inner
+                .strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .or_else(|| inner.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')));
+            let step = match quoted {
+                Some(name) => JsonPathStep::Name(name.into()),
+                None => JsonPathStep::Index(inner.parse().map_err(|_| invalid())?),
+            };
+            steps.push(step);
+            rest = after;
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(steps)
+}
+
+#[cfg(feature = "json")]
+impl<'pool, 'msg> DynamicMessage<'pool, 'msg> {
+    /// A new message of type `table`, allocated in `arena`, with the fields
+    /// of `value`, a proto JSON tree as [`to_json_value`](DynamicMessageRef::to_json_value)
+    /// makes them.
+    pub fn from_json_value(
+        table: &'pool Table,
+        value: &serde_json::Value,
+        arena: &mut crate::arena::Arena<'msg>,
+    ) -> Result<Self, serde_json::Error> {
+        let object = Object::create(table.size as u32, arena).map_err(serde_json::Error::custom)?;
+        let mut msg = DynamicMessage { object, table };
+        msg.serde_deserialize(arena, crate::proto_json::ProtoJsonDeserializer::new(value))?;
+        Ok(msg)
+    }
+}
+
+/// A field value as it appears in the JSON of its message, enums by name.
+struct FieldJson<'a, 'pool, 'msg> {
+    table: &'a Table,
+    field: &'a crate::google::protobuf::FieldDescriptorProto::ProtoType,
+    value: Value<'pool, 'msg>,
+}
+
+impl serde::Serialize for FieldJson<'_, '_, '_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        // Check if this is an enum field - use wrapper that respects is_human_readable
+        if self.field.r#type() != Some(Type::TYPE_ENUM) {
+            return self.value.serialize(serializer);
+        }
+        let table = self.table;
+        let type_name = self.field.type_name();
+        match self.value {
+            Value::Int32(value) => EnumValue {
+                table,
+                type_name,
+                value,
+            }
+            .serialize(serializer),
+            Value::RepeatedInt32(values) => RepeatedEnumValue {
+                table,
+                type_name,
+                values,
+            }
+            .serialize(serializer),
+            _ => {
+                // Can't happen
+                unreachable!("Enum field with non-int32 value");
+            }
+        }
+    }
 }
 
 impl<'pool, 'msg> serde::Serialize for DynamicMessageArray<'pool, 'msg> {
@@ -585,17 +816,11 @@ impl<'pool, 'msg> serde::Serialize for DynamicMessageArray<'pool, 'msg> {
     where
         S: serde::Serializer,
     {
-        if self
-            .table
-            .descriptor
-            .options()
-            .map(|o| o.map_entry())
-            .unwrap_or(false)
-        {
+        if is_map_entry(self.table.descriptor()) {
             use serde::ser::SerializeMap;
             let mut map_serializer = serializer.serialize_map(Some(self.object.len()))?;
 
-            let mut seen_keys = std::collections::hash_set::HashSet::<MapKey>::new();
+            let mut seen_keys = std::collections::hash_set::HashSet::new();
             for index in (0..self.object.len()).rev() {
                 let entry = self.get(index);
                 let key_field = entry
@@ -615,19 +840,9 @@ impl<'pool, 'msg> serde::Serialize for DynamicMessageArray<'pool, 'msg> {
                 let value_val = entry
                     .get_field(value_field)
                     .or_else(|| default_value(value_field));
-                let map_key = match key_val {
-                    Value::Bool(v) => MapKey::Bool(v),
-                    Value::Int32(v) => MapKey::Int32(v),
-                    Value::Int64(v) => MapKey::Int64(v),
-                    Value::UInt32(v) => MapKey::UInt32(v),
-                    Value::UInt64(v) => MapKey::UInt64(v),
-                    Value::String(v) => MapKey::String(v.to_string()),
-                    _ => {
-                        return Err(serde::ser::Error::custom(
-                            "Invalid map key type; must be scalar",
-                        ));
-                    }
-                };
+                let map_key = entry.map_key().ok_or_else(|| {
+                    serde::ser::Error::custom("Invalid map key type; must be scalar")
+                })?;
                 if !seen_keys.insert(map_key) {
                     continue; // Skip duplicate keys, keep the last one
                 }
@@ -635,7 +850,7 @@ impl<'pool, 'msg> serde::Serialize for DynamicMessageArray<'pool, 'msg> {
                 if value_field.r#type() == Some(Type::TYPE_ENUM) {
                     if let Some(Value::Int32(int_val)) = value_val {
                         let enum_val = EnumValue {
-                            descriptor: self.table.descriptor,
+                            table: self.table,
                             type_name: value_field.type_name(),
                             value: int_val,
                         };
@@ -665,24 +880,35 @@ impl<'pool, 'msg> serde::Serialize for Value<'pool, 'msg> {
         match *self {
             Value::Bool(v) => serializer.serialize_bool(v),
             Value::Int32(v) => serializer.serialize_i32(v),
-            Value::Int64(v) => serializer.serialize_i64(v),
+            Value::Int64(v) => Int64(v).serialize(serializer),
             Value::UInt32(v) => serializer.serialize_u32(v),
-            Value::UInt64(v) => serializer.serialize_u64(v),
+            Value::UInt64(v) => Int64(v).serialize(serializer),
             Value::Float(v) => serializer.serialize_f32(v),
             Value::Double(v) => serializer.serialize_f64(v),
             Value::String(v) => serializer.serialize_str(v),
-            Value::Bytes(v) => serializer.serialize_bytes(v),
+            Value::Bytes(v) => crate::proto_json::serialize_bytes(serializer, v),
             Value::Message(ref msg) => msg.serialize(serializer),
             Value::RepeatedBool(list) => list.serialize(serializer),
             Value::RepeatedInt32(list) => list.serialize(serializer),
-            Value::RepeatedInt64(list) => list.serialize(serializer),
+            Value::RepeatedInt64(list) => serializer.collect_seq(list.iter().map(|&v| Int64(v))),
             Value::RepeatedUInt32(list) => list.serialize(serializer),
-            Value::RepeatedUInt64(list) => list.serialize(serializer),
+            Value::RepeatedUInt64(list) => serializer.collect_seq(list.iter().map(|&v| Int64(v))),
             Value::RepeatedFloat(list) => list.serialize(serializer),
             Value::RepeatedDouble(list) => list.serialize(serializer),
             Value::RepeatedString(list) => list.serialize(serializer),
             Value::RepeatedBytes(list) => list.serialize(serializer),
             Value::RepeatedMessage(ref list) => list.serialize(serializer),
+            Value::Enum { number, .. } => match self.enum_name() {
+                Some(name) => serializer.serialize_str(name),
+                None => serializer.serialize_i32(number),
+            },
+            Value::RepeatedEnum { numbers, descriptor } => {
+                let mut seq = serializer.serialize_seq(Some(numbers.len()))?;
+                for &number in numbers {
+                    seq.serialize_element(&Value::Enum { number, descriptor })?;
+                }
+                seq.end()
+            }
         }
     }
 }
@@ -692,7 +918,7 @@ impl serde::Serialize for crate::containers::Bytes {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_bytes(self.as_ref())
+        crate::proto_json::serialize_bytes(serializer, self.as_ref())
     }
 }
 
@@ -705,14 +931,48 @@ impl serde::Serialize for crate::containers::String {
     }
 }
 
+/// Options for parsing proto JSON.
+#[derive(Debug, Clone, Copy)]
+pub struct JsonParseOptions {
+    /// Skip object keys that don't name a field of the message instead of
+    /// failing. The proto3 JSON spec makes rejecting them the default.
+    pub ignore_unknown_fields: bool,
+    /// Fail when an object names the same field twice, under its JSON name or
+    /// its proto name, or sets more than one member of a oneof.
+    pub reject_duplicate_fields: bool,
+    /// Skip enum values given by a name the enum doesn't have instead of
+    /// failing: a singular field is left unset, and the element of a repeated
+    /// field or the entry of a map field is dropped. The proto3 JSON spec makes
+    /// rejecting them the default.
+    pub ignore_unknown_enum_values: bool,
+}
+
+impl Default for JsonParseOptions {
+    fn default() -> Self {
+        JsonParseOptions {
+            ignore_unknown_fields: false,
+            reject_duplicate_fields: true,
+            ignore_unknown_enum_values: false,
+        }
+    }
+}
+
 pub struct SerdeDeserialize<'arena, 'alloc, T>(
     &'arena mut crate::arena::Arena<'alloc>,
+    JsonParseOptions,
     core::marker::PhantomData<T>,
 );
 
 impl<'arena, 'alloc, T> SerdeDeserialize<'arena, 'alloc, T> {
     pub fn new(arena: &'arena mut crate::arena::Arena<'alloc>) -> Self {
-        SerdeDeserialize(arena, core::marker::PhantomData)
+        Self::with_options(arena, JsonParseOptions::default())
+    }
+
+    pub fn with_options(
+        arena: &'arena mut crate::arena::Arena<'alloc>,
+        options: JsonParseOptions,
+    ) -> Self {
+        SerdeDeserialize(arena, options, core::marker::PhantomData)
     }
 }
 
@@ -726,16 +986,146 @@ impl<'de, 'arena, 'alloc, T: crate::generated_code_only::Protobuf + 'alloc>
         D: serde::Deserializer<'de>,
     {
         // Deserialization logic to be implemented
-        let SerdeDeserialize(arena, _) = self;
+        let SerdeDeserialize(arena, options, _) = self;
         let mut msg = T::default();
-        serde_deserialize_struct(msg.as_dyn_mut(), arena, deserializer)?;
+        serde_deserialize_struct_with_options(msg.as_dyn_mut(), arena, deserializer, options)?;
         Ok(msg)
     }
 }
 
+std::thread_local! {
+    /// Arena installed by [`with_arena`] on this thread, or null.
+    static SCOPED_ARENA: core::cell::Cell<*mut crate::arena::Arena<'static>> =
+        const { core::cell::Cell::new(core::ptr::null_mut()) };
+}
+
+/// Run `f` with `arena` as the arena that `serde::Deserialize` impls of
+/// generated types allocate from on this thread.
+///
+/// Code generated with the `serde` option implements `Deserialize` directly
+/// on message types, so they work with any serde-based framework, but that
+/// leaves no way to pass an arena. Those impls allocate from the innermost
+/// `with_arena` arena, and fail outside of one. Where the arena can be passed,
+/// prefer the safe [`SerdeDeserialize`] seed.
+///
+/// # Safety
+///
+/// The messages `Deserialize` returns in `f` point into `arena` without
+/// borrowing it. They must not be used after `arena` is dropped or reset.
+pub unsafe fn with_arena<R>(arena: &mut crate::arena::Arena, f: impl FnOnce() -> R) -> R {
+    struct Restore(*mut crate::arena::Arena<'static>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SCOPED_ARENA.set(self.0);
+        }
+    }
+
+    let arena = (arena as *mut crate::arena::Arena).cast();
+    let _restore = Restore(SCOPED_ARENA.replace(arena));
+    f()
+}
+
+std::thread_local! {
+    /// Pool installed by [`with_type_resolver`] on this thread, or null.
+    static TYPE_RESOLVER: core::cell::Cell<*const crate::descriptor_pool::DescriptorPool<'static>> =
+        const { core::cell::Cell::new(core::ptr::null()) };
+}
+
+std::thread_local! {
+    /// Set while a `text_format::Serializer` writes a message on this thread,
+    /// naming fields by their proto names and enum values as unit variants.
+    static TEXT_FORMAT_NAMES: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+}
+
+/// Keeps text format naming on until dropped, see `TEXT_FORMAT_NAMES`.
+pub(crate) struct TextFormatNames(bool);
+
+impl TextFormatNames {
+    pub(crate) fn new() -> Self {
+        TextFormatNames(TEXT_FORMAT_NAMES.replace(true))
+    }
+}
+
+impl Drop for TextFormatNames {
+    fn drop(&mut self) {
+        TEXT_FORMAT_NAMES.set(self.0);
+    }
+}
+
+/// Run `f` with `pool` resolving the types packed in `google.protobuf.Any`
+/// messages serialized to or deserialized from JSON on this thread.
+///
+/// The JSON of an `Any` inlines the message it packs, which takes knowing its
+/// type. The last segment of the type URL is looked up in the pool of the
+/// innermost `with_type_resolver`, then among the well-known types built into
+/// protocrap: `Any`, `Duration`, `Empty`, `FieldMask`, `ListValue`, `Struct`,
+/// `Timestamp` and `Value`. The wrapper types like `google.protobuf.Int32Value`
+/// aren't built in and resolve only from a pool holding
+/// `google/protobuf/wrappers.proto`. An `Any` of a type that can't be resolved
+/// fails to serialize or deserialize. Binary serde formats keep `Any` as a
+/// plain message.
+pub fn with_type_resolver<R>(
+    pool: &crate::descriptor_pool::DescriptorPool,
+    f: impl FnOnce() -> R,
+) -> R {
+    struct Restore(*const crate::descriptor_pool::DescriptorPool<'static>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            TYPE_RESOLVER.set(self.0);
+        }
+    }
+
+    let pool = (pool as *const crate::descriptor_pool::DescriptorPool).cast();
+    let _restore = Restore(TYPE_RESOLVER.replace(pool));
+    f()
+}
+
+/// The table of the message type named by `type_url`, see
+/// [`with_type_resolver`].
+fn resolve_any_type(type_url: &str) -> Option<&'static Table> {
+    use crate::generated_code_only::Protobuf;
+    use crate::well_known;
+
+    let type_name = type_url.rsplit('/').next().unwrap_or_default();
+    let pool = TYPE_RESOLVER.get();
+    if !pool.is_null() {
+        // The pool outlives the with_type_resolver call, and so every use of
+        // the table during (de)serialization.
+        if let Some(table) = unsafe { &*pool }.get_table(type_name) {
+            return Some(unbound_lifetime(table));
+        }
+    }
+    Some(match type_name {
+        "google.protobuf.Any" => well_known::Any::table(),
+        "google.protobuf.Duration" => well_known::Duration::table(),
+        "google.protobuf.Empty" => well_known::Empty::table(),
+        "google.protobuf.FieldMask" => well_known::FieldMask::table(),
+        "google.protobuf.ListValue" => well_known::ListValue::table(),
+        "google.protobuf.Struct" => well_known::Struct::table(),
+        "google.protobuf.Timestamp" => well_known::Timestamp::table(),
+        "google.protobuf.Value" => well_known::Value::table(),
+        _ => return None,
+    })
+}
+
+/// Call `f` with the arena generated `Deserialize` impls allocate from, see
+/// [`with_arena`], or return `None` outside of `with_arena`.
+pub(crate) fn with_scoped_arena<R>(f: impl FnOnce(&mut crate::arena::Arena<'static>) -> R) -> Option<R> {
+    let arena = SCOPED_ARENA.get();
+    if arena.is_null() {
+        return None;
+    }
+    // Deserializing a message doesn't run other generated `Deserialize` impls,
+    // sub-messages are handled through reflection, so this is the only borrow.
+    Some(f(unsafe { &mut *arena }))
+}
+
 struct ProtobufVisitor<'arena, 'alloc, 'b, 'pool> {
     msg: DynamicMessage<'pool, 'b>,
     arena: &'arena mut crate::arena::Arena<'alloc>,
+    options: JsonParseOptions,
 }
 
 impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::DeserializeSeed<'de>
@@ -747,22 +1137,260 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::DeserializeSeed<'de>
     where
         D: serde::Deserializer<'de>,
     {
-        let ProtobufVisitor { msg, arena } = self;
-        serde_deserialize_struct(msg, arena, deserializer)?;
+        let ProtobufVisitor { msg, arena, options } = self;
+        serde_deserialize_struct_with_options(msg, arena, deserializer, options)?;
         Ok(())
     }
 }
 
+/// Visitor for the JSON of an `Any`, see [`serialize_any`].
+struct AnyVisitor<'arena, 'alloc, 'b, 'pool>(ProtobufVisitor<'arena, 'alloc, 'b, 'pool>);
+
+impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
+    for AnyVisitor<'arena, 'alloc, 'b, 'pool>
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter.write_str("an Any object with an \"@type\" member")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let ProtobufVisitor { msg, arena, options } = self.0;
+
+        // "@type" may come after the members it gives the type of, so they are
+        // buffered until the whole object is read
+        let mut type_url: Option<std::string::String> = None;
+        let mut members = Vec::new();
+        while let Some(key) = map.next_key::<std::string::String>()? {
+            if key == "@type" {
+                if type_url.is_some() {
+                    return Err(A::Error::duplicate_field("@type"));
+                }
+                type_url = Some(map.next_value()?);
+            } else {
+                members.push((key, map.next_value::<JsonNode>()?));
+            }
+        }
+        let Some(type_url) = type_url else {
+            if members.is_empty() {
+                return Ok(());
+            }
+            return Err(A::Error::missing_field("@type"));
+        };
+        let value = pack_any(&type_url, members, options).map_err(A::Error::custom)?;
+
+        let type_url_entry = msg
+            .table
+            .field_entry(1)
+            .ok_or_else(|| A::Error::custom("Any missing field 1"))?;
+        let value_entry = msg
+            .table
+            .field_entry(2)
+            .ok_or_else(|| A::Error::custom("Any missing field 2"))?;
+        let type_url = crate::containers::String::from_str(&type_url, arena).map_err(A::Error::custom)?;
+        let value = crate::containers::Bytes::from_slice(&value, arena).map_err(A::Error::custom)?;
+        set_field(msg.object, type_url_entry, 1, type_url);
+        set_field(msg.object, value_entry, 2, value);
+        Ok(())
+    }
+}
+
+/// Encode the message of type `type_url` given by the members of the JSON of
+/// an `Any` other than `"@type"`.
+fn pack_any(
+    type_url: &str,
+    mut members: Vec<(std::string::String, JsonNode)>,
+    options: JsonParseOptions,
+) -> Result<Vec<u8>, serde::de::value::Error> {
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    let table = resolve_any_type(type_url)
+        .ok_or_else(|| Error::custom(format!("can't resolve Any type '{}'", type_url)))?;
+    let json = if detect_well_known_type(table.descriptor()) == WellKnownType::None {
+        JsonNode::Map(members)
+    } else {
+        match members.pop() {
+            Some((key, value)) if key == "value" && members.is_empty() => value,
+            _ => {
+                return Err(Error::custom(format!(
+                    "Any of type '{}' must have a single \"value\" member",
+                    type_url
+                )));
+            }
+        }
+    };
+
+    let mut arena = crate::arena::Arena::new(&Global);
+    let object = Object::create(table.size as u32, &mut arena).map_err(Error::custom)?;
+    let packed = DynamicMessage {
+        object: &mut *object,
+        table,
+    };
+    let deserializer = crate::proto_json::ProtoJsonDeserializer::new(json);
+    serde_deserialize_struct_with_options(packed, &mut arena, deserializer, options)?;
+    DynamicMessageRef { object, table }
+        .encode_vec::<ANY_STACK_DEPTH>()
+        .map_err(|_| Error::custom(format!("Any of type '{}' nests too deep", type_url)))
+}
+
+/// A JSON value buffered while reading an `Any`, deserialized again once the
+/// type of the packed message is known.
+enum JsonNode {
+    Null,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(std::string::String),
+    Seq(Vec<JsonNode>),
+    Map(Vec<(std::string::String, JsonNode)>),
+}
+
+impl<'de> serde::Deserialize<'de> for JsonNode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(JsonNodeVisitor)
+    }
+}
+
+struct JsonNodeVisitor;
+
+impl<'de> serde::de::Visitor<'de> for JsonNodeVisitor {
+    type Value = JsonNode;
+
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        Ok(JsonNode::Null)
+    }
+
+    fn visit_none<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        Ok(JsonNode::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        serde::Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: serde::de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(JsonNode::Bool(v))
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(JsonNode::I64(v))
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(JsonNode::U64(v))
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(JsonNode::F64(v))
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(JsonNode::String(v.into()))
+    }
+
+    fn visit_string<E: serde::de::Error>(self, v: std::string::String) -> Result<Self::Value, E> {
+        Ok(JsonNode::String(v))
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(JsonNode::Seq(values))
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut members = Vec::new();
+        while let Some(member) = map.next_entry()? {
+            members.push(member);
+        }
+        Ok(JsonNode::Map(members))
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for JsonNode {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: serde::de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        use serde::de::value::{MapDeserializer, SeqDeserializer};
+        match self {
+            JsonNode::Null => visitor.visit_unit(),
+            JsonNode::Bool(v) => visitor.visit_bool(v),
+            JsonNode::I64(v) => visitor.visit_i64(v),
+            JsonNode::U64(v) => visitor.visit_u64(v),
+            JsonNode::F64(v) => visitor.visit_f64(v),
+            JsonNode::String(v) => visitor.visit_string(v),
+            JsonNode::Seq(values) => {
+                let mut seq = SeqDeserializer::new(values.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            JsonNode::Map(members) => {
+                let mut map = MapDeserializer::new(members.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_option<V: serde::de::Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            JsonNode::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct
+        map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> serde::de::IntoDeserializer<'de, serde::de::value::Error> for JsonNode {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
 struct Optional<T>(T);
 
-/// DeserializeSeed for enum values - accepts both integers and string names
+/// DeserializeSeed for enum values - accepts both integers and string names.
+/// Yields `None` for an unknown name when `ignore_unknown` is set.
 struct EnumSeed<'a> {
-    descriptor: &'a crate::google::protobuf::DescriptorProto::ProtoType,
+    table: &'a Table,
     type_name: &'a str,
+    ignore_unknown: bool,
 }
 
 impl<'de, 'a> serde::de::DeserializeSeed<'de> for EnumSeed<'a> {
-    type Value = i32;
+    type Value = Option<i32>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
@@ -773,34 +1401,45 @@ impl<'de, 'a> serde::de::DeserializeSeed<'de> for EnumSeed<'a> {
 }
 
 impl<'de, 'a> serde::de::Visitor<'de> for EnumSeed<'a> {
-    type Value = i32;
+    type Value = Option<i32>;
 
     fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str("enum value (integer or string name)")
     }
 
     fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
-        Ok(v as i32)
+        Ok(Some(v as i32))
     }
 
     fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Self::Value, E> {
-        Ok(v as i32)
+        Ok(Some(v as i32))
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        if is_null_value_enum(self.type_name) {
+            Ok(Some(0))
+        } else {
+            Err(E::invalid_type(serde::de::Unexpected::Unit, &self))
+        }
     }
 
     fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
-        lookup_enum_value(self.descriptor, self.type_name, v).ok_or_else(|| {
-            E::custom(format!(
+        match lookup_enum_value(self.table, self.type_name, v) {
+            Some(v) => Ok(Some(v)),
+            None if self.ignore_unknown => Ok(None),
+            None => Err(E::custom(format!(
                 "unknown enum value '{}' for type '{}'",
                 v, self.type_name
-            ))
-        })
+            ))),
+        }
     }
 }
 
 /// DeserializeSeed for repeated enum values
 struct EnumArraySeed<'a> {
-    descriptor: &'a crate::google::protobuf::DescriptorProto::ProtoType,
+    table: &'a Table,
     type_name: &'a str,
+    ignore_unknown: bool,
 }
 
 impl<'de, 'a> serde::de::DeserializeSeed<'de> for EnumArraySeed<'a> {
@@ -824,10 +1463,11 @@ impl<'de, 'a> serde::de::Visitor<'de> for EnumArraySeed<'a> {
     fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
         let mut values = Vec::new();
         while let Some(v) = seq.next_element_seed(EnumSeed {
-            descriptor: self.descriptor,
+            table: self.table,
             type_name: self.type_name,
+            ignore_unknown: self.ignore_unknown,
         })? {
-            values.push(v);
+            values.extend(v);
         }
         Ok(values)
     }
@@ -877,16 +1517,39 @@ pub fn serde_deserialize_struct<'arena, 'alloc, 'b, 'de, 'pool, D>(
     arena: &'arena mut crate::arena::Arena<'alloc>,
     deserializer: D,
 ) -> Result<(), D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    serde_deserialize_struct_with_options(msg, arena, deserializer, JsonParseOptions::default())
+}
+
+/// Like [`serde_deserialize_struct`], with explicit [`JsonParseOptions`].
+pub fn serde_deserialize_struct_with_options<'arena, 'alloc, 'b, 'de, 'pool, D>(
+    msg: DynamicMessage<'pool, 'b>,
+    arena: &'arena mut crate::arena::Arena<'alloc>,
+    deserializer: D,
+    options: JsonParseOptions,
+) -> Result<(), D::Error>
 where
     D: serde::Deserializer<'de>,
 {
     let descriptor = msg.as_ref().descriptor();
-    let visitor = ProtobufVisitor { msg, arena };
+    let visitor = ProtobufVisitor {
+        msg,
+        arena,
+        options,
+    };
 
     // For well-known types, use appropriate deserialize method
     match detect_well_known_type(descriptor) {
-        WellKnownType::BytesValue => return deserializer.deserialize_bytes(visitor),
-        WellKnownType::None => {}
+        // Human-readable formats carry base64 text, see BytesBuf
+        WellKnownType::BytesValue if !deserializer.is_human_readable() => {
+            return deserializer.deserialize_bytes(visitor);
+        }
+        WellKnownType::Any if deserializer.is_human_readable() => {
+            return deserializer.deserialize_map(AnyVisitor(visitor));
+        }
+        WellKnownType::Any | WellKnownType::None => {}
         _ => return deserializer.deserialize_any(visitor),
     }
 
@@ -898,7 +1561,10 @@ where
     deserializer.deserialize_struct(descriptor_static.name(), field_names_static, visitor)
 }
 
-struct StructKeyVisitor<'a>(&'a std::collections::HashMap<&'static str, usize>);
+struct StructKeyVisitor<'a> {
+    fields: &'a std::collections::HashMap<&'static str, usize>,
+    ignore_unknown_fields: bool,
+}
 
 impl<'de> serde::de::DeserializeSeed<'de> for StructKeyVisitor<'_> {
     type Value = Option<usize>;
@@ -922,8 +1588,12 @@ impl<'de> serde::de::Visitor<'de> for StructKeyVisitor<'_> {
     where
         E: serde::de::Error,
     {
-        // Return None for unknown fields - they'll be skipped
-        Ok(self.0.get(v).copied())
+        // Return None for unknown fields that are to be skipped
+        match self.fields.get(v) {
+            Some(&idx) => Ok(Some(idx)),
+            None if self.ignore_unknown_fields => Ok(None),
+            None => Err(E::unknown_field(v, &[])),
+        }
     }
 }
 
@@ -931,6 +1601,7 @@ struct ProtobufArrayfVisitor<'arena, 'alloc, 'b> {
     rf: &'b mut crate::containers::RepeatedField<crate::base::Message>,
     table: &'b Table,
     arena: &'arena mut crate::arena::Arena<'alloc>,
+    options: JsonParseOptions,
 }
 
 impl<'de, 'arena, 'alloc, 'b> serde::de::DeserializeSeed<'de>
@@ -952,14 +1623,14 @@ impl<'de, 'arena, 'alloc, 'b> serde::de::Visitor<'de>
     type Value = ();
 
     fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
-        formatter.write_str(&format!("an array of {}", self.table.descriptor.name()))
+        formatter.write_str(&format!("an array of {}", self.table.descriptor().name()))
     }
 
     fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
     where
         A: serde::de::SeqAccess<'de>,
     {
-        let ProtobufArrayfVisitor { rf, table, arena } = self;
+        let ProtobufArrayfVisitor { rf, table, arena, options } = self;
         loop {
             let msg_obj = Object::create(table.size as u32, arena).map_err(|e| A::Error::custom(e))?;
 
@@ -969,6 +1640,7 @@ impl<'de, 'arena, 'alloc, 'b> serde::de::Visitor<'de>
                     table,
                 },
                 arena,
+                options,
             };
 
             match seq.next_element_seed(seed)? {
@@ -987,6 +1659,7 @@ struct ProtobufMapVisitor<'arena, 'alloc, 'b> {
     rf: &'b mut crate::containers::RepeatedField<crate::base::Message>,
     table: &'b Table,
     arena: &'arena mut crate::arena::Arena<'alloc>,
+    options: JsonParseOptions,
 }
 
 impl<'de, 'arena, 'alloc, 'b> serde::de::DeserializeSeed<'de>
@@ -1008,7 +1681,7 @@ impl<'de, 'arena, 'alloc, 'b> serde::de::Visitor<'de> for ProtobufMapVisitor<'ar
     fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.write_str(&format!(
             "a map with {} entries",
-            self.table.descriptor.name()
+            self.table.descriptor().name()
         ))
     }
 
@@ -1016,15 +1689,15 @@ impl<'de, 'arena, 'alloc, 'b> serde::de::Visitor<'de> for ProtobufMapVisitor<'ar
     where
         A: serde::de::MapAccess<'de>,
     {
-        let ProtobufMapVisitor { rf, table, arena } = self;
+        let ProtobufMapVisitor { rf, table, arena, options } = self;
 
-        let key_field = &table.descriptor.field()[0];
-        let value_field = &table.descriptor.field()[1];
+        let key_field = &table.descriptor().field()[0];
+        let value_field = &table.descriptor().field()[1];
         let key_entry = table
-            .entry(1)
+            .field_entry(1)
             .ok_or_else(|| serde::de::Error::custom("Map entry missing key field in table"))?;
         let value_entry = table
-            .entry(2)
+            .field_entry(2)
             .ok_or_else(|| serde::de::Error::custom("Map entry missing value field in table"))?;
 
         while let Some(key_str) = map.next_key::<std::string::String>()? {
@@ -1078,14 +1751,18 @@ impl<'de, 'arena, 'alloc, 'b> serde::de::Visitor<'de> for ProtobufMapVisitor<'ar
                 }
                 Type::TYPE_ENUM => {
                     let seed = EnumSeed {
-                        descriptor: table.descriptor,
+                        table,
                         type_name: value_field.type_name(),
+                        ignore_unknown: options.ignore_unknown_enum_values,
+                    };
+                    let Some(v) = map.next_value_seed(seed)? else {
+                        // Drop the entry of an ignored unknown value
+                        continue;
                     };
-                    let v: i32 = map.next_value_seed(seed)?;
                     entry_obj.set::<i32>(value_entry.offset(), value_entry.has_bit_idx(), v);
                 }
                 Type::TYPE_INT64 | Type::TYPE_SINT64 | Type::TYPE_SFIXED64 => {
-                    let v: i64 = map.next_value()?;
+                    let Int64(v) = map.next_value::<Int64<i64>>()?;
                     entry_obj.set::<i64>(value_entry.offset(), value_entry.has_bit_idx(), v);
                 }
                 Type::TYPE_UINT32 | Type::TYPE_FIXED32 => {
@@ -1093,7 +1770,7 @@ impl<'de, 'arena, 'alloc, 'b> serde::de::Visitor<'de> for ProtobufMapVisitor<'ar
                     entry_obj.set::<u32>(value_entry.offset(), value_entry.has_bit_idx(), v);
                 }
                 Type::TYPE_UINT64 | Type::TYPE_FIXED64 => {
-                    let v: u64 = map.next_value()?;
+                    let Int64(v) = map.next_value::<Int64<u64>>()?;
                     entry_obj.set::<u64>(value_entry.offset(), value_entry.has_bit_idx(), v);
                 }
                 Type::TYPE_FLOAT => {
@@ -1131,6 +1808,7 @@ impl<'de, 'arena, 'alloc, 'b> serde::de::Visitor<'de> for ProtobufMapVisitor<'ar
                             table: child_table,
                         },
                         arena,
+                        options,
                     };
                     map.next_value_seed(seed)?;
                     *entry_obj.ref_mut::<crate::base::Message>(offset) =
@@ -1152,7 +1830,7 @@ where
 {
     let entry = msg
         .table
-        .entry(1)
+        .field_entry(1)
         .ok_or_else(|| serde::de::Error::custom("Wrapper missing 'value' field in table"))?;
 
     while let Some(key) = map.next_key::<std::string::String>()? {
@@ -1178,7 +1856,7 @@ where
 {
     let entry = msg
         .table
-        .entry(1)
+        .field_entry(1)
         .ok_or_else(|| serde::de::Error::custom("StringValue missing 'value' field in table"))?;
 
     while let Some(key) = map.next_key::<std::string::String>()? {
@@ -1205,7 +1883,7 @@ where
 {
     let entry = msg
         .table
-        .entry(1)
+        .field_entry(1)
         .ok_or_else(|| serde::de::Error::custom("BytesValue missing 'value' field in table"))?;
 
     while let Some(key) = map.next_key::<std::string::String>()? {
@@ -1228,11 +1906,11 @@ where
 {
     let seconds_entry = msg
         .table
-        .entry(1)
+        .field_entry(1)
         .ok_or_else(|| serde::de::Error::custom("Timestamp missing 'seconds' field in table"))?;
     let nanos_entry = msg
         .table
-        .entry(2)
+        .field_entry(2)
         .ok_or_else(|| serde::de::Error::custom("Timestamp missing 'nanos' field in table"))?;
 
     while let Some(key) = map.next_key::<std::string::String>()? {
@@ -1267,11 +1945,11 @@ where
 {
     let seconds_entry = msg
         .table
-        .entry(1)
+        .field_entry(1)
         .ok_or_else(|| serde::de::Error::custom("Duration missing 'seconds' field in table"))?;
     let nanos_entry = msg
         .table
-        .entry(2)
+        .field_entry(2)
         .ok_or_else(|| serde::de::Error::custom("Duration missing 'nanos' field in table"))?;
 
     while let Some(key) = map.next_key::<std::string::String>()? {
@@ -1306,7 +1984,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     type Value = ();
 
     fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
-        formatter.write_str(self.msg.table.descriptor.name())
+        formatter.write_str(self.msg.table.descriptor().name())
     }
 
     // Handle unwrapped wrapper types from JSON
@@ -1314,12 +1992,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         E: serde::de::Error,
     {
-        match detect_well_known_type(self.msg.table.descriptor) {
+        match detect_well_known_type(self.msg.table.descriptor()) {
             WellKnownType::BoolValue => {
                 let entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("BoolValue missing field 1"))?;
                 self.msg
                     .object
@@ -1331,7 +2009,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                 let entry = self
                     .msg
                     .table
-                    .entry(4)
+                    .field_entry(4)
                     .ok_or_else(|| E::custom("Value missing field 4"))?;
                 self.msg
                     .object
@@ -1346,13 +2024,13 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         E: serde::de::Error,
     {
-        match detect_well_known_type(self.msg.table.descriptor) {
+        match detect_well_known_type(self.msg.table.descriptor()) {
             WellKnownType::Value => {
                 // Value.null_value is field 1 (enum NullValue = 0)
                 let entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("Value missing field 1"))?;
                 self.msg
                     .object
@@ -1367,12 +2045,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         E: serde::de::Error,
     {
-        match detect_well_known_type(self.msg.table.descriptor) {
+        match detect_well_known_type(self.msg.table.descriptor()) {
             WellKnownType::Int32Value => {
                 let entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("Int32Value missing field 1"))?;
                 self.msg
                     .object
@@ -1390,12 +2068,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         E: serde::de::Error,
     {
-        match detect_well_known_type(self.msg.table.descriptor) {
+        match detect_well_known_type(self.msg.table.descriptor()) {
             WellKnownType::Int64Value => {
                 let entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("Int64Value missing field 1"))?;
                 self.msg
                     .object
@@ -1415,12 +2093,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         E: serde::de::Error,
     {
-        match detect_well_known_type(self.msg.table.descriptor) {
+        match detect_well_known_type(self.msg.table.descriptor()) {
             WellKnownType::UInt32Value => {
                 let entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("UInt32Value missing field 1"))?;
                 self.msg
                     .object
@@ -1438,12 +2116,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         E: serde::de::Error,
     {
-        match detect_well_known_type(self.msg.table.descriptor) {
+        match detect_well_known_type(self.msg.table.descriptor()) {
             WellKnownType::UInt64Value => {
                 let entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("UInt64Value missing field 1"))?;
                 self.msg
                     .object
@@ -1463,12 +2141,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         E: serde::de::Error,
     {
-        match detect_well_known_type(self.msg.table.descriptor) {
+        match detect_well_known_type(self.msg.table.descriptor()) {
             WellKnownType::FloatValue => {
                 let entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("FloatValue missing field 1"))?;
                 self.msg
                     .object
@@ -1486,12 +2164,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         E: serde::de::Error,
     {
-        match detect_well_known_type(self.msg.table.descriptor) {
+        match detect_well_known_type(self.msg.table.descriptor()) {
             WellKnownType::DoubleValue => {
                 let entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("DoubleValue missing field 1"))?;
                 self.msg
                     .object
@@ -1504,7 +2182,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                 let entry = self
                     .msg
                     .table
-                    .entry(2)
+                    .field_entry(2)
                     .ok_or_else(|| E::custom("Value missing field 2"))?;
                 self.msg
                     .object
@@ -1519,12 +2197,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         E: serde::de::Error,
     {
-        match detect_well_known_type(self.msg.table.descriptor) {
+        match detect_well_known_type(self.msg.table.descriptor()) {
             WellKnownType::BytesValue => {
                 let entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("BytesValue missing field 1"))?;
                 let b = crate::containers::Bytes::from_slice(v, self.arena).map_err(|e| E::custom(e))?;
                 self.msg.object.set::<crate::containers::Bytes>(
@@ -1542,12 +2220,17 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         E: serde::de::Error,
     {
-        match detect_well_known_type(self.msg.table.descriptor) {
+        match detect_well_known_type(self.msg.table.descriptor()) {
+            WellKnownType::BytesValue => {
+                let bytes = crate::proto_json::decode_base64(v)
+                    .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Str(v), &self))?;
+                self.visit_bytes(&bytes)
+            }
             WellKnownType::StringValue => {
                 let entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("StringValue missing field 1"))?;
                 let s = crate::containers::String::from_str(v, self.arena).map_err(|e| E::custom(e))?;
                 self.msg.object.set::<crate::containers::String>(
@@ -1562,12 +2245,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                 let seconds_entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("Timestamp missing field 1"))?;
                 let nanos_entry = self
                     .msg
                     .table
-                    .entry(2)
+                    .field_entry(2)
                     .ok_or_else(|| E::custom("Timestamp missing field 2"))?;
                 self.msg.object.set::<i64>(
                     seconds_entry.offset(),
@@ -1584,12 +2267,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                 let seconds_entry = self
                     .msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| E::custom("Duration missing field 1"))?;
                 let nanos_entry = self
                     .msg
                     .table
-                    .entry(2)
+                    .field_entry(2)
                     .ok_or_else(|| E::custom("Duration missing field 2"))?;
                 self.msg.object.set::<i64>(
                     seconds_entry.offset(),
@@ -1614,7 +2297,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                 let entry = self
                     .msg
                     .table
-                    .entry(3)
+                    .field_entry(3)
                     .ok_or_else(|| E::custom("Value missing field 3"))?;
                 let s = crate::containers::String::from_str(v, self.arena).map_err(E::custom)?;
                 self.msg
@@ -1630,10 +2313,10 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         A: serde::de::MapAccess<'de>,
     {
-        let ProtobufVisitor { msg, arena } = self;
+        let ProtobufVisitor { msg, arena, options } = self;
 
         // Check if this is a well-known type
-        match detect_well_known_type(msg.table.descriptor) {
+        match detect_well_known_type(msg.table.descriptor()) {
             WellKnownType::BoolValue => return deserialize_wrapper::<A, bool>(msg, map),
             WellKnownType::Int32Value => return deserialize_wrapper::<A, i32>(msg, map),
             WellKnownType::Int64Value => return deserialize_wrapper::<A, i64>(msg, map),
@@ -1651,7 +2334,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                 // Struct has map<string, Value> fields (field 1)
                 let entry = msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| serde::de::Error::custom("Struct missing field 1"))?;
                 let (offset, child_table) = msg.table.aux_entry_decode(entry);
                 let rf = msg
@@ -1661,7 +2344,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                 while let Some(key) = map.next_key::<std::string::String>()? {
                     let entry_obj = Object::create(child_table.size as u32, arena).map_err(|e| A::Error::custom(e))?;
                     // Set key (field 1)
-                    let key_entry = child_table.entry(1).ok_or_else(|| {
+                    let key_entry = child_table.field_entry(1).ok_or_else(|| {
                         serde::de::Error::custom("Struct entry missing key field")
                     })?;
                     let s = crate::containers::String::from_str(&key, arena).map_err(|e| A::Error::custom(e))?;
@@ -1671,7 +2354,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                         s,
                     );
                     // Set value (field 2) - this is a Value message
-                    let value_entry = child_table.entry(2).ok_or_else(|| {
+                    let value_entry = child_table.field_entry(2).ok_or_else(|| {
                         serde::de::Error::custom("Struct entry missing value field")
                     })?;
                     let (offset, child_table) = child_table.aux_entry_decode(value_entry);
@@ -1682,6 +2365,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                             table: child_table,
                         },
                         arena,
+                        options,
                     };
                     map.next_value_seed(seed)?;
                     *entry_obj.ref_mut::<crate::base::Message>(offset) =
@@ -1694,7 +2378,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                 // Value.struct_value is field 5
                 let entry = msg
                     .table
-                    .entry(5)
+                    .field_entry(5)
                     .ok_or_else(|| serde::de::Error::custom("Value missing field 5"))?;
                 let (offset, child_table) = msg.table.aux_entry_decode(entry);
                 let child_obj = Object::create(child_table.size as u32, arena).map_err(|e| A::Error::custom(e))?;
@@ -1704,6 +2388,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                         table: child_table,
                     },
                     arena,
+                    options,
                 };
                 visitor.visit_map(map)?;
                 msg.object.set_oneof(
@@ -1714,35 +2399,41 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                 );
                 return Ok(());
             }
-            WellKnownType::ListValue | WellKnownType::None => {
+            WellKnownType::ListValue | WellKnownType::Any | WellKnownType::None => {
                 // Continue with regular deserialization
             }
         }
 
         let mut field_map = std::collections::HashMap::new();
-        for (field_index, field) in msg.table.descriptor.field().iter().enumerate() {
+        for (field_index, field) in msg.table.descriptor().field().iter().enumerate() {
             // Accept both json_name and original proto field name
             field_map.insert(field.json_name(), field_index);
             field_map.insert(field.name(), field_index);
         }
         let mut seen = std::collections::HashSet::new();
-        while let Some(idx_opt) = map.next_key_seed(StructKeyVisitor(&field_map))? {
+        let mut oneofs_set = std::collections::HashSet::new();
+        while let Some(idx_opt) = map.next_key_seed(StructKeyVisitor {
+            fields: &field_map,
+            ignore_unknown_fields: options.ignore_unknown_fields,
+        })? {
             // Skip unknown fields
             let Some(idx) = idx_opt else {
                 map.next_value::<serde::de::IgnoredAny>()?;
                 continue;
             };
-            let field = &msg.table.descriptor.field()[idx];
-            let entry = msg.table.entry(field.number() as u32).unwrap(); // Safe: field exists in table
-            // Reject duplicate fields (oneofs can have null which clears, so skip oneof check for now)
+            let field = &msg.table.descriptor().field()[idx];
+            let entry = msg.table.field_entry(field.number() as u32).unwrap(); // Safe: field exists in table
+            // Reject duplicate fields. Oneof members are checked once their value
+            // turns out not to be null, since a null member doesn't select the oneof.
             let has_bit_idx = entry.has_bit_idx();
-            if has_bit_idx & 0x80 == 0 && !seen.insert(idx) {
+            if options.reject_duplicate_fields && has_bit_idx & 0x80 == 0 && !seen.insert(idx) {
                 return Err(serde::de::Error::custom("duplicate field"));
             }
             match field.label().unwrap() {
                 Label::LABEL_REPEATED => match field.r#type().unwrap() {
                     Type::TYPE_BOOL => {
                         let Some(slice) = map.next_value::<Option<Vec<bool>>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         for v in slice {
@@ -1750,15 +2441,17 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                         }
                     }
                     Type::TYPE_FIXED64 | Type::TYPE_UINT64 => {
-                        let Some(slice) = map.next_value::<Option<Vec<u64>>>()? else {
+                        let Some(slice) = map.next_value::<Option<Vec<Int64<u64>>>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
-                        for v in slice {
+                        for Int64(v) in slice {
                             msg.object.add::<u64>(entry.offset(), v, arena).map_err(|e| A::Error::custom(e))?;
                         }
                     }
                     Type::TYPE_FIXED32 | Type::TYPE_UINT32 => {
                         let Some(slice) = map.next_value::<Option<Vec<u32>>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         for v in slice {
@@ -1766,15 +2459,17 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                         }
                     }
                     Type::TYPE_SFIXED64 | Type::TYPE_INT64 | Type::TYPE_SINT64 => {
-                        let Some(slice) = map.next_value::<Option<Vec<i64>>>()? else {
+                        let Some(slice) = map.next_value::<Option<Vec<Int64<i64>>>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
-                        for v in slice {
+                        for Int64(v) in slice {
                             msg.object.add::<i64>(entry.offset(), v, arena).map_err(|e| A::Error::custom(e))?;
                         }
                     }
                     Type::TYPE_SFIXED32 | Type::TYPE_INT32 | Type::TYPE_SINT32 => {
                         let Some(slice) = map.next_value::<Option<Vec<i32>>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         for v in slice {
@@ -1783,10 +2478,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                     }
                     Type::TYPE_ENUM => {
                         let seed = EnumArraySeed {
-                            descriptor: msg.table.descriptor,
+                            table: msg.table,
                             type_name: field.type_name(),
+                            ignore_unknown: options.ignore_unknown_enum_values,
                         };
                         let Some(slice) = map.next_value_seed(Optional(seed))? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         for v in slice {
@@ -1795,6 +2492,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                     }
                     Type::TYPE_FLOAT => {
                         let Some(slice) = map.next_value::<Option<Vec<f32>>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         for v in slice {
@@ -1803,6 +2501,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                     }
                     Type::TYPE_DOUBLE => {
                         let Some(slice) = map.next_value::<Option<Vec<f64>>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         for v in slice {
@@ -1811,6 +2510,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                     }
                     Type::TYPE_STRING => {
                         let Some(slice) = map.next_value::<Option<Vec<String>>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         for v in slice {
@@ -1821,6 +2521,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                     }
                     Type::TYPE_BYTES => {
                         let Some(slice) = map.next_value::<Option<Vec<BytesBuf>>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         for v in slice {
@@ -1838,7 +2539,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                             );
 
                         if child_table
-                            .descriptor
+                            .descriptor()
                             .options()
                             .map(|o| o.map_entry())
                             .unwrap_or(false)
@@ -1847,73 +2548,98 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                                 rf,
                                 table: child_table,
                                 arena,
+                                options,
                             });
-                            map.next_value_seed(seed)?;
+                            if map.next_value_seed(seed)?.is_none() {
+                                clear_field(msg.object, msg.table, entry, field);
+                            }
                         } else {
                             let seed = Optional(ProtobufArrayfVisitor {
                                 rf,
                                 table: child_table,
                                 arena,
+                                options,
                             });
-                            map.next_value_seed(seed)?;
+                            if map.next_value_seed(seed)?.is_none() {
+                                clear_field(msg.object, msg.table, entry, field);
+                            }
                         }
                     }
                 },
                 _ => match field.r#type().unwrap() {
                     Type::TYPE_BOOL => {
                         let Some(v) = map.next_value::<Option<bool>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         set_field(msg.object, entry, field.number(), v);
                     }
                     Type::TYPE_FIXED64 | Type::TYPE_UINT64 => {
-                        let Some(v) = map.next_value::<Option<u64>>()? else {
+                        let Some(Int64(v)) = map.next_value::<Option<Int64<u64>>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         set_field(msg.object, entry, field.number(), v);
                     }
                     Type::TYPE_FIXED32 | Type::TYPE_UINT32 => {
                         let Some(v) = map.next_value::<Option<u32>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         set_field(msg.object, entry, field.number(), v);
                     }
                     Type::TYPE_SFIXED64 | Type::TYPE_INT64 | Type::TYPE_SINT64 => {
-                        let Some(v) = map.next_value::<Option<i64>>()? else {
+                        let Some(Int64(v)) = map.next_value::<Option<Int64<i64>>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         set_field(msg.object, entry, field.number(), v);
                     }
                     Type::TYPE_SFIXED32 | Type::TYPE_INT32 | Type::TYPE_SINT32 => {
                         let Some(v) = map.next_value::<Option<i32>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         set_field(msg.object, entry, field.number(), v);
                     }
                     Type::TYPE_ENUM => {
                         let seed = EnumSeed {
-                            descriptor: msg.table.descriptor,
+                            table: msg.table,
                             type_name: field.type_name(),
+                            ignore_unknown: options.ignore_unknown_enum_values,
                         };
-                        let Some(v) = map.next_value_seed(Optional(seed))? else {
+                        let v = if is_null_value_enum(field.type_name()) {
+                            map.next_value_seed(seed)?
+                        } else {
+                            let Some(v) = map.next_value_seed(Optional(seed))? else {
+                                clear_field(msg.object, msg.table, entry, field);
+                                continue;
+                            };
+                            v
+                        };
+                        // An ignored unknown value leaves the field as it was
+                        let Some(v) = v else {
                             continue;
                         };
                         set_field(msg.object, entry, field.number(), v);
                     }
                     Type::TYPE_FLOAT => {
                         let Some(v) = map.next_value::<Option<f32>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         set_field(msg.object, entry, field.number(), v);
                     }
                     Type::TYPE_DOUBLE => {
                         let Some(v) = map.next_value::<Option<f64>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         set_field(msg.object, entry, field.number(), v);
                     }
                     Type::TYPE_STRING => {
                         let Some(v) = map.next_value::<Option<String>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         let s = crate::containers::String::from_str(&v, arena).map_err(|e| A::Error::custom(e))?;
@@ -1921,6 +2647,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                     }
                     Type::TYPE_BYTES => {
                         let Some(v) = map.next_value::<Option<BytesBuf>>()? else {
+                            clear_field(msg.object, msg.table, entry, field);
                             continue;
                         };
                         let b = crate::containers::Bytes::from_slice(&v.0, arena).map_err(|e| A::Error::custom(e))?;
@@ -1930,13 +2657,14 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                         let (offset, child_table) = msg.table.aux_entry_decode(entry);
                         let child_obj = Object::create(child_table.size as u32, arena).map_err(|e| A::Error::custom(e))?;
                         // Value type: null is a valid value, don't wrap with Optional
-                        if detect_well_known_type(child_table.descriptor) == WellKnownType::Value {
+                        if detect_well_known_type(child_table.descriptor()) == WellKnownType::Value {
                             let seed = ProtobufVisitor {
                                 msg: DynamicMessage {
                                     object: child_obj,
                                     table: child_table,
                                 },
                                 arena,
+                                options,
                             };
                             map.next_value_seed(seed)?;
                         } else {
@@ -1946,8 +2674,10 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                                     table: child_table,
                                 },
                                 arena,
+                                options,
                             });
                             if map.next_value_seed(seed)?.is_none() {
+                                clear_field(msg.object, msg.table, entry, field);
                                 continue;
                             };
                         }
@@ -1963,6 +2693,12 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                     }
                 },
             }
+            if options.reject_duplicate_fields
+                && has_bit_idx & 0x80 != 0
+                && !oneofs_set.insert(field.oneof_index())
+            {
+                return Err(serde::de::Error::custom("multiple fields of a oneof set"));
+            }
         }
         Ok(())
     }
@@ -1971,14 +2707,14 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
     where
         A: serde::de::SeqAccess<'de>,
     {
-        let ProtobufVisitor { msg, arena } = self;
+        let ProtobufVisitor { msg, arena, options } = self;
 
-        match detect_well_known_type(msg.table.descriptor) {
+        match detect_well_known_type(msg.table.descriptor()) {
             WellKnownType::ListValue => {
                 // ListValue has repeated Value values (field 1)
                 let entry = msg
                     .table
-                    .entry(1)
+                    .field_entry(1)
                     .ok_or_else(|| serde::de::Error::custom("ListValue missing field 1"))?;
                 let (offset, child_table) = msg.table.aux_entry_decode(entry);
                 let rf = msg
@@ -1992,6 +2728,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                             table: child_table,
                         },
                         arena,
+                        options,
                     };
                     if seq.next_element_seed(seed)?.is_some() {
                         rf.push(crate::base::Message(value_obj as *mut Object), arena).map_err(|e| A::Error::custom(e))?;
@@ -2006,7 +2743,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                 // Value.list_value is field 6
                 let entry = msg
                     .table
-                    .entry(6)
+                    .field_entry(6)
                     .ok_or_else(|| serde::de::Error::custom("Value missing field 6"))?;
                 let (offset, child_table) = msg.table.aux_entry_decode(entry);
                 let list_obj = Object::create(child_table.size as u32, arena).map_err(|e| A::Error::custom(e))?;
@@ -2016,6 +2753,7 @@ impl<'de, 'arena, 'alloc, 'b, 'pool> serde::de::Visitor<'de>
                         table: child_table,
                     },
                     arena,
+                    options,
                 };
                 visitor.visit_seq(seq)?;
                 msg.object.set_oneof(
@@ -2058,6 +2796,13 @@ impl<'de> serde::de::Deserialize<'de> for BytesBuf {
                 Ok(BytesBuf(v))
             }
 
+            // Human-readable serializers write bytes as base64 text
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                crate::proto_json::decode_base64(v)
+                    .map(BytesBuf)
+                    .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Str(v), &self))
+            }
+
             // serde_json's deserialize_bytes delegates to deserialize_seq for JSON arrays,
             // which calls visit_seq instead of visit_bytes. This handles that case.
             fn visit_seq<A: serde::de::SeqAccess<'de>>(
@@ -2072,6 +2817,11 @@ impl<'de> serde::de::Deserialize<'de> for BytesBuf {
             }
         }
 
-        deserializer.deserialize_bytes(BytesVisitor)
+        if deserializer.is_human_readable() {
+            // serde_json's deserialize_bytes hands over string contents verbatim
+            deserializer.deserialize_any(BytesVisitor)
+        } else {
+            deserializer.deserialize_bytes(BytesVisitor)
+        }
     }
 }
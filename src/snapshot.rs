@@ -0,0 +1,265 @@
+//! Self-contained message snapshots.
+//!
+//! A snapshot stores a message together with its type name and the
+//! descriptors of its schema, so a payload captured in production can be
+//! inspected later without hunting for the matching `.proto` files. [`read`]
+//! rebuilds the schema in a fresh [`DescriptorPool`] and decodes the message
+//! dynamically.
+//!
+//! ```
+//! use protocrap::google::protobuf::FileDescriptorProto;
+//! use protocrap::{arena::Arena, snapshot};
+//! use allocator_api2::alloc::Global;
+//!
+//! let file = FileDescriptorProto::ProtoType::file_descriptor();
+//! let mut buffer = Vec::new();
+//! snapshot::write(&mut buffer, file, "google.protobuf.FileDescriptorProto", &[file]).unwrap();
+//!
+//! let snapshot = snapshot::read(&mut &buffer[..]).unwrap();
+//! let mut arena = Arena::new(&Global);
+//! let msg = snapshot.message(&mut arena).unwrap();
+//! assert_eq!(msg.as_ref().get_str("name"), Some("google/protobuf/descriptor.proto"));
+//! ```
+//!
+//! # Format
+//!
+//! The eight bytes `PCSNAP01` followed by the protobuf encoding of
+//!
+//! ```proto
+//! message Snapshot {
+//!   google.protobuf.FileDescriptorSet descriptors = 1;
+//!   string type_name = 2;
+//!   bytes payload = 3;
+//! }
+//! ```
+//!
+//! so the contents can also be examined with `protoc --decode_raw`.
+
+use std::io::{Read, Write};
+
+use crate::arena::Arena;
+use crate::descriptor_pool::DescriptorPool;
+use crate::google::protobuf::DescriptorProto::ProtoType as DescriptorProto;
+use crate::google::protobuf::FileDescriptorProto::ProtoType as FileDescriptorProto;
+use crate::reflection::DynamicMessage;
+use crate::wire::{Reader, WireType, Writer, make_tag, varint_len};
+use crate::{Error, ProtobufMut, ProtobufRef};
+
+/// Stack depth used to encode and decode messages and descriptors.
+const STACK_DEPTH: usize = 64;
+
+/// Leading bytes of every snapshot, the last two being the format version.
+const MAGIC: &[u8; 8] = b"PCSNAP01";
+
+/// Write a snapshot of `msg`, a `type_name` message described by `files`.
+///
+/// `files` must contain the file declaring `type_name` and every file it
+/// imports, directly or indirectly, in any order. For generated types these
+/// are the `file_descriptor()` of the message type and of the types it uses;
+/// for pool messages, [`DescriptorPool::files`].
+///
+/// Fails with [`Error::MessageNotFound`] if `files` doesn't declare
+/// `type_name` with the same name as `msg`'s descriptor.
+pub fn write<'pool>(
+    writer: &mut impl Write,
+    msg: &impl ProtobufRef<'pool>,
+    type_name: &str,
+    files: &[&FileDescriptorProto],
+) -> Result<(), Error<std::io::Error>> {
+    let type_name = type_name.trim_start_matches('.');
+    let declared = files.iter().any(|file| {
+        find_message(file, type_name).is_some_and(|d| d.name() == msg.descriptor().name())
+    });
+    if !declared {
+        return Err(Error::MessageNotFound);
+    }
+
+    let mut descriptors = Vec::new();
+    for file in files {
+        push_field(&mut descriptors, 1, &encode(*file)?)?;
+    }
+    let mut snapshot = MAGIC.to_vec();
+    push_field(&mut snapshot, 1, &descriptors)?;
+    push_field(&mut snapshot, 2, type_name.as_bytes())?;
+    push_field(&mut snapshot, 3, &encode(msg)?)?;
+    writer.write_all(&snapshot)?;
+    Ok(())
+}
+
+/// Read a snapshot written by [`write`], loading its descriptors into a
+/// fresh pool.
+pub fn read(reader: &mut impl Read) -> Result<Snapshot, Error<std::io::Error>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let rest = bytes
+        .strip_prefix(MAGIC.as_slice())
+        .ok_or(Error::InvalidProtobufData)?;
+
+    let mut descriptors: &[u8] = &[];
+    let mut type_name: &[u8] = &[];
+    let mut payload: &[u8] = &[];
+    let mut reader = Reader::new(rest);
+    while !reader.is_empty() {
+        let (field_number, wire_type) = reader.read_tag().map_err(Error::with_io)?;
+        if wire_type != WireType::LengthDelimited {
+            return Err(Error::InvalidProtobufData);
+        }
+        let value = reader.read_bytes().map_err(Error::with_io)?;
+        match field_number {
+            1 => descriptors = value,
+            2 => type_name = value,
+            3 => payload = value,
+            _ => {}
+        }
+    }
+
+    let type_name = std::str::from_utf8(type_name)
+        .map_err(|_| Error::InvalidProtobufData)?
+        .to_string();
+    let pool = DescriptorPool::from_file_descriptor_set_bytes(descriptors).map_err(|e| match e {
+        Error::InvalidProtobufData => Error::InvalidProtobufData,
+        _ => Error::ArenaAllocationFailed,
+    })?;
+    if pool.get_table(&type_name).is_none() {
+        return Err(Error::MessageNotFound);
+    }
+    Ok(Snapshot {
+        pool,
+        type_name,
+        payload: payload.to_vec(),
+    })
+}
+
+/// A snapshot loaded by [`read`].
+pub struct Snapshot {
+    /// Pool holding the schema stored in the snapshot.
+    pub pool: DescriptorPool<'static>,
+    /// Fully qualified name of the message type, without leading dot.
+    pub type_name: String,
+    /// The encoded message.
+    pub payload: Vec<u8>,
+}
+
+impl Snapshot {
+    /// Decode the payload into a new message allocated in `arena`.
+    pub fn message<'pool, 'msg>(
+        &'pool self,
+        arena: &mut Arena<'msg>,
+    ) -> Result<DynamicMessage<'pool, 'msg>, Error> {
+        let mut msg = self
+            .pool
+            .create_message(&self.type_name, arena)
+            .map_err(|_| Error::ArenaAllocationFailed)?;
+        if !msg.decode_flat::<STACK_DEPTH>(arena, &self.payload) {
+            return Err(Error::InvalidProtobufData);
+        }
+        Ok(msg)
+    }
+}
+
+/// The message `full_name` declared in `file`, including nested messages.
+fn find_message<'a>(file: &'a FileDescriptorProto, full_name: &str) -> Option<&'a DescriptorProto> {
+    let mut name = full_name;
+    if !file.package().is_empty() {
+        name = name.strip_prefix(file.package())?.strip_prefix('.')?;
+    }
+    let mut parts = name.split('.');
+    let top_level = parts.next()?;
+    let mut message = file.message_type().iter().find(|m| m.name() == top_level)?;
+    for part in parts {
+        message = message.nested_type().iter().find(|m| m.name() == part)?;
+    }
+    Some(message)
+}
+
+fn encode<'pool>(msg: &impl ProtobufRef<'pool>) -> Result<Vec<u8>, Error<std::io::Error>> {
+    msg.encode_vec::<STACK_DEPTH>().map_err(Error::with_io)
+}
+
+/// Append a length-delimited field.
+fn push_field(
+    buf: &mut Vec<u8>,
+    field_number: u32,
+    value: &[u8],
+) -> Result<(), Error<std::io::Error>> {
+    let tag = make_tag(field_number, WireType::LengthDelimited);
+    let start = buf.len();
+    let len = varint_len(tag.into()) + varint_len(value.len() as u64) + value.len();
+    buf.resize(start + len, 0);
+    let mut writer = Writer::new(&mut buf[start..]);
+    writer
+        .write_tag(field_number, WireType::LengthDelimited)
+        .map_err(Error::with_io)?;
+    writer.write_bytes(value).map_err(Error::with_io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    #[test]
+    fn pool_message_roundtrip() {
+        let mut arena = Arena::new(&Global);
+        let mut file = FileDescriptorProto::default();
+        file.set_name("point.proto", &mut arena).unwrap();
+        file.set_package("geo", &mut arena).unwrap();
+        file.set_syntax("proto3", &mut arena).unwrap();
+        let message = file.add_message_type(&mut arena).unwrap();
+        message.set_name("Point", &mut arena).unwrap();
+        for (number, name) in [(1, "x"), (2, "y")] {
+            let field = message.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(Type::TYPE_SINT32);
+        }
+        let mut pool = DescriptorPool::new(&Global);
+        pool.add_file(&file).unwrap();
+        let mut point = pool.create_message("geo.Point", &mut arena).unwrap();
+        // x: -3, y: 7
+        let data = [0x08, 0x05, 0x10, 0x0e];
+        assert!(point.decode_flat::<32>(&mut arena, &data));
+
+        let mut buffer = Vec::new();
+        write(&mut buffer, &point, ".geo.Point", pool.files()).unwrap();
+        drop(pool);
+
+        let snapshot = read(&mut &buffer[..]).unwrap();
+        assert_eq!(snapshot.type_name, "geo.Point");
+        let restored = snapshot.message(&mut arena).unwrap();
+        assert_eq!(restored.as_ref().get_i32("x"), Some(-3));
+        assert_eq!(restored.as_ref().get_i32("y"), Some(7));
+        assert_eq!(restored.encode_vec::<32>().unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let file = FileDescriptorProto::file_descriptor();
+        let mut buffer = Vec::new();
+        assert!(matches!(
+            write(&mut buffer, file, "google.protobuf.DescriptorProto", &[file]),
+            Err(Error::MessageNotFound)
+        ));
+        assert!(matches!(
+            write(&mut buffer, file, "google.protobuf.FileDescriptorProto", &[]),
+            Err(Error::MessageNotFound)
+        ));
+        assert!(buffer.is_empty());
+
+        write(&mut buffer, file, "google.protobuf.FileDescriptorProto", &[file]).unwrap();
+        assert!(matches!(
+            read(&mut &buffer[1..]),
+            Err(Error::InvalidProtobufData)
+        ));
+        assert!(matches!(
+            read(&mut &buffer[..buffer.len() - 1]),
+            Err(Error::InvalidProtobufData)
+        ));
+    }
+}
@@ -1,12 +1,97 @@
+/// Version of the layout of tables and generated messages, bumped whenever
+/// it changes. Generated tables carry the version of the runtime their code
+/// generator was built with, and the runtime refuses tables of any other
+/// version, see [`Table::check_version`].
+pub const TABLE_VERSION: u16 = 4;
+
+/// The syntax of the file a message is declared in, see [`Table::syntax`].
+///
+/// Field presence and whether repeated scalars are packed by default depend
+/// on it. In editions both come from the features of the file and fields.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    #[default]
+    Proto2,
+    Proto3,
+    Editions,
+}
+
+impl Syntax {
+    /// The syntax named by the `syntax` field of a `FileDescriptorProto`,
+    /// which is proto2 when unset.
+    pub fn from_name(syntax: Option<&str>) -> Self {
+        match syntax {
+            Some("proto3") => Syntax::Proto3,
+            Some("editions") => Syntax::Editions,
+            _ => Syntax::Proto2,
+        }
+    }
+}
+
 #[repr(C)]
 pub struct Table {
+    /// The [`TABLE_VERSION`] the table was generated for. It comes first, so
+    /// it can be read whatever the layout of the rest.
+    pub version: u16,
     pub num_encode_entries: u16,
     pub num_decode_entries: u16,
     pub size: u16,
-    pub descriptor: &'static crate::google::protobuf::DescriptorProto::ProtoType,
+    pub syntax: Syntax,
+    /// Descriptor of the message, `None` in code generated with descriptors
+    /// stripped, see [`descriptor`](Self::descriptor).
+    pub descriptor: Option<&'static crate::google::protobuf::DescriptorProto::ProtoType>,
+    /// The file declaring the message, `None` with descriptors stripped or
+    /// for messages added to a pool without one.
+    pub file: Option<&'static crate::google::protobuf::FileDescriptorProto::ProtoType>,
+    /// Package-qualified name of the message, without a leading dot.
+    pub full_name: &'static str,
+    /// Indices of the encode entries by ascending field number, empty if the
+    /// entries already are in that order. Encoding with
+    /// [`canonical_field_order`](crate::EncodeOptions::canonical_field_order)
+    /// walks it.
+    pub field_number_order: &'static [u16],
 }
 
 impl Table {
+    /// Fails with [`VersionMismatch`](crate::Error::VersionMismatch) if the
+    /// table was generated for another runtime than this one, as when a crate
+    /// generated by one protocrap release is linked against another. Using
+    /// such a table would read it with the wrong layout.
+    pub fn check_version(&self) -> Result<(), crate::Error> {
+        if self.version != TABLE_VERSION {
+            return Err(crate::Error::VersionMismatch);
+        }
+        Ok(())
+    }
+
+    /// Syntax of the file the message is declared in.
+    pub const fn syntax(&self) -> Syntax {
+        self.syntax
+    }
+
+    /// Descriptor of the message.
+    ///
+    /// Reflection, JSON and text format all go through it. Encoding and
+    /// decoding don't, and work on tables generated without descriptors.
+    ///
+    /// # Panics
+    ///
+    /// If the table was generated with descriptors stripped.
+    pub const fn descriptor(&self) -> &'static crate::google::protobuf::DescriptorProto::ProtoType {
+        match self.descriptor {
+            Some(descriptor) => descriptor,
+            None => panic!("message table generated without descriptors"),
+        }
+    }
+
+    /// The file declaring the message, if the table records it. Enums
+    /// declared at file scope or in other messages of the file are only
+    /// found through it.
+    pub const fn file(&self) -> Option<&'static crate::google::protobuf::FileDescriptorProto::ProtoType> {
+        self.file
+    }
+
     pub(crate) fn decode_entries(&self) -> &[crate::decoding::TableEntry] {
         unsafe {
             let ptr = (self as *const Self).add(1) as *const crate::decoding::TableEntry;
@@ -35,12 +120,161 @@ impl Table {
         }
     }
 
+    /// Field offset of the aux entry at `offset`, which unlike
+    /// [`aux_entry`](Self::aux_entry) doesn't need the child table linked yet.
+    pub(crate) fn aux_field_offset(&self, offset: usize) -> u32 {
+        unsafe {
+            let ptr = (self as *const Self as *const u8).add(offset);
+            debug_assert!((ptr as usize).is_multiple_of(core::mem::align_of::<AuxTableEntry>()));
+            (*(ptr as *const AuxTableEntry)).offset
+        }
+    }
+
+    /// Aux entry of a closed enum field: the field offset and the enum's values.
+    pub(crate) fn aux_enum_entry(&self, offset: usize) -> (u32, &EnumTable) {
+        unsafe {
+            let ptr = (self as *const Self as *const u8).add(offset);
+            debug_assert!((ptr as usize).is_multiple_of(core::mem::align_of::<AuxTableEntry>()));
+            let AuxTableEntry {
+                offset,
+                child_table,
+            } = *(ptr as *const AuxTableEntry);
+            debug_assert!(!child_table.is_null());
+            (offset, &*(child_table as *const EnumTable))
+        }
+    }
+
     #[allow(clippy::self_named_constructors)]
     pub(crate) fn table(encode_entries: &[crate::encoding::TableEntry]) -> &Self {
         unsafe { &*(encode_entries.as_ptr_range().end as *const Table) }
     }
 }
 
+impl core::fmt::Debug for Table {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Table")
+            .field("full_name", &self.full_name)
+            .field("size", &self.size)
+            .field("syntax", &self.syntax)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Read-only view of the object layout a [`Table`] describes.
+///
+/// Message objects start with the has-bits, one per field with explicit
+/// storage of its presence, packed 32 to a `u32` word, followed by a `u32`
+/// discriminant per oneof holding the number of the member set, or 0. The
+/// fields come after, members of a oneof sharing the same storage. This lets
+/// debuggers and memory analyzers read raw objects without hardcoding that
+/// knowledge.
+///
+/// ```
+/// use protocrap::TableInspector;
+/// use protocrap::generated_code_only::Protobuf;
+/// use protocrap::google::protobuf::FileDescriptorProto;
+///
+/// let layout = TableInspector::new(FileDescriptorProto::ProtoType::table());
+/// let name = layout.field(1).unwrap();
+/// assert_eq!((name.name, name.has_bit), ("name", Some(0)));
+/// assert!(name.offset + name.size <= layout.size());
+/// ```
+#[derive(Clone, Copy)]
+pub struct TableInspector<'a> {
+    table: &'a Table,
+}
+
+/// Where a field lives in a message object, see [`TableInspector`].
+#[derive(Debug, Clone, Copy)]
+pub struct FieldLayout<'a> {
+    pub number: u32,
+    pub name: &'a str,
+    /// Byte offset of the field's storage in the object.
+    pub offset: usize,
+    /// Bytes of storage: the value for scalars, a pointer for messages and a
+    /// `RepeatedField` for strings, bytes and repeated fields.
+    pub size: usize,
+    /// Index of the field's has-bit, bit `i % 32` of the `u32` at byte offset
+    /// `4 * (i / 32)`. Repeated, message and oneof fields have none.
+    pub has_bit: Option<u32>,
+    /// The oneof the field is a member of.
+    pub oneof: Option<OneofLayout>,
+    /// Table of the message type of message and group fields.
+    pub message_table: Option<&'a Table>,
+}
+
+/// A field's oneof, see [`FieldLayout::oneof`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OneofLayout {
+    /// Index of the oneof in the message descriptor's `oneof_decl`.
+    pub index: u32,
+    /// Byte offset of the `u32` discriminant, holding the number of the
+    /// member set or 0.
+    pub discriminant_offset: usize,
+}
+
+impl<'a> TableInspector<'a> {
+    pub fn new(table: &'a Table) -> Self {
+        TableInspector { table }
+    }
+
+    /// Size of the message object in bytes.
+    pub fn size(&self) -> usize {
+        self.table.size as usize
+    }
+
+    /// The layout of every field, in declaration order.
+    pub fn fields(&self) -> impl Iterator<Item = FieldLayout<'a>> + 'a {
+        let table = self.table;
+        table
+            .descriptor()
+            .field()
+            .iter()
+            .filter_map(move |field| TableInspector { table }.field(field.number() as u32))
+    }
+
+    /// The layout of field `number`, if the message has it.
+    pub fn field(&self, number: u32) -> Option<FieldLayout<'a>> {
+        use crate::wire::FieldKind;
+
+        let table = self.table;
+        let field = table
+            .descriptor()
+            .field()
+            .iter()
+            .find(|field| field.number() as u32 == number)?;
+        let entry = table.field_entry(number)?;
+        let (offset, message_table) = match entry.kind() {
+            FieldKind::Message | FieldKind::Group | FieldKind::RepeatedMessage | FieldKind::RepeatedGroup => {
+                let (offset, child) = table.aux_entry_decode(entry);
+                (offset, Some(child))
+            }
+            _ => (entry.offset(), None),
+        };
+        let size = match entry.kind() {
+            FieldKind::Bool => 1,
+            FieldKind::Varint32 | FieldKind::Int32 | FieldKind::Varint32Zigzag | FieldKind::Fixed32 => 4,
+            FieldKind::Varint64 | FieldKind::Varint64Zigzag | FieldKind::Fixed64 => 8,
+            FieldKind::Message | FieldKind::Group => core::mem::size_of::<crate::base::Message>(),
+            _ => core::mem::size_of::<crate::containers::RepeatedField<u8>>(),
+        };
+        let has_bit_idx = entry.has_bit_idx();
+        let oneof = (has_bit_idx & 0x80 != 0).then(|| OneofLayout {
+            index: field.oneof_index() as u32,
+            discriminant_offset: (has_bit_idx & 0x7F) as usize * 4,
+        });
+        Some(FieldLayout {
+            number,
+            name: field.name(),
+            offset: offset as usize,
+            size,
+            has_bit: crate::reflection::needs_has_bit(field).then_some(has_bit_idx),
+            oneof,
+            message_table,
+        })
+    }
+}
+
 #[repr(C)]
 pub struct TableWithEntries<const E: usize, const D: usize, const A: usize> {
     pub encode_entries: [crate::encoding::TableEntry; E],
@@ -56,5 +290,110 @@ pub struct AuxTableEntry {
     pub child_table: *const Table,
 }
 
+impl AuxTableEntry {
+    /// Aux entry of a closed enum field. The enum table is stored in place of the
+    /// child table.
+    pub const fn closed_enum(offset: u32, enum_table: &'static EnumTable) -> Self {
+        AuxTableEntry {
+            offset,
+            child_table: enum_table as *const EnumTable as *const Table,
+        }
+    }
+}
+
 unsafe impl Send for AuxTableEntry {}
 unsafe impl Sync for AuxTableEntry {}
+
+/// The values of a closed (proto2) enum, sorted and without duplicates.
+#[repr(C)]
+pub struct EnumTable {
+    pub values: &'static [i32],
+}
+
+impl EnumTable {
+    pub(crate) fn contains(&self, value: i32) -> bool {
+        match self.values {
+            // Most enums are a contiguous range
+            [first, .., last] if (*last as i64 - *first as i64 + 1) as usize == self.values.len() => {
+                (*first..=*last).contains(&value)
+            }
+            values => values.binary_search(&value).is_ok(),
+        }
+    }
+}
+
+// Generated tables are plain data, evaluated at compile time into read-only
+// memory, with nothing to run at startup. Constant evaluation can only read
+// statics like that, so this stops compiling if a table ever needs interior
+// mutability.
+const _: () = {
+    let table = &crate::google::protobuf::FileDescriptorProto::TABLE;
+    assert!(table.table.num_decode_entries as usize == table.decode_entries.len());
+};
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::generated_code_only::Protobuf;
+    use crate::google::protobuf::{FileDescriptorProto, SourceCodeInfo};
+    use crate::well_known::Value;
+
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    #[test]
+    fn table_inspector_reads_raw_objects() {
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_syntax("proto3", &mut arena).unwrap();
+        let layout = TableInspector::new(FileDescriptorProto::ProtoType::table());
+        let raw = &file as *const _ as *const u8;
+        let syntax = layout.fields().find(|field| field.name == "syntax").unwrap();
+        let has_bit = syntax.has_bit.unwrap();
+        unsafe {
+            let word = (raw as *const u32).add(has_bit as usize / 32).read();
+            assert_ne!(word & (1 << (has_bit % 32)), 0);
+            let value = &*(raw.add(syntax.offset) as *const crate::containers::String);
+            assert_eq!(value.as_ref() as &str, "proto3");
+        }
+        let source_code_info = layout.field(9).unwrap();
+        assert_eq!(source_code_info.has_bit, None);
+        assert!(core::ptr::eq(
+            source_code_info.message_table.unwrap(),
+            SourceCodeInfo::ProtoType::table()
+        ));
+        assert!(layout.field(1000).is_none());
+
+        // Members of a oneof share storage behind one discriminant
+        let mut value = Value::default();
+        value.set_number_value(2.5);
+        let layout = TableInspector::new(Value::table());
+        let number = layout.field(2).unwrap();
+        let oneof = number.oneof.unwrap();
+        assert_eq!(layout.field(3).unwrap().oneof, Some(oneof));
+        assert_eq!(layout.field(3).unwrap().offset, number.offset);
+        let raw = &value as *const _ as *const u8;
+        unsafe {
+            assert_eq!((raw.add(oneof.discriminant_offset) as *const u32).read(), 2);
+            assert_eq!((raw.add(number.offset) as *const f64).read(), 2.5);
+        }
+
+        // Tables built from descriptors lay out objects the same way
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(FileDescriptorProto::ProtoType::file_descriptor())
+            .unwrap();
+        let dynamic = TableInspector::new(pool.get_table("google.protobuf.FileDescriptorProto").unwrap());
+        let generated = TableInspector::new(FileDescriptorProto::ProtoType::table());
+        assert_eq!(dynamic.size(), generated.size());
+        for (a, b) in dynamic.fields().zip(generated.fields()) {
+            assert_eq!(
+                (a.number, a.offset, a.size, a.has_bit, a.oneof),
+                (b.number, b.offset, b.size, b.has_bit, b.oneof)
+            );
+        }
+        let descriptor = FileDescriptorProto::ProtoType::table().descriptor();
+        assert_eq!(dynamic.fields().count(), descriptor.field().len());
+    }
+}
@@ -35,11 +35,12 @@ pub fn compare_tables_rec(
     dynamic_table: &Table,
     seen: &mut HashSet<*const Table>,
 ) {
-    let type_name = dynamic_table.descriptor.name();
+    let type_name = dynamic_table.descriptor().name();
     if !seen.insert(dynamic_table as *const Table) {
         return;
     }
 
+    assert_eq!(dynamic_table.full_name, static_table.full_name);
     assert_eq!(
         dynamic_table.size, static_table.size,
         "{}: size mismatch",
@@ -55,6 +56,11 @@ pub fn compare_tables_rec(
         "{}: num_decode_entries mismatch",
         type_name
     );
+    assert_eq!(
+        dynamic_table.field_number_order, static_table.field_number_order,
+        "{}: field_number_order mismatch",
+        type_name
+    );
 
     let dynamic_encode = dynamic_table.encode_entries();
     let static_encode = static_table.encode_entries();
@@ -63,7 +69,7 @@ pub fn compare_tables_rec(
     for (i, (dyn_entry, static_entry)) in
         dynamic_encode.iter().zip(static_encode.iter()).enumerate()
     {
-        let field_name = dynamic_table.descriptor.field()[i].name();
+        let field_name = dynamic_table.descriptor().field()[i].name();
         assert_eq!(
             dyn_entry.offset, static_entry.offset,
             "{}.{}: offset",
@@ -0,0 +1,477 @@
+//! Protobuf text format output.
+//!
+//! [`Printer`] prints binary payloads in the text format of C++'s
+//! `TextFormat`, as `protoc --decode` does. It walks the wire bytes with the
+//! message's [`Table`] instead of a decoded message, so fields the schema
+//! doesn't know, which decoding drops, are printed too, under their field
+//! numbers:
+//!
+//! ```text
+//! name: "a.proto"
+//! 1000: 5
+//! 1001 {
+//!   1: 0x3f800000
+//! }
+//! ```
+//!
+//! As in C++, unknown varints print as unsigned numbers, fixed width values in
+//! hex, and length-delimited values as a nested message when they parse as
+//! one and as a string otherwise. With a pool from [`Printer::expand_any`],
+//! `google.protobuf.Any` messages whose type the pool knows print their
+//! contents instead of the encoded `value`:
+//!
+//! ```text
+//! [type.googleapis.com/google.protobuf.Duration] {
+//!   seconds: 5
+//! }
+//! ```
+//!
+//! [`Serializer`] and [`Deserializer`] write and read the text format as a
+//! serde data format, going through a message's serde impls instead.
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::descriptor_pool::DescriptorPool;
+use crate::google::protobuf::FieldDescriptorProto::{ProtoType as FieldDescriptorProto, Type};
+use crate::reflection::{is_any, is_repeated, lookup_enum_name};
+use crate::tables::Table;
+use crate::wire::{Reader, WireType};
+use crate::{Error, ProtobufRef};
+
+#[cfg(feature = "serde_support")]
+pub mod serde;
+#[cfg(feature = "serde_support")]
+pub use self::serde::{Deserializer, Serializer};
+
+/// Nesting depth beyond which printing fails, C++'s default recursion limit.
+const MAX_DEPTH: usize = 100;
+
+/// Prints binary payloads in text format, see the [module docs](self).
+#[derive(Default, Clone, Copy)]
+pub struct Printer<'a> {
+    pool: Option<&'a DescriptorPool<'a>>,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Print `google.protobuf.Any` messages whose type `pool` knows as
+    /// `[type_url] { ... }` with the fields of the packed message.
+    pub fn expand_any(mut self, pool: &'a DescriptorPool<'a>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// The text format of `buf`, an encoded message of type `table`.
+    ///
+    /// Tables come from `Protobuf::table()` of generated types or from
+    /// [`DescriptorPool::get_table`]. Fails with
+    /// [`Error::InvalidProtobufData`] if `buf` isn't valid wire format for the
+    /// type, and with [`Error::MessageTreeTooDeep`] if it nests more than 100
+    /// messages deep.
+    pub fn print(&self, table: &Table, buf: &[u8]) -> Result<String, Error> {
+        let mut out = String::new();
+        self.message(&mut out, Some(table), buf, 0)?;
+        Ok(out)
+    }
+
+    /// The text format of `msg`.
+    ///
+    /// Decoded messages hold no unknown fields, so this prints what
+    /// [`print`](Self::print) prints for the re-encoded message.
+    pub fn print_message<'pool>(&self, msg: &impl ProtobufRef<'pool>) -> Result<String, Error> {
+        let buf = msg.encode_vec::<MAX_DEPTH>()?;
+        self.print(msg.as_dyn().table, &buf)
+    }
+
+    /// Print the message `buf`, expanding it if it's an `Any` the pool can
+    /// resolve.
+    fn message(
+        &self,
+        out: &mut String,
+        table: Option<&Table>,
+        buf: &[u8],
+        depth: usize,
+    ) -> Result<(), Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::MessageTreeTooDeep);
+        }
+        if let Some(table) = table
+            && is_any(table.descriptor())
+            && let Some(pool) = self.pool
+        {
+            let start = out.len();
+            if self.any(out, pool, buf, depth).is_ok() {
+                return Ok(());
+            }
+            out.truncate(start);
+        }
+        self.fields(out, table, &mut Reader::new(buf), None, depth)
+    }
+
+    /// Print `buf`, an `Any`, as `[type_url] { ... }`. Fails unless the type
+    /// is in the pool and the `Any` holds nothing but a valid payload of it.
+    fn any(&self, out: &mut String, pool: &DescriptorPool, buf: &[u8], depth: usize) -> Result<(), Error> {
+        let mut reader = Reader::new(buf);
+        let mut type_url: &[u8] = &[];
+        let mut value: &[u8] = &[];
+        while !reader.is_empty() {
+            match reader.read_tag()? {
+                (1, WireType::LengthDelimited) => type_url = reader.read_bytes()?,
+                (2, WireType::LengthDelimited) => value = reader.read_bytes()?,
+                _ => return Err(Error::MessageNotFound),
+            }
+        }
+        let type_url = core::str::from_utf8(type_url).map_err(|_| Error::InvalidProtobufData)?;
+        let type_name = type_url.rsplit('/').next().unwrap_or_default();
+        let table = pool.get_table(type_name).ok_or(Error::MessageNotFound)?;
+        indent(out, depth);
+        out.push('[');
+        out.push_str(type_url);
+        out.push_str("] {\n");
+        self.message(out, Some(table), value, depth + 1)?;
+        indent(out, depth);
+        out.push_str("}\n");
+        Ok(())
+    }
+
+    /// Print the fields of one message up to the end of `reader`, or up to
+    /// the END_GROUP tag matching `group`.
+    fn fields(
+        &self,
+        out: &mut String,
+        table: Option<&Table>,
+        reader: &mut Reader,
+        group: Option<u32>,
+        depth: usize,
+    ) -> Result<(), Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::MessageTreeTooDeep);
+        }
+        while !reader.is_empty() {
+            let (number, wire_type) = reader.read_tag()?;
+            if wire_type == WireType::EndGroup {
+                if group == Some(number) {
+                    return Ok(());
+                }
+                return Err(Error::InvalidProtobufData);
+            }
+            let field = table.and_then(|table| {
+                let field = table
+                    .descriptor()
+                    .field()
+                    .iter()
+                    .find(|field| field.number() == number as i32)?;
+                Some((table, &**field))
+            });
+            match field {
+                Some((table, field)) if wire_type == wire_type_of(field) => {
+                    self.known(out, table, field, reader, depth)?
+                }
+                // Message fields with delimited encoding, keeping their name
+                Some((table, field)) if wire_type == WireType::StartGroup && field.r#type() == Some(Type::TYPE_MESSAGE) => {
+                    indent(out, depth);
+                    self.group(out, field.name(), table, field, reader, depth)?
+                }
+                Some((table, field))
+                    if wire_type == WireType::LengthDelimited
+                        && is_repeated(field)
+                        && wire_type_of(field) != WireType::LengthDelimited =>
+                {
+                    let mut packed = Reader::new(reader.read_bytes()?);
+                    while !packed.is_empty() {
+                        self.known(out, table, field, &mut packed, depth)?;
+                    }
+                }
+                _ => self.unknown(out, number, wire_type, reader, depth)?,
+            }
+        }
+        if group.is_some() {
+            return Err(Error::InvalidProtobufData);
+        }
+        Ok(())
+    }
+
+    /// Print one value of `field`, a field of `table`'s message, read with
+    /// the field's own wire type.
+    fn known(
+        &self,
+        out: &mut String,
+        table: &Table,
+        field: &FieldDescriptorProto,
+        reader: &mut Reader,
+        depth: usize,
+    ) -> Result<(), Error> {
+        let ty = field.r#type().ok_or(Error::InvalidProtobufData)?;
+        indent(out, depth);
+        if ty == Type::TYPE_GROUP {
+            // Groups are named after their type
+            let name = field.type_name().rsplit('.').next().unwrap_or_default();
+            return self.group(out, name, table, field, reader, depth);
+        }
+        out.push_str(field.name());
+        if ty == Type::TYPE_MESSAGE {
+            out.push_str(" {\n");
+            let value = reader.read_bytes()?;
+            self.message(out, child_table(table, field), value, depth + 1)?;
+            indent(out, depth);
+            out.push_str("}\n");
+            return Ok(());
+        }
+        out.push_str(": ");
+        let _ = match ty {
+            Type::TYPE_INT32 => write!(out, "{}", reader.read_varint()? as i32),
+            Type::TYPE_INT64 => write!(out, "{}", reader.read_varint()? as i64),
+            Type::TYPE_UINT32 => write!(out, "{}", reader.read_varint()? as u32),
+            Type::TYPE_UINT64 => write!(out, "{}", reader.read_varint()?),
+            Type::TYPE_SINT32 => write!(out, "{}", crate::wire::zigzag_decode(reader.read_varint()? as u32 as u64) as i32),
+            Type::TYPE_SINT64 => write!(out, "{}", crate::wire::zigzag_decode(reader.read_varint()?)),
+            Type::TYPE_BOOL => write!(out, "{}", reader.read_varint()? != 0),
+            Type::TYPE_ENUM => {
+                let value = reader.read_varint()? as i32;
+                match lookup_enum_name(table, field.type_name(), value) {
+                    Some(name) => write!(out, "{name}"),
+                    None => write!(out, "{value}"),
+                }
+            }
+            Type::TYPE_FIXED32 => write!(out, "{}", reader.read_fixed32()?),
+            Type::TYPE_SFIXED32 => write!(out, "{}", reader.read_fixed32()? as i32),
+            Type::TYPE_FLOAT => write_float(out, f32::from_bits(reader.read_fixed32()?) as f64),
+            Type::TYPE_FIXED64 => write!(out, "{}", reader.read_fixed64()?),
+            Type::TYPE_SFIXED64 => write!(out, "{}", reader.read_fixed64()? as i64),
+            Type::TYPE_DOUBLE => write_float(out, f64::from_bits(reader.read_fixed64()?)),
+            Type::TYPE_STRING => {
+                let value = reader.read_bytes()?;
+                core::str::from_utf8(value).map_err(|_| Error::InvalidProtobufData)?;
+                write_quoted(out, value, false)
+            }
+            Type::TYPE_BYTES => write_quoted(out, reader.read_bytes()?, true),
+            Type::TYPE_MESSAGE | Type::TYPE_GROUP => unreachable!(),
+        };
+        out.push('\n');
+        Ok(())
+    }
+
+    /// Print a group value of `field` under `name`, the field's own
+    /// indentation already written.
+    fn group(
+        &self,
+        out: &mut String,
+        name: &str,
+        table: &Table,
+        field: &FieldDescriptorProto,
+        reader: &mut Reader,
+        depth: usize,
+    ) -> Result<(), Error> {
+        out.push_str(name);
+        out.push_str(" {\n");
+        let child = child_table(table, field);
+        self.fields(out, child, reader, Some(field.number() as u32), depth + 1)?;
+        indent(out, depth);
+        out.push_str("}\n");
+        Ok(())
+    }
+
+    /// Print a field the schema doesn't know under its number.
+    fn unknown(
+        &self,
+        out: &mut String,
+        number: u32,
+        wire_type: WireType,
+        reader: &mut Reader,
+        depth: usize,
+    ) -> Result<(), Error> {
+        indent(out, depth);
+        let _ = write!(out, "{number}");
+        let _ = match wire_type {
+            WireType::Varint => write!(out, ": {}", reader.read_varint()?),
+            WireType::Fixed64 => write!(out, ": 0x{:016x}", reader.read_fixed64()?),
+            WireType::Fixed32 => write!(out, ": 0x{:08x}", reader.read_fixed32()?),
+            WireType::LengthDelimited => {
+                let value = reader.read_bytes()?;
+                let start = out.len();
+                out.push_str(" {\n");
+                let nested = !value.is_empty()
+                    && self.fields(out, None, &mut Reader::new(value), None, depth + 1).is_ok();
+                if nested {
+                    indent(out, depth);
+                    out.push('}');
+                    Ok(())
+                } else {
+                    out.truncate(start);
+                    out.push_str(": ");
+                    write_quoted(out, value, true)
+                }
+            }
+            WireType::StartGroup => {
+                out.push_str(" {\n");
+                self.fields(out, None, reader, Some(number), depth + 1)?;
+                indent(out, depth);
+                out.push('}');
+                Ok(())
+            }
+            WireType::EndGroup => return Err(Error::InvalidProtobufData),
+        };
+        out.push('\n');
+        Ok(())
+    }
+}
+
+/// The wire type values of `field` are encoded with, unpacked.
+fn wire_type_of(field: &FieldDescriptorProto) -> WireType {
+    match field.r#type() {
+        Some(Type::TYPE_FIXED64 | Type::TYPE_SFIXED64 | Type::TYPE_DOUBLE) => WireType::Fixed64,
+        Some(Type::TYPE_STRING | Type::TYPE_BYTES | Type::TYPE_MESSAGE) => WireType::LengthDelimited,
+        Some(Type::TYPE_GROUP) => WireType::StartGroup,
+        Some(Type::TYPE_FIXED32 | Type::TYPE_SFIXED32 | Type::TYPE_FLOAT) => WireType::Fixed32,
+        _ => WireType::Varint,
+    }
+}
+
+fn child_table<'t>(table: &'t Table, field: &FieldDescriptorProto) -> Option<&'t Table> {
+    let entry = table.field_entry(field.number() as u32)?;
+    Some(table.aux_entry_decode(entry).1)
+}
+
+fn indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+}
+
+fn write_float(out: &mut String, value: f64) -> core::fmt::Result {
+    match value {
+        v if v.is_nan() => write!(out, "nan"),
+        v if v.is_infinite() => write!(out, "{}", if v > 0.0 { "inf" } else { "-inf" }),
+        v => write!(out, "{v}"),
+    }
+}
+
+/// Write `value` as a double-quoted C-escaped string. Non-ASCII bytes are
+/// octal escaped in `bytes` values and kept as they are in strings.
+fn write_quoted(out: &mut String, value: &[u8], escape_non_ascii: bool) -> core::fmt::Result {
+    out.push('"');
+    let mut rest = value;
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            b'"' => out.push_str("\\\""),
+            b'\'' => out.push_str("\\'"),
+            b'\\' => out.push_str("\\\\"),
+            0x20..=0x7e => out.push(byte as char),
+            0x80.. if !escape_non_ascii => {
+                // Whole UTF-8 sequence, `value` having been validated
+                let len = match byte {
+                    0xf0.. => 4,
+                    0xe0.. => 3,
+                    _ => 2,
+                };
+                out.push_str(core::str::from_utf8(&rest[..len]).unwrap());
+                rest = &rest[len..];
+                continue;
+            }
+            _ => write!(out, "\\{byte:03o}")?,
+        }
+        rest = tail;
+    }
+    out.push('"');
+    Ok(())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::generated_code_only::Protobuf;
+    use crate::google::protobuf::{Any, FieldDescriptorProto, FileDescriptorProto};
+
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    #[test]
+    fn known_and_unknown_fields() {
+        let mut buf = std::vec::Vec::new();
+        buf.extend_from_slice(b"\x0a\x07a.proto\x12\x01p");
+        // public_dependency unpacked, then packed
+        buf.extend_from_slice(&[0x50, 0x03, 0x52, 0x02, 0x04, 0x05]);
+        // options { java_package: "j" }
+        buf.extend_from_slice(&[0x42, 0x03, 0x0a, 0x01, b'j']);
+        // 1000: 5, 1001 { 1: 1.0f32 }, 1002: "hi\x01"
+        buf.extend_from_slice(&[0xc0, 0x3e, 0x05]);
+        buf.extend_from_slice(&[0xca, 0x3e, 0x05, 0x0d, 0x00, 0x00, 0x80, 0x3f]);
+        buf.extend_from_slice(&[0xd2, 0x3e, 0x03, b'h', b'i', 0x01]);
+
+        let text = Printer::new()
+            .print(FileDescriptorProto::ProtoType::table(), &buf)
+            .unwrap();
+        assert_eq!(
+            text,
+            "name: \"a.proto\"\n\
+             package: \"p\"\n\
+             public_dependency: 3\n\
+             public_dependency: 4\n\
+             public_dependency: 5\n\
+             options {\n  java_package: \"j\"\n}\n\
+             1000: 5\n\
+             1001 {\n  1: 0x3f800000\n}\n\
+             1002: \"hi\\001\"\n"
+        );
+
+        let table = FileDescriptorProto::ProtoType::table();
+        for bad in [&buf[..buf.len() - 1], &[0x42, 0x02, 0x0a, 0x05][..], &[0x0b][..]] {
+            assert!(matches!(
+                Printer::new().print(table, bad),
+                Err(Error::InvalidProtobufData)
+            ));
+        }
+    }
+
+    #[test]
+    fn enums_and_messages() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut field = FieldDescriptorProto::ProtoType::default();
+        field.set_name("caf\u{e9}\n", &mut arena).unwrap();
+        field.set_label(Label::LABEL_REPEATED);
+        field.set_type(Type::TYPE_INT32);
+        field.set_number(-1);
+        assert_eq!(
+            Printer::new().print_message(&field).unwrap(),
+            "name: \"caf\u{e9}\\n\"\nnumber: -1\nlabel: LABEL_REPEATED\ntype: TYPE_INT32\n"
+        );
+    }
+
+    #[test]
+    fn any_expansion() {
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut any = Any::ProtoType::default();
+        any.set_type_url("type.googleapis.com/google.protobuf.FileDescriptorProto", &mut arena)
+            .unwrap();
+        any.set_value(b"\x0a\x01x", &mut arena).unwrap();
+
+        let plain = "type_url: \"type.googleapis.com/google.protobuf.FileDescriptorProto\"\n\
+                     value: \"\\n\\001x\"\n";
+        assert_eq!(Printer::new().print_message(&any).unwrap(), plain);
+
+        let mut pool = DescriptorPool::new(&Global);
+        pool.add_file(FileDescriptorProto::ProtoType::file_descriptor())
+            .unwrap();
+        let printer = Printer::new().expand_any(&pool);
+        assert_eq!(
+            printer.print_message(&any).unwrap(),
+            "[type.googleapis.com/google.protobuf.FileDescriptorProto] {\n  name: \"x\"\n}\n"
+        );
+
+        // Types missing from the pool print as they are
+        any.set_type_url("type.googleapis.com/unknown.Type", &mut arena)
+            .unwrap();
+        assert!(printer.print_message(&any).unwrap().starts_with("type_url: "));
+    }
+}
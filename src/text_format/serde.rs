@@ -0,0 +1,1360 @@
+//! Text format as a serde data format.
+//!
+//! [`Serializer`] and [`Deserializer`] carry messages through the same serde
+//! code paths as JSON or CBOR, so one generic function can target any of
+//! them:
+//!
+//! ```
+//! use protocrap::{ProtobufMut, ProtobufRef};
+//! use protocrap::arena::Arena;
+//! use protocrap::google::protobuf::FileDescriptorProto;
+//! use protocrap::text_format;
+//! use allocator_api2::alloc::Global;
+//! use serde::Serialize;
+//!
+//! let mut arena = Arena::new(&Global);
+//! let mut file = FileDescriptorProto::ProtoType::default();
+//! file.set_name("a.proto", &mut arena).unwrap();
+//!
+//! let mut text = String::new();
+//! file.as_dyn().serialize(&mut text_format::Serializer::new(&mut text)).unwrap();
+//! assert_eq!(text, "name: \"a.proto\"\n");
+//!
+//! let mut parsed = FileDescriptorProto::ProtoType::default();
+//! let deserializer = text_format::Deserializer::from_str(&text).unwrap();
+//! parsed.serde_deserialize(&mut arena, deserializer).unwrap();
+//! assert_eq!(parsed.name(), "a.proto");
+//! ```
+//!
+//! Fields are named by their proto names, enum values by name unquoted and
+//! repeated fields repeat their name, as in C++'s `TextFormat`. Map fields
+//! are written as repeated `key`/`value` entries. The format isn't
+//! human-readable to serde, so bytes travel raw and `Any` as its plain
+//! fields; other well-known types keep their serde forms, a `Timestamp`
+//! for one being a string, which parses back here but not with `protoc`.
+//! Extensions and `[type_url]` expansions aren't supported.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use ::serde::de::{self, IntoDeserializer, Visitor};
+use ::serde::ser::{self, Impossible, Serialize};
+
+use super::{indent, write_float, write_quoted};
+use crate::serde::TextFormatNames;
+
+/// Error of [`Serializer`] and [`Deserializer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error(String);
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: core::fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: core::fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+fn unsupported(what: &str) -> Error {
+    Error(alloc::format!("text format can't hold {what}"))
+}
+
+/// Writes a message in text format to a `String`.
+pub struct Serializer<'a> {
+    out: &'a mut String,
+    depth: usize,
+}
+
+impl<'a> Serializer<'a> {
+    pub fn new(out: &'a mut String) -> Self {
+        Serializer { out, depth: 0 }
+    }
+}
+
+impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Fields<'a, 'b>;
+    type SerializeStruct = Fields<'a, 'b>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Fields<'a, 'b>, Error> {
+        Ok(Fields::new(self, false))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Fields<'a, 'b>, Error> {
+        Ok(Fields::new(self, false))
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), Error> {
+        Err(unsupported("a bare value"))
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(unsupported("enum variants with data"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(unsupported("a bare list"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(unsupported("tuples"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(unsupported("tuples"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unsupported("tuples"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unsupported("enum variants with data"))
+    }
+}
+
+/// The fields of a message, closed with `}` if nested.
+pub struct Fields<'a, 'b> {
+    ser: &'b mut Serializer<'a>,
+    nested: bool,
+    /// Name of the field whose value comes next, for maps.
+    key: Option<String>,
+    _names: TextFormatNames,
+}
+
+impl<'a, 'b> Fields<'a, 'b> {
+    fn new(ser: &'b mut Serializer<'a>, nested: bool) -> Self {
+        Fields {
+            ser,
+            nested,
+            key: None,
+            _names: TextFormatNames::new(),
+        }
+    }
+
+    fn close(self) -> Result<(), Error> {
+        if self.nested {
+            self.ser.depth -= 1;
+            indent(self.ser.out, self.ser.depth);
+            self.ser.out.push_str("}\n");
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for Fields<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        value.serialize(FieldSerializer {
+            ser: self.ser,
+            name: key,
+            in_list: false,
+        })
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl ser::SerializeMap for Fields<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let name = self.key.take().ok_or_else(|| Error("map value without a key".into()))?;
+        value.serialize(FieldSerializer {
+            ser: self.ser,
+            name: &name,
+            in_list: false,
+        })
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+/// Writes the value of field `name`, with the name.
+struct FieldSerializer<'a, 'b, 'n> {
+    ser: &'b mut Serializer<'a>,
+    name: &'n str,
+    in_list: bool,
+}
+
+impl FieldSerializer<'_, '_, '_> {
+    fn scalar(self, write: impl FnOnce(&mut String) -> core::fmt::Result) -> Result<(), Error> {
+        indent(self.ser.out, self.ser.depth);
+        self.ser.out.push_str(self.name);
+        self.ser.out.push_str(": ");
+        write(self.ser.out).map_err(ser::Error::custom)?;
+        self.ser.out.push('\n');
+        Ok(())
+    }
+
+    fn open(&mut self) {
+        indent(self.ser.out, self.ser.depth);
+        self.ser.out.push_str(self.name);
+        self.ser.out.push_str(" {\n");
+        self.ser.depth += 1;
+    }
+}
+
+impl<'a, 'b, 'n> ser::Serializer for FieldSerializer<'a, 'b, 'n> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Elements<'a, 'b, 'n>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Entries<'a, 'b, 'n>;
+    type SerializeStruct = Fields<'a, 'b>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.scalar(|out| write!(out, "{v}"))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.scalar(|out| write!(out, "{v}"))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.scalar(|out| write!(out, "{v}"))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        // Shortest digits of the f32, not of the f64 it widens to
+        if v.is_finite() {
+            self.scalar(|out| write!(out, "{v}"))
+        } else {
+            self.scalar(|out| write_float(out, v as f64))
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.scalar(|out| write_float(out, v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.scalar(|out| write_quoted(out, v.as_bytes(), false))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.scalar(|out| write_quoted(out, v, true))
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.scalar(|out| out.write_str(variant))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(unsupported("enum variants with data"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Elements<'a, 'b, 'n>, Error> {
+        if self.in_list {
+            return Err(unsupported("nested lists"));
+        }
+        Ok(Elements {
+            ser: self.ser,
+            name: self.name,
+        })
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(unsupported("tuples"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(unsupported("tuples"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unsupported("tuples"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Entries<'a, 'b, 'n>, Error> {
+        Ok(Entries {
+            ser: self.ser,
+            name: self.name,
+        })
+    }
+
+    fn serialize_struct(mut self, _name: &'static str, _len: usize) -> Result<Fields<'a, 'b>, Error> {
+        self.open();
+        Ok(Fields::new(self.ser, true))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unsupported("enum variants with data"))
+    }
+}
+
+/// The elements of a repeated field, each written under the field's name.
+struct Elements<'a, 'b, 'n> {
+    ser: &'b mut Serializer<'a>,
+    name: &'n str,
+}
+
+impl ser::SerializeSeq for Elements<'_, '_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(FieldSerializer {
+            ser: self.ser,
+            name: self.name,
+            in_list: true,
+        })
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// The entries of a map field, each written as a `key`/`value` message
+/// under the field's name.
+struct Entries<'a, 'b, 'n> {
+    ser: &'b mut Serializer<'a>,
+    name: &'n str,
+}
+
+impl ser::SerializeMap for Entries<'_, '_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        let mut entry = FieldSerializer {
+            ser: self.ser,
+            name: self.name,
+            in_list: false,
+        };
+        entry.open();
+        key.serialize(FieldSerializer {
+            ser: self.ser,
+            name: "key",
+            in_list: false,
+        })
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(FieldSerializer {
+            ser: self.ser,
+            name: "value",
+            in_list: false,
+        })?;
+        self.ser.depth -= 1;
+        indent(self.ser.out, self.ser.depth);
+        self.ser.out.push_str("}\n");
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Turns the keys of a top-level map into field names.
+struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    fn serialize_str(self, v: &str) -> Result<String, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.into())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(unsupported("non-string field names"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unsupported("non-string field names"))
+    }
+}
+
+/// A parsed value.
+#[derive(Debug, Clone, PartialEq)]
+enum Node<'de> {
+    /// Identifier, such as an enum value name, `true` or `inf`.
+    Ident(&'de str),
+    Int { negative: bool, magnitude: u64 },
+    Float(f64),
+    /// String or bytes, escapes resolved.
+    Str(Vec<u8>),
+    /// Fields by name, the values of repeated ones gathered in a `List`.
+    Message(Vec<(&'de str, Node<'de>)>),
+    List(Vec<Node<'de>>),
+}
+
+/// Reads a message in text format.
+///
+/// The input is parsed on construction, after which the deserializer hands
+/// its fields to serde. A field given more than once, as repeated fields
+/// are, becomes a list.
+pub struct Deserializer<'de> {
+    root: Node<'de>,
+}
+
+impl<'de> Deserializer<'de> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &'de str) -> Result<Self, Error> {
+        let mut parser = Parser { input, pos: 0 };
+        let root = parser.fields(None)?;
+        Ok(Deserializer { root })
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.root.deserialize_any(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // A top-level map has fields, not key/value entries
+        self.root.deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Node<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Node<'de> {
+    fn unexpected(&self) -> de::Unexpected<'_> {
+        match self {
+            Node::Ident(ident) => de::Unexpected::Other(ident),
+            Node::Int { negative: true, .. } => de::Unexpected::Other("negative integer"),
+            Node::Int { magnitude, .. } => de::Unexpected::Unsigned(*magnitude),
+            Node::Float(v) => de::Unexpected::Float(*v),
+            Node::Str(v) => de::Unexpected::Bytes(v),
+            Node::Message(_) => de::Unexpected::Map,
+            Node::List(_) => de::Unexpected::Seq,
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Node<'de> {
+    type Error = Error;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Node::Ident("true" | "True" | "t") => visitor.visit_bool(true),
+            Node::Ident("false" | "False" | "f") => visitor.visit_bool(false),
+            Node::Ident(ident) => match float_ident(ident) {
+                Some(v) => visitor.visit_f64(v),
+                None => visitor.visit_borrowed_str(ident),
+            },
+            Node::Int { negative: false, magnitude } => visitor.visit_u64(magnitude),
+            Node::Int { negative: true, magnitude } => match 0i64.checked_sub_unsigned(magnitude) {
+                Some(v) => visitor.visit_i64(v),
+                None => Err(Error(alloc::format!("-{magnitude} is out of range"))),
+            },
+            Node::Float(v) => visitor.visit_f64(v),
+            Node::Str(v) => match String::from_utf8(v) {
+                Ok(v) => visitor.visit_string(v),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
+            Node::Message(fields) => {
+                let fields = fields.into_iter().map(|(name, value)| (Key::Name(name), value));
+                visitor.visit_map(de::value::MapDeserializer::new(fields))
+            }
+            Node::List(elements) => visitor.visit_seq(de::value::SeqDeserializer::new(elements.into_iter())),
+        }
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            // Integer literals are valid for floating point fields
+            Node::Int { negative, magnitude } => {
+                let v = magnitude as f64;
+                visitor.visit_f64(if negative { -v } else { v })
+            }
+            node => node.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Node::Str(v) => visitor.visit_byte_buf(v),
+            node => node.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Node::List(elements) => visitor.visit_seq(de::value::SeqDeserializer::new(elements.into_iter())),
+            // A repeated field given once
+            node => visitor.visit_seq(de::value::SeqDeserializer::new(core::iter::once(node))),
+        }
+    }
+
+    /// Maps are repeated messages of a `key` and a `value` field.
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let entries = match self {
+            Node::List(entries) => entries,
+            entry @ Node::Message(_) => alloc::vec![entry],
+            node => return Err(de::Error::invalid_type(node.unexpected(), &visitor)),
+        };
+        let entries = entries
+            .into_iter()
+            .map(|entry| {
+                let Node::Message(fields) = entry else {
+                    return Err(Error("map entries are messages".into()));
+                };
+                let mut key = None;
+                let mut value = None;
+                for (name, node) in fields {
+                    match name {
+                        "key" => key = Some(node),
+                        "value" => value = Some(node),
+                        _ => return Err(Error(alloc::format!("unknown map entry field '{name}'"))),
+                    }
+                }
+                let key = key.ok_or_else(|| Error("map entry without a key".into()))?;
+                let value = value.ok_or_else(|| Error("map entry without a value".into()))?;
+                Ok((Key::Value(key), value))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        visitor.visit_map(de::value::MapDeserializer::new(entries.into_iter()))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string
+        unit unit_struct tuple tuple_struct struct enum identifier
+    }
+}
+
+/// The value of `inf`, `nan` and their spellings.
+fn float_ident(ident: &str) -> Option<f64> {
+    let (negative, ident) = match ident.strip_prefix('-') {
+        Some(ident) => (true, ident),
+        None => (false, ident),
+    };
+    let v = match ident.to_ascii_lowercase().as_str() {
+        "inf" | "infinity" => f64::INFINITY,
+        "nan" => f64::NAN,
+        _ => return None,
+    };
+    Some(if negative { -v } else { v })
+}
+
+/// A map key: a field name, or the `key` of a map entry, which JSON-minded
+/// visitors expect as a string.
+enum Key<'de> {
+    Name(&'de str),
+    Value(Node<'de>),
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Key<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Key<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Key::Name(name) | Key::Value(Node::Ident(name)) => visitor.visit_borrowed_str(name),
+            Key::Value(Node::Int { negative, magnitude }) => {
+                let sign = if negative { "-" } else { "" };
+                visitor.visit_string(alloc::format!("{sign}{magnitude}"))
+            }
+            Key::Value(node) => node.deserialize_any(visitor),
+        }
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct Parser<'de> {
+    input: &'de str,
+    pos: usize,
+}
+
+impl<'de> Parser<'de> {
+    fn error(&self, what: &str) -> Error {
+        let line = self.input[..self.pos].matches('\n').count() + 1;
+        Error(alloc::format!("{what} at line {line}"))
+    }
+
+    fn rest(&self) -> &'de [u8] {
+        &self.input.as_bytes()[self.pos..]
+    }
+
+    /// Skip whitespace and `#` comments, returning the next byte.
+    fn peek(&mut self) -> Option<u8> {
+        loop {
+            match *self.rest().first()? {
+                b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c => self.pos += 1,
+                b'#' => {
+                    let len = self.rest().iter().position(|&b| b == b'\n').unwrap_or(self.rest().len());
+                    self.pos += len;
+                }
+                byte => return Some(byte),
+            }
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn ident(&mut self) -> Result<&'de str, Error> {
+        self.peek();
+        let start = self.pos;
+        let len = self
+            .rest()
+            .iter()
+            .position(|&b| !(b.is_ascii_alphanumeric() || b == b'_'))
+            .unwrap_or(self.rest().len());
+        if len == 0 || self.rest()[0].is_ascii_digit() {
+            return Err(self.error("expected an identifier"));
+        }
+        self.pos += len;
+        Ok(&self.input[start..self.pos])
+    }
+
+    /// Fields up to the end of input, or up to `close`.
+    fn fields(&mut self, close: Option<u8>) -> Result<Node<'de>, Error> {
+        let mut fields: Vec<(&'de str, Node<'de>)> = Vec::new();
+        loop {
+            match self.peek() {
+                None if close.is_none() => break,
+                None => return Err(self.error("unexpected end of input")),
+                Some(byte) if Some(byte) == close => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'[') => return Err(self.error("extensions and Any expansions aren't supported")),
+                Some(_) => {}
+            }
+            let name = self.ident()?;
+            let colon = self.eat(b':');
+            let value = match self.peek() {
+                Some(b'{' | b'<') => self.message()?,
+                Some(b'[') => {
+                    self.pos += 1;
+                    let mut elements = Vec::new();
+                    if !self.eat(b']') {
+                        loop {
+                            elements.push(self.value()?);
+                            if self.eat(b']') {
+                                break;
+                            }
+                            if !self.eat(b',') {
+                                return Err(self.error("expected ',' or ']'"));
+                            }
+                        }
+                    }
+                    Node::List(elements)
+                }
+                _ if colon => self.scalar()?,
+                _ => return Err(self.error("expected ':'")),
+            };
+            if !self.eat(b';') {
+                self.eat(b',');
+            }
+            // Repeated fields gather into a list
+            match fields.iter_mut().find(|(other, _)| *other == name) {
+                Some((_, Node::List(elements))) => match value {
+                    Node::List(more) => elements.extend(more),
+                    value => elements.push(value),
+                },
+                Some((_, first)) => {
+                    let first = core::mem::replace(first, Node::List(Vec::new()));
+                    let Some((_, Node::List(elements))) = fields.iter_mut().find(|(other, _)| *other == name)
+                    else {
+                        unreachable!()
+                    };
+                    elements.push(first);
+                    match value {
+                        Node::List(more) => elements.extend(more),
+                        value => elements.push(value),
+                    }
+                }
+                None => fields.push((name, value)),
+            }
+        }
+        Ok(Node::Message(fields))
+    }
+
+    fn message(&mut self) -> Result<Node<'de>, Error> {
+        let close = if self.eat(b'{') {
+            b'}'
+        } else if self.eat(b'<') {
+            b'>'
+        } else {
+            return Err(self.error("expected '{' or '<'"));
+        };
+        self.fields(Some(close))
+    }
+
+    fn value(&mut self) -> Result<Node<'de>, Error> {
+        match self.peek() {
+            Some(b'{' | b'<') => self.message(),
+            _ => self.scalar(),
+        }
+    }
+
+    fn scalar(&mut self) -> Result<Node<'de>, Error> {
+        match self.peek() {
+            Some(b'"' | b'\'') => {
+                let mut value = Vec::new();
+                while let Some(quote @ (b'"' | b'\'')) = self.peek() {
+                    self.pos += 1;
+                    self.string(quote, &mut value)?;
+                }
+                Ok(Node::Str(value))
+            }
+            Some(b'-') => {
+                self.pos += 1;
+                match self.peek() {
+                    Some(b'0'..=b'9' | b'.') => match self.number()? {
+                        Node::Int { magnitude, .. } => Ok(Node::Int { negative: true, magnitude }),
+                        Node::Float(v) => Ok(Node::Float(-v)),
+                        _ => unreachable!(),
+                    },
+                    _ => match float_ident(self.ident()?) {
+                        Some(v) => Ok(Node::Float(-v)),
+                        None => Err(self.error("expected a number")),
+                    },
+                }
+            }
+            Some(b'0'..=b'9' | b'.') => self.number(),
+            Some(_) => Ok(Node::Ident(self.ident()?)),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Node<'de>, Error> {
+        let start = self.pos;
+        let len = self
+            .rest()
+            .iter()
+            .position(|&b| !(b.is_ascii_alphanumeric() || b == b'.' || b == b'_' || b == b'+' || b == b'-'))
+            .unwrap_or(self.rest().len());
+        // Signs only follow exponents
+        let mut end = len;
+        for i in 1..len {
+            let b = self.rest()[i];
+            let prev = self.rest()[i - 1];
+            let hex = self.rest().len() > 1 && self.rest()[..2].eq_ignore_ascii_case(b"0x");
+            if (b == b'+' || b == b'-') && (hex || !matches!(prev, b'e' | b'E')) {
+                end = i;
+                break;
+            }
+        }
+        let text = &self.input[start..start + end];
+        self.pos += end;
+        let invalid = || Error(alloc::format!("invalid number '{text}'"));
+        let int = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(u64::from_str_radix(hex, 16).map_err(|_| invalid())?)
+        } else if text.len() > 1 && text.starts_with('0') && text.bytes().all(|b| b.is_ascii_digit()) {
+            Some(u64::from_str_radix(&text[1..], 8).map_err(|_| invalid())?)
+        } else if text.bytes().all(|b| b.is_ascii_digit()) {
+            Some(text.parse().map_err(|_| invalid())?)
+        } else {
+            None
+        };
+        if let Some(magnitude) = int {
+            return Ok(Node::Int { negative: false, magnitude });
+        }
+        let float = text.strip_suffix(['f', 'F']).unwrap_or(text);
+        float.parse().map(Node::Float).map_err(|_| invalid())
+    }
+
+    /// The rest of a string opened with `quote`, escapes resolved into `out`.
+    fn string(&mut self, quote: u8, out: &mut Vec<u8>) -> Result<(), Error> {
+        loop {
+            let Some((&byte, _)) = self.rest().split_first() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match byte {
+                b'\n' => return Err(self.error("unterminated string")),
+                b'\\' => self.escape(out)?,
+                _ if byte == quote => return Ok(()),
+                _ => out.push(byte),
+            }
+        }
+    }
+
+    fn escape(&mut self, out: &mut Vec<u8>) -> Result<(), Error> {
+        let Some((&byte, _)) = self.rest().split_first() else {
+            return Err(self.error("unterminated string"));
+        };
+        self.pos += 1;
+        let digits = |parser: &mut Self, radix: u32, max: usize| -> Result<u32, Error> {
+            let len = parser
+                .rest()
+                .iter()
+                .take(max)
+                .take_while(|b| (**b as char).is_digit(radix))
+                .count();
+            let text = &parser.input[parser.pos..parser.pos + len];
+            parser.pos += len;
+            u32::from_str_radix(text, radix).map_err(|_| parser.error("invalid escape"))
+        };
+        match byte {
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'a' => out.push(0x07),
+            b'b' => out.push(0x08),
+            b'f' => out.push(0x0c),
+            b'v' => out.push(0x0b),
+            b'\\' | b'\'' | b'"' | b'?' => out.push(byte),
+            b'0'..=b'7' => {
+                self.pos -= 1;
+                let v = digits(self, 8, 3)?;
+                out.push(u8::try_from(v).map_err(|_| self.error("invalid escape"))?);
+            }
+            b'x' | b'X' => out.push(digits(self, 16, 2)? as u8),
+            b'u' | b'U' => {
+                let v = digits(self, 16, if byte == b'u' { 4 } else { 8 })?;
+                let c = char::from_u32(v).ok_or_else(|| self.error("invalid escape"))?;
+                out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            _ => return Err(self.error("invalid escape")),
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::generated_code_only::Protobuf;
+    use crate::google::protobuf::FileDescriptorProto;
+    use crate::text_format::Printer;
+    use crate::{ProtobufMut, ProtobufRef};
+
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    #[test]
+    fn matches_printer_and_round_trips() {
+        let file = FileDescriptorProto::ProtoType::file_descriptor();
+        let mut text = String::new();
+        file.as_dyn().serialize(&mut Serializer::new(&mut text)).unwrap();
+
+        let bytes = file.encode_vec::<32>().unwrap();
+        let printed = Printer::new()
+            .print(FileDescriptorProto::ProtoType::table(), &bytes)
+            .unwrap();
+        assert_eq!(text, printed);
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut parsed = FileDescriptorProto::ProtoType::default();
+        parsed
+            .serde_deserialize(&mut arena, Deserializer::from_str(&text).unwrap())
+            .unwrap();
+        assert_eq!(parsed.encode_vec::<32>().unwrap(), bytes);
+    }
+
+    #[test]
+    fn reads_protoc_syntax() {
+        use crate::google::protobuf::FieldDescriptorProto::Type;
+
+        let text = r#"
+            # A comment
+            name: "a" 'b' "\101\x42é"
+            dependency: ["x.proto", "y.proto"]
+            dependency: "z.proto";
+            message_type <
+              name: "M",
+              field { name: "f" number: 0x10 type: TYPE_DOUBLE default_value: "-inf" }
+              field { name: "g" number: 017 type: 9 }
+            >
+            options { optimize_for: SPEED cc_enable_arenas: true }
+        "#;
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.serde_deserialize(&mut arena, Deserializer::from_str(text).unwrap())
+            .unwrap();
+        assert_eq!(file.name(), "abAB\u{e9}");
+        assert_eq!(file.dependency().len(), 3);
+        assert_eq!(file.dependency()[2].as_str(), "z.proto");
+        let message = &file.message_type()[0];
+        assert_eq!(message.name(), "M");
+        assert_eq!(message.field()[0].number(), 16);
+        assert_eq!(message.field()[0].r#type(), Some(Type::TYPE_DOUBLE));
+        assert_eq!(message.field()[1].number(), 15);
+        assert_eq!(message.field()[1].r#type(), Some(Type::TYPE_STRING));
+        assert!(file.options().unwrap().cc_enable_arenas());
+
+        for bad in ["name: \"a", "name \"a\"", "[ext]: 1", "message_type { name: \"M\""] {
+            assert!(Deserializer::from_str(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn maps_are_key_value_entries() {
+        use crate::google::protobuf::FieldDescriptorProto::{Label, Type};
+
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("counts.proto", &mut arena).unwrap();
+        file.set_syntax("proto3", &mut arena).unwrap();
+        let message = file.add_message_type(&mut arena).unwrap();
+        message.set_name("Counts", &mut arena).unwrap();
+        let field = message.add_field(&mut arena).unwrap();
+        field.set_name("counts", &mut arena).unwrap();
+        field.set_json_name("counts", &mut arena).unwrap();
+        field.set_number(1);
+        field.set_label(Label::LABEL_REPEATED);
+        field.set_type(Type::TYPE_MESSAGE);
+        field.set_type_name(".Counts.CountsEntry", &mut arena).unwrap();
+        let entry = message.add_nested_type(&mut arena).unwrap();
+        entry.set_name("CountsEntry", &mut arena).unwrap();
+        entry.options_mut(&mut arena).set_map_entry(true);
+        for (number, name, ty) in [(1, "key", Type::TYPE_INT32), (2, "value", Type::TYPE_STRING)] {
+            let field = entry.add_field(&mut arena).unwrap();
+            field.set_name(name, &mut arena).unwrap();
+            field.set_number(number);
+            field.set_label(Label::LABEL_OPTIONAL);
+            field.set_type(ty);
+        }
+        let mut pool = crate::descriptor_pool::DescriptorPool::new(&Global);
+        pool.add_file(&file).unwrap();
+
+        let text = "counts { key: -2 value: \"b\" }\ncounts { key: 1 value: \"a\" }\n";
+        let mut msg = pool.create_message("Counts", &mut arena).unwrap();
+        let deserializer = Deserializer::from_str(text).unwrap();
+        msg.serde_deserialize(&mut arena, deserializer).unwrap();
+
+        let mut out = String::new();
+        msg.as_ref().serialize(&mut Serializer::new(&mut out)).unwrap();
+        // Maps serialize sorted by key
+        assert_eq!(
+            out,
+            "counts {\n  key: 1\n  value: \"a\"\n}\ncounts {\n  key: -2\n  value: \"b\"\n}\n"
+        );
+    }
+}
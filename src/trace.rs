@@ -0,0 +1,110 @@
+//! Optional `tracing` instrumentation.
+//!
+//! With the `tracing` feature enabled these forward to the `tracing` crate;
+//! without it they expand to nothing, so call sites need no `cfg` of their own.
+
+/// Emit a `TRACE` level event.
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::trace!($($arg)*);
+    };
+}
+
+/// Emit a `DEBUG` level event.
+macro_rules! debug_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!($($arg)*);
+    };
+}
+
+/// Enter a `TRACE` level span until the end of the enclosing block.
+macro_rules! enter_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = ::tracing::trace_span!($($arg)*).entered();
+    };
+}
+
+/// Run a future inside a `TRACE` level span. The span is created before the
+/// future, so its fields may borrow what the future later moves.
+macro_rules! instrument {
+    ($future:expr, $($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        let span = ::tracing::trace_span!($($arg)*);
+        let future = $future;
+        #[cfg(feature = "tracing")]
+        let future = ::tracing::Instrument::instrument(future, span);
+        future
+    }};
+}
+
+pub(crate) use {debug_event, enter_span, instrument, trace_event};
+
+#[cfg(all(test, feature = "tracing", feature = "std"))]
+mod tests {
+    use crate::ProtobufMut;
+    use crate::google::protobuf::FileDescriptorProto;
+    use std::sync::Mutex;
+    use tracing::span;
+
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    /// Records span names and event messages.
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    struct MessageVisitor<'a>(&'a mut String);
+
+    impl tracing::field::Visit for MessageVisitor<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn core::fmt::Debug) {
+            if field.name() == "message" {
+                *self.0 = format!("{value:?}");
+            }
+        }
+    }
+
+    impl tracing::Subscriber for &'static Recorder {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            self.0.lock().unwrap().push(span.metadata().name().to_string());
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut message = String::new();
+            event.record(&mut MessageVisitor(&mut message));
+            self.0.lock().unwrap().push(message);
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[test]
+    fn decode_emits_span_and_unknown_field_events() {
+        let recorder: &'static Recorder = Box::leak(Box::default());
+        tracing::subscriber::with_default(recorder, || {
+            let mut arena = crate::arena::Arena::new(&Global);
+            let mut msg = FileDescriptorProto::ProtoType::default();
+            // name: "a", then unknown field 1000 as a varint
+            let bytes = [0x0a, 0x01, b'a', 0xc0, 0x3e, 0x01];
+            assert!(msg.decode_flat::<32>(&mut arena, &bytes));
+        });
+        let recorded = recorder.0.lock().unwrap();
+        assert_eq!(recorded[0], "decode");
+        assert!(recorded.iter().any(|r| r == "skipping unknown field"));
+    }
+}
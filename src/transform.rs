@@ -0,0 +1,252 @@
+//! Re-encoding of wire data without decoding it.
+//!
+//! [`filter_encode`] copies an encoded message while dropping fields. It
+//! walks the wire format guided by the message's table instead of decoding
+//! into an arena, and kept fields are copied byte for byte, so a scrubbing
+//! proxy pays little more than a copy of what it forwards.
+//!
+//! ```
+//! use protocrap::ProtobufRef;
+//! use protocrap::generated_code_only::Protobuf;
+//! use protocrap::google::protobuf::FileDescriptorProto;
+//! use protocrap::transform::{FieldFilter, filter_encode};
+//!
+//! let file = FileDescriptorProto::ProtoType::file_descriptor();
+//! let encoded = file.encode_vec::<32>().unwrap();
+//! let table = FileDescriptorProto::ProtoType::table();
+//!
+//! // Only the file name and the names of its messages
+//! let names = filter_encode(&encoded, table, FieldFilter::Allow(&["name", "message_type.name"])).unwrap();
+//! assert!(names.len() < encoded.len() / 10);
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::Error;
+use crate::google::protobuf::FieldDescriptorProto::{ProtoType as FieldDescriptorProto, Type};
+use crate::tables::Table;
+use crate::wire::{Reader, WireType, Writer};
+
+/// Deepest message nesting [`filter_encode`] follows.
+const MAX_DEPTH: usize = 100;
+
+/// Which fields [`filter_encode`] keeps.
+///
+/// Fields are named by their path of field names from the top-level message,
+/// separated by dots: `"user.email"` is the `email` field of the message in
+/// the `user` field. Paths through repeated message and map fields apply to
+/// every element.
+#[derive(Clone, Copy, Debug)]
+pub enum FieldFilter<'a> {
+    /// Keep the listed fields and drop all others, unknown fields included.
+    /// Message fields on the way to a listed field are kept with only the
+    /// listed fields inside.
+    Allow(&'a [&'a str]),
+    /// Drop the listed fields and keep all others.
+    Drop(&'a [&'a str]),
+}
+
+/// Copy `input`, an encoded message of type `table`, keeping the fields
+/// `filter` lets through.
+///
+/// Tables come from `Protobuf::table()` of generated types or from
+/// [`DescriptorPool::get_table`](crate::descriptor_pool::DescriptorPool::get_table).
+/// Only the wire structure is checked, values of kept fields are copied
+/// without looking at them. Fails with [`Error::InvalidProtobufData`] if
+/// `input` isn't valid wire format, and with [`Error::MessageTreeTooDeep`]
+/// if it nests more than 100 messages deep.
+pub fn filter_encode(input: &[u8], table: &Table, filter: FieldFilter) -> Result<Vec<u8>, Error> {
+    let (allow, paths) = match filter {
+        FieldFilter::Allow(paths) => (true, paths),
+        FieldFilter::Drop(paths) => (false, paths),
+    };
+    let mut out = Vec::with_capacity(input.len());
+    fields(&mut out, table, paths, allow, &mut Reader::new(input), None, 0)?;
+    Ok(out)
+}
+
+/// Copy the fields of a message of type `table` from `reader` to `out`, up to
+/// the end of the input or, for a group, its end tag. `paths` are relative to
+/// the message.
+fn fields(
+    out: &mut Vec<u8>,
+    table: &Table,
+    paths: &[&str],
+    allow: bool,
+    reader: &mut Reader,
+    group: Option<u32>,
+    depth: usize,
+) -> Result<(), Error> {
+    if depth > MAX_DEPTH {
+        return Err(Error::MessageTreeTooDeep);
+    }
+    while !reader.is_empty() {
+        let start = reader.remaining();
+        let (number, wire_type) = reader.read_tag()?;
+        let tag_bytes = &start[..start.len() - reader.remaining().len()];
+        if wire_type == WireType::EndGroup {
+            if group == Some(number) {
+                out.extend_from_slice(tag_bytes);
+                return Ok(());
+            }
+            return Err(Error::InvalidProtobufData);
+        }
+
+        let field = table
+            .descriptor()
+            .field()
+            .iter()
+            .find(|field| field.number() == number as i32);
+        let name = field.map(|field| field.name());
+        let listed = name.is_some_and(|name| paths.contains(&name));
+        let nested: Vec<&str> = match name {
+            Some(name) => paths
+                .iter()
+                .filter_map(|path| path.strip_prefix(name)?.strip_prefix('.'))
+                .collect(),
+            None => Vec::new(),
+        };
+        let child = field.and_then(|field| child_table(table, field));
+        if let Some(child) = child
+            && !listed
+            && !nested.is_empty()
+        {
+            match wire_type {
+                WireType::LengthDelimited => {
+                    let value = reader.read_bytes()?;
+                    let mut inner = Vec::with_capacity(value.len());
+                    fields(&mut inner, child, &nested, allow, &mut Reader::new(value), None, depth + 1)?;
+                    out.extend_from_slice(tag_bytes);
+                    let mut len = [0u8; 10];
+                    let mut writer = Writer::new(&mut len);
+                    writer.write_varint(inner.len() as u64)?;
+                    out.extend_from_slice(writer.written());
+                    out.extend_from_slice(&inner);
+                    continue;
+                }
+                WireType::StartGroup => {
+                    out.extend_from_slice(tag_bytes);
+                    fields(out, child, &nested, allow, reader, Some(number), depth + 1)?;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        skip(reader, number, wire_type, depth)?;
+        if listed == allow {
+            out.extend_from_slice(&start[..start.len() - reader.remaining().len()]);
+        }
+    }
+    if group.is_some() {
+        return Err(Error::InvalidProtobufData);
+    }
+    Ok(())
+}
+
+/// The table of the messages in `field`, if it is a message field.
+fn child_table<'t>(table: &'t Table, field: &FieldDescriptorProto) -> Option<&'t Table> {
+    if !matches!(field.r#type(), Some(Type::TYPE_MESSAGE | Type::TYPE_GROUP)) {
+        return None;
+    }
+    let entry = table.field_entry(field.number() as u32)?;
+    Some(table.aux_entry_decode(entry).1)
+}
+
+/// Skip the value of field `number` with `wire_type`, whose tag was just read.
+fn skip(reader: &mut Reader, number: u32, wire_type: WireType, depth: usize) -> Result<(), Error> {
+    if wire_type != WireType::StartGroup {
+        return reader.skip(number, wire_type);
+    }
+    if depth > MAX_DEPTH {
+        return Err(Error::MessageTreeTooDeep);
+    }
+    loop {
+        match reader.read_tag()? {
+            (end, WireType::EndGroup) if end == number => return Ok(()),
+            (number, wire_type) => skip(reader, number, wire_type, depth + 1)?,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::generated_code_only::Protobuf;
+    use crate::google::protobuf::FileDescriptorProto;
+    use crate::{ProtobufMut, ProtobufRef};
+
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    #[test]
+    fn allow_and_drop_lists() {
+        let table = FileDescriptorProto::ProtoType::table();
+        let mut arena = crate::arena::Arena::new(&Global);
+        let file = |drop_package: bool, drop_field_names: bool, arena: &mut crate::arena::Arena| {
+            let mut file = FileDescriptorProto::ProtoType::default();
+            file.set_name("a.proto", arena).unwrap();
+            if !drop_package {
+                file.set_package("pkg", arena).unwrap();
+            }
+            let message = file.add_message_type(arena).unwrap();
+            message.set_name("M", arena).unwrap();
+            let field = message.add_field(arena).unwrap();
+            if !drop_field_names {
+                field.set_name("f", arena).unwrap();
+            }
+            field.set_number(1);
+            file.encode_vec::<32>().unwrap()
+        };
+        let encoded = file(false, false, &mut arena);
+
+        let dropped = filter_encode(
+            &encoded,
+            table,
+            FieldFilter::Drop(&["package", "message_type.field.name"]),
+        )
+        .unwrap();
+        assert_eq!(dropped, file(true, true, &mut arena));
+
+        // Only the names along the path survive, the field becomes empty
+        let allowed = filter_encode(
+            &encoded,
+            table,
+            FieldFilter::Allow(&["name", "message_type.name", "message_type.field.bogus"]),
+        )
+        .unwrap();
+        let mut expected = FileDescriptorProto::ProtoType::default();
+        expected.set_name("a.proto", &mut arena).unwrap();
+        let message = expected.add_message_type(&mut arena).unwrap();
+        message.set_name("M", &mut arena).unwrap();
+        message.add_field(&mut arena).unwrap();
+        assert_eq!(allowed, expected.encode_vec::<32>().unwrap());
+
+        // Unknown fields, here a group 1000 { 1: 1 }, only pass a drop list
+        let mut with_unknown = encoded.clone();
+        with_unknown.extend_from_slice(&[0xc3, 0x3e, 0x08, 0x01, 0xc4, 0x3e]);
+        assert_eq!(
+            filter_encode(&with_unknown, table, FieldFilter::Drop(&[])).unwrap(),
+            with_unknown
+        );
+        assert_eq!(
+            filter_encode(&with_unknown, table, FieldFilter::Allow(&["name", "package", "message_type"]))
+                .unwrap(),
+            encoded
+        );
+
+        // The filtered message decodes like any other
+        let mut decoded = FileDescriptorProto::ProtoType::default();
+        assert!(decoded.decode_flat::<32>(&mut arena, &allowed));
+        assert_eq!(decoded.message_type()[0].name(), "M");
+
+        for bad in [&encoded[..encoded.len() - 1], &[0xc3, 0x3e, 0x08, 0x01][..], &[0x0c][..]] {
+            assert!(matches!(
+                filter_encode(bad, table, FieldFilter::Drop(&[])),
+                Err(Error::InvalidProtobufData)
+            ));
+        }
+    }
+}
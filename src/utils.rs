@@ -13,12 +13,46 @@ pub const fn likely(b: bool) -> bool {
     b
 }
 
+/// Explicit stack of the decoder and encoder, growing downwards through
+/// `entries`. Internal to the crate: entries are never dropped, a full stack
+/// makes `push` fail rather than grow, and `pop` on an empty one fails.
+/// [`StackWithStorage`] provides the inline storage.
 #[repr(C)]
 pub(crate) struct Stack<T> {
-    pub sp: usize,
+    sp: usize,
+    spill: Spill<T>,
     entries: [MaybeUninit<T>],
 }
 
+/// Arena segments that the entries of a full [`Stack`] are moved to, letting
+/// it grow beyond its inline capacity.
+struct Spill<T> {
+    /// Maximum number of live entries. Below the inline capacity the stack
+    /// doesn't grow.
+    max_depth: usize,
+    /// Segments holding spilled entries, most recently spilled first.
+    full: *mut Segment<T>,
+    /// Segments that were restored and can be spilled into again.
+    free: *mut Segment<T>,
+    /// Number of segments in `full`.
+    len: usize,
+}
+
+impl<T> Spill<T> {
+    const EMPTY: Self = Self {
+        max_depth: 0,
+        full: core::ptr::null_mut(),
+        free: core::ptr::null_mut(),
+        len: 0,
+    };
+}
+
+/// A copy of all inline entries of a stack, in a list of segments.
+struct Segment<T> {
+    next: *mut Segment<T>,
+    entries: *mut MaybeUninit<T>,
+}
+
 impl<T> Stack<T> {
     #[must_use]
     pub(crate) fn push(&mut self, entry: T) -> Option<&mut T> {
@@ -33,11 +67,28 @@ impl<T> Stack<T> {
         Some(slot.write(entry))
     }
 
+    /// Like [`push`](Self::push), but when the inline entries are used up,
+    /// moves them to `arena` to make room, up to the depth set with
+    /// [`set_max_depth`](Self::set_max_depth).
+    #[must_use]
+    #[inline(always)]
+    pub(crate) fn push_spilling(
+        &mut self,
+        entry: T,
+        arena: &mut crate::arena::Arena,
+    ) -> Option<&mut T> {
+        if *core::hint::black_box(&self.sp) == 0 {
+            self.spill(arena)?;
+        }
+        self.push(entry)
+    }
+
     #[must_use]
     pub(crate) fn pop(&mut self) -> Option<T> {
-        let sp = *core::hint::black_box(&self.sp);
+        let mut sp = *core::hint::black_box(&self.sp);
         if sp == self.entries.len() {
-            return None;
+            self.restore()?;
+            sp = 0;
         }
         self.sp = sp + 1;
         let x = unsafe { self.entries[sp].assume_init_read() };
@@ -45,21 +96,115 @@ impl<T> Stack<T> {
         Some(x)
     }
 
+    /// Let [`push_spilling`](Self::push_spilling) grow the stack to
+    /// `max_depth` entries.
+    pub(crate) fn set_max_depth(&mut self, max_depth: usize) {
+        self.spill.max_depth = max_depth;
+    }
+
+    /// Move all entries to a segment, emptying the inline storage.
+    #[cold]
+    #[inline(never)]
+    fn spill(&mut self, arena: &mut crate::arena::Arena) -> Option<()> {
+        let capacity = self.entries.len();
+        let depth = (self.spill.len + 1).checked_mul(capacity)?;
+        if capacity == 0 || depth >= self.spill.max_depth {
+            return None;
+        }
+        let segment = if self.spill.free.is_null() {
+            let entries = arena.alloc_slice::<MaybeUninit<T>>(capacity).ok()?;
+            let segment = arena.alloc::<Segment<T>>().ok()?;
+            unsafe {
+                segment.write(Segment {
+                    next: core::ptr::null_mut(),
+                    entries: entries.cast(),
+                })
+            };
+            segment
+        } else {
+            let segment = self.spill.free;
+            self.spill.free = unsafe { (*segment).next };
+            segment
+        };
+        unsafe {
+            let segment = &mut *segment;
+            segment
+                .entries
+                .copy_from_nonoverlapping(self.entries.as_ptr(), capacity);
+            segment.next = self.spill.full;
+        }
+        self.spill.full = segment;
+        self.spill.len += 1;
+        self.sp = capacity;
+        Some(())
+    }
+
+    /// Move the entries of the most recent segment back, filling the inline
+    /// storage.
+    #[cold]
+    #[inline(never)]
+    fn restore(&mut self) -> Option<()> {
+        let segment = self.spill.full;
+        if segment.is_null() {
+            return None;
+        }
+        unsafe {
+            let segment = &mut *segment;
+            self.entries
+                .as_mut_ptr()
+                .copy_from_nonoverlapping(segment.entries, self.entries.len());
+            self.spill.full = segment.next;
+            segment.next = self.spill.free;
+        }
+        self.spill.free = segment;
+        self.spill.len -= 1;
+        self.sp = 0;
+        Some(())
+    }
+
+    /// The live entries, innermost first.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        let capacity = self.entries.len();
+        let segments = core::iter::successors(
+            (!self.spill.full.is_null()).then_some(self.spill.full),
+            |&segment| {
+                let next = unsafe { (*segment).next };
+                (!next.is_null()).then_some(next)
+            },
+        );
+        self.entries[self.sp..]
+            .iter()
+            .chain(segments.flat_map(move |segment| unsafe {
+                core::slice::from_raw_parts((*segment).entries, capacity)
+            }))
+            .map(|entry| unsafe { entry.assume_init_ref() })
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
-        self.sp == self.entries.len()
+        self.sp == self.entries.len() && self.spill.full.is_null()
+    }
+
+    /// Number of live entries.
+    pub(crate) fn depth(&self) -> usize {
+        let capacity = self.entries.len();
+        capacity - self.sp + self.spill.len * capacity
     }
 }
 
 #[repr(C)]
 pub(crate) struct StackWithStorage<T, const N: usize> {
     sp: usize,
+    spill: Spill<T>,
     entries: [MaybeUninit<T>; N],
 }
 
 impl<T, const N: usize> Default for StackWithStorage<T, N> {
     fn default() -> Self {
+        // Entries left on the stack are forgotten, not dropped
+        const { assert!(!core::mem::needs_drop::<T>()) };
         Self {
             sp: N,
+            spill: Spill::EMPTY,
             entries: [const { MaybeUninit::uninit() }; N],
         }
     }
@@ -99,29 +244,37 @@ impl<T> UpdateByValue for T {
     }
 }
 
-pub struct Ptr<T: ?Sized>(*const T);
+/// A reference with its lifetime erased, for references parked on a
+/// [`Stack`]. The crate only turns it back into a reference while the
+/// referent is alive.
+pub(crate) struct Ptr<T: ?Sized>(*const T);
 
 impl<T: ?Sized> Ptr<T> {
-    pub fn new(r: &T) -> Self {
+    pub(crate) fn new(r: &T) -> Self {
         Ptr(r)
     }
 
-    // Safe! Invariant enforced by constructor
-    pub fn as_ref<'a>(&self) -> &'a T {
+    pub(crate) fn as_ref<'a>(&self) -> &'a T {
         unsafe { &*self.0 }
     }
 }
 
-pub struct PtrMut<T: ?Sized>(*mut T);
+/// Mutable counterpart of [`Ptr`].
+pub(crate) struct PtrMut<T: ?Sized>(*mut T);
 
 impl<T: ?Sized> PtrMut<T> {
-    pub fn new(r: &mut T) -> Self {
+    pub(crate) fn new(r: &mut T) -> Self {
         PtrMut(r)
     }
 
-    pub fn as_mut<'a>(&mut self) -> &'a mut T {
+    pub(crate) fn as_mut<'a>(&mut self) -> &'a mut T {
         unsafe { &mut *self.0 }
     }
+
+    #[cfg(feature = "alloc")]
+    pub(crate) fn as_ref<'a>(&self) -> &'a T {
+        unsafe { &*self.0 }
+    }
 }
 
 pub(crate) fn as_bytes<T>(slice: &[T]) -> &[u8] {
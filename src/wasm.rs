@@ -0,0 +1,117 @@
+//! JavaScript bindings through `wasm-bindgen`.
+//!
+//! Exposes a `DescriptorPool` class converting between protobuf binary and
+//! proto JSON for any message type of a `FileDescriptorSet`, so browser
+//! tooling can use the same tables as the Rust code.
+//!
+//! ```js
+//! const pool = new DescriptorPool(fileDescriptorSetBytes);
+//! const json = pool.decodeToJson("my.package.MyMessage", bytes);
+//! const encoded = pool.encodeFromJson("my.package.MyMessage", json);
+//! ```
+//!
+//! [`decode_to_json`] and [`encode_from_json`] do the conversions on the Rust
+//! side and work on any target.
+
+use wasm_bindgen::prelude::*;
+
+use crate::descriptor_pool::DescriptorPool;
+use crate::Error;
+
+/// Decode `bytes` as a `type_name` message and serialize it as proto JSON.
+///
+/// Same as [`DescriptorPool::binary_to_json`].
+pub fn decode_to_json(
+    pool: &DescriptorPool,
+    type_name: &str,
+    bytes: &[u8],
+) -> Result<String, Error<serde_json::Error>> {
+    pool.binary_to_json(type_name, bytes)
+}
+
+/// Parse `json` as a `type_name` message and encode it to protobuf binary.
+///
+/// Same as [`DescriptorPool::json_to_binary`].
+pub fn encode_from_json(
+    pool: &DescriptorPool,
+    type_name: &str,
+    json: &str,
+) -> Result<Vec<u8>, Error<serde_json::Error>> {
+    pool.json_to_binary(type_name, json)
+}
+
+fn js_error(error: Error<serde_json::Error>) -> JsError {
+    match error {
+        Error::Io(e) => JsError::new(&e.to_string()),
+        e => JsError::new(&e.to_string()),
+    }
+}
+
+/// A descriptor pool for converting messages between binary and JSON.
+#[wasm_bindgen(js_name = DescriptorPool)]
+pub struct JsDescriptorPool {
+    pool: DescriptorPool<'static>,
+}
+
+#[wasm_bindgen(js_class = DescriptorPool)]
+impl JsDescriptorPool {
+    /// Load the message types of an encoded `FileDescriptorSet`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(file_descriptor_set: &[u8]) -> Result<JsDescriptorPool, JsError> {
+        let pool = DescriptorPool::from_file_descriptor_set_bytes(file_descriptor_set)
+            .map_err(|e| JsError::new(&format!("invalid FileDescriptorSet: {e}")))?;
+        Ok(JsDescriptorPool { pool })
+    }
+
+    /// See [`decode_to_json`].
+    #[wasm_bindgen(js_name = decodeToJson)]
+    pub fn decode_to_json(&self, type_name: &str, bytes: &[u8]) -> Result<String, JsError> {
+        decode_to_json(&self.pool, type_name, bytes).map_err(js_error)
+    }
+
+    /// See [`encode_from_json`].
+    #[wasm_bindgen(js_name = encodeFromJson)]
+    pub fn encode_from_json(&self, type_name: &str, json: &str) -> Result<Vec<u8>, JsError> {
+        encode_from_json(&self.pool, type_name, json).map_err(js_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::Arena;
+    use crate::google::protobuf::FileDescriptorProto;
+    use crate::ProtobufRef;
+
+    #[cfg(not(feature = "nightly"))]
+    use allocator_api2::alloc::Global;
+    #[cfg(feature = "nightly")]
+    use std::alloc::Global;
+
+    #[test]
+    fn json_roundtrip() {
+        let mut pool = DescriptorPool::new(&Global);
+        pool.add_file(FileDescriptorProto::ProtoType::file_descriptor())
+            .unwrap();
+
+        let mut arena = Arena::new(&Global);
+        let mut file = FileDescriptorProto::ProtoType::default();
+        file.set_name("a.proto", &mut arena).unwrap();
+        file.set_package("pkg", &mut arena).unwrap();
+        let bytes = file.encode_vec::<32>().unwrap();
+
+        let json = decode_to_json(&pool, ".google.protobuf.FileDescriptorProto", &bytes).unwrap();
+        assert_eq!(json, r#"{"name":"a.proto","package":"pkg"}"#);
+        let encoded = encode_from_json(&pool, "google.protobuf.FileDescriptorProto", &json).unwrap();
+        assert_eq!(encoded, bytes);
+
+        assert!(matches!(
+            decode_to_json(&pool, "no.Such", &bytes),
+            Err(Error::MessageNotFound)
+        ));
+        assert!(matches!(
+            encode_from_json(&pool, "google.protobuf.FileDescriptorProto", "{"),
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,357 @@
+//! Well-known protobuf types.
+//!
+//! The `google/protobuf/*.proto` well-known types are compiled into protocrap
+//! alongside `descriptor.proto`, so they can be used in APIs without running
+//! codegen on the google protos. This module re-exports their message types
+//! under short names and provides conversions to the standard library types,
+//! and to the `time` and `chrono` crates when those features are enabled.
+//!
+//! # Example
+//!
+//! ```
+//! use protocrap::well_known::{Duration, Timestamp};
+//!
+//! let timeout = Duration::try_from(core::time::Duration::from_millis(1500)).unwrap();
+//! assert_eq!(timeout.seconds(), 1);
+//! assert_eq!(timeout.nanos(), 500_000_000);
+//!
+//! let now = Timestamp::try_from(std::time::SystemTime::now()).unwrap();
+//! assert!(now.seconds() > 0);
+//! ```
+
+use crate::google::protobuf;
+
+pub type Any = protobuf::Any::ProtoType;
+pub type Duration = protobuf::Duration::ProtoType;
+pub type Empty = protobuf::Empty::ProtoType;
+pub type FieldMask = protobuf::FieldMask::ProtoType;
+pub type ListValue = protobuf::ListValue::ProtoType;
+pub type NullValue = protobuf::NullValue;
+pub type Struct = protobuf::Struct::ProtoType;
+pub type Timestamp = protobuf::Timestamp::ProtoType;
+pub type Value = protobuf::Value::ProtoType;
+
+const NANOS_PER_SECOND: i32 = 1_000_000_000;
+
+/// Largest `seconds` magnitude allowed in a `Duration` (about 10,000 years).
+const MAX_DURATION_SECONDS: i64 = 315_576_000_000;
+
+/// `Timestamp` range: 0001-01-01T00:00:00Z up to 9999-12-31T23:59:59Z.
+const MIN_TIMESTAMP_SECONDS: i64 = -62_135_596_800;
+const MAX_TIMESTAMP_SECONDS: i64 = 253_402_300_799;
+
+impl Duration {
+    /// Creates a duration from seconds and nanos, which must have the same sign.
+    pub fn new(seconds: i64, nanos: i32) -> Self {
+        let mut duration = Self::default();
+        duration.set_seconds(seconds);
+        duration.set_nanos(nanos);
+        duration
+    }
+
+    /// Returns true if the duration is within the range allowed by `duration.proto`.
+    pub fn is_valid(&self) -> bool {
+        let (seconds, nanos) = (self.seconds(), self.nanos());
+        seconds.abs() <= MAX_DURATION_SECONDS
+            && nanos.abs() < NANOS_PER_SECOND
+            && (seconds == 0 || nanos == 0 || (seconds < 0) == (nanos < 0))
+    }
+}
+
+impl TryFrom<core::time::Duration> for Duration {
+    type Error = crate::Error;
+
+    fn try_from(value: core::time::Duration) -> Result<Self, Self::Error> {
+        let seconds =
+            i64::try_from(value.as_secs()).map_err(|_| crate::Error::InvalidProtobufData)?;
+        if seconds > MAX_DURATION_SECONDS {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        Ok(Self::new(seconds, value.subsec_nanos() as i32))
+    }
+}
+
+impl TryFrom<&Duration> for core::time::Duration {
+    type Error = crate::Error;
+
+    /// Fails for negative or out of range durations.
+    fn try_from(value: &Duration) -> Result<Self, Self::Error> {
+        if !value.is_valid() || value.seconds() < 0 || value.nanos() < 0 {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        Ok(core::time::Duration::new(
+            value.seconds() as u64,
+            value.nanos() as u32,
+        ))
+    }
+}
+
+impl Timestamp {
+    /// Creates a timestamp from seconds since the Unix epoch and non-negative nanos.
+    pub fn new(seconds: i64, nanos: i32) -> Self {
+        let mut timestamp = Self::default();
+        timestamp.set_seconds(seconds);
+        timestamp.set_nanos(nanos);
+        timestamp
+    }
+
+    /// Returns true if the timestamp is within the range allowed by `timestamp.proto`.
+    pub fn is_valid(&self) -> bool {
+        (MIN_TIMESTAMP_SECONDS..=MAX_TIMESTAMP_SECONDS).contains(&self.seconds())
+            && (0..NANOS_PER_SECOND).contains(&self.nanos())
+    }
+
+    /// Returns the current time.
+    ///
+    /// Panics if the system clock is outside the range of `timestamp.proto`.
+    #[cfg(feature = "std")]
+    pub fn now() -> Self {
+        Self::try_from(std::time::SystemTime::now()).expect("system clock out of Timestamp range")
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<std::time::SystemTime> for Timestamp {
+    type Error = crate::Error;
+
+    /// Fails for times outside the range of `timestamp.proto`.
+    fn try_from(value: std::time::SystemTime) -> Result<Self, Self::Error> {
+        let out_of_range = |_| crate::Error::InvalidProtobufData;
+        let timestamp = match value.duration_since(std::time::UNIX_EPOCH) {
+            Ok(since) => Self::new(
+                i64::try_from(since.as_secs()).map_err(out_of_range)?,
+                since.subsec_nanos() as i32,
+            ),
+            Err(err) => {
+                // Before the epoch: nanos still count forward from `seconds`
+                let before = err.duration();
+                let mut seconds = -i64::try_from(before.as_secs()).map_err(out_of_range)?;
+                let mut nanos = before.subsec_nanos() as i32;
+                if nanos > 0 {
+                    seconds -= 1;
+                    nanos = NANOS_PER_SECOND - nanos;
+                }
+                Self::new(seconds, nanos)
+            }
+        };
+        if !timestamp.is_valid() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        Ok(timestamp)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<&Timestamp> for std::time::SystemTime {
+    type Error = crate::Error;
+
+    /// Fails if the timestamp is invalid or not representable.
+    fn try_from(value: &Timestamp) -> Result<Self, Self::Error> {
+        if !value.is_valid() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        let (seconds, nanos) = (value.seconds(), value.nanos());
+        let epoch = std::time::UNIX_EPOCH;
+        let time = if seconds >= 0 {
+            epoch.checked_add(core::time::Duration::from_secs(seconds as u64))
+        } else {
+            epoch.checked_sub(core::time::Duration::from_secs(seconds.unsigned_abs()))
+        };
+        time.and_then(|t| t.checked_add(core::time::Duration::from_nanos(nanos as u64)))
+            .ok_or(crate::Error::InvalidProtobufData)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<time::OffsetDateTime> for Timestamp {
+    type Error = crate::Error;
+
+    fn try_from(value: time::OffsetDateTime) -> Result<Self, Self::Error> {
+        let timestamp = Self::new(value.unix_timestamp(), value.nanosecond() as i32);
+        if !timestamp.is_valid() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        Ok(timestamp)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<&Timestamp> for time::OffsetDateTime {
+    type Error = crate::Error;
+
+    fn try_from(value: &Timestamp) -> Result<Self, Self::Error> {
+        if !value.is_valid() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        let nanos = value.seconds() as i128 * NANOS_PER_SECOND as i128 + value.nanos() as i128;
+        time::OffsetDateTime::from_unix_timestamp_nanos(nanos)
+            .map_err(|_| crate::Error::InvalidProtobufData)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<time::Duration> for Duration {
+    type Error = crate::Error;
+
+    fn try_from(value: time::Duration) -> Result<Self, Self::Error> {
+        // `time::Duration` keeps seconds and nanos with the same sign, as protobuf does
+        let duration = Self::new(value.whole_seconds(), value.subsec_nanoseconds());
+        if !duration.is_valid() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        Ok(duration)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<&Duration> for time::Duration {
+    type Error = crate::Error;
+
+    fn try_from(value: &Duration) -> Result<Self, Self::Error> {
+        if !value.is_valid() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        Ok(time::Duration::new(value.seconds(), value.nanos()))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<chrono::DateTime<chrono::Utc>> for Timestamp {
+    type Error = crate::Error;
+
+    fn try_from(value: chrono::DateTime<chrono::Utc>) -> Result<Self, Self::Error> {
+        // chrono represents a leap second as nanos >= 1s; protobuf smears them
+        let nanos = value
+            .timestamp_subsec_nanos()
+            .min(NANOS_PER_SECOND as u32 - 1);
+        let timestamp = Self::new(value.timestamp(), nanos as i32);
+        if !timestamp.is_valid() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        Ok(timestamp)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<&Timestamp> for chrono::DateTime<chrono::Utc> {
+    type Error = crate::Error;
+
+    fn try_from(value: &Timestamp) -> Result<Self, Self::Error> {
+        if !value.is_valid() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        chrono::DateTime::from_timestamp(value.seconds(), value.nanos() as u32)
+            .ok_or(crate::Error::InvalidProtobufData)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<chrono::TimeDelta> for Duration {
+    type Error = crate::Error;
+
+    fn try_from(value: chrono::TimeDelta) -> Result<Self, Self::Error> {
+        let duration = Self::new(value.num_seconds(), value.subsec_nanos());
+        if !duration.is_valid() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        Ok(duration)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<&Duration> for chrono::TimeDelta {
+    type Error = crate::Error;
+
+    fn try_from(value: &Duration) -> Result<Self, Self::Error> {
+        if !value.is_valid() {
+            return Err(crate::Error::InvalidProtobufData);
+        }
+        chrono::TimeDelta::try_seconds(value.seconds())
+            .and_then(|seconds| {
+                seconds.checked_add(&chrono::TimeDelta::nanoseconds(value.nanos() as i64))
+            })
+            .ok_or(crate::Error::InvalidProtobufData)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn timestamp_system_time_roundtrip() {
+        for time in [
+            UNIX_EPOCH,
+            UNIX_EPOCH + core::time::Duration::new(1_700_000_000, 123_456_789),
+            UNIX_EPOCH - core::time::Duration::new(10, 250_000_000),
+        ] {
+            let timestamp = Timestamp::try_from(time).unwrap();
+            assert!((0..NANOS_PER_SECOND).contains(&timestamp.nanos()));
+            assert_eq!(SystemTime::try_from(&timestamp).unwrap(), time);
+        }
+
+        let before_epoch =
+            Timestamp::try_from(UNIX_EPOCH - core::time::Duration::new(10, 250_000_000)).unwrap();
+        assert_eq!(before_epoch.seconds(), -11);
+        assert_eq!(before_epoch.nanos(), 750_000_000);
+
+        // Past 9999-12-31T23:59:59Z
+        let too_late =
+            UNIX_EPOCH + core::time::Duration::from_secs(MAX_TIMESTAMP_SECONDS as u64 + 1);
+        assert!(Timestamp::try_from(too_late).is_err());
+    }
+
+    #[test]
+    fn duration_conversions() {
+        let duration = Duration::try_from(core::time::Duration::new(5, 7)).unwrap();
+        assert_eq!((duration.seconds(), duration.nanos()), (5, 7));
+        assert_eq!(
+            core::time::Duration::try_from(&duration).unwrap(),
+            core::time::Duration::new(5, 7)
+        );
+
+        assert!(core::time::Duration::try_from(&Duration::new(-1, 0)).is_err());
+        assert!(!Duration::new(1, -1).is_valid());
+        assert!(Duration::try_from(core::time::Duration::from_secs(u64::MAX)).is_err());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_crate_conversions() {
+        let date_time = time::macros::datetime!(2024-02-29 12:34:56.5 UTC);
+        let timestamp = Timestamp::try_from(date_time).unwrap();
+        assert_eq!(timestamp.seconds(), date_time.unix_timestamp());
+        assert_eq!(timestamp.nanos(), 500_000_000);
+        assert_eq!(
+            time::OffsetDateTime::try_from(&timestamp).unwrap(),
+            date_time
+        );
+        assert!(time::OffsetDateTime::try_from(&Timestamp::new(0, -1)).is_err());
+
+        let duration = Duration::try_from(time::Duration::new(-3, -250)).unwrap();
+        assert_eq!((duration.seconds(), duration.nanos()), (-3, -250));
+        assert_eq!(
+            time::Duration::try_from(&duration).unwrap(),
+            time::Duration::new(-3, -250)
+        );
+        assert!(Duration::try_from(time::Duration::MAX).is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_conversions() {
+        let date_time = chrono::DateTime::from_timestamp(1_700_000_000, 42).unwrap();
+        let timestamp = Timestamp::try_from(date_time).unwrap();
+        assert_eq!(
+            (timestamp.seconds(), timestamp.nanos()),
+            (1_700_000_000, 42)
+        );
+        assert_eq!(chrono::DateTime::try_from(&timestamp).unwrap(), date_time);
+
+        let delta = chrono::TimeDelta::milliseconds(-1500);
+        let duration = Duration::try_from(delta).unwrap();
+        assert_eq!((duration.seconds(), duration.nanos()), (-1, -500_000_000));
+        assert_eq!(chrono::TimeDelta::try_from(&duration).unwrap(), delta);
+    }
+}
@@ -1,18 +1,302 @@
+//! Low-level protobuf wire format helpers.
+//!
+//! Tags, zigzag, varints and the bounds-checked [`Reader`] and [`Writer`], for
+//! code that needs a little wire format handling without a schema, like
+//! peeking at one field of a large message or framing records by hand. These
+//! follow semver like the rest of the public API. [`FieldKind`] is how tables
+//! describe fields and gains variants when the table format changes.
+//!
+//! ```
+//! use protocrap::wire::{Reader, WireType, Writer};
+//!
+//! let mut buf = [0u8; 16];
+//! let mut writer = Writer::new(&mut buf);
+//! writer.write_tag(1, WireType::LengthDelimited).unwrap();
+//! writer.write_bytes(b"a.proto").unwrap();
+//! writer.write_tag(7, WireType::Varint).unwrap();
+//! writer.write_varint(150).unwrap();
+//!
+//! let mut reader = Reader::new(writer.written());
+//! assert_eq!(reader.read_tag().unwrap(), (1, WireType::LengthDelimited));
+//! assert_eq!(reader.read_bytes().unwrap(), b"a.proto");
+//! let (number, wire_type) = reader.read_tag().unwrap();
+//! reader.skip(number, wire_type).unwrap();
+//! assert!(reader.is_empty());
+//! ```
+
 use core::{
     ops::{Add, AddAssign, Index, IndexMut, Sub},
     ptr::NonNull,
 };
 
+use crate::Error;
+
 pub(crate) const SLOP_SIZE: usize = 16;
 
-pub(crate) fn zigzag_decode(n: u64) -> i64 {
+/// Largest field number a tag can carry.
+pub const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+
+/// Groups [`Reader::skip`] descends into before rejecting the input.
+const MAX_GROUP_DEPTH: u32 = 100;
+
+/// How a field value is encoded, the low 3 bits of its tag.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WireType {
+    Varint = 0,
+    Fixed64 = 1,
+    LengthDelimited = 2,
+    StartGroup = 3,
+    EndGroup = 4,
+    Fixed32 = 5,
+}
+
+impl WireType {
+    /// The wire type numbered `value`, None for the unused 6 and 7.
+    pub const fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(WireType::Varint),
+            1 => Some(WireType::Fixed64),
+            2 => Some(WireType::LengthDelimited),
+            3 => Some(WireType::StartGroup),
+            4 => Some(WireType::EndGroup),
+            5 => Some(WireType::Fixed32),
+            _ => None,
+        }
+    }
+}
+
+/// The tag of field `field_number` holding a value encoded as `wire_type`.
+pub const fn make_tag(field_number: u32, wire_type: WireType) -> u32 {
+    field_number << 3 | wire_type as u32
+}
+
+/// Field number and wire type of `tag`, None if either is invalid.
+pub const fn split_tag(tag: u32) -> Option<(u32, WireType)> {
+    let field_number = tag >> 3;
+    if field_number == 0 {
+        return None;
+    }
+    match WireType::from_u32(tag & 7) {
+        Some(wire_type) => Some((field_number, wire_type)),
+        None => None,
+    }
+}
+
+/// Decode a `sint32` or `sint64` value from its zigzag encoding.
+pub const fn zigzag_decode(n: u64) -> i64 {
     ((n >> 1) as i64) ^ (-((n & 1) as i64))
 }
 
-pub(crate) fn zigzag_encode(n: i64) -> u64 {
+/// Zigzag encode a `sint32` or `sint64` value, so small negative numbers
+/// make short varints.
+pub const fn zigzag_encode(n: i64) -> u64 {
     ((n as u64) << 1) ^ ((n >> 63) as u64)
 }
 
+/// Number of bytes in the varint encoding of `n`.
+pub const fn varint_len(n: u64) -> usize {
+    let log2 = (n | 1).ilog2();
+    ((log2 * 9 + 64 + 9) / 64) as usize
+}
+
+/// Reads wire format values from a byte slice, failing with
+/// [`Error::InvalidProtobufData`] instead of reading past its end.
+#[derive(Clone, Debug)]
+pub struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub const fn new(buf: &'a [u8]) -> Self {
+        Reader { buf }
+    }
+
+    /// Whether all input was read.
+    pub const fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// The input that wasn't read yet.
+    pub const fn remaining(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    /// Read a varint of up to 10 bytes. Like protoc, bits beyond 64 are
+    /// dropped.
+    pub fn read_varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0;
+        for (i, &byte) in self.buf.iter().enumerate().take(10) {
+            value |= u64::from(byte & 0x7f) << (7 * i);
+            if byte < 0x80 {
+                self.buf = &self.buf[i + 1..];
+                return Ok(value);
+            }
+        }
+        Err(Error::InvalidProtobufData)
+    }
+
+    /// Read a tag, split into field number and wire type.
+    pub fn read_tag(&mut self) -> Result<(u32, WireType), Error> {
+        let tag = u32::try_from(self.read_varint()?).map_err(|_| Error::InvalidProtobufData)?;
+        split_tag(tag).ok_or(Error::InvalidProtobufData)
+    }
+
+    /// Read a little-endian `fixed32`, `sfixed32` or `float` value.
+    pub fn read_fixed32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    /// Read a little-endian `fixed64`, `sfixed64` or `double` value.
+    pub fn read_fixed64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    /// Read a length-delimited value: bytes, a string, a sub-message or a
+    /// packed run.
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = usize::try_from(self.read_varint()?).map_err(|_| Error::InvalidProtobufData)?;
+        self.read_slice(len)
+    }
+
+    /// Skip the value of field `field_number` encoded as `wire_type`, after
+    /// its tag. Groups are skipped up to and including their end tag.
+    pub fn skip(&mut self, field_number: u32, wire_type: WireType) -> Result<(), Error> {
+        self.skip_nested(field_number, wire_type, MAX_GROUP_DEPTH)
+    }
+
+    fn skip_nested(&mut self, field_number: u32, wire_type: WireType, depth: u32) -> Result<(), Error> {
+        match wire_type {
+            WireType::Varint => self.read_varint().map(drop),
+            WireType::Fixed64 => self.read_slice(8).map(drop),
+            WireType::LengthDelimited => self.read_bytes().map(drop),
+            WireType::Fixed32 => self.read_slice(4).map(drop),
+            WireType::EndGroup => Err(Error::InvalidProtobufData),
+            WireType::StartGroup => {
+                let depth = depth.checked_sub(1).ok_or(Error::InvalidProtobufData)?;
+                loop {
+                    match self.read_tag()? {
+                        (number, WireType::EndGroup) if number == field_number => return Ok(()),
+                        (number, wire_type) => self.skip_nested(number, wire_type, depth)?,
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.buf.len() {
+            return Err(Error::InvalidProtobufData);
+        }
+        let (slice, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(slice)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.read_slice(N)?.try_into().unwrap())
+    }
+}
+
+/// Writes wire format values into a byte slice, failing with
+/// [`Error::BufferTooSmall`] when they don't fit.
+#[derive(Debug)]
+pub struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub const fn new(buf: &'a mut [u8]) -> Self {
+        Writer { buf, len: 0 }
+    }
+
+    /// The bytes written so far.
+    pub fn written(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Number of bytes written so far.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether nothing was written yet.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn write_varint(&mut self, mut n: u64) -> Result<(), Error> {
+        let mut bytes = [0u8; 10];
+        let len = varint_len(n);
+        for byte in &mut bytes[..len - 1] {
+            *byte = n as u8 | 0x80;
+            n >>= 7;
+        }
+        bytes[len - 1] = n as u8;
+        self.write_raw(&bytes[..len])
+    }
+
+    /// Write the tag of field `field_number` holding a value encoded as
+    /// `wire_type`. Field numbers above [`MAX_FIELD_NUMBER`] are rejected
+    /// with [`Error::InvalidProtobufData`].
+    pub fn write_tag(&mut self, field_number: u32, wire_type: WireType) -> Result<(), Error> {
+        if field_number == 0 || field_number > MAX_FIELD_NUMBER {
+            return Err(Error::InvalidProtobufData);
+        }
+        self.write_varint(u64::from(make_tag(field_number, wire_type)))
+    }
+
+    pub fn write_fixed32(&mut self, value: u32) -> Result<(), Error> {
+        self.write_raw(&value.to_le_bytes())
+    }
+
+    pub fn write_fixed64(&mut self, value: u64) -> Result<(), Error> {
+        self.write_raw(&value.to_le_bytes())
+    }
+
+    /// Write a length-delimited value, its length followed by `bytes`.
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if varint_len(bytes.len() as u64) + bytes.len() > self.buf.len() - self.len {
+            return Err(Error::BufferTooSmall);
+        }
+        self.write_varint(bytes.len() as u64)?;
+        self.write_raw(bytes)
+    }
+
+    /// Write `bytes` as they are, e.g. an encoded sub-message after its
+    /// length.
+    pub fn write_raw(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let dest = self
+            .buf
+            .get_mut(self.len..self.len + bytes.len())
+            .ok_or(Error::BufferTooSmall)?;
+        dest.copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+}
+
+/// Fixed-width wire values, which are little-endian on the wire.
+pub(crate) trait FixedWidth: Copy {
+    /// Convert between little-endian and native byte order, either way.
+    fn le_to_native(self) -> Self;
+}
+
+impl FixedWidth for u32 {
+    #[inline(always)]
+    fn le_to_native(self) -> Self {
+        u32::from_le(self)
+    }
+}
+
+impl FixedWidth for u64 {
+    #[inline(always)]
+    fn le_to_native(self) -> Self {
+        u64::from_le(self)
+    }
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct ReadCursor(pub NonNull<u8>);
 
@@ -22,7 +306,10 @@ fn read_varint(ptr: ReadCursor) -> (Option<ReadCursor>, u64) {
     let mut extra = 0;
     for i in 0..10 {
         let b = ptr[i];
-        if i == 9 && b != 1 {
+        // Like protoc, a 10th byte ends the varint and only its lowest bit
+        // fits in 64 bits. It's 1 for negative int32 and enum values, which
+        // are sign-extended, and is 0 after padding.
+        if i == 9 && b >= 0x80 {
             break;
         }
         result ^= (b as u64) << (7 * i);
@@ -123,11 +410,12 @@ impl ReadCursor {
         }
     }
 
+    /// Read a little-endian fixed-width value.
     #[inline(always)]
-    pub fn read_unaligned<T>(&mut self) -> T {
+    pub fn read_fixed<T: FixedWidth>(&mut self) -> T {
         let value = unsafe { self.0.cast::<T>().read_unaligned() };
         *self += core::mem::size_of::<T>() as isize;
-        value
+        value.le_to_native()
     }
 
     #[inline(always)]
@@ -181,9 +469,8 @@ impl Index<isize> for ReadCursor {
     }
 }
 
-fn varint_size(n: u64) -> isize {
-    let log2 = (n | 1).ilog2();
-    ((log2 * 9 + 64 + 9) / 64) as isize
+pub(crate) fn varint_size(n: u64) -> isize {
+    varint_len(n) as isize
 }
 
 #[derive(Clone, Copy)]
@@ -208,10 +495,28 @@ impl WriteCursor {
         self[i] = n as u8;
     }
 
-    pub fn write_unaligned<T>(&mut self, value: T) {
+    /// Write a fixed-width value, little-endian.
+    pub fn write_fixed<T: FixedWidth>(&mut self, value: T) {
         *self += -(core::mem::size_of::<T>() as isize);
         unsafe {
-            self.0.cast::<T>().write_unaligned(value);
+            self.0.cast::<T>().write_unaligned(value.le_to_native());
+        }
+    }
+
+    /// Write `bytes[from..]`, where `bytes` holds values `width` bytes wide
+    /// in native byte order, as they go on the wire: little-endian. `from`
+    /// needn't be at a value boundary, letting a packed field be written
+    /// across buffers.
+    pub fn write_le_bytes(&mut self, bytes: &[u8], from: usize, width: usize) {
+        if cfg!(target_endian = "little") || width == 1 {
+            self.write_slice(&bytes[from..]);
+            return;
+        }
+        *self += -((bytes.len() - from) as isize);
+        for (i, pos) in (from..bytes.len()).enumerate() {
+            // The byte `pos % width` bytes into a little-endian value is
+            // that many bytes from the end of a big-endian one
+            self[i as isize] = bytes[pos - pos % width + width - 1 - pos % width];
         }
     }
 
@@ -275,6 +580,7 @@ impl IndexMut<isize> for WriteCursor {
     }
 }
 
+/// How the table entry of a field encodes and decodes it.
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum FieldKind {
@@ -303,4 +609,98 @@ pub enum FieldKind {
     RepeatedString,
     RepeatedMessage,
     RepeatedGroup,
+    /// Proto2 enum, only used in decode entries. Values outside the enum are
+    /// dropped like unknown fields. The entry points to an aux entry holding the
+    /// field offset and the enum's [`EnumTable`](crate::tables::EnumTable).
+    ClosedEnum,
+    /// Repeated counterpart of [`ClosedEnum`](FieldKind::ClosedEnum).
+    RepeatedClosedEnum,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ProtobufMut, ProtobufRef};
+    use crate::google::protobuf::FieldDescriptorProto;
+    use allocator_api2::alloc::Global;
+
+    #[test]
+    fn reader_and_writer_match_generated_code() {
+        let mut arena = crate::arena::Arena::new(&Global);
+        let mut field = FieldDescriptorProto::ProtoType::default();
+        field.set_name("id", &mut arena).unwrap();
+        field.set_number(-2);
+        field.set_oneof_index(300);
+        let encoded = field.encode_vec::<32>().unwrap();
+
+        let mut reader = Reader::new(&encoded);
+        let mut seen = std::vec::Vec::new();
+        while !reader.is_empty() {
+            let (number, wire_type) = reader.read_tag().unwrap();
+            match (number, wire_type) {
+                (1, WireType::LengthDelimited) => assert_eq!(reader.read_bytes().unwrap(), b"id"),
+                (3, WireType::Varint) => assert_eq!(reader.read_varint().unwrap() as i32, -2),
+                _ => reader.skip(number, wire_type).unwrap(),
+            }
+            seen.push(number);
+        }
+        assert_eq!(seen, [1, 3, 9]);
+
+        let mut buf = [0u8; 32];
+        let mut writer = Writer::new(&mut buf);
+        writer.write_tag(1, WireType::LengthDelimited).unwrap();
+        writer.write_bytes(b"id").unwrap();
+        writer.write_tag(3, WireType::Varint).unwrap();
+        writer.write_varint(-2i64 as u64).unwrap();
+        writer.write_tag(9, WireType::Varint).unwrap();
+        writer.write_varint(300).unwrap();
+        assert_eq!(writer.written(), encoded.as_slice());
+
+        let mut decoded = FieldDescriptorProto::ProtoType::default();
+        assert!(decoded.decode_flat::<32>(&mut arena, writer.written()));
+        assert_eq!(decoded.oneof_index(), 300);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(Reader::new(&[0x80]).read_varint().is_err());
+        assert!(Reader::new(&[0xff; 11]).read_varint().is_err());
+        assert_eq!(Reader::new(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]).read_varint().unwrap(), u64::MAX);
+        assert!(Reader::new(&[0x00]).read_tag().is_err());
+        assert!(Reader::new(&[0x0e]).read_tag().is_err());
+        assert!(Reader::new(&[0x05, 0x01]).read_bytes().is_err());
+        assert!(Reader::new(&[0x01, 0x02]).read_fixed32().is_err());
+
+        // A group skips to its matching end tag
+        let mut reader = Reader::new(&[0x08, 0x01, 0x13, 0x08, 0x01, 0x1c, 0x14, 0x2a]);
+        assert!(reader.skip(2, WireType::StartGroup).is_err());
+        let mut reader = Reader::new(&[0x08, 0x01, 0x14, 0x2a]);
+        reader.skip(2, WireType::StartGroup).unwrap();
+        assert_eq!(reader.remaining(), &[0x2a]);
+        let nested = [[0x0b; 200].as_slice(), &[0x0c; 200]].concat();
+        assert!(Reader::new(&nested).skip(1, WireType::StartGroup).is_err());
+
+        let mut buf = [0u8; 3];
+        let mut writer = Writer::new(&mut buf);
+        assert!(writer.write_bytes(b"abc").is_err());
+        assert!(writer.is_empty());
+        assert!(writer.write_tag(MAX_FIELD_NUMBER + 1, WireType::Varint).is_err());
+        writer.write_varint(300).unwrap();
+        assert_eq!(writer.written(), &[0xac, 0x02]);
+        assert!(writer.write_fixed32(1).is_err());
+    }
+
+    #[test]
+    fn zigzag_and_varint_len() {
+        for n in [0i64, -1, 1, -2, i64::MIN, i64::MAX] {
+            assert_eq!(zigzag_decode(zigzag_encode(n)), n);
+        }
+        assert_eq!(zigzag_encode(-1), 1);
+        assert_eq!(zigzag_encode(1), 2);
+        assert_eq!(varint_len(0), 1);
+        assert_eq!(varint_len(127), 1);
+        assert_eq!(varint_len(128), 2);
+        assert_eq!(varint_len(u64::MAX), 10);
+        assert_eq!(split_tag(make_tag(MAX_FIELD_NUMBER, WireType::Fixed32)), Some((MAX_FIELD_NUMBER, WireType::Fixed32)));
+    }
 }
@@ -0,0 +1,68 @@
+// Prints the proto JSON of a golden case as protobuf's C++ library does, the
+// reference the JSON files of test-protos/golden are generated with:
+//
+//   bazel run //test-protos:json_golden -- $PWD/test-protos/golden/defaults.txtpb \
+//       > test-protos/golden/defaults.json
+
+#include <fstream>
+#include <iostream>
+#include <memory>
+#include <sstream>
+#include <string>
+
+#include "google/protobuf/descriptor.h"
+#include "google/protobuf/json/json.h"
+#include "google/protobuf/message.h"
+#include "google/protobuf/text_format.h"
+#include "test-protos/proto/test.pb.h"
+
+using google::protobuf::Descriptor;
+using google::protobuf::DescriptorPool;
+using google::protobuf::Message;
+using google::protobuf::MessageFactory;
+using google::protobuf::TextFormat;
+
+int main(int argc, char** argv) {
+  if (argc != 2) {
+    std::cerr << "usage: json_golden <case>.txtpb\n";
+    return 2;
+  }
+  std::ifstream file(argv[1]);
+  std::stringstream text;
+  text << file.rdbuf();
+
+  // The message type is named in the `# proto-message:` header
+  const std::string header = "# proto-message:";
+  std::string type_name;
+  std::istringstream lines(text.str());
+  for (std::string line; std::getline(lines, line);) {
+    if (line.compare(0, header.size(), header) == 0) {
+      type_name = line.substr(line.find_first_not_of(' ', header.size()));
+      break;
+    }
+  }
+
+  // Links test.proto into the generated pool
+  Test::descriptor();
+  const Descriptor* descriptor = DescriptorPool::generated_pool()->FindMessageTypeByName(type_name);
+  if (descriptor == nullptr) {
+    std::cerr << argv[1] << ": unknown message type '" << type_name << "'\n";
+    return 1;
+  }
+  std::unique_ptr<Message> message(MessageFactory::generated_factory()->GetPrototype(descriptor)->New());
+  if (!TextFormat::ParseFromString(text.str(), message.get())) {
+    std::cerr << argv[1] << ": invalid text format\n";
+    return 1;
+  }
+
+  google::protobuf::json::PrintOptions options;
+  options.add_whitespace = true;
+  std::string json;
+  auto status = google::protobuf::json::MessageToJsonString(*message, &json, options);
+  if (!status.ok()) {
+    std::cerr << argv[1] << ": " << status << "\n";
+    return 1;
+  }
+  std::cout << json;
+  return 0;
+}
@@ -0,0 +1,169 @@
+//! Cross-checks binary and JSON output against `protoc` for the test-protos
+//! golden corpus.
+//!
+//! Every `test-protos/golden/<case>.txtpb` names its message type in a
+//! `# proto-message:` header. `protoc --encode` turns it into the reference
+//! binary encoding, which must survive a decode/encode roundtrip through a
+//! pool built from `test.proto` byte for byte. The proto JSON of the decoded
+//! message must equal `<case>.json` as a JSON value, whitespace aside, and
+//! parsing that JSON must give the reference bytes again.
+//!
+//! The JSON files come from protobuf's C++ printer, never from protocrap:
+//! `bazel run //test-protos:json_golden -- <case>.txtpb` prints one.
+//!
+//! Skipped when `protoc` can't be run. `PROTOC` selects the binary and
+//! `TEST_PROTOS_DIR` the corpus.
+
+#![cfg(feature = "serde_support")]
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use allocator_api2::alloc::Global;
+use protocrap::arena::Arena;
+use protocrap::descriptor_pool::DescriptorPool;
+use protocrap::proto_json::{ProtoJsonDeserializer, ProtoJsonSerializer};
+use protocrap::{ProtobufMut, ProtobufRef};
+use serde::Serialize;
+
+/// Stack depth used to decode and encode the corpus.
+const STACK_DEPTH: usize = 64;
+
+fn protoc() -> Option<String> {
+    let protoc = std::env::var("PROTOC").unwrap_or_else(|_| "protoc".to_string());
+    let available = Command::new(&protoc)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success());
+    available.then_some(protoc)
+}
+
+fn test_protos_dir() -> PathBuf {
+    match std::env::var_os("TEST_PROTOS_DIR") {
+        Some(dir) => dir.into(),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("test-protos"),
+    }
+}
+
+/// Run `protoc` on `test.proto` with `args`, feeding it `stdin`.
+fn run_protoc(protoc: &str, dir: &Path, args: &[&str], stdin: &[u8]) -> Result<Vec<u8>, String> {
+    let mut child = Command::new(protoc)
+        .arg("--proto_path")
+        .arg(dir.join("proto"))
+        .args(args)
+        .arg("test.proto")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin)
+        .map_err(|e| e.to_string())?;
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+    }
+    Ok(output.stdout)
+}
+
+/// Check one golden case, returning a description of the first mismatch.
+fn check_case(
+    protoc: &str,
+    dir: &Path,
+    pool: &DescriptorPool,
+    txtpb: &Path,
+) -> Result<(), String> {
+    let text = std::fs::read_to_string(txtpb).map_err(|e| e.to_string())?;
+    let type_name = text
+        .lines()
+        .find_map(|line| line.strip_prefix("# proto-message:"))
+        .map(str::trim)
+        .ok_or("missing `# proto-message:` header")?;
+    let expected = run_protoc(
+        protoc,
+        dir,
+        &[&format!("--encode={type_name}")],
+        text.as_bytes(),
+    )?;
+
+    let mut arena = Arena::new(&Global);
+    let mut msg = pool
+        .create_message(type_name, &mut arena)
+        .map_err(|e| format!("create_message: {e:?}"))?;
+    msg.try_decode_flat::<STACK_DEPTH>(&mut arena, &expected)
+        .map_err(|e| format!("decoding protoc output: {e}"))?;
+    let encoded = msg.encode_vec::<STACK_DEPTH>().unwrap();
+    if encoded != expected {
+        return Err(format!(
+            "binary mismatch\n  protoc:    {expected:02x?}\n  protocrap: {encoded:02x?}"
+        ));
+    }
+
+    let mut serializer = serde_json::Serializer::pretty(Vec::new());
+    msg.as_dyn()
+        .serialize(ProtoJsonSerializer::new(&mut serializer))
+        .map_err(|e| format!("serializing JSON: {e}"))?;
+    let json = String::from_utf8(serializer.into_inner()).unwrap();
+    let json_path = txtpb.with_extension("json");
+    let golden = std::fs::read_to_string(&json_path)
+        .map_err(|e| format!("{}: {e}", json_path.display()))?;
+    let golden_value: serde_json::Value =
+        serde_json::from_str(&golden).map_err(|e| format!("{}: {e}", json_path.display()))?;
+    if serde_json::from_str::<serde_json::Value>(&json).unwrap() != golden_value {
+        return Err(format!(
+            "JSON differs from {}\n--- golden\n{golden}--- protocrap\n{json}\n",
+            json_path.display()
+        ));
+    }
+
+    let mut parsed = pool
+        .create_message(type_name, &mut arena)
+        .map_err(|e| format!("create_message: {e:?}"))?;
+    let mut deserializer = serde_json::Deserializer::from_str(&golden);
+    parsed
+        .serde_deserialize(&mut arena, ProtoJsonDeserializer::new(&mut deserializer))
+        .map_err(|e| format!("parsing golden JSON: {e}"))?;
+    let reencoded = parsed.encode_vec::<STACK_DEPTH>().unwrap();
+    if reencoded != expected {
+        return Err(format!(
+            "JSON roundtrip changed the message\n  protoc:    {expected:02x?}\n  protocrap: {reencoded:02x?}"
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn golden_corpus_matches_protoc() {
+    let Some(protoc) = protoc() else {
+        eprintln!("protoc not found, skipping golden corpus check");
+        return;
+    };
+    let dir = test_protos_dir();
+    let descriptor_set = run_protoc(&protoc, &dir, &["--descriptor_set_out=/dev/stdout"], b"")
+        .expect("compiling test.proto");
+    let pool = DescriptorPool::from_file_descriptor_set_bytes(&descriptor_set)
+        .expect("loading test.proto descriptors");
+
+    let mut cases: Vec<_> = std::fs::read_dir(dir.join("golden"))
+        .expect("reading golden corpus")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txtpb"))
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "empty golden corpus");
+
+    let failures: Vec<_> = cases
+        .iter()
+        .filter_map(|case| {
+            check_case(&protoc, &dir, &pool, case)
+                .err()
+                .map(|e| format!("{}: {e}", case.display()))
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}